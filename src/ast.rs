@@ -7,13 +7,23 @@
 
 //! Defines the AST structure of a graph DSL file
 
+mod cache;
+mod node_types;
+
+pub use cache::CacheError;
+pub use node_types::NodeTypes;
+pub use node_types::NodeTypesError;
+
 use regex::Regex;
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::OnceLock;
 use tree_sitter::CaptureQuantifier;
 use tree_sitter::Language;
 use tree_sitter::Query;
 
+use crate::checker::CheckWarning;
+use crate::execution::Program;
 use crate::parser::Range;
 use crate::Identifier;
 use crate::Location;
@@ -30,6 +40,11 @@ pub struct File {
     pub stanzas: Vec<Stanza>,
     /// Attribute shorthands defined in the file
     pub shorthands: AttributeShorthands,
+    /// The warnings that checking this file found
+    pub warnings: Vec<CheckWarning>,
+    /// The stanzas and attribute shorthands compiled for the strict engine, the first time the
+    /// file is executed with it.  Changes to the file after that are not seen by the strict engine.
+    pub(crate) strict_program: OnceLock<Program>,
 }
 
 impl File {
@@ -40,6 +55,8 @@ impl File {
             query: None,
             stanzas: Vec::new(),
             shorthands: AttributeShorthands::new(),
+            warnings: Vec::new(),
+            strict_program: OnceLock::new(),
         }
     }
 }
@@ -61,12 +78,20 @@ pub struct Global {
 pub struct Stanza {
     /// The tree-sitter query for this stanza
     pub query: Query,
+    /// The source of the stanza's query, including the capture of the full match
+    pub query_source: String,
     /// The list of statements in the stanza
     pub statements: Vec<Statement>,
     /// Capture index of the full match in the stanza query
     pub full_match_stanza_capture_index: usize,
     /// Capture index of the full match in the file query
     pub full_match_file_capture_index: usize,
+    /// The maximum number of in-progress matches of the stanza query, set with a `@match-limit`
+    /// annotation
+    pub match_limit: Option<u32>,
+    /// The tags of the stanza, set with `@tag` annotations, which
+    /// [`ExecutionConfig::with_tags`][`crate::ExecutionConfig::with_tags`] selects stanzas by
+    pub tags: Vec<String>,
     pub range: Range,
 }
 
@@ -544,6 +569,7 @@ pub enum Expression {
     Variable(Variable),
     // Functions
     Call(Call),
+    FunctionRef(FunctionRef),
     // Regular expression
     RegexCapture(RegexCapture),
 }
@@ -563,6 +589,7 @@ impl std::fmt::Display for Expression {
             Expression::Capture(expr) => expr.fmt(f),
             Expression::Variable(expr) => expr.fmt(f),
             Expression::Call(expr) => expr.fmt(f),
+            Expression::FunctionRef(expr) => expr.fmt(f),
             Expression::RegexCapture(expr) => expr.fmt(f),
         }
     }
@@ -591,6 +618,25 @@ impl std::fmt::Display for Call {
     }
 }
 
+/// A reference to a named function, passed as a parameter to a higher-order function
+#[derive(Debug, Eq, PartialEq)]
+pub struct FunctionRef {
+    pub name: Identifier,
+    pub location: Location,
+}
+
+impl From<FunctionRef> for Expression {
+    fn from(expr: FunctionRef) -> Expression {
+        Expression::FunctionRef(expr)
+    }
+}
+
+impl std::fmt::Display for FunctionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "&{}", self.name)
+    }
+}
+
 /// A capture expression that references a syntax node
 #[derive(Debug, Eq, PartialEq)]
 pub struct Capture {
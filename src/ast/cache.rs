@@ -0,0 +1,1011 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Encodes parsed and checked graph DSL files, so that they can be cached
+//!
+//! An encoded file starts with the magic bytes `TSGF` and a format version, followed by the
+//! version of this library, and the SHA-256 digests of the graph DSL source and of the grammar
+//! that it was compiled for.  Then come the global variables, the stanzas, and the attribute
+//! shorthands of the file.  All integers are encoded as unsigned LEB128 varints, and all strings
+//! as a varint length followed by UTF-8 bytes.  Stanza queries are encoded as their source, and
+//! compiled again when the file is decoded, because tree-sitter cannot encode compiled queries.
+
+use std::convert::TryFrom;
+
+use regex::Regex;
+use sha2::Digest;
+use sha2::Sha256;
+use thiserror::Error;
+use tree_sitter::CaptureQuantifier;
+use tree_sitter::Language;
+use tree_sitter::Query;
+
+use super::*;
+use crate::checker::CheckWarning;
+use crate::parser::Range;
+use crate::Identifier;
+use crate::Location;
+
+const MAGIC: &[u8] = b"TSGF";
+const VERSION: u32 = 5;
+
+/// An error that can occur while decoding a cached graph DSL file
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("Invalid cached file: {0}")]
+    Invalid(String),
+    #[error("Stale cached file: {0}")]
+    Stale(String),
+    #[error("Unsupported cached file format version {0}")]
+    UnsupportedVersion(u32),
+}
+
+impl File {
+    /// Encodes this file in a compact binary format, which can be decoded with
+    /// [`File::from_cache`] to skip parsing and checking the file again.  `source` must be the
+    /// graph DSL source that the file was parsed from.
+    pub fn to_cache(&self, source: &str) -> Vec<u8> {
+        let mut encoder = Encoder {
+            bytes: MAGIC.to_vec(),
+            locations: true,
+        };
+        encoder.varint(VERSION as u64);
+        encoder.string(env!("CARGO_PKG_VERSION"));
+        encoder.bytes(&source_digest(source));
+        encoder.bytes(&grammar_digest(self.language));
+        self.globals.encode(&mut encoder);
+        self.stanzas.encode(&mut encoder);
+        encoder.varint(self.shorthands.iter().count() as u64);
+        for shorthand in self.shorthands.iter() {
+            shorthand.encode(&mut encoder);
+        }
+        self.warnings.encode(&mut encoder);
+        encoder.bytes
+    }
+
+    /// Decodes a file that was encoded with [`File::to_cache`].
+    ///
+    /// Fails with [`CacheError::Stale`] if the file was encoded by another version of this
+    /// library, from another graph DSL source than `source`, or for another grammar than
+    /// `language`, in which case the caller should parse `source` again with [`File::from_str`].
+    /// The queries of the file are compiled again, but the file is not parsed or checked.
+    pub fn from_cache(language: Language, source: &str, bytes: &[u8]) -> Result<File, CacheError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(CacheError::Invalid("missing magic bytes".to_string()));
+        }
+        let mut decoder = Decoder {
+            bytes: &bytes[MAGIC.len()..],
+            language,
+        };
+        let version = u32::decode(&mut decoder)?;
+        if version != VERSION {
+            return Err(CacheError::UnsupportedVersion(version));
+        }
+        let library_version = String::decode(&mut decoder)?;
+        if library_version != env!("CARGO_PKG_VERSION") {
+            return Err(CacheError::Stale(format!(
+                "encoded by version {} of this library",
+                library_version
+            )));
+        }
+        if decoder.bytes()? != source_digest(source) {
+            return Err(CacheError::Stale("encoded for another source".to_string()));
+        }
+        if decoder.bytes()? != grammar_digest(language) {
+            return Err(CacheError::Stale("encoded for another grammar".to_string()));
+        }
+
+        let mut file = File::new(language);
+        file.globals = Vec::decode(&mut decoder)?;
+        file.stanzas = Vec::decode(&mut decoder)?;
+        let shorthands: Vec<AttributeShorthand> = Vec::decode(&mut decoder)?;
+        for shorthand in shorthands {
+            file.shorthands.add(shorthand);
+        }
+        file.warnings = Vec::decode(&mut decoder)?;
+        if !decoder.bytes.is_empty() {
+            return Err(CacheError::Invalid("unexpected trailing bytes".to_string()));
+        }
+        let mut query_source = String::new();
+        for stanza in &file.stanzas {
+            query_source += &stanza.query_source;
+            query_source += "\n";
+        }
+        file.query = Some(compile_query(language, &query_source)?);
+        Ok(file)
+    }
+}
+
+fn source_digest(source: &str) -> Vec<u8> {
+    Sha256::digest(source.as_bytes()).to_vec()
+}
+
+/// Returns a digest of the node kinds and field names of a grammar, which the capture indices
+/// and node kinds in a file's queries depend on.
+fn grammar_digest(language: Language) -> Vec<u8> {
+    let mut digest = Sha256::new();
+    digest.update(language.version().to_le_bytes());
+    for id in 0..language.node_kind_count() as u16 {
+        digest.update(language.node_kind_for_id(id).unwrap_or_default().as_bytes());
+        digest.update([
+            language.node_kind_is_named(id) as u8,
+            language.node_kind_is_visible(id) as u8,
+        ]);
+    }
+    for id in 1..=language.field_count() as u16 {
+        digest.update(
+            language
+                .field_name_for_id(id)
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        digest.update([0]);
+    }
+    digest.finalize().to_vec()
+}
+
+impl Stanza {
+    /// Encodes the statements of this stanza without their locations, so that the statements of
+    /// two stanzas have the same encoding if they only differ in where they are in the file.
+    pub(crate) fn encode_statements_without_locations(&self) -> Vec<u8> {
+        let mut encoder = Encoder {
+            bytes: Vec::new(),
+            locations: false,
+        };
+        self.statements.encode(&mut encoder);
+        encoder.bytes
+    }
+}
+
+fn compile_query(language: Language, source: &str) -> Result<Query, CacheError> {
+    Query::new(language, source)
+        .map_err(|e| CacheError::Invalid(format!("invalid query: {}", e.message)))
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    /// Whether locations are encoded, which they are not when parts of files are only encoded to
+    /// compare them
+    locations: bool,
+}
+
+impl Encoder {
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.bytes.push(tag);
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    language: Language,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, CacheError> {
+        let (byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or_else(|| CacheError::Invalid("unexpected end of input".to_string()))?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, CacheError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err(CacheError::Invalid("varint is too long".to_string()));
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Decodes a count of items, each of which takes at least one byte.
+    fn length(&mut self) -> Result<usize, CacheError> {
+        let length = self.varint()?;
+        if length > self.bytes.len() as u64 {
+            return Err(CacheError::Invalid(format!("invalid length {}", length)));
+        }
+        Ok(length as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], CacheError> {
+        let length = self.length()?;
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn invalid_tag<T>(&self, tag: u8, what: &str) -> Result<T, CacheError> {
+        Err(CacheError::Invalid(format!("invalid {} tag {}", what, tag)))
+    }
+}
+
+/// A part of a file that can be encoded
+trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+/// A part of a file that can be decoded
+trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError>;
+}
+
+//-----------------------------------------------------------------------------
+// Primitives
+
+impl Encode for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.varint(*self as u64);
+    }
+}
+
+impl Decode for usize {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let value = decoder.varint()?;
+        usize::try_from(value)
+            .map_err(|_| CacheError::Invalid(format!("{} does not fit in usize", value)))
+    }
+}
+
+impl Encode for u32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.varint(*self as u64);
+    }
+}
+
+impl Decode for u32 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let value = decoder.varint()?;
+        u32::try_from(value)
+            .map_err(|_| CacheError::Invalid(format!("{} does not fit in 32 bits", value)))
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(self);
+    }
+}
+
+impl Decode for String {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        String::from_utf8(decoder.bytes()?.to_vec())
+            .map_err(|_| CacheError::Invalid("invalid UTF-8 in string".to_string()))
+    }
+}
+
+impl Encode for Identifier {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(self.as_str());
+    }
+}
+
+impl Decode for Identifier {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Identifier::from(String::decode(decoder)?.as_str()))
+    }
+}
+
+impl Encode for Location {
+    fn encode(&self, encoder: &mut Encoder) {
+        if encoder.locations {
+            self.row.encode(encoder);
+            self.column.encode(encoder);
+        }
+    }
+}
+
+impl Decode for Location {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Location {
+            row: usize::decode(decoder)?,
+            column: usize::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for Range {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.start.encode(encoder);
+        self.end.encode(encoder);
+    }
+}
+
+impl Decode for Range {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Range {
+            start: Location::decode(decoder)?,
+            end: Location::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for CaptureQuantifier {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.tag(match self {
+            CaptureQuantifier::Zero => 0,
+            CaptureQuantifier::ZeroOrOne => 1,
+            CaptureQuantifier::ZeroOrMore => 2,
+            CaptureQuantifier::One => 3,
+            CaptureQuantifier::OneOrMore => 4,
+        });
+    }
+}
+
+impl Decode for CaptureQuantifier {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        match decoder.byte()? {
+            0 => Ok(CaptureQuantifier::Zero),
+            1 => Ok(CaptureQuantifier::ZeroOrOne),
+            2 => Ok(CaptureQuantifier::ZeroOrMore),
+            3 => Ok(CaptureQuantifier::One),
+            4 => Ok(CaptureQuantifier::OneOrMore),
+            tag => decoder.invalid_tag(tag, "quantifier"),
+        }
+    }
+}
+
+impl Encode for CheckWarning {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            CheckWarning::DuplicateStanza(stanza, earlier) => {
+                encoder.tag(0);
+                stanza.encode(encoder);
+                earlier.encode(encoder);
+            }
+            CheckWarning::UnreachableStatements(location) => {
+                encoder.tag(1);
+                location.encode(encoder);
+            }
+            CheckWarning::UnusedVariable(name, location) => {
+                encoder.tag(2);
+                name.encode(encoder);
+                location.encode(encoder);
+            }
+            CheckWarning::UnusedCapture(name, location) => {
+                encoder.tag(3);
+                name.encode(encoder);
+                location.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for CheckWarning {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        match decoder.byte()? {
+            0 => Ok(CheckWarning::DuplicateStanza(
+                Location::decode(decoder)?,
+                Location::decode(decoder)?,
+            )),
+            1 => Ok(CheckWarning::UnreachableStatements(Location::decode(
+                decoder,
+            )?)),
+            2 => Ok(CheckWarning::UnusedVariable(
+                String::decode(decoder)?,
+                Location::decode(decoder)?,
+            )),
+            3 => Ok(CheckWarning::UnusedCapture(
+                String::decode(decoder)?,
+                Location::decode(decoder)?,
+            )),
+            tag => decoder.invalid_tag(tag, "warning"),
+        }
+    }
+}
+
+impl Encode for Regex {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.string(self.as_str());
+    }
+}
+
+impl Decode for Regex {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let regex = String::decode(decoder)?;
+        Regex::new(&regex)
+            .map_err(|_| CacheError::Invalid(format!("invalid regular expression /{}/", regex)))
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            None => encoder.tag(0),
+            Some(value) => {
+                encoder.tag(1);
+                value.encode(encoder);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        match decoder.byte()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(decoder)?)),
+            tag => decoder.invalid_tag(tag, "option"),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Box<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.as_ref().encode(encoder);
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Box::new(T::decode(decoder)?))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.varint(self.len() as u64);
+        for element in self {
+            element.encode(encoder);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let length = decoder.length()?;
+        let mut elements = Vec::with_capacity(length);
+        for _ in 0..length {
+            elements.push(T::decode(decoder)?);
+        }
+        Ok(elements)
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Files
+
+impl Encode for Global {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.name.encode(encoder);
+        self.quantifier.encode(encoder);
+        self.default.encode(encoder);
+        self.location.encode(encoder);
+    }
+}
+
+impl Decode for Global {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Global {
+            name: Identifier::decode(decoder)?,
+            quantifier: CaptureQuantifier::decode(decoder)?,
+            default: Option::decode(decoder)?,
+            location: Location::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for Stanza {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.query_source.encode(encoder);
+        self.statements.encode(encoder);
+        self.full_match_stanza_capture_index.encode(encoder);
+        self.full_match_file_capture_index.encode(encoder);
+        self.match_limit.encode(encoder);
+        self.tags.encode(encoder);
+        self.range.encode(encoder);
+    }
+}
+
+impl Decode for Stanza {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let query_source = String::decode(decoder)?;
+        Ok(Stanza {
+            query: compile_query(decoder.language, &query_source)?,
+            query_source,
+            statements: Vec::decode(decoder)?,
+            full_match_stanza_capture_index: usize::decode(decoder)?,
+            full_match_file_capture_index: usize::decode(decoder)?,
+            match_limit: Option::decode(decoder)?,
+            tags: Vec::decode(decoder)?,
+            range: Range::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for AttributeShorthand {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.name.encode(encoder);
+        self.variable.encode(encoder);
+        self.attributes.encode(encoder);
+        self.location.encode(encoder);
+    }
+}
+
+impl Decode for AttributeShorthand {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(AttributeShorthand {
+            name: Identifier::decode(decoder)?,
+            variable: UnscopedVariable::decode(decoder)?,
+            attributes: Vec::decode(decoder)?,
+            location: Location::decode(decoder)?,
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Statements
+
+const DECLARE_IMMUTABLE: u8 = 0;
+const DECLARE_MUTABLE: u8 = 1;
+const ASSIGN: u8 = 2;
+const CREATE_GRAPH_NODE: u8 = 3;
+const ADD_GRAPH_NODE_ATTRIBUTE: u8 = 4;
+const CREATE_EDGE: u8 = 5;
+const ADD_EDGE_ATTRIBUTE: u8 = 6;
+const SCAN: u8 = 7;
+const PRINT: u8 = 8;
+const IF: u8 = 9;
+const FOR_IN: u8 = 10;
+
+impl Encode for Statement {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Statement::DeclareImmutable(stmt) => {
+                encoder.tag(DECLARE_IMMUTABLE);
+                stmt.variable.encode(encoder);
+                stmt.value.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::DeclareMutable(stmt) => {
+                encoder.tag(DECLARE_MUTABLE);
+                stmt.variable.encode(encoder);
+                stmt.value.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::Assign(stmt) => {
+                encoder.tag(ASSIGN);
+                stmt.variable.encode(encoder);
+                stmt.value.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::CreateGraphNode(stmt) => {
+                encoder.tag(CREATE_GRAPH_NODE);
+                stmt.node.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::AddGraphNodeAttribute(stmt) => {
+                encoder.tag(ADD_GRAPH_NODE_ATTRIBUTE);
+                stmt.node.encode(encoder);
+                stmt.attributes.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::CreateEdge(stmt) => {
+                encoder.tag(CREATE_EDGE);
+                stmt.source.encode(encoder);
+                stmt.sink.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::AddEdgeAttribute(stmt) => {
+                encoder.tag(ADD_EDGE_ATTRIBUTE);
+                stmt.source.encode(encoder);
+                stmt.sink.encode(encoder);
+                stmt.attributes.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::Scan(stmt) => {
+                encoder.tag(SCAN);
+                stmt.value.encode(encoder);
+                stmt.arms.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::Print(stmt) => {
+                encoder.tag(PRINT);
+                stmt.values.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::If(stmt) => {
+                encoder.tag(IF);
+                stmt.arms.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+            Statement::ForIn(stmt) => {
+                encoder.tag(FOR_IN);
+                stmt.variable.encode(encoder);
+                stmt.value.encode(encoder);
+                stmt.statements.encode(encoder);
+                stmt.location.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for Statement {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(match decoder.byte()? {
+            DECLARE_IMMUTABLE => DeclareImmutable {
+                variable: Variable::decode(decoder)?,
+                value: Expression::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            DECLARE_MUTABLE => DeclareMutable {
+                variable: Variable::decode(decoder)?,
+                value: Expression::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            ASSIGN => Assign {
+                variable: Variable::decode(decoder)?,
+                value: Expression::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            CREATE_GRAPH_NODE => CreateGraphNode {
+                node: Variable::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            ADD_GRAPH_NODE_ATTRIBUTE => AddGraphNodeAttribute {
+                node: Expression::decode(decoder)?,
+                attributes: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            CREATE_EDGE => CreateEdge {
+                source: Expression::decode(decoder)?,
+                sink: Expression::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            ADD_EDGE_ATTRIBUTE => AddEdgeAttribute {
+                source: Expression::decode(decoder)?,
+                sink: Expression::decode(decoder)?,
+                attributes: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            SCAN => Scan {
+                value: Expression::decode(decoder)?,
+                arms: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            PRINT => Print {
+                values: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            IF => If {
+                arms: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            FOR_IN => ForIn {
+                variable: UnscopedVariable::decode(decoder)?,
+                value: Expression::decode(decoder)?,
+                statements: Vec::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            tag => return decoder.invalid_tag(tag, "statement"),
+        })
+    }
+}
+
+impl Encode for Attribute {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.name.encode(encoder);
+        self.value.encode(encoder);
+    }
+}
+
+impl Decode for Attribute {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(Attribute {
+            name: Identifier::decode(decoder)?,
+            value: Expression::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for ScanArm {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.regex.encode(encoder);
+        self.statements.encode(encoder);
+        self.location.encode(encoder);
+    }
+}
+
+impl Decode for ScanArm {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(ScanArm {
+            regex: Regex::decode(decoder)?,
+            statements: Vec::decode(decoder)?,
+            location: Location::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for IfArm {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.conditions.encode(encoder);
+        self.statements.encode(encoder);
+        self.location.encode(encoder);
+    }
+}
+
+impl Decode for IfArm {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(IfArm {
+            conditions: Vec::decode(decoder)?,
+            statements: Vec::decode(decoder)?,
+            location: Location::decode(decoder)?,
+        })
+    }
+}
+
+const SOME: u8 = 0;
+const NONE: u8 = 1;
+const BOOL: u8 = 2;
+
+impl Encode for Condition {
+    fn encode(&self, encoder: &mut Encoder) {
+        let (tag, value, location) = match self {
+            Condition::Some { value, location } => (SOME, value, location),
+            Condition::None { value, location } => (NONE, value, location),
+            Condition::Bool { value, location } => (BOOL, value, location),
+        };
+        encoder.tag(tag);
+        value.encode(encoder);
+        location.encode(encoder);
+    }
+}
+
+impl Decode for Condition {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        let tag = decoder.byte()?;
+        let value = Expression::decode(decoder)?;
+        let location = Location::decode(decoder)?;
+        match tag {
+            SOME => Ok(Condition::Some { value, location }),
+            NONE => Ok(Condition::None { value, location }),
+            BOOL => Ok(Condition::Bool { value, location }),
+            tag => decoder.invalid_tag(tag, "condition"),
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Variables
+
+const SCOPED: u8 = 0;
+const UNSCOPED: u8 = 1;
+
+impl Encode for Variable {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Variable::Scoped(variable) => {
+                encoder.tag(SCOPED);
+                variable.scope.encode(encoder);
+                variable.name.encode(encoder);
+                variable.location.encode(encoder);
+            }
+            Variable::Unscoped(variable) => {
+                encoder.tag(UNSCOPED);
+                variable.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for Variable {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(match decoder.byte()? {
+            SCOPED => ScopedVariable {
+                scope: Box::decode(decoder)?,
+                name: Identifier::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            UNSCOPED => UnscopedVariable::decode(decoder)?.into(),
+            tag => return decoder.invalid_tag(tag, "variable"),
+        })
+    }
+}
+
+impl Encode for UnscopedVariable {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.name.encode(encoder);
+        self.location.encode(encoder);
+    }
+}
+
+impl Decode for UnscopedVariable {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(UnscopedVariable {
+            name: Identifier::decode(decoder)?,
+            location: Location::decode(decoder)?,
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Expressions
+
+const FALSE_LITERAL: u8 = 0;
+const NULL_LITERAL: u8 = 1;
+const TRUE_LITERAL: u8 = 2;
+const INTEGER_CONSTANT: u8 = 3;
+const STRING_CONSTANT: u8 = 4;
+const LIST_LITERAL: u8 = 5;
+const SET_LITERAL: u8 = 6;
+const LIST_COMPREHENSION: u8 = 7;
+const SET_COMPREHENSION: u8 = 8;
+const CAPTURE: u8 = 9;
+const VARIABLE: u8 = 10;
+const CALL: u8 = 11;
+const FUNCTION_REF: u8 = 12;
+const REGEX_CAPTURE: u8 = 13;
+
+impl Encode for Expression {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Expression::FalseLiteral => encoder.tag(FALSE_LITERAL),
+            Expression::NullLiteral => encoder.tag(NULL_LITERAL),
+            Expression::TrueLiteral => encoder.tag(TRUE_LITERAL),
+            Expression::IntegerConstant(expr) => {
+                encoder.tag(INTEGER_CONSTANT);
+                expr.value.encode(encoder);
+            }
+            Expression::StringConstant(expr) => {
+                encoder.tag(STRING_CONSTANT);
+                expr.value.encode(encoder);
+            }
+            Expression::ListLiteral(expr) => {
+                encoder.tag(LIST_LITERAL);
+                expr.elements.encode(encoder);
+            }
+            Expression::SetLiteral(expr) => {
+                encoder.tag(SET_LITERAL);
+                expr.elements.encode(encoder);
+            }
+            Expression::ListComprehension(expr) => {
+                encoder.tag(LIST_COMPREHENSION);
+                expr.element.encode(encoder);
+                expr.variable.encode(encoder);
+                expr.value.encode(encoder);
+                expr.location.encode(encoder);
+            }
+            Expression::SetComprehension(expr) => {
+                encoder.tag(SET_COMPREHENSION);
+                expr.element.encode(encoder);
+                expr.variable.encode(encoder);
+                expr.value.encode(encoder);
+                expr.location.encode(encoder);
+            }
+            Expression::Capture(expr) => {
+                encoder.tag(CAPTURE);
+                expr.name.encode(encoder);
+                expr.quantifier.encode(encoder);
+                expr.file_capture_index.encode(encoder);
+                expr.stanza_capture_index.encode(encoder);
+                expr.location.encode(encoder);
+            }
+            Expression::Variable(expr) => {
+                encoder.tag(VARIABLE);
+                expr.encode(encoder);
+            }
+            Expression::Call(expr) => {
+                encoder.tag(CALL);
+                expr.function.encode(encoder);
+                expr.parameters.encode(encoder);
+            }
+            Expression::FunctionRef(expr) => {
+                encoder.tag(FUNCTION_REF);
+                expr.name.encode(encoder);
+                expr.location.encode(encoder);
+            }
+            Expression::RegexCapture(expr) => {
+                encoder.tag(REGEX_CAPTURE);
+                expr.match_index.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for Expression {
+    fn decode(decoder: &mut Decoder) -> Result<Self, CacheError> {
+        Ok(match decoder.byte()? {
+            FALSE_LITERAL => Expression::FalseLiteral,
+            NULL_LITERAL => Expression::NullLiteral,
+            TRUE_LITERAL => Expression::TrueLiteral,
+            INTEGER_CONSTANT => IntegerConstant {
+                value: u32::decode(decoder)?,
+            }
+            .into(),
+            STRING_CONSTANT => StringConstant {
+                value: String::decode(decoder)?,
+            }
+            .into(),
+            LIST_LITERAL => ListLiteral {
+                elements: Vec::decode(decoder)?,
+            }
+            .into(),
+            SET_LITERAL => SetLiteral {
+                elements: Vec::decode(decoder)?,
+            }
+            .into(),
+            LIST_COMPREHENSION => ListComprehension {
+                element: Box::decode(decoder)?,
+                variable: UnscopedVariable::decode(decoder)?,
+                value: Box::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            SET_COMPREHENSION => SetComprehension {
+                element: Box::decode(decoder)?,
+                variable: UnscopedVariable::decode(decoder)?,
+                value: Box::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            CAPTURE => Capture {
+                name: Identifier::decode(decoder)?,
+                quantifier: CaptureQuantifier::decode(decoder)?,
+                file_capture_index: usize::decode(decoder)?,
+                stanza_capture_index: usize::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            VARIABLE => Expression::Variable(Variable::decode(decoder)?),
+            CALL => Call {
+                function: Identifier::decode(decoder)?,
+                parameters: Vec::decode(decoder)?,
+            }
+            .into(),
+            FUNCTION_REF => FunctionRef {
+                name: Identifier::decode(decoder)?,
+                location: Location::decode(decoder)?,
+            }
+            .into(),
+            REGEX_CAPTURE => RegexCapture {
+                match_index: usize::decode(decoder)?,
+            }
+            .into(),
+            tag => return decoder.invalid_tag(tag, "expression"),
+        })
+    }
+}
@@ -0,0 +1,439 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Checks the patterns of graph DSL files against the node types of a grammar
+//!
+//! Tree-sitter checks the patterns of a query against the grammar that it is compiled for.  The
+//! `node-types.json` file of a grammar describes the same node kinds, with the fields that each of
+//! them has and the node kinds that each field can contain, so the patterns of a file can also be
+//! checked against another version of the grammar, such as one that the file is about to be
+//! upgraded to, without compiling the file for it.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+use super::File;
+use crate::checker::CheckError;
+use crate::Location;
+
+/// An error that can occur while reading the node types of a grammar
+#[derive(Debug, Error)]
+pub enum NodeTypesError {
+    #[error("Invalid node types: {0}")]
+    Invalid(String),
+}
+
+/// The node kinds of a grammar, and the fields that they have, as described by the
+/// `node-types.json` file that tree-sitter generates for the grammar.  Grammar crates usually
+/// include it as a `NODE_TYPES` constant.
+#[derive(Clone, Debug, Default)]
+pub struct NodeTypes {
+    named: HashMap<String, NodeType>,
+    anonymous: HashSet<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct NodeType {
+    fields: HashMap<String, Vec<Kind>>,
+    subtypes: Vec<Kind>,
+}
+
+/// A node kind, which is named or anonymous
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Kind {
+    name: String,
+    named: bool,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.named {
+            write!(f, "({})", self.name)
+        } else {
+            write!(f, "{:?}", self.name)
+        }
+    }
+}
+
+impl NodeTypes {
+    /// Reads the node types of a grammar from the contents of its `node-types.json` file.
+    pub fn from_json(json: &str) -> Result<NodeTypes, NodeTypesError> {
+        let json: JsonValue =
+            serde_json::from_str(json).map_err(|e| NodeTypesError::Invalid(e.to_string()))?;
+        let entries = json
+            .as_array()
+            .ok_or_else(|| NodeTypesError::Invalid("expected an array".to_string()))?;
+        let mut node_types = NodeTypes::default();
+        for entry in entries {
+            let kind = kind(entry)?;
+            if !kind.named {
+                node_types.anonymous.insert(kind.name);
+                continue;
+            }
+            let mut node_type = NodeType::default();
+            if let Some(fields) = entry.get("fields") {
+                let fields = fields
+                    .as_object()
+                    .ok_or_else(|| invalid(&kind.name, "fields"))?;
+                for (name, field) in fields {
+                    node_type
+                        .fields
+                        .insert(name.clone(), kinds(field, "types", &kind.name)?);
+                }
+            }
+            if entry.get("subtypes").is_some() {
+                node_type.subtypes = kinds(entry, "subtypes", &kind.name)?;
+            }
+            node_types.named.insert(kind.name, node_type);
+        }
+        Ok(node_types)
+    }
+
+    /// Returns whether the grammar has a node kind.
+    fn contains(&self, kind: &Kind) -> bool {
+        if kind.named {
+            self.named.contains_key(&kind.name) || kind.name == "ERROR"
+        } else {
+            self.anonymous.contains(&kind.name)
+        }
+    }
+
+    /// Returns the node kinds that a field of a node kind can contain, or `None` if the node
+    /// kind does not have the field.  A supertype has the fields of all of its subtypes.
+    fn field(&self, kind: &str, field: &str) -> Option<Vec<&Kind>> {
+        let node_type = self.named.get(kind)?;
+        if node_type.subtypes.is_empty() {
+            return Some(node_type.fields.get(field)?.iter().collect());
+        }
+        let kinds = (node_type.subtypes.iter())
+            .filter_map(|subtype| self.field(&subtype.name, field))
+            .flatten()
+            .collect::<Vec<_>>();
+        if kinds.is_empty() {
+            None
+        } else {
+            Some(kinds)
+        }
+    }
+
+    /// Returns whether a node of the given kind is one of the given kinds, or one of their
+    /// subtypes.
+    fn is_one_of(&self, kind: &Kind, kinds: &[&Kind]) -> bool {
+        kinds
+            .iter()
+            .any(|candidate| *candidate == kind || self.is_subtype(kind, candidate))
+    }
+
+    fn is_subtype(&self, kind: &Kind, supertype: &Kind) -> bool {
+        match self.named.get(&supertype.name) {
+            Some(node_type) if supertype.named => (node_type.subtypes.iter())
+                .any(|subtype| subtype == kind || self.is_subtype(kind, subtype)),
+            _ => false,
+        }
+    }
+}
+
+fn invalid(kind: &str, what: &str) -> NodeTypesError {
+    NodeTypesError::Invalid(format!("invalid {} of {}", what, kind))
+}
+
+fn kind(entry: &JsonValue) -> Result<Kind, NodeTypesError> {
+    let entry: &Map<String, JsonValue> = entry
+        .as_object()
+        .ok_or_else(|| NodeTypesError::Invalid("expected a node type object".to_string()))?;
+    let name = (entry.get("type").and_then(JsonValue::as_str))
+        .ok_or_else(|| NodeTypesError::Invalid("missing type of node type".to_string()))?;
+    let named = (entry.get("named").and_then(JsonValue::as_bool))
+        .ok_or_else(|| invalid(name, "named flag"))?;
+    Ok(Kind {
+        name: name.to_string(),
+        named,
+    })
+}
+
+fn kinds(object: &JsonValue, name: &str, kind_name: &str) -> Result<Vec<Kind>, NodeTypesError> {
+    (object.get(name).and_then(JsonValue::as_array))
+        .ok_or_else(|| invalid(kind_name, name))?
+        .iter()
+        .map(kind)
+        .collect()
+}
+
+impl File {
+    /// Checks that the node kinds and field names in the patterns of this file exist in a
+    /// grammar, and that the fields in the patterns can contain the node kinds that the patterns
+    /// expect there.  Patterns that fail the check could never match with that grammar.  This is
+    /// useful before upgrading a grammar, to find the stanzas that use node kinds or fields that
+    /// the new version removed or moved, since the file is parsed for the old version.
+    pub fn check_node_types(&self, node_types: &NodeTypes) -> Result<(), CheckError> {
+        for stanza in &self.stanzas {
+            let mut parser = PatternParser {
+                chars: stanza.query_source.chars().peekable(),
+                location: stanza.range.start,
+                node_types,
+            };
+            while parser.skip_trivia() {
+                parser.check_pattern(None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Walks the source of a query pattern, checking the node kinds and fields in it.  The pattern
+/// has already been compiled by tree-sitter, so it is known to be well-formed.
+struct PatternParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    location: Location,
+    node_types: &'a NodeTypes,
+}
+
+impl<'a> PatternParser<'a> {
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.location.row += 1;
+            self.location.column = 0;
+        } else {
+            self.location.column += 1;
+        }
+        Some(ch)
+    }
+
+    /// Skips whitespace and comments, and returns whether there is anything left.
+    fn skip_trivia(&mut self) -> bool {
+        loop {
+            match self.chars.peek() {
+                Some(ch) if ch.is_whitespace() => {
+                    self.next();
+                }
+                Some(';') => while !matches!(self.next(), Some('\n') | None) {},
+                Some(_) => return true,
+                None => return false,
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let mut identifier = String::new();
+        while let Some(ch) = self.chars.peek() {
+            if !(ch.is_alphanumeric() || matches!(ch, '_' | '-' | '?' | '#')) {
+                break;
+            }
+            identifier.push(*ch);
+            self.next();
+        }
+        identifier
+    }
+
+    fn string(&mut self) -> String {
+        let mut string = String::new();
+        self.next();
+        while let Some(ch) = self.next() {
+            match ch {
+                '"' => break,
+                '\\' => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('0') => string.push('\0'),
+                    Some(ch) => string.push(ch),
+                    None => break,
+                },
+                ch => string.push(ch),
+            }
+        }
+        string
+    }
+
+    /// Checks the next pattern.  `field` is the node kind and field that the pattern is in, if
+    /// any, with the node kinds that the field can contain.
+    fn check_pattern(&mut self, field: Option<(&str, &str, &[&Kind])>) -> Result<(), CheckError> {
+        let location = self.location;
+        match self.chars.peek() {
+            Some('(') => {
+                self.next();
+                self.skip_trivia();
+                match self.chars.peek() {
+                    Some('(') | Some('[') | Some('"') => {
+                        // a group of sibling patterns
+                        while self.skip_trivia() && self.chars.peek() != Some(&')') {
+                            self.check_child(None)?;
+                        }
+                        self.next();
+                    }
+                    _ => {
+                        let name = self.identifier();
+                        if name.starts_with('#') {
+                            self.skip_predicate();
+                            return Ok(());
+                        }
+                        let kind = Kind { name, named: true };
+                        let parent = if kind.name == "_" || kind.name == "MISSING" {
+                            None
+                        } else {
+                            self.check_kind(&kind, location, field)?;
+                            Some(kind.name.as_str())
+                        };
+                        while self.skip_trivia() && self.chars.peek() != Some(&')') {
+                            self.check_child(parent)?;
+                        }
+                        self.next();
+                    }
+                }
+            }
+            Some('[') => {
+                self.next();
+                while self.skip_trivia() && self.chars.peek() != Some(&']') {
+                    self.check_pattern(field)?;
+                }
+                self.next();
+            }
+            Some('"') => {
+                let name = self.string();
+                self.check_kind(&Kind { name, named: false }, location, field)?;
+            }
+            _ => {
+                // a wildcard
+                if self.identifier().is_empty() {
+                    self.next();
+                }
+            }
+        }
+        self.skip_suffixes();
+        Ok(())
+    }
+
+    /// Checks the next child of a node pattern, which can be preceded by a field name, or be a
+    /// negated field.  `parent` is the node kind of the node pattern, if it has a specific one.
+    fn check_child(&mut self, parent: Option<&str>) -> Result<(), CheckError> {
+        match self.chars.peek() {
+            Some('.') => {
+                // an anchor
+                self.next();
+                return Ok(());
+            }
+            Some('!') => {
+                self.next();
+                let location = self.location;
+                let name = self.identifier();
+                if let Some(parent) = parent {
+                    self.field_kinds(parent, &name, location)?;
+                }
+                return Ok(());
+            }
+            Some(ch) if ch.is_alphabetic() => {}
+            _ => {
+                self.check_pattern(None)?;
+                return Ok(());
+            }
+        }
+        let location = self.location;
+        let name = self.identifier();
+        if self.chars.peek() != Some(&':') {
+            // a wildcard
+            self.skip_suffixes();
+            return Ok(());
+        }
+        self.next();
+        self.skip_trivia();
+        match parent {
+            Some(parent) => {
+                let kinds = self.field_kinds(parent, &name, location)?;
+                self.check_pattern(Some((parent, &name, &kinds)))?;
+            }
+            None => {
+                self.check_pattern(None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn field_kinds(
+        &self,
+        parent: &str,
+        field: &str,
+        location: Location,
+    ) -> Result<Vec<&'a Kind>, CheckError> {
+        self.node_types.field(parent, field).ok_or_else(|| {
+            CheckError::UnknownField(format!("({})", parent), field.to_string(), location)
+        })
+    }
+
+    fn check_kind(
+        &self,
+        kind: &Kind,
+        location: Location,
+        field: Option<(&str, &str, &[&Kind])>,
+    ) -> Result<(), CheckError> {
+        if !self.node_types.contains(kind) {
+            return Err(CheckError::UnknownNodeKind(kind.to_string(), location));
+        }
+        if let Some((parent, field, kinds)) = field {
+            if kind.name != "ERROR" && !self.node_types.is_one_of(kind, kinds) {
+                return Err(CheckError::UnexpectedFieldNodeKind(
+                    format!("({})", parent),
+                    field.to_string(),
+                    kind.to_string(),
+                    location,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips the rest of a predicate, whose name has been consumed.
+    fn skip_predicate(&mut self) {
+        let mut depth = 1;
+        while let Some(ch) = self.chars.peek() {
+            match ch {
+                '"' => {
+                    self.string();
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.next();
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    /// Skips the quantifiers and captures after a pattern.
+    fn skip_suffixes(&mut self) {
+        loop {
+            let mut chars = self.chars.clone();
+            while matches!(chars.peek(), Some(ch) if ch.is_whitespace()) {
+                chars.next();
+            }
+            match chars.peek() {
+                Some('*') | Some('+') | Some('?') => {
+                    self.skip_trivia();
+                    self.next();
+                }
+                Some('@') => {
+                    self.skip_trivia();
+                    self.next();
+                    self.identifier();
+                }
+                _ => return,
+            }
+        }
+    }
+}
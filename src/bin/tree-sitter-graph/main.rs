@@ -5,6 +5,9 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::fs;
+use std::io::stdout;
+use std::io::BufWriter;
 use std::path::Path;
 
 use anyhow::anyhow;
@@ -13,15 +16,19 @@ use anyhow::Result;
 use clap::builder::ArgAction;
 use clap::App;
 use clap::Arg;
+use clap::ArgGroup;
 use tree_sitter::Parser;
 use tree_sitter_config::Config;
 use tree_sitter_graph::ast::File;
+use tree_sitter_graph::ast::NodeTypes;
 use tree_sitter_graph::functions::Functions;
 use tree_sitter_graph::graph;
 use tree_sitter_graph::parse_error::ParseError;
 use tree_sitter_graph::ExecutionConfig;
+use tree_sitter_graph::ExecutionReport;
 use tree_sitter_graph::Identifier;
 use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::TraceRecorder;
 use tree_sitter_graph::Variables;
 use tree_sitter_loader::Loader;
 
@@ -36,6 +43,20 @@ fn main() -> Result<()> {
         .version(BUILD_VERSION)
         .author("Douglas Creager <dcreager@dcreager.net>")
         .about("Generates graph structures from tree-sitter syntax trees")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(App::new("builtins").about("Lists the functions that graph DSL files can call"))
+        .subcommand(
+            App::new("replay")
+                .about("Builds the graph again from a trace that was recorded with --trace")
+                .arg(Arg::with_name("trace").index(1).required(true))
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .takes_value(false)
+                        .help("Output the graph as JSON"),
+                ),
+        )
         .arg(Arg::with_name("tsg").index(1).required(true))
         .arg(Arg::with_name("source").index(2).required(true))
         .arg(
@@ -50,13 +71,80 @@ fn main() -> Result<()> {
                 .long("lazy")
                 .help("Use lazy evaluation (experimental)"),
         )
+        .arg(
+            Arg::with_name("parallel")
+                .long("parallel")
+                .help("Execute stanzas, or shards of matches with --lazy, on separate threads"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .help("Print statistics about the execution of each stanza to stderr"),
+        )
+        .arg(
+            Arg::with_name("match-limit")
+                .long("match-limit")
+                .takes_value(true)
+                .help("Limit the number of in-progress matches of the stanza queries"),
+        )
+        .arg(
+            Arg::with_name("tag")
+                .long("tag")
+                .takes_value(true)
+                .action(ArgAction::Append)
+                .help("Only execute the tagged stanzas that have one of these tags"),
+        )
+        .arg(
+            Arg::with_name("exclude-tag")
+                .long("exclude-tag")
+                .takes_value(true)
+                .action(ArgAction::Append)
+                .help("Do not execute the stanzas that have one of these tags"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .conflicts_with("format")
+                .help("Check the rules without building the graph, printing its number of nodes and edges"),
+        )
+        .arg(
+            Arg::with_name("continue-on-error")
+                .long("continue-on-error")
+                .help("Skip the stanza matches that fail, and report their errors at the end"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .takes_value(true)
+                .help("Record a trace of the execution to a file, which the replay command replays"),
+        )
+        .arg(
+            Arg::with_name("node-types")
+                .long("node-types")
+                .takes_value(true)
+                .help("Check the patterns of the TSG file against the node-types.json file of a grammar"),
+        )
         .arg(Arg::with_name("scope").long("scope").takes_value(true))
         .arg(Arg::with_name("json").long("json").takes_value(false))
+        .arg(
+            Arg::with_name("ndjson")
+                .long("ndjson")
+                .takes_value(false)
+                .help("Output the graph as newline-delimited JSON"),
+        )
+        .group(ArgGroup::new("format").args(&["json", "ndjson"]))
+        .arg(
+            Arg::with_name("compact")
+                .long("compact")
+                .requires("json")
+                .takes_value(false)
+                .help("Output JSON without insignificant whitespace"),
+        )
         .arg(
             Arg::with_name("output")
                 .short('o')
                 .long("output")
-                .requires("json")
+                .requires("format")
                 .takes_value(true),
         )
         .arg(
@@ -64,6 +152,12 @@ fn main() -> Result<()> {
                 .long("allow-parse-errors")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("allow-env")
+                .long("allow-env")
+                .takes_value(false)
+                .help("Allow the graph DSL file to read environment variables"),
+        )
         .arg(
             Arg::with_name("global")
                 .long("global")
@@ -72,11 +166,40 @@ fn main() -> Result<()> {
         )
         .get_matches();
 
+    if matches.subcommand_matches("builtins").is_some() {
+        list_builtins(&Functions::stdlib());
+        return Ok(());
+    }
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        return replay(
+            Path::new(matches.value_of("trace").unwrap()),
+            matches.is_present("json"),
+        );
+    }
+
     let tsg_path = Path::new(matches.value_of("tsg").unwrap());
     let source_path = Path::new(matches.value_of("source").unwrap());
     let current_dir = std::env::current_dir().unwrap();
     let quiet = matches.is_present("quiet");
     let lazy = matches.is_present("lazy");
+    let parallel = matches.is_present("parallel");
+    let profile = matches.is_present("profile");
+    let dry_run = matches.is_present("dry-run");
+    let continue_on_error = matches.is_present("continue-on-error");
+    let trace_path = matches.value_of("trace").map(Path::new);
+    let match_limit = matches
+        .value_of("match-limit")
+        .map(|limit| limit.parse::<u32>())
+        .transpose()
+        .context("Expected an integer match limit")?;
+    let tags = (matches.get_many::<String>("tag").unwrap_or_default())
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let exclude_tags = (matches
+        .get_many::<String>("exclude-tag")
+        .unwrap_or_default())
+    .map(String::as_str)
+    .collect::<Vec<_>>();
     let globals = matches.get_many::<String>("global").unwrap_or_default();
     let mut globals_ = Variables::new();
     for kv in globals {
@@ -105,6 +228,22 @@ fn main() -> Result<()> {
             return Err(anyhow!("Cannot parse TSG file {}", tsg_path.display()));
         }
     };
+    for warning in &file.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if let Some(node_types_path) = matches.value_of("node-types").map(Path::new) {
+        let node_types = fs::read_to_string(node_types_path)
+            .with_context(|| format!("Cannot read node types {}", node_types_path.display()))?;
+        let node_types = NodeTypes::from_json(&node_types)?;
+        if let Err(err) = file.check_node_types(&node_types) {
+            eprintln!("{}", err.display_pretty(tsg_path, &tsg));
+            return Err(anyhow!(
+                "TSG file {} does not match the node types {}",
+                tsg_path.display(),
+                node_types_path.display()
+            ));
+        }
+    }
 
     let source = std::fs::read(source_path)
         .with_context(|| format!("Cannot read source file {}", source_path.display()))?;
@@ -133,27 +272,122 @@ fn main() -> Result<()> {
         }
     }
 
-    let functions = Functions::stdlib();
-    let mut config = ExecutionConfig::new(&functions, &globals_).lazy(lazy);
-    let graph = match file.execute(&tree, &source, &mut config, &NoCancellation) {
-        Ok(graph) => graph,
+    let mut functions = Functions::stdlib();
+    if matches.is_present("allow-env") {
+        functions.add_env();
+    }
+    let mut config = ExecutionConfig::new(&functions, &globals_)
+        .lazy(lazy)
+        .parallel(parallel)
+        .profile(profile)
+        .dry_run(dry_run)
+        .continue_on_error(continue_on_error)
+        .with_tags(&tags, &exclude_tags);
+    if let Some(match_limit) = match_limit {
+        config = config.match_limit(match_limit);
+    }
+    let recorder = trace_path.map(|_| TraceRecorder::new());
+    if let Some(recorder) = &recorder {
+        config = config.observer(recorder);
+    }
+    let mut graph = graph::Graph::new();
+    let result = file.execute_into(&mut graph, &tree, &source, &config, &NoCancellation);
+    if let (Some(path), Some(recorder)) = (trace_path, recorder) {
+        recorder
+            .into_trace()
+            .write(BufWriter::new(fs::File::create(path)?))
+            .with_context(|| format!("Cannot write trace {}", path.display()))?;
+    }
+    let report = match result {
+        Ok(report) => report,
         Err(e) => {
             eprintln!("{}", e.display_pretty(source_path, &source, tsg_path, &tsg));
             return Err(anyhow!("Cannot execute TSG file {}", tsg_path.display()));
         }
     };
+    for warning in report.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    for error in report.errors() {
+        eprintln!(
+            "{}",
+            error.display_pretty(source_path, &source, tsg_path, &tsg)
+        );
+    }
+    if let Some(profile) = report.profile() {
+        eprint!("{}", profile);
+    }
+
+    if let Some(stats) = report.dry_run() {
+        if !quiet {
+            println!("nodes: {}", stats.node_count);
+            println!("edges: {}", stats.edge_count);
+            println!("node attributes: {}", stats.node_attribute_count);
+            println!("edge attributes: {}", stats.edge_attribute_count);
+        }
+        return check_errors(&report, tsg_path);
+    }
 
     let json = matches.is_present("json");
+    let ndjson = matches.is_present("ndjson");
     let output_path = matches.value_of("output").map(|str| Path::new(str));
     if json {
-        graph.display_json(output_path).unwrap_or(());
+        let style = if matches.is_present("compact") {
+            graph::JsonStyle::Compact
+        } else {
+            graph::JsonStyle::Pretty
+        };
+        match output_path {
+            Some(path) => graph.write_json(BufWriter::new(fs::File::create(path)?), style)?,
+            None => graph.write_json(BufWriter::new(stdout().lock()), style)?,
+        }
+    } else if ndjson {
+        match output_path {
+            Some(path) => graph.write_ndjson(BufWriter::new(fs::File::create(path)?))?,
+            None => graph.write_ndjson(BufWriter::new(stdout().lock()))?,
+        }
     } else if !quiet {
         print!("{}", graph.pretty_print());
     }
 
+    check_errors(&report, tsg_path)
+}
+
+/// Fails if the execution recorded errors, because it was configured to continue after them.
+fn check_errors(report: &ExecutionReport, tsg_path: &Path) -> Result<()> {
+    if report.errors().is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Executing TSG file {} recorded {} errors",
+        tsg_path.display(),
+        report.errors().len()
+    ))
+}
+
+fn replay(trace_path: &Path, json: bool) -> Result<()> {
+    let trace = std::fs::read_to_string(trace_path)
+        .with_context(|| format!("Cannot read trace {}", trace_path.display()))?;
+    let graph = graph::Trace::read(&trace)
+        .and_then(|trace| trace.replay())
+        .with_context(|| format!("Cannot replay trace {}", trace_path.display()))?;
+    if json {
+        graph.write_json(BufWriter::new(stdout().lock()), graph::JsonStyle::Pretty)?;
+    } else {
+        print!("{}", graph.pretty_print());
+    }
     Ok(())
 }
 
+fn list_builtins(functions: &Functions) {
+    for description in functions.descriptions() {
+        println!("{}", description.signature);
+        if !description.doc.is_empty() {
+            println!("    {}", description.doc);
+        }
+    }
+}
+
 fn init_log() {
     let _ = env_logger::builder()
         .format_level(false)
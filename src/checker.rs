@@ -5,6 +5,8 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::Path;
 
@@ -17,6 +19,8 @@ use tree_sitter::CaptureQuantifier::ZeroOrOne;
 use tree_sitter::Query;
 
 use crate::ast;
+use crate::functions::Functions;
+use crate::functions::ParameterType;
 use crate::parse_error::Excerpt;
 use crate::parser::FULL_MATCH;
 use crate::variables::MutVariables;
@@ -40,18 +44,46 @@ pub enum CheckError {
     ExpectedLocalValue(Location),
     #[error("Expected optional value at {0}")]
     ExpectedOptionalValue(Location),
+    #[error("Expected {} at {}, got {} from {}", .0.description(), .1, .2.description(), .3)]
+    TypeMismatch(ParameterType, Location, ParameterType, Location),
     #[error("Nullable regular expression /{0}/ at {1}")]
     NullableRegex(String, Location),
+    #[error("Undefined function {0} at {1}")]
+    UndefinedFunction(String, Location),
     #[error("Undefined syntax capture @{0} at {1}")]
     UndefinedSyntaxCapture(String, Location),
     #[error("Undefined variable {0} at {1}")]
     UndefinedVariable(String, Location),
-    #[error("Unused capture(s) {0} at {1}. Remove or prefix with _.")]
-    UnusedCaptures(String, Location),
+    #[error("Field {1} of {0} cannot contain {2} at {3}")]
+    UnexpectedFieldNodeKind(String, String, String, Location),
+    #[error("Unknown field {1} of {0} at {2}")]
+    UnknownField(String, String, Location),
+    #[error("Unknown node kind {0} at {1}")]
+    UnknownNodeKind(String, Location),
     #[error("{0}: {1} at {2}")]
     Variable(VariableError, String, Location),
 }
 
+/// A problem in a graph DSL file that does not stop it from being executed, but that is likely a
+/// mistake.  See [`File::warnings`][`crate::ast::File::warnings`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum CheckWarning {
+    /// A stanza has the same pattern, statements, and annotations as an earlier one, so it
+    /// only repeats what the earlier stanza does
+    #[error(
+        "Stanza at {0} duplicates the stanza at {1}, which has the same pattern and statements"
+    )]
+    DuplicateStanza(Location, Location),
+    /// The statements of an `if` arm are never executed, because one of its conditions is always
+    /// false, or because an earlier arm is always taken
+    #[error("Unreachable statements at {0}")]
+    UnreachableStatements(Location),
+    #[error("Unused variable {0} at {1}. Remove or prefix with _.")]
+    UnusedVariable(String, Location),
+    #[error("Unused capture {0} at {1}. Remove or prefix with _.")]
+    UnusedCapture(String, Location),
+}
+
 impl CheckError {
     pub fn display_pretty<'a>(
         &'a self,
@@ -81,10 +113,14 @@ impl std::fmt::Display for DisplayCheckErrorPretty<'_> {
             CheckError::ExpectedListValue(location) => *location,
             CheckError::ExpectedLocalValue(location) => *location,
             CheckError::ExpectedOptionalValue(location) => *location,
+            CheckError::TypeMismatch(_, location, _, _) => *location,
             CheckError::NullableRegex(_, location) => *location,
+            CheckError::UndefinedFunction(_, location) => *location,
             CheckError::UndefinedSyntaxCapture(_, location) => *location,
             CheckError::UndefinedVariable(_, location) => *location,
-            CheckError::UnusedCaptures(_, location) => *location,
+            CheckError::UnexpectedFieldNodeKind(_, _, _, location) => *location,
+            CheckError::UnknownField(_, _, location) => *location,
+            CheckError::UnknownNodeKind(_, location) => *location,
             CheckError::Variable(_, _, location) => *location,
         };
         writeln!(f, "{}", self.error)?;
@@ -99,23 +135,48 @@ impl std::fmt::Display for DisplayCheckErrorPretty<'_> {
                 0
             )
         )?;
+        if let CheckError::TypeMismatch(_, _, _, origin) = self.error {
+            if origin.row != location.row {
+                write!(
+                    f,
+                    "{}",
+                    Excerpt::from_source(
+                        self.path,
+                        self.source,
+                        origin.row,
+                        origin.to_column_range(),
+                        0
+                    )
+                )?;
+            }
+        }
         Ok(())
     }
 }
 
 /// Checker context
 struct CheckContext<'a> {
+    functions: Option<&'a Functions>,
     globals: &'a dyn Variables<VariableResult>,
     file_query: &'a Query,
     stanza_index: usize,
     stanza_query: &'a Query,
     locals: &'a mut dyn MutVariables<VariableResult>,
+    /// The location of the statement being checked
+    location: Location,
+    /// The local variables of the stanza that have not been used yet, by the location where they
+    /// are declared
+    unused_variables: &'a mut HashMap<Location, Identifier>,
 }
 
 #[derive(Clone, Debug)]
 struct VariableResult {
     is_local: bool,
     quantifier: CaptureQuantifier,
+    value_type: ParameterType,
+    type_location: Location,
+    /// The location where the variable is declared, if it is a local variable
+    declaration: Option<Location>,
 }
 
 //-----------------------------------------------------------------------------
@@ -123,6 +184,20 @@ struct VariableResult {
 
 impl ast::File {
     pub fn check(&mut self) -> Result<(), CheckError> {
+        self.check_with(None)
+    }
+
+    /// Checks this file like [`File::check`][`ast::File::check`], which happens when it is
+    /// parsed, and also checks the calls to functions of `functions` that have a
+    /// [signature][`crate::functions::Signature`]: the types of their parameters must match it,
+    /// and their results have the type that it returns.  Calls to other functions can take and
+    /// return any value.  The functions that are passed by reference, with `&name`, must be
+    /// functions of `functions`.
+    pub fn check_with_functions(&mut self, functions: &Functions) -> Result<(), CheckError> {
+        self.check_with(Some(functions))
+    }
+
+    fn check_with(&mut self, functions: Option<&Functions>) -> Result<(), CheckError> {
         let mut globals = VariableMap::new();
         for global in &self.globals {
             globals
@@ -131,6 +206,12 @@ impl ast::File {
                     VariableResult {
                         quantifier: global.quantifier,
                         is_local: true,
+                        value_type: match global.quantifier {
+                            ZeroOrMore | OneOrMore => ParameterType::List,
+                            _ => ParameterType::Any,
+                        },
+                        type_location: global.location,
+                        declaration: None,
                     },
                     false,
                 )
@@ -142,9 +223,24 @@ impl ast::File {
                 })?;
         }
         let file_query = self.query.as_ref().unwrap();
+        let mut warnings = Vec::new();
         for (index, stanza) in self.stanzas.iter_mut().enumerate() {
-            stanza.check(&globals, file_query, index)?;
+            stanza.check(functions, &globals, file_query, index, &mut warnings)?;
+            find_unreachable_statements(&stanza.statements, HashMap::new(), &mut warnings);
+        }
+        let mut stanzas = HashMap::new();
+        for stanza in &self.stanzas {
+            match stanzas.entry(stanza.duplicate_key()) {
+                Entry::Occupied(earlier) => warnings.push(CheckWarning::DuplicateStanza(
+                    stanza.range.start,
+                    *earlier.get(),
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert(stanza.range.start);
+                }
+            }
         }
+        self.warnings = warnings;
         Ok(())
     }
 }
@@ -155,17 +251,23 @@ impl ast::File {
 impl ast::Stanza {
     fn check(
         &mut self,
+        functions: Option<&Functions>,
         globals: &dyn Variables<VariableResult>,
         file_query: &Query,
         stanza_index: usize,
+        warnings: &mut Vec<CheckWarning>,
     ) -> Result<(), CheckError> {
         let mut locals = VariableMap::new();
+        let mut unused_variables = HashMap::new();
         let mut ctx = CheckContext {
+            functions,
             globals,
             file_query,
             stanza_index,
             stanza_query: &self.query,
             locals: &mut locals,
+            location: self.range.start,
+            unused_variables: &mut unused_variables,
         };
         self.full_match_file_capture_index =
             ctx.file_query
@@ -190,22 +292,88 @@ impl ast::Stanza {
             })
             .map(|cn| Identifier::from(cn.as_str()))
             .collect::<HashSet<_>>();
-        let unused_captures = all_captures
-            .difference(&used_captures)
-            .filter(|i| !i.starts_with("_"))
-            .map(|i| format!("@{}", i))
+        let capture_locations = self.capture_locations();
+        let mut unused_variables = unused_variables
+            .into_iter()
+            .map(|(location, name)| {
+                let warning = CheckWarning::UnusedVariable(name.as_str().to_string(), location);
+                (location, warning)
+            })
             .collect::<Vec<_>>();
-        if !unused_captures.is_empty() {
-            return Err(CheckError::UnusedCaptures(
-                unused_captures.join(" "),
-                self.range.start,
-            ));
+        for name in all_captures.difference(&used_captures) {
+            if name.starts_with("_") {
+                continue;
+            }
+            let location = capture_locations
+                .get(name.as_str())
+                .copied()
+                .unwrap_or(self.range.start);
+            let warning = CheckWarning::UnusedCapture(format!("@{}", name), location);
+            unused_variables.push((location, warning));
         }
+        unused_variables.sort_by_key(|(location, _)| (location.row, location.column));
+        warnings.extend(unused_variables.into_iter().map(|(_, warning)| warning));
 
         Ok(())
     }
 }
 
+impl ast::Stanza {
+    /// Returns the location of the first occurrence of each capture in the query of this stanza,
+    /// skipping strings and comments.
+    fn capture_locations(&self) -> HashMap<&str, Location> {
+        let mut locations = HashMap::new();
+        let mut location = self.range.start;
+        let mut in_string = false;
+        let mut in_escape = false;
+        let mut in_comment = false;
+        let mut capture_start = None;
+        for (offset, ch) in self.query_source.char_indices() {
+            if let Some((start, start_location)) = capture_start {
+                if !is_capture_name_char(ch) {
+                    locations
+                        .entry(&self.query_source[start..offset])
+                        .or_insert(start_location);
+                    capture_start = None;
+                }
+            }
+            if in_escape {
+                in_escape = false;
+            } else if in_string {
+                match ch {
+                    '\\' => in_escape = true,
+                    '"' | '\n' => in_string = false,
+                    _ => {}
+                }
+            } else if in_comment {
+                in_comment = ch != '\n';
+            } else if capture_start.is_none() {
+                match ch {
+                    '"' => in_string = true,
+                    ';' => in_comment = true,
+                    '@' => capture_start = Some((offset + 1, location)),
+                    _ => {}
+                }
+            }
+            location.advance(ch);
+        }
+        locations
+    }
+
+    /// Returns what another stanza must have in common with this one to duplicate it: the lines
+    /// of its query, ignoring indentation, its statements, ignoring where they are in the file,
+    /// and its annotations.  Stanzas whose queries are written differently are not duplicates,
+    /// even if they match the same syntax nodes.
+    fn duplicate_key(&self) -> (Vec<&str>, Vec<u8>, Option<u32>, &[String]) {
+        let query = (self.query_source.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let statements = self.encode_statements_without_locations();
+        (query, statements, self.match_limit, &self.tags)
+    }
+}
+
 //-----------------------------------------------------------------------------
 // Statements
 
@@ -216,6 +384,7 @@ struct StatementResult {
 
 impl ast::Statement {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
+        ctx.location = self.location();
         match self {
             Self::DeclareImmutable(stmt) => stmt.check(ctx),
             Self::DeclareMutable(stmt) => stmt.check(ctx),
@@ -239,6 +408,7 @@ impl ast::DeclareImmutable {
         used_captures.extend(value.used_captures.iter().cloned());
         let var_result = self.variable.check_add(ctx, value.into(), false)?;
         used_captures.extend(var_result.used_captures);
+        self.variable.declare_unused(ctx);
         Ok(StatementResult { used_captures })
     }
 }
@@ -250,6 +420,7 @@ impl ast::DeclareMutable {
         used_captures.extend(value.used_captures.iter().cloned());
         let var_result = self.variable.check_add(ctx, value.into(), true)?;
         used_captures.extend(var_result.used_captures);
+        self.variable.declare_unused(ctx);
         Ok(StatementResult { used_captures })
     }
 }
@@ -272,6 +443,9 @@ impl ast::CreateGraphNode {
             VariableResult {
                 is_local: true,
                 quantifier: One,
+                value_type: ParameterType::GraphNode,
+                type_location: self.location,
+                declaration: None,
             },
             false,
         )?;
@@ -285,6 +459,7 @@ impl ast::AddGraphNodeAttribute {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
         let node_result = self.node.check(ctx)?;
+        node_result.expect_type(ParameterType::GraphNode, self.location)?;
         used_captures.extend(node_result.used_captures);
         for attribute in &mut self.attributes {
             let attr_result = attribute.check(ctx)?;
@@ -298,8 +473,10 @@ impl ast::CreateEdge {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
         let source_result = self.source.check(ctx)?;
+        source_result.expect_type(ParameterType::GraphNode, self.location)?;
         used_captures.extend(source_result.used_captures);
         let sink_result = self.sink.check(ctx)?;
+        sink_result.expect_type(ParameterType::GraphNode, self.location)?;
         used_captures.extend(sink_result.used_captures);
         Ok(StatementResult { used_captures })
     }
@@ -309,8 +486,10 @@ impl ast::AddEdgeAttribute {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
         let source_result = self.source.check(ctx)?;
+        source_result.expect_type(ParameterType::GraphNode, self.location)?;
         used_captures.extend(source_result.used_captures);
         let sink_result = self.sink.check(ctx)?;
+        sink_result.expect_type(ParameterType::GraphNode, self.location)?;
         used_captures.extend(sink_result.used_captures);
         for attribute in &mut self.attributes {
             let attr_result = attribute.check(ctx)?;
@@ -328,6 +507,7 @@ impl ast::Scan {
         if !value_result.is_local {
             return Err(CheckError::ExpectedLocalValue(self.location));
         }
+        value_result.expect_type(ParameterType::String, self.location)?;
         used_captures.extend(value_result.used_captures);
 
         for arm in &mut self.arms {
@@ -345,11 +525,14 @@ impl ast::Scan {
 
             let mut arm_locals = VariableMap::nested(ctx.locals);
             let mut arm_ctx = CheckContext {
+                functions: ctx.functions,
                 globals: ctx.globals,
                 file_query: ctx.file_query,
                 stanza_index: ctx.stanza_index,
                 stanza_query: ctx.stanza_query,
                 locals: &mut arm_locals,
+                location: ctx.location,
+                unused_variables: ctx.unused_variables,
             };
 
             for statement in &mut arm.statements {
@@ -384,11 +567,14 @@ impl ast::If {
 
             let mut arm_locals = VariableMap::nested(ctx.locals);
             let mut arm_ctx = CheckContext {
+                functions: ctx.functions,
                 globals: ctx.globals,
                 file_query: ctx.file_query,
                 stanza_index: ctx.stanza_index,
                 stanza_query: ctx.stanza_query,
                 locals: &mut arm_locals,
+                location: ctx.location,
+                unused_variables: ctx.unused_variables,
             };
 
             for statement in &mut arm.statements {
@@ -419,6 +605,7 @@ impl ast::Condition {
                 if !value_result.is_local {
                     return Err(CheckError::ExpectedLocalValue(*location));
                 }
+                value_result.expect_type(ParameterType::Boolean, *location)?;
                 used_captures.extend(value_result.used_captures);
             }
         }
@@ -426,6 +613,84 @@ impl ast::Condition {
     }
 }
 
+impl ast::Condition {
+    /// Returns the value that this condition always has, if it is a `#true` or `#false` literal,
+    /// or a local variable in `constants`, which are bound to such values.
+    fn constant_value(&self, constants: &HashMap<Identifier, bool>) -> Option<bool> {
+        match self {
+            Self::Bool { value, .. } => value.constant_value(constants),
+            Self::Some { .. } | Self::None { .. } => None,
+        }
+    }
+}
+
+impl ast::Expression {
+    /// Returns the boolean value that this expression always has, if it is a `#true` or `#false`
+    /// literal, or a local variable in `constants`, which are bound to such values.
+    fn constant_value(&self, constants: &HashMap<Identifier, bool>) -> Option<bool> {
+        match self {
+            Self::TrueLiteral => Some(true),
+            Self::FalseLiteral => Some(false),
+            Self::Variable(ast::Variable::Unscoped(variable)) => {
+                constants.get(&variable.name).copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether a character can be part of the name of a query capture.
+fn is_capture_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.' | '?' | '!')
+}
+
+/// Reports the arms of the `if` statements in `statements`, and the statements nested in them,
+/// that are never executed.  `constants` holds the local variables of the enclosing blocks that
+/// are bound to `#true` or `#false`, directly or through other such variables.
+fn find_unreachable_statements(
+    statements: &[ast::Statement],
+    mut constants: HashMap<Identifier, bool>,
+    warnings: &mut Vec<CheckWarning>,
+) {
+    for statement in statements {
+        match statement {
+            ast::Statement::DeclareImmutable(stmt) => {
+                if let ast::Variable::Unscoped(variable) = &stmt.variable {
+                    if let Some(value) = stmt.value.constant_value(&constants) {
+                        constants.insert(variable.name.clone(), value);
+                    }
+                }
+            }
+            ast::Statement::If(stmt) => {
+                let mut reachable = true;
+                for arm in &stmt.arms {
+                    let values = (arm.conditions.iter())
+                        .map(|condition| condition.constant_value(&constants))
+                        .collect::<Vec<_>>();
+                    if !reachable || values.contains(&Some(false)) {
+                        warnings.push(CheckWarning::UnreachableStatements(arm.location));
+                        continue;
+                    }
+                    find_unreachable_statements(&arm.statements, constants.clone(), warnings);
+                    // the later arms are only reached if this one may not be taken
+                    reachable = !values.iter().all(|value| *value == Some(true));
+                }
+            }
+            ast::Statement::ForIn(stmt) => {
+                let mut constants = constants.clone();
+                constants.remove(&stmt.variable.name);
+                find_unreachable_statements(&stmt.statements, constants, warnings);
+            }
+            ast::Statement::Scan(stmt) => {
+                for arm in &stmt.arms {
+                    find_unreachable_statements(&arm.statements, constants.clone(), warnings);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 impl ast::ForIn {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
@@ -437,19 +702,23 @@ impl ast::ForIn {
         if value_result.quantifier != ZeroOrMore && value_result.quantifier != OneOrMore {
             return Err(CheckError::ExpectedListValue(self.location));
         }
+        value_result.expect_type(ParameterType::List, self.location)?;
         used_captures.extend(value_result.used_captures.iter().cloned());
 
         let mut loop_locals = VariableMap::nested(ctx.locals);
         let mut loop_ctx = CheckContext {
+            functions: ctx.functions,
             globals: ctx.globals,
             file_query: ctx.file_query,
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            location: ctx.location,
+            unused_variables: ctx.unused_variables,
         };
-        let var_result = self
-            .variable
-            .check_add(&mut loop_ctx, value_result.into(), false)?;
+        let var_result =
+            self.variable
+                .check_add(&mut loop_ctx, value_result.into_element(), false)?;
         used_captures.extend(var_result.used_captures);
 
         for statement in &mut self.statements {
@@ -470,6 +739,27 @@ struct ExpressionResult {
     is_local: bool,
     quantifier: CaptureQuantifier,
     used_captures: HashSet<Identifier>,
+    /// The type of the value, or `Any` if it is not known
+    value_type: ParameterType,
+    /// The location that determines the type of the value
+    type_location: Location,
+}
+
+impl ExpressionResult {
+    fn expect_type(&self, expected: ParameterType, location: Location) -> Result<(), CheckError> {
+        if expected != ParameterType::Any
+            && self.value_type != ParameterType::Any
+            && self.value_type != expected
+        {
+            return Err(CheckError::TypeMismatch(
+                expected,
+                location,
+                self.value_type,
+                self.type_location,
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl ast::Expression {
@@ -479,16 +769,22 @@ impl ast::Expression {
                 is_local: true,
                 quantifier: One,
                 used_captures: HashSet::default(),
+                value_type: ParameterType::Boolean,
+                type_location: ctx.location,
             }),
             Self::NullLiteral => Ok(ExpressionResult {
                 is_local: true,
                 quantifier: One,
                 used_captures: HashSet::default(),
+                value_type: ParameterType::Null,
+                type_location: ctx.location,
             }),
             Self::TrueLiteral => Ok(ExpressionResult {
                 is_local: true,
                 quantifier: One,
                 used_captures: HashSet::default(),
+                value_type: ParameterType::Boolean,
+                type_location: ctx.location,
             }),
             Self::IntegerConstant(expr) => expr.check(ctx),
             Self::StringConstant(expr) => expr.check(ctx),
@@ -499,27 +795,32 @@ impl ast::Expression {
             Self::Capture(expr) => expr.check(ctx),
             Self::Variable(expr) => expr.check_get(ctx),
             Self::Call(expr) => expr.check(ctx),
+            Self::FunctionRef(expr) => expr.check(ctx),
             Self::RegexCapture(expr) => expr.check(ctx),
         }
     }
 }
 
 impl ast::IntegerConstant {
-    fn check(&mut self, _ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         Ok(ExpressionResult {
             is_local: true,
             quantifier: One,
             used_captures: HashSet::default(),
+            value_type: ParameterType::Integer,
+            type_location: ctx.location,
         })
     }
 }
 
 impl ast::StringConstant {
-    fn check(&mut self, _ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         Ok(ExpressionResult {
             is_local: true,
             quantifier: One,
             used_captures: HashSet::default(),
+            value_type: ParameterType::String,
+            type_location: ctx.location,
         })
     }
 }
@@ -537,6 +838,8 @@ impl ast::ListLiteral {
             is_local,
             quantifier: ZeroOrMore,
             used_captures,
+            value_type: ParameterType::List,
+            type_location: ctx.location,
         })
     }
 }
@@ -554,6 +857,8 @@ impl ast::SetLiteral {
             is_local,
             quantifier: ZeroOrMore,
             used_captures,
+            value_type: ParameterType::Set,
+            type_location: ctx.location,
         })
     }
 }
@@ -569,19 +874,23 @@ impl ast::ListComprehension {
         if value_result.quantifier != ZeroOrMore && value_result.quantifier != OneOrMore {
             return Err(CheckError::ExpectedListValue(self.location));
         }
+        value_result.expect_type(ParameterType::List, self.location)?;
         used_captures.extend(value_result.used_captures.iter().cloned());
 
         let mut loop_locals = VariableMap::nested(ctx.locals);
         let mut loop_ctx = CheckContext {
+            functions: ctx.functions,
             globals: ctx.globals,
             file_query: ctx.file_query,
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            location: ctx.location,
+            unused_variables: ctx.unused_variables,
         };
-        let var_result = self
-            .variable
-            .check_add(&mut loop_ctx, value_result.into(), false)?;
+        let var_result =
+            self.variable
+                .check_add(&mut loop_ctx, value_result.into_element(), false)?;
         used_captures.extend(var_result.used_captures);
 
         let element_result = self.element.check(&mut loop_ctx)?;
@@ -591,6 +900,8 @@ impl ast::ListComprehension {
             is_local: element_result.is_local,
             quantifier: ZeroOrMore,
             used_captures,
+            value_type: ParameterType::List,
+            type_location: self.location,
         })
     }
 }
@@ -606,19 +917,23 @@ impl ast::SetComprehension {
         if value_result.quantifier != ZeroOrMore && value_result.quantifier != OneOrMore {
             return Err(CheckError::ExpectedListValue(self.location));
         }
+        value_result.expect_type(ParameterType::List, self.location)?;
         used_captures.extend(value_result.used_captures.iter().cloned());
 
         let mut loop_locals = VariableMap::nested(ctx.locals);
         let mut loop_ctx = CheckContext {
+            functions: ctx.functions,
             globals: ctx.globals,
             file_query: ctx.file_query,
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            location: ctx.location,
+            unused_variables: ctx.unused_variables,
         };
-        let var_result = self
-            .variable
-            .check_add(&mut loop_ctx, value_result.into(), false)?;
+        let var_result =
+            self.variable
+                .check_add(&mut loop_ctx, value_result.into_element(), false)?;
         used_captures.extend(var_result.used_captures);
 
         let element_result = self.element.check(&mut loop_ctx)?;
@@ -628,6 +943,8 @@ impl ast::SetComprehension {
             is_local: element_result.is_local,
             quantifier: ZeroOrMore,
             used_captures,
+            value_type: ParameterType::Set,
+            type_location: self.location,
         })
     }
 }
@@ -650,6 +967,13 @@ impl ast::Capture {
             is_local: true,
             quantifier: self.quantifier,
             used_captures: HashSet::from([self.name.clone()]),
+            value_type: match self.quantifier {
+                One => ParameterType::SyntaxNode,
+                ZeroOrMore | OneOrMore => ParameterType::List,
+                // an optional capture is a syntax node or null
+                _ => ParameterType::Any,
+            },
+            type_location: self.location,
         })
     }
 }
@@ -658,8 +982,14 @@ impl ast::Call {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         let mut is_local = true;
         let mut used_captures = HashSet::new();
-        for parameter in &mut self.parameters {
+        let signature = ctx
+            .functions
+            .and_then(|functions| functions.signature(&self.function));
+        for (index, parameter) in self.parameters.iter_mut().enumerate() {
             let parameter_result = parameter.check(ctx)?;
+            if let Some(parameter_type) = signature.and_then(|s| s.parameter_type(index)) {
+                parameter_result.expect_type(parameter_type, ctx.location)?;
+            }
             is_local &= parameter_result.is_local;
             used_captures.extend(parameter_result.used_captures);
         }
@@ -667,16 +997,40 @@ impl ast::Call {
             is_local,
             quantifier: One, // FIXME we don't really know
             used_captures,
+            value_type: signature.map_or(ParameterType::Any, |s| s.return_type()),
+            type_location: ctx.location,
+        })
+    }
+}
+
+impl ast::FunctionRef {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        if let Some(functions) = ctx.functions {
+            if !functions.contains(&self.name) {
+                return Err(CheckError::UndefinedFunction(
+                    self.name.as_str().to_string(),
+                    self.location,
+                ));
+            }
+        }
+        Ok(ExpressionResult {
+            is_local: true,
+            quantifier: One,
+            used_captures: HashSet::default(),
+            value_type: ParameterType::Function,
+            type_location: self.location,
         })
     }
 }
 
 impl ast::RegexCapture {
-    fn check(&mut self, _ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         Ok(ExpressionResult {
             is_local: true,
             quantifier: One,
             used_captures: HashSet::default(),
+            value_type: ParameterType::String,
+            type_location: ctx.location,
         })
     }
 }
@@ -714,6 +1068,16 @@ impl ast::Variable {
             Self::Scoped(v) => v.check_get(ctx),
         }
     }
+
+    /// Records a local variable that was just declared, so that it is reported if it is never
+    /// used.  Variables whose name starts with an underscore are not reported.
+    fn declare_unused(&self, ctx: &mut CheckContext) {
+        if let Self::Unscoped(v) = self {
+            if !v.name.starts_with("_") {
+                ctx.unused_variables.insert(v.location, v.name.clone());
+            }
+        }
+    }
 }
 
 impl ast::UnscopedVariable {
@@ -736,7 +1100,9 @@ impl ast::UnscopedVariable {
         // assignments, and can assume non-local to be sound.
         if mutable {
             value.is_local = false;
+            value.value_type = ParameterType::Any;
         }
+        value.declaration = Some(self.location);
         ctx.locals
             .add(self.name.clone(), value, mutable)
             .map_err(|e| CheckError::Variable(e, format!("{}", self.name), self.location))?;
@@ -762,6 +1128,9 @@ impl ast::UnscopedVariable {
         // Since we process all statement in order, we don't have info on later
         // assignments, and can assume non-local to be sound.
         value.is_local = false;
+        value.value_type = ParameterType::Any;
+        // assigning a variable does not use it
+        value.declaration = ctx.locals.get(&self.name).and_then(|v| v.declaration);
         ctx.locals
             .set(self.name.clone(), value)
             .map_err(|e| CheckError::Variable(e, format!("{}", self.name), self.location))?;
@@ -771,13 +1140,18 @@ impl ast::UnscopedVariable {
     }
 
     fn check_get(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
-        if let Some(result) = ctx.globals.get(&self.name) {
+        let value = if let Some(result) = ctx.globals.get(&self.name) {
             Some(result)
         } else {
             ctx.locals.get(&self.name)
         }
-        .map(|value| value.into())
-        .ok_or_else(|| CheckError::UndefinedVariable(self.name.as_str().to_string(), self.location))
+        .ok_or_else(|| {
+            CheckError::UndefinedVariable(self.name.as_str().to_string(), self.location)
+        })?;
+        if let Some(declaration) = value.declaration {
+            ctx.unused_variables.remove(&declaration);
+        }
+        Ok(value.into())
     }
 }
 
@@ -789,6 +1163,7 @@ impl ast::ScopedVariable {
         _mutable: bool,
     ) -> Result<StatementResult, CheckError> {
         let scope_result = self.scope.check(ctx)?;
+        scope_result.expect_type(ParameterType::SyntaxNode, self.location)?;
         Ok(scope_result.into())
     }
 
@@ -798,15 +1173,19 @@ impl ast::ScopedVariable {
         _value: VariableResult,
     ) -> Result<StatementResult, CheckError> {
         let scope_result = self.scope.check(ctx)?;
+        scope_result.expect_type(ParameterType::SyntaxNode, self.location)?;
         Ok(scope_result.into())
     }
 
     fn check_get(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         let scope_result = self.scope.check(ctx)?;
+        scope_result.expect_type(ParameterType::SyntaxNode, self.location)?;
         Ok(ExpressionResult {
             is_local: false,
             quantifier: One, // FIXME we don't really know
             used_captures: scope_result.used_captures,
+            value_type: ParameterType::Any,
+            type_location: self.location,
         })
     }
 }
@@ -845,6 +1224,8 @@ impl Into<ExpressionResult> for &VariableResult {
             is_local: self.is_local,
             quantifier: self.quantifier,
             used_captures: HashSet::default(),
+            value_type: self.value_type,
+            type_location: self.type_location,
         }
     }
 }
@@ -854,6 +1235,20 @@ impl Into<VariableResult> for ExpressionResult {
         VariableResult {
             is_local: self.is_local,
             quantifier: self.quantifier,
+            value_type: self.value_type,
+            type_location: self.type_location,
+            declaration: None,
+        }
+    }
+}
+
+impl ExpressionResult {
+    /// Returns the result of a variable that is bound to each element of this list value in turn.
+    fn into_element(self) -> VariableResult {
+        VariableResult {
+            // the elements of a list can have any type
+            value_type: ParameterType::Any,
+            ..self.into()
         }
     }
 }
@@ -5,9 +5,20 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
 use thiserror::Error;
 use tree_sitter::CaptureQuantifier;
 use tree_sitter::Node;
+use tree_sitter::Query;
 use tree_sitter::QueryMatch;
 use tree_sitter::Tree;
 
@@ -24,14 +35,105 @@ use crate::variables::Globals;
 use crate::Identifier;
 use crate::Location;
 
+#[cfg(feature = "async")]
+mod asynchronous;
+mod batch;
+mod differential;
+mod dry_run;
 pub(crate) mod error;
+mod incremental;
+mod injection;
 mod lazy;
+mod limits;
+mod observer;
+mod parallel;
+mod profile;
+mod session;
+mod state;
 mod strict;
+mod trace;
+mod yielding;
+
+pub use batch::ROOT_VARIABLE;
+pub use differential::Engine;
+pub use differential::EngineDifference;
+pub(crate) use dry_run::DryRun;
+pub use dry_run::DryRunStats;
+pub use incremental::PreviousExecution;
+pub use injection::Injection;
+pub use lazy::OnDemandGraph;
+pub use limits::LazyMemoryPolicy;
+pub use limits::ResourceLimits;
+pub(crate) use limits::ResourceUsage;
+pub use observer::ExecutionObserver;
+pub use observer::GraphElement;
+pub use profile::Profile;
+pub use profile::StanzaProfile;
+pub use session::ExecutionSession;
+pub(crate) use session::SessionCaches;
+pub(crate) use session::SessionCursor;
+pub use state::ExecutionReport;
+pub use state::ExecutionState;
+pub(crate) use strict::Program;
+pub use trace::TraceRecorder;
+pub(crate) use yielding::run_to_completion;
+pub(crate) use yielding::Yielding;
+
+/// Records the error of a failed stanza match or statement in the state of the execution, and
+/// returns `Ok`, if the execution continues on errors and the error does not stop it.
+pub(crate) fn recover(
+    state: &mut ExecutionState,
+    observer: Option<&dyn ExecutionObserver>,
+    continue_on_error: bool,
+    result: Result<(), ExecutionError>,
+) -> Result<(), ExecutionError> {
+    match result {
+        Err(error) if continue_on_error && !error.stops_execution() => {
+            if let Some(observer) = observer {
+                observer.on_error(&error);
+            }
+            state.add_error(error);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// The largest match limit that tree-sitter query cursors support
+pub(crate) const MAX_MATCH_LIMIT: u32 = 65536;
+
+/// Decides whether to execute a stanza for a match, given the syntax node that the whole stanza
+/// query matched.
+pub(crate) type MatchFilter<'f> = dyn Fn(&Stanza, Node) -> bool + Sync + 'f;
+
+/// Decides whether to execute the stanzas for a query match, before any of their statements run.
+pub type MatchPredicate<'f> = dyn Fn(&QueryMatch) -> bool + Sync + 'f;
+
+/// Selects the stanzas to execute by their tags
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Returns whether to execute `stanza`.
+    pub(crate) fn selects(&self, stanza: &Stanza) -> bool {
+        if stanza.tags.iter().any(|tag| self.exclude.contains(tag)) {
+            return false;
+        }
+        stanza.tags.is_empty()
+            || self.include.is_empty()
+            || stanza.tags.iter().any(|tag| self.include.contains(tag))
+    }
+}
 
 impl File {
     /// Executes this graph DSL file against a source file.  You must provide the parsed syntax
     /// tree (`tree`) as well as the source text that it was parsed from (`source`).  You also
     /// provide the set of functions and global variables that are available during execution.
+    /// Use [`File::execute_into`] to also get the warnings, errors, and profile that the execution
+    /// records.
     pub fn execute<'a, 'tree>(
         &self,
         tree: &'tree Tree,
@@ -49,6 +151,7 @@ impl File {
     /// text that it was parsed from (`source`).  You also provide the set of functions and global
     /// variables that are available during execution. This variant is useful when you need to
     /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
+    /// Returns the report of what the execution recorded besides the graph.
     pub fn execute_into<'a, 'tree>(
         &self,
         graph: &mut Graph<'tree>,
@@ -56,43 +159,138 @@ impl File {
         source: &'tree str,
         config: &ExecutionConfig,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<(), ExecutionError> {
-        if config.lazy {
-            self.execute_lazy_into(graph, tree, source, config, cancellation_flag)
+    ) -> Result<ExecutionReport, ExecutionError> {
+        run_to_completion(self.execute_yielding_into(
+            graph,
+            tree,
+            source,
+            config,
+            cancellation_flag,
+        ))
+    }
+
+    /// Executes this graph DSL file against a source file, like [`File::execute_into`], but
+    /// yields between stanza matches if `config` asks it to.
+    async fn execute_yielding_into<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'_, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<ExecutionReport, ExecutionError> {
+        let mut state = ExecutionState::start(self, config);
+        let dry_run_config;
+        let config = if state.writes_graph() {
+            config
         } else {
-            self.execute_strict_into(graph, tree, source, config, cancellation_flag)
+            dry_run_config = config.clone().parallel(false).with_provenance(false);
+            &dry_run_config
+        };
+        let token_cancellation;
+        let cancellation_flag = match &config.settings.cancellation_token {
+            Some(token) => {
+                token_cancellation = TokenCancellation {
+                    flag: cancellation_flag,
+                    token,
+                };
+                &token_cancellation as &dyn CancellationFlag
+            }
+            None => cancellation_flag,
+        };
+        let result = if config.settings.lazy {
+            self.execute_lazy_into(graph, &mut state, tree, source, config, cancellation_flag)
+                .await
+        } else {
+            self.execute_strict_into(graph, &mut state, tree, source, config, cancellation_flag)
+                .await
+        };
+        if let (Err(error), Some(observer)) = (&result, config.settings.observer) {
+            observer.on_error(error);
+        }
+        result.map(|()| state.into_report())
+    }
+
+    /// Executes this graph DSL file against a source file with lazy evaluation, saving the
+    /// results into an existing `Graph` instance, like [`File::execute_into`], but only evaluates
+    /// the parts of the graph that the caller asks for.  This is useful when the caller only
+    /// needs part of the graph, such as the nodes reachable from the node for the syntax node
+    /// under an editor's cursor.
+    ///
+    /// The returned [`OnDemandGraph`] holds on to `graph` until it is dropped.  The graph has all
+    /// of the graph nodes that the execution creates, but none of their attributes and edges
+    /// until they are forced.  The `lazy` and `dry_run` settings of `config` are ignored.
+    pub fn execute_on_demand_into<'a, 'tree>(
+        &self,
+        graph: &'a mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'a, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<OnDemandGraph<'a, 'tree>, ExecutionError> {
+        let mut state = ExecutionState::start(self, &config.clone().dry_run(false));
+        let token = config.settings.cancellation_token.as_ref();
+        let result = with_cancellation_token(token, cancellation_flag, |cancellation_flag| {
+            run_to_completion(self.build_lazy_graph(
+                graph,
+                &mut state,
+                tree,
+                source,
+                config,
+                cancellation_flag,
+            ))
+        });
+        match result {
+            Ok(evaluation) => Ok(OnDemandGraph::new(
+                graph,
+                state,
+                evaluation,
+                config.settings.cancellation_token.clone(),
+            )),
+            Err(error) => {
+                if let Some(observer) = config.settings.observer {
+                    observer.on_error(&error);
+                }
+                Err(error)
+            }
         }
     }
 
+    /// Returns the combined query of all stanzas in this file, which parsing the file creates.
+    fn file_query(&self) -> Result<&Query, ExecutionError> {
+        self.query.as_ref().ok_or_else(|| {
+            ExecutionError::MissingQuery(
+                "for the file, which must be parsed before it is executed".to_string(),
+            )
+        })
+    }
+
     pub(self) fn check_globals(&self, globals: &mut Globals) -> Result<(), ExecutionError> {
         for global in &self.globals {
-            match globals.get(&global.name) {
-                None => {
-                    if let Some(default) = &global.default {
-                        globals
-                            .add(global.name.clone(), default.to_string().into())
-                            .map_err(|_| {
-                                ExecutionError::DuplicateVariable(format!(
-                                    "global variable {} already defined",
-                                    global.name
-                                ))
-                            })?;
-                    } else {
-                        return Err(ExecutionError::MissingGlobalVariable(
-                            global.name.as_str().to_string(),
-                        ));
-                    }
+            if !globals.contains(&global.name) {
+                if let Some(default) = &global.default {
+                    globals
+                        .add(global.name.clone(), default.to_string().into())
+                        .map_err(|_| {
+                            ExecutionError::DuplicateVariable(format!(
+                                "global variable {} already defined",
+                                global.name
+                            ))
+                        })?;
+                } else {
+                    return Err(ExecutionError::MissingGlobalVariable(
+                        global.name.as_str().to_string(),
+                    ));
                 }
-                Some(value) => {
-                    if global.quantifier == CaptureQuantifier::ZeroOrMore
-                        || global.quantifier == CaptureQuantifier::OneOrMore
-                    {
-                        if value.as_list().is_err() {
-                            return Err(ExecutionError::ExpectedList(
-                                global.name.as_str().to_string(),
-                            ));
-                        }
-                    }
+            } else if let Some(value) = globals.get_computed(&global.name) {
+                // lazy global variables are not computed just to check them
+                if (global.quantifier == CaptureQuantifier::ZeroOrMore
+                    || global.quantifier == CaptureQuantifier::OneOrMore)
+                    && value.as_list().is_err()
+                {
+                    return Err(ExecutionError::ExpectedList(
+                        global.name.as_str().to_string(),
+                    ));
                 }
             }
         }
@@ -250,12 +448,44 @@ impl<'a, 'tree> Match<'a, 'tree> {
 }
 
 /// Configuration for the execution of a File
+#[derive(Clone)]
 pub struct ExecutionConfig<'a, 'g> {
     pub(crate) functions: &'a Functions,
     pub(crate) globals: &'a Globals<'g>,
+    pub(crate) settings: ExecutionSettings<'a>,
+}
+
+/// The settings of an execution other than its functions and global variables, which, unlike
+/// those, can be shared between threads
+#[derive(Clone)]
+pub(crate) struct ExecutionSettings<'a> {
     pub(crate) lazy: bool,
     pub(crate) location_attr: Option<Identifier>,
     pub(crate) variable_name_attr: Option<Identifier>,
+    pub(crate) random_seed: u64,
+    pub(crate) first_symbol: u32,
+    pub(crate) fixed_time: Option<SystemTime>,
+    pub(crate) allow_exec_capture: bool,
+    pub(crate) attribute_types: HashMap<Identifier, AttributeType>,
+    pub(crate) provenance: bool,
+    pub(crate) parallel: bool,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) resource_limits: ResourceLimits,
+    pub(crate) profile: bool,
+    pub(crate) match_limit: Option<u32>,
+    pub(crate) dry_run: bool,
+    pub(crate) observer: Option<&'a dyn ExecutionObserver>,
+    pub(crate) byte_range: Option<Range<usize>>,
+    pub(crate) injection_anchor_attr: Option<Identifier>,
+    pub(crate) continue_on_error: bool,
+    pub(crate) session: Option<&'a ExecutionSession>,
+    pub(crate) tags: TagFilter,
+    pub(crate) yielding: Yielding,
+    pub(crate) match_predicate: Option<&'a MatchPredicate<'a>>,
+    pub(crate) match_filter: Option<&'a MatchFilter<'a>>,
+    pub(crate) stanza_group: Option<Vec<usize>>,
 }
 
 impl<'a, 'g> ExecutionConfig<'a, 'g> {
@@ -263,33 +493,472 @@ impl<'a, 'g> ExecutionConfig<'a, 'g> {
         Self {
             functions,
             globals,
-            lazy: false,
-            location_attr: None,
-            variable_name_attr: None,
+            settings: ExecutionSettings {
+                lazy: false,
+                location_attr: None,
+                variable_name_attr: None,
+                random_seed: 0,
+                first_symbol: 0,
+                fixed_time: None,
+                allow_exec_capture: false,
+                attribute_types: HashMap::new(),
+                provenance: false,
+                parallel: false,
+                cancellation_token: None,
+                timeout: None,
+                deadline: None,
+                resource_limits: ResourceLimits::default(),
+                profile: false,
+                match_limit: None,
+                dry_run: false,
+                observer: None,
+                byte_range: None,
+                injection_anchor_attr: None,
+                continue_on_error: false,
+                session: None,
+                tags: TagFilter::default(),
+                yielding: Yielding::Never,
+                match_predicate: None,
+                match_filter: None,
+                stanza_group: None,
+            },
         }
     }
 
     pub fn debug_attributes(
-        self,
+        mut self,
         location_attr: Identifier,
         variable_name_attr: Identifier,
     ) -> Self {
-        Self {
-            functions: self.functions,
-            globals: self.globals,
-            lazy: self.lazy,
-            location_attr: location_attr.into(),
-            variable_name_attr: variable_name_attr.into(),
+        self.settings.location_attr = location_attr.into();
+        self.settings.variable_name_attr = variable_name_attr.into();
+        self
+    }
+
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.settings.lazy = lazy;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random number generator used by the
+    /// [`random-int`][`crate::reference::functions#random-int`] function.  Each execution starts
+    /// from this seed, which defaults to 0, so executions are reproducible.
+    pub fn random_seed(mut self, random_seed: u64) -> Self {
+        self.settings.random_seed = random_seed;
+        self
+    }
+
+    /// Sets the number of the first symbol that the
+    /// [`gensym`][`crate::reference::functions#gensym`] function returns.  Each execution starts
+    /// counting from this number, which defaults to 0, so executions are reproducible.
+    pub fn first_symbol(mut self, first_symbol: u32) -> Self {
+        self.settings.first_symbol = first_symbol;
+        self
+    }
+
+    /// Sets a fixed time to be returned by the [`now-unix`][`crate::reference::functions#now-unix`]
+    /// and [`now-iso8601`][`crate::reference::functions#now-iso8601`] functions, instead of the
+    /// time at which execution starts.  This is useful for deterministic tests.
+    pub fn fixed_time(mut self, fixed_time: SystemTime) -> Self {
+        self.settings.fixed_time = Some(fixed_time);
+        self
+    }
+
+    /// Sets whether the [`exec-capture`][`crate::reference::functions#exec-capture`] function is
+    /// allowed to run external processes.  This is disabled by default.
+    #[cfg(feature = "exec-capture")]
+    pub fn allow_exec_capture(mut self, allow_exec_capture: bool) -> Self {
+        self.settings.allow_exec_capture = allow_exec_capture;
+        self
+    }
+
+    /// Sets whether to record which statement created each graph node, edge, and attribute.  See
+    /// [`Graph::node_provenance`][`crate::graph::Graph::node_provenance`] and its siblings.  This
+    /// is disabled by default, since it uses a significant amount of memory for large graphs.
+    pub fn with_provenance(mut self, provenance: bool) -> Self {
+        self.settings.provenance = provenance;
+        self
+    }
+
+    /// Sets whether the execution engines use separate threads.  This is disabled by default.
+    /// Threads are only used with the `parallel` cargo feature.  Without it, the strict engine
+    /// executes the groups of stanzas described below one after the other, with the same result,
+    /// and the lazy engine executes sequentially.
+    ///
+    /// The lazy engine splits the stanza matches into shards of consecutive matches, and builds the
+    /// lazy graph for each shard on a separate thread, when there are enough matches for this to be
+    /// worthwhile.  The shards are merged in match order before the lazy graph is evaluated, so the
+    /// result is the same as that of a sequential execution, except for the values of
+    /// [`gensym`][`crate::reference::functions#gensym`] and
+    /// [`random-int`][`crate::reference::functions#random-int`] calls in conditions and loops,
+    /// which are evaluated while the lazy graph is built, and which draw numbers from a sequence of
+    /// their own for each match, derived from the [seed][`ExecutionConfig::random_seed`] and the
+    /// match, so that they do not depend on how the matches are split.  If a shard fails, the
+    /// matches are executed sequentially instead, so that errors are the same as well.
+    ///
+    /// The strict engine runs independent stanzas on separate threads.  Stanzas are independent if
+    /// they do not share any scoped variable names.  Each group of stanzas that depend on each
+    /// other is executed on its own thread, in the order they appear in the file, into a separate
+    /// graph.  When all groups have finished, their graphs are added to the result in the order of
+    /// each group's first stanza, so the graph nodes that a group creates are numbered after those
+    /// of the groups before it, and otherwise in the order they were created.  The result does not
+    /// depend on how the groups were scheduled, but can differ from that of a sequential execution:
+    ///
+    ///  - Functions that inspect the graph only see the nodes and edges created by their own
+    ///    group, and any that were in the graph before the execution started.
+    ///  - Each group has its own [function state][`crate::functions::FunctionState`], and
+    ///    [`gensym`][`crate::reference::functions#gensym`] symbols interleaved with those of the
+    ///    other groups.
+    ///  - Each match draws [`random-int`][`crate::reference::functions#random-int`] numbers from
+    ///    its own sequence, which is derived from the [seed][`ExecutionConfig::random_seed`], the
+    ///    stanza, and the position of the match among the matches of the stanza.
+    ///  - Output from `print` statements of different groups is interleaved.
+    ///  - If several groups fail, the error of the group whose first stanza comes first is
+    ///    returned, and no results are added to the graph.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.settings.parallel = parallel;
+        self
+    }
+
+    /// Sets a token that cancels the execution when it is cancelled, in addition to the
+    /// cancellation flag that is passed to [`File::execute`] and its siblings.  The token is
+    /// checked before each stanza match is executed, and while statements and values are
+    /// evaluated, so an execution that is no longer needed, for example because the source was
+    /// edited again, stops soon after the token is cancelled, with [`ExecutionError::Cancelled`].
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.settings.cancellation_token = Some(token);
+        self
+    }
+
+    /// Sets how long an execution may take.  The time is checked before each stanza match is
+    /// executed, before each statement is executed, while `scan` statements match their regular
+    /// expressions, and while lazy variables are forced.  An execution that takes longer fails
+    /// with [`ExecutionError::TimedOut`], in the context of the statement that was being executed
+    /// when it was checked, if there was one.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.timeout = Some(timeout);
+        self.settings.deadline = None;
+        self
+    }
+
+    /// Sets limits on how much an execution can add to the graph.  There are no limits by
+    /// default.  See [`ResourceLimits`] for what each of them counts.
+    pub fn resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.settings.resource_limits = resource_limits;
+        self
+    }
+
+    /// Sets whether to collect statistics about the work done for each stanza: how many matches it
+    /// was executed for, how long finding and executing them took, and how many graph nodes and
+    /// edges they created.  Use [`ExecutionReport::profile`] to get the statistics after the
+    /// execution.  This is disabled by default.
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.settings.profile = profile;
+        self
+    }
+
+    /// Sets the maximum number of matches that the query of each stanza can have in progress at a
+    /// time, for the stanzas that do not set their own limit with a `@match-limit` annotation.
+    /// Queries that exceed the limit drop their oldest in-progress matches, so that pathological
+    /// source files cannot make them use unbounded time and memory, and record an
+    /// [`ExecutionWarning::MatchLimitExceeded`][`crate::ExecutionWarning::MatchLimitExceeded`]
+    /// warning in the report of the execution, because some matches may be missing.  The limit is
+    /// clamped to the range that tree-sitter supports, from 1 to 65536.
+    ///
+    /// With lazy evaluation, the stanzas share a single query, whose limit is the smallest of
+    /// this limit and the limits of the stanzas.
+    pub fn match_limit(mut self, match_limit: u32) -> Self {
+        self.settings.match_limit = Some(match_limit.clamp(1, MAX_MATCH_LIMIT));
+        self
+    }
+
+    /// Sets whether the execution is a dry run, which validates the rules against a source file
+    /// without the memory cost of building its graph.  A dry run matches the stanza queries and
+    /// evaluates their statements as usual, and fails with the same errors, but does not add the
+    /// graph nodes, edges, and attributes that it creates to the graph.  Instead,
+    /// [`ExecutionReport::dry_run`] returns how many of them the execution would have added.
+    ///
+    /// If the file calls a function that
+    /// [reads the graph][`crate::functions::Function::reads_graph`], such as
+    /// [`get-attr`][`crate::reference::functions#get-attr`], the dry run adds to the graph as
+    /// usual, so that the function sees the values that it would in a normal execution.  A dry
+    /// run that does not add to the graph is not run in
+    /// [parallel][`ExecutionConfig::parallel`] and does not record
+    /// [provenance][`ExecutionConfig::with_provenance`].  On-demand executions ignore this
+    /// setting, because they need the graph to evaluate the parts that are asked for.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.settings.dry_run = dry_run;
+        self
+    }
+
+    /// Sets an observer that is told about the progress of the execution: which stanzas and
+    /// matches are executed, which graph nodes and attributes are created, and whether the
+    /// execution fails.  See [`ExecutionObserver`] for when each of its methods is called.
+    pub fn observer(mut self, observer: &'a dyn ExecutionObserver) -> Self {
+        self.settings.observer = Some(observer);
+        self
+    }
+
+    /// Sets a predicate that decides which query matches to execute stanzas for.  Matches that
+    /// the predicate rejects are skipped before any statements run, which is useful to ignore
+    /// parts of the source file, such as regions of generated code, without changing every
+    /// stanza.
+    ///
+    /// Stanzas are usually matched with a single query that combines the queries of all stanzas,
+    /// but stanzas with a match limit are matched with their own query, so the pattern and
+    /// capture indices of a match are not those of any particular query.  The predicate should
+    /// look at the syntax nodes that were captured instead.
+    pub fn filter_matches(mut self, predicate: &'a MatchPredicate<'a>) -> Self {
+        self.settings.match_predicate = Some(predicate);
+        self
+    }
+
+    /// Restricts the execution to a range of bytes of the source file, so that executing a file
+    /// for a small part of a large source file, such as the function under an editor's cursor, is
+    /// cheap.  The stanzas are only executed for the matches of their queries that intersect the
+    /// range, which tree-sitter finds without visiting the rest of the syntax tree.  By default,
+    /// the whole source file is used.
+    pub fn within_byte_range(mut self, byte_range: Range<usize>) -> Self {
+        self.settings.byte_range = Some(byte_range);
+        self
+    }
+
+    /// Sets the attribute that marks the graph nodes that anchor the edges between the graphs of
+    /// a source file and of the regions of it that are written in other languages, when it is
+    /// executed with [`File::execute_with_injections`].  See [`Injection`] for how the anchors
+    /// are connected.  By default, no edges are added between them.
+    pub fn injection_anchors(mut self, anchor_attr: Identifier) -> Self {
+        self.settings.injection_anchor_attr = Some(anchor_attr);
+        self
+    }
+
+    /// Sets whether the execution continues when executing a stanza for a match fails.  If so, the
+    /// error is recorded in the report of the execution, where [`ExecutionReport::errors`] returns
+    /// it, with the context of the statement that failed, and the rest of the stanza's statements
+    /// are skipped for that match.  The graph nodes, edges, and attributes that the stanza added
+    /// before the statement that failed are kept.  With lazy evaluation, statements are skipped
+    /// individually, when evaluating them fails.  Cancellation, timeouts, and exceeded resource
+    /// limits still stop the execution.  This is disabled by default.
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.settings.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Sets the session that the execution reuses query cursors, regular expressions, and
+    /// interned strings from, and keeps those it creates in.  See [`ExecutionSession`] for what a
+    /// session keeps.  Using the same session for the executions of many source files saves the
+    /// work of creating them again for each file.  By default, executions do not use a session.
+    pub fn session(mut self, session: &'a ExecutionSession) -> Self {
+        self.settings.session = Some(session);
+        self
+    }
+
+    /// Selects the stanzas to execute by the tags that their `@tag` annotations give them, so that
+    /// a graph DSL file can serve several purposes, such as a quick outline of a source file and a
+    /// deeper analysis of it.  A stanza with tags is executed if it has one of the `include` tags,
+    /// or `include` is empty, and none of the `exclude` tags.  Stanzas without tags are always
+    /// executed.  By default, all stanzas are executed.
+    pub fn with_tags(mut self, include: &[&str], exclude: &[&str]) -> Self {
+        self.settings.tags = TagFilter {
+            include: include.iter().map(|tag| tag.to_string()).collect(),
+            exclude: exclude.iter().map(|tag| tag.to_string()).collect(),
+        };
+        self
+    }
+
+    /// Records the error of a failed stanza match or statement in the state of the execution, and
+    /// returns `Ok`, if the execution continues on errors and the error does not stop it.
+    pub(crate) fn recover(
+        &self,
+        state: &mut ExecutionState,
+        result: Result<(), ExecutionError>,
+    ) -> Result<(), ExecutionError> {
+        recover(
+            state,
+            self.settings.observer,
+            self.settings.continue_on_error,
+            result,
+        )
+    }
+
+    /// Returns a cursor for executing a query with this configuration, which has the given match
+    /// limit, and only finds matches within the configured byte range.  The cursor is taken from
+    /// the configured session, if any, so its settings are reset to those of a new cursor first.
+    pub(crate) fn query_cursor(&self, match_limit: Option<u32>) -> SessionCursor<'a> {
+        let mut cursor = match self.settings.session {
+            Some(session) => session.cursor(),
+            None => SessionCursor::new(),
+        };
+        cursor.set_match_limit(match_limit.unwrap_or(u32::MAX));
+        cursor.set_byte_range(self.settings.byte_range.clone().unwrap_or(0..usize::MAX));
+        cursor
+    }
+
+    /// Returns whether to execute the stanzas for a query match, according to the configured
+    /// match predicate, if any.
+    pub(crate) fn selects_match(&self, mat: &QueryMatch) -> bool {
+        match self.settings.match_predicate {
+            Some(predicate) => predicate(mat),
+            None => true,
         }
     }
 
-    pub fn lazy(self, lazy: bool) -> Self {
-        Self {
-            functions: self.functions,
-            globals: self.globals,
-            lazy,
-            location_attr: self.location_attr,
-            variable_name_attr: self.variable_name_attr,
+    /// Returns whether to execute the stanza with the given index, according to the configured
+    /// tags, and to the group of stanzas that a parallel execution runs on this thread, if any.
+    pub(crate) fn selects_stanza(&self, index: usize, stanza: &Stanza) -> bool {
+        self.settings.tags.selects(stanza)
+            && match &self.settings.stanza_group {
+                Some(group) => group.contains(&index),
+                None => true,
+            }
+    }
+
+    /// Returns whether to execute `stanza` for a match whose whole stanza query matched `node`,
+    /// according to the configured tags, and to the filter of an incremental execution, if any.
+    pub(crate) fn selects_node(&self, stanza: &Stanza, node: Node) -> bool {
+        self.settings.tags.selects(stanza)
+            && match self.settings.match_filter {
+                Some(filter) => filter(stanza, node),
+                None => true,
+            }
+    }
+
+    /// Returns when an execution with this configuration that starts now times out.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.settings.deadline.or_else(|| {
+            self.settings
+                .timeout
+                .map(|timeout| Instant::now() + timeout)
+        })
+    }
+
+    /// Requires all values of the attribute `name`, of both graph nodes and edges, to have the
+    /// given type.  Executing an `attr` statement that sets the attribute to a value of another
+    /// type fails.
+    pub fn attribute_type(mut self, name: Identifier, attribute_type: AttributeType) -> Self {
+        self.settings.attribute_types.insert(name, attribute_type);
+        self
+    }
+
+    /// Checks that a value conforms to the type registered for an attribute, if there is one.
+    pub(crate) fn check_attribute_type(
+        attribute_types: &HashMap<Identifier, AttributeType>,
+        name: &Identifier,
+        value: &Value,
+    ) -> Result<(), ExecutionError> {
+        match attribute_types.get(name) {
+            Some(expected) if !expected.matches(value) => {
+                Err(ExecutionError::InvalidAttributeType(
+                    name.to_string(),
+                    expected.to_string(),
+                    AttributeType::of(value).to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The type of value that an attribute is required to have.  See
+/// [`ExecutionConfig::attribute_type`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AttributeType {
+    /// Any value
+    Any,
+    /// The null value
+    Null,
+    /// A boolean
+    Boolean,
+    /// An integer
+    Integer,
+    /// A string
+    String,
+    /// A syntax node
+    SyntaxNode,
+    /// A graph node
+    GraphNode,
+    /// A function
+    Function,
+    /// A list whose elements all have the given type
+    List(Box<AttributeType>),
+    /// A set whose elements all have the given type
+    Set(Box<AttributeType>),
+    /// A map whose values all have the given type
+    Map(Box<AttributeType>),
+    /// A source span
+    Span,
+}
+
+impl AttributeType {
+    /// Returns whether a value has this type.
+    pub fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::List(element), Value::List(values)) => values.iter().all(|v| element.matches(v)),
+            (Self::Set(element), Value::Set(values)) => values.iter().all(|v| element.matches(v)),
+            (Self::Map(element), Value::Map(values)) => values.values().all(|v| element.matches(v)),
+            _ => matches!(
+                (self, value),
+                (Self::Any, _)
+                    | (Self::Null, Value::Null)
+                    | (Self::Boolean, Value::Boolean(_))
+                    | (Self::Integer, Value::Integer(_))
+                    | (Self::String, Value::String(_))
+                    | (Self::SyntaxNode, Value::SyntaxNode(_))
+                    | (Self::GraphNode, Value::GraphNode(_))
+                    | (Self::Function, Value::Function(_))
+                    | (Self::Span, Value::Span { .. })
+            ),
+        }
+    }
+
+    /// Returns the most specific type of a value.  The element type of a list, set, or map is only
+    /// known if all of its elements have the same type.
+    pub fn of(value: &Value) -> AttributeType {
+        fn element_type<'a>(mut values: impl Iterator<Item = &'a Value>) -> AttributeType {
+            let first = match values.next() {
+                Some(first) => AttributeType::of(first),
+                None => return AttributeType::Any,
+            };
+            if values.all(|value| AttributeType::of(value) == first) {
+                first
+            } else {
+                AttributeType::Any
+            }
+        }
+        match value {
+            Value::Null => Self::Null,
+            Value::Boolean(_) => Self::Boolean,
+            Value::Integer(_) => Self::Integer,
+            Value::String(_) => Self::String,
+            Value::List(values) => Self::List(Box::new(element_type(values.iter()))),
+            Value::Set(values) => Self::Set(Box::new(element_type(values.iter()))),
+            Value::Map(values) => Self::Map(Box::new(element_type(values.values()))),
+            Value::SyntaxNode(_) => Self::SyntaxNode,
+            Value::GraphNode(_) => Self::GraphNode,
+            Value::Function(_) => Self::Function,
+            Value::Span { .. } => Self::Span,
+        }
+    }
+}
+
+impl fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Null => write!(f, "null"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Integer => write!(f, "integer"),
+            Self::String => write!(f, "string"),
+            Self::SyntaxNode => write!(f, "syntax-node"),
+            Self::GraphNode => write!(f, "graph-node"),
+            Self::Function => write!(f, "function"),
+            Self::List(element) if **element == Self::Any => write!(f, "list"),
+            Self::List(element) => write!(f, "list-of-{}", element),
+            Self::Set(element) if **element == Self::Any => write!(f, "set"),
+            Self::Set(element) => write!(f, "set-of-{}", element),
+            Self::Map(element) if **element == Self::Any => write!(f, "map"),
+            Self::Map(element) => write!(f, "map-of-{}", element),
+            Self::Span => write!(f, "span"),
         }
     }
 }
@@ -306,10 +975,98 @@ impl CancellationFlag for NoCancellation {
     }
 }
 
+/// A cancellation flag that can be cancelled from another thread.  Clones of a token share its
+/// state, so a host can keep a clone, and cancel it when the execution is no longer needed.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the executions that check this token, or any of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl CancellationFlag for CancellationToken {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.is_cancelled() {
+            return Err(CancellationError(at));
+        }
+        Ok(())
+    }
+}
+
+/// The cancellation flag of an execution whose config has a cancellation token.
+struct TokenCancellation<'a> {
+    flag: &'a dyn CancellationFlag,
+    token: &'a CancellationToken,
+}
+
+impl CancellationFlag for TokenCancellation<'_> {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        self.token.check(at)?;
+        self.flag.check(at)
+    }
+}
+
+/// Calls `f` with a cancellation flag that is set when either `cancellation_flag` or `token`,
+/// if there is one, is.
+pub(crate) fn with_cancellation_token<R>(
+    token: Option<&CancellationToken>,
+    cancellation_flag: &dyn CancellationFlag,
+    f: impl FnOnce(&dyn CancellationFlag) -> R,
+) -> R {
+    match token {
+        Some(token) => f(&TokenCancellation {
+            flag: cancellation_flag,
+            token,
+        }),
+        None => f(cancellation_flag),
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("Cancelled at \"{0}\"")]
 pub struct CancellationError(pub &'static str);
 
+/// Numbers the matches of a query by their position among the matches of the same pattern, which
+/// identifies them independently of the matches of the other patterns.  See
+/// [`ExecutionState::start_match`].
+pub(crate) fn number_matches<'cursor, 'tree: 'cursor>(
+    matches: impl Iterator<Item = QueryMatch<'cursor, 'tree>>,
+) -> impl Iterator<Item = (usize, QueryMatch<'cursor, 'tree>)> {
+    let mut counts = Vec::new();
+    matches.map(move |mat| {
+        if counts.len() <= mat.pattern_index {
+            counts.resize(mat.pattern_index + 1, 0);
+        }
+        let ordinal = counts[mat.pattern_index];
+        counts[mat.pattern_index] += 1;
+        (ordinal, mat)
+    })
+}
+
+/// Fails with [`ExecutionError::TimedOut`] if `deadline` has passed.
+pub(crate) fn check_deadline(
+    deadline: Option<Instant>,
+    at: &'static str,
+) -> Result<(), ExecutionError> {
+    match deadline {
+        Some(deadline) if Instant::now() >= deadline => {
+            Err(ExecutionError::TimedOut(format!("while {}", at)))
+        }
+        _ => Ok(()),
+    }
+}
+
 impl Value {
     pub fn from_nodes<'tree, NI: IntoIterator<Item = Node<'tree>>>(
         graph: &mut Graph<'tree>,
@@ -346,7 +1103,7 @@ impl CreateEdge {
         attributes: &mut Attributes,
         config: &ExecutionConfig,
     ) -> Result<(), ExecutionError> {
-        if let Some(location_attr) = &config.location_attr {
+        if let Some(location_attr) = &config.settings.location_attr {
             attributes
                 .add(
                     location_attr.clone(),
@@ -367,14 +1124,14 @@ impl Variable {
         attributes: &mut Attributes,
         config: &ExecutionConfig,
     ) -> Result<(), ExecutionError> {
-        if let Some(variable_name_attr) = &config.variable_name_attr {
+        if let Some(variable_name_attr) = &config.settings.variable_name_attr {
             attributes
                 .add(variable_name_attr.clone(), format!("{}", self))
                 .map_err(|_| {
                     ExecutionError::DuplicateAttribute(variable_name_attr.as_str().into())
                 })?;
         }
-        if let Some(location_attr) = &config.location_attr {
+        if let Some(location_attr) = &config.settings.location_attr {
             let location = match &self {
                 Variable::Scoped(v) => v.location,
                 Variable::Unscoped(v) => v.location,
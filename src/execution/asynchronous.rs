@@ -0,0 +1,61 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Executes graph DSL files as futures, which yield between stanza matches
+
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionReport;
+use crate::execution::Yielding;
+use crate::graph::Graph;
+
+impl File {
+    /// Executes this graph DSL file against a source file, like [`File::execute`], but as a
+    /// future, which yields to the executor after each stanza match that it executes, and, with
+    /// lazy evaluation, after each statement of the lazy graph that it evaluates.  Long executions
+    /// can then share a thread with other tasks, and stop at the next match when the future is
+    /// dropped.  The future does not depend on a particular async runtime.
+    ///
+    /// The future refers to the global variables of `config`, which cannot be shared between
+    /// threads, so it is not `Send`.  With Tokio, run it on a `LocalSet`, for instance.  The
+    /// `parallel` setting of `config` is ignored, because executing on other threads would block
+    /// the executor.
+    pub async fn execute_async<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'_, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let mut graph = Graph::new();
+        self.execute_into_async(&mut graph, tree, source, config, cancellation_flag)
+            .await?;
+        Ok(graph)
+    }
+
+    /// Executes this graph DSL file against a source file, saving the results into an existing
+    /// `Graph` instance, like [`File::execute_into`], but as a future, like
+    /// [`File::execute_async`].  If the future is dropped before it is done, `graph` keeps the
+    /// results of the stanza matches that were executed.
+    pub async fn execute_into_async<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'_, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<ExecutionReport, ExecutionError> {
+        let mut config = config.clone().parallel(false);
+        config.settings.yielding = Yielding::BetweenMatches;
+        self.execute_yielding_into(graph, tree, source, &config, cancellation_flag)
+            .await
+    }
+}
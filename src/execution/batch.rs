@@ -0,0 +1,85 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Executes graph DSL files against many source files, into a single graph
+
+use std::path::Path;
+
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::Context;
+use crate::execution::error::ExecutionError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionReport;
+use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::graph::Value;
+use crate::variables::Globals;
+use crate::Identifier;
+
+/// The name of the global variable that holds the root graph node of each source file, when
+/// executing a graph DSL file with [`File::execute_files_into`]
+pub const ROOT_VARIABLE: &str = "ROOT";
+
+impl File {
+    /// Executes this graph DSL file against each of the given source files in turn, adding the
+    /// results to a single existing `Graph` instance.  Each source file is given by its path, its
+    /// source text, and the syntax tree that was parsed from it, which must all outlive the graph.
+    /// The files are executed as the iterator yields them, so they can be parsed on the fly.
+    ///
+    /// A new graph node is created for each source file, before executing the stanzas for it, and
+    /// is given to them as the value of the global variable named [`ROOT_VARIABLE`], which the
+    /// graph DSL file can declare to connect the graph of each file to the rest.  Returns these
+    /// root nodes, in the order of the source files, and the combined report of the executions.
+    /// The other global variables of `config` are
+    /// given to the stanzas of each file.  The timeout of `config`, if any, applies to each source
+    /// file separately.
+    ///
+    /// Stops at the first source file that fails, and returns its error, with the path of the file
+    /// as context.  The graph keeps the results of the source files before it.
+    pub fn execute_files_into<'tree, P, I>(
+        &self,
+        graph: &mut Graph<'tree>,
+        files: I,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(Vec<GraphNodeRef>, ExecutionReport), ExecutionError>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (P, &'tree str, &'tree Tree)>,
+    {
+        let mut roots = Vec::new();
+        let mut report = ExecutionReport::default();
+        for (path, source, tree) in files {
+            let root = graph.add_graph_node();
+            roots.push(root);
+            // the root node shadows a global variable of the same name in `config`
+            let mut globals = Globals::nested(config.globals);
+            let _ = globals.add(Identifier::from(ROOT_VARIABLE), Value::GraphNode(root));
+            let config = ExecutionConfig {
+                globals: &globals,
+                ..config.clone()
+            };
+            let file_report = self
+                .execute_into(graph, tree, source, &config, cancellation_flag)
+                .map_err(|error| match error {
+                    cancelled @ ExecutionError::Cancelled(_) => cancelled,
+                    error => ExecutionError::InContext(
+                        Context::Other(format!(
+                            "Error executing source file {}",
+                            path.as_ref().display()
+                        )),
+                        Box::new(error),
+                    ),
+                })?;
+            report.extend(file_report);
+        }
+        Ok((roots, report))
+    }
+}
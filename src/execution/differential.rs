@@ -0,0 +1,366 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Compares the graphs that the strict and lazy execution engines build
+
+use std::collections::HashMap;
+use std::fmt;
+
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::graph::StoredAttributes;
+use crate::graph::Value;
+use crate::Identifier;
+
+/// One of the execution engines
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Engine {
+    Strict,
+    Lazy,
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Engine::Strict => write!(f, "strict"),
+            Engine::Lazy => write!(f, "lazy"),
+        }
+    }
+}
+
+/// A difference between the graphs that the strict and lazy execution engines build for the same
+/// inputs, which [`File::compare_engines`] returns.  Graph nodes are described by the statement
+/// that created them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EngineDifference {
+    /// Only one of the engines failed, with the given error
+    Failed { engine: Engine, error: String },
+    /// Only one of the engines created a graph node
+    Node { engine: Engine, node: String },
+    /// Only one of the engines created an edge
+    Edge {
+        engine: Engine,
+        source: String,
+        sink: String,
+    },
+    /// The engines set an attribute of a graph node to different values, or only one of them set
+    /// it
+    NodeAttribute {
+        node: String,
+        name: Identifier,
+        strict: Option<String>,
+        lazy: Option<String>,
+    },
+    /// The engines set an attribute of an edge to different values, or only one of them set it
+    EdgeAttribute {
+        source: String,
+        sink: String,
+        name: Identifier,
+        strict: Option<String>,
+        lazy: Option<String>,
+    },
+}
+
+impl fmt::Display for EngineDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = |value: &Option<String>| match value {
+            Some(value) => value.clone(),
+            None => "nothing".to_string(),
+        };
+        match self {
+            Self::Failed { engine, error } => {
+                write!(f, "Only the {} engine failed: {}", engine, error)
+            }
+            Self::Node { engine, node } => {
+                write!(f, "Only the {} engine created {}", engine, node)
+            }
+            Self::Edge {
+                engine,
+                source,
+                sink,
+            } => write!(
+                f,
+                "Only the {} engine created an edge from {} to {}",
+                engine, source, sink
+            ),
+            Self::NodeAttribute {
+                node,
+                name,
+                strict,
+                lazy,
+            } => write!(
+                f,
+                "Attribute {} of {} is {} with the strict engine and {} with the lazy engine",
+                name,
+                node,
+                value(strict),
+                value(lazy)
+            ),
+            Self::EdgeAttribute {
+                source,
+                sink,
+                name,
+                strict,
+                lazy,
+            } => write!(
+                f,
+                "Attribute {} of the edge from {} to {} is {} with the strict engine and {} with \
+                 the lazy engine",
+                name,
+                source,
+                sink,
+                value(strict),
+                value(lazy)
+            ),
+        }
+    }
+}
+
+impl File {
+    /// Executes this graph DSL file against a source file with both the strict and the lazy
+    /// execution engine, and returns the differences between the graphs that they build, which
+    /// is empty if the engines agree.  This is meant for tests, of graph DSL files and of this
+    /// library, that check that a file does not depend on which engine executes it.
+    ///
+    /// The `lazy` setting of `config` is ignored, and provenance is recorded, because the graph
+    /// nodes of the two graphs are matched up by the statement and the stanza match that created
+    /// them.  The differences that the engines are documented to have are not reported:
+    ///
+    ///  - the graph nodes and syntax nodes of the graphs are numbered differently, because the
+    ///    lazy engine executes the matches of all stanzas in the order that the file query finds
+    ///    them, instead of executing the stanzas in turn, and
+    ///  - the elements of sets that contain graph nodes can be in a different order.
+    ///
+    /// Graph nodes that were not created by a `node` statement, such as those that the
+    /// [`node`][`crate::reference::functions#node`] function creates, are matched up in the order
+    /// they were created, which can differ between the engines.  If both engines fail, the error
+    /// of the strict engine is returned.
+    pub fn compare_engines<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<EngineDifference>, ExecutionError> {
+        let execute = |lazy| {
+            let config = config.clone().lazy(lazy).with_provenance(true);
+            self.execute(tree, source, &config, cancellation_flag)
+        };
+        match (execute(false), execute(true)) {
+            (Ok(strict), Ok(lazy)) => Ok(GraphComparison::new(&strict, &lazy).differences()),
+            (Err(error), Ok(_)) => Ok(vec![EngineDifference::Failed {
+                engine: Engine::Strict,
+                error: error.to_string(),
+            }]),
+            (Ok(_), Err(error)) => Ok(vec![EngineDifference::Failed {
+                engine: Engine::Lazy,
+                error: error.to_string(),
+            }]),
+            (Err(error), Err(_)) => Err(error),
+        }
+    }
+}
+
+/// The graphs that the strict and lazy engines built, and which of their graph nodes correspond
+struct GraphComparison<'a, 'tree> {
+    strict: &'a Graph<'tree>,
+    lazy: &'a Graph<'tree>,
+    /// The node of the lazy graph that each node of the strict graph corresponds to, if any
+    to_lazy: Vec<Option<GraphNodeRef>>,
+    /// The node of the strict graph that each node of the lazy graph corresponds to, if any
+    to_strict: Vec<Option<GraphNodeRef>>,
+}
+
+impl<'a, 'tree> GraphComparison<'a, 'tree> {
+    /// Matches up the graph nodes of the graphs by the statement and stanza match that created
+    /// them, and then by the order in which they were created.
+    fn new(strict: &'a Graph<'tree>, lazy: &'a Graph<'tree>) -> Self {
+        let mut lazy_nodes = HashMap::new();
+        for node in lazy.iter_nodes() {
+            lazy_nodes
+                .entry(node_key(lazy, node))
+                .or_insert_with(Vec::new)
+                .push(node);
+        }
+        let mut lazy_nodes = lazy_nodes
+            .into_iter()
+            .map(|(key, nodes)| (key, nodes.into_iter()))
+            .collect::<HashMap<_, _>>();
+        let mut to_lazy = vec![None; strict.node_count()];
+        let mut to_strict = vec![None; lazy.node_count()];
+        for node in strict.iter_nodes() {
+            let lazy_node = lazy_nodes
+                .get_mut(&node_key(strict, node))
+                .and_then(|nodes| nodes.next());
+            if let Some(lazy_node) = lazy_node {
+                to_lazy[node.index()] = Some(lazy_node);
+                to_strict[lazy_node.index()] = Some(node);
+            }
+        }
+        Self {
+            strict,
+            lazy,
+            to_lazy,
+            to_strict,
+        }
+    }
+
+    fn differences(&self) -> Vec<EngineDifference> {
+        let mut differences = Vec::new();
+        for node in self.strict.iter_nodes() {
+            let lazy_node = match self.to_lazy[node.index()] {
+                Some(lazy_node) => lazy_node,
+                None => {
+                    differences.push(EngineDifference::Node {
+                        engine: Engine::Strict,
+                        node: describe(self.strict, node),
+                    });
+                    continue;
+                }
+            };
+            for (name, strict, lazy) in self.compare_attributes(
+                self.strict.node(node).attributes(),
+                self.lazy.node(lazy_node).attributes(),
+            ) {
+                differences.push(EngineDifference::NodeAttribute {
+                    node: describe(self.strict, node),
+                    name,
+                    strict,
+                    lazy,
+                });
+            }
+            for (sink, edge) in self.strict.node(node).iter_edges() {
+                let lazy_edge = (self.to_lazy[sink.index()])
+                    .and_then(|lazy_sink| self.lazy.node(lazy_node).get_edge(lazy_sink));
+                let lazy_edge = match lazy_edge {
+                    Some(lazy_edge) => lazy_edge,
+                    None => {
+                        differences.push(EngineDifference::Edge {
+                            engine: Engine::Strict,
+                            source: describe(self.strict, node),
+                            sink: describe(self.strict, sink),
+                        });
+                        continue;
+                    }
+                };
+                for (name, strict, lazy) in
+                    self.compare_attributes(edge.attributes(), lazy_edge.attributes())
+                {
+                    differences.push(EngineDifference::EdgeAttribute {
+                        source: describe(self.strict, node),
+                        sink: describe(self.strict, sink),
+                        name,
+                        strict,
+                        lazy,
+                    });
+                }
+            }
+        }
+        for node in self.lazy.iter_nodes() {
+            let strict_node = match self.to_strict[node.index()] {
+                Some(strict_node) => strict_node,
+                None => {
+                    differences.push(EngineDifference::Node {
+                        engine: Engine::Lazy,
+                        node: describe(self.lazy, node),
+                    });
+                    continue;
+                }
+            };
+            for (sink, _) in self.lazy.node(node).iter_edges() {
+                let strict_edge = (self.to_strict[sink.index()])
+                    .and_then(|strict_sink| self.strict.node(strict_node).get_edge(strict_sink));
+                if strict_edge.is_none() {
+                    differences.push(EngineDifference::Edge {
+                        engine: Engine::Lazy,
+                        source: describe(self.lazy, node),
+                        sink: describe(self.lazy, sink),
+                    });
+                }
+            }
+        }
+        differences
+    }
+
+    /// Returns the name and the values of each attribute that differs between the attributes of
+    /// corresponding nodes or edges.
+    fn compare_attributes(
+        &self,
+        strict: StoredAttributes,
+        lazy: StoredAttributes,
+    ) -> Vec<(Identifier, Option<String>, Option<String>)> {
+        let mut differences = Vec::new();
+        for (name, strict_value) in strict.iter() {
+            match lazy.get(name) {
+                Some(lazy_value) if self.values_match(strict_value, lazy_value) => {}
+                lazy_value => differences.push((
+                    name.clone(),
+                    Some(strict_value.to_string()),
+                    lazy_value.map(|value| value.to_string()),
+                )),
+            }
+        }
+        for (name, lazy_value) in lazy.iter() {
+            if strict.get(name).is_none() {
+                differences.push((name.clone(), None, Some(lazy_value.to_string())));
+            }
+        }
+        differences
+    }
+
+    /// Returns whether a value of the strict graph is the same as a value of the lazy graph, once
+    /// the nodes they refer to are matched up.
+    fn values_match(&self, strict: &Value, lazy: &Value) -> bool {
+        match (strict, lazy) {
+            (Value::GraphNode(strict), Value::GraphNode(lazy)) => {
+                self.to_lazy[strict.index()] == Some(*lazy)
+            }
+            (Value::SyntaxNode(strict), Value::SyntaxNode(lazy)) => {
+                self.strict[*strict] == self.lazy[*lazy]
+            }
+            (Value::List(strict), Value::List(lazy)) => {
+                strict.len() == lazy.len()
+                    && (strict.iter().zip(lazy))
+                        .all(|(strict, lazy)| self.values_match(strict, lazy))
+            }
+            (Value::Set(strict), Value::Set(lazy)) => {
+                strict.len() == lazy.len()
+                    && strict
+                        .iter()
+                        .all(|strict| lazy.iter().any(|lazy| self.values_match(strict, lazy)))
+            }
+            (Value::Map(strict), Value::Map(lazy)) => {
+                strict.len() == lazy.len()
+                    && strict.iter().all(|(key, strict)| {
+                        (lazy.get(key)).is_some_and(|lazy| self.values_match(strict, lazy))
+                    })
+            }
+            _ => strict == lazy,
+        }
+    }
+}
+
+/// Returns what the graph nodes of the strict and lazy graphs are matched up by: the statement
+/// that created the node, and the stanza match that it was executed for, if known.
+fn node_key(graph: &Graph, node: GraphNodeRef) -> Option<String> {
+    graph
+        .node_provenance(node)
+        .map(|provenance| provenance.to_string())
+}
+
+fn describe(graph: &Graph, node: GraphNodeRef) -> String {
+    match graph.node_provenance(node) {
+        Some(provenance) => format!("{} created by {}", node, provenance),
+        None => format!("{}", node),
+    }
+}
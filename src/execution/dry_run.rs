@@ -0,0 +1,234 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Records what a dry run would add to a graph
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use crate::execution::limits::value_bytes;
+use crate::graph::Attributes;
+use crate::graph::Graph;
+use crate::graph::GraphNode;
+use crate::graph::GraphNodeRef;
+use crate::graph::Value;
+use crate::Identifier;
+
+/// What a dry run would have added to the graph.  See
+/// [`ExecutionConfig::dry_run`][`crate::ExecutionConfig::dry_run`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DryRunStats {
+    /// The number of graph nodes
+    pub node_count: usize,
+    /// The number of edges
+    pub edge_count: usize,
+    /// The number of attributes on all nodes
+    pub node_attribute_count: usize,
+    /// The number of attributes on all edges
+    pub edge_attribute_count: usize,
+    /// The number of nodes that have each attribute
+    pub node_attribute_names: BTreeMap<Identifier, usize>,
+    /// The number of edges that have each attribute
+    pub edge_attribute_names: BTreeMap<Identifier, usize>,
+    /// A rough estimate of the memory that the attribute values would have used, in bytes
+    pub attribute_bytes: usize,
+}
+
+impl DryRunStats {
+    /// Adds the statistics of another dry run to these.
+    pub(crate) fn extend(&mut self, other: &DryRunStats) {
+        self.node_count += other.node_count;
+        self.edge_count += other.edge_count;
+        self.node_attribute_count += other.node_attribute_count;
+        self.edge_attribute_count += other.edge_attribute_count;
+        for (name, count) in &other.node_attribute_names {
+            *self.node_attribute_names.entry(name.clone()).or_default() += count;
+        }
+        for (name, count) in &other.edge_attribute_names {
+            *self.edge_attribute_names.entry(name.clone()).or_default() += count;
+        }
+        self.attribute_bytes += other.attribute_bytes;
+    }
+
+    fn add_node_attribute(&mut self, name: &Identifier, value: &Value) {
+        self.node_attribute_count += 1;
+        *self.node_attribute_names.entry(name.clone()).or_default() += 1;
+        self.attribute_bytes += value_bytes(value);
+    }
+
+    fn add_edge_attribute(&mut self, name: &Identifier, value: &Value) {
+        self.edge_attribute_count += 1;
+        *self.edge_attribute_names.entry(name.clone()).or_default() += 1;
+        self.attribute_bytes += value_bytes(value);
+    }
+}
+
+/// Counts what a dry run adds to the graph.  If the graph DSL file calls a function that reads
+/// the graph, the dry run writes to the graph as usual, so that the function sees the same graph as
+/// it would in a normal execution.  Otherwise, the graph is left as it was, and the dry run keeps
+/// track of the graph nodes, edges, and attributes that it would have added, so that it fails
+/// when a normal execution would.  The graph nodes that it creates are numbered after the nodes
+/// of the graph.
+pub(crate) struct DryRun {
+    writes_graph: bool,
+    stats: DryRunStats,
+    nodes: usize,
+    edges: HashSet<(GraphNodeRef, GraphNodeRef)>,
+    node_attributes: HashSet<(GraphNodeRef, Identifier)>,
+    edge_attributes: HashSet<(GraphNodeRef, GraphNodeRef, Identifier)>,
+}
+
+impl DryRun {
+    pub(crate) fn new(writes_graph: bool) -> Self {
+        Self {
+            writes_graph,
+            stats: DryRunStats::default(),
+            nodes: 0,
+            edges: HashSet::new(),
+            node_attributes: HashSet::new(),
+            edge_attributes: HashSet::new(),
+        }
+    }
+
+    /// Returns whether the dry run writes to the graph.
+    pub(crate) fn writes_graph(&self) -> bool {
+        self.writes_graph
+    }
+
+    /// Returns the dry run of a part of the execution that is run on another thread, into a fork
+    /// of the graph.  Only dry runs that write to the graph are run in parallel.
+    pub(crate) fn fork(&self) -> Self {
+        debug_assert!(
+            self.writes_graph,
+            "dry run without a graph cannot be forked"
+        );
+        Self::new(self.writes_graph)
+    }
+
+    /// Adds what a dry run returned by [`DryRun::fork`] counted to this one.
+    pub(crate) fn join(&mut self, fork: &DryRun) {
+        self.stats.extend(&fork.stats);
+    }
+
+    pub(crate) fn into_stats(self) -> DryRunStats {
+        self.stats
+    }
+
+    /// Counts a graph node with the given attributes.
+    pub(crate) fn count_node(&mut self, attributes: &Attributes) {
+        self.stats.node_count += 1;
+        for (name, value) in attributes.iter() {
+            self.stats.add_node_attribute(name, value);
+        }
+    }
+
+    /// Counts an edge with the given attributes.
+    pub(crate) fn count_edge(&mut self, attributes: &Attributes) {
+        self.stats.edge_count += 1;
+        for (name, value) in attributes.iter() {
+            self.stats.add_edge_attribute(name, value);
+        }
+    }
+
+    pub(crate) fn count_node_attribute(&mut self, name: &Identifier, value: &Value) {
+        self.stats.add_node_attribute(name, value);
+    }
+
+    pub(crate) fn count_edge_attribute(&mut self, name: &Identifier, value: &Value) {
+        self.stats.add_edge_attribute(name, value);
+    }
+
+    /// Records a graph node with the given attributes, without adding it to the graph, and
+    /// returns the reference that it would have had.
+    pub(crate) fn add_graph_node(
+        &mut self,
+        graph: &Graph,
+        attributes: &Attributes,
+    ) -> GraphNodeRef {
+        let node = GraphNodeRef::new(graph.node_count() + self.nodes);
+        self.nodes += 1;
+        self.count_node(attributes);
+        for (name, _) in attributes.iter() {
+            self.node_attributes.insert((node, name.clone()));
+        }
+        node
+    }
+
+    /// Records an edge with the given attributes, without adding it to the graph.  Returns
+    /// whether the edge is new.
+    pub(crate) fn add_edge(
+        &mut self,
+        graph: &Graph,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        attributes: &Attributes,
+    ) -> bool {
+        if self.has_edge(graph, source, sink) {
+            return false;
+        }
+        self.edges.insert((source, sink));
+        self.count_edge(attributes);
+        for (name, _) in attributes.iter() {
+            self.edge_attributes.insert((source, sink, name.clone()));
+        }
+        true
+    }
+
+    /// Returns whether the graph has an edge between two graph nodes, or the dry run recorded
+    /// one.
+    pub(crate) fn has_edge(&self, graph: &Graph, source: GraphNodeRef, sink: GraphNodeRef) -> bool {
+        self.edges.contains(&(source, sink))
+            || existing_node(graph, source).is_some_and(|node| node.get_edge(sink).is_some())
+    }
+
+    /// Records an attribute of a graph node, without adding it to the graph.  Returns whether
+    /// the graph node did not have the attribute yet.
+    pub(crate) fn add_node_attribute(
+        &mut self,
+        graph: &Graph,
+        node: GraphNodeRef,
+        name: &Identifier,
+        value: &Value,
+    ) -> bool {
+        let existing =
+            existing_node(graph, node).is_some_and(|node| node.attributes().get(name).is_some());
+        if existing || !self.node_attributes.insert((node, name.clone())) {
+            return false;
+        }
+        self.stats.add_node_attribute(name, value);
+        true
+    }
+
+    /// Records an attribute of an edge that the graph has or that the dry run recorded, without
+    /// adding it to the graph.  Returns whether the edge did not have the attribute yet.
+    pub(crate) fn add_edge_attribute(
+        &mut self,
+        graph: &Graph,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        name: &Identifier,
+        value: &Value,
+    ) -> bool {
+        let existing = existing_node(graph, source)
+            .and_then(|node| node.get_edge(sink))
+            .is_some_and(|edge| edge.attributes().get(name).is_some());
+        if existing || !self.edge_attributes.insert((source, sink, name.clone())) {
+            return false;
+        }
+        self.stats.add_edge_attribute(name, value);
+        true
+    }
+}
+
+/// Returns a graph node of the graph, or `None` if it is one that a dry run recorded.
+fn existing_node<'a>(graph: &'a Graph, node: GraphNodeRef) -> Option<GraphNode<'a>> {
+    if node.index() < graph.node_count() {
+        Some(graph.node(node))
+    } else {
+        None
+    }
+}
@@ -11,6 +11,7 @@ use thiserror::Error;
 use crate::ast::Stanza;
 use crate::ast::Statement;
 use crate::execution::CancellationError;
+use crate::graph::Provenance;
 use crate::parse_error::Excerpt;
 use crate::Location;
 
@@ -35,6 +36,12 @@ pub enum ExecutionError {
     ExpectedGraphNode(String),
     #[error("Expected a list {0}")]
     ExpectedList(String),
+    #[error("Expected a set {0}")]
+    ExpectedSet(String),
+    #[error("Expected a map {0}")]
+    ExpectedMap(String),
+    #[error("Expected a span {0}")]
+    ExpectedSpan(String),
     #[error("Expected a boolean {0}")]
     ExpectedBoolean(String),
     #[error("Expected an integer {0}")]
@@ -43,12 +50,18 @@ pub enum ExecutionError {
     ExpectedString(String),
     #[error("Expected a syntax node {0}")]
     ExpectedSyntaxNode(String),
+    #[error("Expected a function {0}")]
+    ExpectedFunction(String),
+    #[error("Attribute {0} must have type {1}, but the value has type {2}")]
+    InvalidAttributeType(String, String, String),
     #[error("Invalid parameters {0}")]
     InvalidParameters(String),
     #[error("Scoped variables can only be attached to syntax nodes {0}")]
     InvalidVariableScope(String),
     #[error("Missing global variable {0}")]
     MissingGlobalVariable(String),
+    #[error("Missing query {0}")]
+    MissingQuery(String),
     #[error("Recursively defined scoped variable {0}")]
     RecursivelyDefinedScopedVariable(String),
     #[error("Recursively defined variable {0}")]
@@ -69,12 +82,29 @@ pub enum ExecutionError {
     UndefinedVariable(String),
     #[error("Cannot add scoped variable after being forced {0}")]
     VariableScopesAlreadyForced(String),
+    #[error("Cannot read the graph in {0} before the lazy graph is evaluated, such as in a condition or loop")]
+    GraphReadBeforeEvaluation(String),
     #[error("Function {0} failed: {1}")]
     FunctionFailed(String, String),
+    #[error("Timed out {0}")]
+    TimedOut(String),
+    #[error("Resource limit exceeded: {0}")]
+    ResourceLimitExceeded(String),
+    #[error("Memory budget exceeded: lazy evaluation holds about {used} bytes, more than the budget of {budget} bytes")]
+    MemoryBudgetExceeded { used: usize, budget: usize },
     #[error("{0}. Caused by: {1}")]
     InContext(Context, Box<ExecutionError>),
 }
 
+/// A problem that did not stop the execution of a graph DSL file, but may have made the graph that
+/// it built incomplete.  See
+/// [`ExecutionReport::warnings`][`crate::ExecutionReport::warnings`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ExecutionWarning {
+    #[error("Match limit exceeded {0}, so some matches may be missing")]
+    MatchLimitExceeded(String),
+}
+
 #[derive(Clone, Debug)]
 pub enum Context {
     Statement(Vec<StatementContext>),
@@ -101,9 +131,14 @@ impl StatementContext {
         }
     }
 
-    pub(crate) fn update_statement(&mut self, stmt: &Statement) {
-        self.statement = format!("{}", stmt);
-        self.statement_location = stmt.location();
+    /// Returns the provenance of graph elements created by the statement.
+    pub(crate) fn provenance(&self) -> Provenance {
+        Provenance {
+            stanza_location: self.stanza_location,
+            statement_location: self.statement_location,
+            source_location: self.source_location,
+            node_kind: self.node_kind.clone(),
+        }
     }
 }
 
@@ -180,6 +215,19 @@ impl<R> ResultWithExecutionError<R> for Result<R, ExecutionError> {
 }
 
 impl ExecutionError {
+    /// Returns whether this error stops an execution, even if it continues on errors, because it
+    /// was cancelled, timed out, or exceeded a resource limit or the memory budget.
+    pub(crate) fn stops_execution(&self) -> bool {
+        match self {
+            ExecutionError::Cancelled(_)
+            | ExecutionError::TimedOut(_)
+            | ExecutionError::ResourceLimitExceeded(_)
+            | ExecutionError::MemoryBudgetExceeded { .. } => true,
+            ExecutionError::InContext(_, cause) => cause.stops_execution(),
+            _ => false,
+        }
+    }
+
     pub fn display_pretty<'a>(
         &'a self,
         source_path: &'a Path,
@@ -0,0 +1,469 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Re-executes graph DSL files after their source has been edited
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use tree_sitter::InputEdit;
+use tree_sitter::Node;
+use tree_sitter::Point;
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::graph::Provenance;
+use crate::graph::SourcePosition;
+use crate::graph::Value;
+use crate::Location;
+
+/// What [`File::execute_incremental`] needs to know about a previous execution of a graph DSL
+/// file, and about the edits to the source file since.
+pub struct PreviousExecution<'a, 'old> {
+    /// The graph that the previous execution produced
+    pub graph: &'a Graph<'old>,
+    /// The syntax tree that the previous execution was against
+    pub tree: &'a Tree,
+    /// The edits to the source file since the previous execution, in the order they were applied
+    pub edits: &'a [InputEdit],
+}
+
+/// Identifies a stanza match by the location of the stanza, and by the start position and kind of
+/// the syntax node that it matched.
+type MatchKey = ((usize, usize), (usize, usize), String);
+
+impl File {
+    /// Executes this graph DSL file against an edited source file, reusing the parts of the graph
+    /// that it produced for the source before the edits.  You must provide the graph and syntax
+    /// tree from before the edits and the edits themselves (`previous`), and the syntax tree
+    /// (`tree`) and source text (`source`) after them.
+    ///
+    /// Only the stanza matches whose syntax nodes overlap the edits are executed again, together
+    /// with all matches that created edges or attributes on the same graph nodes as them, or
+    /// referred to the same graph nodes in attribute values.  The graph elements that the
+    /// remaining matches created are copied from `previous`, with their syntax node references and
+    /// spans moved to `tree`.  Graph nodes are numbered differently than they would be by a full
+    /// execution, but the resulting graph is otherwise the same, provided that matches only depend
+    /// on each other through the graph elements they share.  Values that matches pass through
+    /// scoped variables without sharing graph nodes are not tracked.  Neither are other values
+    /// computed from source positions, such as those returned by `start-row`, or the results of
+    /// functions that inspect the graph or depend on the order of execution, such as `gensym`.
+    ///
+    /// Incremental execution relies on the provenance of every element of the previous graph, so
+    /// it must have been created by executing with [`ExecutionConfig::with_provenance`], or by a
+    /// previous incremental execution, which always records provenance.  If it was not, or if a
+    /// syntax node that it refers to cannot be found in the edited tree, this falls back to
+    /// executing the whole file again.  Errors from executing the affected matches are returned.
+    pub fn execute_incremental<'tree>(
+        &self,
+        previous: &PreviousExecution,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let config = config.clone().with_provenance(true);
+        let incremental = Incremental {
+            previous: previous.graph,
+            old_tree: previous.tree,
+            tree,
+            edits: previous.edits,
+        };
+        if let Some(reused) = incremental.reuse_unaffected_matches() {
+            let Reused {
+                mut graph,
+                rerun,
+                dirty,
+            } = reused;
+            let filter = |stanza: &crate::ast::Stanza, node: Node| {
+                let key = (
+                    (stanza.range.start.row, stanza.range.start.column),
+                    (node.start_position().row, node.start_position().column),
+                    node.kind().to_string(),
+                );
+                rerun.contains(&key) || overlaps(node.start_byte(), node.end_byte(), &dirty)
+            };
+            let mut config = config.clone();
+            config.settings.match_filter = Some(&filter);
+            self.execute_into(&mut graph, tree, source, &config, cancellation_flag)?;
+            return Ok(graph);
+        }
+        self.execute(tree, source, &config, cancellation_flag)
+    }
+}
+
+/// The inputs of an incremental execution.
+struct Incremental<'a, 'old, 'tree> {
+    previous: &'a Graph<'old>,
+    old_tree: &'a Tree,
+    tree: &'tree Tree,
+    edits: &'a [InputEdit],
+}
+
+/// The part of a graph that an incremental execution reuses, and the matches that it must execute
+/// again to complete it.
+struct Reused<'tree> {
+    graph: Graph<'tree>,
+    /// The matches to execute again, identified by the start position of their syntax node in the
+    /// edited tree
+    rerun: HashSet<MatchKey>,
+    /// The byte ranges of the edited tree that were changed by the edits
+    dirty: Vec<(usize, usize)>,
+}
+
+impl<'a, 'old, 'tree> Incremental<'a, 'old, 'tree> {
+    /// Copies the graph elements of the matches that are not affected by the edits into a new
+    /// graph.  Returns `None` if that is not possible, because the provenance of an element is
+    /// missing, or because a syntax node cannot be found in the edited tree.
+    fn reuse_unaffected_matches(&self) -> Option<Reused<'tree>> {
+        let previous = self.previous;
+        let mut matches = MatchSets::default();
+        let mut creators = Vec::with_capacity(previous.node_count());
+        for node in previous.iter_nodes() {
+            creators.push(matches.add(previous.node_provenance(node)?));
+        }
+
+        // Matches that touch the same graph nodes must be executed together.
+        for node in previous.iter_nodes() {
+            let creator = creators[node.index()];
+            for (name, value) in previous.node(node).attributes().iter() {
+                let setter = matches.add(previous.node_attribute_provenance(node, name)?);
+                matches.union(setter, creator);
+                for_each_graph_node(value, &mut |other| {
+                    matches.union(setter, creators[other.index()])
+                });
+            }
+            for (sink, edge) in previous.node(node).iter_edges() {
+                let creator_of_edge = matches.add(previous.edge_provenance(node, sink)?);
+                matches.union(creator_of_edge, creator);
+                matches.union(creator_of_edge, creators[sink.index()]);
+                for (name, value) in edge.attributes().iter() {
+                    let setter = matches.add(previous.edge_attribute_provenance(node, sink, name)?);
+                    matches.union(setter, creator_of_edge);
+                    for_each_graph_node(value, &mut |other| {
+                        matches.union(setter, creators[other.index()])
+                    });
+                }
+            }
+        }
+
+        let dirty = self.dirty_ranges();
+        let mut moved_keys = Vec::with_capacity(matches.keys.len());
+        let mut dirty_sets = HashSet::new();
+        for (index, key) in matches.keys.clone().into_iter().enumerate() {
+            let start = Point::new((key.1).0, (key.1).1);
+            let node = find_node_starting_at(self.old_tree, start, &key.2)?;
+            let start_byte = self.move_byte(node.start_byte());
+            let end_byte = self.move_byte(node.end_byte());
+            if overlaps(start_byte, end_byte, &dirty) {
+                dirty_sets.insert(matches.find(index));
+            }
+            let start = self.move_point(start);
+            moved_keys.push((key.0, (start.row, start.column), key.2));
+        }
+        let mut is_dirty = |index: usize| dirty_sets.contains(&matches.find(index));
+        let rerun = (0..moved_keys.len())
+            .filter(|index| is_dirty(*index))
+            .map(|index| moved_keys[index].clone())
+            .collect();
+
+        let mut graph = Graph::new();
+        let mut mapping = vec![None; previous.node_count()];
+        for node in previous.iter_nodes() {
+            if !is_dirty(creators[node.index()]) {
+                mapping[node.index()] = Some(graph.add_graph_node());
+            }
+        }
+        for node in previous.iter_nodes() {
+            let new_node = match mapping[node.index()] {
+                Some(new_node) => new_node,
+                None => continue,
+            };
+            let provenance = self.move_provenance(previous.node_provenance(node)?);
+            graph.set_node_provenance(new_node, provenance);
+            if let Some(group) = previous.node_group(node) {
+                graph.set_node_group(new_node, group);
+            }
+            for (name, value) in previous.node(node).attributes().iter() {
+                let value = self.move_value(value, &mut graph, &mapping)?;
+                let value = graph.intern(value);
+                let _ = graph
+                    .node_mut(new_node)
+                    .attributes()
+                    .add(name.clone(), value);
+                let provenance = previous.node_attribute_provenance(node, name)?;
+                let provenance = self.move_provenance(provenance);
+                graph.set_node_attribute_provenance(new_node, name.clone(), provenance);
+            }
+            for (sink, edge) in previous.node(node).iter_edges() {
+                let new_sink = mapping[sink.index()]?;
+                let _ = graph.node_mut(new_node).add_edge(new_sink);
+                let provenance = self.move_provenance(previous.edge_provenance(node, sink)?);
+                graph.set_edge_provenance(new_node, new_sink, provenance);
+                for (name, value) in edge.attributes().iter() {
+                    let value = self.move_value(value, &mut graph, &mapping)?;
+                    let value = graph.intern(value);
+                    let graph_node = graph.node_mut(new_node);
+                    let mut new_edge = match graph_node.add_edge(new_sink) {
+                        Ok(edge) => edge,
+                        Err(edge) => edge,
+                    };
+                    let _ = new_edge.attributes().add(name.clone(), value);
+                    let provenance = previous.edge_attribute_provenance(node, sink, name)?;
+                    let provenance = self.move_provenance(provenance);
+                    graph.set_edge_attribute_provenance(
+                        new_node,
+                        new_sink,
+                        name.clone(),
+                        provenance,
+                    );
+                }
+            }
+        }
+
+        Some(Reused {
+            graph,
+            rerun,
+            dirty,
+        })
+    }
+
+    /// Returns the byte ranges of the edited tree that the edits changed, either by replacing
+    /// their text, or by changing the structure of the syntax tree around them.
+    fn dirty_ranges(&self) -> Vec<(usize, usize)> {
+        let mut dirty = Vec::new();
+        for (index, edit) in self.edits.iter().enumerate() {
+            let mut start = edit.start_byte;
+            let mut end = edit.new_end_byte;
+            for later in &self.edits[index + 1..] {
+                start = move_byte(start, later);
+                end = move_byte(end, later);
+            }
+            dirty.push((start, end));
+        }
+        let mut edited = self.old_tree.clone();
+        for edit in self.edits {
+            edited.edit(edit);
+        }
+        dirty.extend(
+            edited
+                .changed_ranges(self.tree)
+                .map(|range| (range.start_byte, range.end_byte)),
+        );
+        dirty
+    }
+
+    /// Returns where a byte offset of the old tree ends up after the edits.
+    fn move_byte(&self, byte: usize) -> usize {
+        self.edits.iter().fold(byte, move_byte)
+    }
+
+    /// Returns where a position of the old tree ends up after the edits.
+    fn move_point(&self, point: Point) -> Point {
+        self.edits.iter().fold(point, move_point)
+    }
+
+    fn move_provenance(&self, provenance: &Provenance) -> Provenance {
+        let location = provenance.source_location;
+        let start = self.move_point(Point::new(location.row, location.column));
+        Provenance {
+            source_location: Location::from(start),
+            ..provenance.clone()
+        }
+    }
+
+    /// Returns a copy of a value from the previous graph that refers to the new graph and the
+    /// edited tree instead.
+    fn move_value(
+        &self,
+        value: &Value,
+        graph: &mut Graph<'tree>,
+        mapping: &[Option<GraphNodeRef>],
+    ) -> Option<Value> {
+        Some(match value {
+            Value::List(values) => Value::List(
+                values
+                    .iter()
+                    .map(|value| self.move_value(value, graph, mapping))
+                    .collect::<Option<_>>()?,
+            ),
+            Value::Set(values) => Value::Set(
+                values
+                    .iter()
+                    .map(|value| self.move_value(value, graph, mapping))
+                    .collect::<Option<_>>()?,
+            ),
+            Value::Map(values) => Value::Map(
+                values
+                    .iter()
+                    .map(|(key, value)| {
+                        Some((key.clone(), self.move_value(value, graph, mapping)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Value::Span { start, end } => Value::Span {
+                start: self.move_position(*start),
+                end: self.move_position(*end),
+            },
+            Value::SyntaxNode(node) => {
+                let node = self.previous[*node];
+                let start = self.move_byte(node.start_byte());
+                let end = self.move_byte(node.end_byte());
+                let node = find_node_spanning(self.tree, start, end, node.kind())?;
+                graph.add_syntax_node(node).into()
+            }
+            Value::GraphNode(node) => mapping[node.index()]?.into(),
+            value => value.clone(),
+        })
+    }
+
+    fn move_position(&self, position: SourcePosition) -> SourcePosition {
+        let point = self.move_point(Point::new(position.row, position.column));
+        SourcePosition {
+            byte: self.move_byte(position.byte),
+            row: point.row,
+            column: point.column,
+        }
+    }
+}
+
+/// The stanza matches that created the elements of a graph, partitioned into sets of matches that
+/// must be executed together.
+#[derive(Default)]
+struct MatchSets {
+    keys: Vec<MatchKey>,
+    indices: HashMap<MatchKey, usize>,
+    parents: Vec<usize>,
+}
+
+impl MatchSets {
+    /// Adds the match that a provenance refers to, if it is not already present, returning its
+    /// index.
+    fn add(&mut self, provenance: &Provenance) -> usize {
+        let key = (
+            (
+                provenance.stanza_location.row,
+                provenance.stanza_location.column,
+            ),
+            (
+                provenance.source_location.row,
+                provenance.source_location.column,
+            ),
+            provenance.node_kind.clone(),
+        );
+        let keys = &mut self.keys;
+        let parents = &mut self.parents;
+        *self.indices.entry(key).or_insert_with_key(|key| {
+            keys.push(key.clone());
+            parents.push(parents.len());
+            parents.len() - 1
+        })
+    }
+
+    /// Returns the representative of the set that a match belongs to.
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn union(&mut self, left: usize, right: usize) {
+        let left = self.find(left);
+        let right = self.find(right);
+        self.parents[left] = right;
+    }
+}
+
+fn for_each_graph_node<F: FnMut(GraphNodeRef)>(value: &Value, f: &mut F) {
+    match value {
+        Value::List(values) => values
+            .iter()
+            .for_each(|value| for_each_graph_node(value, f)),
+        Value::Set(values) => values
+            .iter()
+            .for_each(|value| for_each_graph_node(value, f)),
+        Value::Map(values) => values
+            .values()
+            .for_each(|value| for_each_graph_node(value, f)),
+        Value::GraphNode(node) => f(*node),
+        _ => {}
+    }
+}
+
+/// Returns whether a byte range overlaps, or touches, any of a list of byte ranges.
+fn overlaps(start: usize, end: usize, ranges: &[(usize, usize)]) -> bool {
+    ranges
+        .iter()
+        .any(|(range_start, range_end)| start <= *range_end && *range_start <= end)
+}
+
+/// Returns where a byte offset ends up after an edit.  Offsets inside the replaced text move to
+/// the end of the replacement.
+fn move_byte(byte: usize, edit: &InputEdit) -> usize {
+    if byte >= edit.old_end_byte {
+        byte - edit.old_end_byte + edit.new_end_byte
+    } else if byte > edit.start_byte {
+        edit.new_end_byte
+    } else {
+        byte
+    }
+}
+
+/// Returns where a position ends up after an edit.  Positions inside the replaced text move to the
+/// end of the replacement.
+fn move_point(point: Point, edit: &InputEdit) -> Point {
+    let old_end = edit.old_end_position;
+    let new_end = edit.new_end_position;
+    if point >= old_end {
+        if point.row == old_end.row {
+            Point::new(new_end.row, point.column - old_end.column + new_end.column)
+        } else {
+            Point::new(point.row - old_end.row + new_end.row, point.column)
+        }
+    } else if point > edit.start_position {
+        new_end
+    } else {
+        point
+    }
+}
+
+/// Returns the outermost syntax node of a kind that starts at a position.
+fn find_node_starting_at<'t>(tree: &'t Tree, start: Point, kind: &str) -> Option<Node<'t>> {
+    let mut node = tree.root_node().descendant_for_point_range(start, start);
+    let mut found = None;
+    while let Some(candidate) = node {
+        if candidate.start_position() == start && candidate.kind() == kind {
+            found = Some(candidate);
+        }
+        node = candidate.parent();
+    }
+    found
+}
+
+/// Returns the innermost syntax node of a kind that spans exactly a byte range.
+fn find_node_spanning<'t>(
+    tree: &'t Tree,
+    start: usize,
+    end: usize,
+    kind: &str,
+) -> Option<Node<'t>> {
+    let mut node = tree.root_node().descendant_for_byte_range(start, end)?;
+    while node.start_byte() == start && node.end_byte() == end {
+        if node.kind() == kind {
+            return Some(node);
+        }
+        node = node.parent()?;
+    }
+    None
+}
@@ -0,0 +1,145 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Executes graph DSL files against the regions of a source file that are written in other
+//! languages
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionReport;
+use crate::graph::Graph;
+use crate::graph::Value;
+use crate::Identifier;
+
+/// A region of a source file that is written in another language, such as a script in an HTML
+/// file, and the graph DSL file for that language.
+///
+/// The host finds the regions, for example with the injection queries of the source file's
+/// grammar, and parses each of them with
+/// [`Parser::set_included_ranges`][`tree_sitter::Parser::set_included_ranges`] restricted to the
+/// region, so that the positions in its syntax tree are those of the whole source file.
+///
+/// When a source file is executed with [`File::execute_with_injections`], and the configuration
+/// sets an anchor attribute with [`ExecutionConfig::injection_anchors`], the graph nodes whose
+/// anchor attribute is a syntax node anchor the edges between languages: an edge is added from
+/// each anchor of the source file whose syntax node contains the byte range of an injection, to
+/// each anchor of the injection whose syntax node is the root of its syntax tree.
+pub struct Injection<'a, 'tree> {
+    /// The graph DSL file to execute against the region
+    pub file: &'a File,
+    /// The syntax tree of the region
+    pub tree: &'tree Tree,
+    /// The range of bytes of the source file that the region spans
+    pub byte_range: Range<usize>,
+}
+
+impl File {
+    /// Executes this graph DSL file against a source file, and the graph DSL files of its
+    /// injections against the regions of the source file that are written in other languages,
+    /// building a single graph.  See [`Injection`] for how the graphs of the languages are
+    /// connected.
+    pub fn execute_with_injections<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        injections: &[Injection<'_, 'tree>],
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let mut graph = Graph::new();
+        self.execute_with_injections_into(
+            &mut graph,
+            tree,
+            source,
+            injections,
+            config,
+            cancellation_flag,
+        )?;
+        Ok(graph)
+    }
+
+    /// Executes this graph DSL file and the graph DSL files of the injections against a source
+    /// file, like [`File::execute_with_injections`], saving the results into an existing `Graph`
+    /// instance.  Each file is executed in turn, like with [`File::execute_into`], so resource
+    /// limits apply to each of them separately.  The report combines the warnings and errors of
+    /// all of the files, and its profile, if any, only covers this file.
+    pub fn execute_with_injections_into<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        injections: &[Injection<'_, 'tree>],
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<ExecutionReport, ExecutionError> {
+        let mut report = self.execute_into(graph, tree, source, config, cancellation_flag)?;
+        let injection_config = config.clone().profile(false);
+        for injection in injections {
+            report.extend(injection.file.execute_into(
+                graph,
+                injection.tree,
+                source,
+                &injection_config,
+                cancellation_flag,
+            )?);
+        }
+        if let Some(anchor_attr) = &config.settings.injection_anchor_attr {
+            link_anchors(graph, tree, injections, anchor_attr);
+        }
+        Ok(report)
+    }
+}
+
+/// Adds an edge from each anchor of the source file to each anchor of the injections that it
+/// contains.
+fn link_anchors(
+    graph: &mut Graph,
+    tree: &Tree,
+    injections: &[Injection],
+    anchor_attr: &Identifier,
+) {
+    let root = tree.root_node();
+    let mut host_anchors = Vec::new();
+    let mut injection_anchors = Vec::new();
+    for node in graph.iter_nodes() {
+        let syntax_node = match graph.node(node).attributes().get(anchor_attr) {
+            Some(Value::SyntaxNode(syntax_node)) => graph[*syntax_node],
+            _ => continue,
+        };
+        if root_of(syntax_node) == root {
+            host_anchors.push((node, syntax_node.byte_range()));
+        } else if let Some(index) = injections
+            .iter()
+            .position(|injection| injection.tree.root_node() == syntax_node)
+        {
+            injection_anchors.push((node, index));
+        }
+    }
+    for (injection_anchor, index) in injection_anchors {
+        let byte_range = &injections[index].byte_range;
+        for (host_anchor, host_range) in &host_anchors {
+            if host_range.start <= byte_range.start && byte_range.end <= host_range.end {
+                let _ = graph.node_mut(*host_anchor).add_edge(injection_anchor);
+            }
+        }
+    }
+}
+
+/// Returns the root of the syntax tree that a syntax node belongs to.
+fn root_of(mut node: Node) -> Node {
+    while let Some(parent) = node.parent() {
+        node = parent;
+    }
+    node
+}
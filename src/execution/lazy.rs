@@ -5,6 +5,8 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+mod on_demand;
+mod parallel;
 mod statements;
 mod store;
 mod values;
@@ -12,16 +14,28 @@ mod values;
 use log::{debug, trace};
 
 use std::collections::HashMap;
+use std::time::Instant;
 
 use tree_sitter::QueryCursor;
 use tree_sitter::QueryMatch;
 use tree_sitter::Tree;
 
 use crate::ast;
+use crate::execution::check_deadline;
 use crate::execution::error::ExecutionError;
+use crate::execution::error::ExecutionWarning;
 use crate::execution::error::ResultWithExecutionError;
 use crate::execution::error::StatementContext;
+use crate::execution::number_matches;
+use crate::execution::profile::Measurement;
+use crate::execution::recover;
+use crate::execution::AttributeType;
 use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionObserver;
+use crate::execution::ExecutionState;
+use crate::execution::LazyMemoryPolicy;
+use crate::execution::SessionCursor;
+use crate::execution::Yielding;
 use crate::functions::Functions;
 use crate::graph;
 use crate::graph::Attributes;
@@ -33,6 +47,7 @@ use crate::variables::VariableMap;
 use crate::CancellationFlag;
 use crate::Identifier;
 
+pub use on_demand::OnDemandGraph;
 use statements::*;
 use store::*;
 use values::*;
@@ -43,68 +58,211 @@ impl ast::File {
     /// text that it was parsed from (`source`).  You also provide the set of functions and global
     /// variables that are available during execution. This variant is useful when you need to
     /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
-    pub(super) fn execute_lazy_into<'a, 'tree>(
+    pub(super) async fn execute_lazy_into<'tree>(
         &self,
         graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
         tree: &'tree Tree,
         source: &'tree str,
-        config: &ExecutionConfig,
+        config: &ExecutionConfig<'_, '_>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), ExecutionError> {
+        let mut evaluation = self
+            .build_lazy_graph(graph, state, tree, source, config, cancellation_flag)
+            .await?;
+        evaluation
+            .evaluate_all(graph, state, cancellation_flag)
+            .await
+    }
+
+    /// Executes the stanzas of this file against a source file, for the stanza matches that
+    /// `config` selects, and returns the lazy graph that they built, without evaluating it.
+    /// Yields after each match if `config` asks to.
+    pub(super) async fn build_lazy_graph<'a, 'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'a, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<LazyEvaluation<'a, 'tree>, ExecutionError> {
+        let functions = config.functions;
+        let observer = config.settings.observer;
         let mut globals = Globals::nested(config.globals);
         self.check_globals(&mut globals)?;
         let mut config = ExecutionConfig {
-            functions: config.functions,
             globals: &globals,
-            lazy: config.lazy,
-            location_attr: config.location_attr.clone(),
-            variable_name_attr: config.variable_name_attr.clone(),
+            ..config.clone()
+        };
+        config.settings.deadline = config.deadline();
+
+        if let Some(observer) = config.settings.observer {
+            for stanza in self
+                .stanzas
+                .iter()
+                .filter(|s| config.settings.tags.selects(s))
+            {
+                observer.on_stanza_start(stanza);
+            }
+        }
+        let parallel_matches = if config.settings.parallel {
+            self.execute_matches_lazy_parallel(
+                graph,
+                state,
+                tree,
+                source,
+                &config,
+                cancellation_flag,
+            )?
+        } else {
+            None
+        };
+        let matches = match parallel_matches {
+            Some(matches) => matches,
+            None => {
+                let mut cursor = self.query_cursor_lazy(&config);
+                let query = self.file_query()?;
+                let matches = self
+                    .execute_matches_lazy(
+                        graph,
+                        state,
+                        source,
+                        &config,
+                        cancellation_flag,
+                        number_matches(cursor.matches(query, tree.root_node(), source.as_bytes())),
+                    )
+                    .await?;
+                check_match_limit(&cursor, state);
+                matches
+            }
         };
 
+        Ok(LazyEvaluation {
+            source,
+            functions,
+            attribute_types: config.settings.attribute_types,
+            provenance: config.settings.provenance,
+            deadline: config.settings.deadline,
+            observer,
+            continue_on_error: config.settings.continue_on_error,
+            yielding: config.settings.yielding,
+            evaluated: vec![false; matches.lazy_graph.len()],
+            matches,
+            function_parameters: Vec::new(),
+            prev_element_debug_info: HashMap::new(),
+            node_attribute_statements: NodeAttributeStatements::default(),
+        })
+    }
+
+    /// Executes the stanzas for `matches`, which are matches of the file query, building the lazy
+    /// graph of the statements that they execute.  Yields after each match if `config` asks to.
+    async fn execute_matches_lazy<'cursor, 'tree: 'cursor>(
+        &self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        source: &'tree str,
+        config: &ExecutionConfig<'_, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+        matches: impl Iterator<Item = (usize, QueryMatch<'cursor, 'tree>)>,
+    ) -> Result<LazyMatches, ExecutionError> {
         let mut locals = VariableMap::new();
         let mut store = LazyStore::new();
         let mut scoped_store = LazyScopedVariables::new();
         let mut lazy_graph = Vec::new();
         let mut function_parameters = Vec::new();
         let mut prev_element_debug_info = HashMap::new();
+        let mut inspects_edges = false;
 
-        self.try_visit_matches_lazy(tree, source, |stanza, mat| {
+        let mut query_start = Instant::now();
+        for (ordinal, mat) in matches {
+            let query_time = query_start.elapsed();
             cancellation_flag.check("processing matches")?;
-            stanza.execute_lazy(
+            check_deadline(config.settings.deadline, "processing matches")?;
+            let stanza = &self.stanzas[mat.pattern_index];
+            let node = mat
+                .nodes_for_capture_index(stanza.full_match_file_capture_index as u32)
+                .next()
+                .expect("missing full capture");
+            if !config.selects_node(stanza, node) || !config.selects_match(&mat) {
+                query_start = Instant::now();
+                continue;
+            }
+            if let Some(observer) = config.settings.observer {
+                observer.on_match(stanza, node);
+            }
+            if let Some(profile) = state.profile_mut() {
+                profile.add_match(mat.pattern_index, query_time);
+            }
+            state.start_match(mat.pattern_index, ordinal);
+            let measurement = Measurement::start(state);
+            let result = stanza.execute_lazy(
                 source,
                 &mat,
                 graph,
-                &mut config,
+                state,
+                config,
                 &mut locals,
                 &mut store,
                 &mut scoped_store,
                 &mut lazy_graph,
                 &mut function_parameters,
                 &mut prev_element_debug_info,
+                &mut inspects_edges,
                 &self.shorthands,
                 cancellation_flag,
-            )
-        })?;
-
-        let mut exec = EvaluationContext {
-            source,
-            graph,
-            functions: config.functions,
-            store: &store,
-            scoped_store: &scoped_store,
-            function_parameters: &mut function_parameters,
-            prev_element_debug_info: &mut prev_element_debug_info,
-            cancellation_flag,
-        };
-        for graph_stmt in &lazy_graph {
-            graph_stmt.evaluate(&mut exec)?;
+            );
+            Measurement::finish(measurement, state, mat.pattern_index);
+            config.recover(state, result)?;
+            if let Err(error) = state.resource_usage().check_lazy_bytes(store.bytes()) {
+                if state.resource_usage().lazy_memory_policy() != LazyMemoryPolicy::ForceEagerly {
+                    return Err(error);
+                }
+                let mut exec = EvaluationContext {
+                    source,
+                    graph,
+                    state,
+                    functions: config.functions,
+                    attribute_types: &config.settings.attribute_types,
+                    provenance: config.settings.provenance,
+                    store: &store,
+                    scoped_store: &scoped_store,
+                    function_parameters: &mut function_parameters,
+                    prev_element_debug_info: &mut prev_element_debug_info,
+                    deadline: config.settings.deadline,
+                    observer: config.settings.observer,
+                    continue_on_error: config.settings.continue_on_error,
+                    statements: &lazy_graph,
+                    evaluated: &mut [],
+                    node_attribute_statements: &mut NodeAttributeStatements::default(),
+                    building: true,
+                    cancellation_flag,
+                };
+                store.force_eagerly(&mut exec)?;
+                state.resource_usage().check_lazy_bytes(store.bytes())?;
+            }
+            config.settings.yielding.yield_now().await;
+            query_start = Instant::now();
         }
-        // make sure any unforced values are now forced, to surface any problems
-        // hidden by the fact that the values were unused
-        store.evaluate_all(&mut exec)?;
-        scoped_store.evaluate_all(&mut exec)?;
 
-        Ok(())
+        Ok(LazyMatches {
+            store,
+            scoped_store,
+            lazy_graph,
+            inspects_edges,
+        })
+    }
+
+    /// Returns a cursor for the file query.  The stanzas share the query, so its match limit is the
+    /// smallest of the one in `config` and those of the stanzas.
+    fn query_cursor_lazy<'a>(&self, config: &ExecutionConfig<'a, '_>) -> SessionCursor<'a> {
+        let match_limit = self
+            .stanzas
+            .iter()
+            .filter_map(|stanza| stanza.match_limit)
+            .chain(config.settings.match_limit)
+            .min();
+        config.query_cursor(match_limit)
     }
 
     pub(super) fn try_visit_matches_lazy<'tree, E, F>(
@@ -127,10 +285,112 @@ impl ast::File {
     }
 }
 
+/// Records a warning if the file query exceeded its match limit while `cursor` executed it.
+fn check_match_limit(cursor: &QueryCursor, state: &mut ExecutionState) {
+    if cursor.did_exceed_match_limit() {
+        state.add_warning(ExecutionWarning::MatchLimitExceeded(
+            "for the file query".to_string(),
+        ));
+    }
+}
+
+/// The lazy graph, and the values it refers to, that executing the stanzas for some matches built
+struct LazyMatches {
+    store: LazyStore,
+    scoped_store: LazyScopedVariables,
+    lazy_graph: Vec<LazyStatement>,
+    inspects_edges: bool,
+}
+
+/// The lazy graph that executing the stanzas built, and the state needed to evaluate it
+pub(crate) struct LazyEvaluation<'a, 'tree> {
+    source: &'tree str,
+    functions: &'a Functions,
+    attribute_types: HashMap<Identifier, AttributeType>,
+    provenance: bool,
+    deadline: Option<Instant>,
+    observer: Option<&'a dyn ExecutionObserver>,
+    continue_on_error: bool,
+    yielding: Yielding,
+    matches: LazyMatches,
+    evaluated: Vec<bool>, // whether each of the statements has been (or is being) evaluated
+    function_parameters: Vec<graph::Value>, // re-usable buffer to reduce memory allocations
+    prev_element_debug_info: HashMap<GraphElementKey, DebugInfo>,
+    node_attribute_statements: NodeAttributeStatements,
+}
+
+impl<'tree> LazyEvaluation<'_, 'tree> {
+    /// Returns a context for evaluating the lazy graph into `graph`, which must be the graph that
+    /// it was built for.
+    fn context<'s>(
+        &'s mut self,
+        graph: &'s mut Graph<'tree>,
+        state: &'s mut ExecutionState,
+        cancellation_flag: &'s dyn CancellationFlag,
+    ) -> Result<EvaluationContext<'s, 'tree>, ExecutionError> {
+        let inspects_edges = std::mem::take(&mut self.matches.inspects_edges);
+        let mut exec = EvaluationContext {
+            source: self.source,
+            graph,
+            state,
+            functions: self.functions,
+            attribute_types: &self.attribute_types,
+            provenance: self.provenance,
+            deadline: self.deadline,
+            observer: self.observer,
+            continue_on_error: self.continue_on_error,
+            store: &self.matches.store,
+            scoped_store: &self.matches.scoped_store,
+            function_parameters: &mut self.function_parameters,
+            prev_element_debug_info: &mut self.prev_element_debug_info,
+            statements: &self.matches.lazy_graph,
+            evaluated: &mut self.evaluated,
+            node_attribute_statements: &mut self.node_attribute_statements,
+            building: false,
+            cancellation_flag,
+        };
+        if inspects_edges {
+            // create all edges first, so that the functions that inspect the graph's edges see
+            // all of them, regardless of where the edges are created
+            for (index, graph_stmt) in exec.statements.iter().enumerate() {
+                if graph_stmt.is_create_edge() {
+                    exec.evaluate_statement_profiled(index)?;
+                }
+            }
+        }
+        Ok(exec)
+    }
+
+    /// Evaluates all of the lazy graph that has not been evaluated yet, yielding after each
+    /// statement if the configuration of the execution asks to.
+    pub(super) async fn evaluate_all(
+        &mut self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let yielding = self.yielding;
+        let mut exec = self.context(graph, state, cancellation_flag)?;
+        for index in 0..exec.statements.len() {
+            exec.evaluate_statement_profiled(index)?;
+            yielding.yield_now().await;
+        }
+        // make sure any unforced values are now forced, to surface any problems
+        // hidden by the fact that the values were unused
+        let store = exec.store;
+        let scoped_store = exec.scoped_store;
+        let result = store.evaluate_all(&mut exec);
+        exec.recover(result)?;
+        let result = scoped_store.evaluate_all(&mut exec);
+        exec.recover(result)
+    }
+}
+
 /// Context for execution, which executes stanzas to build the lazy graph
 struct ExecutionContext<'a, 'c, 'g, 'tree> {
     source: &'tree str,
     graph: &'a mut Graph<'tree>,
+    state: &'a mut ExecutionState,
     config: &'a ExecutionConfig<'c, 'g>,
     locals: &'a mut dyn MutVariables<LazyValue>,
     current_regex_captures: &'a Vec<String>,
@@ -140,6 +400,7 @@ struct ExecutionContext<'a, 'c, 'g, 'tree> {
     lazy_graph: &'a mut Vec<LazyStatement>,
     function_parameters: &'a mut Vec<graph::Value>, // re-usable buffer to reduce memory allocations
     prev_element_debug_info: &'a mut HashMap<GraphElementKey, DebugInfo>,
+    inspects_edges: &'a mut bool,
     error_context: StatementContext,
     shorthands: &'a ast::AttributeShorthands,
     cancellation_flag: &'a dyn CancellationFlag,
@@ -149,14 +410,121 @@ struct ExecutionContext<'a, 'c, 'g, 'tree> {
 pub(self) struct EvaluationContext<'a, 'tree> {
     pub source: &'tree str,
     pub graph: &'a mut Graph<'tree>,
+    pub state: &'a mut ExecutionState,
     pub functions: &'a Functions,
+    pub attribute_types: &'a HashMap<Identifier, AttributeType>,
+    pub provenance: bool,
     pub store: &'a LazyStore,
     pub scoped_store: &'a LazyScopedVariables,
     pub function_parameters: &'a mut Vec<graph::Value>, // re-usable buffer to reduce memory allocations
     pub prev_element_debug_info: &'a mut HashMap<GraphElementKey, DebugInfo>,
+    pub deadline: Option<Instant>,
+    pub observer: Option<&'a dyn ExecutionObserver>,
+    pub continue_on_error: bool,
+    pub statements: &'a [LazyStatement],
+    pub evaluated: &'a mut [bool], // whether each of the statements has been (or is being) evaluated
+    pub node_attribute_statements: &'a mut NodeAttributeStatements,
+    pub building: bool, // whether the stanzas are still being executed, so the graph is incomplete
     pub cancellation_flag: &'a dyn CancellationFlag,
 }
 
+/// The statements that set each attribute of each graph node.  The statements that set an
+/// attribute are found when it is first forced, so that forcing an attribute does not have to look
+/// at every statement.
+#[derive(Default)]
+struct NodeAttributeStatements {
+    /// The statements that set each attribute, of any graph node, or `None` if they haven't been
+    /// found yet
+    by_name: Option<HashMap<Identifier, Vec<usize>>>,
+    /// The statements that set each attribute, by graph node, for the attributes that have been
+    /// forced
+    by_node: HashMap<Identifier, HashMap<graph::GraphNodeRef, Vec<usize>>>,
+}
+
+impl EvaluationContext<'_, '_> {
+    /// Evaluates a lazy graph statement, unless it has already been evaluated.
+    fn evaluate_statement(&mut self, index: usize) -> Result<(), ExecutionError> {
+        if self.evaluated[index] {
+            return Ok(());
+        }
+        self.evaluated[index] = true;
+        let statements = self.statements;
+        statements[index].evaluate(self)
+    }
+
+    /// Evaluates a lazy graph statement, like [`Self::evaluate_statement`], and attributes the
+    /// work to the stanza that produced the statement, if the execution is being profiled.
+    fn evaluate_statement_profiled(&mut self, index: usize) -> Result<(), ExecutionError> {
+        let measurement = Measurement::start(self.state);
+        let result = self.evaluate_statement(index);
+        self.recover(result)?;
+        let location = self.statements[index].stanza_location();
+        let stanza = self
+            .state
+            .profile()
+            .and_then(|profile| profile.stanza_index(location));
+        if let Some(stanza) = stanza {
+            Measurement::finish(measurement, self.state, stanza);
+        }
+        Ok(())
+    }
+
+    /// Records the error of a statement that failed, like [`ExecutionConfig::recover`].
+    fn recover(&mut self, result: Result<(), ExecutionError>) -> Result<(), ExecutionError> {
+        recover(self.state, self.observer, self.continue_on_error, result)
+    }
+
+    /// Evaluates any pending statements that set the given attribute of a graph node, so that
+    /// its value can be read.
+    fn force_node_attribute(
+        &mut self,
+        node: graph::GraphNodeRef,
+        name: &str,
+    ) -> Result<(), ExecutionError> {
+        let statements = self.statements;
+        let by_name = self
+            .node_attribute_statements
+            .by_name
+            .get_or_insert_with(|| {
+                let mut by_name = HashMap::new();
+                for (index, statement) in statements.iter().enumerate() {
+                    for name in statement.node_attribute_names() {
+                        by_name
+                            .entry(name.clone())
+                            .or_insert_with(Vec::new)
+                            .push(index);
+                    }
+                }
+                by_name
+            });
+        let (name, indices) = match by_name.get_key_value(name) {
+            Some((name, indices)) => (name.clone(), indices.clone()),
+            None => return Ok(()),
+        };
+        if !self.node_attribute_statements.by_node.contains_key(&name) {
+            // evaluating the graph nodes of the statements might force other attributes, so the
+            // statements are only recorded once all of them are known
+            let mut by_node = HashMap::new();
+            for index in indices {
+                if let Some(node) = statements[index].graph_node(self)? {
+                    by_node.entry(node).or_insert_with(Vec::new).push(index);
+                }
+            }
+            self.node_attribute_statements
+                .by_node
+                .insert(name.clone(), by_node);
+        }
+        let indices = self.node_attribute_statements.by_node[&name]
+            .get(&node)
+            .cloned()
+            .unwrap_or_default();
+        for index in indices {
+            self.evaluate_statement(index)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub(super) enum GraphElementKey {
     NodeAttribute(graph::GraphNodeRef, Identifier),
@@ -170,6 +538,7 @@ impl ast::Stanza {
         source: &'tree str,
         mat: &QueryMatch<'_, 'tree>,
         graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
         config: &ExecutionConfig,
         locals: &mut VariableMap<'l, LazyValue>,
         store: &mut LazyStore,
@@ -177,6 +546,7 @@ impl ast::Stanza {
         lazy_graph: &mut Vec<LazyStatement>,
         function_parameters: &mut Vec<graph::Value>,
         prev_element_debug_info: &mut HashMap<GraphElementKey, DebugInfo>,
+        inspects_edges: &mut bool,
         shorthands: &ast::AttributeShorthands,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), ExecutionError> {
@@ -193,6 +563,7 @@ impl ast::Stanza {
             let mut exec = ExecutionContext {
                 source,
                 graph,
+                state,
                 config,
                 locals,
                 current_regex_captures: &current_regex_captures,
@@ -202,6 +573,7 @@ impl ast::Stanza {
                 lazy_graph,
                 function_parameters,
                 prev_element_debug_info,
+                inspects_edges,
                 error_context,
                 shorthands,
                 cancellation_flag,
@@ -218,6 +590,10 @@ impl ast::Stanza {
 impl ast::Statement {
     fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         exec.cancellation_flag.check("executing statement")?;
+        check_deadline(exec.config.settings.deadline, "executing statement")?;
+        if let Some(observer) = exec.config.settings.observer {
+            observer.on_statement(self);
+        }
         match self {
             Self::DeclareImmutable(statement) => statement.execute_lazy(exec),
             Self::DeclareMutable(statement) => statement.execute_lazy(exec),
@@ -257,9 +633,17 @@ impl ast::Assign {
 
 impl ast::CreateGraphNode {
     fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let graph_node = exec.graph.add_graph_node();
-        self.node
-            .add_debug_attrs(&mut exec.graph[graph_node].attributes, exec.config)?;
+        exec.state.resource_usage().add_node()?;
+        let mut attributes = Attributes::new();
+        self.node.add_debug_attrs(&mut attributes, exec.config)?;
+        let graph_node = exec.state.add_graph_node(exec.graph, attributes);
+        if let Some(observer) = exec.config.settings.observer {
+            observer.on_node_created(graph_node);
+        }
+        if exec.config.settings.provenance {
+            let provenance = exec.error_context.provenance();
+            exec.graph.set_node_provenance(graph_node, provenance);
+        }
         self.node.add_lazy(exec, graph_node.into(), false)
     }
 }
@@ -317,6 +701,7 @@ impl ast::Scan {
             matches.clear();
             for (index, arm) in self.arms.iter().enumerate() {
                 exec.cancellation_flag.check("processing scan matches")?;
+                check_deadline(exec.config.settings.deadline, "processing scan matches")?;
                 let captures = arm.regex.captures(&match_string[i..]);
                 if let Some(captures) = captures {
                     if captures
@@ -356,6 +741,7 @@ impl ast::Scan {
             let mut arm_exec = ExecutionContext {
                 source: exec.source,
                 graph: exec.graph,
+                state: exec.state,
                 config: exec.config,
                 locals: &mut arm_locals,
                 current_regex_captures: &current_regex_captures,
@@ -365,6 +751,7 @@ impl ast::Scan {
                 lazy_graph: exec.lazy_graph,
                 function_parameters: exec.function_parameters,
                 prev_element_debug_info: exec.prev_element_debug_info,
+                inspects_edges: exec.inspects_edges,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
@@ -421,6 +808,7 @@ impl ast::If {
                 let mut arm_exec = ExecutionContext {
                     source: exec.source,
                     graph: exec.graph,
+                    state: exec.state,
                     config: exec.config,
                     locals: &mut arm_locals,
                     current_regex_captures: exec.current_regex_captures,
@@ -430,6 +818,7 @@ impl ast::If {
                     lazy_graph: exec.lazy_graph,
                     function_parameters: exec.function_parameters,
                     prev_element_debug_info: exec.prev_element_debug_info,
+                    inspects_edges: exec.inspects_edges,
                     error_context: exec.error_context.clone(),
                     shorthands: exec.shorthands,
                     cancellation_flag: exec.cancellation_flag,
@@ -448,7 +837,8 @@ impl ast::If {
 
 impl ast::Condition {
     // Eagerly evaluate the condition to a boolean. It assumes the argument expressions
-    // are local (i.e., `is_local = true` in the checker).
+    // are local (i.e., `is_local = true` in the checker). The condition cannot read the graph,
+    // because the lazy graph has not been evaluated yet.
     fn test_eager(&self, exec: &mut ExecutionContext) -> Result<bool, ExecutionError> {
         match self {
             Self::Some { value, .. } => Ok(!value.evaluate_eager(exec)?.is_null()),
@@ -467,6 +857,7 @@ impl ast::ForIn {
             let mut loop_exec = ExecutionContext {
                 source: exec.source,
                 graph: exec.graph,
+                state: exec.state,
                 config: exec.config,
                 locals: &mut loop_locals,
                 current_regex_captures: exec.current_regex_captures,
@@ -476,6 +867,7 @@ impl ast::ForIn {
                 lazy_graph: exec.lazy_graph,
                 function_parameters: exec.function_parameters,
                 prev_element_debug_info: exec.prev_element_debug_info,
+                inspects_edges: exec.inspects_edges,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
@@ -507,6 +899,7 @@ impl ast::Expression {
             Self::Capture(expr) => expr.evaluate_lazy(exec),
             Self::Variable(expr) => expr.evaluate_lazy(exec),
             Self::Call(expr) => expr.evaluate_lazy(exec),
+            Self::FunctionRef(expr) => expr.evaluate_lazy(exec),
             Self::RegexCapture(expr) => expr.evaluate_lazy(exec),
         }
     }
@@ -517,11 +910,21 @@ impl ast::Expression {
         self.evaluate_lazy(exec)?.evaluate(&mut EvaluationContext {
             source: exec.source,
             graph: exec.graph,
+            state: exec.state,
             functions: exec.config.functions,
+            attribute_types: &exec.config.settings.attribute_types,
+            provenance: exec.config.settings.provenance,
+            deadline: exec.config.settings.deadline,
+            observer: exec.config.settings.observer,
+            continue_on_error: exec.config.settings.continue_on_error,
             store: exec.store,
             scoped_store: exec.scoped_store,
             function_parameters: exec.function_parameters,
             prev_element_debug_info: exec.prev_element_debug_info,
+            statements: &[],
+            evaluated: &mut [],
+            node_attribute_statements: &mut NodeAttributeStatements::default(),
+            building: true,
             cancellation_flag: exec.cancellation_flag,
         })
     }
@@ -559,6 +962,7 @@ impl ast::ListComprehension {
             let mut loop_exec = ExecutionContext {
                 source: exec.source,
                 graph: exec.graph,
+                state: exec.state,
                 config: exec.config,
                 locals: &mut loop_locals,
                 current_regex_captures: exec.current_regex_captures,
@@ -568,6 +972,7 @@ impl ast::ListComprehension {
                 lazy_graph: exec.lazy_graph,
                 function_parameters: exec.function_parameters,
                 prev_element_debug_info: exec.prev_element_debug_info,
+                inspects_edges: exec.inspects_edges,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
@@ -601,6 +1006,7 @@ impl ast::SetComprehension {
             let mut loop_exec = ExecutionContext {
                 source: exec.source,
                 graph: exec.graph,
+                state: exec.state,
                 config: exec.config,
                 locals: &mut loop_locals,
                 current_regex_captures: exec.current_regex_captures,
@@ -610,6 +1016,7 @@ impl ast::SetComprehension {
                 lazy_graph: exec.lazy_graph,
                 function_parameters: exec.function_parameters,
                 prev_element_debug_info: exec.prev_element_debug_info,
+                inspects_edges: exec.inspects_edges,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
@@ -637,6 +1044,7 @@ impl ast::Capture {
 
 impl ast::Call {
     fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        *exec.inspects_edges |= exec.config.functions.reads_graph(&self.function);
         let mut parameters = Vec::new();
         for parameter in &self.parameters {
             parameters.push(parameter.evaluate_lazy(exec)?);
@@ -645,6 +1053,18 @@ impl ast::Call {
     }
 }
 
+impl ast::FunctionRef {
+    fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        let function = exec
+            .config
+            .functions
+            .get(&self.name)
+            .ok_or(ExecutionError::UndefinedFunction(format!("{}", self.name)))?;
+        *exec.inspects_edges |= function.reads_graph();
+        Ok(graph::Value::from(function).into())
+    }
+}
+
 impl ast::RegexCapture {
     fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
         let value = exec.current_regex_captures[self.match_index].clone();
@@ -745,7 +1165,7 @@ impl ast::UnscopedVariable {
         value: LazyValue,
         mutable: bool,
     ) -> Result<(), ExecutionError> {
-        if exec.config.globals.get(&self.name).is_some() {
+        if exec.config.globals.contains(&self.name) {
             return Err(ExecutionError::DuplicateVariable(format!(
                 " global {}",
                 self
@@ -762,7 +1182,7 @@ impl ast::UnscopedVariable {
         exec: &mut ExecutionContext,
         value: LazyValue,
     ) -> Result<(), ExecutionError> {
-        if exec.config.globals.get(&self.name).is_some() {
+        if exec.config.globals.contains(&self.name) {
             return Err(ExecutionError::CannotAssignImmutableVariable(format!(
                 " global {}",
                 self
@@ -815,6 +1235,7 @@ impl ast::AttributeShorthand {
         let mut shorthand_exec = ExecutionContext {
             source: exec.source,
             graph: exec.graph,
+            state: exec.state,
             config: exec.config,
             locals: &mut shorthand_locals,
             current_regex_captures: exec.current_regex_captures,
@@ -824,6 +1245,7 @@ impl ast::AttributeShorthand {
             lazy_graph: exec.lazy_graph,
             function_parameters: exec.function_parameters,
             prev_element_debug_info: exec.prev_element_debug_info,
+            inspects_edges: exec.inspects_edges,
             error_context: exec.error_context.clone(),
             shorthands: exec.shorthands,
             cancellation_flag: exec.cancellation_flag,
@@ -0,0 +1,159 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Evaluates the parts of the lazy graph that the caller asks for
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::execution::error::ExecutionError;
+use crate::execution::run_to_completion;
+use crate::execution::with_cancellation_token;
+use crate::execution::CancellationToken;
+use crate::execution::ExecutionReport;
+use crate::execution::ExecutionState;
+use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::CancellationFlag;
+
+use super::EvaluationContext;
+use super::LazyEvaluation;
+
+/// A graph whose attributes and edges are only evaluated when they are forced, which is returned
+/// by [`File::execute_on_demand_into`][`crate::ast::File::execute_on_demand_into`].
+///
+/// If forcing fails, the graph may be left with some of the attributes and edges that were
+/// being forced, and should not be forced any further.
+pub struct OnDemandGraph<'a, 'tree> {
+    graph: &'a mut Graph<'tree>,
+    state: ExecutionState,
+    evaluation: LazyEvaluation<'a, 'tree>,
+    cancellation_token: Option<CancellationToken>,
+    /// The statements that add attributes or edges to each graph node, which are found the
+    /// first time that a node is forced
+    node_statements: Option<HashMap<GraphNodeRef, Vec<usize>>>,
+}
+
+impl<'a, 'tree> OnDemandGraph<'a, 'tree> {
+    pub(crate) fn new(
+        graph: &'a mut Graph<'tree>,
+        state: ExecutionState,
+        evaluation: LazyEvaluation<'a, 'tree>,
+        cancellation_token: Option<CancellationToken>,
+    ) -> Self {
+        Self {
+            graph,
+            state,
+            evaluation,
+            cancellation_token,
+            node_statements: None,
+        }
+    }
+
+    /// Returns the graph, with the attributes and edges that have been forced so far.
+    pub fn graph(&self) -> &Graph<'tree> {
+        self.graph
+    }
+
+    /// Finishes the execution, and returns the report of what it recorded besides the graph,
+    /// including while forcing.
+    pub fn into_report(self) -> ExecutionReport {
+        self.state.into_report()
+    }
+
+    /// Evaluates the attributes of a graph node, the edges from it, and their attributes.
+    pub fn force_node(
+        &mut self,
+        node: GraphNodeRef,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        self.force_nodes(node, false, cancellation_flag)
+    }
+
+    /// Evaluates the attributes of a graph node, the edges from it, and their attributes, and
+    /// then does the same for the sinks of those edges, so that all of the nodes reachable from
+    /// `root` are complete.
+    pub fn force_reachable(
+        &mut self,
+        root: GraphNodeRef,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        self.force_nodes(root, true, cancellation_flag)
+    }
+
+    /// Evaluates everything that has not been forced yet, which leaves the graph the same as if
+    /// the file had been executed with [`File::execute_into`][`crate::ast::File::execute_into`].
+    pub fn force_all(
+        &mut self,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let graph = &mut *self.graph;
+        let state = &mut self.state;
+        let evaluation = &mut self.evaluation;
+        with_cancellation_token(
+            self.cancellation_token.as_ref(),
+            cancellation_flag,
+            |cancellation_flag| {
+                run_to_completion(evaluation.evaluate_all(graph, state, cancellation_flag))
+            },
+        )
+    }
+
+    fn force_nodes(
+        &mut self,
+        root: GraphNodeRef,
+        follow_edges: bool,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let graph = &mut *self.graph;
+        let state = &mut self.state;
+        let evaluation = &mut self.evaluation;
+        let node_statements = &mut self.node_statements;
+        with_cancellation_token(
+            self.cancellation_token.as_ref(),
+            cancellation_flag,
+            |cancellation_flag| {
+                let mut exec = evaluation.context(graph, state, cancellation_flag)?;
+                let node_statements = match node_statements {
+                    Some(node_statements) => node_statements,
+                    None => node_statements.insert(find_node_statements(&mut exec)?),
+                };
+                let mut visited = HashSet::new();
+                let mut queue = vec![root];
+                while let Some(node) = queue.pop() {
+                    if !visited.insert(node) {
+                        continue;
+                    }
+                    for index in node_statements.get(&node).into_iter().flatten() {
+                        exec.evaluate_statement_profiled(*index)?;
+                    }
+                    if follow_edges {
+                        queue.extend(exec.graph.node(node).iter_edges().map(|(sink, _)| sink));
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Returns the statements that add attributes or edges to each graph node.
+fn find_node_statements(
+    exec: &mut EvaluationContext,
+) -> Result<HashMap<GraphNodeRef, Vec<usize>>, ExecutionError> {
+    let mut node_statements = HashMap::new();
+    let statements = exec.statements;
+    for (index, statement) in statements.iter().enumerate() {
+        if let Some(node) = statement.graph_node(exec)? {
+            node_statements
+                .entry(node)
+                .or_insert_with(Vec::new)
+                .push(index);
+        }
+    }
+    Ok(node_statements)
+}
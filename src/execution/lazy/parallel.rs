@@ -0,0 +1,148 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Executes the stanzas for consecutive shards of the matches on separate threads
+
+use tree_sitter::Tree;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::number_matches;
+use crate::execution::parallel::run_in_parallel;
+use crate::execution::parallel::thread_count;
+use crate::execution::parallel::SharedConfig;
+use crate::execution::run_to_completion;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionState;
+use crate::graph::FragmentMapping;
+use crate::graph::Graph;
+
+use super::check_match_limit;
+use super::LazyMatches;
+use super::LazyScopedVariables;
+use super::LazyStore;
+
+/// The smallest number of matches that is worth executing on a separate thread.
+const MIN_SHARD_MATCHES: usize = 256;
+
+/// The largest number of shards to split the matches into, per thread of the thread pool.
+const MAX_SHARDS_PER_THREAD: usize = 4;
+
+/// How the values that were created while executing a shard are updated when the shard is merged
+/// after the shards before it.
+pub(super) struct ShardMapping {
+    /// Where the shard's graph nodes and syntax nodes ended up in the joined graph
+    pub(super) nodes: FragmentMapping,
+    /// Where the shard's store starts in the merged store
+    pub(super) store_offset: usize,
+}
+
+impl File {
+    /// Builds the lazy graph like [`File::execute_matches_lazy`], but splits the matches into
+    /// shards of consecutive matches, executes each shard on a separate thread, into a fork of
+    /// `graph`, and then merges the shards back in match order.
+    ///
+    /// The shards only share scoped variables, which are not evaluated until the lazy graph is
+    /// complete, so the merged result is the same as that of executing the matches sequentially.
+    /// Returns `None`, and leaves `graph` and `state` as they were, if there are too few matches or
+    /// threads for this to be worth it, or if any shard fails.  The caller then executes the matches
+    /// sequentially, so that it reports the same error as a sequential execution would.
+    pub(super) fn execute_matches_lazy_parallel<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Option<LazyMatches>, ExecutionError> {
+        let threads = thread_count();
+        if threads <= 1 {
+            return Ok(None);
+        }
+        let mut cursor = self.query_cursor_lazy(config);
+        let query = self.file_query()?;
+        let match_count = cursor
+            .matches(query, tree.root_node(), source.as_bytes())
+            .count();
+        let shard_count = (match_count / MIN_SHARD_MATCHES).min(MAX_SHARDS_PER_THREAD * threads);
+        if shard_count <= 1 {
+            return Ok(None);
+        }
+        let shared = SharedConfig::new(self, config, config.globals);
+
+        let base = graph.snapshot();
+        let mut tasks = Vec::with_capacity(shard_count);
+        for index in 0..shard_count {
+            let mut fragment = graph.fork();
+            let mut fragment_state = state.fork(index as u32, shard_count as u32);
+            let matches =
+                index * match_count / shard_count..(index + 1) * match_count / shard_count;
+            let shared = &shared;
+            tasks.push(move |cancellation_flag: &dyn CancellationFlag| {
+                let matches = shared.with_config(true, |config| {
+                    config
+                        .functions
+                        .on_execution_start(fragment_state.function_state());
+                    let mut cursor = self.query_cursor_lazy(config);
+                    let all_matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+                    let matches = run_to_completion(
+                        self.execute_matches_lazy(
+                            &mut fragment.0,
+                            &mut fragment_state,
+                            source,
+                            config,
+                            cancellation_flag,
+                            number_matches(all_matches)
+                                .skip(matches.start)
+                                .take(matches.len()),
+                        ),
+                    )?;
+                    check_match_limit(&cursor, &mut fragment_state);
+                    Ok::<_, ExecutionError>(matches)
+                })?;
+                Ok((fragment, fragment_state, matches))
+            });
+        }
+
+        let shards = match run_in_parallel(tasks, cancellation_flag) {
+            Ok(shards) => shards,
+            Err(ExecutionError::Cancelled(error)) => return Err(error.into()),
+            Err(_) => return Ok(None),
+        };
+
+        let mut merged = LazyMatches {
+            store: LazyStore::new(),
+            scoped_store: LazyScopedVariables::new(),
+            lazy_graph: Vec::new(),
+            inspects_edges: false,
+        };
+        for (fragment, fragment_state, matches) in shards {
+            let mapping = ShardMapping {
+                nodes: graph.join(&base, fragment)?,
+                store_offset: merged.store.len(),
+            };
+            state.join(fragment_state)?;
+            merged.append(matches, &mapping);
+        }
+        Ok(Some(merged))
+    }
+}
+
+impl LazyMatches {
+    /// Adds the matches of a later shard after these ones.
+    fn append(&mut self, other: LazyMatches, mapping: &ShardMapping) {
+        self.store.append(other.store, mapping);
+        self.scoped_store.append(other.scoped_store, mapping);
+        for mut statement in other.lazy_graph {
+            statement.remap(mapping);
+            self.lazy_graph.push(statement);
+        }
+        self.inspects_edges |= other.inspects_edges;
+    }
+}
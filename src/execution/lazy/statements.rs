@@ -12,11 +12,17 @@ use log::{debug, trace};
 use std::convert::From;
 use std::fmt;
 
+use crate::execution::check_deadline;
 use crate::execution::error::ExecutionError;
 use crate::execution::error::ResultWithExecutionError;
+use crate::execution::ExecutionConfig;
+use crate::execution::GraphElement;
 use crate::graph::Attributes;
+use crate::graph::GraphNodeRef;
 use crate::Identifier;
+use crate::Location;
 
+use super::parallel::ShardMapping;
 use super::store::DebugInfo;
 use super::values::*;
 use super::EvaluationContext;
@@ -32,8 +38,87 @@ pub(super) enum LazyStatement {
 }
 
 impl LazyStatement {
+    pub(super) fn is_create_edge(&self) -> bool {
+        matches!(self, Self::CreateEdge(_))
+    }
+
+    /// Returns the location of the stanza that produced this statement.
+    pub(super) fn stanza_location(&self) -> Location {
+        let debug_info = match self {
+            Self::AddGraphNodeAttribute(stmt) => &stmt.debug_info,
+            Self::CreateEdge(stmt) => &stmt.debug_info,
+            Self::AddEdgeAttribute(stmt) => &stmt.debug_info,
+            Self::Print(stmt) => &stmt.debug_info,
+        };
+        debug_info.stanza_location()
+    }
+
+    /// Updates the values of this statement when the shard it was created in is merged.
+    pub(super) fn remap(&mut self, mapping: &ShardMapping) {
+        match self {
+            Self::AddGraphNodeAttribute(stmt) => {
+                stmt.node.remap(mapping);
+                for attribute in &mut stmt.attributes {
+                    attribute.value.remap(mapping);
+                }
+            }
+            Self::CreateEdge(stmt) => {
+                // the attributes of the statement only hold debug information
+                stmt.source.remap(mapping);
+                stmt.sink.remap(mapping);
+            }
+            Self::AddEdgeAttribute(stmt) => {
+                stmt.source.remap(mapping);
+                stmt.sink.remap(mapping);
+                for attribute in &mut stmt.attributes {
+                    attribute.value.remap(mapping);
+                }
+            }
+            Self::Print(stmt) => {
+                for argument in &mut stmt.arguments {
+                    if let LazyPrintArgument::Value(value) = argument {
+                        value.remap(mapping);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the graph node that this statement adds attributes or edges to, if any.
+    pub(super) fn graph_node(
+        &self,
+        exec: &mut EvaluationContext,
+    ) -> Result<Option<GraphNodeRef>, ExecutionError> {
+        let node = match self {
+            Self::AddGraphNodeAttribute(stmt) => stmt
+                .node
+                .evaluate_as_graph_node(exec)
+                .with_context(|| stmt.debug_info.clone().into())?,
+            Self::CreateEdge(stmt) => stmt
+                .source
+                .evaluate_as_graph_node(exec)
+                .with_context(|| stmt.debug_info.clone().into())?,
+            Self::AddEdgeAttribute(stmt) => stmt
+                .source
+                .evaluate_as_graph_node(exec)
+                .with_context(|| stmt.debug_info.clone().into())?,
+            Self::Print(_) => return Ok(None),
+        };
+        Ok(Some(node))
+    }
+
+    /// Returns the names of the graph node attributes that this statement sets.
+    pub(super) fn node_attribute_names(&self) -> impl Iterator<Item = &Identifier> {
+        let attributes = match self {
+            Self::AddGraphNodeAttribute(stmt) => stmt.attributes.as_slice(),
+            _ => &[],
+        };
+        attributes.iter().map(|attr| &attr.name)
+    }
+
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
         exec.cancellation_flag.check("evaluating statement")?;
+        check_deadline(exec.deadline, "evaluating statement")?;
         debug!("eval {}", self);
         trace!("{{");
         let result = match self {
@@ -115,22 +200,34 @@ impl LazyAddGraphNodeAttribute {
         let node = self.node.evaluate_as_graph_node(exec)?;
         for attribute in &self.attributes {
             let value = attribute.value.evaluate(exec)?;
+            ExecutionConfig::check_attribute_type(exec.attribute_types, &attribute.name, &value)?;
+            exec.state.resource_usage().add_attribute(&value)?;
+            if let Some(observer) = exec.observer {
+                observer.on_attr_set(GraphElement::Node(node), &attribute.name, &value);
+            }
             let prev_debug_info = exec.prev_element_debug_info.insert(
                 GraphElementKey::NodeAttribute(node, attribute.name.clone()),
                 self.debug_info.clone(),
             );
-            exec.graph[node]
-                .attributes
-                .add(attribute.name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        "{} on {} at {} and {}",
-                        attribute.name,
-                        node,
-                        prev_debug_info.unwrap(),
-                        self.debug_info,
-                    ))
-                })?;
+            if !exec
+                .state
+                .add_node_attribute(exec.graph, node, attribute.name.clone(), value)
+            {
+                return Err(ExecutionError::DuplicateAttribute(format!(
+                    "{} on {} at {} and {}",
+                    attribute.name,
+                    node,
+                    prev_debug_info.unwrap(),
+                    self.debug_info,
+                )));
+            }
+            if exec.provenance {
+                exec.graph.set_node_attribute_provenance(
+                    node,
+                    attribute.name.clone(),
+                    self.debug_info.provenance(),
+                );
+            }
         }
         Ok(())
     }
@@ -176,19 +273,24 @@ impl LazyCreateEdge {
         let prev_debug_info = exec
             .prev_element_debug_info
             .insert(GraphElementKey::Edge(source, sink), self.debug_info.clone());
-        let edge = match exec.graph[source].add_edge(sink) {
-            Ok(edge) => edge,
-            Err(_) => {
-                return Err(ExecutionError::DuplicateEdge(format!(
-                    "({} -> {}) at {} and {}",
-                    source,
-                    sink,
-                    prev_debug_info.unwrap(),
-                    self.debug_info,
-                )))?
-            }
-        };
-        edge.attributes = self.attributes.clone();
+        exec.state.resource_usage().add_edge()?;
+        let attributes = self.attributes.clone();
+        if !exec.state.add_edge(exec.graph, source, sink, attributes) {
+            return Err(ExecutionError::DuplicateEdge(format!(
+                "({} -> {}) at {} and {}",
+                source,
+                sink,
+                prev_debug_info.unwrap(),
+                self.debug_info,
+            )));
+        }
+        if let Some(observer) = exec.observer {
+            observer.on_edge_created(source, sink);
+        }
+        if exec.provenance {
+            exec.graph
+                .set_edge_provenance(source, sink, self.debug_info.provenance());
+        }
         Ok(())
     }
 }
@@ -232,29 +334,45 @@ impl LazyAddEdgeAttribute {
         let sink = self.sink.evaluate_as_graph_node(exec)?;
         for attribute in &self.attributes {
             let value = attribute.value.evaluate(exec)?;
-            let edge = match exec.graph[source].get_edge_mut(sink) {
-                Some(edge) => Ok(edge),
-                None => Err(ExecutionError::UndefinedEdge(format!(
+            ExecutionConfig::check_attribute_type(exec.attribute_types, &attribute.name, &value)?;
+            exec.state.resource_usage().add_attribute(&value)?;
+            if let Some(observer) = exec.observer {
+                observer.on_attr_set(GraphElement::Edge(source, sink), &attribute.name, &value);
+            }
+            if !exec.state.has_edge(exec.graph, source, sink) {
+                return Err(ExecutionError::UndefinedEdge(format!(
                     "({} -> {}) at {}",
                     source, sink, self.debug_info,
-                ))),
-            }?;
+                )));
+            }
             let prev_debug_info = exec.prev_element_debug_info.insert(
                 GraphElementKey::EdgeAttribute(source, sink, attribute.name.clone()),
                 self.debug_info.clone(),
             );
-            edge.attributes
-                .add(attribute.name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        "{} on edge ({} -> {}) at {} and {}",
-                        attribute.name,
-                        source,
-                        sink,
-                        prev_debug_info.unwrap(),
-                        self.debug_info,
-                    ))
-                })?;
+            if !exec.state.add_edge_attribute(
+                exec.graph,
+                source,
+                sink,
+                attribute.name.clone(),
+                value,
+            ) {
+                return Err(ExecutionError::DuplicateAttribute(format!(
+                    "{} on edge ({} -> {}) at {} and {}",
+                    attribute.name,
+                    source,
+                    sink,
+                    prev_debug_info.unwrap(),
+                    self.debug_info,
+                )));
+            }
+            if exec.provenance {
+                exec.graph.set_edge_attribute_provenance(
+                    source,
+                    sink,
+                    attribute.name.clone(),
+                    self.debug_info.provenance(),
+                );
+            }
         }
         Ok(())
     }
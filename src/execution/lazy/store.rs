@@ -13,16 +13,20 @@ use std::cell::Cell;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
-use std::rc::Rc;
 
+use crate::execution::check_deadline;
 use crate::execution::error::Context;
 use crate::execution::error::ExecutionError;
 use crate::execution::error::ResultWithExecutionError;
 use crate::execution::error::StatementContext;
+use crate::execution::limits::value_bytes;
 use crate::graph;
+use crate::graph::Provenance;
 use crate::graph::SyntaxNodeRef;
 use crate::Identifier;
+use crate::Location;
 
+use super::parallel::ShardMapping;
 use super::values::*;
 use super::EvaluationContext;
 
@@ -43,6 +47,10 @@ impl LazyVariable {
     ) -> Result<graph::Value, ExecutionError> {
         exec.store.evaluate(self, exec)
     }
+
+    pub(super) fn remap(&mut self, mapping: &ShardMapping) {
+        self.store_location += mapping.store_offset;
+    }
 }
 
 impl fmt::Display for LazyVariable {
@@ -55,12 +63,16 @@ impl fmt::Display for LazyVariable {
 #[derive(Default)]
 pub(super) struct LazyStore {
     elements: Vec<Thunk>,
+    bytes: Cell<usize>, // approximate size of the thunks, whether they are forced or not
+    forcing: RefCell<Vec<usize>>, // locations of the thunks that are being forced, innermost last
 }
 
 impl LazyStore {
     pub(super) fn new() -> Self {
         Self {
             elements: Vec::new(),
+            bytes: Cell::new(0),
+            forcing: RefCell::new(Vec::new()),
         }
     }
 
@@ -68,6 +80,8 @@ impl LazyStore {
         let store_location = self.elements.len();
         let variable = LazyVariable::new(store_location);
         trace!("store {} = {}", store_location, value);
+        self.bytes
+            .set(self.bytes.get() + std::mem::size_of::<Thunk>() + value.approximate_bytes());
         self.elements.push(Thunk::new(value, debug_info));
         variable
     }
@@ -77,16 +91,102 @@ impl LazyStore {
         variable: &LazyVariable,
         exec: &mut EvaluationContext,
     ) -> Result<graph::Value, ExecutionError> {
-        let variable = &self.elements[variable.store_location];
-        let debug_info = variable.debug_info.clone();
-        let value = variable.force(exec).with_context(|| debug_info.0.into())?;
+        let debug_info = self.elements[variable.store_location].debug_info.clone();
+        let value = self
+            .force(variable.store_location, exec)
+            .with_context(|| debug_info.0.into())?;
         Ok(value)
     }
 
+    /// Forces the thunk at the given location, keeping track of the thunks that are being forced,
+    /// so that a thunk that depends on itself is reported with the whole cycle, and the depth of
+    /// the thunks that are forced at once is limited.
+    fn force(
+        &self,
+        store_location: usize,
+        exec: &mut EvaluationContext,
+    ) -> Result<graph::Value, ExecutionError> {
+        let thunk = &self.elements[store_location];
+        if matches!(*thunk.state.borrow(), ThunkState::Forcing) {
+            return Err(ExecutionError::RecursivelyDefinedVariable(
+                self.cycle(store_location),
+            ));
+        }
+        let depth = {
+            let mut forcing = self.forcing.borrow_mut();
+            forcing.push(store_location);
+            forcing.len()
+        };
+        let result = match exec.state.resource_usage().check_forcing_depth(depth) {
+            Ok(()) => thunk.force(exec, &self.bytes),
+            Err(error) => Err(error),
+        };
+        self.forcing.borrow_mut().pop();
+        result
+    }
+
+    /// Describes the cycle of thunks that are being forced, from the one at the given location to
+    /// the innermost one, which depends on it again.
+    fn cycle(&self, store_location: usize) -> String {
+        let forcing = self.forcing.borrow();
+        let start = forcing
+            .iter()
+            .position(|location| *location == store_location)
+            .unwrap_or(forcing.len());
+        forcing[start..]
+            .iter()
+            .chain(std::iter::once(&store_location))
+            .map(|location| self.elements[*location].debug_info.0.statement.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns the approximate number of bytes that the thunks in this store take in memory.
+    pub(super) fn bytes(&self) -> usize {
+        self.bytes.get()
+    }
+
+    pub(super) fn is_forced(&self, variable: &LazyVariable) -> bool {
+        matches!(
+            *self.elements[variable.store_location].state.borrow(),
+            ThunkState::Forced(_)
+        )
+    }
+
+    /// Adds the thunks of a store from a later shard to the end of this one.
+    pub(super) fn append(&mut self, other: LazyStore, mapping: &ShardMapping) {
+        self.bytes.set(self.bytes.get() + other.bytes.get());
+        for mut thunk in other.elements {
+            thunk.remap(mapping);
+            self.elements.push(thunk);
+        }
+    }
+
     pub(super) fn evaluate_all(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
-        for variable in &self.elements {
+        for (store_location, variable) in self.elements.iter().enumerate() {
             let debug_info = variable.debug_info.clone();
-            variable.force(exec).with_context(|| debug_info.0.into())?;
+            self.force(store_location, exec)
+                .with_context(|| debug_info.0.into())?;
+        }
+        Ok(())
+    }
+
+    /// Forces the thunks that can be forced before the lazy graph is evaluated, in order, which
+    /// replaces their expressions with their values.  A thunk that fails is left unforced, so that
+    /// evaluating the lazy graph reports its error as it would otherwise.
+    pub(super) fn force_eagerly(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
+        for variable in &self.elements {
+            let eager = match &*variable.state.borrow() {
+                ThunkState::Unforced(value) => value.can_force_eagerly(self, exec.functions),
+                _ => false,
+            };
+            if eager {
+                variable.force_eagerly(exec, &self.bytes)?;
+            }
         }
         Ok(())
     }
@@ -134,6 +234,30 @@ impl LazyScopedVariables {
         }
     }
 
+    /// Adds the values of the variables of a later shard after the values of this one.
+    pub(super) fn append(&mut self, other: LazyScopedVariables, mapping: &ShardMapping) {
+        for (name, values) in other.variables {
+            let pairs = match values.into_inner() {
+                ScopedValues::Unforced(pairs) => pairs,
+                _ => panic!(
+                    "scoped variable {} forced before lazy graph was built",
+                    name
+                ),
+            };
+            let values = self
+                .variables
+                .entry(name)
+                .or_insert_with(|| Cell::new(ScopedValues::new()));
+            if let ScopedValues::Unforced(existing) = values.get_mut() {
+                for (mut scope, mut value, debug_info) in pairs {
+                    scope.remap(mapping);
+                    value.remap(mapping);
+                    existing.push((scope, value, debug_info));
+                }
+            }
+        }
+    }
+
     pub(super) fn evaluate(
         &self,
         scope: &SyntaxNodeRef,
@@ -222,7 +346,7 @@ impl ScopedValues {
 
 /// Thunk holding a lazy value or a forced graph value
 struct Thunk {
-    state: Rc<RefCell<ThunkState>>,
+    state: RefCell<ThunkState>,
     debug_info: DebugInfo,
 }
 
@@ -245,19 +369,31 @@ impl fmt::Display for ThunkState {
 impl Thunk {
     fn new(value: LazyValue, debug_info: DebugInfo) -> Thunk {
         Thunk {
-            state: Rc::new(RefCell::new(ThunkState::Unforced(value))),
+            state: RefCell::new(ThunkState::Unforced(value)),
             debug_info,
         }
     }
 
-    fn force(&self, exec: &mut EvaluationContext) -> Result<graph::Value, ExecutionError> {
+    /// Forces this thunk, updating the size of the store that holds it in `bytes`.
+    fn force(
+        &self,
+        exec: &mut EvaluationContext,
+        bytes: &Cell<usize>,
+    ) -> Result<graph::Value, ExecutionError> {
+        exec.cancellation_flag.check("forcing variable")?;
+        check_deadline(exec.deadline, "forcing variable")?;
         let state = self.state.replace(ThunkState::Forcing);
         trace!("force {}", state);
         let value = match state {
             ThunkState::Unforced(value) => {
                 // it is important that we do not hold a borrow of self.forced_values when executing self.value.evaluate
-                let value = value.evaluate(exec)?;
-                Ok(value)
+                let forced = value.evaluate(exec)?;
+                if let Some(observer) = exec.observer {
+                    observer.on_thunk_forced(self.debug_info.0.statement_location, &forced);
+                }
+                bytes.set(bytes.get() + value_bytes(&forced) - value.approximate_bytes());
+                exec.state.resource_usage().check_lazy_bytes(bytes.get())?;
+                Ok(forced)
             }
             ThunkState::Forced(value) => Ok(value),
             ThunkState::Forcing => Err(ExecutionError::RecursivelyDefinedVariable(format!(
@@ -268,12 +404,67 @@ impl Thunk {
         *self.state.borrow_mut() = ThunkState::Forced(value.clone());
         Ok(value)
     }
+
+    /// Forces this unforced thunk before the lazy graph is evaluated.  If that fails, the thunk is
+    /// left unforced, and only errors that stop the execution are returned.
+    fn force_eagerly(
+        &self,
+        exec: &mut EvaluationContext,
+        bytes: &Cell<usize>,
+    ) -> Result<(), ExecutionError> {
+        let value = match self.state.replace(ThunkState::Forcing) {
+            ThunkState::Unforced(value) => value,
+            state => {
+                self.state.replace(state);
+                return Ok(());
+            }
+        };
+        trace!("force eagerly {}", value);
+        match value.evaluate(exec) {
+            Ok(forced) => {
+                if let Some(observer) = exec.observer {
+                    observer.on_thunk_forced(self.debug_info.0.statement_location, &forced);
+                }
+                bytes.set(bytes.get() + value_bytes(&forced) - value.approximate_bytes());
+                self.state.replace(ThunkState::Forced(forced));
+                Ok(())
+            }
+            Err(error) => {
+                self.state.replace(ThunkState::Unforced(value));
+                if error.stops_execution() {
+                    Err(error)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    fn remap(&mut self, mapping: &ShardMapping) {
+        match self.state.get_mut() {
+            ThunkState::Unforced(value) => value.remap(mapping),
+            ThunkState::Forced(value) => *value = mapping.nodes.remap(value),
+            ThunkState::Forcing => {}
+        }
+    }
 }
 
 /// Debug info for tracking origins of values
 #[derive(Debug, Clone)]
 pub(super) struct DebugInfo(StatementContext);
 
+impl DebugInfo {
+    /// Returns the provenance of graph elements created by the statement.
+    pub(super) fn provenance(&self) -> Provenance {
+        self.0.provenance()
+    }
+
+    /// Returns the location of the stanza that the statement belongs to.
+    pub(super) fn stanza_location(&self) -> Location {
+        self.0.stanza_location
+    }
+}
+
 impl From<StatementContext> for DebugInfo {
     fn from(value: StatementContext) -> Self {
         Self(value)
@@ -13,11 +13,17 @@ use std::convert::From;
 use std::fmt;
 
 use crate::execution::error::ExecutionError;
+use crate::execution::limits::value_bytes;
+use crate::execution::ExecutionState;
+use crate::functions::Functions;
+use crate::functions::LazyParameters;
+use crate::graph::Graph;
 use crate::graph::GraphNodeRef;
 use crate::graph::SyntaxNodeRef;
 use crate::graph::Value;
 use crate::Identifier;
 
+use super::parallel::ShardMapping;
 use super::store::*;
 use super::EvaluationContext;
 
@@ -121,6 +127,12 @@ impl LazyValue {
         exec.cancellation_flag.check("evaluating value")?;
         trace!("eval {} {{", self);
         let ret = match self {
+            Self::Value(Value::Function(function)) if exec.building && function.reads_graph() => {
+                Err(ExecutionError::GraphReadBeforeEvaluation(format!(
+                    "reference to {}",
+                    function.name()
+                )))
+            }
             Self::Value(value) => Ok(value.clone()),
             Self::List(expr) => expr.evaluate(exec),
             Self::Set(expr) => expr.evaluate(exec),
@@ -132,6 +144,56 @@ impl LazyValue {
         Ok(ret)
     }
 
+    /// Updates the graph nodes, syntax nodes, and store locations that this value refers to when
+    /// the shard it was created in is merged.
+    pub(super) fn remap(&mut self, mapping: &ShardMapping) {
+        match self {
+            Self::Value(value) => *value = mapping.nodes.remap(value),
+            Self::List(expr) => expr.elements.iter_mut().for_each(|e| e.remap(mapping)),
+            Self::Set(expr) => expr.elements.iter_mut().for_each(|e| e.remap(mapping)),
+            Self::Variable(expr) => expr.remap(mapping),
+            Self::ScopedVariable(expr) => expr.scope.remap(mapping),
+            Self::Call(expr) => expr.arguments.iter_mut().for_each(|e| e.remap(mapping)),
+        }
+    }
+
+    /// Returns the approximate number of bytes that this value takes in memory.
+    pub(super) fn approximate_bytes(&self) -> usize {
+        let elements = |elements: &[LazyValue]| -> usize {
+            elements.iter().map(LazyValue::approximate_bytes).sum()
+        };
+        match self {
+            Self::Value(value) => value_bytes(value),
+            Self::List(expr) => std::mem::size_of::<Self>() + elements(&expr.elements),
+            Self::Set(expr) => std::mem::size_of::<Self>() + elements(&expr.elements),
+            Self::Variable(_) => std::mem::size_of::<Self>(),
+            Self::ScopedVariable(expr) => {
+                std::mem::size_of::<Self>() + expr.scope.approximate_bytes()
+            }
+            Self::Call(expr) => std::mem::size_of::<Self>() + elements(&expr.arguments),
+        }
+    }
+
+    /// Returns whether this value can be evaluated before the lazy graph is, because it only
+    /// refers to forced thunks, and does not depend on scoped variables or on the attributes or
+    /// edges of the graph.
+    pub(super) fn can_force_eagerly(&self, store: &LazyStore, functions: &Functions) -> bool {
+        let elements = |elements: &[LazyValue]| {
+            elements
+                .iter()
+                .all(|e| e.can_force_eagerly(store, functions))
+        };
+        match self {
+            Self::Value(Value::Function(function)) => !function.reads_graph(),
+            Self::Value(_) => true,
+            Self::List(expr) => elements(&expr.elements),
+            Self::Set(expr) => elements(&expr.elements),
+            Self::Variable(expr) => store.is_forced(expr),
+            Self::ScopedVariable(_) => false,
+            Self::Call(expr) => !functions.reads_graph(&expr.function) && elements(&expr.arguments),
+        }
+    }
+
     pub(super) fn evaluate_as_graph_node(
         &self,
         exec: &mut EvaluationContext,
@@ -218,7 +280,9 @@ impl LazyList {
             .iter()
             .map(|e| e.evaluate(exec))
             .collect::<Result<_, _>>()?;
-        Ok(Value::List(elements))
+        exec.state
+            .resource_usage()
+            .check_length(Value::List(elements))
     }
 }
 
@@ -255,7 +319,9 @@ impl LazySet {
             .iter()
             .map(|e| e.evaluate(exec))
             .collect::<Result<_, _>>()?;
-        Ok(Value::Set(elements))
+        exec.state
+            .resource_usage()
+            .check_length(Value::Set(elements))
     }
 }
 
@@ -291,14 +357,36 @@ impl LazyCall {
     }
 
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let result = self.evaluate_unchecked(exec)?;
+        exec.state.resource_usage().check_length(result)
+    }
+
+    fn evaluate_unchecked(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let function = exec.functions.lookup(&self.function)?;
+        if exec.building && function.reads_graph() {
+            return Err(ExecutionError::GraphReadBeforeEvaluation(format!(
+                "call to {}",
+                self.function
+            )));
+        }
+        if function.lazy_parameters() {
+            let source = exec.source;
+            return function.call_lazy(
+                source,
+                &mut LazyCallParameters {
+                    exec,
+                    arguments: self.arguments.iter(),
+                },
+            );
+        }
         for argument in &self.arguments {
             let argument = argument.evaluate(exec)?;
             exec.function_parameters.push(argument);
         }
 
-        exec.functions.call(
-            &self.function,
+        function.call(
             exec.graph,
+            exec.state,
             exec.source,
             &mut exec
                 .function_parameters
@@ -307,6 +395,44 @@ impl LazyCall {
     }
 }
 
+/// The arguments of a call to a function that evaluates its parameters on demand
+struct LazyCallParameters<'e, 'a, 'tree> {
+    exec: &'e mut EvaluationContext<'a, 'tree>,
+    arguments: std::slice::Iter<'e, LazyValue>,
+}
+
+impl<'tree> LazyParameters<'tree> for LazyCallParameters<'_, '_, 'tree> {
+    fn param(&mut self) -> Result<Value, ExecutionError> {
+        let argument = self.arguments.next().ok_or_else(|| {
+            ExecutionError::InvalidParameters("expected more parameters".to_string())
+        })?;
+        argument.evaluate(self.exec)
+    }
+
+    fn skip(&mut self) -> Result<(), ExecutionError> {
+        self.arguments.next().ok_or_else(|| {
+            ExecutionError::InvalidParameters("expected more parameters".to_string())
+        })?;
+        Ok(())
+    }
+
+    fn remaining(&self) -> usize {
+        self.arguments.len()
+    }
+
+    fn context(&mut self) -> (&mut Graph<'tree>, &mut ExecutionState) {
+        (self.exec.graph, self.exec.state)
+    }
+
+    fn require_node_attribute(
+        &mut self,
+        node: GraphNodeRef,
+        name: &str,
+    ) -> Result<(), ExecutionError> {
+        self.exec.force_node_attribute(node, name)
+    }
+}
+
 impl fmt::Display for LazyCall {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(call '{}", self.function)?;
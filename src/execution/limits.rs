@@ -0,0 +1,196 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Limits on how much an execution can add to a graph
+
+use crate::execution::error::ExecutionError;
+use crate::graph::Value;
+
+/// Limits on how much an execution can add to the graph, which stop inputs or rules that make the
+/// graph grow without bound from exhausting the resources of the process.  Each limit is disabled
+/// when it is `None`, which is the default.  An execution that exceeds a limit fails with
+/// [`ExecutionError::ResourceLimitExceeded`], except for the memory budget of lazy evaluation,
+/// which fails with [`ExecutionError::MemoryBudgetExceeded`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The maximum number of graph nodes that an execution can create
+    pub max_nodes: Option<usize>,
+    /// The maximum number of edges that an execution can create
+    pub max_edges: Option<usize>,
+    /// The maximum total size of the attributes that an execution can add to graph nodes and
+    /// edges, which is the total length in bytes of the strings in their values
+    pub max_attribute_bytes: Option<usize>,
+    /// The maximum number of elements of the list, set, and map values that an execution creates
+    pub max_list_length: Option<usize>,
+    /// The memory budget of lazy evaluation, which is the maximum approximate number of bytes that
+    /// the variables of the stanzas can take in memory, whether their values have been computed
+    /// or not.  It does not apply to strict evaluation, which does not keep variables after each
+    /// stanza match.
+    pub max_lazy_bytes: Option<usize>,
+    /// What lazy evaluation does when its variables take more memory than `max_lazy_bytes`
+    pub lazy_memory_policy: LazyMemoryPolicy,
+    /// The maximum number of variables that lazy evaluation computes the values of at once,
+    /// because the value of each depends on the next.  Computing a long chain of such variables
+    /// needs a deep native stack, so set this limit to make those chains fail instead of
+    /// overflowing the stack of the thread that executes them.
+    pub max_forcing_depth: Option<usize>,
+}
+
+/// What lazy evaluation does when the variables of the stanzas exceed the memory budget in
+/// [`ResourceLimits::max_lazy_bytes`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LazyMemoryPolicy {
+    /// Fail with [`ExecutionError::MemoryBudgetExceeded`]
+    #[default]
+    Fail,
+    /// While the stanzas are executed, compute the values of the variables that can be computed
+    /// before the graph is built, and drop the expressions that they were defined by, failing only
+    /// if the variables still exceed the budget.  A variable can be computed early unless it
+    /// depends on a scoped variable, or on a function that reads the attributes or edges of the
+    /// graph.  Functions that create graph nodes create them earlier, so the nodes may be numbered
+    /// differently than they would be otherwise.  Once the stanzas are executed, all variables are
+    /// computed anyway, so exceeding the budget then fails.
+    ForceEagerly,
+}
+
+/// How much an execution has added to a graph, and how much it can add.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ResourceUsage {
+    limits: ResourceLimits,
+    nodes: usize,
+    edges: usize,
+    attribute_bytes: usize,
+}
+
+impl ResourceUsage {
+    pub(crate) fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the usage of a fork of the graph, which has the same limits, and has not added
+    /// anything yet.
+    pub(crate) fn fork(&self) -> Self {
+        Self::new(self.limits.clone())
+    }
+
+    /// Adds the usage of a fork of the graph to this one.
+    pub(crate) fn join(&mut self, other: &ResourceUsage) -> Result<(), ExecutionError> {
+        self.nodes += other.nodes;
+        self.edges += other.edges;
+        self.attribute_bytes += other.attribute_bytes;
+        check("graph nodes", self.nodes, self.limits.max_nodes)?;
+        check("edges", self.edges, self.limits.max_edges)?;
+        check(
+            "bytes of attributes",
+            self.attribute_bytes,
+            self.limits.max_attribute_bytes,
+        )
+    }
+
+    /// Returns the number of graph nodes that the execution has created.
+    pub(crate) fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Returns the number of edges that the execution has created.
+    pub(crate) fn edges(&self) -> usize {
+        self.edges
+    }
+
+    pub(crate) fn add_node(&mut self) -> Result<(), ExecutionError> {
+        self.nodes += 1;
+        check("graph nodes", self.nodes, self.limits.max_nodes)
+    }
+
+    pub(crate) fn add_edge(&mut self) -> Result<(), ExecutionError> {
+        self.edges += 1;
+        check("edges", self.edges, self.limits.max_edges)
+    }
+
+    pub(crate) fn add_attribute(&mut self, value: &Value) -> Result<(), ExecutionError> {
+        self.attribute_bytes += string_bytes(value);
+        check(
+            "bytes of attributes",
+            self.attribute_bytes,
+            self.limits.max_attribute_bytes,
+        )
+    }
+
+    /// Checks the approximate number of bytes that the variables of lazy evaluation take.
+    pub(crate) fn check_lazy_bytes(&self, bytes: usize) -> Result<(), ExecutionError> {
+        match self.limits.max_lazy_bytes {
+            Some(budget) if bytes > budget => Err(ExecutionError::MemoryBudgetExceeded {
+                used: bytes,
+                budget,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the number of variables that lazy evaluation is computing the values of at once.
+    pub(crate) fn check_forcing_depth(&self, depth: usize) -> Result<(), ExecutionError> {
+        check("nested variables", depth, self.limits.max_forcing_depth)
+    }
+
+    pub(crate) fn lazy_memory_policy(&self) -> LazyMemoryPolicy {
+        self.limits.lazy_memory_policy
+    }
+
+    /// Checks the number of elements of a list, set, or map value, and returns the value.
+    pub(crate) fn check_length(&self, value: Value) -> Result<Value, ExecutionError> {
+        let length = match &value {
+            Value::List(values) => values.len(),
+            Value::Set(values) => values.len(),
+            Value::Map(values) => values.len(),
+            _ => return Ok(value),
+        };
+        check("list elements", length, self.limits.max_list_length)?;
+        Ok(value)
+    }
+}
+
+fn check(what: &str, count: usize, limit: Option<usize>) -> Result<(), ExecutionError> {
+    match limit {
+        Some(limit) if count > limit => Err(ExecutionError::ResourceLimitExceeded(format!(
+            "more than {} {}",
+            limit, what
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the approximate number of bytes that a value takes in memory.
+pub(crate) fn value_bytes(value: &Value) -> usize {
+    std::mem::size_of::<Value>()
+        + match value {
+            Value::String(string) => string.len(),
+            Value::List(values) => values.iter().map(value_bytes).sum(),
+            Value::Set(values) => values.iter().map(value_bytes).sum(),
+            Value::Map(values) => values
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value_bytes(value))
+                .sum(),
+            _ => 0,
+        }
+}
+
+/// Returns the total length of the strings in a value.
+fn string_bytes(value: &Value) -> usize {
+    match value {
+        Value::String(string) => string.len(),
+        Value::List(values) => values.iter().map(string_bytes).sum(),
+        Value::Set(values) => values.iter().map(string_bytes).sum(),
+        Value::Map(values) => values
+            .iter()
+            .map(|(name, value)| name.as_str().len() + string_bytes(value))
+            .sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,68 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Hooks that observe the progress of an execution
+
+use tree_sitter::Node;
+
+use crate::ast::Stanza;
+use crate::ast::Statement;
+use crate::execution::error::ExecutionError;
+use crate::graph::GraphNodeRef;
+use crate::graph::Value;
+use crate::Identifier;
+use crate::Location;
+
+/// Observes the progress of an execution, so that hosts can implement progress bars, debuggers,
+/// and metrics on top of it.  Install an observer with
+/// [`ExecutionConfig::observer`][`crate::ExecutionConfig::observer`].  Every method does nothing
+/// by default, so implementations only need to provide the ones they are interested in.
+///
+/// With parallel execution, the methods are called from the threads that execute the stanzas, so
+/// they can be called concurrently, and the graph nodes that they are given refer to the graph
+/// that each thread builds, which are numbered differently once they are joined into the result.
+pub trait ExecutionObserver: Sync {
+    /// Called before the matches of a stanza are executed.  The stanzas that do not set their own
+    /// match limit share a single query, so this is called for all of them before any of their
+    /// matches is executed.
+    fn on_stanza_start(&self, _stanza: &Stanza) {}
+
+    /// Called before a stanza is executed for a match, with the syntax node that the whole stanza
+    /// query matched.
+    fn on_match(&self, _stanza: &Stanza, _node: Node) {}
+
+    /// Called before a statement is executed for a match.  With lazy evaluation, this is called
+    /// when the statements are executed to build the lazy graph, and the values they refer to are
+    /// computed later, when their thunks are forced.
+    fn on_statement(&self, _statement: &Statement) {}
+
+    /// Called when lazy evaluation forces the thunk of a variable, with the location of the
+    /// statement that defined the variable and the value that it was computed to be.
+    fn on_thunk_forced(&self, _statement_location: Location, _value: &Value) {}
+
+    /// Called when a `node` statement creates a graph node.
+    fn on_node_created(&self, _node: GraphNodeRef) {}
+
+    /// Called when an `edge` statement creates an edge.
+    fn on_edge_created(&self, _source: GraphNodeRef, _sink: GraphNodeRef) {}
+
+    /// Called before an `attr` statement adds an attribute to a graph node or edge, with the
+    /// value of the attribute, even if the execution is a dry run.
+    fn on_attr_set(&self, _element: GraphElement, _name: &Identifier, _value: &Value) {}
+
+    /// Called when the execution fails, with the error that it returns, or when it records an
+    /// error and continues, if it is configured to with
+    /// [`ExecutionConfig::continue_on_error`][`crate::ExecutionConfig::continue_on_error`].
+    fn on_error(&self, _error: &ExecutionError) {}
+}
+
+/// A graph node or edge that an [`ExecutionObserver`] is told about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphElement {
+    Node(GraphNodeRef),
+    Edge(GraphNodeRef, GraphNodeRef),
+}
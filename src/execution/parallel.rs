@@ -0,0 +1,207 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Runs parts of an execution on separate threads, if the `parallel` feature is enabled
+
+#[cfg(feature = "parallel")]
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::AtomicUsize;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::Ordering;
+#[cfg(feature = "parallel")]
+use std::sync::mpsc;
+#[cfg(feature = "parallel")]
+use std::time::Duration;
+
+#[cfg(feature = "parallel")]
+use rayon::Yield;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+#[cfg(feature = "parallel")]
+use crate::execution::CancellationError;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionSettings;
+use crate::execution::Yielding;
+use crate::functions::Functions;
+use crate::graph::Value;
+use crate::variables::Globals;
+use crate::Identifier;
+
+/// How often to check the caller's cancellation flag while tasks are executing.
+#[cfg(feature = "parallel")]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The settings of an execution, in a form that can be shared between threads.
+pub(super) struct SharedConfig<'a> {
+    functions: &'a Functions,
+    globals: Vec<(Identifier, Value)>,
+    settings: ExecutionSettings<'a>,
+}
+
+impl<'a> SharedConfig<'a> {
+    /// Copies the settings of `config`.  Global variables can refer to variable environments
+    /// that cannot be shared between threads, so only the values of the global variables that
+    /// `file` declares are copied, from `globals`, which must already have been checked.
+    pub(super) fn new(file: &File, config: &ExecutionConfig<'a, '_>, globals: &Globals) -> Self {
+        let mut settings = config.settings.clone();
+        settings.deadline = config.deadline();
+        Self {
+            functions: config.functions,
+            globals: file
+                .globals
+                .iter()
+                .filter_map(|global| {
+                    Some((global.name.clone(), globals.get(&global.name)?.clone()))
+                })
+                .collect(),
+            settings,
+        }
+    }
+
+    /// Calls `f` with an execution configuration that has these settings.
+    pub(super) fn with_config<R, F>(&self, lazy: bool, f: F) -> R
+    where
+        F: FnOnce(&ExecutionConfig) -> R,
+    {
+        let mut globals = Globals::new();
+        for (name, value) in &self.globals {
+            let _ = globals.add(name.clone(), value.clone());
+        }
+        let mut settings = self.settings.clone();
+        settings.lazy = lazy;
+        settings.parallel = false;
+        settings.cancellation_token = None;
+        settings.yielding = Yielding::Never;
+        f(&ExecutionConfig {
+            functions: self.functions,
+            globals: &globals,
+            settings,
+        })
+    }
+}
+
+/// Returns the number of threads that [`run_in_parallel`] runs tasks on, which is 1 if the
+/// `parallel` feature is disabled.
+pub(super) fn thread_count() -> usize {
+    #[cfg(feature = "parallel")]
+    return rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    return 1;
+}
+
+/// Runs tasks on the thread pool, and returns their results in order.  If any of the tasks fail,
+/// returns the error of the first one that did.
+///
+/// Each task is given a cancellation flag, which is raised when `cancellation_flag` is, or when a
+/// task before it fails, since its result would be discarded anyway.  Tasks before a failed one
+/// keep running, so that which error is returned does not depend on how the tasks are scheduled.
+#[cfg(feature = "parallel")]
+pub(super) fn run_in_parallel<T, F>(
+    tasks: Vec<F>,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<T>, ExecutionError>
+where
+    F: FnOnce(&dyn CancellationFlag) -> Result<T, ExecutionError> + Send,
+    T: Send,
+{
+    let cancelled = AtomicBool::new(false);
+    let first_failed = AtomicUsize::new(usize::MAX);
+    let mut results = Vec::with_capacity(tasks.len());
+    results.resize_with(tasks.len(), || None);
+    let mut cancellation = None;
+    let (sender, receiver) = mpsc::channel();
+    rayon::in_place_scope(|scope| {
+        for (index, task) in tasks.into_iter().enumerate() {
+            let sender = sender.clone();
+            let cancellation_flag = TaskCancellation {
+                index,
+                cancelled: &cancelled,
+                first_failed: &first_failed,
+            };
+            scope.spawn(move |_| {
+                let result = task(&cancellation_flag);
+                if result.is_err() {
+                    cancellation_flag
+                        .first_failed
+                        .fetch_min(index, Ordering::SeqCst);
+                }
+                let _ = sender.send((index, result));
+            });
+        }
+        drop(sender);
+
+        loop {
+            if cancellation.is_none() {
+                if let Err(error) = cancellation_flag.check("executing in parallel") {
+                    cancelled.store(true, Ordering::SeqCst);
+                    cancellation = Some(error);
+                }
+            }
+            // If this thread belongs to the thread pool, help to execute the tasks, so that they
+            // make progress even if the pool has no other threads.
+            let received = match rayon::yield_now() {
+                Some(Yield::Executed) => receiver.try_recv().ok(),
+                _ => match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok(received) => Some(received),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                },
+            };
+            if let Some((index, result)) = received {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    if let Some(error) = cancellation {
+        return Err(error.into());
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("missing task result"))
+        .collect()
+}
+
+/// Runs tasks one after the other on the current thread, because the `parallel` feature is
+/// disabled, and returns their results in order.  If any of the tasks fail, returns the error of
+/// the first one that did, without running the tasks after it.
+#[cfg(not(feature = "parallel"))]
+pub(super) fn run_in_parallel<T, F>(
+    tasks: Vec<F>,
+    cancellation_flag: &dyn CancellationFlag,
+) -> Result<Vec<T>, ExecutionError>
+where
+    F: FnOnce(&dyn CancellationFlag) -> Result<T, ExecutionError>,
+{
+    tasks
+        .into_iter()
+        .map(|task| task(cancellation_flag))
+        .collect()
+}
+
+/// The cancellation flag of one task of [`run_in_parallel`].
+#[cfg(feature = "parallel")]
+struct TaskCancellation<'a> {
+    index: usize,
+    cancelled: &'a AtomicBool,
+    first_failed: &'a AtomicUsize,
+}
+
+#[cfg(feature = "parallel")]
+impl CancellationFlag for TaskCancellation<'_> {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        if self.cancelled.load(Ordering::Relaxed)
+            || self.first_failed.load(Ordering::Relaxed) < self.index
+        {
+            return Err(CancellationError(at));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Statistics about where an execution spends its time
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::ast::File;
+use crate::execution::ExecutionState;
+use crate::Location;
+
+/// Statistics about the work that an execution did for each stanza of the file, which are collected
+/// when [`ExecutionConfig::profile`][`crate::ExecutionConfig::profile`] is enabled, and returned by
+/// [`ExecutionReport::profile`][`crate::ExecutionReport::profile`].  Its `Display` implementation
+/// prints them as a table.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    stanzas: Vec<StanzaProfile>,
+    stanza_indices: HashMap<Location, usize>,
+}
+
+/// Statistics about the work that an execution did for a stanza
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StanzaProfile {
+    /// The location of the stanza in the file
+    pub location: Location,
+    /// The number of matches that the stanza was executed for
+    pub matches: usize,
+    /// The time spent finding the stanza's matches
+    pub query_time: Duration,
+    /// The time spent executing the stanza's statements.  With lazy evaluation, this includes
+    /// the time spent evaluating the graph statements that the stanza's statements produced, and
+    /// any values and statements that they forced.
+    pub evaluation_time: Duration,
+    /// The number of graph nodes that the stanza's statements created
+    pub nodes: usize,
+    /// The number of edges that the stanza's statements created
+    pub edges: usize,
+}
+
+impl Profile {
+    pub(crate) fn new(file: &File) -> Self {
+        Self {
+            stanzas: file
+                .stanzas
+                .iter()
+                .map(|stanza| StanzaProfile {
+                    location: stanza.range.start,
+                    ..Default::default()
+                })
+                .collect(),
+            stanza_indices: file
+                .stanzas
+                .iter()
+                .enumerate()
+                .map(|(index, stanza)| (stanza.range.start, index))
+                .collect(),
+        }
+    }
+
+    /// Returns the statistics of each stanza, in file order.
+    pub fn stanzas(&self) -> &[StanzaProfile] {
+        &self.stanzas
+    }
+
+    /// Returns an empty profile for a fork of the graph.
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            stanzas: self
+                .stanzas
+                .iter()
+                .map(|stanza| StanzaProfile {
+                    location: stanza.location,
+                    ..Default::default()
+                })
+                .collect(),
+            stanza_indices: self.stanza_indices.clone(),
+        }
+    }
+
+    /// Adds the statistics of a fork of the graph to this profile.
+    pub(crate) fn join(&mut self, other: &Profile) {
+        for (stanza, other) in self.stanzas.iter_mut().zip(&other.stanzas) {
+            stanza.matches += other.matches;
+            stanza.query_time += other.query_time;
+            stanza.evaluation_time += other.evaluation_time;
+            stanza.nodes += other.nodes;
+            stanza.edges += other.edges;
+        }
+    }
+
+    /// Returns the index of the stanza at the given location.
+    pub(crate) fn stanza_index(&self, location: Location) -> Option<usize> {
+        self.stanza_indices.get(&location).copied()
+    }
+
+    /// Records a match of a stanza, which took `query_time` to find.
+    pub(crate) fn add_match(&mut self, stanza: usize, query_time: Duration) {
+        let stanza = &mut self.stanzas[stanza];
+        stanza.matches += 1;
+        stanza.query_time += query_time;
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>12} {:>16} {:>10} {:>10}",
+            "stanza", "matches", "query (ms)", "evaluation (ms)", "nodes", "edges"
+        )?;
+        for stanza in &self.stanzas {
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>12.3} {:>16.3} {:>10} {:>10}",
+                stanza.location.to_string(),
+                stanza.matches,
+                stanza.query_time.as_secs_f64() * 1000.0,
+                stanza.evaluation_time.as_secs_f64() * 1000.0,
+                stanza.nodes,
+                stanza.edges,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures the time that part of an execution takes, and the graph nodes and edges that it
+/// creates, so that they can be attributed to a stanza.
+pub(crate) struct Measurement {
+    start: Instant,
+    nodes: usize,
+    edges: usize,
+}
+
+impl Measurement {
+    /// Starts a measurement, if the execution is being profiled.
+    pub(crate) fn start(state: &mut ExecutionState) -> Option<Self> {
+        state.profile()?;
+        let usage = state.resource_usage();
+        Some(Self {
+            start: Instant::now(),
+            nodes: usage.nodes(),
+            edges: usage.edges(),
+        })
+    }
+
+    /// Adds what happened since the measurement started to the statistics of a stanza.
+    pub(crate) fn finish(measurement: Option<Self>, state: &mut ExecutionState, stanza: usize) {
+        let measurement = match measurement {
+            Some(measurement) => measurement,
+            None => return,
+        };
+        let elapsed = measurement.start.elapsed();
+        let usage = state.resource_usage();
+        let nodes = usage.nodes() - measurement.nodes;
+        let edges = usage.edges() - measurement.edges;
+        if let Some(profile) = state.profile_mut() {
+            let stanza = &mut profile.stanzas[stanza];
+            stanza.evaluation_time += elapsed;
+            stanza.nodes += nodes;
+            stanza.edges += edges;
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Keeps the state that executions of graph DSL files can share
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use regex::Regex;
+use tree_sitter::QueryCursor;
+
+/// State that is kept across executions of graph DSL files, so that processing a large batch of
+/// source files does not repeat the setup work of each execution.  Install a session with
+/// [`ExecutionConfig::session`][`crate::ExecutionConfig::session`].  A session keeps:
+///
+///  - the query cursors that executions find stanza matches with, which are reused instead of
+///    being allocated for each execution,
+///  - the regular expressions that the [`replace`][`crate::reference::functions#replace`]
+///    function compiles, by pattern, and
+///  - the strings that executions intern into their graphs, so that the graphs built with the
+///    same session share the storage of equal strings (see
+///    [`Graph::intern`][`crate::graph::Graph::intern`]).
+///
+/// The queries of a graph DSL file are compiled once, when the file is parsed, and are kept by
+/// the [`File`][`crate::ast::File`] itself.
+///
+/// A session can be shared by executions on several threads.  Its caches grow with each
+/// execution; use [`ExecutionSession::clear`] to drop them.
+#[derive(Default)]
+pub struct ExecutionSession {
+    cursors: Mutex<Vec<QueryCursor>>,
+    pub(crate) caches: Arc<SessionCaches>,
+}
+
+impl ExecutionSession {
+    /// Creates a new session, which has nothing cached yet.
+    pub fn new() -> ExecutionSession {
+        ExecutionSession::default()
+    }
+
+    /// Drops the query cursors, regular expressions, and strings that this session keeps.  The
+    /// graphs that were built with the session keep their strings.
+    pub fn clear(&self) {
+        lock(&self.cursors).clear();
+        lock(&self.caches.strings).clear();
+        lock(&self.caches.regexes).clear();
+    }
+
+    /// Returns a query cursor, which is returned to this session when it is dropped.
+    pub(crate) fn cursor(&self) -> SessionCursor<'_> {
+        let cursor = lock(&self.cursors).pop().unwrap_or_else(QueryCursor::new);
+        SessionCursor {
+            cursor: Some(cursor),
+            session: Some(self),
+        }
+    }
+}
+
+/// The caches of a session that the graphs built with it refer to
+#[derive(Default)]
+pub(crate) struct SessionCaches {
+    strings: Mutex<HashSet<Arc<str>>>,
+    regexes: Mutex<HashMap<String, Regex>>,
+}
+
+impl SessionCaches {
+    /// Returns a string equal to `string` that was interned before, if there is one, or else
+    /// interns `string`.
+    pub(crate) fn intern(&self, string: Arc<str>) -> Arc<str> {
+        let mut strings = lock(&self.strings);
+        match strings.get(&string) {
+            Some(interned) => interned.clone(),
+            None => {
+                strings.insert(string.clone());
+                string
+            }
+        }
+    }
+
+    /// Returns the compiled regular expression for `pattern`, compiling it if it was not before.
+    pub(crate) fn regex(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        if let Some(regex) = lock(&self.regexes).get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern)?;
+        lock(&self.regexes).insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+}
+
+/// A query cursor for an execution, which is returned to the session it was taken from, if any,
+/// when it is dropped
+pub(crate) struct SessionCursor<'a> {
+    cursor: Option<QueryCursor>,
+    session: Option<&'a ExecutionSession>,
+}
+
+impl SessionCursor<'_> {
+    /// Returns a new cursor that does not belong to a session.
+    pub(crate) fn new() -> Self {
+        SessionCursor {
+            cursor: Some(QueryCursor::new()),
+            session: None,
+        }
+    }
+}
+
+impl Deref for SessionCursor<'_> {
+    type Target = QueryCursor;
+
+    fn deref(&self) -> &QueryCursor {
+        self.cursor.as_ref().expect("missing cursor")
+    }
+}
+
+impl DerefMut for SessionCursor<'_> {
+    fn deref_mut(&mut self) -> &mut QueryCursor {
+        self.cursor.as_mut().expect("missing cursor")
+    }
+}
+
+impl Drop for SessionCursor<'_> {
+    fn drop(&mut self) {
+        if let (Some(session), Some(cursor)) = (self.session, self.cursor.take()) {
+            lock(&session.cursors).push(cursor);
+        }
+    }
+}
+
+/// Locks a mutex of a session.  The caches stay consistent even if a thread panicked while
+/// holding the lock, so a poisoned lock is used anyway.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
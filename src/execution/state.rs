@@ -0,0 +1,408 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Keeps the state of an execution that is not part of the graph it builds
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use regex::Regex;
+
+use crate::ast::File;
+use crate::execution::error::ExecutionError;
+use crate::execution::error::ExecutionWarning;
+use crate::execution::DryRun;
+use crate::execution::DryRunStats;
+use crate::execution::ExecutionConfig;
+use crate::execution::Profile;
+use crate::execution::ResourceUsage;
+use crate::execution::SessionCaches;
+use crate::functions::FunctionState;
+use crate::graph::Attributes;
+use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::graph::Value;
+use crate::Identifier;
+
+/// The state of an execution of a graph DSL file that is not part of the graph that it builds,
+/// such as the state of the functions that it calls, and the warnings and errors that it records.
+/// Functions are passed the state along with the graph.  Use [`ExecutionState::new`] to call a
+/// function outside of an execution.
+#[derive(Default)]
+pub struct ExecutionState {
+    random_seed: u64,
+    random_state: u64,
+    /// Whether each match draws random numbers from its own sequence, which is the case for the
+    /// parts of a parallel execution
+    random_per_match: bool,
+    symbol_count: u32,
+    symbol_skip: u32,
+    execution_time: Option<SystemTime>,
+    function_state: FunctionState,
+    exec_capture_allowed: bool,
+    dry_run: Option<DryRun>,
+    resource_usage: ResourceUsage,
+    profile: Option<Profile>,
+    warnings: Vec<ExecutionWarning>,
+    errors: Vec<ExecutionError>,
+    session_caches: Option<Arc<SessionCaches>>,
+}
+
+impl ExecutionState {
+    /// Creates the state of an execution with the default configuration, which has not done
+    /// anything yet.
+    pub fn new() -> ExecutionState {
+        ExecutionState::default()
+    }
+
+    /// Creates the state of an execution of `file` with the given configuration, and lets the
+    /// functions of the configuration add their state to it.
+    pub(crate) fn start(file: &File, config: &ExecutionConfig) -> ExecutionState {
+        let settings = &config.settings;
+        let mut state = ExecutionState {
+            random_seed: settings.random_seed,
+            random_state: settings.random_seed,
+            random_per_match: false,
+            symbol_count: settings.first_symbol,
+            symbol_skip: 0,
+            execution_time: Some(settings.fixed_time.unwrap_or_else(SystemTime::now)),
+            function_state: FunctionState::new(),
+            exec_capture_allowed: settings.allow_exec_capture,
+            dry_run: if settings.dry_run {
+                Some(DryRun::new(file.reads_graph(config.functions)))
+            } else {
+                None
+            },
+            resource_usage: ResourceUsage::new(settings.resource_limits.clone()),
+            profile: if settings.profile {
+                Some(Profile::new(file))
+            } else {
+                None
+            },
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            session_caches: settings.session.map(|session| session.caches.clone()),
+        };
+        config
+            .functions
+            .on_execution_start(&mut state.function_state);
+        state
+    }
+
+    /// Returns the state that functions keep for the duration of the execution.  See
+    /// [`Function::on_execution_start`][`crate::functions::Function::on_execution_start`].
+    pub fn function_state(&mut self) -> &mut FunctionState {
+        &mut self.function_state
+    }
+
+    /// Called before the stanza with the given index is executed for a match, given the position
+    /// of the match among the matches of the stanza.  If this is the state of a part of a parallel
+    /// execution, the match draws random numbers from its own sequence, which is derived from the
+    /// seed and the match, so that they do not depend on how the execution was split up.
+    pub(crate) fn start_match(&mut self, stanza: usize, ordinal: usize) {
+        if self.random_per_match {
+            self.random_state = mix(mix(self.random_seed ^ stanza as u64) ^ ordinal as u64);
+        }
+    }
+
+    /// Returns the next number from the execution's pseudo-random number generator, which is an
+    /// implementation of SplitMix64.
+    pub(crate) fn next_random(&mut self) -> u64 {
+        self.random_state = self.random_state.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.random_state)
+    }
+
+    /// Returns a number that has not been returned before in this execution, for use by the
+    /// [`gensym`][`crate::reference::functions#gensym`] function.
+    pub(crate) fn next_symbol(&mut self) -> u32 {
+        let symbol = self.symbol_count;
+        self.symbol_count += self.symbol_skip + 1;
+        symbol
+    }
+
+    /// Returns the time at which the execution started, or the current time if it has not
+    /// started, for the [`now-unix`][`crate::reference::functions#now-unix`] and
+    /// [`now-iso8601`][`crate::reference::functions#now-iso8601`] functions.
+    pub(crate) fn execution_time(&self) -> SystemTime {
+        self.execution_time.unwrap_or_else(SystemTime::now)
+    }
+
+    /// Returns whether the [`exec-capture`][`crate::reference::functions#exec-capture`] function
+    /// is allowed to run external processes.
+    #[cfg_attr(not(feature = "exec-capture"), allow(dead_code))]
+    pub(crate) fn exec_capture_allowed(&self) -> bool {
+        self.exec_capture_allowed
+    }
+
+    /// Compiles a regular expression that a function uses, reusing the one that the session of
+    /// the execution compiled before for the same pattern, if any.
+    pub(crate) fn regex(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        match &self.session_caches {
+            Some(session_caches) => session_caches.regex(pattern),
+            None => Regex::new(pattern),
+        }
+    }
+
+    /// Returns whether the execution adds the graph nodes, edges, and attributes that it creates
+    /// to the graph, which a dry run only does if the graph DSL file reads the graph.  See
+    /// [`ExecutionConfig::dry_run`][`crate::ExecutionConfig::dry_run`].
+    pub(crate) fn writes_graph(&self) -> bool {
+        match &self.dry_run {
+            Some(dry_run) => dry_run.writes_graph(),
+            None => true,
+        }
+    }
+
+    /// Adds a graph node with the given attributes to `graph`, or only records it if the
+    /// execution is a dry run that does not write to the graph.
+    pub(crate) fn add_graph_node(
+        &mut self,
+        graph: &mut Graph,
+        attributes: Attributes,
+    ) -> GraphNodeRef {
+        match &mut self.dry_run {
+            Some(dry_run) if !dry_run.writes_graph() => {
+                return dry_run.add_graph_node(graph, &attributes)
+            }
+            Some(dry_run) => dry_run.count_node(&attributes),
+            None => {}
+        }
+        let node = graph.add_graph_node();
+        graph.node_mut(node).attributes().extend(attributes);
+        node
+    }
+
+    /// Adds an edge with the given attributes to `graph`, like [`ExecutionState::add_graph_node`].
+    /// Returns whether the edge is new.
+    pub(crate) fn add_edge(
+        &mut self,
+        graph: &mut Graph,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        attributes: Attributes,
+    ) -> bool {
+        match &mut self.dry_run {
+            Some(dry_run) if !dry_run.writes_graph() => {
+                return dry_run.add_edge(graph, source, sink, &attributes)
+            }
+            Some(dry_run) if graph.node(source).get_edge(sink).is_none() => {
+                dry_run.count_edge(&attributes)
+            }
+            _ => {}
+        }
+        match graph.node_mut(source).add_edge(sink) {
+            Ok(mut edge) => {
+                edge.attributes().extend(attributes);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns whether there is an edge between two graph nodes, including the edges that a dry
+    /// run recorded.
+    pub(crate) fn has_edge(&self, graph: &Graph, source: GraphNodeRef, sink: GraphNodeRef) -> bool {
+        match &self.dry_run {
+            Some(dry_run) if !dry_run.writes_graph() => dry_run.has_edge(graph, source, sink),
+            _ => graph.node(source).get_edge(sink).is_some(),
+        }
+    }
+
+    /// Adds an attribute to a graph node, like [`ExecutionState::add_graph_node`], interning its
+    /// value into `graph`.  Returns whether the graph node did not have the attribute yet.
+    pub(crate) fn add_node_attribute(
+        &mut self,
+        graph: &mut Graph,
+        node: GraphNodeRef,
+        name: Identifier,
+        value: Value,
+    ) -> bool {
+        match &mut self.dry_run {
+            Some(dry_run) if !dry_run.writes_graph() => {
+                return dry_run.add_node_attribute(graph, node, &name, &value)
+            }
+            Some(dry_run) if graph.node(node).attributes().get(&name).is_none() => {
+                dry_run.count_node_attribute(&name, &value)
+            }
+            _ => {}
+        }
+        let value = graph.intern_in_session(value, self.session_caches.as_deref());
+        graph.node_mut(node).attributes().add(name, value).is_ok()
+    }
+
+    /// Adds an attribute to an edge, which must exist, like
+    /// [`ExecutionState::add_node_attribute`].  Returns whether the edge did not have the
+    /// attribute yet.
+    pub(crate) fn add_edge_attribute(
+        &mut self,
+        graph: &mut Graph,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        name: Identifier,
+        value: Value,
+    ) -> bool {
+        match &mut self.dry_run {
+            Some(dry_run) if !dry_run.writes_graph() => {
+                return dry_run.add_edge_attribute(graph, source, sink, &name, &value)
+            }
+            Some(dry_run) => {
+                let edge = graph.node(source).get_edge(sink).expect("missing edge");
+                if edge.attributes().get(&name).is_none() {
+                    dry_run.count_edge_attribute(&name, &value);
+                }
+            }
+            None => {}
+        }
+        let value = graph.intern_in_session(value, self.session_caches.as_deref());
+        let mut node = graph.node_mut(source);
+        let mut edge = node.get_edge_mut(sink).expect("missing edge");
+        edge.attributes().add(name, value).is_ok()
+    }
+
+    /// Returns how much the execution has added to the graph.
+    pub(crate) fn resource_usage(&mut self) -> &mut ResourceUsage {
+        &mut self.resource_usage
+    }
+
+    /// Returns the statistics that the execution collects, if it is being profiled.
+    pub(crate) fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub(crate) fn profile_mut(&mut self) -> Option<&mut Profile> {
+        self.profile.as_mut()
+    }
+
+    /// Records a warning, unless the execution already recorded the same one.
+    pub(crate) fn add_warning(&mut self, warning: ExecutionWarning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Records an error that the execution continued after.
+    pub(crate) fn add_error(&mut self, error: ExecutionError) {
+        self.errors.push(error);
+    }
+
+    /// Returns the state of a part of the execution that is run on another thread, into a fork
+    /// of the graph.  The part is the `index`th of `count` parts forked at the same time: its
+    /// [`gensym`][`crate::reference::functions#gensym`] symbols are interleaved with theirs, so
+    /// that they are distinct.  Each match that it executes draws random numbers from its own
+    /// sequence, see [`ExecutionState::start_match`], and its functions start with empty state.
+    /// Use [`ExecutionState::join`] to add what it recorded back to this state.
+    pub(crate) fn fork(&self, index: u32, count: u32) -> ExecutionState {
+        let step = self.symbol_skip + 1;
+        ExecutionState {
+            random_seed: self.random_seed,
+            random_per_match: true,
+            symbol_count: self.symbol_count + index * step,
+            symbol_skip: step * count - 1,
+            execution_time: self.execution_time,
+            exec_capture_allowed: self.exec_capture_allowed,
+            dry_run: self.dry_run.as_ref().map(DryRun::fork),
+            resource_usage: self.resource_usage.fork(),
+            profile: self.profile.as_ref().map(|profile| profile.fork()),
+            session_caches: self.session_caches.clone(),
+            ..ExecutionState::default()
+        }
+    }
+
+    /// Adds what a state returned by [`ExecutionState::fork`] recorded to this state.  What the
+    /// fork added to its graph counts towards this state's resource limits.
+    pub(crate) fn join(&mut self, fork: ExecutionState) -> Result<(), ExecutionError> {
+        self.resource_usage.join(&fork.resource_usage)?;
+        self.symbol_count = self.symbol_count.max(fork.symbol_count);
+        if let (Some(profile), Some(fork_profile)) = (&mut self.profile, &fork.profile) {
+            profile.join(fork_profile);
+        }
+        if let (Some(dry_run), Some(fork_dry_run)) = (&mut self.dry_run, &fork.dry_run) {
+            dry_run.join(fork_dry_run);
+        }
+        for warning in fork.warnings {
+            self.add_warning(warning);
+        }
+        for error in fork.errors {
+            self.add_error(error);
+        }
+        Ok(())
+    }
+
+    /// Finishes the execution, dropping the state of its functions, and returns what it recorded.
+    pub(crate) fn into_report(self) -> ExecutionReport {
+        ExecutionReport {
+            profile: self.profile,
+            dry_run: self.dry_run.map(DryRun::into_stats),
+            warnings: self.warnings,
+            errors: self.errors,
+        }
+    }
+}
+
+/// Mixes the bits of a number, as the output function of SplitMix64 does.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// What an execution of a graph DSL file recorded besides the graph that it built, which is
+/// returned by [`File::execute_into`][`crate::ast::File::execute_into`] and the other methods that
+/// execute into an existing graph.
+#[derive(Debug, Default)]
+pub struct ExecutionReport {
+    profile: Option<Profile>,
+    dry_run: Option<DryRunStats>,
+    warnings: Vec<ExecutionWarning>,
+    errors: Vec<ExecutionError>,
+}
+
+impl ExecutionReport {
+    /// Returns the statistics that the execution collected, if it was configured to with
+    /// [`ExecutionConfig::profile`][`crate::ExecutionConfig::profile`].
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Returns what the execution would have added to the graph, if it was a dry run, configured
+    /// with [`ExecutionConfig::dry_run`][`crate::ExecutionConfig::dry_run`].
+    pub fn dry_run(&self) -> Option<&DryRunStats> {
+        self.dry_run.as_ref()
+    }
+
+    /// Returns the warnings that the execution recorded.
+    pub fn warnings(&self) -> &[ExecutionWarning] {
+        &self.warnings
+    }
+
+    /// Returns the errors that the execution recorded, in the order they occurred, when it was
+    /// configured to continue on errors with
+    /// [`ExecutionConfig::continue_on_error`][`crate::ExecutionConfig::continue_on_error`].
+    pub fn errors(&self) -> &[ExecutionError] {
+        &self.errors
+    }
+
+    /// Adds what another execution of the same file recorded to this report.
+    pub(crate) fn extend(&mut self, other: ExecutionReport) {
+        match (&mut self.profile, other.profile) {
+            (Some(profile), Some(other)) => profile.join(&other),
+            (profile @ None, other) => *profile = other,
+            _ => {}
+        }
+        match (&mut self.dry_run, other.dry_run) {
+            (Some(dry_run), Some(other)) => dry_run.extend(&other),
+            (dry_run @ None, other) => *dry_run = other,
+            _ => {}
+        }
+        for warning in other.warnings {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+        }
+        self.errors.extend(other.errors);
+    }
+}
@@ -5,49 +5,39 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
-use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+use tree_sitter::Node;
+use tree_sitter::QueryCapture;
 use tree_sitter::QueryCursor;
 use tree_sitter::QueryMatch;
 use tree_sitter::Tree;
 
 use crate::ast::AddEdgeAttribute;
 use crate::ast::AddGraphNodeAttribute;
-use crate::ast::Assign;
-use crate::ast::Attribute;
-use crate::ast::AttributeShorthand;
-use crate::ast::AttributeShorthands;
-use crate::ast::Call;
-use crate::ast::Capture;
-use crate::ast::Condition;
-use crate::ast::CreateEdge;
-use crate::ast::CreateGraphNode;
-use crate::ast::DeclareImmutable;
-use crate::ast::DeclareMutable;
-use crate::ast::Expression;
 use crate::ast::File;
-use crate::ast::ForIn;
-use crate::ast::If;
-use crate::ast::IntegerConstant;
-use crate::ast::ListComprehension;
-use crate::ast::ListLiteral;
-use crate::ast::Print;
-use crate::ast::RegexCapture;
 use crate::ast::Scan;
-use crate::ast::ScopedVariable;
-use crate::ast::SetComprehension;
-use crate::ast::SetLiteral;
 use crate::ast::Stanza;
 use crate::ast::Statement;
-use crate::ast::StringConstant;
-use crate::ast::UnscopedVariable;
-use crate::ast::Variable;
+use crate::execution::check_deadline;
+use crate::execution::error::Context;
 use crate::execution::error::ExecutionError;
+use crate::execution::error::ExecutionWarning;
 use crate::execution::error::ResultWithExecutionError;
 use crate::execution::error::StatementContext;
+use crate::execution::number_matches;
+use crate::execution::profile::Measurement;
 use crate::execution::CancellationFlag;
 use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionState;
+use crate::execution::GraphElement;
+use crate::functions::Functions;
+use crate::functions::LazyParameters;
+use crate::graph::Attributes;
 use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
+use crate::graph::Provenance;
 use crate::graph::SyntaxNodeRef;
 use crate::graph::Value;
 use crate::variables::Globals;
@@ -57,53 +47,175 @@ use crate::variables::Variables;
 use crate::Identifier;
 use crate::Location;
 
+use self::ir::Block;
+use self::ir::Code;
+use self::ir::LinkedProgram;
+use self::ir::Op;
+pub(crate) use self::ir::Program;
+use self::ir::Test;
+
+mod ir;
+mod parallel;
+
 impl File {
     /// Executes this graph DSL file against a source file, saving the results into an existing
     /// `Graph` instance.  You must provide the parsed syntax tree (`tree`) as well as the source
     /// text that it was parsed from (`source`).  You also provide the set of functions and global
     /// variables that are available during execution. This variant is useful when you need to
     /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
-    pub(super) fn execute_strict_into<'a, 'tree>(
+    pub(super) async fn execute_strict_into<'tree>(
         &self,
         graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
         tree: &'tree Tree,
         source: &'tree str,
-        config: &ExecutionConfig,
+        config: &ExecutionConfig<'_, '_>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), ExecutionError> {
+        if config.settings.parallel {
+            return self.execute_strict_parallel_into(
+                graph,
+                state,
+                tree,
+                source,
+                config,
+                cancellation_flag,
+            );
+        }
+
         let mut globals = Globals::nested(config.globals);
         self.check_globals(&mut globals)?;
         let mut config = ExecutionConfig {
-            functions: config.functions,
             globals: &globals,
-            lazy: config.lazy,
-            location_attr: config.location_attr.clone(),
-            variable_name_attr: config.variable_name_attr.clone(),
+            ..config.clone()
+        };
+        config.settings.parallel = false;
+        config.settings.deadline = config.deadline();
+        self.execute_stanzas_strict(graph, state, tree, source, &config, cancellation_flag)
+            .await
+    }
+
+    /// Executes the stanzas of this file that `config` selects, for the stanza matches that it
+    /// selects, yielding after each match if `config` asks to.  The stanzas that do not set their
+    /// own match limit share a single pass over the syntax tree with the file query, whose match
+    /// limit is the one in `config`, and their matches are executed in the order that the query
+    /// finds them.  The stanzas with their own match limit are then executed in file order, each
+    /// with its own query.  The global variables in `config` must already have been checked.
+    async fn execute_stanzas_strict<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig<'_, '_>,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let mut shared = vec![false; self.stanzas.len()];
+        let mut limited = Vec::new();
+        for (index, stanza) in self.stanzas.iter().enumerate() {
+            if !config.selects_stanza(index, stanza) {
+                continue;
+            }
+            match stanza.match_limit {
+                None => shared[index] = true,
+                Some(_) => limited.push(index),
+            }
+        }
+        let file_query = self.file_query()?;
+        let program = self.strict_program().link(self, config);
+        let mut executor = MatchExecutor {
+            stanzas: &self.stanzas,
+            program: &program,
+            source,
+            graph,
+            state,
+            config,
+            cancellation_flag,
+            scoped: ScopedVariables::new(),
+            stack: Vec::new(),
+            frame: Vec::new(),
         };
 
-        let mut locals = VariableMap::new();
-        let mut scoped = ScopedVariables::new();
-        let current_regex_captures = Vec::new();
-        let mut function_parameters = Vec::new();
+        if shared.contains(&true) {
+            if let Some(observer) = config.settings.observer {
+                for (stanza, _) in (self.stanzas.iter().zip(&shared)).filter(|(_, s)| **s) {
+                    observer.on_stanza_start(stanza);
+                }
+            }
+            let mut cursor = config.query_cursor(config.settings.match_limit);
+            let mut query_start = Instant::now();
+            let matches = cursor.matches(file_query, tree.root_node(), source.as_bytes());
+            for (ordinal, mat) in number_matches(matches) {
+                let query_time = query_start.elapsed();
+                if shared[mat.pattern_index] && config.selects_match(&mat) {
+                    executor.execute(mat.pattern_index, ordinal, mat.captures, query_time)?;
+                    config.settings.yielding.yield_now().await;
+                }
+                query_start = Instant::now();
+            }
+            if cursor.did_exceed_match_limit() {
+                executor
+                    .state
+                    .add_warning(ExecutionWarning::MatchLimitExceeded(
+                        "for the file query".to_string(),
+                    ));
+            }
+        }
 
-        self.try_visit_matches_strict(tree, source, |stanza, mat| {
-            stanza.execute(
-                source,
-                &mat,
-                graph,
-                &mut config,
-                &mut locals,
-                &mut scoped,
-                &current_regex_captures,
-                &mut function_parameters,
-                &self.shorthands,
-                cancellation_flag,
-            )
-        })?;
+        let mut captures = Vec::new();
+        for index in limited {
+            let stanza = &self.stanzas[index];
+            if let Some(observer) = config.settings.observer {
+                observer.on_stanza_start(stanza);
+            }
+            let capture_indices = (stanza.query.capture_names().iter())
+                .map(|name| {
+                    file_query
+                        .capture_index_for_name(name)
+                        .expect("missing index for capture")
+                })
+                .collect::<Vec<_>>();
+            let mut cursor = config.query_cursor(stanza.match_limit);
+            let mut query_start = Instant::now();
+            let matches = cursor.matches(&stanza.query, tree.root_node(), source.as_bytes());
+            for (ordinal, mat) in number_matches(matches) {
+                let query_time = query_start.elapsed();
+                if config.selects_match(&mat) {
+                    captures.clear();
+                    captures.extend(mat.captures.iter().map(|capture| QueryCapture {
+                        node: capture.node,
+                        index: capture_indices[capture.index as usize],
+                    }));
+                    executor.execute(index, ordinal, &captures, query_time)?;
+                    config.settings.yielding.yield_now().await;
+                }
+                query_start = Instant::now();
+            }
+            if cursor.did_exceed_match_limit() {
+                executor
+                    .state
+                    .add_warning(ExecutionWarning::MatchLimitExceeded(format!(
+                        "for stanza at {}",
+                        stanza.range.start
+                    )));
+            }
+        }
 
         Ok(())
     }
 
+    /// Returns the program that the strict engine executes for this file, compiling it the first
+    /// time the file is executed.
+    fn strict_program(&self) -> &Program {
+        self.strict_program.get_or_init(|| Program::compile(self))
+    }
+
+    /// Returns whether the stanzas of this file call or refer to a function that
+    /// [reads the graph][`crate::functions::Function::reads_graph`].
+    pub(super) fn reads_graph(&self, functions: &Functions) -> bool {
+        self.strict_program().reads_graph(functions)
+    }
+
     pub(super) fn try_visit_matches_strict<'tree, E, F>(
         &self,
         tree: &'tree Tree,
@@ -120,19 +232,103 @@ impl File {
     }
 }
 
-/// State that is threaded through the execution
-struct ExecutionContext<'a, 'c, 'g, 's, 'tree> {
+/// Executes the compiled code of the stanzas of a file for their matches, as the queries find
+/// them.  The captures of the matches use the capture indices of the file query.
+struct MatchExecutor<'a, 'c, 'g, 's, 'tree> {
+    stanzas: &'a [Stanza],
+    program: &'a LinkedProgram<'a>,
+    source: &'tree str,
+    graph: &'a mut Graph<'tree>,
+    state: &'a mut ExecutionState,
+    config: &'a ExecutionConfig<'c, 'g>,
+    cancellation_flag: &'a dyn CancellationFlag,
+    scoped: ScopedVariables<'s>,
+    stack: Vec<Value>,
+    frame: Vec<Value>,
+}
+
+impl<'a, 'c, 'g, 's, 'tree> MatchExecutor<'a, 'c, 'g, 's, 'tree> {
+    /// Executes the stanza with the given index for a match, which took `query_time` to find.
+    /// `ordinal` is the position of the match among the matches of the stanza.
+    fn execute(
+        &mut self,
+        index: usize,
+        ordinal: usize,
+        captures: &[QueryCapture<'tree>],
+        query_time: Duration,
+    ) -> Result<(), ExecutionError> {
+        self.cancellation_flag.check("processing matches")?;
+        check_deadline(self.config.settings.deadline, "processing matches")?;
+        let stanza = &self.stanzas[index];
+        let node = full_match_node(stanza, captures);
+        if !self.config.selects_node(stanza, node) {
+            return Ok(());
+        }
+        if let Some(observer) = self.config.settings.observer {
+            observer.on_match(stanza, node);
+        }
+        if let Some(profile) = self.state.profile_mut() {
+            profile.add_match(index, query_time);
+        }
+        self.state.start_match(index, ordinal);
+        let measurement = Measurement::start(self.state);
+        let result = Interpreter {
+            program: self.program,
+            source: self.source,
+            graph: self.graph,
+            state: self.state,
+            config: self.config,
+            scoped: &mut self.scoped,
+            stack: &mut self.stack,
+            frame: &mut self.frame,
+            stanza,
+            captures,
+            cancellation_flag: self.cancellation_flag,
+            regex_captures: Vec::new(),
+            statement: None,
+            target: None,
+        }
+        .execute(&self.program.program.stanzas[index]);
+        Measurement::finish(measurement, self.state, index);
+        self.config.recover(self.state, result)
+    }
+}
+
+/// Returns the syntax node that the whole query of a stanza matched, given the captures of the
+/// match.
+fn full_match_node<'tree>(stanza: &Stanza, captures: &[QueryCapture<'tree>]) -> Node<'tree> {
+    captures
+        .iter()
+        .find(|capture| capture.index == stanza.full_match_file_capture_index as u32)
+        .expect("missing full capture")
+        .node
+}
+
+/// State that is threaded through the execution of a stanza's compiled code for one of its
+/// matches
+struct Interpreter<'a, 'c, 'g, 's, 'tree> {
+    program: &'a LinkedProgram<'a>,
     source: &'tree str,
     graph: &'a mut Graph<'tree>,
+    state: &'a mut ExecutionState,
     config: &'a ExecutionConfig<'c, 'g>,
-    locals: &'a mut dyn MutVariables<Value>,
     scoped: &'a mut ScopedVariables<'s>,
-    current_regex_captures: &'a Vec<String>,
-    function_parameters: &'a mut Vec<Value>,
-    mat: &'a QueryMatch<'a, 'tree>,
-    error_context: StatementContext,
-    shorthands: &'a AttributeShorthands,
+    stack: &'a mut Vec<Value>,
+    frame: &'a mut Vec<Value>,
+    stanza: &'a Stanza,
+    captures: &'a [QueryCapture<'tree>],
     cancellation_flag: &'a dyn CancellationFlag,
+    regex_captures: Vec<String>,
+    /// The innermost statement that is being executed
+    statement: Option<&'a Statement>,
+    /// The graph node or edge that attribute ops add attributes to
+    target: Option<AttributeTarget<'a>>,
+}
+
+#[derive(Clone, Copy)]
+enum AttributeTarget<'a> {
+    Node(GraphNodeRef, &'a AddGraphNodeAttribute),
+    Edge(GraphNodeRef, GraphNodeRef, &'a AddEdgeAttribute),
 }
 
 struct ScopedVariables<'a> {
@@ -152,63 +348,36 @@ impl<'a> ScopedVariables<'a> {
 }
 
 impl Stanza {
-    fn execute<'a, 'g, 'l, 's, 'tree>(
+    pub(super) fn try_visit_matches_strict<'tree, E, F>(
         &self,
+        tree: &'tree Tree,
         source: &'tree str,
-        mat: &QueryMatch<'_, 'tree>,
-        graph: &mut Graph<'tree>,
-        config: &ExecutionConfig<'_, 'g>,
-        locals: &mut VariableMap<'l, Value>,
-        scoped: &mut ScopedVariables<'s>,
-        current_regex_captures: &Vec<String>,
-        function_parameters: &mut Vec<Value>,
-        shorthands: &AttributeShorthands,
-        cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<(), ExecutionError> {
-        locals.clear();
-        for statement in &self.statements {
-            let error_context = {
-                let node = mat
-                    .nodes_for_capture_index(self.full_match_stanza_capture_index as u32)
-                    .next()
-                    .expect("missing full capture");
-                StatementContext::new(&statement, &self, &node)
-            };
-            let mut exec = ExecutionContext {
-                source,
-                graph,
-                config,
-                locals,
-                scoped,
-                current_regex_captures,
-                function_parameters,
-                mat: &mat,
-                error_context,
-                shorthands,
-                cancellation_flag,
-            };
-            statement
-                .execute(&mut exec)
-                .with_context(|| exec.error_context.into())?;
-        }
+        visit: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(QueryMatch<'_, 'tree>) -> Result<(), E>,
+    {
+        self.try_visit_matches_with(tree, source, &mut QueryCursor::new(), visit)?;
         Ok(())
     }
 
-    pub(super) fn try_visit_matches_strict<'tree, E, F>(
+    /// Visits the matches of the stanza query that `cursor` finds.  Returns whether the query
+    /// exceeded the cursor's match limit, in which case some matches may have been dropped.
+    fn try_visit_matches_with<'tree, E, F>(
         &self,
         tree: &'tree Tree,
         source: &'tree str,
+        cursor: &mut QueryCursor,
         mut visit: F,
-    ) -> Result<(), E>
+    ) -> Result<bool, E>
     where
         F: FnMut(QueryMatch<'_, 'tree>) -> Result<(), E>,
     {
-        let mut cursor = QueryCursor::new();
         let matches = cursor.matches(&self.query, tree.root_node(), source.as_bytes());
         for mat in matches {
             visit(mat)?;
         }
-        Ok(())
+        Ok(cursor.did_exceed_match_limit())
     }
 }
 
@@ -228,131 +397,422 @@ impl Statement {
             Statement::ForIn(s) => s.location,
         }
     }
-
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        exec.cancellation_flag.check("executing statement")?;
-        match self {
-            Statement::DeclareImmutable(statement) => statement.execute(exec),
-            Statement::DeclareMutable(statement) => statement.execute(exec),
-            Statement::Assign(statement) => statement.execute(exec),
-            Statement::CreateGraphNode(statement) => statement.execute(exec),
-            Statement::AddGraphNodeAttribute(statement) => statement.execute(exec),
-            Statement::CreateEdge(statement) => statement.execute(exec),
-            Statement::AddEdgeAttribute(statement) => statement.execute(exec),
-            Statement::Scan(statement) => statement.execute(exec),
-            Statement::Print(statement) => statement.execute(exec),
-            Statement::If(statement) => statement.execute(exec),
-            Statement::ForIn(statement) => statement.execute(exec),
-        }
-    }
 }
 
-impl DeclareImmutable {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let value = self.value.evaluate(exec)?;
-        self.variable.add(exec, value, false)
+impl<'a, 'tree> Interpreter<'a, '_, '_, '_, 'tree> {
+    fn execute(&mut self, code: &Code) -> Result<(), ExecutionError> {
+        self.stack.clear();
+        self.frame.clear();
+        self.frame.resize(code.slots, Value::Null);
+        self.run(code.body, None)
     }
-}
 
-impl DeclareMutable {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let value = self.value.evaluate(exec)?;
-        self.variable.add(exec, value, true)
+    /// Executes the ops of a block.  An error in a statement of the block is reported in the
+    /// context of `arm`, if it is given, and then of the statement.
+    fn run(
+        &mut self,
+        block: Block,
+        arm: Option<&dyn Fn() -> Context>,
+    ) -> Result<(), ExecutionError> {
+        let outer = self.statement;
+        let mut statement = None;
+        for op in block.ops(self.program.program) {
+            if let Op::Statement(index) = op {
+                statement = Some(self.program.statement(*index));
+                self.statement = statement;
+            }
+            if let Err(error) = self.step(op) {
+                self.statement = outer;
+                let mut result = Err(error);
+                if let Some(arm) = arm {
+                    result = result.with_context(arm);
+                }
+                if let Some(statement) = statement {
+                    result = result.with_context(|| self.context(statement).into());
+                }
+                return result;
+            }
+        }
+        self.statement = outer;
+        Ok(())
     }
-}
 
-impl Assign {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let value = self.value.evaluate(exec)?;
-        self.variable.set(exec, value)
+    fn step(&mut self, op: &'a Op) -> Result<(), ExecutionError> {
+        match op {
+            Op::Statement(index) => {
+                self.cancellation_flag.check("executing statement")?;
+                check_deadline(self.config.settings.deadline, "executing statement")?;
+                if let Some(observer) = self.config.settings.observer {
+                    observer.on_statement(self.program.statement(*index));
+                }
+            }
+            Op::Store(name, slot) => {
+                if self.program.is_global(*name) {
+                    return Err(ExecutionError::DuplicateVariable(format!(
+                        " global {}",
+                        self.program.name(*name)
+                    )));
+                }
+                self.frame[*slot] = self.pop();
+            }
+            Op::Assign(name, local) => {
+                let name_text = || format!("{}", self.program.name(*name));
+                match local {
+                    _ if self.program.is_global(*name) => {
+                        return Err(ExecutionError::CannotAssignImmutableVariable(format!(
+                            " global {}",
+                            name_text()
+                        )))
+                    }
+                    Some((slot, true)) => self.frame[*slot] = self.pop(),
+                    Some((_, false)) => {
+                        return Err(ExecutionError::CannotAssignImmutableVariable(name_text()))
+                    }
+                    None => return Err(ExecutionError::UndefinedVariable(name_text())),
+                }
+            }
+            Op::AddScoped(variable, mutable) => {
+                let scope = self.pop_scope()?;
+                let value = self.pop();
+                self.scoped
+                    .get(scope)
+                    .add(variable.name.clone(), value, *mutable)
+                    .map_err(|_| ExecutionError::DuplicateVariable(variable.text.clone()))?;
+            }
+            Op::SetScoped(variable) => {
+                let scope = self.pop_scope()?;
+                let value = self.pop();
+                self.scoped
+                    .get(scope)
+                    .set(variable.name.clone(), value)
+                    .map_err(|_| ExecutionError::DuplicateVariable(variable.text.clone()))?;
+            }
+            Op::CreateGraphNode => {
+                let statement = match self.statement {
+                    Some(Statement::CreateGraphNode(statement)) => statement,
+                    _ => unreachable!("graph node created outside of its statement"),
+                };
+                self.state.resource_usage().add_node()?;
+                let mut attributes = Attributes::new();
+                statement
+                    .node
+                    .add_debug_attrs(&mut attributes, self.config)?;
+                let graph_node = self.state.add_graph_node(self.graph, attributes);
+                if let Some(observer) = self.config.settings.observer {
+                    observer.on_node_created(graph_node);
+                }
+                if self.config.settings.provenance {
+                    let provenance = self.provenance();
+                    self.graph.set_node_provenance(graph_node, provenance);
+                }
+                self.stack.push(Value::GraphNode(graph_node));
+            }
+            Op::NodeAttributes => {
+                let statement = match self.statement {
+                    Some(Statement::AddGraphNodeAttribute(statement)) => statement,
+                    _ => unreachable!("graph node attributes added outside of their statement"),
+                };
+                let node = self.pop().into_graph_node_ref()?;
+                self.target = Some(AttributeTarget::Node(node, statement));
+            }
+            Op::ExpectGraphNode => {
+                let node = self.pop().into_graph_node_ref()?;
+                self.stack.push(Value::GraphNode(node));
+            }
+            Op::CreateEdge => {
+                let statement = match self.statement {
+                    Some(Statement::CreateEdge(statement)) => statement,
+                    _ => unreachable!("edge created outside of its statement"),
+                };
+                let sink = self.pop().into_graph_node_ref()?;
+                let source = self.pop().into_graph_node_ref()?;
+                self.state.resource_usage().add_edge()?;
+                let mut attributes = Attributes::new();
+                statement.add_debug_attrs(&mut attributes, self.config)?;
+                if !self.state.add_edge(self.graph, source, sink, attributes) {
+                    return Err(ExecutionError::DuplicateEdge(format!(
+                        "({} -> {}) in {}",
+                        source, sink, statement,
+                    )));
+                }
+                if let Some(observer) = self.config.settings.observer {
+                    observer.on_edge_created(source, sink);
+                }
+                if self.config.settings.provenance {
+                    let provenance = self.provenance();
+                    self.graph.set_edge_provenance(source, sink, provenance);
+                }
+            }
+            Op::EdgeAttributes => {
+                let statement = match self.statement {
+                    Some(Statement::AddEdgeAttribute(statement)) => statement,
+                    _ => unreachable!("edge attributes added outside of their statement"),
+                };
+                let sink = self.pop().into_graph_node_ref()?;
+                let source = self.pop().into_graph_node_ref()?;
+                self.target = Some(AttributeTarget::Edge(source, sink, statement));
+            }
+            Op::StartAttribute => self.cancellation_flag.check("executing attribute")?,
+            Op::Attribute(name) => {
+                let value = self.pop();
+                self.add_attribute(name.clone(), value)?;
+            }
+            Op::Shorthand(index) => {
+                // the shorthand's code stores the value on the stack in its own frame
+                let shorthand = &self.program.program.shorthands[*index];
+                let frame = std::mem::replace(self.frame, vec![Value::Null; shorthand.slots]);
+                let result = self.run(shorthand.body, None);
+                *self.frame = frame;
+                result?;
+            }
+            Op::Scan(arms) => {
+                let statement = match self.statement {
+                    Some(Statement::Scan(statement)) => statement,
+                    _ => unreachable!("scan executed outside of its statement"),
+                };
+                self.scan(statement, arms)?
+            }
+            Op::PrintString(value) => eprint!("{}", value),
+            Op::PrintValue => eprint!("{:?}", self.pop()),
+            Op::PrintNewline => eprintln!(),
+            Op::If(arms) => {
+                for arm in arms.iter() {
+                    self.run(arm.conditions, None)?;
+                    let start = self.stack.len() - arm.condition_count;
+                    let holds = (self.stack.drain(start..))
+                        .all(|result| matches!(result, Value::Boolean(true)));
+                    if holds {
+                        self.run(arm.statements, None)?;
+                        break;
+                    }
+                }
+            }
+            Op::ForIn(body) => {
+                let values = self.pop().into_list()?;
+                for value in values {
+                    self.stack.push(value);
+                    self.run(*body, None)?;
+                }
+            }
+            Op::Constant(value) => self.stack.push(value.clone()),
+            Op::Capture(index, quantifier) => {
+                let index = *index;
+                let nodes = (self.captures.iter())
+                    .filter(move |capture| capture.index == index)
+                    .map(|capture| capture.node);
+                let value = Value::from_nodes(self.graph, nodes, *quantifier);
+                self.stack.push(value);
+            }
+            Op::Variable(name, local) => {
+                let value = if self.program.is_global(*name) {
+                    let name = self.program.name(*name);
+                    self.config
+                        .globals
+                        .get(name)
+                        .expect("missing global")
+                        .clone()
+                } else if let Some(slot) = local {
+                    self.frame[*slot].clone()
+                } else {
+                    return Err(ExecutionError::UndefinedVariable(format!(
+                        "{}",
+                        self.program.name(*name)
+                    )));
+                };
+                self.stack.push(value);
+            }
+            Op::Scoped(variable) => {
+                let scope = self.pop_scope()?;
+                let value = self.scoped.get(scope).get(&variable.name).ok_or_else(|| {
+                    ExecutionError::UndefinedVariable(format!(
+                        "{} on node {}",
+                        variable.text, scope
+                    ))
+                })?;
+                let value = value.clone();
+                self.stack.push(value);
+            }
+            Op::RegexCapture(index) => {
+                let capture = self
+                    .regex_captures
+                    .get(*index)
+                    .ok_or_else(|| ExecutionError::UndefinedRegexCapture(format!("${}", index)))?;
+                let value = Value::String(capture.clone().into());
+                self.stack.push(value);
+            }
+            Op::List(count) => {
+                let elements = self.stack.split_off(self.stack.len() - count);
+                self.push_checked(Value::List(elements))?;
+            }
+            Op::Set(count) => {
+                let start = self.stack.len() - count;
+                let elements = self.stack.drain(start..).collect();
+                self.push_checked(Value::Set(elements))?;
+            }
+            Op::Comprehension(element, set) => {
+                let values = self.pop().into_list()?;
+                let mut elements = Vec::with_capacity(values.len());
+                for value in values {
+                    self.stack.push(value);
+                    self.run(*element, None)?;
+                    elements.push(self.pop());
+                }
+                if *set {
+                    self.push_checked(Value::Set(elements.into_iter().collect()))?;
+                } else {
+                    self.push_checked(Value::List(elements))?;
+                }
+            }
+            Op::Call(function, parameters) => {
+                let function = self.program.function(*function)?;
+                let source = self.source;
+                let result = if function.lazy_parameters() {
+                    function.call_lazy(
+                        source,
+                        &mut CallParameters {
+                            interpreter: self,
+                            parameters: parameters.iter(),
+                        },
+                    )?
+                } else {
+                    let start = self.stack.len();
+                    for parameter in parameters.iter() {
+                        self.run(*parameter, None)?;
+                    }
+                    function.call(
+                        self.graph,
+                        self.state,
+                        source,
+                        &mut self.stack.drain(start..),
+                    )?
+                };
+                self.push_checked(result)?;
+            }
+            Op::FunctionRef(function) => {
+                let value = self.program.reference(*function)?;
+                self.stack.push(value);
+            }
+            Op::Condition(test) => {
+                let value = self.pop();
+                let result = match test {
+                    Test::Some => !value.is_null(),
+                    Test::None => value.is_null(),
+                    Test::Bool => value.into_boolean()?,
+                };
+                self.stack.push(Value::Boolean(result));
+            }
+        }
+        Ok(())
     }
-}
 
-impl CreateGraphNode {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let graph_node = exec.graph.add_graph_node();
-        self.node
-            .add_debug_attrs(&mut exec.graph[graph_node].attributes, exec.config)?;
-        let value = Value::GraphNode(graph_node);
-        self.node.add(exec, value, false)
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("missing value on stack")
     }
-}
 
-impl AddGraphNodeAttribute {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let node = self.node.evaluate(exec)?.into_graph_node_ref()?;
-        let add_attribute = |exec: &mut ExecutionContext, name: Identifier, value: Value| {
-            exec.graph[node]
-                .attributes
-                .add(name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        " {} on graph node ({}) in {}",
-                        name, node, self,
-                    ))
-                })
-        };
-        for attribute in &self.attributes {
-            attribute.execute(exec, &add_attribute)?;
+    fn pop_scope(&mut self) -> Result<SyntaxNodeRef, ExecutionError> {
+        match self.pop() {
+            Value::SyntaxNode(scope) => Ok(scope),
+            scope => Err(ExecutionError::InvalidVariableScope(format!(
+                "got {}",
+                scope
+            ))),
         }
-        Ok(())
     }
-}
 
-impl CreateEdge {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let source = self.source.evaluate(exec)?.into_graph_node_ref()?;
-        let sink = self.sink.evaluate(exec)?.into_graph_node_ref()?;
-        let edge = match exec.graph[source].add_edge(sink) {
-            Ok(edge) => edge,
-            Err(_) => {
-                return Err(ExecutionError::DuplicateEdge(format!(
-                    "({} -> {}) in {}",
-                    source, sink, self,
-                )))?
-            }
-        };
-        self.add_debug_attrs(&mut edge.attributes, exec.config)?;
+    /// Checks the length of a list, set, or map value, and pushes it.
+    fn push_checked(&mut self, value: Value) -> Result<(), ExecutionError> {
+        let value = self.state.resource_usage().check_length(value)?;
+        self.stack.push(value);
         Ok(())
     }
-}
 
-impl AddEdgeAttribute {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let source = self.source.evaluate(exec)?.into_graph_node_ref()?;
-        let sink = self.sink.evaluate(exec)?.into_graph_node_ref()?;
-        let add_attribute = |exec: &mut ExecutionContext, name: Identifier, value: Value| {
-            let edge = match exec.graph[source].get_edge_mut(sink) {
-                Some(edge) => Ok(edge),
-                None => Err(ExecutionError::UndefinedEdge(format!(
-                    "({} -> {}) in {}",
-                    source, sink, self,
-                ))),
-            }?;
-            edge.attributes.add(name.clone(), value).map_err(|_| {
-                ExecutionError::DuplicateAttribute(format!(
-                    " {} on edge ({} -> {}) in {}",
-                    name, source, sink, self,
-                ))
-            })
-        };
-        for attribute in &self.attributes {
-            attribute.execute(exec, &add_attribute)?;
+    fn full_match_node(&self) -> Node<'tree> {
+        full_match_node(self.stanza, self.captures)
+    }
+
+    fn context(&self, statement: &Statement) -> StatementContext {
+        StatementContext::new(statement, self.stanza, &self.full_match_node())
+    }
+
+    /// Returns the provenance of graph elements created by the current statement.
+    fn provenance(&self) -> Provenance {
+        let node = self.full_match_node();
+        Provenance {
+            stanza_location: self.stanza.range.start,
+            statement_location: self.statement.expect("missing statement").location(),
+            source_location: Location::from(node.range().start_point),
+            node_kind: node.kind().to_string(),
+        }
+    }
+
+    fn add_attribute(&mut self, name: Identifier, value: Value) -> Result<(), ExecutionError> {
+        ExecutionConfig::check_attribute_type(
+            &self.config.settings.attribute_types,
+            &name,
+            &value,
+        )?;
+        self.state.resource_usage().add_attribute(&value)?;
+        let target = self.target.expect("missing attribute target");
+        if let Some(observer) = self.config.settings.observer {
+            let element = match target {
+                AttributeTarget::Node(node, _) => GraphElement::Node(node),
+                AttributeTarget::Edge(source, sink, _) => GraphElement::Edge(source, sink),
+            };
+            observer.on_attr_set(element, &name, &value);
+        }
+        match target {
+            AttributeTarget::Node(node, statement) => {
+                if !self
+                    .state
+                    .add_node_attribute(self.graph, node, name.clone(), value)
+                {
+                    return Err(ExecutionError::DuplicateAttribute(format!(
+                        " {} on graph node ({}) in {}",
+                        name, node, statement,
+                    )));
+                }
+                if self.config.settings.provenance {
+                    let provenance = self.provenance();
+                    self.graph
+                        .set_node_attribute_provenance(node, name, provenance);
+                }
+            }
+            AttributeTarget::Edge(source, sink, statement) => {
+                if !self.state.has_edge(self.graph, source, sink) {
+                    return Err(ExecutionError::UndefinedEdge(format!(
+                        "({} -> {}) in {}",
+                        source, sink, statement,
+                    )));
+                }
+                if !self
+                    .state
+                    .add_edge_attribute(self.graph, source, sink, name.clone(), value)
+                {
+                    return Err(ExecutionError::DuplicateAttribute(format!(
+                        " {} on edge ({} -> {}) in {}",
+                        name, source, sink, statement,
+                    )));
+                }
+                if self.config.settings.provenance {
+                    let provenance = self.provenance();
+                    self.graph
+                        .set_edge_attribute_provenance(source, sink, name, provenance);
+                }
+            }
         }
         Ok(())
     }
-}
 
-impl Scan {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let match_string = self.value.evaluate(exec)?.into_string()?;
+    fn scan(&mut self, statement: &'a Scan, arms: &'a [Block]) -> Result<(), ExecutionError> {
+        let match_string = self.pop().into_string()?;
 
         let mut i = 0;
         let mut matches = Vec::new();
         while i < match_string.len() {
-            exec.cancellation_flag.check("processing scan matches")?;
+            self.cancellation_flag.check("processing scan matches")?;
+            check_deadline(self.config.settings.deadline, "processing scan matches")?;
             matches.clear();
-            for (index, arm) in self.arms.iter().enumerate() {
+            for (index, arm) in statement.arms.iter().enumerate() {
                 let captures = arm.regex.captures(&match_string[i..]);
                 if let Some(captures) = captures {
                     if captures
@@ -379,39 +839,21 @@ impl Scan {
                 (range.start, *index)
             });
 
-            let (regex_captures, block_index) = &matches[0];
-            let arm = &self.arms[*block_index];
-
-            let mut current_regex_captures = Vec::new();
-            for regex_capture in regex_captures.iter() {
-                current_regex_captures
-                    .push(regex_capture.map(|m| m.as_str()).unwrap_or("").to_string());
-            }
-
-            let mut arm_locals = VariableMap::nested(exec.locals);
-            let mut arm_exec = ExecutionContext {
-                source: exec.source,
-                graph: exec.graph,
-                config: exec.config,
-                locals: &mut arm_locals,
-                scoped: exec.scoped,
-                current_regex_captures: &current_regex_captures,
-                function_parameters: exec.function_parameters,
-                mat: exec.mat,
-                error_context: exec.error_context.clone(),
-                shorthands: exec.shorthands,
-                cancellation_flag: exec.cancellation_flag,
-            };
-
-            for statement in &arm.statements {
-                arm_exec.error_context.update_statement(statement);
-                statement
-                    .execute(&mut arm_exec)
-                    .with_context(|| {
-                        format!("matching {} with arm \"{}\"", match_string, arm.regex,).into()
-                    })
-                    .with_context(|| arm_exec.error_context.clone().into())?;
-            }
+            let (regex_captures, arm_index) = &matches[0];
+            let arm = &statement.arms[*arm_index];
+
+            let current_regex_captures = regex_captures
+                .iter()
+                .map(|regex_capture| regex_capture.map(|m| m.as_str()).unwrap_or("").to_string())
+                .collect();
+            let regex_captures_outside_arm =
+                std::mem::replace(&mut self.regex_captures, current_regex_captures);
+            let result = self.run(
+                arms[*arm_index],
+                Some(&|| format!("matching {} with arm \"{}\"", match_string, arm.regex).into()),
+            );
+            self.regex_captures = regex_captures_outside_arm;
+            result?;
 
             i += regex_captures
                 .get(0)
@@ -424,433 +866,33 @@ impl Scan {
     }
 }
 
-impl Print {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        for value in &self.values {
-            if let Expression::StringConstant(expr) = value {
-                eprint!("{}", expr.value);
-            } else {
-                let value = value.evaluate(exec)?;
-                eprint!("{:?}", value);
-            }
-        }
-        eprintln!();
-        Ok(())
-    }
-}
-
-impl If {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        for arm in &self.arms {
-            let mut result = true;
-            for condition in &arm.conditions {
-                result &= condition.test(exec)?;
-            }
-            if result {
-                let mut arm_locals = VariableMap::nested(exec.locals);
-                let mut arm_exec = ExecutionContext {
-                    source: exec.source,
-                    graph: exec.graph,
-                    config: exec.config,
-                    locals: &mut arm_locals,
-                    scoped: exec.scoped,
-                    current_regex_captures: exec.current_regex_captures,
-                    function_parameters: exec.function_parameters,
-                    mat: exec.mat,
-                    error_context: exec.error_context.clone(),
-                    shorthands: exec.shorthands,
-                    cancellation_flag: exec.cancellation_flag,
-                };
-                for stmt in &arm.statements {
-                    arm_exec.error_context.update_statement(stmt);
-                    stmt.execute(&mut arm_exec)
-                        .with_context(|| arm_exec.error_context.clone().into())?;
-                }
-                break;
-            }
-        }
-        Ok(())
-    }
+/// The parameters of a call to a function that evaluates its parameters on demand
+struct CallParameters<'e, 'a, 'c, 'g, 's, 'tree> {
+    interpreter: &'e mut Interpreter<'a, 'c, 'g, 's, 'tree>,
+    parameters: std::slice::Iter<'a, Block>,
 }
 
-impl Condition {
-    fn test(&self, exec: &mut ExecutionContext) -> Result<bool, ExecutionError> {
-        match self {
-            Condition::Some { value, .. } => Ok(!value.evaluate(exec)?.is_null()),
-            Condition::None { value, .. } => Ok(value.evaluate(exec)?.is_null()),
-            Condition::Bool { value, .. } => Ok(value.evaluate(exec)?.into_boolean()?),
-        }
+impl<'tree> LazyParameters<'tree> for CallParameters<'_, '_, '_, '_, '_, 'tree> {
+    fn param(&mut self) -> Result<Value, ExecutionError> {
+        let parameter = self.parameters.next().ok_or_else(|| {
+            ExecutionError::InvalidParameters("expected more parameters".to_string())
+        })?;
+        self.interpreter.run(*parameter, None)?;
+        Ok(self.interpreter.pop())
     }
-}
 
-impl ForIn {
-    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
-        let values = self.value.evaluate(exec)?.into_list()?;
-        let mut loop_locals = VariableMap::nested(exec.locals);
-        for value in values {
-            loop_locals.clear();
-            let mut loop_exec = ExecutionContext {
-                source: exec.source,
-                graph: exec.graph,
-                config: exec.config,
-                locals: &mut loop_locals,
-                scoped: exec.scoped,
-                current_regex_captures: exec.current_regex_captures,
-                function_parameters: exec.function_parameters,
-                mat: exec.mat,
-                error_context: exec.error_context.clone(),
-                shorthands: exec.shorthands,
-                cancellation_flag: exec.cancellation_flag,
-            };
-            self.variable.add(&mut loop_exec, value, false)?;
-            for stmt in &self.statements {
-                loop_exec.error_context.update_statement(stmt);
-                stmt.execute(&mut loop_exec)
-                    .with_context(|| loop_exec.error_context.clone().into())?;
-            }
-        }
+    fn skip(&mut self) -> Result<(), ExecutionError> {
+        self.parameters.next().ok_or_else(|| {
+            ExecutionError::InvalidParameters("expected more parameters".to_string())
+        })?;
         Ok(())
     }
-}
-
-impl Expression {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        match self {
-            Expression::FalseLiteral => Ok(Value::Boolean(false)),
-            Expression::NullLiteral => Ok(Value::Null),
-            Expression::TrueLiteral => Ok(Value::Boolean(true)),
-            Expression::IntegerConstant(expr) => expr.evaluate(exec),
-            Expression::StringConstant(expr) => expr.evaluate(exec),
-            Expression::ListLiteral(expr) => expr.evaluate(exec),
-            Expression::SetLiteral(expr) => expr.evaluate(exec),
-            Expression::ListComprehension(expr) => expr.evaluate(exec),
-            Expression::SetComprehension(expr) => expr.evaluate(exec),
-            Expression::Capture(expr) => expr.evaluate(exec),
-            Expression::Variable(expr) => expr.evaluate(exec),
-            Expression::Call(expr) => expr.evaluate(exec),
-            Expression::RegexCapture(expr) => expr.evaluate(exec),
-        }
-    }
-}
-
-impl IntegerConstant {
-    fn evaluate(&self, _exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        Ok(Value::Integer(self.value))
-    }
-}
-
-impl StringConstant {
-    fn evaluate(&self, _exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        Ok(Value::String(self.value.clone()))
-    }
-}
-
-impl ListLiteral {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let elements = self
-            .elements
-            .iter()
-            .map(|e| e.evaluate(exec))
-            .collect::<Result<_, _>>()?;
-        Ok(Value::List(elements))
-    }
-}
-
-impl ListComprehension {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let values = self.value.evaluate(exec)?.into_list()?;
-        let mut elements = Vec::new();
-        let mut loop_locals = VariableMap::nested(exec.locals);
-        for value in values {
-            loop_locals.clear();
-            let mut loop_exec = ExecutionContext {
-                source: exec.source,
-                graph: exec.graph,
-                config: exec.config,
-                locals: &mut loop_locals,
-                scoped: exec.scoped,
-                current_regex_captures: exec.current_regex_captures,
-                function_parameters: exec.function_parameters,
-                mat: exec.mat,
-                error_context: exec.error_context.clone(),
-                shorthands: exec.shorthands,
-                cancellation_flag: exec.cancellation_flag,
-            };
-            self.variable.add(&mut loop_exec, value, false)?;
-            let element = self.element.evaluate(&mut loop_exec)?;
-            elements.push(element);
-        }
-        Ok(Value::List(elements))
-    }
-}
-
-impl SetLiteral {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let elements = self
-            .elements
-            .iter()
-            .map(|e| e.evaluate(exec))
-            .collect::<Result<_, _>>()?;
-        Ok(Value::Set(elements))
-    }
-}
-
-impl SetComprehension {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let values = self.value.evaluate(exec)?.into_list()?;
-        let mut elements = BTreeSet::new();
-        let mut loop_locals = VariableMap::nested(exec.locals);
-        for value in values {
-            loop_locals.clear();
-            let mut loop_exec = ExecutionContext {
-                source: exec.source,
-                graph: exec.graph,
-                config: exec.config,
-                locals: &mut loop_locals,
-                scoped: exec.scoped,
-                current_regex_captures: exec.current_regex_captures,
-                function_parameters: exec.function_parameters,
-                mat: exec.mat,
-                error_context: exec.error_context.clone(),
-                shorthands: exec.shorthands,
-                cancellation_flag: exec.cancellation_flag,
-            };
-            self.variable.add(&mut loop_exec, value, false)?;
-            let element = self.element.evaluate(&mut loop_exec)?;
-            elements.insert(element);
-        }
-        Ok(Value::Set(elements))
-    }
-}
-
-impl Capture {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        Ok(Value::from_nodes(
-            exec.graph,
-            exec.mat
-                .nodes_for_capture_index(self.stanza_capture_index as u32),
-            self.quantifier,
-        )
-        .into())
-    }
-}
-
-impl Call {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        for parameter in &self.parameters {
-            let parameter = parameter.evaluate(exec)?;
-            exec.function_parameters.push(parameter);
-        }
-        exec.config.functions.call(
-            &self.function,
-            exec.graph,
-            exec.source,
-            &mut exec
-                .function_parameters
-                .drain(exec.function_parameters.len() - self.parameters.len()..),
-        )
-    }
-}
-
-impl RegexCapture {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let capture = exec
-            .current_regex_captures
-            .get(self.match_index)
-            .ok_or(ExecutionError::UndefinedRegexCapture(format!("{}", self)))?;
-        Ok(Value::String(capture.clone()))
-    }
-}
-
-impl Variable {
-    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        let value = self.get(exec)?;
-        Ok(value.clone())
-    }
-}
-
-impl Variable {
-    fn get<'a>(&self, exec: &'a mut ExecutionContext) -> Result<&'a Value, ExecutionError> {
-        match self {
-            Variable::Scoped(variable) => variable.get(exec),
-            Variable::Unscoped(variable) => variable.get(exec),
-        }
-    }
-
-    fn add(
-        &self,
-        exec: &mut ExecutionContext,
-        value: Value,
-        mutable: bool,
-    ) -> Result<(), ExecutionError> {
-        match self {
-            Variable::Scoped(variable) => variable.add(exec, value, mutable),
-            Variable::Unscoped(variable) => variable.add(exec, value, mutable),
-        }
-    }
-
-    fn set(&self, exec: &mut ExecutionContext, value: Value) -> Result<(), ExecutionError> {
-        match self {
-            Variable::Scoped(variable) => variable.set(exec, value),
-            Variable::Unscoped(variable) => variable.set(exec, value),
-        }
-    }
-}
-
-impl ScopedVariable {
-    fn get<'a>(&self, exec: &'a mut ExecutionContext) -> Result<&'a Value, ExecutionError> {
-        let scope = self.scope.evaluate(exec)?;
-        let scope = match scope {
-            Value::SyntaxNode(scope) => scope,
-            _ => {
-                return Err(ExecutionError::InvalidVariableScope(format!(
-                    "got {}",
-                    scope
-                )))
-            }
-        };
-        let variables = exec.scoped.get(scope);
-        if let Some(value) = variables.get(&self.name) {
-            Ok(value)
-        } else {
-            Err(ExecutionError::UndefinedVariable(format!(
-                "{} on node {}",
-                self, scope
-            )))
-        }
-    }
-
-    fn add(
-        &self,
-        exec: &mut ExecutionContext,
-        value: Value,
-        mutable: bool,
-    ) -> Result<(), ExecutionError> {
-        let scope = self.scope.evaluate(exec)?;
-        let scope = match scope {
-            Value::SyntaxNode(scope) => scope,
-            _ => {
-                return Err(ExecutionError::InvalidVariableScope(format!(
-                    "got {}",
-                    scope
-                )))
-            }
-        };
-        let variables = exec.scoped.get(scope);
-        variables
-            .add(self.name.clone(), value, mutable)
-            .map_err(|_| ExecutionError::DuplicateVariable(format!("{}", self)))
-    }
-
-    fn set(&self, exec: &mut ExecutionContext, value: Value) -> Result<(), ExecutionError> {
-        let scope = self.scope.evaluate(exec)?;
-        let scope = match scope {
-            Value::SyntaxNode(scope) => scope,
-            _ => {
-                return Err(ExecutionError::InvalidVariableScope(format!(
-                    "got {}",
-                    scope,
-                )))
-            }
-        };
-        let variables = exec.scoped.get(scope);
-        variables
-            .set(self.name.clone(), value)
-            .map_err(|_| ExecutionError::DuplicateVariable(format!("{}", self)))
-    }
-}
-
-impl UnscopedVariable {
-    fn get<'a>(&self, exec: &'a mut ExecutionContext) -> Result<&'a Value, ExecutionError> {
-        if let Some(value) = exec.config.globals.get(&self.name) {
-            Some(value)
-        } else {
-            exec.locals.get(&self.name)
-        }
-        .ok_or_else(|| ExecutionError::UndefinedVariable(format!("{}", self)))
-    }
-
-    fn add(
-        &self,
-        exec: &mut ExecutionContext,
-        value: Value,
-        mutable: bool,
-    ) -> Result<(), ExecutionError> {
-        if exec.config.globals.get(&self.name).is_some() {
-            return Err(ExecutionError::DuplicateVariable(format!(
-                " global {}",
-                self,
-            )));
-        }
-        exec.locals
-            .add(self.name.clone(), value, mutable)
-            .map_err(|_| ExecutionError::DuplicateVariable(format!(" local {}", self)))
-    }
 
-    fn set(&self, exec: &mut ExecutionContext, value: Value) -> Result<(), ExecutionError> {
-        if exec.config.globals.get(&self.name).is_some() {
-            return Err(ExecutionError::CannotAssignImmutableVariable(format!(
-                " global {}",
-                self,
-            )));
-        }
-        exec.locals.set(self.name.clone(), value).map_err(|_| {
-            if exec.locals.get(&self.name).is_some() {
-                ExecutionError::CannotAssignImmutableVariable(format!("{}", self))
-            } else {
-                ExecutionError::UndefinedVariable(format!("{}", self))
-            }
-        })
-    }
-}
-
-impl Attribute {
-    fn execute<F>(
-        &self,
-        exec: &mut ExecutionContext,
-        add_attribute: &F,
-    ) -> Result<(), ExecutionError>
-    where
-        F: Fn(&mut ExecutionContext, Identifier, Value) -> Result<(), ExecutionError>,
-    {
-        exec.cancellation_flag.check("executing attribute")?;
-        let value = self.value.evaluate(exec)?;
-        if let Some(shorthand) = exec.shorthands.get(&self.name) {
-            shorthand.execute(exec, add_attribute, value)
-        } else {
-            add_attribute(exec, self.name.clone(), value)
-        }
+    fn remaining(&self) -> usize {
+        self.parameters.len()
     }
-}
 
-impl AttributeShorthand {
-    fn execute<F>(
-        &self,
-        exec: &mut ExecutionContext,
-        add_attribute: &F,
-        value: Value,
-    ) -> Result<(), ExecutionError>
-    where
-        F: Fn(&mut ExecutionContext, Identifier, Value) -> Result<(), ExecutionError>,
-    {
-        let mut shorthand_locals = VariableMap::new();
-        let mut shorthand_exec = ExecutionContext {
-            source: exec.source,
-            graph: exec.graph,
-            config: exec.config,
-            locals: &mut shorthand_locals,
-            scoped: exec.scoped,
-            current_regex_captures: exec.current_regex_captures,
-            function_parameters: exec.function_parameters,
-            mat: exec.mat,
-            error_context: exec.error_context.clone(),
-            shorthands: exec.shorthands,
-            cancellation_flag: exec.cancellation_flag,
-        };
-        self.variable.add(&mut shorthand_exec, value, false)?;
-        for attr in &self.attributes {
-            attr.execute(&mut shorthand_exec, add_attribute)?;
-        }
-        Ok(())
+    fn context(&mut self) -> (&mut Graph<'tree>, &mut ExecutionState) {
+        (self.interpreter.graph, self.interpreter.state)
     }
 }
@@ -0,0 +1,641 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Lowers the stanzas of a file into flat code for the strict execution engine
+//!
+//! Expressions are compiled into postfix code that pushes values onto a stack, and statements into
+//! ops that pop their operands from it.  Local variables are resolved to slots in a frame, and
+//! attribute shorthands to their code, when the file is compiled.  Programs do not depend on the
+//! configuration of an execution, so a file compiles its program once, the first time it is
+//! executed, and each execution links it to its global variables and functions, so that none of
+//! them are looked up by name while the stanzas are executed.  The blocks of a program, such as
+//! stanza bodies and the arms of `if` statements, are ranges of ops in a single array, whose ops
+//! are executed in order.
+
+use std::collections::HashMap;
+use std::fmt;
+use tree_sitter::CaptureQuantifier;
+
+use crate::ast::Attribute;
+use crate::ast::Condition;
+use crate::ast::Expression;
+use crate::ast::File;
+use crate::ast::FunctionRef;
+use crate::ast::ScopedVariable;
+use crate::ast::Statement;
+use crate::ast::UnscopedVariable;
+use crate::ast::Variable;
+use crate::execution::error::ExecutionError;
+use crate::execution::ExecutionConfig;
+use crate::functions::Function;
+use crate::functions::Functions;
+use crate::graph::Value;
+use crate::Identifier;
+
+/// The stanzas and attribute shorthands of a file, compiled for the strict engine
+pub(crate) struct Program {
+    ops: Vec<Op>,
+    /// The code of each stanza of the file, in file order
+    pub(super) stanzas: Vec<Code>,
+    /// The code of each attribute shorthand of the file, which expects the shorthand's value on
+    /// the stack
+    pub(super) shorthands: Vec<Code>,
+    /// The number of statements in the stanzas of the file
+    statement_count: usize,
+    /// The names of the unscoped variables that the code uses, which refer to global variables
+    /// if the execution has global variables with those names
+    names: Vec<Identifier>,
+    /// The names of the functions that the code calls
+    calls: Vec<Identifier>,
+    /// The names of the functions that the code refers to with function references
+    references: Vec<Identifier>,
+}
+
+impl fmt::Debug for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Program")
+            .field("ops", &self.ops.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A block of code, and the number of local variable slots that its frame needs
+pub(super) struct Code {
+    pub(super) body: Block,
+    pub(super) slots: usize,
+}
+
+/// A range of ops of a program, which are executed in order
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Block {
+    start: usize,
+    end: usize,
+}
+
+impl Block {
+    pub(super) fn ops<'p>(&self, program: &'p Program) -> &'p [Op] {
+        &program.ops[self.start..self.end]
+    }
+}
+
+/// Ops that belong to a statement, such as [`Op::CreateGraphNode`], get the AST node of the
+/// statement from the [`Op::Statement`] that starts it.  Names and functions are referred to by
+/// their index in the tables of the program, which are resolved when it is linked.
+pub(super) enum Op {
+    // Statements
+    /// Starts executing the statement with the given index, which errors in the following ops of
+    /// the block are reported in
+    Statement(usize),
+    /// Pops a value and stores it in a local variable slot, unless the execution has a global
+    /// variable with the name, which the local variable would hide
+    Store(usize, usize),
+    /// Pops a value and assigns it to the unscoped variable with the given name, which is the
+    /// local variable in the given slot, and whether it is mutable, if there is one
+    Assign(usize, Option<(usize, bool)>),
+    /// Pops a syntax node and a value, and adds the value as a variable of the syntax node
+    AddScoped(ScopedName, bool),
+    /// Pops a syntax node and a value, and sets a variable of the syntax node to the value
+    SetScoped(ScopedName),
+    /// Creates a graph node and pushes it
+    CreateGraphNode,
+    /// Pops a graph node, which the following attribute ops add attributes to
+    NodeAttributes,
+    /// Checks that the value on top of the stack is a graph node
+    ExpectGraphNode,
+    /// Pops a sink and a source graph node, and adds an edge between them
+    CreateEdge,
+    /// Pops a sink and a source graph node, whose edge the following attribute ops add
+    /// attributes to
+    EdgeAttributes,
+    /// Starts executing an attribute
+    StartAttribute,
+    /// Pops a value and adds it as an attribute
+    Attribute(Identifier),
+    /// Pops a value and executes the attribute shorthand with the given index for it
+    Shorthand(usize),
+    /// Pops a string and executes the block of the scan arm that matches each part of it
+    Scan(Box<[Block]>),
+    PrintString(String),
+    /// Pops a value and prints it
+    PrintValue,
+    PrintNewline,
+    /// Executes the block of the first arm whose conditions hold
+    If(Box<[IfArm]>),
+    /// Pops a list, and executes the block for each of its elements, which is pushed first
+    ForIn(Block),
+
+    // Expressions
+    Constant(Value),
+    Capture(u32, CaptureQuantifier),
+    /// Pushes the value of the unscoped variable with the given name, which is the local
+    /// variable in the given slot, if there is one, and the execution has no global variable with
+    /// the name
+    Variable(usize, Option<usize>),
+    /// Pops a syntax node and pushes the value of a variable of it
+    Scoped(ScopedName),
+    /// Pushes the regular expression capture with the given index
+    RegexCapture(usize),
+    /// Pops the given number of values and pushes a list of them
+    List(usize),
+    /// Pops the given number of values and pushes a set of them
+    Set(usize),
+    /// Pops a list, and pushes a list or set of the values of the block for each of its
+    /// elements, which is pushed first
+    Comprehension(Block, bool),
+    /// Calls the function with the given index with the values of the blocks, which are evaluated
+    /// on demand if the function evaluates its parameters lazily
+    Call(usize, Box<[Block]>),
+    /// Pushes a reference to the function with the given index
+    FunctionRef(usize),
+    /// Pops a value and pushes the result of testing it
+    Condition(Test),
+}
+
+/// A scoped variable, and how errors about it show it
+pub(super) struct ScopedName {
+    pub(super) name: Identifier,
+    pub(super) text: String,
+}
+
+impl ScopedName {
+    fn new(variable: &ScopedVariable) -> Self {
+        Self {
+            name: variable.name.clone(),
+            text: variable.to_string(),
+        }
+    }
+}
+
+/// What a condition of an `if` statement tests its value for
+#[derive(Clone, Copy)]
+pub(super) enum Test {
+    Some,
+    None,
+    Bool,
+}
+
+/// An arm of an `if` statement, whose conditions block pushes the result of each condition
+pub(super) struct IfArm {
+    pub(super) conditions: Block,
+    pub(super) condition_count: usize,
+    pub(super) statements: Block,
+}
+
+/// A program that is linked to the global variables and functions of an execution
+pub(super) struct LinkedProgram<'a> {
+    pub(super) program: &'a Program,
+    statements: Vec<&'a Statement>,
+    globals: Vec<bool>,
+    calls: Vec<Option<&'a (dyn Function + Send + Sync)>>,
+    references: Vec<Option<Value>>,
+}
+
+impl<'a> LinkedProgram<'a> {
+    /// Returns the statement with the given index.
+    pub(super) fn statement(&self, index: usize) -> &'a Statement {
+        self.statements[index]
+    }
+
+    /// Returns the unscoped variable name with the given index.
+    pub(super) fn name(&self, name: usize) -> &'a Identifier {
+        &self.program.names[name]
+    }
+
+    /// Returns whether the unscoped variable name with the given index refers to a global
+    /// variable.
+    pub(super) fn is_global(&self, name: usize) -> bool {
+        self.globals[name]
+    }
+
+    /// Returns the function with the given index.
+    pub(super) fn function(
+        &self,
+        index: usize,
+    ) -> Result<&'a (dyn Function + Send + Sync), ExecutionError> {
+        self.calls[index].ok_or_else(|| {
+            ExecutionError::UndefinedFunction(format!("{}", self.program.calls[index]))
+        })
+    }
+
+    /// Returns a reference to the function with the given index.
+    pub(super) fn reference(&self, index: usize) -> Result<Value, ExecutionError> {
+        self.references[index].clone().ok_or_else(|| {
+            ExecutionError::UndefinedFunction(format!("{}", self.program.references[index]))
+        })
+    }
+}
+
+impl Program {
+    /// Compiles the stanzas and attribute shorthands of a file.
+    pub(super) fn compile(file: &File) -> Self {
+        let shorthands = file.shorthands.iter().collect::<Vec<_>>();
+        let mut compiler = Compiler {
+            ops: Vec::new(),
+            shorthand_indices: (shorthands.iter().enumerate())
+                .map(|(index, shorthand)| (&shorthand.name, index))
+                .collect(),
+            scopes: Vec::new(),
+            slots: 0,
+            statement_count: 0,
+            names: Table::default(),
+            calls: Table::default(),
+            references: Table::default(),
+        };
+        let stanzas = (file.stanzas.iter())
+            .map(|stanza| {
+                compiler.code(|compiler, code| compiler.statements(&stanza.statements, code))
+            })
+            .collect();
+        let shorthands = (shorthands.into_iter())
+            .map(|shorthand| {
+                compiler.code(|compiler, code| {
+                    compiler.add_local(&shorthand.variable, code);
+                    for attribute in &shorthand.attributes {
+                        compiler.attribute(attribute, code);
+                    }
+                })
+            })
+            .collect();
+        Program {
+            ops: compiler.ops,
+            stanzas,
+            shorthands,
+            statement_count: compiler.statement_count,
+            names: compiler.names.names,
+            calls: compiler.calls.names,
+            references: compiler.references.names,
+        }
+    }
+
+    /// Returns whether the code calls or refers to a function that
+    /// [reads the graph][`Function::reads_graph`].
+    pub(super) fn reads_graph(&self, functions: &Functions) -> bool {
+        (self.calls.iter())
+            .chain(&self.references)
+            .any(|name| functions.reads_graph(name))
+    }
+
+    /// Links this program, which was compiled from `file`, to the global variables and functions
+    /// of an execution.  The global variables must already have been checked.
+    pub(super) fn link<'a>(
+        &'a self,
+        file: &'a File,
+        config: &ExecutionConfig<'a, '_>,
+    ) -> LinkedProgram<'a> {
+        let mut statements = Vec::with_capacity(self.statement_count);
+        for stanza in &file.stanzas {
+            add_statements(&stanza.statements, &mut statements);
+        }
+        debug_assert_eq!(statements.len(), self.statement_count);
+        LinkedProgram {
+            program: self,
+            statements,
+            globals: (self.names.iter())
+                .map(|name| config.globals.contains(name))
+                .collect(),
+            calls: (self.calls.iter())
+                .map(|name| config.functions.lookup(name).ok())
+                .collect(),
+            references: (self.references.iter())
+                .map(|name| config.functions.get(name).map(Value::from))
+                .collect(),
+        }
+    }
+}
+
+/// Adds `statements`, and the statements nested in them, to `table`, in the order in which the
+/// compiler numbers them.
+fn add_statements<'a>(statements: &'a [Statement], table: &mut Vec<&'a Statement>) {
+    for statement in statements {
+        table.push(statement);
+        match statement {
+            Statement::Scan(stmt) => {
+                for arm in &stmt.arms {
+                    add_statements(&arm.statements, table);
+                }
+            }
+            Statement::If(stmt) => {
+                for arm in &stmt.arms {
+                    add_statements(&arm.statements, table);
+                }
+            }
+            Statement::ForIn(stmt) => add_statements(&stmt.statements, table),
+            _ => {}
+        }
+    }
+}
+
+/// The names that a program refers to by index
+#[derive(Default)]
+struct Table<'a> {
+    indices: HashMap<&'a Identifier, usize>,
+    names: Vec<Identifier>,
+}
+
+impl<'a> Table<'a> {
+    fn index(&mut self, name: &'a Identifier) -> usize {
+        let names = &mut self.names;
+        *self.indices.entry(name).or_insert_with(|| {
+            names.push(name.clone());
+            names.len() - 1
+        })
+    }
+}
+
+struct Compiler<'a> {
+    ops: Vec<Op>,
+    shorthand_indices: HashMap<&'a Identifier, usize>,
+    /// The local variables in scope, innermost last, with their slots and whether they are
+    /// mutable
+    scopes: Vec<HashMap<&'a Identifier, (usize, bool)>>,
+    slots: usize,
+    statement_count: usize,
+    names: Table<'a>,
+    calls: Table<'a>,
+    references: Table<'a>,
+}
+
+impl<'a> Compiler<'a> {
+    /// Compiles code with its own frame of local variables.
+    fn code<F>(&mut self, compile: F) -> Code
+    where
+        F: FnOnce(&mut Self, &mut Vec<Op>),
+    {
+        let scopes = std::mem::take(&mut self.scopes);
+        let slots = std::mem::replace(&mut self.slots, 0);
+        let body = self.scoped_block(compile);
+        let code = Code {
+            body,
+            slots: self.slots,
+        };
+        self.scopes = scopes;
+        self.slots = slots;
+        code
+    }
+
+    /// Compiles a block, in which local variables can be added that are not visible outside of
+    /// it.
+    fn scoped_block<F>(&mut self, compile: F) -> Block
+    where
+        F: FnOnce(&mut Self, &mut Vec<Op>),
+    {
+        self.scopes.push(HashMap::new());
+        let block = self.block(compile);
+        self.scopes.pop();
+        block
+    }
+
+    /// Compiles a block.  The ops of the block are added to the program after the ops of any
+    /// blocks nested in it.
+    fn block<F>(&mut self, compile: F) -> Block
+    where
+        F: FnOnce(&mut Self, &mut Vec<Op>),
+    {
+        let mut code = Vec::new();
+        compile(self, &mut code);
+        let start = self.ops.len();
+        self.ops.extend(code);
+        Block {
+            start,
+            end: self.ops.len(),
+        }
+    }
+
+    fn statements(&mut self, statements: &'a [Statement], code: &mut Vec<Op>) {
+        for statement in statements {
+            code.push(Op::Statement(self.statement_count));
+            self.statement_count += 1;
+            self.statement(statement, code);
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement, code: &mut Vec<Op>) {
+        match statement {
+            Statement::DeclareImmutable(stmt) => {
+                self.expression(&stmt.value, code);
+                self.add_variable(&stmt.variable, false, code);
+            }
+            Statement::DeclareMutable(stmt) => {
+                self.expression(&stmt.value, code);
+                self.add_variable(&stmt.variable, true, code);
+            }
+            Statement::Assign(stmt) => {
+                self.expression(&stmt.value, code);
+                self.set_variable(&stmt.variable, code);
+            }
+            Statement::CreateGraphNode(stmt) => {
+                code.push(Op::CreateGraphNode);
+                self.add_variable(&stmt.node, false, code);
+            }
+            Statement::AddGraphNodeAttribute(stmt) => {
+                self.expression(&stmt.node, code);
+                code.push(Op::NodeAttributes);
+                for attribute in &stmt.attributes {
+                    self.attribute(attribute, code);
+                }
+            }
+            Statement::CreateEdge(stmt) => {
+                self.expression(&stmt.source, code);
+                code.push(Op::ExpectGraphNode);
+                self.expression(&stmt.sink, code);
+                code.push(Op::CreateEdge);
+            }
+            Statement::AddEdgeAttribute(stmt) => {
+                self.expression(&stmt.source, code);
+                code.push(Op::ExpectGraphNode);
+                self.expression(&stmt.sink, code);
+                code.push(Op::EdgeAttributes);
+                for attribute in &stmt.attributes {
+                    self.attribute(attribute, code);
+                }
+            }
+            Statement::Scan(stmt) => {
+                self.expression(&stmt.value, code);
+                let arms = (stmt.arms.iter())
+                    .map(|arm| {
+                        self.scoped_block(|compiler, code| {
+                            compiler.statements(&arm.statements, code)
+                        })
+                    })
+                    .collect();
+                code.push(Op::Scan(arms));
+            }
+            Statement::Print(stmt) => {
+                for value in &stmt.values {
+                    if let Expression::StringConstant(expr) = value {
+                        code.push(Op::PrintString(expr.value.clone()));
+                    } else {
+                        self.expression(value, code);
+                        code.push(Op::PrintValue);
+                    }
+                }
+                code.push(Op::PrintNewline);
+            }
+            Statement::If(stmt) => {
+                let arms = (stmt.arms.iter())
+                    .map(|arm| IfArm {
+                        conditions: self.block(|compiler, code| {
+                            for condition in &arm.conditions {
+                                compiler.condition(condition, code);
+                            }
+                        }),
+                        condition_count: arm.conditions.len(),
+                        statements: self.scoped_block(|compiler, code| {
+                            compiler.statements(&arm.statements, code)
+                        }),
+                    })
+                    .collect();
+                code.push(Op::If(arms));
+            }
+            Statement::ForIn(stmt) => {
+                self.expression(&stmt.value, code);
+                let body = self.scoped_block(|compiler, code| {
+                    compiler.add_local(&stmt.variable, code);
+                    compiler.statements(&stmt.statements, code);
+                });
+                code.push(Op::ForIn(body));
+            }
+        }
+    }
+
+    fn attribute(&mut self, attribute: &'a Attribute, code: &mut Vec<Op>) {
+        code.push(Op::StartAttribute);
+        self.expression(&attribute.value, code);
+        match self.shorthand_indices.get(&attribute.name) {
+            Some(index) => code.push(Op::Shorthand(*index)),
+            None => code.push(Op::Attribute(attribute.name.clone())),
+        }
+    }
+
+    fn condition(&mut self, condition: &'a Condition, code: &mut Vec<Op>) {
+        let (value, test) = match condition {
+            Condition::Some { value, .. } => (value, Test::Some),
+            Condition::None { value, .. } => (value, Test::None),
+            Condition::Bool { value, .. } => (value, Test::Bool),
+        };
+        self.expression(value, code);
+        code.push(Op::Condition(test));
+    }
+
+    fn expression(&mut self, expression: &'a Expression, code: &mut Vec<Op>) {
+        match expression {
+            Expression::FalseLiteral => code.push(Op::Constant(Value::Boolean(false))),
+            Expression::NullLiteral => code.push(Op::Constant(Value::Null)),
+            Expression::TrueLiteral => code.push(Op::Constant(Value::Boolean(true))),
+            Expression::IntegerConstant(expr) => {
+                code.push(Op::Constant(Value::Integer(expr.value)))
+            }
+            Expression::StringConstant(expr) => {
+                code.push(Op::Constant(Value::String(expr.value.clone().into())))
+            }
+            Expression::ListLiteral(expr) => {
+                for element in &expr.elements {
+                    self.expression(element, code);
+                }
+                code.push(Op::List(expr.elements.len()));
+            }
+            Expression::SetLiteral(expr) => {
+                for element in &expr.elements {
+                    self.expression(element, code);
+                }
+                code.push(Op::Set(expr.elements.len()));
+            }
+            Expression::ListComprehension(expr) => {
+                self.expression(&expr.value, code);
+                let element = self.comprehension(&expr.variable, &expr.element);
+                code.push(Op::Comprehension(element, false));
+            }
+            Expression::SetComprehension(expr) => {
+                self.expression(&expr.value, code);
+                let element = self.comprehension(&expr.variable, &expr.element);
+                code.push(Op::Comprehension(element, true));
+            }
+            Expression::Capture(expr) => {
+                code.push(Op::Capture(expr.file_capture_index as u32, expr.quantifier))
+            }
+            Expression::Variable(Variable::Scoped(variable)) => {
+                self.expression(&variable.scope, code);
+                code.push(Op::Scoped(ScopedName::new(variable)));
+            }
+            Expression::Variable(Variable::Unscoped(variable)) => {
+                let slot = self.local(&variable.name).map(|(slot, _)| slot);
+                code.push(Op::Variable(self.names.index(&variable.name), slot));
+            }
+            Expression::Call(expr) => {
+                let parameters = (expr.parameters.iter())
+                    .map(|parameter| {
+                        self.block(|compiler, code| compiler.expression(parameter, code))
+                    })
+                    .collect();
+                code.push(Op::Call(self.calls.index(&expr.function), parameters));
+            }
+            Expression::FunctionRef(FunctionRef { name, .. }) => {
+                code.push(Op::FunctionRef(self.references.index(name)))
+            }
+            Expression::RegexCapture(expr) => code.push(Op::RegexCapture(expr.match_index)),
+        }
+    }
+
+    /// Compiles the element of a comprehension, whose block expects the value of the variable on
+    /// the stack.
+    fn comprehension(&mut self, variable: &'a UnscopedVariable, element: &'a Expression) -> Block {
+        self.scoped_block(|compiler, code| {
+            compiler.add_local(variable, code);
+            compiler.expression(element, code);
+        })
+    }
+
+    fn add_variable(&mut self, variable: &'a Variable, mutable: bool, code: &mut Vec<Op>) {
+        match variable {
+            Variable::Scoped(variable) => {
+                self.expression(&variable.scope, code);
+                code.push(Op::AddScoped(ScopedName::new(variable), mutable));
+            }
+            Variable::Unscoped(variable) => self.add_local_slot(variable, mutable, code),
+        }
+    }
+
+    fn add_local(&mut self, variable: &'a UnscopedVariable, code: &mut Vec<Op>) {
+        self.add_local_slot(variable, false, code);
+    }
+
+    /// Adds a local variable to the innermost scope, and an op that stores the value on the
+    /// stack in its slot.
+    fn add_local_slot(
+        &mut self,
+        variable: &'a UnscopedVariable,
+        mutable: bool,
+        code: &mut Vec<Op>,
+    ) {
+        let slot = self.slots;
+        self.slots += 1;
+        self.scopes
+            .last_mut()
+            .expect("missing scope")
+            .insert(&variable.name, (slot, mutable));
+        code.push(Op::Store(self.names.index(&variable.name), slot));
+    }
+
+    fn set_variable(&mut self, variable: &'a Variable, code: &mut Vec<Op>) {
+        match variable {
+            Variable::Scoped(variable) => {
+                self.expression(&variable.scope, code);
+                code.push(Op::SetScoped(ScopedName::new(variable)));
+            }
+            Variable::Unscoped(variable) => {
+                let local = self.local(&variable.name);
+                code.push(Op::Assign(self.names.index(&variable.name), local));
+            }
+        }
+    }
+
+    /// Returns the slot of the local variable with the given name in the innermost scope that
+    /// has one, and whether it is mutable.
+    fn local(&self, name: &Identifier) -> Option<(usize, bool)> {
+        (self.scopes.iter().rev()).find_map(|scope| scope.get(name).copied())
+    }
+}
@@ -0,0 +1,246 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Executes independent groups of stanzas on separate threads
+
+use std::collections::HashMap;
+
+use tree_sitter::Tree;
+
+use crate::ast::Attribute;
+use crate::ast::Condition;
+use crate::ast::Expression;
+use crate::ast::File;
+use crate::ast::Statement;
+use crate::ast::Variable;
+use crate::execution::error::ExecutionError;
+use crate::execution::parallel::run_in_parallel;
+use crate::execution::parallel::SharedConfig;
+use crate::execution::run_to_completion;
+use crate::execution::CancellationFlag;
+use crate::execution::ExecutionConfig;
+use crate::execution::ExecutionState;
+use crate::graph::Graph;
+use crate::variables::Globals;
+use crate::Identifier;
+
+impl File {
+    /// Executes this graph DSL file like [`File::execute_strict_into`], but executes each group
+    /// of stanzas that share scoped variables on a separate thread, into a fork of `graph`, and
+    /// then joins the forks back into `graph` in group order.  See
+    /// [`ExecutionConfig::parallel`] for how the result can differ from a sequential execution.
+    pub(in crate::execution) fn execute_strict_parallel_into<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        state: &mut ExecutionState,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let mut globals = Globals::nested(config.globals);
+        self.check_globals(&mut globals)?;
+        let shared = SharedConfig::new(self, config, &globals);
+
+        let groups = self.independent_stanza_groups();
+        let base = graph.snapshot();
+        let mut tasks = Vec::with_capacity(groups.len());
+        for (index, group) in groups.iter().enumerate() {
+            let mut fragment = graph.fork();
+            let mut fragment_state = state.fork(index as u32, groups.len() as u32);
+            let shared = &shared;
+            tasks.push(move |cancellation_flag: &dyn CancellationFlag| {
+                shared.with_config(false, |config| {
+                    config
+                        .functions
+                        .on_execution_start(fragment_state.function_state());
+                    let mut config = config.clone();
+                    config.settings.stanza_group = Some(group.clone());
+                    run_to_completion(self.execute_stanzas_strict(
+                        &mut fragment.0,
+                        &mut fragment_state,
+                        tree,
+                        source,
+                        &config,
+                        cancellation_flag,
+                    ))
+                })?;
+                Ok((fragment, fragment_state))
+            });
+        }
+
+        for (fragment, fragment_state) in run_in_parallel(tasks, cancellation_flag)? {
+            graph.join(&base, fragment)?;
+            state.join(fragment_state)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the stanzas of this file, grouped so that stanzas that use scoped
+    /// variables with the same name are in the same group.  Groups are ordered by their first
+    /// stanza, and the stanzas in each group are in file order.
+    fn independent_stanza_groups(&self) -> Vec<Vec<usize>> {
+        let mut shorthand_names = Vec::new();
+        for shorthand in self.shorthands.iter() {
+            for attribute in &shorthand.attributes {
+                attribute.scoped_variable_names(&mut shorthand_names);
+            }
+        }
+
+        let mut parents = (0..self.stanzas.len()).collect::<Vec<_>>();
+        fn find(parents: &mut [usize], stanza: usize) -> usize {
+            let mut root = stanza;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            parents[stanza] = root;
+            root
+        }
+        let mut users = HashMap::new();
+        for (index, stanza) in self.stanzas.iter().enumerate() {
+            let mut names = shorthand_names.clone();
+            for statement in &stanza.statements {
+                statement.scoped_variable_names(&mut names);
+            }
+            for name in names {
+                let user = *users.entry(name).or_insert(index);
+                let (left, right) = (find(&mut parents, user), find(&mut parents, index));
+                parents[left.max(right)] = left.min(right);
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_indices = HashMap::new();
+        for index in 0..self.stanzas.len() {
+            let root = find(&mut parents, index);
+            let group = *group_indices.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(index);
+        }
+        groups
+    }
+}
+
+impl Statement {
+    /// Adds the names of the scoped variables that this statement uses to `names`.
+    fn scoped_variable_names<'a>(&'a self, names: &mut Vec<&'a Identifier>) {
+        match self {
+            Statement::DeclareImmutable(s) => {
+                s.variable.scoped_variable_names(names);
+                s.value.scoped_variable_names(names);
+            }
+            Statement::DeclareMutable(s) => {
+                s.variable.scoped_variable_names(names);
+                s.value.scoped_variable_names(names);
+            }
+            Statement::Assign(s) => {
+                s.variable.scoped_variable_names(names);
+                s.value.scoped_variable_names(names);
+            }
+            Statement::CreateGraphNode(s) => s.node.scoped_variable_names(names),
+            Statement::AddGraphNodeAttribute(s) => {
+                s.node.scoped_variable_names(names);
+                for attribute in &s.attributes {
+                    attribute.scoped_variable_names(names);
+                }
+            }
+            Statement::CreateEdge(s) => {
+                s.source.scoped_variable_names(names);
+                s.sink.scoped_variable_names(names);
+            }
+            Statement::AddEdgeAttribute(s) => {
+                s.source.scoped_variable_names(names);
+                s.sink.scoped_variable_names(names);
+                for attribute in &s.attributes {
+                    attribute.scoped_variable_names(names);
+                }
+            }
+            Statement::Scan(s) => {
+                s.value.scoped_variable_names(names);
+                for arm in &s.arms {
+                    for statement in &arm.statements {
+                        statement.scoped_variable_names(names);
+                    }
+                }
+            }
+            Statement::Print(s) => {
+                for value in &s.values {
+                    value.scoped_variable_names(names);
+                }
+            }
+            Statement::If(s) => {
+                for arm in &s.arms {
+                    for condition in &arm.conditions {
+                        match condition {
+                            Condition::Some { value, .. }
+                            | Condition::None { value, .. }
+                            | Condition::Bool { value, .. } => value.scoped_variable_names(names),
+                        }
+                    }
+                    for statement in &arm.statements {
+                        statement.scoped_variable_names(names);
+                    }
+                }
+            }
+            Statement::ForIn(s) => {
+                s.value.scoped_variable_names(names);
+                for statement in &s.statements {
+                    statement.scoped_variable_names(names);
+                }
+            }
+        }
+    }
+}
+
+impl Attribute {
+    fn scoped_variable_names<'a>(&'a self, names: &mut Vec<&'a Identifier>) {
+        self.value.scoped_variable_names(names);
+    }
+}
+
+impl Variable {
+    fn scoped_variable_names<'a>(&'a self, names: &mut Vec<&'a Identifier>) {
+        if let Variable::Scoped(variable) = self {
+            names.push(&variable.name);
+            variable.scope.scoped_variable_names(names);
+        }
+    }
+}
+
+impl Expression {
+    fn scoped_variable_names<'a>(&'a self, names: &mut Vec<&'a Identifier>) {
+        match self {
+            Expression::ListLiteral(expr) => {
+                for element in &expr.elements {
+                    element.scoped_variable_names(names);
+                }
+            }
+            Expression::SetLiteral(expr) => {
+                for element in &expr.elements {
+                    element.scoped_variable_names(names);
+                }
+            }
+            Expression::ListComprehension(expr) => {
+                expr.element.scoped_variable_names(names);
+                expr.value.scoped_variable_names(names);
+            }
+            Expression::SetComprehension(expr) => {
+                expr.element.scoped_variable_names(names);
+                expr.value.scoped_variable_names(names);
+            }
+            Expression::Variable(variable) => variable.scoped_variable_names(names),
+            Expression::Call(expr) => {
+                for parameter in &expr.parameters {
+                    parameter.scoped_variable_names(names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
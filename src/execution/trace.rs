@@ -0,0 +1,109 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Records traces of executions
+
+use std::sync::Mutex;
+
+use tree_sitter::Node;
+
+use crate::ast::Stanza;
+use crate::ast::Statement;
+use crate::execution::error::ExecutionError;
+use crate::execution::ExecutionObserver;
+use crate::execution::GraphElement;
+use crate::graph::GraphNodeRef;
+use crate::graph::Trace;
+use crate::graph::TraceEvent;
+use crate::graph::Value;
+use crate::Identifier;
+use crate::Location;
+
+/// An [`ExecutionObserver`] that records a [`Trace`] of the execution: the stanzas and matches
+/// that are executed, the statements that are executed, the thunks that lazy evaluation forces,
+/// the graph nodes, edges, and attributes that are added, and the errors.  Install it with
+/// [`ExecutionConfig::observer`][`crate::ExecutionConfig::observer`], and take the trace with
+/// [`TraceRecorder::into_trace`] when the execution is done, whether or not it succeeded.
+///
+/// The graph nodes in the trace are those of the graph that the execution builds, unless the
+/// execution is [parallel][`crate::ExecutionConfig::parallel`], in which case they cannot be
+/// replayed.
+#[derive(Default)]
+pub struct TraceRecorder {
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+impl TraceRecorder {
+    /// Creates a new recorder, which has not recorded any events yet.
+    pub fn new() -> TraceRecorder {
+        TraceRecorder::default()
+    }
+
+    /// Returns the trace of the events that this recorder recorded.
+    pub fn into_trace(self) -> Trace {
+        Trace {
+            events: self.events.into_inner().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    fn record(&self, event: TraceEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event);
+    }
+}
+
+impl ExecutionObserver for TraceRecorder {
+    fn on_stanza_start(&self, stanza: &Stanza) {
+        self.record(TraceEvent::StanzaStarted {
+            stanza: stanza.range.start,
+        });
+    }
+
+    fn on_match(&self, stanza: &Stanza, node: Node) {
+        self.record(TraceEvent::Matched {
+            stanza: stanza.range.start,
+            node_kind: node.kind().to_string(),
+            source_location: Location::from(node.start_position()),
+        });
+    }
+
+    fn on_statement(&self, statement: &Statement) {
+        self.record(TraceEvent::Statement {
+            location: statement.location(),
+            statement: statement.to_string(),
+        });
+    }
+
+    fn on_thunk_forced(&self, statement_location: Location, value: &Value) {
+        self.record(TraceEvent::ThunkForced {
+            statement: statement_location,
+            value: value.clone(),
+        });
+    }
+
+    fn on_node_created(&self, node: GraphNodeRef) {
+        self.record(TraceEvent::NodeCreated(node));
+    }
+
+    fn on_edge_created(&self, source: GraphNodeRef, sink: GraphNodeRef) {
+        self.record(TraceEvent::EdgeCreated(source, sink));
+    }
+
+    fn on_attr_set(&self, element: GraphElement, name: &Identifier, value: &Value) {
+        self.record(TraceEvent::AttributeSet {
+            element,
+            name: name.clone(),
+            value: value.clone(),
+        });
+    }
+
+    fn on_error(&self, error: &ExecutionError) {
+        self.record(TraceEvent::Error(error.to_string()));
+    }
+}
@@ -0,0 +1,86 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Lets executions give control back to their caller between stanza matches
+//!
+//! The engines are written as futures, which yield between the stanza matches that they execute,
+//! and between the statements of the lazy graph that they evaluate, if they are asked to.  Only
+//! the async entry points of the `async` feature ask them to.  Without it, the futures never
+//! yield, and the blocking entry points finish them with a single poll.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::task::Waker;
+use std::thread;
+use std::thread::Thread;
+
+/// Whether an execution yields to its caller between stanza matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Yielding {
+    Never,
+    #[cfg(feature = "async")]
+    BetweenMatches,
+}
+
+impl Yielding {
+    /// Returns a future that gives control back to the caller once, if the execution yields,
+    /// and is ready immediately otherwise.
+    pub(crate) fn yield_now(self) -> YieldNow {
+        YieldNow {
+            pending: match self {
+                Yielding::Never => false,
+                #[cfg(feature = "async")]
+                Yielding::BetweenMatches => true,
+            },
+        }
+    }
+}
+
+pub(crate) struct YieldNow {
+    pending: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if std::mem::take(&mut self.pending) {
+            // the execution can continue right away, once the caller has had its turn
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Runs an execution to completion on the current thread.  Executions that do not yield are
+/// done after the first poll.  If one yields anyway, it is polled again once it is woken.
+pub(crate) fn run_to_completion<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Wakes the thread that runs an execution to completion
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
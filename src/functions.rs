@@ -7,18 +7,24 @@
 
 //! Functions that can be called by graph DSL files
 
+use std::any::Any;
+use std::any::TypeId;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::execution::error::ExecutionError;
+use crate::execution::ExecutionState;
+use crate::graph::FunctionRef;
 use crate::graph::Graph;
+use crate::graph::GraphNodeRef;
 use crate::graph::Value;
 use crate::Identifier;
 
 /// The implementation of a function that can be called from the graph DSL.
 ///
 /// You have access to the graph, as it has been constructed up to the point of the function call,
-/// as well as the text content of the source file that's being processed.
+/// the [state of the execution][`ExecutionState`] that calls the function, as well as the text
+/// content of the source file that's being processed.
 ///
 /// Any other data that you need must be passed in as a parameter to the function.  You can use the
 /// [`Parameters`][] trait to consume those parameters and verify that you received the correct
@@ -27,9 +33,115 @@ pub trait Function {
     fn call(
         &self,
         graph: &mut Graph,
+        state: &mut ExecutionState,
         source: &str,
         parameters: &mut dyn Parameters,
     ) -> Result<Value, ExecutionError>;
+
+    /// Called at the start of each execution of a graph DSL file, before any stanzas are
+    /// executed.  Functions that need state that lasts for the duration of an execution, such as
+    /// counters or caches, can add it to `state` here, and access it during calls via
+    /// [`ExecutionState::function_state`][].  The state is dropped
+    /// when the execution finishes.
+    fn on_execution_start(&self, _state: &mut FunctionState) {}
+
+    /// Returns whether this function evaluates its parameters on demand.  If it does, then calls
+    /// to it from a graph DSL file go through [`call_lazy`][`Function::call_lazy`] instead of
+    /// [`call`][`Function::call`], and each parameter is only evaluated when the function asks for
+    /// it.  This lets you implement functions that short-circuit, such as
+    /// [`coalesce`][`crate::reference::functions#coalesce`].
+    fn lazy_parameters(&self) -> bool {
+        false
+    }
+
+    /// Calls this function with parameters that are evaluated on demand.  This is only called if
+    /// [`lazy_parameters`][`Function::lazy_parameters`] returns `true`.  The default
+    /// implementation evaluates all of the parameters and passes them to
+    /// [`call`][`Function::call`].
+    fn call_lazy(
+        &self,
+        source: &str,
+        parameters: &mut dyn LazyParameters,
+    ) -> Result<Value, ExecutionError> {
+        let mut values = Vec::with_capacity(parameters.remaining());
+        while parameters.remaining() > 0 {
+            values.push(parameters.param()?);
+        }
+        let (graph, state) = parameters.context();
+        self.call(graph, state, source, &mut values.into_iter())
+    }
+
+    /// Returns whether this function reads the attributes or edges of the graph.  The lazy
+    /// evaluator creates all of the edges of the graph before it calls such a function, and
+    /// reports an error if the function is called while the stanzas are still being executed,
+    /// such as from the condition of an `if` statement, because the graph is not complete yet.
+    fn reads_graph(&self) -> bool {
+        false
+    }
+
+    /// Returns the names of the parameters that this function expects, separated by spaces (for
+    /// instance, `list key`), or `None` if they are not documented.  Optional parameters are
+    /// written in square brackets, and a parameter that can be repeated ends with `...`.
+    fn parameters(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns a short description of what this function does, or `None` if it is not documented.
+    fn doc(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the signature of this function, or `None` if it does not have one.  Functions that
+    /// have a signature have the types of their parameters and results checked when a graph DSL
+    /// file is checked with
+    /// [`File::check_with_functions`][`crate::ast::File::check_with_functions`].
+    fn signature(&self) -> Option<&Signature> {
+        None
+    }
+}
+
+/// A description of a function in a [`Functions`][] library, suitable for showing to the authors
+/// of graph DSL files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FunctionDescription {
+    /// The name of the function
+    pub name: Identifier,
+    /// The signature of the function, in the syntax of a call, such as `(sort-by list key)`
+    pub signature: String,
+    /// A short description of what the function does, or an empty string if it is not documented
+    pub doc: String,
+}
+
+impl FunctionDescription {
+    fn new(name: &Identifier, function: &(dyn Function + Send + Sync)) -> FunctionDescription {
+        let signature = match function.parameters() {
+            Some("") => format!("({})", name),
+            Some(parameters) => format!("({} {})", name, parameters),
+            None => format!("({} ...)", name),
+        };
+        FunctionDescription {
+            name: name.clone(),
+            signature,
+            doc: function.doc().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Returns the namespace of the function, such as `ns` for `ns::name`, or `None` if the
+    /// function is not in a namespace.
+    pub fn namespace(&self) -> Option<&str> {
+        self.name
+            .as_str()
+            .rsplit_once("::")
+            .map(|(namespace, _)| namespace)
+    }
+
+    /// Returns the name of the function within its namespace, such as `name` for `ns::name`.
+    pub fn local_name(&self) -> &str {
+        match self.name.as_str().rsplit_once("::") {
+            Some((_, name)) => name,
+            None => self.name.as_str(),
+        }
+    }
 }
 
 /// A helper trait for consuming the parameters of a function.  You will typically use it as
@@ -41,7 +153,7 @@ pub trait Function {
 /// # use tree_sitter_graph::graph::Value;
 /// # use tree_sitter_graph::ExecutionError;
 /// # fn main() -> Result<(), ExecutionError> {
-/// # let param_vec = vec![Value::String("test".to_string()), Value::Integer(42)];
+/// # let param_vec = vec![Value::from("test"), Value::Integer(42)];
 /// # let mut params = param_vec.into_iter();
 /// let first_param = params.param()?.into_string()?;
 /// let second_param = params.param()?.as_integer()?;
@@ -83,6 +195,412 @@ where
     }
 }
 
+/// State that functions keep for the duration of a single execution of a graph DSL file.  Values
+/// are stored by type, so each function should use its own type for its state.  See
+/// [`Function::on_execution_start`][].
+#[derive(Default)]
+pub struct FunctionState {
+    values: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl FunctionState {
+    /// Creates a new, empty state.
+    pub fn new() -> FunctionState {
+        FunctionState::default()
+    }
+
+    /// Adds a value to the state, replacing any existing value of the same type.
+    pub fn insert<T: Any + Send>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Returns a reference to the value of the given type, if there is one.
+    pub fn get<T: Any + Send>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of the given type, if there is one.
+    pub fn get_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes all values from the state.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// The type of value that a function parameter accepts, as part of a [`Signature`][].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParameterType {
+    /// Any value
+    Any,
+    /// The null value
+    Null,
+    /// A boolean
+    Boolean,
+    /// An integer
+    Integer,
+    /// A string
+    String,
+    /// A list
+    List,
+    /// A set
+    Set,
+    /// A map
+    Map,
+    /// A source span
+    Span,
+    /// A syntax node
+    SyntaxNode,
+    /// A graph node
+    GraphNode,
+    /// A function
+    Function,
+}
+
+impl ParameterType {
+    /// Returns whether a value has this type.
+    pub fn matches(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (Self::Any, _)
+                | (Self::Null, Value::Null)
+                | (Self::Boolean, Value::Boolean(_))
+                | (Self::Integer, Value::Integer(_))
+                | (Self::String, Value::String(_))
+                | (Self::List, Value::List(_))
+                | (Self::Set, Value::Set(_))
+                | (Self::Map, Value::Map(_))
+                | (Self::Span, Value::Span { .. })
+                | (Self::SyntaxNode, Value::SyntaxNode(_))
+                | (Self::GraphNode, Value::GraphNode(_))
+                | (Self::Function, Value::Function(_))
+        )
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Self::Any => "any value",
+            Self::Null => "null",
+            Self::Boolean => "a boolean",
+            Self::Integer => "an integer",
+            Self::String => "a string",
+            Self::List => "a list",
+            Self::Set => "a set",
+            Self::Map => "a map",
+            Self::Span => "a span",
+            Self::SyntaxNode => "a syntax node",
+            Self::GraphNode => "a graph node",
+            Self::Function => "a function",
+        }
+    }
+}
+
+impl std::fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Self::Any => "any",
+            Self::Null => "null",
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::String => "string",
+            Self::List => "list",
+            Self::Set => "set",
+            Self::Map => "map",
+            Self::Span => "span",
+            Self::SyntaxNode => "syntax-node",
+            Self::GraphNode => "graph-node",
+            Self::Function => "function",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A declarative description of the parameters that a function expects.  Functions that are added
+/// to a library with [`Functions::add_with_signature`][] have their parameters checked against
+/// their signature before they are called, so that they don't have to validate the number and
+/// types of their parameters themselves.
+///
+/// ```
+/// # use tree_sitter_graph::functions::ParameterType;
+/// # use tree_sitter_graph::functions::Signature;
+/// // (f integer [string] any...)
+/// let signature = Signature::new()
+///     .required(ParameterType::Integer)
+///     .optional(ParameterType::String)
+///     .variadic(ParameterType::Any);
+/// ```
+///
+/// A signature can also give the type of the value that the function returns, which is not checked
+/// when the function is called, but lets the checker infer the types of the values that calls to
+/// the function compute.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Signature {
+    required: Vec<ParameterType>,
+    optional: Vec<ParameterType>,
+    variadic: Option<ParameterType>,
+    returns: Option<ParameterType>,
+}
+
+impl Signature {
+    /// Creates a new signature for a function that takes no parameters.
+    pub fn new() -> Signature {
+        Signature::default()
+    }
+
+    /// Adds a required parameter, which must come before any optional or variadic parameters.
+    pub fn required(mut self, parameter_type: ParameterType) -> Signature {
+        self.required.push(parameter_type);
+        self
+    }
+
+    /// Adds an optional parameter, which must come after the required parameters.
+    pub fn optional(mut self, parameter_type: ParameterType) -> Signature {
+        self.optional.push(parameter_type);
+        self
+    }
+
+    /// Allows any number of additional parameters of the given type after the required and
+    /// optional ones.
+    pub fn variadic(mut self, parameter_type: ParameterType) -> Signature {
+        self.variadic = Some(parameter_type);
+        self
+    }
+
+    /// Sets the type of the value that the function returns.  Without it, the function can return
+    /// any value.
+    pub fn returns(mut self, return_type: ParameterType) -> Signature {
+        self.returns = Some(return_type);
+        self
+    }
+
+    /// Returns the type of the parameter at the given index, or `None` if the function does not
+    /// take that many parameters.
+    pub(crate) fn parameter_type(&self, index: usize) -> Option<ParameterType> {
+        (self.required.iter())
+            .chain(self.optional.iter())
+            .chain(self.variadic.iter().cycle())
+            .nth(index)
+            .copied()
+    }
+
+    /// Returns the type of the value that the function returns.
+    pub(crate) fn return_type(&self) -> ParameterType {
+        self.returns.unwrap_or(ParameterType::Any)
+    }
+
+    /// Checks that a list of parameters matches this signature.  `name` is the name of the
+    /// function being called, which is included in any error.
+    pub fn check(&self, name: &Identifier, parameters: &[Value]) -> Result<(), ExecutionError> {
+        let min = self.required.len();
+        let max = min + self.optional.len();
+        let count_matches =
+            parameters.len() >= min && (self.variadic.is_some() || parameters.len() <= max);
+        if !count_matches {
+            let singular = min == 1 && (self.variadic.is_some() || max == 1);
+            let expected = if self.variadic.is_some() {
+                format!("at least {}", min)
+            } else if min == max {
+                format!("{}", min)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return Err(ExecutionError::InvalidParameters(format!(
+                "for {}: expected {} parameter{}, got {}",
+                name,
+                expected,
+                if singular { "" } else { "s" },
+                parameters.len(),
+            )));
+        }
+        let types = self
+            .required
+            .iter()
+            .chain(self.optional.iter())
+            .chain(self.variadic.iter().cycle());
+        for (index, (parameter, parameter_type)) in parameters.iter().zip(types).enumerate() {
+            if !parameter_type.matches(parameter) {
+                return Err(ExecutionError::InvalidParameters(format!(
+                    "for {}: expected parameter {} to be {}, got {}",
+                    name,
+                    index + 1,
+                    parameter_type.description(),
+                    parameter,
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parameters = Vec::new();
+        parameters.extend(self.required.iter().map(|t| t.to_string()));
+        parameters.extend(self.optional.iter().map(|t| format!("[{}]", t)));
+        parameters.extend(self.variadic.iter().map(|t| format!("{}...", t)));
+        write!(f, "{}", parameters.join(" "))
+    }
+}
+
+/// A function whose parameters are checked against a [`Signature`][] before it is called.
+struct SignedFunction<F> {
+    name: Identifier,
+    signature: Signature,
+    parameters: String,
+    function: F,
+}
+
+impl<F: Function> Function for SignedFunction<F> {
+    fn call(
+        &self,
+        graph: &mut Graph,
+        state: &mut ExecutionState,
+        source: &str,
+        parameters: &mut dyn Parameters,
+    ) -> Result<Value, ExecutionError> {
+        let mut values = Vec::new();
+        while let Ok(value) = parameters.param() {
+            values.push(value);
+        }
+        self.signature.check(&self.name, &values)?;
+        self.function
+            .call(graph, state, source, &mut values.into_iter())
+    }
+
+    fn on_execution_start(&self, state: &mut FunctionState) {
+        self.function.on_execution_start(state)
+    }
+
+    fn reads_graph(&self) -> bool {
+        self.function.reads_graph()
+    }
+
+    fn parameters(&self) -> Option<&str> {
+        self.function.parameters().or(Some(&self.parameters))
+    }
+
+    fn doc(&self) -> Option<&str> {
+        self.function.doc()
+    }
+
+    fn signature(&self) -> Option<&Signature> {
+        Some(&self.signature)
+    }
+}
+
+/// A function whose results are cached for the duration of each execution, by the values of its
+/// parameters.  See [`Functions::add_pure`][].
+struct PureFunction<F> {
+    name: Identifier,
+    function: F,
+}
+
+/// The results of the calls to pure functions in the current execution
+#[derive(Default)]
+struct PureCalls(HashMap<(Identifier, Vec<Value>), Value>);
+
+impl<F: Function> Function for PureFunction<F> {
+    fn call(
+        &self,
+        graph: &mut Graph,
+        state: &mut ExecutionState,
+        source: &str,
+        parameters: &mut dyn Parameters,
+    ) -> Result<Value, ExecutionError> {
+        let mut values = Vec::new();
+        while let Ok(value) = parameters.param() {
+            values.push(value);
+        }
+        let key = (self.name.clone(), values);
+        if let Some(result) = state
+            .function_state()
+            .get::<PureCalls>()
+            .and_then(|calls| calls.0.get(&key))
+        {
+            return Ok(result.clone());
+        }
+        let result = self
+            .function
+            .call(graph, state, source, &mut key.1.clone().into_iter())?;
+        if let Some(calls) = state.function_state().get_mut::<PureCalls>() {
+            calls.0.insert(key, result.clone());
+        }
+        Ok(result)
+    }
+
+    fn on_execution_start(&self, state: &mut FunctionState) {
+        self.function.on_execution_start(state);
+        if state.get::<PureCalls>().is_none() {
+            state.insert(PureCalls::default());
+        }
+    }
+
+    fn reads_graph(&self) -> bool {
+        self.function.reads_graph()
+    }
+
+    fn parameters(&self) -> Option<&str> {
+        self.function.parameters()
+    }
+
+    fn doc(&self) -> Option<&str> {
+        self.function.doc()
+    }
+
+    fn signature(&self) -> Option<&Signature> {
+        self.function.signature()
+    }
+}
+
+/// The parameters of a call to a function that [evaluates its parameters on
+/// demand][`Function::lazy_parameters`].  A parameter is only evaluated when it is requested with
+/// [`param`][`LazyParameters::param`]; parameters that are skipped, or never requested, are not
+/// evaluated at all.
+pub trait LazyParameters<'tree> {
+    /// Evaluates and returns the next parameter, returning an error if you have exhausted all of
+    /// the parameters that were passed in.
+    fn param(&mut self) -> Result<Value, ExecutionError>;
+
+    /// Skips the next parameter without evaluating it, returning an error if you have exhausted
+    /// all of the parameters that were passed in.
+    fn skip(&mut self) -> Result<(), ExecutionError>;
+
+    /// Returns the number of parameters that have not been evaluated or skipped yet.
+    fn remaining(&self) -> usize;
+
+    /// Returns the graph that is being constructed, and the state of the execution that is
+    /// constructing it.
+    fn context(&mut self) -> (&mut Graph<'tree>, &mut ExecutionState);
+
+    /// Makes sure that the given attribute of a graph node has been set, if any statement sets
+    /// it, so that a function that [reads the graph][`Function::reads_graph`] sees its value
+    /// regardless of where in the file it is set.  This does nothing when the statements are
+    /// executed in order.
+    fn require_node_attribute(
+        &mut self,
+        _node: GraphNodeRef,
+        _name: &str,
+    ) -> Result<(), ExecutionError> {
+        Ok(())
+    }
+
+    /// Ensures that there are no more parameters to consume.
+    fn finish(&mut self) -> Result<(), ExecutionError> {
+        if self.remaining() > 0 {
+            return Err(ExecutionError::InvalidParameters(
+                "unexpected extra parameter".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// A library of named functions.
 #[derive(Default)]
 pub struct Functions {
@@ -102,6 +620,16 @@ impl Functions {
         // general functions
         functions.add(Identifier::from("eq"), stdlib::Eq);
         functions.add(Identifier::from("is-null"), stdlib::IsNull);
+        functions.add(Identifier::from("is-string"), stdlib::IsString);
+        functions.add(Identifier::from("is-int"), stdlib::IsInt);
+        functions.add(Identifier::from("is-list"), stdlib::IsList);
+        functions.add(Identifier::from("is-set"), stdlib::IsSet);
+        functions.add(Identifier::from("is-map"), stdlib::IsMap);
+        functions.add(Identifier::from("is-span"), stdlib::IsSpan);
+        functions.add(Identifier::from("is-syntax-node"), stdlib::IsSyntaxNode);
+        functions.add(Identifier::from("is-graph-node"), stdlib::IsGraphNode);
+        functions.add(Identifier::from("contains"), stdlib::Contains);
+        functions.add(Identifier::from("coalesce"), stdlib::Coalesce);
         // tree functions
         functions.add(
             Identifier::from("named-child-index"),
@@ -115,30 +643,151 @@ impl Functions {
         );
         functions.add(Identifier::from("end-row"), stdlib::syntax::EndRow);
         functions.add(Identifier::from("end-column"), stdlib::syntax::EndColumn);
+        functions.add(Identifier::from("start-byte"), stdlib::syntax::StartByte);
+        functions.add(Identifier::from("end-byte"), stdlib::syntax::EndByte);
+        functions.add(Identifier::from("span"), stdlib::syntax::Span);
+        functions.add(
+            Identifier::from("text-between"),
+            stdlib::syntax::TextBetween,
+        );
+        functions.add(Identifier::from("source-line"), stdlib::syntax::SourceLine);
         functions.add(Identifier::from("node-type"), stdlib::syntax::NodeType);
+        functions.add(Identifier::from("kind-id"), stdlib::syntax::KindId);
+        functions.add(Identifier::from("field-name"), stdlib::syntax::FieldName);
         functions.add(
             Identifier::from("named-child-count"),
             stdlib::syntax::NamedChildCount,
         );
+        functions.add(
+            Identifier::from("child-by-field"),
+            stdlib::syntax::ChildByField,
+        );
+        functions.add(
+            Identifier::from("next-named-sibling"),
+            stdlib::syntax::NextNamedSibling,
+        );
+        functions.add(
+            Identifier::from("previous-named-sibling"),
+            stdlib::syntax::PreviousNamedSibling,
+        );
+        functions.add(
+            Identifier::from("named-siblings"),
+            stdlib::syntax::NamedSiblings,
+        );
+        functions.add(Identifier::from("ancestors"), stdlib::syntax::Ancestors);
+        functions.add(
+            Identifier::from("descendants-of-kind"),
+            stdlib::syntax::DescendantsOfKind,
+        );
+        functions.add(
+            Identifier::from("parent-of-type"),
+            stdlib::syntax::ParentOfType,
+        );
         // graph functions
         functions.add(Identifier::from("node"), stdlib::graph::Node);
+        functions.add(Identifier::from("gensym"), stdlib::graph::Gensym);
+        functions.add(Identifier::from("out-edges"), stdlib::graph::OutEdges);
+        functions.add(Identifier::from("in-edges"), stdlib::graph::InEdges);
+        functions.add(Identifier::from("neighbors"), stdlib::graph::Neighbors);
+        functions.add(Identifier::from("has-edge"), stdlib::graph::HasEdge);
+        functions.add(Identifier::from("get-attr"), stdlib::graph::GetAttr);
         // boolean functions
         functions.add(Identifier::from("not"), stdlib::bool::Not);
         functions.add(Identifier::from("and"), stdlib::bool::And);
         functions.add(Identifier::from("or"), stdlib::bool::Or);
         // math functions
         functions.add(Identifier::from("plus"), stdlib::math::Plus);
+        functions.add(Identifier::from("random-int"), stdlib::math::RandomInt);
+        // time functions
+        functions.add(Identifier::from("now-unix"), stdlib::time::NowUnix);
+        functions.add(Identifier::from("now-iso8601"), stdlib::time::NowIso8601);
         // string functions
         functions.add(Identifier::from("format"), stdlib::string::Format);
         functions.add(Identifier::from("replace"), stdlib::string::Replace);
+        // hash functions
+        functions.add(Identifier::from("sha256"), stdlib::hash::Sha256);
+        functions.add(Identifier::from("fnv1a"), stdlib::hash::Fnv1a);
+        functions.add(Identifier::from("stable-id"), stdlib::hash::StableId);
+        // encoding functions
+        functions.add(
+            Identifier::from("base64-encode"),
+            stdlib::encoding::Base64Encode,
+        );
+        functions.add(
+            Identifier::from("base64-decode"),
+            stdlib::encoding::Base64Decode,
+        );
+        functions.add(
+            Identifier::from("json-encode"),
+            stdlib::encoding::JsonEncode,
+        );
+        functions.add(
+            Identifier::from("json-decode"),
+            stdlib::encoding::JsonDecode,
+        );
+        // path functions
+        functions.add(Identifier::from("path-basename"), stdlib::path::Basename);
+        functions.add(Identifier::from("path-dirname"), stdlib::path::Dirname);
+        functions.add(Identifier::from("path-extension"), stdlib::path::Extension);
+        functions.add(Identifier::from("path-join"), stdlib::path::Join);
+        functions.add(Identifier::from("path-normalize"), stdlib::path::Normalize);
+        // conversion functions
+        functions.add(Identifier::from("to-string"), stdlib::conversion::ToString);
+        functions.add(Identifier::from("to-int"), stdlib::conversion::ToInt);
+        functions.add(Identifier::from("to-bool"), stdlib::conversion::ToBool);
+        // process functions
+        #[cfg(feature = "exec-capture")]
+        functions.add(
+            Identifier::from("exec-capture"),
+            stdlib::process::ExecCapture,
+        );
         // list functions
         functions.add(Identifier::from("concat"), stdlib::list::Concat);
         functions.add(Identifier::from("is-empty"), stdlib::list::IsEmpty);
         functions.add(Identifier::from("join"), stdlib::list::Join);
         functions.add(Identifier::from("length"), stdlib::list::Length);
+        functions.add(Identifier::from("sort"), stdlib::list::Sort);
+        functions.add(Identifier::from("sort-by"), stdlib::list::SortBy);
+        functions.add(Identifier::from("map"), stdlib::list::Map);
+        functions.add(Identifier::from("filter"), stdlib::list::Filter);
+        functions.add(Identifier::from("flatten"), stdlib::list::Flatten);
+        functions.add(Identifier::from("unique"), stdlib::list::Unique);
+        functions.add(Identifier::from("zip"), stdlib::list::Zip);
+        // set functions
+        functions.add(Identifier::from("union"), stdlib::set::Union);
+        functions.add(Identifier::from("intersect"), stdlib::set::Intersect);
+        functions.add(Identifier::from("difference"), stdlib::set::Difference);
+        functions.add(
+            Identifier::from("symmetric-difference"),
+            stdlib::set::SymmetricDifference,
+        );
+        // map functions
+        functions.add(Identifier::from("map-of"), stdlib::map::MapOf);
+        functions.add(Identifier::from("map-get"), stdlib::map::MapGet);
+        functions.add(Identifier::from("map-keys"), stdlib::map::MapKeys);
+        functions.add(Identifier::from("map-values"), stdlib::map::MapValues);
         functions
     }
 
+    /// Adds all of the functions in another library to this one, in a namespace.  Each function
+    /// `name` is added as `namespace::name`, which graph DSL files can call as
+    /// `(namespace::name ...)`.  This lets you ship packs of functions whose names cannot collide
+    /// with those of the standard library, or of other packs.
+    pub fn add_namespace(&mut self, namespace: &str, functions: Functions) {
+        for (name, function) in functions.functions {
+            let name = Identifier::from(format!("{}::{}", namespace, name).as_str());
+            self.functions.insert(name, function);
+        }
+    }
+
+    /// Adds the [`env`][`crate::reference::functions#env`] function, which reads environment
+    /// variables, to this library.  It is not part of the [standard library][`Functions::stdlib`],
+    /// since it makes the result of executing a graph DSL file depend on the environment of the
+    /// process executing it.
+    pub fn add_env(&mut self) {
+        self.add(Identifier::from("env"), stdlib::Env);
+    }
+
     /// Adds a new function to this library.
     pub fn add<F>(&mut self, name: Identifier, function: F)
     where
@@ -147,31 +796,145 @@ impl Functions {
         self.functions.insert(name, Arc::new(function));
     }
 
+    /// Adds a new function to this library, whose parameters are checked against `signature`
+    /// before it is called.  If the number or types of the parameters don't match, the call fails
+    /// with an [`ExecutionError::InvalidParameters`][] error without calling the function.
+    pub fn add_with_signature<F>(&mut self, name: Identifier, signature: Signature, function: F)
+    where
+        F: Function + Send + Sync + 'static,
+    {
+        let parameters = signature.to_string();
+        self.add(
+            name.clone(),
+            SignedFunction {
+                name,
+                signature,
+                parameters,
+                function,
+            },
+        );
+    }
+
+    /// Adds a new pure function to this library, whose result only depends on the values of its
+    /// parameters and on the source text.  Within each execution, the function is called once for
+    /// each distinct list of parameter values, and later calls with the same values return the
+    /// cached result.  Failed calls are not cached.  The parameters of a pure function are always
+    /// evaluated before it is called, even if it [evaluates its parameters on
+    /// demand][`Function::lazy_parameters`].
+    ///
+    /// Do not add functions that read or change the graph, or that keep state, such as
+    /// [`gensym`][`crate::reference::functions#gensym`], as pure functions.
+    pub fn add_pure<F>(&mut self, name: Identifier, function: F)
+    where
+        F: Function + Send + Sync + 'static,
+    {
+        self.add(name.clone(), PureFunction { name, function });
+    }
+
+    /// Returns a reference to a named function, or `None` if there is no function with that name.
+    pub fn get(&self, name: &Identifier) -> Option<FunctionRef> {
+        self.functions
+            .get(name)
+            .map(|function| FunctionRef::new(name.clone(), function.clone()))
+    }
+
+    /// Returns the signature of a named function, or `None` if there is no function with that name,
+    /// or if it does not have a signature.
+    pub fn signature(&self, name: &Identifier) -> Option<&Signature> {
+        self.functions.get(name)?.signature()
+    }
+
+    /// Returns the number of functions in this library.
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// Returns whether this library contains no functions.
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Returns whether this library contains a function with the given name.
+    pub fn contains(&self, name: &Identifier) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Returns the names of all of the functions in this library, ordered by name.
+    pub fn names(&self) -> Vec<Identifier> {
+        let mut names = self.functions.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Returns a description of a named function, or `None` if there is no function with that
+    /// name.
+    pub fn describe(&self, name: &Identifier) -> Option<FunctionDescription> {
+        self.functions
+            .get_key_value(name)
+            .map(|(name, function)| FunctionDescription::new(name, function.as_ref()))
+    }
+
+    /// Returns descriptions of all of the functions in this library, ordered by name.
+    pub fn descriptions(&self) -> Vec<FunctionDescription> {
+        let mut descriptions = self
+            .functions
+            .iter()
+            .map(|(name, function)| FunctionDescription::new(name, function.as_ref()))
+            .collect::<Vec<_>>();
+        descriptions.sort_by(|a, b| a.name.cmp(&b.name));
+        descriptions
+    }
+
+    /// Notifies all of the functions in this library that an execution is starting.
+    pub(crate) fn on_execution_start(&self, state: &mut FunctionState) {
+        for function in self.functions.values() {
+            function.on_execution_start(state);
+        }
+    }
+
     /// Calls a named function, returning an error if there is no function with that name.
     pub fn call(
         &self,
         name: &Identifier,
         graph: &mut Graph,
+        state: &mut ExecutionState,
         source: &str,
         parameters: &mut dyn Parameters,
     ) -> Result<Value, ExecutionError> {
-        let function = self
-            .functions
+        self.lookup(name)?.call(graph, state, source, parameters)
+    }
+
+    /// Returns whether a named function [reads the graph][`Function::reads_graph`].
+    pub(crate) fn reads_graph(&self, name: &Identifier) -> bool {
+        match self.functions.get(name) {
+            Some(function) => function.reads_graph(),
+            None => false,
+        }
+    }
+
+    /// Returns the implementation of a named function.
+    pub(crate) fn lookup(
+        &self,
+        name: &Identifier,
+    ) -> Result<&(dyn Function + Send + Sync), ExecutionError> {
+        self.functions
             .get(name)
-            .ok_or(ExecutionError::UndefinedFunction(format!("{}", name)))?;
-        function.call(graph, source, parameters)
+            .map(|function| function.as_ref())
+            .ok_or_else(|| ExecutionError::UndefinedFunction(format!("{}", name)))
     }
 }
 
 /// Implementations of the [standard library functions][`crate::reference::functions`]
 pub mod stdlib {
-    use regex::Regex;
 
     use crate::execution::error::ExecutionError;
+    use crate::execution::ExecutionState;
     use crate::graph::Graph;
+    use crate::graph::SourcePosition;
     use crate::graph::Value;
 
     use super::Function;
+    use super::LazyParameters;
     use super::Parameters;
 
     /// The implementation of the standard [`eq`][`crate::reference::functions#eq`] function.
@@ -181,6 +944,7 @@ pub mod stdlib {
         fn call(
             &self,
             _graph: &mut Graph,
+            _state: &mut ExecutionState,
             _source: &str,
             parameters: &mut dyn Parameters,
         ) -> Result<Value, ExecutionError> {
@@ -218,6 +982,11 @@ pub mod stdlib {
                     Value::Set(right) => return Ok((left == right).into()),
                     _ => {}
                 },
+                Value::Map(left) => match &right {
+                    Value::Null => return Ok(false.into()),
+                    Value::Map(right) => return Ok((left == right).into()),
+                    _ => {}
+                },
                 Value::SyntaxNode(left) => match &right {
                     Value::Null => return Ok(false.into()),
                     Value::SyntaxNode(right) => return Ok((left == right).into()),
@@ -228,6 +997,16 @@ pub mod stdlib {
                     Value::GraphNode(right) => return Ok((left == right).into()),
                     _ => {}
                 },
+                Value::Function(left) => match &right {
+                    Value::Null => return Ok(false.into()),
+                    Value::Function(right) => return Ok((left == right).into()),
+                    _ => {}
+                },
+                Value::Span { .. } => match &right {
+                    Value::Null => return Ok(false.into()),
+                    Value::Span { .. } => return Ok((left == right).into()),
+                    _ => {}
+                },
             };
             Err(ExecutionError::FunctionFailed(
                 "eq".into(),
@@ -237,6 +1016,14 @@ pub mod stdlib {
                 ),
             ))
         }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("left right")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if values are equal.")
+        }
     }
 
     /// The implementation of the standard [`is-null`][`crate::reference::functions#is-null`] function.
@@ -246,6 +1033,7 @@ pub mod stdlib {
         fn call(
             &self,
             _graph: &mut Graph,
+            _state: &mut ExecutionState,
             _source: &str,
             parameters: &mut dyn Parameters,
         ) -> Result<Value, ExecutionError> {
@@ -258,19 +1046,364 @@ pub mod stdlib {
             };
             Ok(result.into())
         }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if an optional value is missing.")
+        }
     }
 
-    pub mod syntax {
-        use super::*;
+    /// The implementation of the standard [`is-string`][`crate::reference::functions#is-string`]
+    /// function.
+    pub struct IsString;
 
-        /// The implementation of the standard [`named-child-index`][`crate::reference::functions#named-child-index`]
-        /// function.
-        pub struct NamedChildIndex;
+    impl Function for IsString {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::String(_)).into())
+        }
 
-        impl Function for NamedChildIndex {
-            fn call(
-                &self,
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a string.")
+        }
+    }
+
+    /// The implementation of the standard [`is-int`][`crate::reference::functions#is-int`]
+    /// function.
+    pub struct IsInt;
+
+    impl Function for IsInt {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::Integer(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is an integer.")
+        }
+    }
+
+    /// The implementation of the standard [`is-list`][`crate::reference::functions#is-list`]
+    /// function.
+    pub struct IsList;
+
+    impl Function for IsList {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::List(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a list.")
+        }
+    }
+
+    /// The implementation of the standard [`is-set`][`crate::reference::functions#is-set`]
+    /// function.
+    pub struct IsSet;
+
+    impl Function for IsSet {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::Set(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a set.")
+        }
+    }
+
+    /// The implementation of the standard [`is-span`][`crate::reference::functions#is-span`]
+    /// function.
+    pub struct IsSpan;
+
+    impl Function for IsSpan {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::Span { .. }).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a span.")
+        }
+    }
+
+    /// The implementation of the standard [`is-map`][`crate::reference::functions#is-map`]
+    /// function.
+    pub struct IsMap;
+
+    impl Function for IsMap {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::Map(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a map.")
+        }
+    }
+
+    /// The implementation of the standard [`is-syntax-node`][`crate::reference::functions#is-syntax-node`]
+    /// function.
+    pub struct IsSyntaxNode;
+
+    impl Function for IsSyntaxNode {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::SyntaxNode(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a syntax node.")
+        }
+    }
+
+    /// The implementation of the standard [`is-graph-node`][`crate::reference::functions#is-graph-node`]
+    /// function.
+    pub struct IsGraphNode;
+
+    impl Function for IsGraphNode {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            Ok(matches!(parameter, Value::GraphNode(_)).into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("value")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a value is a graph node.")
+        }
+    }
+
+    /// The implementation of the standard [`coalesce`][`crate::reference::functions#coalesce`]
+    /// function.
+    ///
+    /// Note that `coalesce` takes [lazy parameters][`Function::lazy_parameters`], so that both
+    /// execution engines call [`Function::call_lazy`], which skips the parameters after the first
+    /// non-null one without evaluating them.  [`Function::call`] is used when the function is
+    /// called with parameters that have already been evaluated, such as via
+    /// [`Functions::call`][].
+    ///
+    /// [`Functions::call`]: super::Functions::call
+    pub struct Coalesce;
+
+    impl Function for Coalesce {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            while let Ok(parameter) = parameters.param() {
+                if !parameter.is_null() {
+                    return Ok(parameter);
+                }
+            }
+            Ok(Value::Null)
+        }
+
+        /// `coalesce` only evaluates its parameters up to the first non-null one, so that the
+        /// remaining ones can refer to values that are not defined, or have side effects like
+        /// creating graph nodes, without that mattering when an earlier parameter is present.
+        fn lazy_parameters(&self) -> bool {
+            true
+        }
+
+        fn call_lazy(
+            &self,
+            _source: &str,
+            parameters: &mut dyn LazyParameters,
+        ) -> Result<Value, ExecutionError> {
+            while parameters.remaining() > 0 {
+                let parameter = parameters.param()?;
+                if !parameter.is_null() {
+                    return Ok(parameter);
+                }
+            }
+            Ok(Value::Null)
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("values...")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Returns the first non-null value.")
+        }
+    }
+
+    /// The implementation of the standard [`contains`][`crate::reference::functions#contains`]
+    /// function.
+    pub struct Contains;
+
+    impl Function for Contains {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let collection = parameters.param()?;
+            let item = parameters.param()?;
+            parameters.finish()?;
+            let result = match &collection {
+                Value::List(values) => values.contains(&item),
+                Value::Set(values) => values.contains(&item),
+                Value::String(string) => string.contains(item.as_str()?),
+                _ => {
+                    return Err(ExecutionError::FunctionFailed(
+                        "contains".into(),
+                        format!("Expected a list, set, or string, got {}", collection),
+                    ))
+                }
+            };
+            Ok(result.into())
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("collection item")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Check if a list, set, or string contains a value.")
+        }
+    }
+
+    /// The implementation of the standard [`env`][`crate::reference::functions#env`] function.
+    pub struct Env;
+
+    impl Function for Env {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let name = parameters.param()?.into_string()?;
+            parameters.finish()?;
+            match std::env::var(&name) {
+                Ok(value) => Ok(Value::String(value.into())),
+                Err(std::env::VarError::NotPresent) => Ok(Value::Null),
+                Err(err) => Err(ExecutionError::FunctionFailed(
+                    "env".into(),
+                    format!("Cannot read environment variable {}: {}", name, err),
+                )),
+            }
+        }
+
+        fn parameters(&self) -> Option<&str> {
+            Some("name")
+        }
+
+        fn doc(&self) -> Option<&str> {
+            Some("Returns the value of an environment variable, or null if it is not set.")
+        }
+    }
+
+    pub mod syntax {
+        use super::*;
+
+        /// The implementation of the standard [`named-child-index`][`crate::reference::functions#named-child-index`]
+        /// function.
+        pub struct NamedChildIndex;
+
+        impl Function for NamedChildIndex {
+            fn call(
+                &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
@@ -295,6 +1428,14 @@ pub mod stdlib {
                     ))?;
                 Ok(Value::Integer(index as u32))
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the index of a \"named child\" within its parent.")
+            }
         }
 
         /// The implementation of the standard [`source-text`][`crate::reference::functions#source-text`]
@@ -305,12 +1446,21 @@ pub mod stdlib {
             fn call(
                 &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
                 let node = graph[parameters.param()?.into_syntax_node_ref()?];
                 parameters.finish()?;
-                Ok(Value::String(source[node.byte_range()].to_string()))
+                Ok(Value::String(source[node.byte_range()].into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the source text represented by a syntax node.")
             }
         }
 
@@ -322,6 +1472,7 @@ pub mod stdlib {
             fn call(
                 &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
@@ -329,6 +1480,14 @@ pub mod stdlib {
                 parameters.finish()?;
                 Ok(Value::Integer(node.start_position().row as u32))
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based start row of a syntax node.")
+            }
         }
 
         // The implementation of the standard
@@ -340,6 +1499,7 @@ pub mod stdlib {
             fn call(
                 &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
@@ -347,6 +1507,14 @@ pub mod stdlib {
                 parameters.finish()?;
                 Ok(Value::Integer(node.start_position().column as u32))
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based start column of a syntax node.")
+            }
         }
 
         // The implementation of the standard [`end-row`][`crate::reference::functions#end-row`]
@@ -357,6 +1525,7 @@ pub mod stdlib {
             fn call(
                 &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
@@ -364,296 +1533,2608 @@ pub mod stdlib {
                 parameters.finish()?;
                 Ok(Value::Integer(node.end_position().row as u32))
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based end row of a syntax node.")
+            }
+        }
+
+        // The implementation of the standard [`end-column`][`crate::reference::functions#end-column`]
+        // function.
+        pub struct EndColumn;
+
+        impl Function for EndColumn {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::Integer(node.end_position().column as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based end column of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`start-byte`][`crate::reference::functions#start-byte`] function.
+        pub struct StartByte;
+
+        impl Function for StartByte {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::Integer(node.start_byte() as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based start byte offset of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard [`span`][`crate::reference::functions#span`]
+        /// function.
+        pub struct Span;
+
+        impl Function for Span {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let first = graph[parameters.param()?.into_syntax_node_ref()?];
+                let last = match parameters.param() {
+                    Ok(last) => graph[last.into_syntax_node_ref()?],
+                    Err(_) => first,
+                };
+                parameters.finish()?;
+                Ok(Value::Span {
+                    start: SourcePosition::start_of(&first),
+                    end: SourcePosition::end_of(&last),
+                })
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node [last]")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the span of source covered by a syntax node, or by a range of them.")
+            }
+        }
+
+        /// The implementation of the standard [`end-byte`][`crate::reference::functions#end-byte`]
+        /// function.
+        pub struct EndByte;
+
+        impl Function for EndByte {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::Integer(node.end_byte() as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the zero-based end byte offset of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`text-between`][`crate::reference::functions#text-between`] function.
+        pub struct TextBetween;
+
+        impl Function for TextBetween {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let first = graph[parameters.param()?.into_syntax_node_ref()?];
+                let second = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                let start = first.end_byte();
+                let end = second.start_byte();
+                if end < start {
+                    return Err(ExecutionError::FunctionFailed(
+                        "text-between".into(),
+                        format!(
+                            "Second node starts at byte {}, before the first node ends at byte {}",
+                            end, start
+                        ),
+                    ));
+                }
+                Ok(Value::String(source[start..end].into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("first second")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the source text between the end of one syntax node and the start of another.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`source-line`][`crate::reference::functions#source-line`] function.
+        pub struct SourceLine;
+
+        impl Function for SourceLine {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let row = match parameters.param()? {
+                    Value::SyntaxNode(node) => graph[node].start_position().row,
+                    Value::Integer(row) => row as usize,
+                    value => {
+                        return Err(ExecutionError::FunctionFailed(
+                            "source-line".into(),
+                            format!("Expected a syntax node or a row, got {}", value),
+                        ))
+                    }
+                };
+                parameters.finish()?;
+                let line = source.split('\n').nth(row).ok_or_else(|| {
+                    ExecutionError::FunctionFailed(
+                        "source-line".into(),
+                        format!("Row {} is past the end of the source", row),
+                    )
+                })?;
+                Ok(Value::String(line.trim_end_matches('\r').into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node-or-row")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the line of source text containing a syntax node, or with a given row.")
+            }
+        }
+
+        // The implementation of the standard [`node-type`][`crate::reference::functions#node-type`]
+        // function.
+        pub struct NodeType;
+
+        impl Function for NodeType {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::String(node.kind().into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns a syntax node's type as a string.")
+            }
+        }
+
+        /// The implementation of the standard [`kind-id`][`crate::reference::functions#kind-id`]
+        /// function.
+        pub struct KindId;
+
+        impl Function for KindId {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::Integer(node.kind_id() as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the numeric ID of a syntax node's type.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`field-name`][`crate::reference::functions#field-name`] function.
+        pub struct FieldName;
+
+        impl Function for FieldName {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                let parent = match node.parent() {
+                    Some(parent) => parent,
+                    None => return Ok(Value::Null),
+                };
+                let mut cursor = parent.walk();
+                if cursor.goto_first_child() {
+                    loop {
+                        if cursor.node() == node {
+                            return Ok(match cursor.field_name() {
+                                Some(field_name) => field_name.into(),
+                                None => Value::Null,
+                            });
+                        }
+                        if !cursor.goto_next_sibling() {
+                            break;
+                        }
+                    }
+                }
+                Ok(Value::Null)
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the name of the field under which a syntax node appears in its parent.")
+            }
+        }
+
+        // The implementation of the standard
+        // [`named-child-count`][`crate::reference::functions#named-child-count`] function.
+
+        pub struct NamedChildCount;
+
+        impl Function for NamedChildCount {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(Value::Integer(node.named_child_count() as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the number of \"named children\" of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`child-by-field`][`crate::reference::functions#child-by-field`] function.
+        pub struct ChildByField;
+
+        impl Function for ChildByField {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let field = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                match node.child_by_field_name(&field) {
+                    Some(child) => Ok(graph.add_syntax_node(child).into()),
+                    None => Ok(Value::Null),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node field")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the child of a syntax node for a field.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`next-named-sibling`][`crate::reference::functions#next-named-sibling`] function.
+        pub struct NextNamedSibling;
+
+        impl Function for NextNamedSibling {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                match node.next_named_sibling() {
+                    Some(sibling) => Ok(graph.add_syntax_node(sibling).into()),
+                    None => Ok(Value::Null),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the next named sibling of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`previous-named-sibling`][`crate::reference::functions#previous-named-sibling`]
+        /// function.
+        pub struct PreviousNamedSibling;
+
+        impl Function for PreviousNamedSibling {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                match node.prev_named_sibling() {
+                    Some(sibling) => Ok(graph.add_syntax_node(sibling).into()),
+                    None => Ok(Value::Null),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the previous named sibling of a syntax node.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`named-siblings`][`crate::reference::functions#named-siblings`] function.
+        pub struct NamedSiblings;
+
+        impl Function for NamedSiblings {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                let parent = match node.parent() {
+                    Some(parent) => parent,
+                    None => return Ok(Vec::<Value>::new().into()),
+                };
+                let mut tree_cursor = parent.walk();
+                let siblings = parent
+                    .named_children(&mut tree_cursor)
+                    .filter(|sibling| *sibling != node)
+                    .collect::<Vec<_>>();
+                Ok(siblings
+                    .into_iter()
+                    .map(|sibling| graph.add_syntax_node(sibling).into())
+                    .collect::<Vec<Value>>()
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the other named children of a syntax node's parent.")
+            }
+        }
+
+        /// The implementation of the standard [`ancestors`][`crate::reference::functions#ancestors`]
+        /// function.
+        pub struct Ancestors;
+
+        impl Function for Ancestors {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                let mut ancestors = Vec::new();
+                let mut current = node.parent();
+                while let Some(ancestor) = current {
+                    ancestors.push(graph.add_syntax_node(ancestor).into());
+                    current = ancestor.parent();
+                }
+                Ok(ancestors.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the ancestors of a syntax node, starting with its parent.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`descendants-of-kind`][`crate::reference::functions#descendants-of-kind`] function.
+        pub struct DescendantsOfKind;
+
+        impl Function for DescendantsOfKind {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let kind = parameters.param()?.into_string()?;
+                let max_depth = match parameters.param() {
+                    Ok(max_depth) => Some(max_depth.into_integer()?),
+                    Err(_) => None,
+                };
+                parameters.finish()?;
+
+                let mut descendants = Vec::new();
+                let mut cursor = node.walk();
+                let mut depth = 0;
+                if max_depth != Some(0) && cursor.goto_first_child() {
+                    depth += 1;
+                    'walk: loop {
+                        let current = cursor.node();
+                        if current.kind() == kind {
+                            descendants.push(current);
+                        }
+                        let can_descend = match max_depth {
+                            Some(max_depth) => depth < max_depth,
+                            None => true,
+                        };
+                        if can_descend && cursor.goto_first_child() {
+                            depth += 1;
+                            continue;
+                        }
+                        while !cursor.goto_next_sibling() {
+                            cursor.goto_parent();
+                            depth -= 1;
+                            if depth == 0 {
+                                break 'walk;
+                            }
+                        }
+                    }
+                }
+
+                Ok(descendants
+                    .into_iter()
+                    .map(|descendant| graph.add_syntax_node(descendant).into())
+                    .collect::<Vec<Value>>()
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node kind [max-depth]")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the descendants of a syntax node that have the given type.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`parent-of-type`][`crate::reference::functions#parent-of-type`] function.
+        pub struct ParentOfType;
+
+        impl Function for ParentOfType {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let mut types = vec![parameters.param()?.into_string()?];
+                while let Ok(node_type) = parameters.param() {
+                    types.push(node_type.into_string()?);
+                }
+                let mut current = node.parent();
+                while let Some(ancestor) = current {
+                    if types.iter().any(|node_type| node_type == ancestor.kind()) {
+                        return Ok(graph.add_syntax_node(ancestor).into());
+                    }
+                    current = ancestor.parent();
+                }
+                Ok(Value::Null)
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node types...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the nearest ancestor of a syntax node that has one of the given types.")
+            }
+        }
+    }
+
+    pub mod graph {
+        use std::collections::BTreeSet;
+
+        use crate::graph::Attributes;
+        use crate::graph::GraphNodeRef;
+
+        use super::*;
+
+        /// The implementation of the standard [`node`][`crate::reference::functions#node`] function.
+        pub struct Node;
+
+        impl Function for Node {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                parameters.finish()?;
+                state.resource_usage().add_node()?;
+                let node = state.add_graph_node(graph, Attributes::new());
+                Ok(Value::GraphNode(node))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Creates a new graph node.")
+            }
+        }
+
+        /// The implementation of the standard [`gensym`][`crate::reference::functions#gensym`]
+        /// function.
+        pub struct Gensym;
+
+        impl Function for Gensym {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let prefix = match parameters.param() {
+                    Ok(prefix) => prefix.into_string()?,
+                    Err(_) => "g".to_string(),
+                };
+                parameters.finish()?;
+                Ok(Value::String(
+                    format!("{}{}", prefix, state.next_symbol()).into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("[prefix]")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns a string that is different each time it is called.")
+            }
+        }
+
+        /// The implementation of the standard [`out-edges`][`crate::reference::functions#out-edges`]
+        /// function.
+        pub struct OutEdges;
+
+        impl Function for OutEdges {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?.into_graph_node_ref()?;
+                parameters.finish()?;
+                Ok(sinks(graph, node)
+                    .map(Value::GraphNode)
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the sinks of the edges leaving a graph node.")
+            }
+        }
+
+        /// The implementation of the standard [`in-edges`][`crate::reference::functions#in-edges`]
+        /// function.
+        pub struct InEdges;
+
+        impl Function for InEdges {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?.into_graph_node_ref()?;
+                parameters.finish()?;
+                Ok(sources(graph, node)
+                    .map(Value::GraphNode)
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the sources of the edges entering a graph node.")
+            }
+        }
+
+        /// The implementation of the standard [`neighbors`][`crate::reference::functions#neighbors`]
+        /// function.
+        pub struct Neighbors;
+
+        impl Function for Neighbors {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?.into_graph_node_ref()?;
+                parameters.finish()?;
+                let neighbors = sinks(graph, node)
+                    .chain(sources(graph, node))
+                    .collect::<BTreeSet<_>>();
+                Ok(neighbors
+                    .into_iter()
+                    .map(Value::GraphNode)
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the graph nodes connected to a graph node by an edge in either direction.")
+            }
+        }
+
+        /// The implementation of the standard [`has-edge`][`crate::reference::functions#has-edge`]
+        /// function.
+        pub struct HasEdge;
+
+        impl Function for HasEdge {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let source = parameters.param()?.into_graph_node_ref()?;
+                let sink = parameters.param()?.into_graph_node_ref()?;
+                parameters.finish()?;
+                Ok(graph.node(source).get_edge(sink).is_some().into())
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("source sink")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns whether there is an edge from one graph node to another.")
+            }
+        }
+
+        /// The implementation of the standard [`get-attr`][`crate::reference::functions#get-attr`]
+        /// function.
+        pub struct GetAttr;
+
+        impl Function for GetAttr {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?.into_graph_node_ref()?;
+                let name = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(graph
+                    .node(node)
+                    .attributes()
+                    .get(name.as_str())
+                    .cloned()
+                    .unwrap_or(Value::Null))
+            }
+
+            fn lazy_parameters(&self) -> bool {
+                true
+            }
+
+            fn call_lazy(
+                &self,
+                source: &str,
+                parameters: &mut dyn LazyParameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?;
+                let name = parameters.param()?;
+                parameters.finish()?;
+                if let (Value::GraphNode(node), Value::String(name)) = (&node, &name) {
+                    parameters.require_node_attribute(*node, name)?;
+                }
+                let (graph, state) = parameters.context();
+                self.call(graph, state, source, &mut vec![node, name].into_iter())
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("node name")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the value of an attribute of a graph node, or null if it is not set.")
+            }
+        }
+
+        fn sinks<'a>(
+            graph: &'a Graph,
+            node: GraphNodeRef,
+        ) -> impl Iterator<Item = GraphNodeRef> + 'a {
+            graph.node(node).iter_edges().map(|(sink, _)| sink)
+        }
+
+        fn sources<'a>(
+            graph: &'a Graph,
+            node: GraphNodeRef,
+        ) -> impl Iterator<Item = GraphNodeRef> + 'a {
+            graph
+                .iter_nodes()
+                .filter(move |source| graph.node(*source).get_edge(node).is_some())
+        }
+    }
+
+    pub mod bool {
+        use super::*;
+
+        /// The implementation of the standard [`not`][`crate::reference::functions#not`] function.
+        pub struct Not;
+
+        impl Function for Not {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let result = !parameters.param()?.as_boolean()?;
+                parameters.finish()?;
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("value")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Negates a boolean value.")
+            }
+        }
+
+        /// The implementation of the standard [`and`][`crate::reference::functions#and`] function.
+        pub struct And;
+
+        impl Function for And {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = true;
+                while let Ok(parameter) = parameters.param() {
+                    result &= parameter.as_boolean()?;
+                }
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("values...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the conjunction of boolean values.")
+            }
+        }
+
+        /// The implementation of the standard [`or`][`crate::reference::functions#or`] function.
+        pub struct Or;
+
+        impl Function for Or {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = false;
+                while let Ok(parameter) = parameters.param() {
+                    result |= parameter.as_boolean()?;
+                }
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("values...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the disjunction of boolean values.")
+            }
+        }
+    }
+
+    pub mod math {
+        use super::*;
+
+        /// The implementation of the standard [`plus`][`crate::reference::functions#plus`] function.
+        pub struct Plus;
+
+        impl Function for Plus {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = 0;
+                while let Ok(parameter) = parameters.param() {
+                    result += parameter.as_integer()?;
+                }
+                Ok(Value::Integer(result))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("values...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Adds integers together.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`random-int`][`crate::reference::functions#random-int`] function.
+        pub struct RandomInt;
+
+        impl Function for RandomInt {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let lo = parameters.param()?.into_integer()?;
+                let hi = parameters.param()?.into_integer()?;
+                parameters.finish()?;
+                if lo > hi {
+                    return Err(ExecutionError::FunctionFailed(
+                        "random-int".into(),
+                        format!("Lower bound {} is greater than upper bound {}", lo, hi),
+                    ));
+                }
+                let range = (hi - lo) as u64 + 1;
+                Ok(Value::Integer(lo + (state.next_random() % range) as u32))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("lo hi")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns a pseudo-random integer between two bounds, inclusive.")
+            }
+        }
+    }
+
+    pub mod time {
+        use std::convert::TryFrom;
+        use std::time::UNIX_EPOCH;
+
+        use super::*;
+
+        /// The implementation of the standard [`now-unix`][`crate::reference::functions#now-unix`]
+        /// function.
+        pub struct NowUnix;
+
+        impl Function for NowUnix {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                parameters.finish()?;
+                let seconds = state
+                    .execution_time()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+                let seconds = u32::try_from(seconds).map_err(|_| {
+                    ExecutionError::FunctionFailed(
+                        "now-unix".into(),
+                        format!("Cannot represent time {} as an integer", seconds),
+                    )
+                })?;
+                Ok(Value::Integer(seconds))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some(
+                    "Returns the time at which execution started, in seconds since the Unix epoch.",
+                )
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`now-iso8601`][`crate::reference::functions#now-iso8601`] function.
+        pub struct NowIso8601;
+
+        impl Function for NowIso8601 {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                parameters.finish()?;
+                let time = state.execution_time();
+                Ok(Value::String(
+                    humantime::format_rfc3339_seconds(time).to_string().into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the time at which execution started, as an ISO 8601 string.")
+            }
+        }
+    }
+
+    pub mod string {
+        use super::*;
+
+        /// The implementation of the standard [`format`][`crate::reference::functions#format`] function.
+        pub struct Format;
+
+        impl Function for Format {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let format = parameters.param()?.into_string()?;
+                let mut result = String::new();
+                let mut it = format.chars().enumerate().into_iter();
+                while let Some((_, c)) = it.next() {
+                    match c {
+                        '{' => match it.next() {
+                            Some((_, '{')) => result.push('{'),
+                            Some((_, '}')) => {
+                                let value = parameters.param()?;
+                                result += &value.to_string();
+                            },
+                            Some((i, c)) => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected character `{}` after `{{` at position {} in format string `{}`. Expected `{{` or `}}`.", c, i + 1, format))),
+                            None => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected end of format string `{}` after `{{`. Expected `{{` or `}}`.", format))),
+                        },
+                        '}' => match it.next() {
+                            Some((_, '}')) => result.push('}'),
+                            Some((i, c)) => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected character `{}` after `}}` at position {} in format string `{}`. Expected `}}`.", c, i + 1, format))),
+                            None => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected end of format string `{}` after `{{. Expected `}}`.", format))),
+                        },
+                        c => result.push(c),
+                    }
+                }
+                parameters.finish()?;
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("format values...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Formats a string according to the given format string and arguments.")
+            }
+        }
+
+        /// The implementation of the standard [`replace`][`crate::reference::functions#replace`] function.
+        pub struct Replace;
+
+        impl Function for Replace {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let pattern = parameters.param()?.into_string()?;
+                let pattern = state.regex(&pattern).map_err(|e| {
+                    ExecutionError::FunctionFailed("replace".into(), format!("{}", e))
+                })?;
+                let replacement = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::String(
+                    pattern.replace_all(&text, replacement).to_string().into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("text pattern replacement")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Applies a regular expression to a string, replacing any text that matches.")
+            }
+        }
+    }
+
+    pub mod hash {
+        use sha2::Digest;
+
+        use super::*;
+
+        /// The implementation of the standard [`sha256`][`crate::reference::functions#sha256`]
+        /// function.
+        pub struct Sha256;
+
+        impl Function for Sha256 {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let digest = sha2::Sha256::digest(text.as_bytes());
+                Ok(Value::String(to_hex(&digest).into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("text")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the SHA-256 hash of a string, as hexadecimal digits.")
+            }
+        }
+
+        /// The implementation of the standard [`fnv1a`][`crate::reference::functions#fnv1a`]
+        /// function.
+        pub struct Fnv1a;
+
+        impl Function for Fnv1a {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::String(
+                    format!("{:016x}", fnv1a(text.as_bytes())).into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("text")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the 64-bit FNV-1a hash of a string, as hexadecimal digits.")
+            }
+        }
+
+        /// The implementation of the standard [`stable-id`][`crate::reference::functions#stable-id`]
+        /// function.
+        pub struct StableId;
+
+        impl Function for StableId {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut parts = Vec::new();
+                while let Ok(part) = parameters.param() {
+                    parts.push(part);
+                }
+                Ok(Value::String(stable_id(graph, &parts).into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("parts...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns a short identifier derived from the values of its parameters.")
+            }
+        }
+
+        /// Returns a short identifier that only depends on the contents of some values.  This is
+        /// the result of the [`stable-id`][`crate::reference::functions#stable-id`] function.
+        pub(crate) fn stable_id<'a>(
+            graph: &Graph,
+            values: impl IntoIterator<Item = &'a Value>,
+        ) -> String {
+            let mut encoding = String::new();
+            for value in values {
+                encode_stably(graph, value, &mut encoding);
+            }
+            let digest = sha2::Sha256::digest(encoding.as_bytes());
+            to_hex(&digest[..8])
+        }
+
+        /// Appends an unambiguous encoding of a value to `encoding`, which only depends on the
+        /// contents of the value, and not on memory addresses or evaluation order.  Syntax nodes are
+        /// encoded by their position and type, and graph nodes by their index.
+        fn encode_stably(graph: &Graph, value: &Value, encoding: &mut String) {
+            match value {
+                Value::Null => encoding.push_str("n;"),
+                Value::Boolean(bool) => encoding.push_str(if *bool { "b1;" } else { "b0;" }),
+                Value::Integer(int) => encoding.push_str(&format!("i{};", int)),
+                Value::String(string) => {
+                    encoding.push_str(&format!("s{}:{};", string.len(), string))
+                }
+                Value::List(values) => {
+                    encoding.push_str(&format!("l{}:", values.len()));
+                    for value in values {
+                        encode_stably(graph, value, encoding);
+                    }
+                    encoding.push(';');
+                }
+                Value::Set(values) => {
+                    let mut values = values.iter().collect::<Vec<_>>();
+                    values.sort_by(|a, b| super::list::compare_values(graph, a, b));
+                    encoding.push_str(&format!("t{}:", values.len()));
+                    for value in values {
+                        encode_stably(graph, value, encoding);
+                    }
+                    encoding.push(';');
+                }
+                Value::Map(values) => {
+                    encoding.push_str(&format!("m{}:", values.len()));
+                    for (key, value) in values {
+                        encoding.push_str(&format!("{}:{};", key.as_str().len(), key));
+                        encode_stably(graph, value, encoding);
+                    }
+                    encoding.push(';');
+                }
+                Value::SyntaxNode(node) => {
+                    let node = graph[*node];
+                    encoding.push_str(&format!(
+                        "y{}:{}:{}:{};",
+                        node.start_byte(),
+                        node.end_byte(),
+                        node.kind().len(),
+                        node.kind()
+                    ));
+                }
+                Value::GraphNode(node) => encoding.push_str(&format!("g{};", node.index())),
+                Value::Function(function) => {
+                    encoding.push_str(&format!("f{}:{};", function.name().len(), function.name()))
+                }
+                Value::Span { start, end } => encoding.push_str(&format!(
+                    "r{}:{}:{}:{}:{}:{};",
+                    start.byte, start.row, start.column, end.byte, end.row, end.column
+                )),
+            }
+        }
+
+        /// Computes the 64-bit FNV-1a hash of some bytes.
+        pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+            const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+            const PRIME: u64 = 0x100000001b3;
+            bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+                (hash ^ (*byte as u64)).wrapping_mul(PRIME)
+            })
+        }
+
+        pub(crate) fn to_hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+    }
+
+    pub mod encoding {
+        use std::collections::BTreeMap;
+        use std::convert::TryFrom;
+
+        use base64::Engine;
+
+        use crate::Identifier;
+
+        use super::*;
+
+        /// The implementation of the standard
+        /// [`base64-encode`][`crate::reference::functions#base64-encode`] function.
+        pub struct Base64Encode;
+
+        impl Function for Base64Encode {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::String(
+                    base64::engine::general_purpose::STANDARD
+                        .encode(text.as_bytes())
+                        .into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("text")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Encodes a string using base64.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`base64-decode`][`crate::reference::functions#base64-decode`] function.
+        pub struct Base64Decode;
+
+        impl Function for Base64Decode {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let encoded = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(encoded.as_bytes())
+                    .map_err(|err| {
+                        ExecutionError::FunctionFailed(
+                            "base64-decode".into(),
+                            format!("Invalid base64 {:?}: {}", encoded, err),
+                        )
+                    })?;
+                let text = String::from_utf8(bytes).map_err(|err| {
+                    ExecutionError::FunctionFailed(
+                        "base64-decode".into(),
+                        format!("Decoded value is not valid UTF-8: {}", err),
+                    )
+                })?;
+                Ok(Value::String(text.into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("encoded")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Decodes a base64-encoded string.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`json-encode`][`crate::reference::functions#json-encode`] function.
+        pub struct JsonEncode;
+
+        impl Function for JsonEncode {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let value = parameters.param()?;
+                parameters.finish()?;
+                let json = to_json(graph, &value)?;
+                Ok(Value::String(json.to_string().into()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("value")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Encodes a value as a JSON string.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`json-decode`][`crate::reference::functions#json-decode`] function.
+        pub struct JsonDecode;
+
+        impl Function for JsonDecode {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let json = serde_json::from_str(&text).map_err(|err| {
+                    ExecutionError::FunctionFailed(
+                        "json-decode".into(),
+                        format!("Invalid JSON: {}", err),
+                    )
+                })?;
+                from_json(json)
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("text")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Decodes a JSON string into a value.")
+            }
+        }
+
+        fn to_json(graph: &Graph, value: &Value) -> Result<serde_json::Value, ExecutionError> {
+            Ok(match value {
+                Value::Null => serde_json::Value::Null,
+                Value::Boolean(bool) => (*bool).into(),
+                Value::Integer(int) => (*int).into(),
+                Value::String(string) => (**string).into(),
+                Value::List(values) => values
+                    .iter()
+                    .map(|value| to_json(graph, value))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into(),
+                Value::Set(values) => {
+                    let mut values = values.iter().collect::<Vec<_>>();
+                    values.sort_by(|a, b| super::list::compare_values(graph, a, b));
+                    values
+                        .into_iter()
+                        .map(|value| to_json(graph, value))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .into()
+                }
+                Value::Map(values) => values
+                    .iter()
+                    .map(|(key, value)| Ok((key.to_string(), to_json(graph, value)?)))
+                    .collect::<Result<serde_json::Map<_, _>, ExecutionError>>()?
+                    .into(),
+                _ => {
+                    return Err(ExecutionError::FunctionFailed(
+                        "json-encode".into(),
+                        format!("Cannot encode {} as JSON", value),
+                    ))
+                }
+            })
+        }
+
+        fn from_json(json: serde_json::Value) -> Result<Value, ExecutionError> {
+            Ok(match json {
+                serde_json::Value::Null => Value::Null,
+                serde_json::Value::Bool(bool) => bool.into(),
+                serde_json::Value::Number(number) => {
+                    match number.as_u64().and_then(|int| u32::try_from(int).ok()) {
+                        Some(int) => int.into(),
+                        None => {
+                            return Err(ExecutionError::FunctionFailed(
+                                "json-decode".into(),
+                                format!("Cannot represent number {} as an integer", number),
+                            ))
+                        }
+                    }
+                }
+                serde_json::Value::String(string) => string.into(),
+                serde_json::Value::Array(values) => values
+                    .into_iter()
+                    .map(from_json)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into(),
+                serde_json::Value::Object(entries) => entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((Identifier::from(key.as_str()), from_json(value)?)))
+                    .collect::<Result<BTreeMap<_, _>, ExecutionError>>()?
+                    .into(),
+            })
+        }
+    }
+
+    pub mod path {
+        use std::path::Component;
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        use super::*;
+
+        /// The implementation of the standard
+        /// [`path-basename`][`crate::reference::functions#path-basename`] function.
+        pub struct Basename;
+
+        impl Function for Basename {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let path = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(optional_path_value(
+                    Path::new(&path).file_name().map(Path::new),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("path")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the final component of a path.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`path-dirname`][`crate::reference::functions#path-dirname`] function.
+        pub struct Dirname;
+
+        impl Function for Dirname {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let path = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(optional_path_value(Path::new(&path).parent()))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("path")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns a path without its final component.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`path-extension`][`crate::reference::functions#path-extension`] function.
+        pub struct Extension;
+
+        impl Function for Extension {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let path = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(optional_path_value(
+                    Path::new(&path).extension().map(Path::new),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("path")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the extension of the final component of a path.")
+            }
+        }
+
+        /// The implementation of the standard [`path-join`][`crate::reference::functions#path-join`]
+        /// function.
+        pub struct Join;
+
+        impl Function for Join {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = PathBuf::new();
+                while let Ok(path) = parameters.param() {
+                    result.push(path.into_string()?);
+                }
+                Ok(path_value(&result))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("paths...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Joins paths together.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`path-normalize`][`crate::reference::functions#path-normalize`] function.
+        pub struct Normalize;
+
+        impl Function for Normalize {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let path = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let mut result = PathBuf::new();
+                for component in Path::new(&path).components() {
+                    match component {
+                        Component::CurDir => {}
+                        Component::ParentDir => match result.components().next_back() {
+                            Some(Component::Normal(_)) => {
+                                result.pop();
+                            }
+                            Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                            _ => result.push(component),
+                        },
+                        _ => result.push(component),
+                    }
+                }
+                if result.as_os_str().is_empty() {
+                    result.push(".");
+                }
+                Ok(path_value(&result))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("path")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Removes `.` and `..` components from a path, without accessing the filesystem.")
+            }
+        }
+
+        fn path_value(path: &Path) -> Value {
+            Value::String(path.to_string_lossy().into_owned().into())
+        }
+
+        fn optional_path_value(path: Option<&Path>) -> Value {
+            match path {
+                Some(path) => path_value(path),
+                None => Value::Null,
+            }
+        }
+    }
+
+    #[cfg(feature = "exec-capture")]
+    pub mod process {
+        use std::process::Command;
+        use std::process::Stdio;
+
+        use super::*;
+
+        /// The implementation of the standard
+        /// [`exec-capture`][`crate::reference::functions#exec-capture`] function.
+        pub struct ExecCapture;
+
+        impl Function for ExecCapture {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let command = parameters.param()?.into_string()?;
+                let mut args = Vec::new();
+                while let Ok(arg) = parameters.param() {
+                    args.push(arg.into_string()?);
+                }
+                if !state.exec_capture_allowed() {
+                    return Err(ExecutionError::FunctionFailed(
+                        "exec-capture".into(),
+                        "Running external processes is not allowed".into(),
+                    ));
+                }
+                let output = Command::new(&command)
+                    .args(&args)
+                    .stdin(Stdio::null())
+                    .output()
+                    .map_err(|err| {
+                        ExecutionError::FunctionFailed(
+                            "exec-capture".into(),
+                            format!("Cannot run {}: {}", command, err),
+                        )
+                    })?;
+                if !output.status.success() {
+                    return Err(ExecutionError::FunctionFailed(
+                        "exec-capture".into(),
+                        format!(
+                            "{} failed with {}: {}",
+                            command,
+                            output.status,
+                            String::from_utf8_lossy(&output.stderr).trim_end(),
+                        ),
+                    ));
+                }
+                let stdout = String::from_utf8(output.stdout).map_err(|_| {
+                    ExecutionError::FunctionFailed(
+                        "exec-capture".into(),
+                        format!("Output of {} is not valid UTF-8", command),
+                    )
+                })?;
+                Ok(Value::String(
+                    stdout.trim_end_matches(&['\r', '\n'][..]).into(),
+                ))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("command args...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Runs an external command and returns its output.")
+            }
+        }
+    }
+
+    pub mod conversion {
+        use super::*;
+
+        /// The implementation of the standard
+        /// [`to-string`][`crate::reference::functions#to-string`] function.
+        pub struct ToString;
+
+        impl Function for ToString {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let value = parameters.param()?;
+                parameters.finish()?;
+                match value {
+                    Value::String(string) => Ok(Value::String(string)),
+                    value => Ok(format!("{}", value).into()),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("value")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Converts a value to a string.")
+            }
+        }
+
+        /// The implementation of the standard [`to-int`][`crate::reference::functions#to-int`]
+        /// function.
+        pub struct ToInt;
+
+        impl Function for ToInt {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let value = parameters.param()?;
+                parameters.finish()?;
+                match value {
+                    Value::Integer(int) => Ok(int.into()),
+                    Value::String(string) => match string.trim().parse::<u32>() {
+                        Ok(int) => Ok(int.into()),
+                        Err(err) => Err(ExecutionError::FunctionFailed(
+                            "to-int".into(),
+                            format!("Cannot convert {:?} to an integer: {}", string, err),
+                        )),
+                    },
+                    value => Err(ExecutionError::FunctionFailed(
+                        "to-int".into(),
+                        format!("Cannot convert {} to an integer", value),
+                    )),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("value")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Converts a string to an integer.")
+            }
+        }
+
+        /// The implementation of the standard [`to-bool`][`crate::reference::functions#to-bool`]
+        /// function.
+        pub struct ToBool;
+
+        impl Function for ToBool {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let value = parameters.param()?;
+                parameters.finish()?;
+                match value {
+                    Value::Boolean(bool) => Ok(bool.into()),
+                    Value::String(string) => match string.trim() {
+                        "true" | "#true" => Ok(true.into()),
+                        "false" | "#false" => Ok(false.into()),
+                        _ => Err(ExecutionError::FunctionFailed(
+                            "to-bool".into(),
+                            format!("Cannot convert {:?} to a boolean", string),
+                        )),
+                    },
+                    value => Err(ExecutionError::FunctionFailed(
+                        "to-bool".into(),
+                        format!("Cannot convert {} to a boolean", value),
+                    )),
+                }
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("value")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Converts a string to a boolean.")
+            }
+        }
+    }
+
+    pub mod list {
+        use std::cmp::Ordering;
+        use std::collections::HashSet;
+
+        use super::*;
+
+        /// The implementation of the standard [`concat`][`crate::reference::functions#concat`] function.
+        pub struct Concat;
+
+        impl Function for Concat {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = Vec::new();
+                while let Ok(list) = parameters.param() {
+                    result.append(&mut list.into_list()?);
+                }
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("lists...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Concatenate list arguments.")
+            }
+        }
+
+        /// The implementation of the standard [`is-empty`][`crate::reference::functions#is-empty`] function.
+        pub struct IsEmpty;
+
+        impl Function for IsEmpty {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let list = parameters.param()?.into_list()?;
+                Ok(list.is_empty().into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Test whether a list is empty or not.")
+            }
+        }
+
+        /// The implementation of the standard [`join`][`crate::reference::functions#join`] function.
+        pub struct Join;
+
+        impl Function for Join {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let list = parameters.param()?.into_list()?;
+                let sep = match parameters.param() {
+                    Ok(sep) => sep.into_string()?,
+                    Err(_) => "".to_string(),
+                };
+                parameters.finish()?;
+                let result = list
+                    .into_iter()
+                    .map(|x| format!("{}", x))
+                    .collect::<Vec<_>>()
+                    .join(&sep);
+                Ok(result.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list [sep]")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Join a list of values using the given separator.")
+            }
+        }
+
+        /// The implementation of the standard [`length`][`crate::reference::functions#length`] function.
+        pub struct Length;
+
+        impl Function for Length {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let list = parameters.param()?.into_list()?;
+                Ok((list.len() as u32).into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Determine the length of a list.")
+            }
         }
 
-        // The implementation of the standard [`end-column`][`crate::reference::functions#end-column`]
-        // function.
-        pub struct EndColumn;
+        /// The implementation of the standard [`sort`][`crate::reference::functions#sort`] function.
+        pub struct Sort;
 
-        impl Function for EndColumn {
+        impl Function for Sort {
             fn call(
                 &self,
                 graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let mut list = parameters.param()?.into_list()?;
                 parameters.finish()?;
-                Ok(Value::Integer(node.end_position().column as u32))
+                list.sort_by(|a, b| compare_values(graph, a, b));
+                Ok(list.into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Sorts a list of values.")
             }
         }
 
-        // The implementation of the standard [`node-type`][`crate::reference::functions#node-type`]
-        // function.
-        pub struct NodeType;
+        /// The implementation of the standard [`sort-by`][`crate::reference::functions#sort-by`]
+        /// function.
+        pub struct SortBy;
 
-        impl Function for NodeType {
+        impl Function for SortBy {
             fn call(
                 &self,
                 graph: &mut Graph,
-                _source: &str,
+                _state: &mut ExecutionState,
+                source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let list = parameters.param()?.into_list()?;
+                let key = parameters.param()?.into_string()?;
                 parameters.finish()?;
-                Ok(Value::String(node.kind().to_string()))
+                let mut keyed = list
+                    .into_iter()
+                    .map(|value| Ok((sort_key(graph, source, &value, &key)?, value)))
+                    .collect::<Result<Vec<_>, ExecutionError>>()?;
+                keyed.sort_by(|(a, _), (b, _)| compare_values(graph, a, b));
+                Ok(keyed
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect::<Vec<_>>()
+                    .into())
             }
-        }
 
-        // The implementation of the standard
-        // [`named-child-count`][`crate::reference::functions#named-child-count`] function.
+            fn reads_graph(&self) -> bool {
+                true
+            }
 
-        pub struct NamedChildCount;
+            fn parameters(&self) -> Option<&str> {
+                Some("list key")
+            }
 
-        impl Function for NamedChildCount {
+            fn doc(&self) -> Option<&str> {
+                Some("Sorts a list of syntax nodes or graph nodes by a key.")
+            }
+        }
+
+        /// The implementation of the standard [`map`][`crate::reference::functions#map`] function.
+        pub struct Map;
+
+        impl Function for Map {
             fn call(
                 &self,
                 graph: &mut Graph,
-                _source: &str,
+                state: &mut ExecutionState,
+                source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let list = parameters.param()?.into_list()?;
+                let function = parameters.param()?.into_function_ref()?;
                 parameters.finish()?;
-                Ok(Value::Integer(node.named_child_count() as u32))
+                let mut result = Vec::with_capacity(list.len());
+                for value in list {
+                    result.push(function.call(
+                        graph,
+                        state,
+                        source,
+                        &mut std::iter::once(value),
+                    )?);
+                }
+                Ok(result.into())
             }
-        }
-    }
 
-    pub mod graph {
-        use super::*;
+            fn parameters(&self) -> Option<&str> {
+                Some("list function")
+            }
 
-        /// The implementation of the standard [`node`][`crate::reference::functions#node`] function.
-        pub struct Node;
+            fn doc(&self) -> Option<&str> {
+                Some("Applies a function to each element of a list.")
+            }
+        }
 
-        impl Function for Node {
+        /// The implementation of the standard [`filter`][`crate::reference::functions#filter`]
+        /// function.
+        pub struct Filter;
+
+        impl Function for Filter {
             fn call(
                 &self,
                 graph: &mut Graph,
-                _source: &str,
+                state: &mut ExecutionState,
+                source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
+                let list = parameters.param()?.into_list()?;
+                let function = parameters.param()?.into_function_ref()?;
                 parameters.finish()?;
-                let node = graph.add_graph_node();
-                Ok(Value::GraphNode(node))
+                let mut result = Vec::new();
+                for value in list {
+                    let keep = function
+                        .call(graph, state, source, &mut std::iter::once(value.clone()))?
+                        .into_boolean()?;
+                    if keep {
+                        result.push(value);
+                    }
+                }
+                Ok(result.into())
             }
-        }
-    }
 
-    pub mod bool {
-        use super::*;
+            fn parameters(&self) -> Option<&str> {
+                Some("list function")
+            }
 
-        /// The implementation of the standard [`not`][`crate::reference::functions#not`] function.
-        pub struct Not;
+            fn doc(&self) -> Option<&str> {
+                Some("Selects the elements of a list that satisfy a predicate.")
+            }
+        }
 
-        impl Function for Not {
+        /// The implementation of the standard [`flatten`][`crate::reference::functions#flatten`]
+        /// function.
+        pub struct Flatten;
+
+        impl Function for Flatten {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let result = !parameters.param()?.as_boolean()?;
+                let list = parameters.param()?.into_list()?;
                 parameters.finish()?;
+                let mut result = Vec::new();
+                for value in list {
+                    match value {
+                        Value::List(mut values) => result.append(&mut values),
+                        value => result.push(value),
+                    }
+                }
                 Ok(result.into())
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Flattens a list of lists by one level.")
+            }
         }
 
-        /// The implementation of the standard [`and`][`crate::reference::functions#and`] function.
-        pub struct And;
+        /// The implementation of the standard [`unique`][`crate::reference::functions#unique`]
+        /// function.
+        pub struct Unique;
 
-        impl Function for And {
+        impl Function for Unique {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let mut result = true;
-                while let Ok(parameter) = parameters.param() {
-                    result &= parameter.as_boolean()?;
+                let list = parameters.param()?.into_list()?;
+                parameters.finish()?;
+                let mut seen = HashSet::new();
+                let mut result = Vec::new();
+                for value in list {
+                    if seen.insert(value.clone()) {
+                        result.push(value);
+                    }
                 }
                 Ok(result.into())
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("list")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Removes duplicate values from a list, keeping the first occurrence of each.")
+            }
         }
 
-        /// The implementation of the standard [`or`][`crate::reference::functions#or`] function.
-        pub struct Or;
+        /// The implementation of the standard [`zip`][`crate::reference::functions#zip`] function.
+        pub struct Zip;
 
-        impl Function for Or {
+        impl Function for Zip {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let mut result = false;
-                while let Ok(parameter) = parameters.param() {
-                    result |= parameter.as_boolean()?;
+                let left = parameters.param()?.into_list()?;
+                let right = parameters.param()?.into_list()?;
+                parameters.finish()?;
+                Ok(left
+                    .into_iter()
+                    .zip(right)
+                    .map(|(left, right)| Value::List(vec![left, right]))
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("left right")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Pairs up the elements of two lists.")
+            }
+        }
+
+        /// Returns the sort key of a value for [`SortBy`][].  Graph nodes are keyed by the value of
+        /// the named attribute (or null if it is missing), and syntax nodes by the named property.
+        fn sort_key(
+            graph: &Graph,
+            source: &str,
+            value: &Value,
+            key: &str,
+        ) -> Result<Value, ExecutionError> {
+            match value {
+                Value::GraphNode(node) => Ok(graph
+                    .node(*node)
+                    .attributes()
+                    .get(key)
+                    .cloned()
+                    .unwrap_or(Value::Null)),
+                Value::SyntaxNode(node) => {
+                    let node = graph[*node];
+                    Ok(match key {
+                        "start-byte" => Value::Integer(node.start_byte() as u32),
+                        "end-byte" => Value::Integer(node.end_byte() as u32),
+                        "start-row" => Value::Integer(node.start_position().row as u32),
+                        "start-column" => Value::Integer(node.start_position().column as u32),
+                        "end-row" => Value::Integer(node.end_position().row as u32),
+                        "end-column" => Value::Integer(node.end_position().column as u32),
+                        "node-type" => Value::String(node.kind().into()),
+                        "source-text" => Value::String(source[node.byte_range()].into()),
+                        _ => {
+                            return Err(ExecutionError::FunctionFailed(
+                                "sort-by".into(),
+                                format!("Unknown sort key `{}` for syntax node", key),
+                            ))
+                        }
+                    })
+                }
+                _ => Err(ExecutionError::FunctionFailed(
+                    "sort-by".into(),
+                    format!("Cannot sort value {} by key `{}`", value, key),
+                )),
+            }
+        }
+
+        /// Compares two values using the ordering documented for the
+        /// [`sort`][`crate::reference::functions#sort`] function.  Values of different types are
+        /// ordered by type, and syntax nodes are ordered by their position in the source, so that
+        /// the result does not depend on how the syntax tree happens to be laid out in memory.
+        pub(crate) fn compare_values(graph: &Graph, left: &Value, right: &Value) -> Ordering {
+            match (left, right) {
+                (Value::List(left), Value::List(right)) => compare_sequences(graph, left, right),
+                (Value::Set(left), Value::Set(right)) => compare_sequences(graph, left, right),
+                (Value::Map(left), Value::Map(right)) => {
+                    let mut left = left.iter();
+                    let mut right = right.iter();
+                    loop {
+                        match (left.next(), right.next()) {
+                            (None, None) => return Ordering::Equal,
+                            (None, Some(_)) => return Ordering::Less,
+                            (Some(_), None) => return Ordering::Greater,
+                            (Some((lk, lv)), Some((rk, rv))) => {
+                                match lk.cmp(rk).then_with(|| compare_values(graph, lv, rv)) {
+                                    Ordering::Equal => {}
+                                    ordering => return ordering,
+                                }
+                            }
+                        }
+                    }
+                }
+                (Value::SyntaxNode(left), Value::SyntaxNode(right)) => {
+                    let left = graph[*left];
+                    let right = graph[*right];
+                    (left.start_byte(), right.end_byte(), left.kind()).cmp(&(
+                        right.start_byte(),
+                        left.end_byte(),
+                        right.kind(),
+                    ))
+                }
+                _ => type_rank(left)
+                    .cmp(&type_rank(right))
+                    .then_with(|| left.cmp(right)),
+            }
+        }
+
+        fn compare_sequences<'a, I>(graph: &Graph, left: I, right: I) -> Ordering
+        where
+            I: IntoIterator<Item = &'a Value>,
+        {
+            let mut left = left.into_iter();
+            let mut right = right.into_iter();
+            loop {
+                match (left.next(), right.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(l), Some(r)) => match compare_values(graph, l, r) {
+                        Ordering::Equal => {}
+                        ordering => return ordering,
+                    },
                 }
-                Ok(result.into())
+            }
+        }
+
+        fn type_rank(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Boolean(_) => 1,
+                Value::Integer(_) => 2,
+                Value::String(_) => 3,
+                Value::List(_) => 4,
+                Value::Set(_) => 5,
+                Value::Map(_) => 6,
+                Value::Span { .. } => 7,
+                Value::SyntaxNode(_) => 8,
+                Value::GraphNode(_) => 9,
+                Value::Function(_) => 10,
             }
         }
     }
 
-    pub mod math {
+    pub mod set {
+        use std::collections::BTreeSet;
+
         use super::*;
 
-        /// The implementation of the standard [`plus`][`crate::reference::functions#plus`] function.
-        pub struct Plus;
+        /// The implementation of the standard
+        /// [`union`][`crate::reference::functions#union`] function.
+        pub struct Union;
 
-        impl Function for Plus {
+        impl Function for Union {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let mut result = 0;
-                while let Ok(parameter) = parameters.param() {
-                    result += parameter.as_integer()?;
-                }
-                Ok(Value::Integer(result))
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
+                parameters.finish()?;
+                Ok(left.union(&right).cloned().collect::<BTreeSet<_>>().into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("left right")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the union of two sets.")
             }
         }
-    }
 
-    pub mod string {
-        use super::*;
+        /// The implementation of the standard
+        /// [`intersect`][`crate::reference::functions#intersect`] function.
+        pub struct Intersect;
 
-        /// The implementation of the standard [`format`][`crate::reference::functions#format`] function.
-        pub struct Format;
+        impl Function for Intersect {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _state: &mut ExecutionState,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
+                parameters.finish()?;
+                Ok(left
+                    .intersection(&right)
+                    .cloned()
+                    .collect::<BTreeSet<_>>()
+                    .into())
+            }
 
-        impl Function for Format {
+            fn parameters(&self) -> Option<&str> {
+                Some("left right")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the intersection of two sets.")
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`difference`][`crate::reference::functions#difference`] function.
+        pub struct Difference;
+
+        impl Function for Difference {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let format = parameters.param()?.into_string()?;
-                let mut result = String::new();
-                let mut it = format.chars().enumerate().into_iter();
-                while let Some((_, c)) = it.next() {
-                    match c {
-                        '{' => match it.next() {
-                            Some((_, '{')) => result.push('{'),
-                            Some((_, '}')) => {
-                                let value = parameters.param()?;
-                                result += &value.to_string();
-                            },
-                            Some((i, c)) => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected character `{}` after `{{` at position {} in format string `{}`. Expected `{{` or `}}`.", c, i + 1, format))),
-                            None => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected end of format string `{}` after `{{`. Expected `{{` or `}}`.", format))),
-                        },
-                        '}' => match it.next() {
-                            Some((_, '}')) => result.push('}'),
-                            Some((i, c)) => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected character `{}` after `}}` at position {} in format string `{}`. Expected `}}`.", c, i + 1, format))),
-                            None => return Err(ExecutionError::FunctionFailed("format".into(), format!("Unexpected end of format string `{}` after `{{. Expected `}}`.", format))),
-                        },
-                        c => result.push(c),
-                    }
-                }
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
                 parameters.finish()?;
-                Ok(result.into())
+                Ok(left
+                    .difference(&right)
+                    .cloned()
+                    .collect::<BTreeSet<_>>()
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("left right")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the values of one set that are not in another.")
             }
         }
 
-        /// The implementation of the standard [`replace`][`crate::reference::functions#replace`] function.
-        pub struct Replace;
+        /// The implementation of the standard
+        /// [`symmetric-difference`][`crate::reference::functions#symmetric-difference`] function.
+        pub struct SymmetricDifference;
 
-        impl Function for Replace {
+        impl Function for SymmetricDifference {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let text = parameters.param()?.into_string()?;
-                let pattern = parameters.param()?.into_string()?;
-                let pattern = Regex::new(&pattern).map_err(|e| {
-                    ExecutionError::FunctionFailed("replace".into(), format!("{}", e))
-                })?;
-                let replacement = parameters.param()?.into_string()?;
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
                 parameters.finish()?;
-                Ok(Value::String(
-                    pattern.replace_all(&text, replacement).to_string(),
-                ))
+                Ok(left
+                    .symmetric_difference(&right)
+                    .cloned()
+                    .collect::<BTreeSet<_>>()
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("left right")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Computes the values that are in exactly one of two sets.")
             }
         }
     }
 
-    pub mod list {
+    pub mod map {
+        use std::collections::BTreeMap;
+
         use super::*;
+        use crate::Identifier;
 
-        /// The implementation of the standard [`concat`][`crate::reference::functions#concat`] function.
-        pub struct Concat;
+        /// The implementation of the standard
+        /// [`map-of`][`crate::reference::functions#map-of`] function.
+        pub struct MapOf;
 
-        impl Function for Concat {
+        impl Function for MapOf {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let mut result = Vec::new();
-                while let Ok(list) = parameters.param() {
-                    result.append(&mut list.into_list()?);
+                let mut result = BTreeMap::new();
+                while let Ok(key) = parameters.param() {
+                    let key = Identifier::from(key.as_str()?);
+                    let value = parameters.param().map_err(|_| {
+                        ExecutionError::FunctionFailed(
+                            "map-of".into(),
+                            format!("Missing value for key {}", key),
+                        )
+                    })?;
+                    result.insert(key, value);
                 }
                 Ok(result.into())
             }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("key value ...")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Creates a map from alternating keys and values.")
+            }
         }
 
-        /// The implementation of the standard [`is-empty`][`crate::reference::functions#is-empty`] function.
-        pub struct IsEmpty;
+        /// The implementation of the standard
+        /// [`map-get`][`crate::reference::functions#map-get`] function.
+        pub struct MapGet;
 
-        impl Function for IsEmpty {
+        impl Function for MapGet {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let list = parameters.param()?.into_list()?;
-                Ok(list.is_empty().into())
+                let map = parameters.param()?.into_map()?;
+                let key = parameters.param()?;
+                parameters.finish()?;
+                Ok(map.get(key.as_str()?).cloned().unwrap_or(Value::Null))
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("map key")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the value of a key in a map, or null if it is missing.")
             }
         }
 
-        /// The implementation of the standard [`join`][`crate::reference::functions#join`] function.
-        pub struct Join;
+        /// The implementation of the standard
+        /// [`map-keys`][`crate::reference::functions#map-keys`] function.
+        pub struct MapKeys;
 
-        impl Function for Join {
+        impl Function for MapKeys {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let list = parameters.param()?.into_list()?;
-                let sep = match parameters.param() {
-                    Ok(sep) => sep.into_string()?,
-                    Err(_) => "".to_string(),
-                };
+                let map = parameters.param()?.into_map()?;
                 parameters.finish()?;
-                let result = list
-                    .into_iter()
-                    .map(|x| format!("{}", x))
+                Ok(map
+                    .keys()
+                    .map(|key| Value::from(key.as_str()))
                     .collect::<Vec<_>>()
-                    .join(&sep);
-                Ok(result.into())
+                    .into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("map")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the keys of a map, in order.")
             }
         }
 
-        /// The implementation of the standard [`length`][`crate::reference::functions#length`] function.
-        pub struct Length;
+        /// The implementation of the standard
+        /// [`map-values`][`crate::reference::functions#map-values`] function.
+        pub struct MapValues;
 
-        impl Function for Length {
+        impl Function for MapValues {
             fn call(
                 &self,
                 _graph: &mut Graph,
+                _state: &mut ExecutionState,
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let list = parameters.param()?.into_list()?;
-                Ok((list.len() as u32).into())
+                let map = parameters.param()?.into_map()?;
+                parameters.finish()?;
+                Ok(map.into_values().collect::<Vec<_>>().into())
+            }
+
+            fn parameters(&self) -> Option<&str> {
+                Some("map")
+            }
+
+            fn doc(&self) -> Option<&str> {
+                Some("Returns the values of a map, ordered by key.")
             }
         }
     }
@@ -7,8 +7,73 @@
 
 //! Defines data types for the graphs produced by the graph DSL
 
+mod arena;
+mod binary;
+mod builder;
+mod cypher;
+mod dot;
+mod fragment;
+mod gexf;
+mod group;
+mod html;
+mod index;
+mod intern;
+mod json;
+mod merge;
+mod mermaid;
+mod mutation;
+#[cfg(feature = "neo4j")]
+mod neo4j;
+mod pattern;
+mod provenance;
+mod schema;
+mod snapshot;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stable_id;
+mod stats;
+mod trace;
+mod turtle;
+
+pub use arena::Edge;
+pub use arena::EdgeMut;
+pub use arena::GraphNode;
+pub use arena::GraphNodeMut;
+pub use arena::StoredAttributes;
+pub use arena::StoredAttributesMut;
+pub use binary::DecodeError;
+pub use builder::EdgeBuilder;
+pub use builder::GraphBuilder;
+pub use builder::NodeBuilder;
+pub use cypher::CypherOptions;
+pub use dot::DotOptions;
+pub use index::AttributeIndex;
+pub use merge::ConflictPolicy;
+pub use merge::MergeError;
+#[cfg(feature = "neo4j")]
+pub use neo4j::Neo4jSink;
+pub use pattern::EdgePattern;
+pub use pattern::NodePattern;
+pub use pattern::PathPattern;
+pub use pattern::PatternMatch;
+pub use provenance::Provenance;
+pub use schema::AttributeSchema;
+pub use schema::GraphSchema;
+pub use schema::SchemaElement;
+pub use schema::SchemaError;
+pub use snapshot::GraphSnapshot;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSink;
+pub use stable_id::StableIdError;
+pub use stats::GraphStats;
+pub use trace::Trace;
+pub use trace::TraceEvent;
+pub use turtle::TurtleOptions;
+
+pub(crate) use fragment::FragmentMapping;
+
 use std::borrow::Borrow;
-use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::fmt;
@@ -17,27 +82,57 @@ use std::hash::Hash;
 use std::io::prelude::*;
 use std::io::stdout;
 use std::ops::Index;
-use std::ops::IndexMut;
 use std::path::Path;
+use std::sync::Arc;
 
 use serde::ser::SerializeMap;
 use serde::ser::SerializeSeq;
 use serde::Serialize;
 use serde::Serializer;
 use serde_json;
-use smallvec::SmallVec;
 use tree_sitter::Node;
 
 use crate::execution::error::ExecutionError;
+use crate::execution::ExecutionState;
+use crate::functions::Function;
+use crate::functions::Parameters;
 use crate::Identifier;
 use crate::Location;
 
+/// The version of the JSON format that graphs are serialized in.  It is incremented whenever the
+/// format changes in a way that existing consumers could observe.
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+/// A [JSON Schema](https://json-schema.org) describing the JSON format that graphs are serialized
+/// in, for the current [`JSON_FORMAT_VERSION`].
+pub const JSON_SCHEMA: &str = include_str!("graph/schema.json");
+
+/// The layout of the JSON output produced by [`Graph::write_json`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JsonStyle {
+    /// Indented output, with one value per line
+    Pretty,
+    /// Output without any insignificant whitespace
+    Compact,
+}
+
 /// A graph produced by executing a graph DSL file.  Graphs include a lifetime parameter to ensure
 /// that they don't outlive the tree-sitter syntax tree that they are generated from.
+///
+/// The contents of a graph are iterated and serialized in a deterministic order: nodes in the order
+/// they were added, edges ordered by the index of their sink node, and attributes in the order they
+/// were added.  The pretty-printed form lists attributes by name instead.  Syntax nodes are
+/// numbered in the order they were first added, so graphs built the same way from the same syntax
+/// tree serialize identically across runs and platforms.
 #[derive(Default)]
 pub struct Graph<'tree> {
-    syntax_nodes: HashMap<SyntaxNodeID, Node<'tree>>,
-    graph_nodes: Vec<GraphNode>,
+    syntax_nodes: snapshot::CowVec<Node<'tree>>,
+    syntax_node_ids: HashMap<usize, SyntaxNodeID>,
+    arenas: arena::Arenas,
+    indices: index::GraphIndices,
+    provenance: provenance::ProvenanceTable,
+    strings: intern::StringTable,
+    groups: group::GroupTable,
 }
 
 type SyntaxNodeID = u32;
@@ -54,35 +149,68 @@ impl<'tree> Graph<'tree> {
     /// The graph won't contain _every_ syntax node in the parsed syntax tree; it will only contain
     /// those nodes that are referenced at some point during the execution of the graph DSL file.
     pub fn add_syntax_node(&mut self, node: Node<'tree>) -> SyntaxNodeRef {
-        let index = node.id() as SyntaxNodeID;
-        let node_ref = SyntaxNodeRef {
+        let next_index = self.syntax_nodes.len() as SyntaxNodeID;
+        let index = *self.syntax_node_ids.entry(node.id()).or_insert(next_index);
+        if index == next_index {
+            self.syntax_nodes.push(node);
+        }
+        SyntaxNodeRef {
             index,
             kind: node.kind(),
             position: node.start_position(),
-        };
-        self.syntax_nodes.entry(index).or_insert(node);
-        node_ref
+        }
     }
 
     /// Adds a new graph node to the graph, returning a graph DSL reference to it.
     pub fn add_graph_node(&mut self) -> GraphNodeRef {
-        let graph_node = GraphNode::new();
-        let index = self.graph_nodes.len() as GraphNodeID;
-        self.graph_nodes.push(graph_node);
-        GraphNodeRef(index)
+        self.arenas.add_node()
+    }
+
+    /// Returns a graph node, with its attributes and outgoing edges.
+    pub fn node(&self, node: GraphNodeRef) -> GraphNode<'_> {
+        assert!(node.index() < self.node_count(), "no graph node {}", node);
+        GraphNode {
+            arenas: &self.arenas,
+            index: node.0,
+        }
+    }
+
+    /// Returns a graph node whose attributes and outgoing edges can be modified.
+    pub fn node_mut(&mut self, node: GraphNodeRef) -> GraphNodeMut<'_> {
+        assert!(node.index() < self.node_count(), "no graph node {}", node);
+        self.indices.invalidate();
+        GraphNodeMut {
+            arenas: &mut self.arenas,
+            index: node.0,
+        }
+    }
+
+    /// Returns an iterator of all of the graph nodes, in the order they were added.
+    fn graph_nodes(&self) -> impl Iterator<Item = GraphNode<'_>> {
+        self.iter_nodes().map(move |node| self.node(node))
     }
 
-    /// Pretty-prints the contents of this graph.
+    /// Pretty-prints the contents of this graph.  Nodes and edges that provenance was recorded for
+    /// are annotated with the statement that created them.
     pub fn pretty_print<'a>(&'a self) -> impl fmt::Display + 'a {
         struct DisplayGraph<'a, 'tree>(&'a Graph<'tree>);
 
         impl<'a, 'tree> fmt::Display for DisplayGraph<'a, 'tree> {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
                 let graph = self.0;
-                for (node_index, node) in graph.graph_nodes.iter().enumerate() {
-                    write!(f, "node {}\n{}", node_index, node.attributes)?;
-                    for (sink, edge) in &node.outgoing_edges {
-                        write!(f, "edge {} -> {}\n{}", node_index, *sink, edge.attributes)?;
+                for node_ref in graph.iter_nodes() {
+                    let node = graph.node(node_ref);
+                    write!(f, "node {}", node_ref.0)?;
+                    if let Some(provenance) = graph.node_provenance(node_ref) {
+                        write!(f, " from {}", provenance)?;
+                    }
+                    write!(f, "\n{}", node.attributes())?;
+                    for (sink, edge) in node.iter_edges() {
+                        write!(f, "edge {} -> {}", node_ref.0, sink.0)?;
+                        if let Some(provenance) = graph.edge_provenance(node_ref, sink) {
+                            write!(f, " from {}", provenance)?;
+                        }
+                        write!(f, "\n{}", edge.attributes())?;
                     }
                 }
                 Ok(())
@@ -99,190 +227,210 @@ impl<'tree> Graph<'tree> {
         })
     }
 
-    // Returns an iterator of references to all of the nodes in the graph.
+    /// Writes the contents of this graph as a JSON document.  The document is an object with a
+    /// `version` field, containing [`JSON_FORMAT_VERSION`], and a `nodes` field, containing the
+    /// graph nodes ordered by index, each with its outgoing edges and attributes.  The format is
+    /// described precisely by [`JSON_SCHEMA`].  Graphs with the same nodes, edges, and attributes
+    /// are always written as the same document.
+    pub fn write_json<W: Write>(&self, writer: W, style: JsonStyle) -> std::io::Result<()> {
+        match style {
+            JsonStyle::Pretty => serde_json::to_writer_pretty(writer, self)?,
+            JsonStyle::Compact => serde_json::to_writer(writer, self)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the contents of this graph as newline-delimited JSON, with one JSON object per
+    /// line.  All nodes are written first, as `{"type":"node","id":…,"attrs":{…}}`, followed by
+    /// all edges, as `{"type":"edge","source":…,"sink":…,"attrs":{…}}`.  Each object is written
+    /// as soon as it is serialized, so the output never has to be held in memory as a whole.
+    pub fn write_ndjson<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        for node in self.graph_nodes() {
+            serde_json::to_writer(&mut writer, &SerializeNdjsonNode(node))?;
+            writer.write_all(b"\n")?;
+        }
+        for node_ref in self.iter_nodes() {
+            for (sink, edge) in self.node(node_ref).iter_edges() {
+                serde_json::to_writer(&mut writer, &SerializeNdjsonEdge(node_ref, sink, edge))?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Streams the contents of this graph to a [`GraphSink`].  All nodes are sent first, ordered
+    /// by index, followed by all edges, after which the sink is finished.
+    pub fn write_to_sink<S: GraphSink>(&self, sink: &mut S) -> Result<(), S::Error> {
+        for node in self.iter_nodes() {
+            sink.node(node.index(), self.node(node).attributes())?;
+        }
+        for node in self.iter_nodes() {
+            for (target, edge) in self.node(node).iter_edges() {
+                sink.edge(node.index(), target.index(), edge.attributes())?;
+            }
+        }
+        sink.finish()
+    }
+
+    /// Returns an iterator of references to all of the nodes in the graph, in the order they were
+    /// added.
     pub fn iter_nodes(&self) -> impl Iterator<Item = GraphNodeRef> {
-        (0..self.graph_nodes.len() as u32).map(GraphNodeRef)
+        (0..self.node_count() as GraphNodeID).map(GraphNodeRef)
     }
 
     // Returns the number of nodes in the graph.
     pub fn node_count(&self) -> usize {
-        self.graph_nodes.len()
+        self.arenas.node_count()
+    }
+
+    /// Puts this graph in canonical form, by ordering the attributes of each node and edge by name.
+    /// (The outgoing edges of each node are always ordered by the index of their sink node.)  Use
+    /// this before serializing graphs to get output that does not depend on the order in which
+    /// attributes were added, for example in snapshot tests.
+    pub fn canonicalize(&mut self) {
+        self.arenas.sort_attributes_by_name();
+        self.indices.invalidate();
     }
 }
 
 impl<'tree> Index<SyntaxNodeRef> for Graph<'tree> {
     type Output = Node<'tree>;
     fn index(&self, node_ref: SyntaxNodeRef) -> &Node<'tree> {
-        &self.syntax_nodes[&node_ref.index]
+        &self.syntax_nodes[node_ref.index as usize]
     }
 }
 
-impl Index<GraphNodeRef> for Graph<'_> {
-    type Output = GraphNode;
-    fn index(&self, index: GraphNodeRef) -> &GraphNode {
-        &self.graph_nodes[index.0 as usize]
+impl<'tree> Serialize for Graph<'tree> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // serializing as a map instead of a struct so we don't have to encode a struct name
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("version", &JSON_FORMAT_VERSION)?;
+        map.serialize_entry("nodes", &SerializeGraphNodes(&self.arenas))?;
+        map.end()
     }
 }
 
-impl<'tree> IndexMut<GraphNodeRef> for Graph<'_> {
-    fn index_mut(&mut self, index: GraphNodeRef) -> &mut GraphNode {
-        &mut self.graph_nodes[index.0 as usize]
-    }
-}
+struct SerializeGraphNodes<'a>(&'a arena::Arenas);
 
-impl<'tree> Serialize for Graph<'tree> {
+impl<'a> Serialize for SerializeGraphNodes<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut seq = serializer.serialize_seq(Some(self.graph_nodes.len()))?;
-        for (node_index, node) in self.graph_nodes.iter().enumerate() {
-            seq.serialize_element(&SerializeGraphNode(node_index, node))?;
+        let arenas = self.0;
+        let mut seq = serializer.serialize_seq(Some(arenas.node_count()))?;
+        for index in 0..arenas.node_count() as GraphNodeID {
+            seq.serialize_element(&SerializeGraphNode(GraphNode { arenas, index }))?;
         }
         seq.end()
     }
 }
 
-/// A node in a graph
-pub struct GraphNode {
-    outgoing_edges: SmallVec<[(GraphNodeID, Edge); 8]>,
-    /// The set of attributes associated with this graph node
-    pub attributes: Attributes,
-}
+/// A destination that the nodes and edges of a graph can be streamed to, using
+/// [`Graph::write_to_sink`].
+pub trait GraphSink {
+    type Error;
 
-impl GraphNode {
-    fn new() -> GraphNode {
-        GraphNode {
-            outgoing_edges: SmallVec::new(),
-            attributes: Attributes::new(),
-        }
-    }
-
-    /// Adds an edge to this node.  There can be at most one edge connecting any two graph nodes;
-    /// the result indicates whether the edge is new (`Ok`) or already existed (`Err`).  In either
-    /// case, you also get a mutable reference to the [`Edge`][] instance for the edge.
-    pub fn add_edge(&mut self, sink: GraphNodeRef) -> Result<&mut Edge, &mut Edge> {
-        let sink = sink.0;
-        match self
-            .outgoing_edges
-            .binary_search_by_key(&sink, |(sink, _)| *sink)
-        {
-            Ok(index) => Err(&mut self.outgoing_edges[index].1),
-            Err(index) => {
-                self.outgoing_edges.insert(index, (sink, Edge::new()));
-                Ok(&mut self.outgoing_edges[index].1)
-            }
-        }
-    }
+    /// Receives a graph node, identified by its index.
+    fn node(&mut self, index: usize, attributes: StoredAttributes) -> Result<(), Self::Error>;
 
-    /// Returns a reference to an outgoing edge from this node, if it exists.
-    pub fn get_edge(&self, sink: GraphNodeRef) -> Option<&Edge> {
-        let sink = sink.0;
-        self.outgoing_edges
-            .binary_search_by_key(&sink, |(sink, _)| *sink)
-            .ok()
-            .map(|index| &self.outgoing_edges[index].1)
-    }
+    /// Receives an edge between two graph nodes.  Both nodes have already been received.
+    fn edge(
+        &mut self,
+        source: usize,
+        sink: usize,
+        attributes: StoredAttributes,
+    ) -> Result<(), Self::Error>;
 
-    /// Returns a mutable reference to an outgoing edge from this node, if it exists.
-    pub fn get_edge_mut(&mut self, sink: GraphNodeRef) -> Option<&mut Edge> {
-        let sink = sink.0;
-        self.outgoing_edges
-            .binary_search_by_key(&sink, |(sink, _)| *sink)
-            .ok()
-            .map(move |index| &mut self.outgoing_edges[index].1)
-    }
-
-    // Returns an iterator of all of the outgoing edges from this node.
-    pub fn iter_edges(&self) -> impl Iterator<Item = (GraphNodeRef, &Edge)> + '_ {
-        self.outgoing_edges
-            .iter()
-            .map(|(id, edge)| (GraphNodeRef(*id), edge))
-    }
-
-    // Returns the number of outgoing edges from this node.
-    pub fn edge_count(&self) -> usize {
-        self.outgoing_edges.len()
+    /// Called once all nodes and edges have been received.
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
     }
 }
 
-struct SerializeGraphNode<'a>(usize, &'a GraphNode);
+struct SerializeGraphNode<'a>(GraphNode<'a>);
 
 impl<'a> Serialize for SerializeGraphNode<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let node_index = self.0;
-        let node = self.1;
+        let node = self.0;
         // serializing as a map instead of a struct so we don't have to encode a struct name
         let mut map = serializer.serialize_map(None)?;
-        map.serialize_entry("id", &node_index)?;
-        map.serialize_entry("edges", &SerializeGraphNodeEdges(&node.outgoing_edges))?;
-        map.serialize_entry("attrs", &node.attributes)?;
+        map.serialize_entry("id", &node.index)?;
+        map.serialize_entry("edges", &SerializeGraphNodeEdges(node))?;
+        map.serialize_entry("attrs", &node.attributes())?;
         map.end()
     }
 }
 
-struct SerializeGraphNodeEdges<'a>(&'a SmallVec<[(GraphNodeID, Edge); 8]>);
+struct SerializeGraphNodeEdges<'a>(GraphNode<'a>);
 
 impl<'a> Serialize for SerializeGraphNodeEdges<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let edges = self.0;
-        let mut seq = serializer.serialize_seq(Some(edges.len()))?;
-        for element in edges {
-            seq.serialize_element(&SerializeGraphNodeEdge(&element))?;
+        let node = self.0;
+        let mut seq = serializer.serialize_seq(Some(node.edge_count()))?;
+        for (sink, edge) in node.iter_edges() {
+            seq.serialize_element(&SerializeGraphNodeEdge(sink, edge))?;
         }
         seq.end()
     }
 }
 
-struct SerializeGraphNodeEdge<'a>(&'a (GraphNodeID, Edge));
+struct SerializeGraphNodeEdge<'a>(GraphNodeRef, Edge<'a>);
 
 impl<'a> Serialize for SerializeGraphNodeEdge<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let wrapped = &self.0;
-        let sink = &wrapped.0;
-        let edge = &wrapped.1;
         let mut map = serializer.serialize_map(None)?;
-        map.serialize_entry("sink", sink)?;
-        map.serialize_entry("attrs", &edge.attributes)?;
+        map.serialize_entry("sink", &self.0 .0)?;
+        map.serialize_entry("attrs", &self.1.attributes())?;
         map.end()
     }
 }
 
-/// An edge between two nodes in a graph
-pub struct Edge {
-    /// The set of attributes associated with this edge
-    pub attributes: Attributes,
+struct SerializeNdjsonNode<'a>(GraphNode<'a>);
+
+impl<'a> Serialize for SerializeNdjsonNode<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", "node")?;
+        map.serialize_entry("id", &self.0.index)?;
+        map.serialize_entry("attrs", &self.0.attributes())?;
+        map.end()
+    }
 }
 
-impl Edge {
-    fn new() -> Edge {
-        Edge {
-            attributes: Attributes::new(),
-        }
+struct SerializeNdjsonEdge<'a>(GraphNodeRef, GraphNodeRef, Edge<'a>);
+
+impl<'a> Serialize for SerializeNdjsonEdge<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", "edge")?;
+        map.serialize_entry("source", &self.0 .0)?;
+        map.serialize_entry("sink", &self.1 .0)?;
+        map.serialize_entry("attrs", &self.2.attributes())?;
+        map.end()
     }
 }
 
-/// A set of attributes associated with a graph node or edge
-#[derive(Clone, Debug)]
+/// A set of attributes that is not stored in a graph, such as one that is about to be added to a
+/// graph node or edge.  Attributes are kept in the order they were added.
+#[derive(Clone)]
 pub struct Attributes {
-    values: HashMap<Identifier, Value>,
+    values: Vec<(Identifier, Value)>,
 }
 
 impl Attributes {
     /// Creates a new, empty set of attributes.
     pub fn new() -> Attributes {
-        Attributes {
-            values: HashMap::new(),
-        }
+        Attributes { values: Vec::new() }
     }
 
-    /// Adds an attribute to this attribute set.  If there was already an attribute with the same
-    /// name, replaces its value and returns `Err`.
+    /// Adds an attribute to the end of this attribute set.  If there was already an attribute with
+    /// the same name, replaces its value in place and returns `Err`.
     pub fn add<V: Into<Value>>(&mut self, name: Identifier, value: V) -> Result<(), ()> {
-        match self.values.entry(name) {
-            Entry::Occupied(mut o) => {
-                o.insert(value.into());
+        match self.values.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, existing)) => {
+                *existing = value.into();
                 Err(())
             }
-            Entry::Vacant(v) => {
-                v.insert(value.into());
+            None => {
+                self.values.push((name, value.into()));
                 Ok(())
             }
         }
@@ -291,23 +439,79 @@ impl Attributes {
     /// Returns the value of a particular attribute, if it exists.
     pub fn get<Q>(&self, name: &Q) -> Option<&Value>
     where
-        Q: ?Sized + Eq + Hash,
+        Q: ?Sized + Eq,
         Identifier: Borrow<Q>,
     {
-        self.values.get(name.borrow())
+        self.values
+            .iter()
+            .find(|(key, _)| key.borrow() == name)
+            .map(|(_, value)| value)
     }
 
+    /// Returns an iterator of all of the attributes, in the order they were added.
     pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &Value)> {
-        self.values.iter()
+        self.values.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Returns a mutable reference to the value of a particular attribute, if it exists.
+    pub fn get_mut<Q>(&mut self, name: &Q) -> Option<&mut Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        self.values
+            .iter_mut()
+            .find(|(key, _)| key.borrow() == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Removes an attribute from this attribute set, returning its value if it existed.
+    pub fn remove<Q>(&mut self, name: &Q) -> Option<Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        self.values
+            .iter()
+            .position(|(key, _)| key.borrow() == name)
+            .map(|index| self.values.remove(index).1)
+    }
+
+    /// Removes all attributes for which the predicate returns `false`.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&Identifier, &Value) -> bool,
+    {
+        self.values.retain(|(key, value)| keep(key, value));
+    }
+
+    /// Orders the attributes in this attribute set by name.
+    pub fn sort_by_name(&mut self) {
+        self.values.sort_by(|(left, _), (right, _)| left.cmp(right));
+    }
+
+    /// Returns the number of attributes in this attribute set.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether this attribute set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
 impl std::fmt::Display for Attributes {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut keys = self.values.keys().collect::<Vec<_>>();
-        keys.sort_by(|a, b| a.cmp(b));
-        for key in &keys {
-            let value = &self.values[*key];
+        let mut values = self.values.iter().collect::<Vec<_>>();
+        values.sort_by(|(left, _), (right, _)| left.cmp(right));
+        for (key, value) in values {
             write!(f, "  {}: {:?}\n", key, value)?;
         }
         Ok(())
@@ -317,7 +521,7 @@ impl std::fmt::Display for Attributes {
 impl Serialize for Attributes {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let mut map = serializer.serialize_map(None)?;
-        for (key, value) in &self.values {
+        for (key, value) in self.iter() {
             map.serialize_entry(key, value)?;
         }
         map.end()
@@ -331,13 +535,20 @@ pub enum Value {
     Null,
     Boolean(bool),
     Integer(u32),
-    String(String),
+    String(Arc<str>),
     // Compound
     List(Vec<Value>),
     Set(BTreeSet<Value>),
+    Map(BTreeMap<Identifier, Value>),
+    // Source ranges
+    Span {
+        start: SourcePosition,
+        end: SourcePosition,
+    },
     // References
     SyntaxNode(SyntaxNodeRef),
     GraphNode(GraphNodeRef),
+    Function(FunctionRef),
 }
 
 impl Value {
@@ -382,7 +593,7 @@ impl Value {
     /// Coerces this value into a string, returning an error if it's some other type of value.
     pub fn into_string(self) -> Result<String, ExecutionError> {
         match self {
-            Value::String(value) => Ok(value),
+            Value::String(value) => Ok(value.to_string()),
             _ => Err(ExecutionError::ExpectedString(format!("got {}", self))),
         }
     }
@@ -409,6 +620,45 @@ impl Value {
         }
     }
 
+    /// Coerces this value into a set, returning an error if it's some other type of value.
+    pub fn into_set(self) -> Result<BTreeSet<Value>, ExecutionError> {
+        match self {
+            Value::Set(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedSet(format!("got {}", self))),
+        }
+    }
+
+    pub fn as_set(&self) -> Result<&BTreeSet<Value>, ExecutionError> {
+        match self {
+            Value::Set(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedSet(format!("got {}", self))),
+        }
+    }
+
+    /// Coerces this value into a map, returning an error if it's some other type of value.
+    pub fn into_map(self) -> Result<BTreeMap<Identifier, Value>, ExecutionError> {
+        match self {
+            Value::Map(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedMap(format!("got {}", self))),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&BTreeMap<Identifier, Value>, ExecutionError> {
+        match self {
+            Value::Map(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedMap(format!("got {}", self))),
+        }
+    }
+
+    /// Coerces this value into the start and end of a span, returning an error if it's some other
+    /// type of value.
+    pub fn as_span(&self) -> Result<(SourcePosition, SourcePosition), ExecutionError> {
+        match self {
+            Value::Span { start, end } => Ok((*start, *end)),
+            _ => Err(ExecutionError::ExpectedSpan(format!("got {}", self))),
+        }
+    }
+
     /// Coerces this value into a graph node reference, returning an error if it's some other type
     /// of value.
     pub fn into_graph_node_ref<'a, 'tree>(self) -> Result<GraphNodeRef, ExecutionError> {
@@ -450,6 +700,24 @@ impl Value {
             _ => Err(ExecutionError::ExpectedSyntaxNode(format!("got {}", self))),
         }
     }
+
+    /// Coerces this value into a function reference, returning an error if it's some other type
+    /// of value.
+    pub fn into_function_ref(self) -> Result<FunctionRef, ExecutionError> {
+        match self {
+            Value::Function(function) => Ok(function),
+            _ => Err(ExecutionError::ExpectedFunction(format!("got {}", self))),
+        }
+    }
+
+    /// Returns the function reference that this value contains, returning an error if it's some
+    /// other type of value.
+    pub fn as_function_ref(&self) -> Result<&FunctionRef, ExecutionError> {
+        match self {
+            Value::Function(function) => Ok(function),
+            _ => Err(ExecutionError::ExpectedFunction(format!("got {}", self))),
+        }
+    }
 }
 
 impl From<bool> for Value {
@@ -466,13 +734,13 @@ impl From<u32> for Value {
 
 impl From<&str> for Value {
     fn from(value: &str) -> Value {
-        Value::String(value.to_string())
+        Value::String(value.into())
     }
 }
 
 impl From<String> for Value {
     fn from(value: String) -> Value {
-        Value::String(value)
+        Value::String(value.into())
     }
 }
 
@@ -488,6 +756,12 @@ impl From<BTreeSet<Value>> for Value {
     }
 }
 
+impl From<BTreeMap<Identifier, Value>> for Value {
+    fn from(value: BTreeMap<Identifier, Value>) -> Value {
+        Value::Map(value)
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -527,8 +801,23 @@ impl std::fmt::Display for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Map(value) => {
+                write!(f, "{{")?;
+                let mut first = true;
+                for (key, element) in value {
+                    if first {
+                        write!(f, "{}: {}", key, element)?;
+                        first = false;
+                    } else {
+                        write!(f, ", {}: {}", key, element)?;
+                    }
+                }
+                write!(f, "}}")
+            }
             Value::SyntaxNode(node) => node.fmt(f),
             Value::GraphNode(node) => node.fmt(f),
+            Value::Span { start, end } => write!(f, "[span {}-{}]", start, end),
+            Value::Function(function) => function.fmt(f),
         }
     }
 }
@@ -572,8 +861,23 @@ impl std::fmt::Debug for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Map(value) => {
+                write!(f, "{{")?;
+                let mut first = true;
+                for (key, element) in value {
+                    if first {
+                        write!(f, "{}: {:?}", key, element)?;
+                        first = false;
+                    } else {
+                        write!(f, ", {}: {:?}", key, element)?;
+                    }
+                }
+                write!(f, "}}")
+            }
             Value::SyntaxNode(node) => node.fmt(f),
             Value::GraphNode(node) => node.fmt(f),
+            Value::Span { start, end } => write!(f, "[span {}-{}]", start, end),
+            Value::Function(function) => function.fmt(f),
         }
     }
 }
@@ -601,7 +905,7 @@ impl Serialize for Value {
             Value::String(str) => {
                 let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "string")?;
-                map.serialize_entry("string", str)?;
+                map.serialize_entry("string", &**str)?;
                 map.end()
             }
             Value::List(list) => {
@@ -616,10 +920,19 @@ impl Serialize for Value {
                 map.serialize_entry("values", set)?;
                 map.end()
             }
+            Value::Map(values) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "map")?;
+                map.serialize_entry("values", values)?;
+                map.end()
+            }
             Value::SyntaxNode(node) => {
                 let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "syntaxNode")?;
                 map.serialize_entry("id", &node.index)?;
+                map.serialize_entry("kind", node.kind)?;
+                map.serialize_entry("row", &node.position.row)?;
+                map.serialize_entry("column", &node.position.column)?;
                 map.end()
             }
             Value::GraphNode(node) => {
@@ -628,6 +941,81 @@ impl Serialize for Value {
                 map.serialize_entry("id", &node.0)?;
                 map.end()
             }
+            Value::Function(function) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "function")?;
+                map.serialize_entry("name", function.name.as_str())?;
+                map.end()
+            }
+            Value::Span { start, end } => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "span")?;
+                map.serialize_entry("start", start)?;
+                map.serialize_entry("end", end)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// A position in a source file, as a byte offset and as a zero-based row and column.  A pair of
+/// positions forms a [`Value::Span`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SourcePosition {
+    pub byte: usize,
+    pub row: usize,
+    pub column: usize,
+}
+
+impl SourcePosition {
+    /// Returns the position at which a syntax node starts.
+    pub fn start_of(node: &Node) -> SourcePosition {
+        SourcePosition {
+            byte: node.start_byte(),
+            row: node.start_position().row,
+            column: node.start_position().column,
+        }
+    }
+
+    /// Returns the position at which a syntax node ends.
+    pub fn end_of(node: &Node) -> SourcePosition {
+        SourcePosition {
+            byte: node.end_byte(),
+            row: node.end_position().row,
+            column: node.end_position().column,
+        }
+    }
+}
+
+impl std::fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "({}, {})", self.row + 1, self.column + 1)
+    }
+}
+
+impl Serialize for SourcePosition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("byte", &self.byte)?;
+        map.serialize_entry("row", &self.row)?;
+        map.serialize_entry("column", &self.column)?;
+        map.end()
+    }
+}
+
+impl From<tree_sitter::Range> for Value {
+    fn from(range: tree_sitter::Range) -> Value {
+        Value::Span {
+            start: SourcePosition {
+                byte: range.start_byte,
+                row: range.start_point.row,
+                column: range.start_point.column,
+            },
+            end: SourcePosition {
+                byte: range.end_byte,
+                row: range.end_point.row,
+                column: range.end_point.column,
+            },
         }
     }
 }
@@ -690,6 +1078,11 @@ impl std::fmt::Debug for SyntaxNodeRef {
 pub struct GraphNodeRef(GraphNodeID);
 
 impl GraphNodeRef {
+    /// Returns a reference to the graph node with the given index, which does not have to exist.
+    pub(crate) fn new(index: usize) -> GraphNodeRef {
+        GraphNodeRef(index as GraphNodeID)
+    }
+
     /// Returns the index of the graph node that this reference refers to.
     pub fn index(self) -> usize {
         self.0 as usize
@@ -713,3 +1106,82 @@ impl std::fmt::Debug for GraphNodeRef {
         write!(f, "[graph node {}]", self.0)
     }
 }
+
+/// A reference to a named function, which can be passed as a value to higher-order functions
+/// like `map` and `filter`.  Function references are compared by name.
+#[derive(Clone)]
+pub struct FunctionRef {
+    name: Identifier,
+    function: Arc<dyn Function + Send + Sync>,
+}
+
+impl FunctionRef {
+    pub(crate) fn new(name: Identifier, function: Arc<dyn Function + Send + Sync>) -> FunctionRef {
+        FunctionRef { name, function }
+    }
+
+    /// Returns the name of the function that this reference refers to.
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    /// Calls the referenced function with the given parameters.
+    pub fn call(
+        &self,
+        graph: &mut Graph,
+        state: &mut ExecutionState,
+        source: &str,
+        parameters: &mut dyn Parameters,
+    ) -> Result<Value, ExecutionError> {
+        self.function.call(graph, state, source, parameters)
+    }
+
+    /// Returns whether the referenced function [reads the graph][`Function::reads_graph`].
+    pub(crate) fn reads_graph(&self) -> bool {
+        self.function.reads_graph()
+    }
+}
+
+impl From<FunctionRef> for Value {
+    fn from(value: FunctionRef) -> Value {
+        Value::Function(value)
+    }
+}
+
+impl PartialEq for FunctionRef {
+    fn eq(&self, other: &FunctionRef) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for FunctionRef {}
+
+impl PartialOrd for FunctionRef {
+    fn partial_cmp(&self, other: &FunctionRef) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FunctionRef {
+    fn cmp(&self, other: &FunctionRef) -> std::cmp::Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+impl Hash for FunctionRef {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
+
+impl std::fmt::Display for FunctionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[function {}]", self.name)
+    }
+}
+
+impl std::fmt::Debug for FunctionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[function {}]", self.name)
+    }
+}
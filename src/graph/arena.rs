@@ -0,0 +1,757 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Stores the graph nodes, edges, and attributes of a graph in arenas
+//!
+//! Every graph node is a fixed-size record in the node arena, which refers to its outgoing edges
+//! and attributes by index.  The outgoing edges of a node are a range of the edge arena, ordered
+//! by sink, which is moved to the end of the arena with twice the capacity when it is full.  The
+//! attributes of a node or edge are a chain of records in the attribute arena, in the order they
+//! were added.  Records that are no longer used, because their edge or attribute was removed or
+//! their range was moved, are left in place until the graph is rebuilt.
+//!
+//! The arenas are [`CowVec`]s, so graphs that are forked or snapshotted share them until either
+//! side modifies them.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::mem::size_of;
+
+use serde::ser::SerializeMap;
+use serde::Serialize;
+use serde::Serializer;
+
+use super::snapshot::CowVec;
+use super::Attributes;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use super::Value;
+use crate::Identifier;
+
+/// The index of an unused attribute chain link.
+const NONE: u32 = u32::MAX;
+
+/// The arenas that the graph nodes, edges, and attributes of a graph are stored in.
+#[derive(Clone, Default)]
+pub(super) struct Arenas {
+    nodes: CowVec<NodeRecord>,
+    edges: CowVec<EdgeRecord>,
+    attributes: CowVec<AttributeRecord>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct NodeRecord {
+    edges: EdgeRange,
+    attributes: AttributeChain,
+}
+
+/// The range of the edge arena containing the outgoing edges of a node.  Only the first `len`
+/// records are in use; the remaining ones are reserved for edges that are added later.
+#[derive(Clone, Copy, Default)]
+struct EdgeRange {
+    start: u32,
+    len: u32,
+    capacity: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct EdgeRecord {
+    sink: GraphNodeID,
+    attributes: AttributeChain,
+}
+
+/// The first and last attribute records of a node or edge, which link to each other in order.
+#[derive(Clone, Copy)]
+struct AttributeChain {
+    first: u32,
+    last: u32,
+    len: u32,
+}
+
+impl Default for AttributeChain {
+    fn default() -> Self {
+        Self {
+            first: NONE,
+            last: NONE,
+            len: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AttributeRecord {
+    name: Identifier,
+    value: Value,
+    next: u32,
+}
+
+/// The node or edge that an attribute chain belongs to, as an index into its arena.
+#[derive(Clone, Copy)]
+enum Owner {
+    Node(u32),
+    Edge(u32),
+}
+
+impl Arenas {
+    pub(super) fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(super) fn add_node(&mut self) -> GraphNodeRef {
+        self.nodes.push(NodeRecord::default());
+        GraphNodeRef((self.nodes.len() - 1) as GraphNodeID)
+    }
+
+    /// Returns the number of bytes used by the records of the arenas, including the ones that are
+    /// no longer used, but not by values that they refer to.
+    pub(super) fn record_bytes(&self) -> usize {
+        self.nodes.len() * size_of::<NodeRecord>()
+            + self.edges.len() * size_of::<EdgeRecord>()
+            + self.attributes.len() * size_of::<AttributeRecord>()
+    }
+
+    /// Returns mutable references to the values of all attributes in the graph, including removed
+    /// ones.
+    pub(super) fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.attributes.iter_mut().map(|record| &mut record.value)
+    }
+
+    /// Orders the attributes of every graph node and edge by name.
+    pub(super) fn sort_attributes_by_name(&mut self) {
+        for index in 0..self.nodes.len() {
+            let range = self.nodes[index].edges;
+            let owners = std::iter::once(Owner::Node(index as u32))
+                .chain((range.start..range.start + range.len).map(Owner::Edge));
+            for owner in owners {
+                StoredAttributesMut {
+                    arenas: self,
+                    owner,
+                }
+                .sort_by_name();
+            }
+        }
+    }
+
+    /// Returns whether a graph node and its edges and attributes are stored in chunks that are
+    /// shared with another graph, which means that they are the same in both.
+    pub(super) fn shares_node_with(&self, other: &Arenas, index: usize) -> bool {
+        if !self.nodes.shares_chunk_with(&other.nodes, index) {
+            return false;
+        }
+        let node = self.nodes[index];
+        let edges = node.edges.start as usize..(node.edges.start + node.edges.len) as usize;
+        edges
+            .clone()
+            .all(|edge| self.edges.shares_chunk_with(&other.edges, edge))
+            && self.shares_attributes_with(other, node.attributes)
+            && edges
+                .into_iter()
+                .all(|edge| self.shares_attributes_with(other, self.edges[edge].attributes))
+    }
+
+    fn shares_attributes_with(&self, other: &Arenas, chain: AttributeChain) -> bool {
+        let mut next = chain.first;
+        while next != NONE {
+            if !self
+                .attributes
+                .shares_chunk_with(&other.attributes, next as usize)
+            {
+                return false;
+            }
+            next = self.attributes[next as usize].next;
+        }
+        true
+    }
+
+    fn chain(&self, owner: Owner) -> AttributeChain {
+        match owner {
+            Owner::Node(index) => self.nodes[index as usize].attributes,
+            Owner::Edge(index) => self.edges[index as usize].attributes,
+        }
+    }
+
+    fn set_chain(&mut self, owner: Owner, chain: AttributeChain) {
+        match owner {
+            Owner::Node(index) => self.nodes[index as usize].attributes = chain,
+            Owner::Edge(index) => self.edges[index as usize].attributes = chain,
+        }
+    }
+
+    /// Returns the index of the attribute record with a particular name, and of the record before
+    /// it in its chain.
+    fn find_attribute<Q>(&self, chain: AttributeChain, name: &Q) -> Option<(u32, u32)>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        let mut previous = NONE;
+        let mut next = chain.first;
+        while next != NONE {
+            let record = &self.attributes[next as usize];
+            if record.name.borrow() == name {
+                return Some((previous, next));
+            }
+            previous = next;
+            next = record.next;
+        }
+        None
+    }
+
+    /// Removes an attribute record from its chain, returning its value.
+    fn unlink_attribute(&mut self, chain: &mut AttributeChain, previous: u32, index: u32) -> Value {
+        let record = &mut self.attributes[index as usize];
+        let next = record.next;
+        let value = std::mem::replace(&mut record.value, Value::Null);
+        if previous == NONE {
+            chain.first = next;
+        } else {
+            self.attributes[previous as usize].next = next;
+        }
+        if chain.last == index {
+            chain.last = previous;
+        }
+        chain.len -= 1;
+        value
+    }
+
+    /// Returns the position of the edge with a particular sink among the outgoing edges of a node,
+    /// or the position that it would be inserted at.
+    fn find_edge(&self, range: EdgeRange, sink: GraphNodeID) -> Result<u32, u32> {
+        let (mut low, mut high) = (0, range.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let candidate = self.edges[(range.start + middle) as usize].sink;
+            if candidate == sink {
+                return Ok(middle);
+            } else if candidate < sink {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Err(low)
+    }
+
+    /// Makes room for one more outgoing edge of a node, by extending its range in place if it is
+    /// at the end of the edge arena, and by moving it there otherwise.
+    fn reserve_edge(&mut self, node: usize) {
+        let mut range = self.nodes[node].edges;
+        if range.len < range.capacity {
+            return;
+        }
+        let capacity = (range.capacity * 2).max(2);
+        if range.capacity == 0 || (range.start + range.capacity) as usize != self.edges.len() {
+            let start = self.edges.len() as u32;
+            for index in range.start..range.start + range.len {
+                let edge = self.edges[index as usize];
+                self.edges.push(edge);
+            }
+            range.start = start;
+            range.capacity = range.len;
+        }
+        while range.capacity < capacity {
+            self.edges.push(EdgeRecord::default());
+            range.capacity += 1;
+        }
+        self.nodes[node].edges = range;
+    }
+
+    /// Copies the attributes of a node or edge of another graph to a node or edge of this one.
+    fn copy_attributes(&mut self, owner: Owner, source: StoredAttributes) {
+        let mut target = StoredAttributesMut {
+            arenas: self,
+            owner,
+        };
+        for (name, value) in source.iter() {
+            let _ = target.add(name.clone(), value.clone());
+        }
+    }
+
+    /// Returns a copy of these arenas that only contains the records that are still in use, with
+    /// the graph nodes that are mapped to `None` removed, along with their incoming edges.  The
+    /// mapping must number the remaining nodes densely, in order.
+    pub(super) fn compact(&self, mapping: &[Option<GraphNodeRef>]) -> Arenas {
+        let mut compacted = Arenas::default();
+        for _ in mapping.iter().flatten() {
+            compacted.add_node();
+        }
+        for (index, new_node) in mapping.iter().enumerate() {
+            let new_node = match new_node {
+                Some(new_node) => *new_node,
+                None => continue,
+            };
+            let node = GraphNode {
+                arenas: self,
+                index: index as GraphNodeID,
+            };
+            compacted.copy_attributes(Owner::Node(new_node.0), node.attributes());
+            for (sink, edge) in node.iter_edges() {
+                let sink = match mapping[sink.index()] {
+                    Some(sink) => sink,
+                    None => continue,
+                };
+                let owner = {
+                    let node = GraphNodeMut {
+                        arenas: &mut compacted,
+                        index: new_node.0,
+                    };
+                    let edge = match node.add_edge(sink) {
+                        Ok(edge) => edge,
+                        Err(edge) => edge,
+                    };
+                    Owner::Edge(edge.index)
+                };
+                compacted.copy_attributes(owner, edge.attributes());
+            }
+        }
+        compacted
+    }
+}
+
+/// A node in a graph, which can be accessed using [`Graph::node`][crate::graph::Graph::node].
+#[derive(Clone, Copy)]
+pub struct GraphNode<'a> {
+    pub(super) arenas: &'a Arenas,
+    pub(super) index: GraphNodeID,
+}
+
+impl<'a> GraphNode<'a> {
+    fn record(&self) -> NodeRecord {
+        self.arenas.nodes[self.index as usize]
+    }
+
+    /// Returns the set of attributes associated with this graph node.
+    pub fn attributes(&self) -> StoredAttributes<'a> {
+        StoredAttributes {
+            arenas: self.arenas,
+            chain: self.record().attributes,
+        }
+    }
+
+    /// Returns an outgoing edge from this node, if it exists.
+    pub fn get_edge(&self, sink: GraphNodeRef) -> Option<Edge<'a>> {
+        let range = self.record().edges;
+        self.arenas
+            .find_edge(range, sink.0)
+            .ok()
+            .map(|position| Edge {
+                arenas: self.arenas,
+                index: range.start + position,
+            })
+    }
+
+    /// Returns an iterator of all of the outgoing edges from this node, ordered by the index of
+    /// their sink node.
+    pub fn iter_edges(&self) -> impl Iterator<Item = (GraphNodeRef, Edge<'a>)> {
+        let arenas = self.arenas;
+        let range = self.record().edges;
+        (range.start..range.start + range.len).map(move |index| {
+            let edge = Edge { arenas, index };
+            (edge.sink(), edge)
+        })
+    }
+
+    /// Returns the number of outgoing edges from this node.
+    pub fn edge_count(&self) -> usize {
+        self.record().edges.len as usize
+    }
+}
+
+/// A node in a graph that can be modified, which can be accessed using
+/// [`Graph::node_mut`][crate::graph::Graph::node_mut].
+pub struct GraphNodeMut<'a> {
+    pub(super) arenas: &'a mut Arenas,
+    pub(super) index: GraphNodeID,
+}
+
+impl<'a> GraphNodeMut<'a> {
+    /// Returns a read-only view of this node.
+    pub fn as_node(&self) -> GraphNode<'_> {
+        GraphNode {
+            arenas: self.arenas,
+            index: self.index,
+        }
+    }
+
+    /// Returns the set of attributes associated with this graph node.
+    pub fn attributes(&mut self) -> StoredAttributesMut<'_> {
+        StoredAttributesMut {
+            arenas: self.arenas,
+            owner: Owner::Node(self.index),
+        }
+    }
+
+    /// Returns the set of attributes associated with this graph node, for as long as this node
+    /// could have been modified.
+    pub fn into_attributes(self) -> StoredAttributesMut<'a> {
+        StoredAttributesMut {
+            arenas: self.arenas,
+            owner: Owner::Node(self.index),
+        }
+    }
+
+    /// Adds an edge to this node.  There can be at most one edge connecting any two graph nodes;
+    /// the result indicates whether the edge is new (`Ok`) or already existed (`Err`).  In either
+    /// case, you also get an [`EdgeMut`][] for the edge, which can be used for as long as this node
+    /// could have been.
+    pub fn add_edge(self, sink: GraphNodeRef) -> Result<EdgeMut<'a>, EdgeMut<'a>> {
+        let node = self.index as usize;
+        let range = self.arenas.nodes[node].edges;
+        let position = match self.arenas.find_edge(range, sink.0) {
+            Ok(position) => {
+                return Err(EdgeMut {
+                    arenas: self.arenas,
+                    index: range.start + position,
+                })
+            }
+            Err(position) => position,
+        };
+        self.arenas.reserve_edge(node);
+        let mut range = self.arenas.nodes[node].edges;
+        for index in (range.start + position..range.start + range.len).rev() {
+            let edge = self.arenas.edges[index as usize];
+            self.arenas.edges[index as usize + 1] = edge;
+        }
+        let index = range.start + position;
+        self.arenas.edges[index as usize] = EdgeRecord {
+            sink: sink.0,
+            attributes: AttributeChain::default(),
+        };
+        range.len += 1;
+        self.arenas.nodes[node].edges = range;
+        Ok(EdgeMut {
+            arenas: self.arenas,
+            index,
+        })
+    }
+
+    /// Returns an outgoing edge from this node that can be modified, if it exists.
+    pub fn get_edge_mut(&mut self, sink: GraphNodeRef) -> Option<EdgeMut<'_>> {
+        GraphNodeMut {
+            arenas: self.arenas,
+            index: self.index,
+        }
+        .into_edge_mut(sink)
+    }
+
+    /// Returns an outgoing edge from this node that can be modified, if it exists, for as long as
+    /// this node could have been modified.
+    pub fn into_edge_mut(self, sink: GraphNodeRef) -> Option<EdgeMut<'a>> {
+        let range = self.arenas.nodes[self.index as usize].edges;
+        match self.arenas.find_edge(range, sink.0) {
+            Ok(position) => Some(EdgeMut {
+                arenas: self.arenas,
+                index: range.start + position,
+            }),
+            Err(_) => None,
+        }
+    }
+
+    /// Removes an outgoing edge from this node, returning its attributes if it existed.
+    pub fn remove_edge(&mut self, sink: GraphNodeRef) -> Option<Attributes> {
+        let node = self.index as usize;
+        let mut range = self.arenas.nodes[node].edges;
+        let position = self.arenas.find_edge(range, sink.0).ok()?;
+        let index = range.start + position;
+        let attributes = Edge {
+            arenas: self.arenas,
+            index,
+        }
+        .attributes()
+        .to_attributes();
+        for index in index..range.start + range.len - 1 {
+            let edge = self.arenas.edges[index as usize + 1];
+            self.arenas.edges[index as usize] = edge;
+        }
+        range.len -= 1;
+        self.arenas.nodes[node].edges = range;
+        Some(attributes)
+    }
+}
+
+/// An edge between two nodes in a graph
+#[derive(Clone, Copy)]
+pub struct Edge<'a> {
+    arenas: &'a Arenas,
+    index: u32,
+}
+
+impl<'a> Edge<'a> {
+    /// Returns the node that this edge points to.
+    pub fn sink(&self) -> GraphNodeRef {
+        GraphNodeRef(self.arenas.edges[self.index as usize].sink)
+    }
+
+    /// Returns the set of attributes associated with this edge.
+    pub fn attributes(&self) -> StoredAttributes<'a> {
+        StoredAttributes {
+            arenas: self.arenas,
+            chain: self.arenas.edges[self.index as usize].attributes,
+        }
+    }
+}
+
+/// An edge between two nodes in a graph that can be modified
+pub struct EdgeMut<'a> {
+    arenas: &'a mut Arenas,
+    index: u32,
+}
+
+impl<'a> EdgeMut<'a> {
+    /// Returns the node that this edge points to.
+    pub fn sink(&self) -> GraphNodeRef {
+        GraphNodeRef(self.arenas.edges[self.index as usize].sink)
+    }
+
+    /// Returns the set of attributes associated with this edge.
+    pub fn attributes(&mut self) -> StoredAttributesMut<'_> {
+        StoredAttributesMut {
+            arenas: self.arenas,
+            owner: Owner::Edge(self.index),
+        }
+    }
+
+    /// Returns the set of attributes associated with this edge, for as long as this edge could
+    /// have been modified.
+    pub fn into_attributes(self) -> StoredAttributesMut<'a> {
+        StoredAttributesMut {
+            arenas: self.arenas,
+            owner: Owner::Edge(self.index),
+        }
+    }
+}
+
+/// The set of attributes associated with a graph node or edge, as stored in its graph
+#[derive(Clone, Copy)]
+pub struct StoredAttributes<'a> {
+    arenas: &'a Arenas,
+    chain: AttributeChain,
+}
+
+impl<'a> StoredAttributes<'a> {
+    /// Returns the value of a particular attribute, if it exists.
+    pub fn get<Q>(&self, name: &Q) -> Option<&'a Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        let arenas = self.arenas;
+        arenas
+            .find_attribute(self.chain, name)
+            .map(|(_, index)| &arenas.attributes[index as usize].value)
+    }
+
+    /// Returns an iterator of all of the attributes, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a Identifier, &'a Value)> {
+        let arenas = self.arenas;
+        let mut next = self.chain.first;
+        std::iter::from_fn(move || {
+            if next == NONE {
+                return None;
+            }
+            let record = &arenas.attributes[next as usize];
+            next = record.next;
+            Some((&record.name, &record.value))
+        })
+    }
+
+    /// Returns the number of attributes in this attribute set.
+    pub fn len(&self) -> usize {
+        self.chain.len as usize
+    }
+
+    /// Returns whether this attribute set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.chain.len == 0
+    }
+
+    /// Returns a copy of these attributes that is not stored in the graph.
+    pub fn to_attributes(&self) -> Attributes {
+        let mut attributes = Attributes::new();
+        for (name, value) in self.iter() {
+            let _ = attributes.add(name.clone(), value.clone());
+        }
+        attributes
+    }
+}
+
+impl fmt::Debug for StoredAttributes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Display for StoredAttributes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut values = self.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(name, _)| *name);
+        for (key, value) in values {
+            writeln!(f, "  {}: {:?}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for StoredAttributes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The set of attributes associated with a graph node or edge that can be modified
+pub struct StoredAttributesMut<'a> {
+    arenas: &'a mut Arenas,
+    owner: Owner,
+}
+
+impl<'a> StoredAttributesMut<'a> {
+    /// Returns a read-only view of these attributes.
+    pub fn as_attributes(&self) -> StoredAttributes<'_> {
+        StoredAttributes {
+            arenas: self.arenas,
+            chain: self.arenas.chain(self.owner),
+        }
+    }
+
+    /// Adds an attribute to the end of this attribute set.  If there was already an attribute with
+    /// the same name, replaces its value in place and returns `Err`.
+    pub fn add<V: Into<Value>>(&mut self, name: Identifier, value: V) -> Result<(), ()> {
+        let mut chain = self.arenas.chain(self.owner);
+        if let Some((_, index)) = self.arenas.find_attribute(chain, &name) {
+            self.arenas.attributes[index as usize].value = value.into();
+            return Err(());
+        }
+        let index = self.arenas.attributes.len() as u32;
+        self.arenas.attributes.push(AttributeRecord {
+            name,
+            value: value.into(),
+            next: NONE,
+        });
+        if chain.last == NONE {
+            chain.first = index;
+        } else {
+            self.arenas.attributes[chain.last as usize].next = index;
+        }
+        chain.last = index;
+        chain.len += 1;
+        self.arenas.set_chain(self.owner, chain);
+        Ok(())
+    }
+
+    /// Adds all of the attributes of an attribute set, replacing the values of attributes that
+    /// already exist.
+    pub fn extend(&mut self, attributes: Attributes) {
+        for (name, value) in attributes.values {
+            let _ = self.add(name, value);
+        }
+    }
+
+    /// Returns the value of a particular attribute, if it exists.
+    pub fn get<Q>(&self, name: &Q) -> Option<&Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        self.as_attributes().get(name)
+    }
+
+    /// Returns an iterator of all of the attributes, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &Value)> {
+        self.as_attributes().iter()
+    }
+
+    /// Returns a mutable reference to the value of a particular attribute, if it exists.
+    pub fn get_mut<Q>(&mut self, name: &Q) -> Option<&mut Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        let chain = self.arenas.chain(self.owner);
+        let (_, index) = self.arenas.find_attribute(chain, name)?;
+        Some(&mut self.arenas.attributes[index as usize].value)
+    }
+
+    /// Removes an attribute from this attribute set, returning its value if it existed.
+    pub fn remove<Q>(&mut self, name: &Q) -> Option<Value>
+    where
+        Q: ?Sized + Eq,
+        Identifier: Borrow<Q>,
+    {
+        let mut chain = self.arenas.chain(self.owner);
+        let (previous, index) = self.arenas.find_attribute(chain, name)?;
+        let value = self.arenas.unlink_attribute(&mut chain, previous, index);
+        self.arenas.set_chain(self.owner, chain);
+        Some(value)
+    }
+
+    /// Removes all attributes for which the predicate returns `false`.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&Identifier, &Value) -> bool,
+    {
+        let mut chain = self.arenas.chain(self.owner);
+        let mut previous = NONE;
+        let mut next = chain.first;
+        while next != NONE {
+            let record = &self.arenas.attributes[next as usize];
+            let following = record.next;
+            if keep(&record.name, &record.value) {
+                previous = next;
+            } else {
+                self.arenas.unlink_attribute(&mut chain, previous, next);
+            }
+            next = following;
+        }
+        self.arenas.set_chain(self.owner, chain);
+    }
+
+    /// Orders the attributes in this attribute set by name.
+    pub fn sort_by_name(&mut self) {
+        let mut chain = self.arenas.chain(self.owner);
+        let mut indices = Vec::with_capacity(chain.len as usize);
+        let mut next = chain.first;
+        while next != NONE {
+            indices.push(next);
+            next = self.arenas.attributes[next as usize].next;
+        }
+        let attributes = &self.arenas.attributes;
+        indices.sort_by(|left, right| {
+            attributes[*left as usize]
+                .name
+                .cmp(&attributes[*right as usize].name)
+        });
+        for pair in indices.windows(2) {
+            self.arenas.attributes[pair[0] as usize].next = pair[1];
+        }
+        if let (Some(first), Some(last)) = (indices.first(), indices.last()) {
+            self.arenas.attributes[*last as usize].next = NONE;
+            chain.first = *first;
+            chain.last = *last;
+        }
+        self.arenas.set_chain(self.owner, chain);
+    }
+
+    /// Returns the number of attributes in this attribute set.
+    pub fn len(&self) -> usize {
+        self.arenas.chain(self.owner).len as usize
+    }
+
+    /// Returns whether this attribute set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for StoredAttributesMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_attributes().fmt(f)
+    }
+}
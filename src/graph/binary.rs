@@ -0,0 +1,408 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Encodes graphs in a compact binary format
+//!
+//! An encoded graph starts with the magic bytes `TSGB` and a format version, followed by a table
+//! of all attribute names, the graph's gensym counter, and then each graph node with its
+//! attributes and outgoing edges.  All integers are encoded as unsigned LEB128 varints, and all
+//! strings as a varint length followed by UTF-8 bytes.  Attribute names are encoded as indices
+//! into the name table.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+use super::Attributes;
+use super::FunctionRef;
+use super::Graph;
+use super::GraphNodeRef;
+use super::SourcePosition;
+use super::StoredAttributes;
+use super::SyntaxNodeRef;
+use super::Value;
+use crate::execution::error::ExecutionError;
+use crate::execution::ExecutionState;
+use crate::functions::Function;
+use crate::functions::Parameters;
+use crate::Identifier;
+
+const MAGIC: &[u8] = b"TSGB";
+const VERSION: u32 = 1;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INTEGER: u8 = 3;
+const STRING: u8 = 4;
+const LIST: u8 = 5;
+const SET: u8 = 6;
+const SYNTAX_NODE: u8 = 7;
+const GRAPH_NODE: u8 = 8;
+const FUNCTION: u8 = 9;
+const MAP: u8 = 10;
+const SPAN: u8 = 11;
+
+/// An error that can occur while decoding a graph
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("Invalid graph encoding: {0}")]
+    Invalid(String),
+    #[error("Unsupported graph format version {0}")]
+    UnsupportedVersion(u32),
+}
+
+impl<'tree> Graph<'tree> {
+    /// Encodes the contents of this graph in a compact binary format, which can be decoded with
+    /// [`Graph::from_binary`].
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut name_table = BTreeSet::new();
+        for node in self.iter_nodes() {
+            let node = self.node(node);
+            let edge_attributes = node.iter_edges().map(|(_, edge)| edge.attributes());
+            for attributes in std::iter::once(node.attributes()).chain(edge_attributes) {
+                name_table.extend(attributes.iter().map(|(name, _)| name.clone()));
+            }
+        }
+
+        let mut encoder = Encoder {
+            bytes: MAGIC.to_vec(),
+            names: (name_table.iter().cloned())
+                .enumerate()
+                .map(|(index, name)| (name, index))
+                .collect(),
+        };
+        encoder.varint(VERSION as u64);
+        encoder.varint(name_table.len() as u64);
+        for name in &name_table {
+            encoder.string(name.as_str());
+        }
+        encoder.varint(self.node_count() as u64);
+        for node in self.iter_nodes() {
+            let node = self.node(node);
+            encoder.attributes(node.attributes());
+            encoder.varint(node.edge_count() as u64);
+            for (sink, edge) in node.iter_edges() {
+                encoder.varint(sink.index() as u64);
+                encoder.attributes(edge.attributes());
+            }
+        }
+        encoder.bytes
+    }
+
+    /// Decodes a graph that was encoded with [`Graph::to_binary`].
+    ///
+    /// The decoded graph does not contain any syntax nodes, since they belong to a syntax tree
+    /// that is no longer available.  Syntax node references in attribute values keep their kind
+    /// and location, but cannot be used to look up the syntax nodes they refer to.  Function
+    /// references keep their name, but fail when called.
+    pub fn from_binary(bytes: &[u8]) -> Result<Graph<'tree>, DecodeError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(DecodeError::Invalid("missing magic bytes".to_string()));
+        }
+        let mut decoder = Decoder {
+            bytes: &bytes[MAGIC.len()..],
+            names: Vec::new(),
+        };
+        let version = decoder.u32()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let name_count = decoder.length()?;
+        for _ in 0..name_count {
+            let name = decoder.string()?;
+            decoder.names.push(Identifier::from(name.as_str()));
+        }
+
+        let mut graph = Graph::new();
+        let node_count = decoder.length()?;
+        for _ in 0..node_count {
+            let node_ref = graph.add_graph_node();
+            graph
+                .node_mut(node_ref)
+                .attributes()
+                .extend(decoder.attributes()?);
+            let edge_count = decoder.length()?;
+            for _ in 0..edge_count {
+                let sink = decoder.u32()?;
+                if sink as usize >= node_count {
+                    return Err(DecodeError::Invalid(format!(
+                        "edge to missing node {}",
+                        sink
+                    )));
+                }
+                let mut edge = match graph.node_mut(node_ref).add_edge(GraphNodeRef(sink)) {
+                    Ok(edge) => edge,
+                    Err(edge) => edge,
+                };
+                edge.attributes().extend(decoder.attributes()?);
+            }
+        }
+        if !decoder.bytes.is_empty() {
+            return Err(DecodeError::Invalid("trailing bytes".to_string()));
+        }
+        Ok(graph)
+    }
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    names: HashMap<Identifier, usize>,
+}
+
+impl Encoder {
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn attributes(&mut self, attributes: StoredAttributes) {
+        self.varint(attributes.len() as u64);
+        for (name, value) in attributes.iter() {
+            self.varint(self.names[name] as u64);
+            self.value(value);
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.bytes.push(NULL),
+            Value::Boolean(false) => self.bytes.push(FALSE),
+            Value::Boolean(true) => self.bytes.push(TRUE),
+            Value::Integer(value) => {
+                self.bytes.push(INTEGER);
+                self.varint(*value as u64);
+            }
+            Value::String(value) => {
+                self.bytes.push(STRING);
+                self.string(value);
+            }
+            Value::List(values) => {
+                self.bytes.push(LIST);
+                self.varint(values.len() as u64);
+                values.iter().for_each(|value| self.value(value));
+            }
+            Value::Set(values) => {
+                self.bytes.push(SET);
+                self.varint(values.len() as u64);
+                values.iter().for_each(|value| self.value(value));
+            }
+            Value::Map(values) => {
+                self.bytes.push(MAP);
+                self.varint(values.len() as u64);
+                for (key, value) in values {
+                    self.string(key.as_str());
+                    self.value(value);
+                }
+            }
+            Value::SyntaxNode(node) => {
+                self.bytes.push(SYNTAX_NODE);
+                self.varint(node.index as u64);
+                self.string(node.kind);
+                self.varint(node.position.row as u64);
+                self.varint(node.position.column as u64);
+            }
+            Value::GraphNode(node) => {
+                self.bytes.push(GRAPH_NODE);
+                self.varint(node.0 as u64);
+            }
+            Value::Function(function) => {
+                self.bytes.push(FUNCTION);
+                self.string(function.name().as_str());
+            }
+            Value::Span { start, end } => {
+                self.bytes.push(SPAN);
+                for position in [start, end] {
+                    self.varint(position.byte as u64);
+                    self.varint(position.row as u64);
+                    self.varint(position.column as u64);
+                }
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    names: Vec<Identifier>,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (byte, rest) = self
+            .bytes
+            .split_first()
+            .ok_or_else(|| DecodeError::Invalid("unexpected end of input".to_string()))?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err(DecodeError::Invalid("varint is too long".to_string()));
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let value = self.varint()?;
+        u32::try_from(value)
+            .map_err(|_| DecodeError::Invalid(format!("{} does not fit in 32 bits", value)))
+    }
+
+    /// Decodes a count of items, each of which takes at least one byte.
+    fn position(&mut self) -> Result<SourcePosition, DecodeError> {
+        Ok(SourcePosition {
+            byte: self.varint()? as usize,
+            row: self.varint()? as usize,
+            column: self.varint()? as usize,
+        })
+    }
+
+    fn length(&mut self) -> Result<usize, DecodeError> {
+        let length = self.varint()?;
+        if length > self.bytes.len() as u64 {
+            return Err(DecodeError::Invalid(format!("invalid length {}", length)));
+        }
+        Ok(length as usize)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let length = self.length()?;
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| DecodeError::Invalid("invalid UTF-8 in string".to_string()))
+    }
+
+    fn attributes(&mut self) -> Result<Attributes, DecodeError> {
+        let mut attributes = Attributes::new();
+        let count = self.length()?;
+        for _ in 0..count {
+            let index = self.length()?;
+            let name =
+                self.names.get(index).cloned().ok_or_else(|| {
+                    DecodeError::Invalid(format!("invalid attribute name {}", index))
+                })?;
+            let value = self.value()?;
+            attributes
+                .add(name.clone(), value)
+                .map_err(|_| DecodeError::Invalid(format!("duplicate attribute {}", name)))?;
+        }
+        Ok(attributes)
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        Ok(match self.byte()? {
+            NULL => Value::Null,
+            FALSE => Value::Boolean(false),
+            TRUE => Value::Boolean(true),
+            INTEGER => Value::Integer(self.u32()?),
+            STRING => Value::String(self.string()?.into()),
+            LIST => {
+                let count = self.length()?;
+                Value::List((0..count).map(|_| self.value()).collect::<Result<_, _>>()?)
+            }
+            SET => {
+                let count = self.length()?;
+                Value::Set((0..count).map(|_| self.value()).collect::<Result<_, _>>()?)
+            }
+            MAP => {
+                let count = self.length()?;
+                Value::Map(
+                    (0..count)
+                        .map(|_| Ok((Identifier::from(self.string()?.as_str()), self.value()?)))
+                        .collect::<Result<_, DecodeError>>()?,
+                )
+            }
+            SYNTAX_NODE => {
+                let index = self.u32()?;
+                let kind = intern_kind(self.string()?);
+                let row = self.varint()? as usize;
+                let column = self.varint()? as usize;
+                Value::SyntaxNode(SyntaxNodeRef {
+                    index,
+                    kind,
+                    position: tree_sitter::Point { row, column },
+                })
+            }
+            GRAPH_NODE => Value::GraphNode(GraphNodeRef(self.u32()?)),
+            FUNCTION => Value::Function(unresolved_function(self.string()?)),
+            SPAN => Value::Span {
+                start: self.position()?,
+                end: self.position()?,
+            },
+            tag => return Err(DecodeError::Invalid(format!("invalid value tag {}", tag))),
+        })
+    }
+}
+
+/// Returns a static copy of a syntax node kind.  Each distinct kind is only allocated once, so
+/// the memory used is bounded by the number of node kinds in the grammars that produced the
+/// decoded graphs.
+pub(super) fn intern_kind(kind: String) -> &'static str {
+    static KINDS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut kinds = KINDS.get_or_init(Default::default).lock().unwrap();
+    match kinds.get(kind.as_str()) {
+        Some(kind) => kind,
+        None => {
+            let kind: &'static str = Box::leak(kind.into_boxed_str());
+            kinds.insert(kind);
+            kind
+        }
+    }
+}
+
+/// Returns a reference to a function that is not available in a decoded graph.
+pub(super) fn unresolved_function(name: String) -> FunctionRef {
+    struct UnresolvedFunction(Identifier);
+
+    impl Function for UnresolvedFunction {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            _parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            Err(ExecutionError::FunctionFailed(
+                self.0.to_string(),
+                "function is not available in a decoded graph".to_string(),
+            ))
+        }
+    }
+
+    let name = Identifier::from(name.as_str());
+    FunctionRef::new(name.clone(), Arc::new(UnresolvedFunction(name)))
+}
@@ -0,0 +1,148 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Builds graphs from Rust code
+
+use tree_sitter::Node;
+
+use super::Graph;
+use super::GraphNodeRef;
+use super::SyntaxNodeRef;
+use super::Value;
+
+/// Adds nodes and edges to a graph from Rust code, without going through the graph DSL.  Use it to
+/// build graphs from scratch, or to add synthetic nodes to the graph that executing a DSL file
+/// produced.
+///
+/// ```
+/// # use tree_sitter_graph::graph::{Graph, GraphBuilder};
+/// let mut graph = Graph::new();
+/// let mut builder = GraphBuilder::new(&mut graph);
+/// let module = builder.node().attr("kind", "module").finish();
+/// let file = builder
+///     .node()
+///     .attr("kind", "file")
+///     .attr("path", "src/main.py")
+///     .edge_to(module)
+///     .finish();
+/// builder.edge(file, module).attr("label", "contains");
+/// assert_eq!(graph.node_count(), 2);
+/// ```
+///
+/// Attribute values are interned, as they are during execution.  Setting an attribute that already
+/// exists replaces its value, and adding an edge that already exists leaves it unchanged.
+pub struct GraphBuilder<'a, 'tree> {
+    graph: &'a mut Graph<'tree>,
+}
+
+impl<'a, 'tree> GraphBuilder<'a, 'tree> {
+    /// Creates a builder that adds to an existing graph.
+    pub fn new(graph: &'a mut Graph<'tree>) -> Self {
+        Self { graph }
+    }
+
+    /// Adds a new graph node, returning a builder for its attributes and edges.
+    pub fn node(&mut self) -> NodeBuilder<'_, 'tree> {
+        let node = self.graph.add_graph_node();
+        NodeBuilder {
+            graph: self.graph,
+            node,
+        }
+    }
+
+    /// Returns a builder for the attributes and edges of a node that is already in the graph.
+    pub fn existing_node(&mut self, node: GraphNodeRef) -> NodeBuilder<'_, 'tree> {
+        NodeBuilder {
+            graph: self.graph,
+            node,
+        }
+    }
+
+    /// Adds an edge between two graph nodes, if it does not already exist, returning a builder for
+    /// its attributes.
+    pub fn edge(&mut self, source: GraphNodeRef, sink: GraphNodeRef) -> EdgeBuilder<'_, 'tree> {
+        let _ = self.graph.node_mut(source).add_edge(sink);
+        EdgeBuilder {
+            graph: self.graph,
+            source,
+            sink,
+        }
+    }
+
+    /// Adds a syntax node to the graph, returning a reference that can be used as an attribute
+    /// value.
+    pub fn syntax_node(&mut self, node: Node<'tree>) -> SyntaxNodeRef {
+        self.graph.add_syntax_node(node)
+    }
+
+    /// Returns the graph being built.
+    pub fn graph(&self) -> &Graph<'tree> {
+        self.graph
+    }
+}
+
+/// Sets the attributes and adds the edges of a graph node.  See [`GraphBuilder::node`].
+pub struct NodeBuilder<'a, 'tree> {
+    graph: &'a mut Graph<'tree>,
+    node: GraphNodeRef,
+}
+
+impl<'a, 'tree> NodeBuilder<'a, 'tree> {
+    /// Sets an attribute of the node.
+    pub fn attr<V: Into<Value>>(self, name: &str, value: V) -> Self {
+        let value = self.graph.intern(value.into());
+        let _ = self
+            .graph
+            .node_mut(self.node)
+            .attributes()
+            .add(name.into(), value);
+        self
+    }
+
+    /// Places the node in a group.  See [`Graph::set_node_group`].
+    pub fn group(self, group: &str) -> Self {
+        self.graph.set_node_group(self.node, group);
+        self
+    }
+
+    /// Adds an edge from the node to another node.
+    pub fn edge_to(self, sink: GraphNodeRef) -> Self {
+        let _ = self.graph.node_mut(self.node).add_edge(sink);
+        self
+    }
+
+    /// Adds an edge from another node to the node.
+    pub fn edge_from(self, source: GraphNodeRef) -> Self {
+        let _ = self.graph.node_mut(source).add_edge(self.node);
+        self
+    }
+
+    /// Returns a reference to the node.
+    pub fn finish(self) -> GraphNodeRef {
+        self.node
+    }
+}
+
+/// Sets the attributes of an edge.  See [`GraphBuilder::edge`].
+pub struct EdgeBuilder<'a, 'tree> {
+    graph: &'a mut Graph<'tree>,
+    source: GraphNodeRef,
+    sink: GraphNodeRef,
+}
+
+impl<'a, 'tree> EdgeBuilder<'a, 'tree> {
+    /// Sets an attribute of the edge.
+    pub fn attr<V: Into<Value>>(self, name: &str, value: V) -> Self {
+        let value = self.graph.intern(value.into());
+        let mut node = self.graph.node_mut(self.source);
+        let mut edge = node
+            .get_edge_mut(self.sink)
+            .expect("edge was added by the builder");
+        let _ = edge.attributes().add(name.into(), value);
+        self
+    }
+}
@@ -0,0 +1,248 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs as [Cypher](https://opencypher.org) statements
+
+use std::fmt;
+
+use super::Graph;
+use super::GraphNodeRef;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+/// Options that control how a graph is rendered by [`Graph::to_cypher`].  By default, every node
+/// is created with the `Node` label and an `id` property containing its index, and every edge is
+/// created with the `EDGE` type.
+#[derive(Clone, Debug)]
+pub struct CypherOptions {
+    node_label: String,
+    edge_type: String,
+    identity_attrs: Vec<Identifier>,
+}
+
+impl Default for CypherOptions {
+    fn default() -> Self {
+        Self {
+            node_label: "Node".to_string(),
+            edge_type: "EDGE".to_string(),
+            identity_attrs: Vec::new(),
+        }
+    }
+}
+
+impl CypherOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the label that is given to every node.
+    pub fn node_label(mut self, label: &str) -> Self {
+        self.node_label = label.to_string();
+        self
+    }
+
+    /// Sets the relationship type that is given to every edge.
+    pub fn edge_type(mut self, edge_type: &str) -> Self {
+        self.edge_type = edge_type.to_string();
+        self
+    }
+
+    /// Identifies nodes by the values of the given attributes instead of by their index.  Nodes
+    /// are then written with `MERGE` instead of `CREATE`, so that loading several graphs into the
+    /// same database reuses the nodes they have in common.  Every node should have all of the
+    /// identity attributes; missing ones are left out of the node's identity.
+    pub fn identity_attrs(mut self, names: &[&str]) -> Self {
+        self.identity_attrs = names.iter().map(|name| Identifier::from(*name)).collect();
+        self
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as a sequence of [Cypher](https://opencypher.org)
+    /// statements, one per line, which create all of its nodes and then all of its edges.  Null
+    /// attribute values are omitted, and graph node references are written as node indices.
+    pub fn to_cypher<'a>(&'a self, options: &'a CypherOptions) -> impl fmt::Display + 'a {
+        struct DisplayCypher<'a, 'tree>(&'a Graph<'tree>, &'a CypherOptions);
+
+        impl<'a, 'tree> fmt::Display for DisplayCypher<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                let options = self.1;
+                let label = Name(&options.node_label);
+                let merge = !options.identity_attrs.is_empty();
+
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    if merge {
+                        write!(f, "MERGE (n:{} ", label)?;
+                        write_identity(f, options, node_index, node.attributes())?;
+                        write!(f, ")")?;
+                        if node
+                            .attributes()
+                            .iter()
+                            .any(|(name, value)| !is_identity(options, name) && !value.is_null())
+                        {
+                            write!(f, " SET n += ")?;
+                            write_properties(f, None, node.attributes(), |name| {
+                                !is_identity(options, name)
+                            })?;
+                        }
+                        writeln!(f, ";")?;
+                    } else {
+                        write!(f, "CREATE (n:{} ", label)?;
+                        write_properties(f, Some(node_index), node.attributes(), |_| true)?;
+                        writeln!(f, ");")?;
+                    }
+                }
+
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    for (sink, edge) in node.iter_edges() {
+                        let sink = sink.index();
+                        write!(f, "MATCH (a:{} ", label)?;
+                        write_identity(f, options, node_index, node.attributes())?;
+                        write!(f, "), (b:{} ", label)?;
+                        write_identity(
+                            f,
+                            options,
+                            sink,
+                            graph.node(GraphNodeRef::new(sink)).attributes(),
+                        )?;
+                        write!(f, ") CREATE (a)-[:{}", Name(&options.edge_type))?;
+                        if edge.attributes().iter().any(|(_, value)| !value.is_null()) {
+                            write!(f, " ")?;
+                            write_properties(f, None, edge.attributes(), |_| true)?;
+                        }
+                        writeln!(f, "]->(b);")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        DisplayCypher(self, options)
+    }
+}
+
+fn is_identity(options: &CypherOptions, name: &Identifier) -> bool {
+    options.identity_attrs.iter().any(|attr| attr == name)
+}
+
+/// Writes the property map that identifies a node: either its identity attributes, or its index.
+fn write_identity(
+    f: &mut fmt::Formatter,
+    options: &CypherOptions,
+    node_index: usize,
+    attributes: StoredAttributes,
+) -> fmt::Result {
+    if options.identity_attrs.is_empty() {
+        return write!(f, "{{id: {}}}", node_index);
+    }
+    write!(f, "{{")?;
+    let mut first = true;
+    for name in &options.identity_attrs {
+        let value = match attributes.get(name) {
+            Some(value) if !value.is_null() => value,
+            _ => continue,
+        };
+        if !first {
+            write!(f, ", ")?;
+        }
+        first = false;
+        write!(f, "{}: {}", Name(name.as_str()), Literal(value))?;
+    }
+    write!(f, "}}")
+}
+
+/// Writes a property map containing the non-null attributes that satisfy `include`, preceded by
+/// an `id` property if `node_index` is given.
+fn write_properties(
+    f: &mut fmt::Formatter,
+    node_index: Option<usize>,
+    attributes: StoredAttributes,
+    include: impl Fn(&Identifier) -> bool,
+) -> fmt::Result {
+    let mut values = attributes
+        .iter()
+        .filter(|(name, value)| include(name) && !value.is_null())
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| a.0.cmp(b.0));
+    write!(f, "{{")?;
+    let mut first = true;
+    if let Some(node_index) = node_index {
+        write!(f, "id: {}", node_index)?;
+        first = false;
+    }
+    for (name, value) in values {
+        if !first {
+            write!(f, ", ")?;
+        }
+        first = false;
+        write!(f, "{}: {}", Name(name.as_str()), Literal(value))?;
+    }
+    write!(f, "}}")
+}
+
+/// A label, relationship type, or property key, which is quoted with backticks unless it is a
+/// plain identifier.
+pub(super) struct Name<'a>(pub(super) &'a str);
+
+impl fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut chars = self.0.chars();
+        let plain = matches!(chars.next(), Some(ch) if ch.is_ascii_alphabetic() || ch == '_')
+            && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if plain {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "`{}`", self.0.replace('`', "``"))
+        }
+    }
+}
+
+/// A value written as a Cypher literal.
+struct Literal<'a>(&'a Value);
+
+impl fmt::Display for Literal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::Null => write!(f, "null"),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::String(value) => write_string(f, value),
+            Value::List(values) => write_list(f, values.iter()),
+            Value::Set(values) => write_list(f, values.iter()),
+            Value::GraphNode(node) => write!(f, "{}", node.index()),
+            value => write_string(f, &value.to_string()),
+        }
+    }
+}
+
+fn write_list<'a>(f: &mut fmt::Formatter, values: impl Iterator<Item = &'a Value>) -> fmt::Result {
+    write!(f, "[")?;
+    for (index, value) in values.enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", Literal(value))?;
+    }
+    write!(f, "]")
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "'")?;
+    for ch in value.chars() {
+        match ch {
+            '\'' => write!(f, "\\'")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "'")
+}
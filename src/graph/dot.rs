@@ -0,0 +1,186 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs in the [DOT](https://graphviz.org/doc/info/lang.html) format
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::Graph;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use super::StoredAttributes;
+use crate::Identifier;
+
+/// Options that control how a graph is rendered by [`Graph::to_dot`].  By default, each node is
+/// labeled with its index, and all DOT visual properties are left at their defaults.
+#[derive(Clone, Debug, Default)]
+pub struct DotOptions {
+    shape_attr: Option<Identifier>,
+    color_attr: Option<Identifier>,
+    label_template: Option<String>,
+    cluster_attr: Option<Identifier>,
+}
+
+impl DotOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the value of the given node attribute, when present, as the node's DOT `shape`.
+    pub fn shape_attr(mut self, name: &str) -> Self {
+        self.shape_attr = Some(name.into());
+        self
+    }
+
+    /// Uses the value of the given node attribute, when present, as the node's DOT `color`.
+    pub fn color_attr(mut self, name: &str) -> Self {
+        self.color_attr = Some(name.into());
+        self
+    }
+
+    /// Labels each node using a template, in which every `{name}` placeholder is replaced by the
+    /// value of the node's `name` attribute, or by nothing if the node has no such attribute.  The
+    /// placeholder `{}` is replaced by the node's index.
+    pub fn label_template(mut self, template: &str) -> Self {
+        self.label_template = Some(template.to_string());
+        self
+    }
+
+    /// Groups nodes into DOT clusters by the value of the given node attribute, instead of by
+    /// their [group][`Graph::set_node_group`].  Nodes without the attribute are not placed in any
+    /// cluster.
+    pub fn cluster_attr(mut self, name: &str) -> Self {
+        self.cluster_attr = Some(name.into());
+        self
+    }
+
+    fn label(&self, node_index: usize, attributes: StoredAttributes) -> String {
+        let template = match &self.label_template {
+            Some(template) => template,
+            None => return node_index.to_string(),
+        };
+        let mut label = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            label.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('}') {
+                Some(end) => {
+                    let name = &after[..end];
+                    if name.is_empty() {
+                        label.push_str(&node_index.to_string());
+                    } else if let Some(value) = attributes.get(name) {
+                        label.push_str(&value.to_string());
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    label.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        label.push_str(rest);
+        label
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as a [DOT](https://graphviz.org/doc/info/lang.html)
+    /// digraph, styled according to the given options.
+    pub fn to_dot<'a>(&'a self, options: &'a DotOptions) -> impl fmt::Display + 'a {
+        struct DisplayDot<'a, 'tree>(&'a Graph<'tree>, &'a DotOptions);
+
+        impl<'a, 'tree> fmt::Display for DisplayDot<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                let options = self.1;
+                writeln!(f, "digraph {{")?;
+
+                let mut clusters = BTreeMap::new();
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    let cluster = match &options.cluster_attr {
+                        Some(name) => node.attributes().get(name).map(|value| value.to_string()),
+                        None => graph
+                            .node_group(GraphNodeRef(node_index as GraphNodeID))
+                            .map(|group| group.to_string()),
+                    };
+                    match cluster {
+                        Some(cluster) => clusters
+                            .entry(cluster)
+                            .or_insert_with(Vec::new)
+                            .push(node_index),
+                        None => write_node(f, "  ", options, node_index, node.attributes())?,
+                    }
+                }
+                for (cluster_index, (cluster, node_indices)) in clusters.iter().enumerate() {
+                    writeln!(f, "  subgraph cluster_{} {{", cluster_index)?;
+                    writeln!(f, "    label=\"{}\";", Escaped(cluster))?;
+                    for node_index in node_indices {
+                        let attributes = graph.node(GraphNodeRef::new(*node_index)).attributes();
+                        write_node(f, "    ", options, *node_index, attributes)?;
+                    }
+                    writeln!(f, "  }}")?;
+                }
+
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    for (sink, _) in node.iter_edges() {
+                        writeln!(f, "  n{} -> n{};", node_index, sink.index())?;
+                    }
+                }
+                writeln!(f, "}}")
+            }
+        }
+
+        DisplayDot(self, options)
+    }
+}
+
+fn write_node(
+    f: &mut fmt::Formatter,
+    indent: &str,
+    options: &DotOptions,
+    node_index: usize,
+    attributes: StoredAttributes,
+) -> fmt::Result {
+    let label = options.label(node_index, attributes);
+    write!(
+        f,
+        "{}n{} [label=\"{}\"",
+        indent,
+        node_index,
+        Escaped(&label)
+    )?;
+    for (property, attr) in [
+        ("shape", &options.shape_attr),
+        ("color", &options.color_attr),
+    ] {
+        let value = attr.as_ref().and_then(|name| attributes.get(name));
+        if let Some(value) = value {
+            write!(f, " {}=\"{}\"", property, Escaped(&value.to_string()))?;
+        }
+    }
+    writeln!(f, "];")
+}
+
+/// Escapes the characters of a string that are not allowed in DOT quoted strings.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ch in self.0.chars() {
+            match ch {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '\n' => write!(f, "\\n")?,
+                ch => write!(f, "{}", ch)?,
+            }
+        }
+        Ok(())
+    }
+}
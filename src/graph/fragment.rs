@@ -0,0 +1,186 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Splits a graph into fragments that can be built separately, and joins them back together
+
+use super::merge::remap;
+use super::merge::Mapping;
+use super::Graph;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use super::GraphSnapshot;
+use super::StoredAttributes;
+use super::StoredAttributesMut;
+use super::SyntaxNodeID;
+use super::SyntaxNodeRef;
+use super::Value;
+use crate::execution::error::ExecutionError;
+use crate::Identifier;
+
+/// A graph that was forked from another one, which can be sent to another thread to be built.
+pub(crate) struct GraphFragment<'tree>(pub(crate) Graph<'tree>);
+
+// Send must be implemented for GraphFragment -> Graph -> Node -> ffi::TSTree
+// This is okay because Send and Sync _are_ implemented for Tree, which also holds ffi::TSTree
+unsafe impl Send for GraphFragment<'_> {}
+
+/// The nodes of a graph that the nodes of a fragment ended up as when it was joined into it.
+pub(crate) struct FragmentMapping(Mapping);
+
+impl FragmentMapping {
+    /// Updates the node references in a value from the fragment to refer to the joined nodes.
+    pub(crate) fn remap(&self, value: &Value) -> Value {
+        remap(value, &self.0)
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns a new graph containing the syntax nodes and graph nodes of this one, which share
+    /// their storage until they are modified.  Use [`Graph::join`] to add its contents back to this
+    /// graph.
+    pub(crate) fn fork(&self) -> GraphFragment<'tree> {
+        GraphFragment(Graph {
+            syntax_nodes: self.syntax_nodes.clone(),
+            syntax_node_ids: self.syntax_node_ids.clone(),
+            arenas: self.arenas.clone(),
+            ..Graph::default()
+        })
+    }
+
+    /// Adds the contents of a fragment returned by [`Graph::fork`] to this graph.  `base` is a
+    /// snapshot of this graph taken when the fragment was forked.
+    ///
+    /// The graph nodes that the fragment added are appended to this graph, in the order they were
+    /// added, and node references in their attribute values are updated to match.  Attributes and
+    /// edges that the fragment added to the nodes of `base` are added to the same nodes of this
+    /// graph, which fails if this graph already has them, for example because another fragment
+    /// added them as well.  The provenance and groups of the fragment's contents are copied too.
+    /// Returns where the fragment's nodes ended up.
+    pub(crate) fn join(
+        &mut self,
+        base: &GraphSnapshot<'tree>,
+        fragment: GraphFragment<'tree>,
+    ) -> Result<FragmentMapping, ExecutionError> {
+        let fragment = fragment.0;
+        let syntax_base = base.syntax_nodes.len();
+        let node_base = base.node_count();
+
+        let mut mapping = Mapping {
+            graph_nodes: Vec::with_capacity(fragment.node_count()),
+            syntax_nodes: Vec::with_capacity(fragment.syntax_nodes.len()),
+        };
+        for (index, node) in fragment.syntax_nodes.iter().enumerate() {
+            let node_ref = if index < syntax_base {
+                SyntaxNodeRef {
+                    index: index as SyntaxNodeID,
+                    kind: node.kind(),
+                    position: node.start_position(),
+                }
+            } else {
+                self.add_syntax_node(*node)
+            };
+            mapping.syntax_nodes.push(node_ref);
+        }
+        let first_new_node = self.node_count();
+        for index in 0..fragment.node_count() {
+            let index = if index < node_base {
+                index
+            } else {
+                first_new_node + index - node_base
+            };
+            mapping.graph_nodes.push(GraphNodeRef(index as GraphNodeID));
+        }
+
+        for node in fragment.iter_nodes() {
+            let index = node.index();
+            let node_ref = mapping.graph_nodes[index];
+            if index >= node_base {
+                self.add_graph_node();
+            } else if fragment.arenas.shares_node_with(&base.arenas, index) {
+                continue;
+            }
+            let node = fragment.node(node);
+            let base_node = if index < node_base {
+                Some(base.node(node_ref))
+            } else {
+                None
+            };
+            self.join_attributes(
+                base_node.map(|node| node.attributes()),
+                node.attributes(),
+                &mapping,
+                |name| format!(" {} on graph node ({})", name, node_ref),
+                |graph| graph.node_mut(node_ref).into_attributes(),
+            )?;
+            for (sink, edge) in node.iter_edges() {
+                let sink = mapping.graph_nodes[sink.index()];
+                let base_edge = base_node.and_then(|node| node.get_edge(sink));
+                if base_edge.is_none() && self.node_mut(node_ref).add_edge(sink).is_err() {
+                    return Err(ExecutionError::DuplicateEdge(format!(
+                        "({} -> {})",
+                        node_ref, sink
+                    )));
+                }
+                self.join_attributes(
+                    base_edge.map(|edge| edge.attributes()),
+                    edge.attributes(),
+                    &mapping,
+                    |name| format!(" {} on edge ({} -> {})", name, node_ref, sink),
+                    |graph| {
+                        graph
+                            .node_mut(node_ref)
+                            .into_edge_mut(sink)
+                            .expect("missing joined edge")
+                            .into_attributes()
+                    },
+                )?;
+            }
+        }
+
+        for node in fragment.iter_nodes() {
+            if let Some(group) = fragment.node_group(node) {
+                self.set_node_group(mapping.graph_nodes[node.index()], group);
+            }
+        }
+        let mut provenance = fragment.provenance;
+        let graph_mapping = mapping
+            .graph_nodes
+            .iter()
+            .map(|node| Some(*node))
+            .collect::<Vec<_>>();
+        provenance.remap(&graph_mapping);
+        self.provenance.extend(provenance);
+        self.indices.invalidate();
+        Ok(FragmentMapping(mapping))
+    }
+
+    /// Adds the attributes of a fragment's node or edge that are not in the corresponding node or
+    /// edge of the fragment's base to the node or edge of this graph that `target` returns.
+    fn join_attributes<D, T>(
+        &mut self,
+        base: Option<StoredAttributes>,
+        attributes: StoredAttributes,
+        mapping: &Mapping,
+        describe: D,
+        target: T,
+    ) -> Result<(), ExecutionError>
+    where
+        D: Fn(&Identifier) -> String,
+        T: for<'a> Fn(&'a mut Graph<'tree>) -> StoredAttributesMut<'a>,
+    {
+        for (name, value) in attributes.iter() {
+            if base.and_then(|base| base.get(name)).is_some() {
+                continue;
+            }
+            let value = self.intern(remap(value, mapping));
+            target(self)
+                .add(name.clone(), value)
+                .map_err(|_| ExecutionError::DuplicateAttribute(describe(name)))?;
+        }
+        Ok(())
+    }
+}
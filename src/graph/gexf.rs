@@ -0,0 +1,171 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs in the [GEXF](https://gexf.net) format
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::Graph;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as a [GEXF](https://gexf.net) document.  Every graph
+    /// node and edge attribute is declared as a GEXF attribute with the same name, whose type is
+    /// `integer` or `boolean` if all of its values have that type, and `string` otherwise.  Null
+    /// attribute values are omitted.
+    pub fn to_gexf<'a>(&'a self) -> impl fmt::Display + 'a {
+        struct DisplayGexf<'a, 'tree>(&'a Graph<'tree>);
+
+        impl<'a, 'tree> fmt::Display for DisplayGexf<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                let node_types = attribute_types(graph.graph_nodes().map(|n| n.attributes()));
+                let edge_types = attribute_types(
+                    graph
+                        .graph_nodes()
+                        .flat_map(|n| n.iter_edges().map(|(_, e)| e.attributes())),
+                );
+
+                writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+                writeln!(f, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+                writeln!(f, r#"  <graph defaultedgetype="directed">"#)?;
+                write_attribute_declarations(f, "node", &node_types)?;
+                write_attribute_declarations(f, "edge", &edge_types)?;
+
+                writeln!(f, "    <nodes>")?;
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    write!(f, r#"      <node id="{0}" label="{0}""#, node_index)?;
+                    write_attribute_values(f, "      ", "node", node.attributes())?;
+                }
+                writeln!(f, "    </nodes>")?;
+
+                writeln!(f, "    <edges>")?;
+                let mut edge_index = 0;
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    for (sink, edge) in node.iter_edges() {
+                        write!(
+                            f,
+                            r#"      <edge id="{}" source="{}" target="{}""#,
+                            edge_index,
+                            node_index,
+                            sink.index()
+                        )?;
+                        write_attribute_values(f, "      ", "edge", edge.attributes())?;
+                        edge_index += 1;
+                    }
+                }
+                writeln!(f, "    </edges>")?;
+
+                writeln!(f, "  </graph>")?;
+                writeln!(f, "</gexf>")
+            }
+        }
+
+        DisplayGexf(self)
+    }
+}
+
+/// Determines the GEXF type of each attribute that appears in a collection of attribute sets.
+fn attribute_types<'a>(
+    attribute_sets: impl Iterator<Item = StoredAttributes<'a>>,
+) -> BTreeMap<&'a Identifier, &'static str> {
+    let mut types = BTreeMap::new();
+    for attributes in attribute_sets {
+        for (name, value) in attributes.iter() {
+            let value_type = match value {
+                Value::Null => continue,
+                Value::Boolean(_) => "boolean",
+                Value::Integer(_) => "integer",
+                _ => "string",
+            };
+            types
+                .entry(name)
+                .and_modify(|existing| {
+                    if *existing != value_type {
+                        *existing = "string";
+                    }
+                })
+                .or_insert(value_type);
+        }
+    }
+    types
+}
+
+fn write_attribute_declarations(
+    f: &mut fmt::Formatter,
+    class: &str,
+    types: &BTreeMap<&Identifier, &'static str>,
+) -> fmt::Result {
+    if types.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, r#"    <attributes class="{}">"#, class)?;
+    for (name, value_type) in types {
+        writeln!(
+            f,
+            r#"      <attribute id="{0}" title="{0}" type="{1}"/>"#,
+            Escaped(name.as_str()),
+            value_type
+        )?;
+    }
+    writeln!(f, "    </attributes>")
+}
+
+/// Writes the attribute values of a node or edge, and closes the element that was started for it.
+fn write_attribute_values(
+    f: &mut fmt::Formatter,
+    indent: &str,
+    element: &str,
+    attributes: StoredAttributes,
+) -> fmt::Result {
+    let mut values = attributes
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return writeln!(f, "/>");
+    }
+    values.sort_by(|a, b| a.0.cmp(b.0));
+    writeln!(f, ">")?;
+    writeln!(f, "{}  <attvalues>", indent)?;
+    for (name, value) in values {
+        writeln!(
+            f,
+            r#"{}    <attvalue for="{}" value="{}"/>"#,
+            indent,
+            Escaped(name.as_str()),
+            Escaped(&value.to_string())
+        )?;
+    }
+    writeln!(f, "{}  </attvalues>", indent)?;
+    writeln!(f, "{}</{}>", indent, element)
+}
+
+/// Escapes the characters of a string that are not allowed in XML attribute values.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ch in self.0.chars() {
+            match ch {
+                '&' => write!(f, "&amp;")?,
+                '<' => write!(f, "&lt;")?,
+                '>' => write!(f, "&gt;")?,
+                '"' => write!(f, "&quot;")?,
+                '\'' => write!(f, "&apos;")?,
+                '\n' => write!(f, "&#10;")?,
+                '\r' => write!(f, "&#13;")?,
+                '\t' => write!(f, "&#9;")?,
+                ch => write!(f, "{}", ch)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Groups the nodes of a graph, and extracts the subgraph of a group
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::mutation::remap_value;
+use super::Graph;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+
+/// The groups that the nodes of a graph belong to.
+#[derive(Default)]
+pub(super) struct GroupTable {
+    names: HashSet<Arc<str>>,
+    nodes: HashMap<GraphNodeID, Arc<str>>,
+}
+
+impl GroupTable {
+    fn set(&mut self, node: GraphNodeID, group: &str) {
+        let name = match self.names.get(group) {
+            Some(name) => name.clone(),
+            None => {
+                let name = Arc::<str>::from(group);
+                self.names.insert(name.clone());
+                name
+            }
+        };
+        self.nodes.insert(node, name);
+    }
+
+    /// Renumbers the graph nodes that belong to groups, removing nodes that are mapped to `None`.
+    pub(super) fn remap(&mut self, mapping: &[Option<GraphNodeRef>]) {
+        self.nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter_map(|(node, name)| Some((mapping[node as usize]?.0, name)))
+            .collect();
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Places a graph node in a group, such as the source file or the stanza that it comes from,
+    /// replacing any group it was already in.  Groups are stored alongside the nodes of the graph,
+    /// rather than in their attributes, and are rendered as clusters by [`Graph::to_dot`].
+    pub fn set_node_group(&mut self, node: GraphNodeRef, group: &str) {
+        self.groups.set(node.0, group);
+    }
+
+    /// Removes a graph node from its group, if it is in one.
+    pub fn clear_node_group(&mut self, node: GraphNodeRef) {
+        self.groups.nodes.remove(&node.0);
+    }
+
+    /// Returns the group that a graph node is in, if any.
+    pub fn node_group(&self, node: GraphNodeRef) -> Option<&str> {
+        self.groups.nodes.get(&node.0).map(|name| &**name)
+    }
+
+    /// Returns the names of all groups that contain at least one node, ordered by name.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups
+            .nodes
+            .values()
+            .map(|name| &**name)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+
+    /// Returns the graph nodes in a group, ordered by index.
+    pub fn nodes_in_group(&self, group: &str) -> Vec<GraphNodeRef> {
+        let mut nodes = self
+            .groups
+            .nodes
+            .iter()
+            .filter(|(_, name)| &***name == group)
+            .map(|(node, _)| GraphNodeRef(*node))
+            .collect::<Vec<_>>();
+        nodes.sort();
+        nodes
+    }
+
+    /// Returns a new graph containing the nodes of a group, and the edges between them.  The
+    /// nodes keep their relative order, and graph node references in their attribute values are
+    /// updated to refer to the new graph; references to nodes outside of the group are replaced
+    /// with null.  The new graph contains all syntax nodes of this graph, with the same indices,
+    /// so syntax node references remain valid.  Any provenance recorded for the nodes is copied
+    /// as well.
+    pub fn subgraph(&self, group: &str) -> Graph<'tree> {
+        let mut mapping = vec![None; self.node_count()];
+        let mut subgraph = Graph::new();
+        for node in &self.syntax_nodes {
+            subgraph.add_syntax_node(*node);
+        }
+        for node in self.nodes_in_group(group) {
+            mapping[node.index()] = Some(subgraph.add_graph_node());
+        }
+
+        for (node, new_node) in self.iter_nodes().zip(&mapping) {
+            let new_node = match new_node {
+                Some(new_node) => *new_node,
+                None => continue,
+            };
+            let node = self.node(node);
+            let mut attributes = node.attributes().to_attributes();
+            for (_, value) in attributes.values.iter_mut() {
+                remap_value(value, &mapping);
+            }
+            subgraph.node_mut(new_node).attributes().extend(attributes);
+            for (sink, edge) in node.iter_edges() {
+                let sink = match mapping[sink.index()] {
+                    Some(sink) => sink,
+                    None => continue,
+                };
+                let mut attributes = edge.attributes().to_attributes();
+                for (_, value) in attributes.values.iter_mut() {
+                    remap_value(value, &mapping);
+                }
+                let new_node = subgraph.node_mut(new_node);
+                let mut new_edge = match new_node.add_edge(sink) {
+                    Ok(edge) => edge,
+                    Err(edge) => edge,
+                };
+                new_edge.attributes().extend(attributes);
+            }
+            subgraph.set_node_group(new_node, group);
+        }
+        subgraph.provenance = self.provenance.clone();
+        subgraph.provenance.remap(&mapping);
+        subgraph
+    }
+}
@@ -0,0 +1,76 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs as interactive HTML pages
+
+use std::fmt;
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+
+use super::Graph;
+use super::StoredAttributes;
+
+/// The page that graphs are rendered into.  The graph data replaces the `/*GRAPH_DATA*/` marker.
+const TEMPLATE: &str = include_str!("html_template.html");
+const DATA_MARKER: &str = "/*GRAPH_DATA*/";
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as a self-contained HTML page, which lays out the graph
+    /// with a force-directed layout, shows the attributes of nodes when hovering over them, and
+    /// can highlight the nodes that have an attribute, or an attribute with a particular value.
+    /// The page does not load any external resources.  Each graph node is labeled with the value
+    /// of its `label_attr` attribute, if given and present, and with its index otherwise.
+    pub fn to_html<'a>(&'a self, label_attr: Option<&'a str>) -> impl fmt::Display + 'a {
+        struct DisplayHtml<'a, 'tree>(&'a Graph<'tree>, Option<&'a str>);
+
+        impl<'a, 'tree> fmt::Display for DisplayHtml<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                let mut nodes = Vec::with_capacity(graph.node_count());
+                let mut edges = Vec::new();
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    let label = self
+                        .1
+                        .and_then(|label_attr| node.attributes().get(label_attr))
+                        .map(|label| label.to_string())
+                        .unwrap_or_else(|| node_index.to_string());
+                    nodes.push(json!({
+                        "id": node_index,
+                        "label": label,
+                        "attrs": attributes(node.attributes()),
+                    }));
+                    for (sink, edge) in node.iter_edges() {
+                        edges.push(json!({
+                            "source": node_index,
+                            "sink": sink.index(),
+                            "attrs": attributes(edge.attributes()),
+                        }));
+                    }
+                }
+                let data = json!({ "nodes": nodes, "edges": edges }).to_string();
+                let (head, tail) = TEMPLATE
+                    .split_once(DATA_MARKER)
+                    .expect("HTML template contains data marker");
+                // the data is embedded in a script element, which must not contain `</`
+                write!(f, "{}{}{}", head, data.replace("</", "<\\/"), tail)
+            }
+        }
+
+        DisplayHtml(self, label_attr)
+    }
+}
+
+/// Returns the attributes of a node or edge as a JSON object of their displayed values.
+fn attributes(attributes: StoredAttributes) -> JsonValue {
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| (name.to_string(), JsonValue::String(value.to_string())))
+        .collect::<Map<_, _>>();
+    JsonValue::Object(attributes)
+}
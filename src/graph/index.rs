@@ -0,0 +1,229 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Indexes the nodes of a graph by attribute value, by the syntax nodes they refer to, and by
+//! their incoming edges
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use super::Edge;
+use super::Graph;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use super::SyntaxNodeID;
+use super::SyntaxNodeRef;
+use super::Value;
+use crate::Identifier;
+use crate::Location;
+
+/// A lookup from the values of one graph node attribute to the nodes that have them.  See
+/// [`Graph::index_by_attribute`].
+#[derive(Debug, Default)]
+pub struct AttributeIndex {
+    nodes: HashMap<Value, Vec<GraphNodeRef>>,
+}
+
+impl AttributeIndex {
+    /// Returns the nodes whose attribute has the given value, ordered by index.
+    pub fn get(&self, value: &Value) -> &[GraphNodeRef] {
+        self.nodes.get(value).map_or(&[], |nodes| nodes.as_slice())
+    }
+
+    /// Returns the distinct values of the attribute, and the nodes that have each of them.
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &[GraphNodeRef])> {
+        self.nodes
+            .iter()
+            .map(|(value, nodes)| (value, nodes.as_slice()))
+    }
+}
+
+/// A lookup from syntax nodes to the graph nodes that refer to them.
+type SyntaxNodeIndex = HashMap<SyntaxNodeID, Vec<GraphNodeRef>>;
+
+/// The indices of a graph that have been built since it was last modified.
+#[derive(Default)]
+pub(super) struct GraphIndices {
+    attributes: Mutex<HashMap<Identifier, Arc<AttributeIndex>>>,
+    syntax_nodes: Mutex<Option<Arc<SyntaxNodeIndex>>>,
+    incoming_edges: Mutex<Option<Arc<Vec<Vec<GraphNodeID>>>>>,
+}
+
+impl GraphIndices {
+    /// Discards all indices.  Called whenever a graph node might be modified.
+    pub(super) fn invalidate(&mut self) {
+        let attributes = self.attributes.get_mut().unwrap_or_else(|e| e.into_inner());
+        if !attributes.is_empty() {
+            attributes.clear();
+        }
+        *self
+            .syntax_nodes
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        *self
+            .incoming_edges
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns an index from the values of the given node attribute to the nodes that have them.
+    /// The index is built the first time it is requested, and reused until the graph is next
+    /// modified.
+    pub fn index_by_attribute(&self, name: &Identifier) -> Arc<AttributeIndex> {
+        let mut indices = self
+            .indices
+            .attributes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        indices
+            .entry(name.clone())
+            .or_insert_with(|| {
+                let mut index = AttributeIndex::default();
+                for (node_index, node) in self.graph_nodes().enumerate() {
+                    if let Some(value) = node.attributes().get(name) {
+                        let nodes = index.nodes.entry(value.clone()).or_default();
+                        nodes.push(GraphNodeRef(node_index as u32));
+                    }
+                }
+                Arc::new(index)
+            })
+            .clone()
+    }
+
+    /// Returns the graph nodes that refer to the given syntax node in any of their attributes,
+    /// including inside lists, sets, and maps, ordered by index.  Like attribute indices, the lookup
+    /// from syntax nodes to graph nodes is built when first needed, and reused until the graph
+    /// is next modified.
+    pub fn graph_nodes_for_syntax_node(&self, node: SyntaxNodeRef) -> Vec<GraphNodeRef> {
+        let index = self.syntax_node_index();
+        index.get(&node.index).cloned().unwrap_or_default()
+    }
+
+    /// Returns the graph nodes that refer to any syntax node whose range contains the given
+    /// position, ordered from the innermost syntax node to the outermost.
+    pub fn graph_nodes_at(&self, position: Location) -> Vec<GraphNodeRef> {
+        let point = tree_sitter::Point {
+            row: position.row,
+            column: position.column,
+        };
+        let index = self.syntax_node_index();
+        let mut containing = self
+            .syntax_nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| (id as SyntaxNodeID, node))
+            .filter(|(id, node)| {
+                index.contains_key(id)
+                    && node.start_position() <= point
+                    && point < node.end_position()
+            })
+            .collect::<Vec<_>>();
+        containing.sort_by_key(|(_, node)| (Reverse(node.start_byte()), node.end_byte()));
+        let mut result = Vec::new();
+        for (id, _) in containing {
+            for graph_node in &index[&id] {
+                if !result.contains(graph_node) {
+                    result.push(*graph_node);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns an iterator of all of the incoming edges to a node, and the nodes they come from,
+    /// ordered by the index of the source node.  The lookup from nodes to their incoming edges is
+    /// built when first needed, and reused until the graph is next modified.
+    pub fn incoming_edges(
+        &self,
+        sink: GraphNodeRef,
+    ) -> impl Iterator<Item = (GraphNodeRef, Edge<'_>)> + '_ {
+        let index = self.incoming_edge_index();
+        let count = index.get(sink.index()).map_or(0, |sources| sources.len());
+        (0..count).map(move |i| {
+            let source = GraphNodeRef(index[sink.index()][i]);
+            let edge = self
+                .node(source)
+                .get_edge(sink)
+                .expect("indexed edge exists");
+            (source, edge)
+        })
+    }
+
+    /// Returns the number of incoming edges to a node.
+    pub fn incoming_edge_count(&self, sink: GraphNodeRef) -> usize {
+        let index = self.incoming_edge_index();
+        index.get(sink.index()).map_or(0, |sources| sources.len())
+    }
+
+    fn incoming_edge_index(&self) -> Arc<Vec<Vec<GraphNodeID>>> {
+        let mut index = self
+            .indices
+            .incoming_edges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        index
+            .get_or_insert_with(|| {
+                let mut index = vec![Vec::new(); self.node_count()];
+                for (source, node) in self.graph_nodes().enumerate() {
+                    for (sink, _) in node.iter_edges() {
+                        index[sink.index()].push(source as GraphNodeID);
+                    }
+                }
+                Arc::new(index)
+            })
+            .clone()
+    }
+
+    fn syntax_node_index(&self) -> Arc<SyntaxNodeIndex> {
+        let mut index = self
+            .indices
+            .syntax_nodes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        index
+            .get_or_insert_with(|| {
+                let mut index = HashMap::new();
+                for (node_index, node) in self.graph_nodes().enumerate() {
+                    let node_ref = GraphNodeRef(node_index as u32);
+                    for (_, value) in node.attributes().iter() {
+                        add_syntax_node_references(&mut index, value, node_ref);
+                    }
+                }
+                Arc::new(index)
+            })
+            .clone()
+    }
+}
+
+fn add_syntax_node_references(
+    index: &mut SyntaxNodeIndex,
+    value: &Value,
+    graph_node: GraphNodeRef,
+) {
+    match value {
+        Value::SyntaxNode(node) => {
+            let nodes = index.entry(node.index).or_default();
+            if nodes.last() != Some(&graph_node) {
+                nodes.push(graph_node);
+            }
+        }
+        Value::List(values) => values
+            .iter()
+            .for_each(|value| add_syntax_node_references(index, value, graph_node)),
+        Value::Set(values) => values
+            .iter()
+            .for_each(|value| add_syntax_node_references(index, value, graph_node)),
+        Value::Map(values) => values
+            .values()
+            .for_each(|value| add_syntax_node_references(index, value, graph_node)),
+        _ => {}
+    }
+}
@@ -0,0 +1,97 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Shares the storage of equal string attribute values
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::Graph;
+use super::Value;
+use crate::execution::SessionCaches;
+
+/// The distinct strings that have been interned into a graph.
+#[derive(Default)]
+pub(super) struct StringTable {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringTable {
+    /// Interns a string, sharing the storage of the strings of an execution session, if any.
+    fn intern(&mut self, string: Arc<str>, session: Option<&SessionCaches>) -> Arc<str> {
+        match self.strings.get(&string) {
+            Some(interned) => interned.clone(),
+            None => {
+                let string = match session {
+                    Some(session) => session.intern(string),
+                    None => string,
+                };
+                self.strings.insert(string.clone());
+                string
+            }
+        }
+    }
+
+    fn intern_value(&mut self, value: &mut Value, session: Option<&SessionCaches>) {
+        match value {
+            Value::String(string) => *string = self.intern(string.clone(), session),
+            Value::List(values) => values
+                .iter_mut()
+                .for_each(|value| self.intern_value(value, session)),
+            Value::Set(values) => {
+                *values = std::mem::take(values)
+                    .into_iter()
+                    .map(|mut value| {
+                        self.intern_value(&mut value, session);
+                        value
+                    })
+                    .collect();
+            }
+            Value::Map(values) => values
+                .values_mut()
+                .for_each(|value| self.intern_value(value, session)),
+            _ => {}
+        }
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns a value whose strings, including those inside lists, sets, and maps, share their
+    /// storage with any equal strings that were interned into this graph before.  Execution
+    /// interns every attribute value it adds, so that graphs in which a handful of strings (kinds,
+    /// labels, common names) are repeated across many nodes only store each of them once.  An
+    /// execution with an [execution session][`crate::ExecutionSession`] also shares the storage of
+    /// the strings of the attribute values it adds with those of the other graphs built with it.
+    pub fn intern(&mut self, value: Value) -> Value {
+        self.intern_in_session(value, None)
+    }
+
+    /// Interns a value, like [`Graph::intern`], also sharing the storage of its strings with those
+    /// of the other graphs built with the session whose caches are given, if any.
+    pub(crate) fn intern_in_session(
+        &mut self,
+        mut value: Value,
+        session: Option<&SessionCaches>,
+    ) -> Value {
+        self.strings.intern_value(&mut value, session);
+        value
+    }
+
+    /// Interns the values of all node and edge attributes in this graph.  Use this after adding
+    /// attributes directly, rather than via execution.
+    pub fn intern_attributes(&mut self) {
+        let strings = &mut self.strings;
+        for value in self.arenas.values_mut() {
+            strings.intern_value(value, None);
+        }
+    }
+
+    /// Returns the number of distinct strings that have been interned into this graph.
+    pub fn interned_string_count(&self) -> usize {
+        self.strings.strings.len()
+    }
+}
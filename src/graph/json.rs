@@ -0,0 +1,238 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Decodes graphs from the JSON format that they are serialized in
+
+use std::convert::TryFrom;
+
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+
+use super::binary::intern_kind;
+use super::binary::unresolved_function;
+use super::Attributes;
+use super::DecodeError;
+use super::Graph;
+use super::GraphNodeRef;
+use super::SourcePosition;
+use super::SyntaxNodeRef;
+use super::Value;
+use super::JSON_FORMAT_VERSION;
+use crate::Identifier;
+
+impl<'tree> Graph<'tree> {
+    /// Decodes a graph from a JSON document in the format described by
+    /// [`JSON_SCHEMA`][`super::JSON_SCHEMA`], as written by [`Graph::write_json`].
+    ///
+    /// The decoded graph does not contain any syntax nodes, since they belong to a syntax tree
+    /// that is no longer available.  Syntax node references in attribute values keep their kind
+    /// and location, but cannot be used to look up the syntax nodes they refer to.  Function
+    /// references keep their name, but fail when called.  Attributes are decoded in order of their
+    /// names, rather than in the order they appear in the document.  New nodes and edges can be
+    /// added to the decoded graph as usual.
+    pub fn from_json(json: &str) -> Result<Graph<'tree>, DecodeError> {
+        let document: JsonValue =
+            serde_json::from_str(json).map_err(|e| DecodeError::Invalid(e.to_string()))?;
+        graph(&document)
+    }
+}
+
+fn graph<'tree>(document: &JsonValue) -> Result<Graph<'tree>, DecodeError> {
+    let document = object(document, "graph")?;
+    let version = u32_field(document, "version")?;
+    if version != JSON_FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let nodes = array(field(document, "nodes")?, "nodes")?;
+    let mut graph = Graph::new();
+    for (node_index, node) in nodes.iter().enumerate() {
+        let node = object(node, "node")?;
+        if u32_field(node, "id")? as usize != node_index {
+            return Err(DecodeError::Invalid(format!(
+                "node {} is out of order",
+                node_index
+            )));
+        }
+        let node_ref = graph.add_graph_node();
+        graph
+            .node_mut(node_ref)
+            .attributes()
+            .extend(attributes(field(node, "attrs")?)?);
+        for edge in array(field(node, "edges")?, "edges")? {
+            let edge = object(edge, "edge")?;
+            let sink = u32_field(edge, "sink")?;
+            if sink as usize >= nodes.len() {
+                return Err(DecodeError::Invalid(format!(
+                    "edge to missing node {}",
+                    sink
+                )));
+            }
+            let mut graph_edge = match graph.node_mut(node_ref).add_edge(GraphNodeRef(sink)) {
+                Ok(edge) => edge,
+                Err(edge) => edge,
+            };
+            graph_edge
+                .attributes()
+                .extend(attributes(field(edge, "attrs")?)?);
+        }
+    }
+    Ok(graph)
+}
+
+pub(super) fn field<'a>(
+    object: &'a Map<String, JsonValue>,
+    name: &str,
+) -> Result<&'a JsonValue, DecodeError> {
+    object
+        .get(name)
+        .ok_or_else(|| DecodeError::Invalid(format!("missing field {}", name)))
+}
+
+pub(super) fn object<'a>(
+    value: &'a JsonValue,
+    what: &str,
+) -> Result<&'a Map<String, JsonValue>, DecodeError> {
+    value
+        .as_object()
+        .ok_or_else(|| DecodeError::Invalid(format!("expected {} to be an object", what)))
+}
+
+fn array<'a>(value: &'a JsonValue, what: &str) -> Result<&'a Vec<JsonValue>, DecodeError> {
+    value
+        .as_array()
+        .ok_or_else(|| DecodeError::Invalid(format!("expected {} to be an array", what)))
+}
+
+pub(super) fn u32_field(object: &Map<String, JsonValue>, name: &str) -> Result<u32, DecodeError> {
+    field(object, name)?
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| DecodeError::Invalid(format!("expected {} to be an integer", name)))
+}
+
+pub(super) fn str_field<'a>(
+    object: &'a Map<String, JsonValue>,
+    name: &str,
+) -> Result<&'a str, DecodeError> {
+    field(object, name)?
+        .as_str()
+        .ok_or_else(|| DecodeError::Invalid(format!("expected {} to be a string", name)))
+}
+
+fn attributes(value: &JsonValue) -> Result<Attributes, DecodeError> {
+    let mut attributes = Attributes::new();
+    for (name, value) in object(value, "attrs")? {
+        // JSON objects cannot contain duplicate keys, so this cannot fail
+        let _ = attributes.add(Identifier::from(name.as_str()), self::value(value)?);
+    }
+    Ok(attributes)
+}
+
+pub(super) fn value(value: &JsonValue) -> Result<Value, DecodeError> {
+    let value = object(value, "value")?;
+    Ok(match str_field(value, "type")? {
+        "null" => Value::Null,
+        "bool" => Value::Boolean(
+            field(value, "bool")?
+                .as_bool()
+                .ok_or_else(|| DecodeError::Invalid("expected bool to be a boolean".into()))?,
+        ),
+        "int" => Value::Integer(u32_field(value, "int")?),
+        "string" => Value::String(str_field(value, "string")?.into()),
+        "list" => Value::List(values(value)?.collect::<Result<_, _>>()?),
+        "set" => Value::Set(values(value)?.collect::<Result<_, _>>()?),
+        "map" => Value::Map(
+            object(field(value, "values")?, "values")?
+                .iter()
+                .map(|(key, value)| Ok((Identifier::from(key.as_str()), self::value(value)?)))
+                .collect::<Result<_, DecodeError>>()?,
+        ),
+        "syntaxNode" => Value::SyntaxNode(SyntaxNodeRef {
+            index: u32_field(value, "id")?,
+            kind: intern_kind(str_field(value, "kind")?.to_string()),
+            position: tree_sitter::Point {
+                row: u32_field(value, "row")? as usize,
+                column: u32_field(value, "column")? as usize,
+            },
+        }),
+        "graphNode" => Value::GraphNode(GraphNodeRef(u32_field(value, "id")?)),
+        "function" => Value::Function(unresolved_function(str_field(value, "name")?.to_string())),
+        "span" => Value::Span {
+            start: position(object(field(value, "start")?, "start")?)?,
+            end: position(object(field(value, "end")?, "end")?)?,
+        },
+        other => {
+            return Err(DecodeError::Invalid(format!(
+                "invalid value type {}",
+                other
+            )))
+        }
+    })
+}
+
+fn position(value: &Map<String, JsonValue>) -> Result<SourcePosition, DecodeError> {
+    Ok(SourcePosition {
+        byte: u32_field(value, "byte")? as usize,
+        row: u32_field(value, "row")? as usize,
+        column: u32_field(value, "column")? as usize,
+    })
+}
+
+fn values<'a>(
+    value: &'a Map<String, JsonValue>,
+) -> Result<impl Iterator<Item = Result<Value, DecodeError>> + 'a, DecodeError> {
+    Ok(array(field(value, "values")?, "values")?
+        .iter()
+        .map(self::value))
+}
+
+#[cfg(feature = "serde")]
+mod deserialize {
+    use serde::de::Error;
+    use serde::ser::SerializeMap;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+    use serde_json::Value as JsonValue;
+
+    use super::super::Attributes;
+    use super::super::Edge;
+    use super::super::Graph;
+    use super::super::Value;
+
+    impl<'de, 'tree> Deserialize<'de> for Graph<'tree> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let document = JsonValue::deserialize(deserializer)?;
+            super::graph(&document).map_err(D::Error::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Attributes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let attributes = JsonValue::deserialize(deserializer)?;
+            super::attributes(&attributes).map_err(D::Error::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Value {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let value = JsonValue::deserialize(deserializer)?;
+            super::value(&value).map_err(D::Error::custom)
+        }
+    }
+
+    impl Serialize for Edge<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            // serializing as a map instead of a struct so we don't have to encode a struct name
+            let mut map = serializer.serialize_map(None)?;
+            map.serialize_entry("attrs", &self.attributes())?;
+            map.end()
+        }
+    }
+}
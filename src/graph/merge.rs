@@ -0,0 +1,182 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Merges graphs into each other
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::Graph;
+use super::GraphNodeRef;
+use super::StoredAttributes;
+use super::StoredAttributesMut;
+use super::SyntaxNodeRef;
+use super::Value;
+use crate::Identifier;
+
+/// Determines what happens when two merged nodes or edges have different values for the same
+/// attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConflictPolicy {
+    /// Keep the value of the graph that is merged into
+    KeepExisting,
+    /// Replace the value with the one from the graph that is merged in
+    Overwrite,
+    /// Fail the merge
+    Error,
+}
+
+/// An error that can occur while merging graphs
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("Conflicting values for attribute {0}: {1:?} and {2:?}")]
+    ConflictingAttribute(Identifier, Value, Value),
+}
+
+impl<'tree> Graph<'tree> {
+    /// Merges the nodes and edges of another graph into this one.
+    ///
+    /// Nodes in the other graph that have all of the `identity` attributes are merged with the
+    /// node of this graph that has the same values for them, if there is one.  All other nodes are
+    /// added as new nodes.  (No nodes are merged if `identity` is empty.)  The attributes and edges
+    /// of merged nodes are combined, with `policy` deciding between differing attribute values.
+    /// Graph node and syntax node references in attribute values are updated to refer to the
+    /// merged nodes.  Nodes keep their group in this graph, if they have one, and are otherwise
+    /// placed in their group in the other graph.
+    ///
+    /// Returns the node in this graph that each node of the other graph, by index, ended up as.
+    /// If the merge fails, this graph may already contain some of the other graph's contents.
+    pub fn merge(
+        &mut self,
+        other: &Graph<'tree>,
+        identity: &[Identifier],
+        policy: ConflictPolicy,
+    ) -> Result<Vec<GraphNodeRef>, MergeError> {
+        let mut identities = HashMap::new();
+        if !identity.is_empty() {
+            for node_ref in self.iter_nodes() {
+                if let Some(key) = identity_key(self.node(node_ref).attributes(), identity) {
+                    identities.entry(key).or_insert(node_ref);
+                }
+            }
+        }
+
+        let mut mapping = Mapping {
+            graph_nodes: Vec::with_capacity(other.node_count()),
+            syntax_nodes: Vec::with_capacity(other.syntax_nodes.len()),
+        };
+        for node in &other.syntax_nodes {
+            let node_ref = self.add_syntax_node(*node);
+            mapping.syntax_nodes.push(node_ref);
+        }
+
+        for node in other.iter_nodes() {
+            let node_ref = match identity_key(other.node(node).attributes(), identity) {
+                Some(key) if !identity.is_empty() => {
+                    let arenas = &mut self.arenas;
+                    *identities.entry(key).or_insert_with(|| arenas.add_node())
+                }
+                _ => self.add_graph_node(),
+            };
+            mapping.graph_nodes.push(node_ref);
+        }
+
+        for (node, node_ref) in other.iter_nodes().zip(&mapping.graph_nodes) {
+            let node = other.node(node);
+            merge_attributes(
+                self.node_mut(*node_ref).attributes(),
+                node.attributes(),
+                &mapping,
+                policy,
+            )?;
+            for (sink, edge) in node.iter_edges() {
+                let sink = mapping.graph_nodes[sink.index()];
+                let node = self.node_mut(*node_ref);
+                let mut merged = match node.add_edge(sink) {
+                    Ok(edge) => edge,
+                    Err(edge) => edge,
+                };
+                merge_attributes(merged.attributes(), edge.attributes(), &mapping, policy)?;
+            }
+        }
+
+        for (node, node_ref) in other.iter_nodes().zip(&mapping.graph_nodes) {
+            if let (Some(group), None) = (other.node_group(node), self.node_group(*node_ref)) {
+                self.set_node_group(*node_ref, group);
+            }
+        }
+        Ok(mapping.graph_nodes)
+    }
+}
+
+/// The nodes of this graph that the nodes of the other graph ended up as.
+pub(super) struct Mapping {
+    pub(super) graph_nodes: Vec<GraphNodeRef>,
+    pub(super) syntax_nodes: Vec<SyntaxNodeRef>,
+}
+
+/// Returns the values of a node's identity attributes, if it has all of them.
+fn identity_key(attributes: StoredAttributes, identity: &[Identifier]) -> Option<Vec<Value>> {
+    identity
+        .iter()
+        .map(|name| attributes.get(name).cloned())
+        .collect()
+}
+
+fn merge_attributes(
+    mut target: StoredAttributesMut,
+    source: StoredAttributes,
+    mapping: &Mapping,
+    policy: ConflictPolicy,
+) -> Result<(), MergeError> {
+    for (name, value) in source.iter() {
+        let value = remap(value, mapping);
+        match target.get(name) {
+            Some(existing) if *existing == value => {}
+            Some(existing) => match policy {
+                ConflictPolicy::KeepExisting => {}
+                ConflictPolicy::Overwrite => {
+                    let _ = target.add(name.clone(), value);
+                }
+                ConflictPolicy::Error => {
+                    return Err(MergeError::ConflictingAttribute(
+                        name.clone(),
+                        existing.clone(),
+                        value,
+                    ))
+                }
+            },
+            None => {
+                let _ = target.add(name.clone(), value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Updates the node references in a value from the other graph to refer to merged nodes.
+pub(super) fn remap(value: &Value, mapping: &Mapping) -> Value {
+    match value {
+        Value::GraphNode(node) => Value::GraphNode(mapping.graph_nodes[node.index()]),
+        Value::SyntaxNode(node) => Value::SyntaxNode(
+            *mapping
+                .syntax_nodes
+                .get(node.index as usize)
+                .unwrap_or(node),
+        ),
+        Value::List(values) => Value::List(values.iter().map(|v| remap(v, mapping)).collect()),
+        Value::Set(values) => Value::Set(values.iter().map(|v| remap(v, mapping)).collect()),
+        Value::Map(values) => Value::Map(
+            values
+                .iter()
+                .map(|(k, v)| (k.clone(), remap(v, mapping)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
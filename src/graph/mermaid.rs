@@ -0,0 +1,63 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs as [Mermaid](https://mermaid.js.org) flowcharts
+
+use std::fmt;
+
+use super::Graph;
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as a [Mermaid](https://mermaid.js.org) flowchart, which
+    /// can be embedded in Markdown documents.  Each graph node is labeled with the value of its
+    /// `label_attr` attribute, if given and present, and with its index otherwise.
+    pub fn to_mermaid<'a>(&'a self, label_attr: Option<&'a str>) -> impl fmt::Display + 'a {
+        struct DisplayMermaid<'a, 'tree>(&'a Graph<'tree>, Option<&'a str>);
+
+        impl<'a, 'tree> fmt::Display for DisplayMermaid<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                writeln!(f, "flowchart TD")?;
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    let label = self
+                        .1
+                        .and_then(|label_attr| node.attributes().get(label_attr))
+                        .map(|label| label.to_string())
+                        .unwrap_or_else(|| node_index.to_string());
+                    writeln!(f, "  n{}[\"{}\"]", node_index, Escaped(&label))?;
+                }
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    for (sink, _) in node.iter_edges() {
+                        writeln!(f, "  n{} --> n{}", node_index, sink.index())?;
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        DisplayMermaid(self, label_attr)
+    }
+}
+
+/// Escapes the characters of a string that have a special meaning in Mermaid labels.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ch in self.0.chars() {
+            match ch {
+                '"' => write!(f, "#quot;")?,
+                '#' => write!(f, "#35;")?,
+                '<' => write!(f, "#lt;")?,
+                '>' => write!(f, "#gt;")?,
+                '\n' => write!(f, "<br/>")?,
+                ch => write!(f, "{}", ch)?,
+            }
+        }
+        Ok(())
+    }
+}
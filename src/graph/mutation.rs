@@ -0,0 +1,100 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Removes nodes and edges from finished graphs
+
+use std::collections::HashSet;
+
+use super::Attributes;
+use super::Graph;
+use super::GraphNode;
+use super::GraphNodeRef;
+use super::Value;
+
+impl<'tree> Graph<'tree> {
+    /// Removes an edge from the graph, along with any provenance recorded for it, returning the
+    /// attributes of the edge if it existed.
+    pub fn remove_edge(&mut self, source: GraphNodeRef, sink: GraphNodeRef) -> Option<Attributes> {
+        let edge = self.node_mut(source).remove_edge(sink)?;
+        self.provenance.remove_edge(source.0, sink.0);
+        Some(edge)
+    }
+
+    /// Removes the given graph nodes from the graph, along with their incoming and outgoing
+    /// edges.  See [`retain_nodes`][`Graph::retain_nodes`] for how the remaining nodes are
+    /// renumbered.
+    pub fn remove_nodes<I>(&mut self, nodes: I) -> Vec<Option<GraphNodeRef>>
+    where
+        I: IntoIterator<Item = GraphNodeRef>,
+    {
+        let removed = nodes.into_iter().collect::<HashSet<_>>();
+        self.retain_nodes(|node, _| !removed.contains(&node))
+    }
+
+    /// Removes all graph nodes for which the predicate returns `false`, along with their incoming
+    /// and outgoing edges.
+    ///
+    /// The remaining nodes keep their relative order, but are renumbered so that their indices
+    /// stay dense.  This invalidates any [`GraphNodeRef`]s held outside of the graph; the result
+    /// maps the index of each original node to its new reference, or to `None` if it was removed.
+    /// Graph node references in attribute values are updated, and references to removed nodes
+    /// are replaced with null.  Removed nodes are also removed from their groups.
+    pub fn retain_nodes<F>(&mut self, mut keep: F) -> Vec<Option<GraphNodeRef>>
+    where
+        F: FnMut(GraphNodeRef, GraphNode) -> bool,
+    {
+        let mut mapping = Vec::with_capacity(self.node_count());
+        let mut next_index = 0;
+        for node in self.iter_nodes() {
+            if keep(node, self.node(node)) {
+                mapping.push(Some(GraphNodeRef(next_index)));
+                next_index += 1;
+            } else {
+                mapping.push(None);
+            }
+        }
+        if next_index as usize == self.node_count() {
+            return mapping;
+        }
+
+        self.arenas = self.arenas.compact(&mapping);
+        for value in self.arenas.values_mut() {
+            remap_value(value, &mapping);
+        }
+        self.provenance.remap(&mapping);
+        self.groups.remap(&mapping);
+        self.indices.invalidate();
+        mapping
+    }
+}
+
+pub(super) fn remap_value(value: &mut Value, mapping: &[Option<GraphNodeRef>]) {
+    match value {
+        Value::GraphNode(node) => {
+            *value = match mapping[node.index()] {
+                Some(node) => Value::GraphNode(node),
+                None => Value::Null,
+            }
+        }
+        Value::List(values) => values
+            .iter_mut()
+            .for_each(|value| remap_value(value, mapping)),
+        Value::Map(values) => values
+            .values_mut()
+            .for_each(|value| remap_value(value, mapping)),
+        Value::Set(values) => {
+            *values = std::mem::take(values)
+                .into_iter()
+                .map(|mut value| {
+                    remap_value(&mut value, mapping);
+                    value
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
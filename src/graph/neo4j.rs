@@ -0,0 +1,179 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Streams graphs to a [Neo4j](https://neo4j.com) database over the Bolt protocol
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use neo4rs::query;
+use neo4rs::BoltType;
+use tokio::runtime::Runtime;
+
+use super::cypher::Name;
+use super::GraphSink;
+use super::StoredAttributes;
+use super::Value;
+
+/// A [`GraphSink`] that writes nodes and edges to a Neo4j database.  Every node is created with
+/// the `Node` label and an `id` property containing its index, and every edge is created with the
+/// `EDGE` type.  Nodes and edges are sent in batches, and a batch that fails is retried with an
+/// exponential backoff.  Pending batches are only sent when the sink is finished.
+pub struct Neo4jSink {
+    runtime: Runtime,
+    graph: neo4rs::Graph,
+    node_label: String,
+    edge_type: String,
+    batch_size: usize,
+    max_retries: u32,
+    nodes: Vec<BoltType>,
+    edges: Vec<BoltType>,
+}
+
+impl Neo4jSink {
+    /// Connects to the Neo4j database at the given Bolt URI.
+    pub fn connect(uri: &str, user: &str, password: &str) -> Result<Self, neo4rs::Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let graph = runtime.block_on(neo4rs::Graph::new(uri, user, password))?;
+        Ok(Self {
+            runtime,
+            graph,
+            node_label: "Node".to_string(),
+            edge_type: "EDGE".to_string(),
+            batch_size: 1000,
+            max_retries: 3,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        })
+    }
+
+    /// Sets the label that is given to every node.
+    pub fn node_label(mut self, label: &str) -> Self {
+        self.node_label = label.to_string();
+        self
+    }
+
+    /// Sets the relationship type that is given to every edge.
+    pub fn edge_type(mut self, edge_type: &str) -> Self {
+        self.edge_type = edge_type.to_string();
+        self
+    }
+
+    /// Sets the number of nodes or edges that are sent to the database in each query.  Defaults
+    /// to 1000.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the number of times that a failed batch is retried before giving up.  Defaults to 3.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    fn flush_nodes(&mut self) -> Result<(), neo4rs::Error> {
+        if self.nodes.is_empty() {
+            return Ok(());
+        }
+        let statement = format!(
+            "UNWIND $rows AS row CREATE (n:{}) SET n = row",
+            Name(&self.node_label)
+        );
+        let rows = std::mem::take(&mut self.nodes);
+        self.run(&statement, rows)
+    }
+
+    fn flush_edges(&mut self) -> Result<(), neo4rs::Error> {
+        if self.edges.is_empty() {
+            return Ok(());
+        }
+        let statement = format!(
+            "UNWIND $rows AS row MATCH (a:{0} {{id: row.source}}), (b:{0} {{id: row.sink}}) CREATE (a)-[r:{1}]->(b) SET r = row.attrs",
+            Name(&self.node_label),
+            Name(&self.edge_type)
+        );
+        let rows = std::mem::take(&mut self.edges);
+        self.run(&statement, rows)
+    }
+
+    fn run(&self, statement: &str, rows: Vec<BoltType>) -> Result<(), neo4rs::Error> {
+        let mut attempt = 0;
+        loop {
+            let query = query(statement).param("rows", rows.clone());
+            match self.runtime.block_on(self.graph.run(query)) {
+                Ok(()) => return Ok(()),
+                Err(_) if attempt < self.max_retries => {
+                    std::thread::sleep(Duration::from_millis(100 << attempt));
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl GraphSink for Neo4jSink {
+    type Error = neo4rs::Error;
+
+    fn node(&mut self, index: usize, attributes: StoredAttributes) -> Result<(), Self::Error> {
+        let mut properties = properties(attributes);
+        properties.insert("id".to_string(), BoltType::from(index as i64));
+        self.nodes.push(properties.into());
+        if self.nodes.len() >= self.batch_size {
+            self.flush_nodes()?;
+        }
+        Ok(())
+    }
+
+    fn edge(
+        &mut self,
+        source: usize,
+        sink: usize,
+        attributes: StoredAttributes,
+    ) -> Result<(), Self::Error> {
+        // Edges can only be matched up with nodes that have already been written.
+        self.flush_nodes()?;
+        let mut row = HashMap::new();
+        row.insert("source".to_string(), BoltType::from(source as i64));
+        row.insert("sink".to_string(), BoltType::from(sink as i64));
+        row.insert("attrs".to_string(), properties(attributes).into());
+        self.edges.push(row.into());
+        if self.edges.len() >= self.batch_size {
+            self.flush_edges()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.flush_nodes()?;
+        self.flush_edges()
+    }
+}
+
+/// Converts the non-null attributes of a node or edge into Neo4j properties.
+fn properties(attributes: StoredAttributes) -> HashMap<String, BoltType> {
+    attributes
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| (name.to_string(), bolt_value(value)))
+        .collect()
+}
+
+fn bolt_value(value: &Value) -> BoltType {
+    match value {
+        Value::Boolean(value) => BoltType::from(*value),
+        Value::Integer(value) => BoltType::from(*value as i64),
+        Value::String(value) => BoltType::from(&**value),
+        Value::List(values) => values.iter().map(bolt_value).collect::<Vec<_>>().into(),
+        Value::Set(values) => values.iter().map(bolt_value).collect::<Vec<_>>().into(),
+        Value::GraphNode(node) => BoltType::from(node.index() as i64),
+        value => BoltType::from(value.to_string()),
+    }
+}
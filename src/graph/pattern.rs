@@ -0,0 +1,205 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Matches path patterns against graphs
+
+use super::Graph;
+use super::GraphNodeRef;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+/// A set of conditions on the attributes of a graph node or edge.
+#[derive(Clone, Debug, Default)]
+struct AttributeFilter {
+    conditions: Vec<(Identifier, Option<Value>)>,
+}
+
+impl AttributeFilter {
+    fn matches(&self, attributes: StoredAttributes) -> bool {
+        self.conditions
+            .iter()
+            .all(|(name, expected)| match (attributes.get(name), expected) {
+                (Some(_), None) => true,
+                (Some(value), Some(expected)) => value == expected,
+                (None, _) => false,
+            })
+    }
+}
+
+/// A pattern that matches graph nodes, and binds each matching node to a name.
+#[derive(Clone, Debug)]
+pub struct NodePattern {
+    name: String,
+    filter: AttributeFilter,
+}
+
+impl NodePattern {
+    /// Creates a pattern that matches any node, binding it to the given name.  If the same name is
+    /// used for several nodes of a [`PathPattern`], they must all match the same node.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            filter: AttributeFilter::default(),
+        }
+    }
+
+    /// Only matches nodes whose attribute `name` has the given value.
+    pub fn attr<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.filter
+            .conditions
+            .push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Only matches nodes that have an attribute `name`.
+    pub fn has_attr(mut self, name: &str) -> Self {
+        self.filter.conditions.push((name.into(), None));
+        self
+    }
+}
+
+/// A pattern that matches graph edges.
+#[derive(Clone, Debug, Default)]
+pub struct EdgePattern {
+    filter: AttributeFilter,
+}
+
+impl EdgePattern {
+    /// Creates a pattern that matches any edge.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches edges whose attribute `name` has the given value.
+    pub fn attr<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.filter
+            .conditions
+            .push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Only matches edges that have an attribute `name`.
+    pub fn has_attr(mut self, name: &str) -> Self {
+        self.filter.conditions.push((name.into(), None));
+        self
+    }
+}
+
+/// A pattern that matches paths through a graph: a starting node, followed by any number of
+/// steps, each of which follows an outgoing edge to another node.
+///
+/// ```
+/// # use tree_sitter_graph::graph::{EdgePattern, NodePattern, PathPattern};
+/// let pattern = PathPattern::new(NodePattern::new("definition").attr("kind", "definition"))
+///     .then(
+///         EdgePattern::new().attr("label", "contains"),
+///         NodePattern::new("call").attr("kind", "call"),
+///     );
+/// ```
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    start: NodePattern,
+    steps: Vec<(EdgePattern, NodePattern)>,
+}
+
+impl PathPattern {
+    /// Creates a pattern that matches a single node.
+    pub fn new(start: NodePattern) -> Self {
+        Self {
+            start,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Extends the pattern with an edge from the last node of the path to another node.
+    pub fn then(mut self, edge: EdgePattern, node: NodePattern) -> Self {
+        self.steps.push((edge, node));
+        self
+    }
+}
+
+/// A match of a [`PathPattern`], which binds the names of its node patterns to graph nodes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PatternMatch {
+    bindings: Vec<(String, GraphNodeRef)>,
+}
+
+impl PatternMatch {
+    /// Returns the graph node that the node pattern with the given name matched.
+    pub fn get(&self, name: &str) -> Option<GraphNodeRef> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == name)
+            .map(|(_, node)| *node)
+    }
+
+    /// Returns the graph nodes that the node patterns matched, in the order they appear in the
+    /// pattern.
+    pub fn nodes(&self) -> impl Iterator<Item = GraphNodeRef> + '_ {
+        self.bindings.iter().map(|(_, node)| *node)
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns all matches of a path pattern in this graph, ordered by the index of their starting
+    /// node and then by the order in which their edges were added.
+    pub fn find_paths(&self, pattern: &PathPattern) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        let mut bindings = Vec::new();
+        for node in self.iter_nodes() {
+            if self.bind(&pattern.start, node, &mut bindings) {
+                self.find_steps(&pattern.steps, &mut bindings, &mut matches);
+                bindings.pop();
+            }
+        }
+        matches
+    }
+
+    /// Binds a node pattern to a node, if the node matches it, returning whether it did.
+    fn bind(
+        &self,
+        pattern: &NodePattern,
+        node: GraphNodeRef,
+        bindings: &mut Vec<(String, GraphNodeRef)>,
+    ) -> bool {
+        if !pattern.filter.matches(self.node(node).attributes()) {
+            return false;
+        }
+        let bound = bindings.iter().find(|(name, _)| *name == pattern.name);
+        if matches!(bound, Some((_, bound)) if *bound != node) {
+            return false;
+        }
+        bindings.push((pattern.name.clone(), node));
+        true
+    }
+
+    fn find_steps(
+        &self,
+        steps: &[(EdgePattern, NodePattern)],
+        bindings: &mut Vec<(String, GraphNodeRef)>,
+        matches: &mut Vec<PatternMatch>,
+    ) {
+        let ((edge_pattern, node_pattern), rest) = match steps.split_first() {
+            Some(step) => step,
+            None => {
+                let bindings = bindings.clone();
+                matches.push(PatternMatch { bindings });
+                return;
+            }
+        };
+        let source = bindings.last().expect("path has a start node").1;
+        for (sink, edge) in self.node(source).iter_edges() {
+            if edge_pattern.filter.matches(edge.attributes())
+                && self.bind(node_pattern, sink, bindings)
+            {
+                self.find_steps(rest, bindings, matches);
+                bindings.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Records which statements created the nodes, edges, and attributes of a graph
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::Graph;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use crate::Identifier;
+use crate::Location;
+
+/// The statement that created a graph node, edge, or attribute, and the stanza match it was
+/// executed for.  Provenance is only recorded when executing with
+/// [`ExecutionConfig::with_provenance`][`crate::ExecutionConfig::with_provenance`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    /// The location of the stanza in the graph DSL file
+    pub stanza_location: Location,
+    /// The location of the statement in the graph DSL file
+    pub statement_location: Location,
+    /// The location in the source file of the syntax node that the stanza matched
+    pub source_location: Location,
+    /// The kind of the syntax node that the stanza matched
+    pub node_kind: String,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "statement at {} of stanza at {} matching ({}) node at {}",
+            self.statement_location, self.stanza_location, self.node_kind, self.source_location,
+        )
+    }
+}
+
+/// The provenance of the elements of a graph.
+#[derive(Clone, Default)]
+pub(super) struct ProvenanceTable {
+    nodes: HashMap<GraphNodeID, Provenance>,
+    edges: HashMap<(GraphNodeID, GraphNodeID), Provenance>,
+    node_attributes: HashMap<(GraphNodeID, Identifier), Provenance>,
+    edge_attributes: HashMap<(GraphNodeID, GraphNodeID, Identifier), Provenance>,
+}
+
+impl ProvenanceTable {
+    /// Forgets the provenance of an edge and its attributes.
+    pub(super) fn remove_edge(&mut self, source: GraphNodeID, sink: GraphNodeID) {
+        self.edges.remove(&(source, sink));
+        self.edge_attributes
+            .retain(|(s, t, _), _| (*s, *t) != (source, sink));
+    }
+
+    /// Adds the provenance recorded in another table, replacing any that this table has for the
+    /// same elements.
+    pub(super) fn extend(&mut self, other: ProvenanceTable) {
+        self.nodes.extend(other.nodes);
+        self.edges.extend(other.edges);
+        self.node_attributes.extend(other.node_attributes);
+        self.edge_attributes.extend(other.edge_attributes);
+    }
+
+    /// Renumbers the graph nodes that provenance is recorded for, forgetting the provenance of
+    /// nodes that are mapped to `None`, and of their edges and attributes.
+    pub(super) fn remap(&mut self, mapping: &[Option<GraphNodeRef>]) {
+        let remap = |node: GraphNodeID| mapping[node as usize].map(|node| node.0);
+        self.nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter_map(|(node, p)| Some((remap(node)?, p)))
+            .collect();
+        self.edges = std::mem::take(&mut self.edges)
+            .into_iter()
+            .filter_map(|((source, sink), p)| Some(((remap(source)?, remap(sink)?), p)))
+            .collect();
+        self.node_attributes = std::mem::take(&mut self.node_attributes)
+            .into_iter()
+            .filter_map(|((node, name), p)| Some(((remap(node)?, name), p)))
+            .collect();
+        self.edge_attributes = std::mem::take(&mut self.edge_attributes)
+            .into_iter()
+            .filter_map(|((source, sink, name), p)| Some(((remap(source)?, remap(sink)?, name), p)))
+            .collect();
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    pub(crate) fn set_node_provenance(&mut self, node: GraphNodeRef, provenance: Provenance) {
+        self.provenance.nodes.insert(node.0, provenance);
+    }
+
+    pub(crate) fn set_edge_provenance(
+        &mut self,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        provenance: Provenance,
+    ) {
+        self.provenance.edges.insert((source.0, sink.0), provenance);
+    }
+
+    pub(crate) fn set_node_attribute_provenance(
+        &mut self,
+        node: GraphNodeRef,
+        name: Identifier,
+        provenance: Provenance,
+    ) {
+        self.provenance
+            .node_attributes
+            .insert((node.0, name), provenance);
+    }
+
+    pub(crate) fn set_edge_attribute_provenance(
+        &mut self,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        name: Identifier,
+        provenance: Provenance,
+    ) {
+        self.provenance
+            .edge_attributes
+            .insert((source.0, sink.0, name), provenance);
+    }
+
+    /// Returns the statement that created a graph node, if provenance was recorded for it.
+    pub fn node_provenance(&self, node: GraphNodeRef) -> Option<&Provenance> {
+        self.provenance.nodes.get(&node.0)
+    }
+
+    /// Returns the statement that created an edge, if provenance was recorded for it.
+    pub fn edge_provenance(&self, source: GraphNodeRef, sink: GraphNodeRef) -> Option<&Provenance> {
+        self.provenance.edges.get(&(source.0, sink.0))
+    }
+
+    /// Returns the statement that set an attribute of a graph node, if provenance was recorded
+    /// for it.
+    pub fn node_attribute_provenance(
+        &self,
+        node: GraphNodeRef,
+        name: &Identifier,
+    ) -> Option<&Provenance> {
+        self.provenance.node_attributes.get(&(node.0, name.clone()))
+    }
+
+    /// Returns the statement that set an attribute of an edge, if provenance was recorded for it.
+    pub fn edge_attribute_provenance(
+        &self,
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        name: &Identifier,
+    ) -> Option<&Provenance> {
+        self.provenance
+            .edge_attributes
+            .get(&(source.0, sink.0, name.clone()))
+    }
+}
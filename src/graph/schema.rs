@@ -0,0 +1,250 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Validates graphs against a declared schema
+
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use super::Graph;
+use super::GraphNodeRef;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+/// The attributes that a graph node or edge must or may have.  Attributes that are not declared
+/// are not allowed.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeSchema {
+    attributes: HashMap<Identifier, bool>,
+}
+
+impl AttributeSchema {
+    /// Creates a schema that does not allow any attributes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares an attribute that must be present.
+    pub fn required(mut self, name: &str) -> Self {
+        self.attributes.insert(name.into(), true);
+        self
+    }
+
+    /// Declares an attribute that may be present.
+    pub fn optional(mut self, name: &str) -> Self {
+        self.attributes.insert(name.into(), false);
+        self
+    }
+}
+
+/// A description of the nodes and edges that a graph is expected to contain.  Use
+/// [`Graph::validate`] to check a graph against it.
+///
+/// Nodes can be divided into kinds by the value of a _kind attribute_, with different attributes
+/// declared for each kind.  Nodes without the kind attribute, or all nodes if there is no kind
+/// attribute, are checked against the schema given to [`nodes`][`GraphSchema::nodes`].  Nodes or
+/// edges that there is no schema for are not checked.
+#[derive(Clone, Debug, Default)]
+pub struct GraphSchema {
+    kind_attr: Option<Identifier>,
+    kinds: HashMap<Value, AttributeSchema>,
+    nodes: Option<AttributeSchema>,
+    edges: Option<AttributeSchema>,
+    location_attr: Option<Identifier>,
+    variable_name_attr: Option<Identifier>,
+}
+
+impl GraphSchema {
+    /// Creates a schema that does not check anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the attribute whose value determines the kind of a node.
+    pub fn kind_attr(mut self, name: &str) -> Self {
+        self.kind_attr = Some(name.into());
+        self
+    }
+
+    /// Declares a node kind and its attributes.  Once any kinds are declared, nodes whose kind
+    /// attribute has a different value are not allowed.
+    pub fn node_kind<V: Into<Value>>(mut self, kind: V, attributes: AttributeSchema) -> Self {
+        self.kinds.insert(kind.into(), attributes);
+        self
+    }
+
+    /// Declares the attributes of nodes that do not have a kind.
+    pub fn nodes(mut self, attributes: AttributeSchema) -> Self {
+        self.nodes = Some(attributes);
+        self
+    }
+
+    /// Declares the attributes of edges.
+    pub fn edges(mut self, attributes: AttributeSchema) -> Self {
+        self.edges = Some(attributes);
+        self
+    }
+
+    /// Allows the debug attributes that execution adds when configured with
+    /// [`ExecutionConfig::debug_attributes`][`crate::ExecutionConfig::debug_attributes`] on all
+    /// nodes and edges, and uses them to report the statements that created the offending nodes
+    /// and edges.
+    pub fn debug_attributes(mut self, location_attr: &str, variable_name_attr: &str) -> Self {
+        self.location_attr = Some(location_attr.into());
+        self.variable_name_attr = Some(variable_name_attr.into());
+        self
+    }
+
+    fn location(&self, attributes: StoredAttributes) -> Option<String> {
+        let location_attr = self.location_attr.as_ref()?;
+        let location = attributes.get(location_attr)?.as_str().ok()?;
+        let variable_name = self
+            .variable_name_attr
+            .as_ref()
+            .and_then(|name| attributes.get(name))
+            .and_then(|name| name.as_str().ok());
+        Some(match variable_name {
+            Some(variable_name) => format!("{} at {}", variable_name, location),
+            None => location.to_string(),
+        })
+    }
+
+    fn check(
+        &self,
+        element: impl Fn() -> SchemaElement,
+        attributes: StoredAttributes,
+        schema: &AttributeSchema,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let mut required = schema
+            .attributes
+            .iter()
+            .filter(|(_, required)| **required)
+            .map(|(name, _)| name)
+            .filter(|name| attributes.get(*name).is_none())
+            .collect::<Vec<_>>();
+        required.sort();
+        for name in required {
+            errors.push(SchemaError::MissingAttribute(element(), name.clone()));
+        }
+        let mut undeclared = attributes
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                !schema.attributes.contains_key(*name)
+                    && Some(*name) != self.kind_attr.as_ref()
+                    && Some(*name) != self.location_attr.as_ref()
+                    && Some(*name) != self.variable_name_attr.as_ref()
+            })
+            .collect::<Vec<_>>();
+        undeclared.sort();
+        for name in undeclared {
+            errors.push(SchemaError::UndeclaredAttribute(element(), name.clone()));
+        }
+    }
+}
+
+/// The graph node or edge that a [`SchemaError`] is about.  The location is the statement that
+/// created the node or edge, if the schema declares debug attributes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SchemaElement {
+    Node {
+        node: GraphNodeRef,
+        location: Option<String>,
+    },
+    Edge {
+        source: GraphNodeRef,
+        sink: GraphNodeRef,
+        location: Option<String>,
+    },
+}
+
+impl fmt::Display for SchemaElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = match self {
+            Self::Node { node, location } => {
+                write!(f, "node {}", node.index())?;
+                location
+            }
+            Self::Edge {
+                source,
+                sink,
+                location,
+            } => {
+                write!(f, "edge {} -> {}", source.index(), sink.index())?;
+                location
+            }
+        };
+        if let Some(location) = location {
+            write!(f, " (created by {})", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// A way in which a graph does not conform to a [`GraphSchema`]
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum SchemaError {
+    #[error("{0} has undeclared kind {1}")]
+    UndeclaredKind(SchemaElement, Value),
+    #[error("{0} is missing required attribute {1}")]
+    MissingAttribute(SchemaElement, Identifier),
+    #[error("{0} has undeclared attribute {1}")]
+    UndeclaredAttribute(SchemaElement, Identifier),
+}
+
+impl<'tree> Graph<'tree> {
+    /// Checks that the nodes and edges of this graph conform to a schema, returning all of the
+    /// ways in which they do not, ordered by node.
+    pub fn validate(&self, schema: &GraphSchema) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        for node_ref in self.iter_nodes() {
+            let node = self.node(node_ref);
+            let element = || SchemaElement::Node {
+                node: node_ref,
+                location: schema.location(node.attributes()),
+            };
+            let kind = schema
+                .kind_attr
+                .as_ref()
+                .and_then(|kind_attr| node.attributes().get(kind_attr));
+            let attributes = match kind {
+                Some(kind) => match schema.kinds.get(kind) {
+                    Some(attributes) => Some(attributes),
+                    None if schema.kinds.is_empty() => schema.nodes.as_ref(),
+                    None => {
+                        errors.push(SchemaError::UndeclaredKind(element(), kind.clone()));
+                        None
+                    }
+                },
+                None => schema.nodes.as_ref(),
+            };
+            if let Some(attributes) = attributes {
+                schema.check(element, node.attributes(), attributes, &mut errors);
+            }
+
+            if let Some(attributes) = &schema.edges {
+                for (sink, edge) in node.iter_edges() {
+                    let element = || SchemaElement::Edge {
+                        source: node_ref,
+                        sink,
+                        location: schema.location(edge.attributes()),
+                    };
+                    schema.check(element, edge.attributes(), attributes, &mut errors);
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,268 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Takes cheap copy-on-write snapshots of graphs
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::ops::IndexMut;
+use std::sync::Arc;
+
+use serde::ser::SerializeMap;
+use serde::Serialize;
+use serde::Serializer;
+use tree_sitter::Node;
+
+use super::arena::Arenas;
+use super::Graph;
+use super::GraphNode;
+use super::GraphNodeID;
+use super::GraphNodeRef;
+use super::SerializeGraphNodes;
+use super::StoredAttributes;
+use super::SyntaxNodeRef;
+use super::JSON_FORMAT_VERSION;
+
+/// The number of elements in each chunk of a [`CowVec`].
+const CHUNK_SIZE: usize = 1024;
+
+/// A vector that is stored in fixed-size chunks, which are shared between clones of the vector
+/// until one of them modifies them.  Cloning the vector only copies a pointer per chunk, and
+/// modifying an element only copies the chunk that contains it.
+#[derive(Clone)]
+pub(super) struct CowVec<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+}
+
+impl<T> Default for CowVec<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> CowVec<T> {
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(super) fn push(&mut self, value: T) {
+        if self.len == self.chunks.len() * CHUNK_SIZE {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_SIZE)));
+        }
+        let chunk = self.chunks.last_mut().expect("chunk was just added");
+        Arc::make_mut(chunk).push(value);
+        self.len += 1;
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    /// Returns an iterator of mutable references to all elements, which copies every chunk that
+    /// is shared with another vector.
+    pub(super) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks
+            .iter_mut()
+            .flat_map(|chunk| Arc::make_mut(chunk).iter_mut())
+    }
+
+    /// Returns whether the chunk containing an element is shared with another vector, which
+    /// means that the element is the same in both.
+    pub(super) fn shares_chunk_with(&self, other: &CowVec<T>, index: usize) -> bool {
+        let chunk = index / CHUNK_SIZE;
+        match (self.chunks.get(chunk), other.chunks.get(chunk)) {
+            (Some(left), Some(right)) => Arc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl<T> Index<usize> for CowVec<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        &self.chunks[index / CHUNK_SIZE][index % CHUNK_SIZE]
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for CowVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        &mut Arc::make_mut(&mut self.chunks[index / CHUNK_SIZE])[index % CHUNK_SIZE]
+    }
+}
+
+impl<T: Clone> FromIterator<T> for CowVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut result = CowVec::default();
+        for value in iter {
+            result.push(value);
+        }
+        result
+    }
+}
+
+impl<T: Clone> IntoIterator for CowVec<T> {
+    type Item = T;
+    type IntoIter =
+        std::iter::FlatMap<std::vec::IntoIter<Arc<Vec<T>>>, Vec<T>, fn(Arc<Vec<T>>) -> Vec<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        fn unwrap_chunk<T: Clone>(chunk: Arc<Vec<T>>) -> Vec<T> {
+            Arc::try_unwrap(chunk).unwrap_or_else(|chunk| (*chunk).clone())
+        }
+        self.chunks
+            .into_iter()
+            .flat_map(unwrap_chunk as fn(Arc<Vec<T>>) -> Vec<T>)
+    }
+}
+
+impl<'a, T: Clone> IntoIterator for &'a CowVec<T> {
+    type Item = &'a T;
+    type IntoIter = Box<dyn Iterator<Item = &'a T> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<'a, T: Clone> IntoIterator for &'a mut CowVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = Box<dyn Iterator<Item = &'a mut T> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter_mut())
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns a read-only snapshot of the nodes, edges, and attributes that this graph contains
+    /// right now.  Taking a snapshot does not copy the graph: the snapshot shares its arenas with
+    /// the graph, and only the parts of the graph that are modified afterwards are copied, a chunk
+    /// of records at a time.  This makes it possible to keep the state of a large graph after each
+    /// step of an execution, for example to compare them while debugging.
+    pub fn snapshot(&self) -> GraphSnapshot<'tree> {
+        GraphSnapshot {
+            syntax_nodes: self.syntax_nodes.clone(),
+            arenas: self.arenas.clone(),
+        }
+    }
+}
+
+/// The contents of a graph at the time [`Graph::snapshot`] was called.  Snapshots do not include
+/// the provenance or groups of the graph's nodes.
+#[derive(Clone)]
+pub struct GraphSnapshot<'tree> {
+    pub(super) syntax_nodes: CowVec<Node<'tree>>,
+    pub(super) arenas: Arenas,
+}
+
+impl<'tree> GraphSnapshot<'tree> {
+    /// Returns an iterator of references to all of the nodes in the snapshot, in the order they
+    /// were added.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = GraphNodeRef> {
+        (0..self.node_count() as GraphNodeID).map(GraphNodeRef)
+    }
+
+    /// Returns the number of nodes in the snapshot.
+    pub fn node_count(&self) -> usize {
+        self.arenas.node_count()
+    }
+
+    /// Returns a graph node of the snapshot, with its attributes and outgoing edges.
+    pub fn node(&self, node: GraphNodeRef) -> GraphNode<'_> {
+        assert!(node.index() < self.node_count(), "no graph node {}", node);
+        GraphNode {
+            arenas: &self.arenas,
+            index: node.0,
+        }
+    }
+
+    /// Returns the nodes that were added, or whose edges or attributes are different, in a later
+    /// snapshot of the same graph, ordered by index.  Nodes that share their storage with this
+    /// snapshot are skipped without being compared.
+    pub fn changed_nodes(&self, later: &GraphSnapshot<'tree>) -> Vec<GraphNodeRef> {
+        let mut changed = Vec::new();
+        for node in later.iter_nodes() {
+            let unchanged = node.index() < self.node_count()
+                && (later.arenas.shares_node_with(&self.arenas, node.index())
+                    || same_contents(self.node(node), later.node(node)));
+            if !unchanged {
+                changed.push(node);
+            }
+        }
+        changed
+    }
+
+    /// Pretty-prints the contents of this snapshot, in the same format as
+    /// [`Graph::pretty_print`].
+    pub fn pretty_print<'a>(&'a self) -> impl fmt::Display + 'a {
+        struct DisplaySnapshot<'a, 'tree>(&'a GraphSnapshot<'tree>);
+
+        impl<'a, 'tree> fmt::Display for DisplaySnapshot<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                for node_ref in self.0.iter_nodes() {
+                    let node = self.0.node(node_ref);
+                    write!(f, "node {}\n{}", node_ref.0, node.attributes())?;
+                    for (sink, edge) in node.iter_edges() {
+                        write!(
+                            f,
+                            "edge {} -> {}\n{}",
+                            node_ref.0,
+                            sink.0,
+                            edge.attributes()
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        DisplaySnapshot(self)
+    }
+}
+
+/// Returns whether two graph nodes have the same attributes and outgoing edges, regardless of the
+/// order in which their attributes were added.
+fn same_contents(left: GraphNode, right: GraphNode) -> bool {
+    left.edge_count() == right.edge_count()
+        && same_attributes(left.attributes(), right.attributes())
+        && left.iter_edges().zip(right.iter_edges()).all(
+            |((left_sink, left_edge), (right_sink, right_edge))| {
+                left_sink == right_sink
+                    && same_attributes(left_edge.attributes(), right_edge.attributes())
+            },
+        )
+}
+
+fn same_attributes(left: StoredAttributes, right: StoredAttributes) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .all(|(name, value)| right.get(name) == Some(value))
+}
+
+impl<'tree> Index<SyntaxNodeRef> for GraphSnapshot<'tree> {
+    type Output = Node<'tree>;
+    fn index(&self, node_ref: SyntaxNodeRef) -> &Node<'tree> {
+        &self.syntax_nodes[node_ref.index as usize]
+    }
+}
+
+impl<'tree> Serialize for GraphSnapshot<'tree> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("version", &JSON_FORMAT_VERSION)?;
+        map.serialize_entry("nodes", &SerializeGraphNodes(&self.arenas))?;
+        map.end()
+    }
+}
@@ -0,0 +1,127 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Stores graphs in [SQLite](https://sqlite.org) databases
+
+use rusqlite::params;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+
+use super::GraphSink;
+use super::StoredAttributes;
+use super::Value;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS nodes (
+        id INTEGER PRIMARY KEY
+    );
+    CREATE TABLE IF NOT EXISTS edges (
+        id INTEGER PRIMARY KEY,
+        source INTEGER NOT NULL REFERENCES nodes(id),
+        sink INTEGER NOT NULL REFERENCES nodes(id)
+    );
+    CREATE TABLE IF NOT EXISTS node_attributes (
+        node INTEGER NOT NULL REFERENCES nodes(id),
+        name TEXT NOT NULL,
+        value
+    );
+    CREATE TABLE IF NOT EXISTS edge_attributes (
+        edge INTEGER NOT NULL REFERENCES edges(id),
+        name TEXT NOT NULL,
+        value
+    );
+    CREATE INDEX IF NOT EXISTS edges_by_source ON edges(source);
+    CREATE INDEX IF NOT EXISTS edges_by_sink ON edges(sink);
+    CREATE INDEX IF NOT EXISTS node_attributes_by_node ON node_attributes(node);
+    CREATE INDEX IF NOT EXISTS node_attributes_by_value ON node_attributes(name, value);
+    CREATE INDEX IF NOT EXISTS edge_attributes_by_edge ON edge_attributes(edge);
+    CREATE INDEX IF NOT EXISTS edge_attributes_by_value ON edge_attributes(name, value);
+";
+
+/// A [`GraphSink`] that stores nodes, edges, and their attributes in a SQLite database, using the
+/// following tables:
+///
+/// - `nodes(id)`, containing the index of each graph node
+/// - `edges(id, source, sink)`, containing each edge and the nodes that it connects
+/// - `node_attributes(node, name, value)` and `edge_attributes(edge, name, value)`, containing
+///   the non-null attributes of each node and edge
+///
+/// Integer and boolean attribute values are stored as SQLite integers, and all other values as
+/// text.  The attribute tables are indexed by name and value.  The whole graph is written in a
+/// single transaction, which is committed when the sink is finished.
+pub struct SqliteSink {
+    connection: Connection,
+}
+
+impl SqliteSink {
+    /// Creates a sink that writes to the given database, creating the graph tables if they do
+    /// not exist yet.
+    pub fn new(connection: Connection) -> Result<Self, rusqlite::Error> {
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch("BEGIN")?;
+        Ok(Self { connection })
+    }
+
+    /// Returns the database that this sink writes to.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Returns the database that this sink writes to, consuming the sink.
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+}
+
+impl GraphSink for SqliteSink {
+    type Error = rusqlite::Error;
+
+    fn node(&mut self, index: usize, attributes: StoredAttributes) -> Result<(), Self::Error> {
+        self.connection
+            .prepare_cached("INSERT INTO nodes (id) VALUES (?1)")?
+            .execute(params![index as i64])?;
+        let mut insert = self.connection.prepare_cached(
+            "INSERT INTO node_attributes (node, name, value) VALUES (?1, ?2, ?3)",
+        )?;
+        for (name, value) in attributes.iter().filter(|(_, value)| !value.is_null()) {
+            insert.execute(params![index as i64, name.as_str(), sql_value(value)])?;
+        }
+        Ok(())
+    }
+
+    fn edge(
+        &mut self,
+        source: usize,
+        sink: usize,
+        attributes: StoredAttributes,
+    ) -> Result<(), Self::Error> {
+        self.connection
+            .prepare_cached("INSERT INTO edges (source, sink) VALUES (?1, ?2)")?
+            .execute(params![source as i64, sink as i64])?;
+        let edge = self.connection.last_insert_rowid();
+        let mut insert = self.connection.prepare_cached(
+            "INSERT INTO edge_attributes (edge, name, value) VALUES (?1, ?2, ?3)",
+        )?;
+        for (name, value) in attributes.iter().filter(|(_, value)| !value.is_null()) {
+            insert.execute(params![edge, name.as_str(), sql_value(value)])?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        self.connection.execute_batch("COMMIT")
+    }
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Boolean(value) => SqlValue::Integer(*value as i64),
+        Value::Integer(value) => SqlValue::Integer(*value as i64),
+        Value::String(value) => SqlValue::Text(value.to_string()),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
@@ -0,0 +1,67 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Derives node identifiers from the contents of nodes
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::Graph;
+use super::GraphNodeRef;
+use crate::functions::stdlib::hash::stable_id;
+use crate::Identifier;
+
+/// An error that can occur while adding stable node identifiers to a graph
+#[derive(Debug, Error)]
+pub enum StableIdError {
+    #[error("Nodes {0} and {1} have the same stable id {2}")]
+    DuplicateId(GraphNodeRef, GraphNodeRef, String),
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns an identifier for a node that is derived from the values of its `identity`
+    /// attributes, instead of the order in which nodes were added, or `None` if the node does not
+    /// have all of them.  Nodes with the same identity attribute values get the same identifier
+    /// across executions, even if other nodes are added or removed, as long as the values do not
+    /// refer to graph nodes.
+    ///
+    /// The identifier is the same as the result of calling the
+    /// [`stable-id`][`crate::reference::functions#stable-id`] function with the attribute values,
+    /// in the order of `identity`.
+    pub fn stable_node_id(&self, node: GraphNodeRef, identity: &[Identifier]) -> Option<String> {
+        let attributes = self.node(node).attributes();
+        let values = identity
+            .iter()
+            .map(|name| attributes.get(name))
+            .collect::<Option<Vec<_>>>()?;
+        Some(stable_id(self, values))
+    }
+
+    /// Sets the `id_attr` attribute of every node that has all of the `identity` attributes to
+    /// the node's [stable identifier][`Graph::stable_node_id`], so that it is included when the
+    /// graph is serialized or exported.  Fails if two nodes would get the same identifier, in
+    /// which case no attributes are set.
+    pub fn add_stable_node_ids(
+        &mut self,
+        identity: &[Identifier],
+        id_attr: Identifier,
+    ) -> Result<(), StableIdError> {
+        let mut ids = HashMap::new();
+        for node in self.iter_nodes() {
+            if let Some(id) = self.stable_node_id(node, identity) {
+                if let Some(previous) = ids.insert(id.clone(), node) {
+                    return Err(StableIdError::DuplicateId(previous, node, id));
+                }
+            }
+        }
+        for (id, node) in ids {
+            let _ = self.node_mut(node).attributes().add(id_attr.clone(), id);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Collects statistics about the contents of a graph
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use super::Graph;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+/// Statistics about the contents of a graph.  See [`Graph::stats`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GraphStats {
+    /// The number of graph nodes
+    pub node_count: usize,
+    /// The number of edges
+    pub edge_count: usize,
+    /// The number of syntax nodes that the graph refers to
+    pub syntax_node_count: usize,
+    /// The number of attributes on all nodes
+    pub node_attribute_count: usize,
+    /// The number of attributes on all edges
+    pub edge_attribute_count: usize,
+    /// The number of nodes with each number of outgoing edges
+    pub out_degrees: BTreeMap<usize, usize>,
+    /// The number of nodes with each number of incoming edges
+    pub in_degrees: BTreeMap<usize, usize>,
+    /// The number of nodes that have each attribute
+    pub node_attribute_names: BTreeMap<Identifier, usize>,
+    /// The number of edges that have each attribute
+    pub edge_attribute_names: BTreeMap<Identifier, usize>,
+    /// A rough estimate of the memory used by the graph's nodes, edges, and attribute values, in
+    /// bytes.  It includes the arena records of removed edges and attributes, but does not account
+    /// for allocator overhead or unused capacity.
+    pub estimated_bytes: usize,
+}
+
+impl<'tree> Graph<'tree> {
+    /// Returns statistics about the contents of this graph.
+    pub fn stats(&self) -> GraphStats {
+        let mut stats = GraphStats {
+            node_count: self.node_count(),
+            syntax_node_count: self.syntax_nodes.len(),
+            estimated_bytes: self.arenas.record_bytes(),
+            ..GraphStats::default()
+        };
+        let mut in_degrees = vec![0usize; self.node_count()];
+        for node in self.graph_nodes() {
+            *stats.out_degrees.entry(node.edge_count()).or_default() += 1;
+            stats.node_attribute_count += count_attributes(
+                node.attributes(),
+                &mut stats.node_attribute_names,
+                &mut stats.estimated_bytes,
+            );
+            for (sink, edge) in node.iter_edges() {
+                stats.edge_count += 1;
+                in_degrees[sink.index()] += 1;
+                stats.edge_attribute_count += count_attributes(
+                    edge.attributes(),
+                    &mut stats.edge_attribute_names,
+                    &mut stats.estimated_bytes,
+                );
+            }
+        }
+        for in_degree in in_degrees {
+            *stats.in_degrees.entry(in_degree).or_default() += 1;
+        }
+        stats
+    }
+}
+
+/// Counts the attributes in an attribute set, adding them to the attribute name frequencies and
+/// the memory estimate, and returns the number of attributes.
+fn count_attributes(
+    attributes: StoredAttributes,
+    names: &mut BTreeMap<Identifier, usize>,
+    estimated_bytes: &mut usize,
+) -> usize {
+    let mut count = 0;
+    for (name, value) in attributes.iter() {
+        count += 1;
+        *names.entry(name.clone()).or_default() += 1;
+        *estimated_bytes += heap_size(value);
+    }
+    count
+}
+
+/// Returns the number of bytes that a value stores outside of itself.
+fn heap_size(value: &Value) -> usize {
+    match value {
+        Value::String(string) => string.len(),
+        Value::List(values) => values
+            .iter()
+            .map(|value| size_of::<Value>() + heap_size(value))
+            .sum(),
+        Value::Set(values) => values
+            .iter()
+            .map(|value| size_of::<Value>() + heap_size(value))
+            .sum(),
+        Value::Map(values) => values
+            .values()
+            .map(|value| size_of::<(Identifier, Value)>() + heap_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,283 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Records traces of executions, and replays the graph mutations in them
+//!
+//! A trace is encoded as newline-delimited JSON, with one object per event, whose `event` field
+//! says which kind of event it is.  Locations are objects with zero-based `row` and `column`
+//! fields, graph nodes are their indices, and values are encoded like attribute values in the
+//! JSON format of graphs.
+
+use std::io::Write;
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+
+use super::json::field;
+use super::json::object;
+use super::json::str_field;
+use super::json::u32_field;
+use super::json::value;
+use super::DecodeError;
+use super::Graph;
+use super::GraphNodeRef;
+use super::Value;
+use crate::execution::GraphElement;
+use crate::Identifier;
+use crate::Location;
+
+/// An event of an execution, which a [`Trace`] records
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// The matches of the stanza at the given location are about to be executed
+    StanzaStarted { stanza: Location },
+    /// A stanza is about to be executed for a match, whose syntax node has the given kind and
+    /// location in the source file
+    Matched {
+        stanza: Location,
+        node_kind: String,
+        source_location: Location,
+    },
+    /// A statement is about to be executed
+    Statement {
+        location: Location,
+        statement: String,
+    },
+    /// Lazy evaluation computed the value of the variable that the statement at the given
+    /// location defined
+    ThunkForced { statement: Location, value: Value },
+    /// A graph node was created
+    NodeCreated(GraphNodeRef),
+    /// An edge was created
+    EdgeCreated(GraphNodeRef, GraphNodeRef),
+    /// An attribute of a graph node or edge was set
+    AttributeSet {
+        element: GraphElement,
+        name: Identifier,
+        value: Value,
+    },
+    /// The execution failed, or recorded an error and continued
+    Error(String),
+}
+
+/// The events of an execution, in the order they happened, which can be written to a file, read
+/// back, and replayed to build the graph again without the source file, its syntax tree, or the
+/// graph DSL file.  Record a trace with a
+/// [`TraceRecorder`][`crate::TraceRecorder`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// Writes this trace as newline-delimited JSON, with one line per event.
+    pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, &encode(event))?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a trace that was written with [`Trace::write`].
+    pub fn read(ndjson: &str) -> Result<Trace, DecodeError> {
+        let events = ndjson
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let event: JsonValue =
+                    serde_json::from_str(line).map_err(|e| DecodeError::Invalid(e.to_string()))?;
+                decode(object(&event, "event")?)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Trace { events })
+    }
+
+    /// Builds the graph that the traced execution built, by replaying the graph nodes, edges,
+    /// and attributes that it added, in order.  Replaying part of a trace builds the graph as it
+    /// was at that point of the execution.
+    ///
+    /// Like graphs decoded with [`Graph::from_json`], the graph does not contain any syntax
+    /// nodes.  The debug attributes that
+    /// [`ExecutionConfig::debug_attributes`][`crate::ExecutionConfig::debug_attributes`] adds are
+    /// not traced, so the graph does not have them.
+    pub fn replay<'tree>(&self) -> Result<Graph<'tree>, DecodeError> {
+        let mut graph = Graph::new();
+        let check = |graph: &Graph, node: GraphNodeRef| {
+            if node.index() < graph.node_count() {
+                Ok(node)
+            } else {
+                Err(DecodeError::Invalid(format!("missing node {}", node)))
+            }
+        };
+        for event in &self.events {
+            match event {
+                TraceEvent::NodeCreated(node) => {
+                    let added = graph.add_graph_node();
+                    if added != *node {
+                        return Err(DecodeError::Invalid(format!(
+                            "node {} created out of order",
+                            node
+                        )));
+                    }
+                }
+                TraceEvent::EdgeCreated(source, sink) => {
+                    let source = check(&graph, *source)?;
+                    let sink = check(&graph, *sink)?;
+                    // an execution fails if it creates an edge twice, so the edge is replayed once
+                    let _ = graph.node_mut(source).add_edge(sink);
+                }
+                TraceEvent::AttributeSet {
+                    element,
+                    name,
+                    value,
+                } => {
+                    let value = graph.intern(value.clone());
+                    let mut attributes = match element {
+                        GraphElement::Node(node) => {
+                            let node = check(&graph, *node)?;
+                            graph.node_mut(node).into_attributes()
+                        }
+                        GraphElement::Edge(source, sink) => {
+                            let source = check(&graph, *source)?;
+                            match graph.node_mut(source).into_edge_mut(*sink) {
+                                Some(edge) => edge.into_attributes(),
+                                None => {
+                                    return Err(DecodeError::Invalid(format!(
+                                        "missing edge ({} -> {})",
+                                        source, sink
+                                    )))
+                                }
+                            }
+                        }
+                    };
+                    // an execution replaces the value of a duplicate attribute before failing
+                    let _ = attributes.add(name.clone(), value);
+                }
+                _ => {}
+            }
+        }
+        Ok(graph)
+    }
+}
+
+fn encode(event: &TraceEvent) -> JsonValue {
+    let location = |location: &Location| json!({ "row": location.row, "column": location.column });
+    match event {
+        TraceEvent::StanzaStarted { stanza } => json!({
+            "event": "stanzaStarted",
+            "stanza": location(stanza),
+        }),
+        TraceEvent::Matched {
+            stanza,
+            node_kind,
+            source_location,
+        } => json!({
+            "event": "matched",
+            "stanza": location(stanza),
+            "nodeKind": node_kind,
+            "sourceLocation": location(source_location),
+        }),
+        TraceEvent::Statement {
+            location: statement_location,
+            statement,
+        } => json!({
+            "event": "statement",
+            "location": location(statement_location),
+            "statement": statement,
+        }),
+        TraceEvent::ThunkForced { statement, value } => json!({
+            "event": "thunkForced",
+            "statement": location(statement),
+            "value": value,
+        }),
+        TraceEvent::NodeCreated(node) => json!({
+            "event": "nodeCreated",
+            "node": node.index(),
+        }),
+        TraceEvent::EdgeCreated(source, sink) => json!({
+            "event": "edgeCreated",
+            "source": source.index(),
+            "sink": sink.index(),
+        }),
+        TraceEvent::AttributeSet {
+            element: GraphElement::Node(node),
+            name,
+            value,
+        } => json!({
+            "event": "nodeAttributeSet",
+            "node": node.index(),
+            "name": name.as_str(),
+            "value": value,
+        }),
+        TraceEvent::AttributeSet {
+            element: GraphElement::Edge(source, sink),
+            name,
+            value,
+        } => json!({
+            "event": "edgeAttributeSet",
+            "source": source.index(),
+            "sink": sink.index(),
+            "name": name.as_str(),
+            "value": value,
+        }),
+        TraceEvent::Error(error) => json!({
+            "event": "error",
+            "error": error,
+        }),
+    }
+}
+
+fn decode(event: &Map<String, JsonValue>) -> Result<TraceEvent, DecodeError> {
+    let location = |name: &str| -> Result<Location, DecodeError> {
+        let location = object(field(event, name)?, name)?;
+        Ok(Location {
+            row: u32_field(location, "row")? as usize,
+            column: u32_field(location, "column")? as usize,
+        })
+    };
+    let node = |name: &str| Ok(GraphNodeRef(u32_field(event, name)?));
+    let name = || Ok(Identifier::from(str_field(event, "name")?));
+    Ok(match str_field(event, "event")? {
+        "stanzaStarted" => TraceEvent::StanzaStarted {
+            stanza: location("stanza")?,
+        },
+        "matched" => TraceEvent::Matched {
+            stanza: location("stanza")?,
+            node_kind: str_field(event, "nodeKind")?.to_string(),
+            source_location: location("sourceLocation")?,
+        },
+        "statement" => TraceEvent::Statement {
+            location: location("location")?,
+            statement: str_field(event, "statement")?.to_string(),
+        },
+        "thunkForced" => TraceEvent::ThunkForced {
+            statement: location("statement")?,
+            value: value(field(event, "value")?)?,
+        },
+        "nodeCreated" => TraceEvent::NodeCreated(node("node")?),
+        "edgeCreated" => TraceEvent::EdgeCreated(node("source")?, node("sink")?),
+        "nodeAttributeSet" => TraceEvent::AttributeSet {
+            element: GraphElement::Node(node("node")?),
+            name: name()?,
+            value: value(field(event, "value")?)?,
+        },
+        "edgeAttributeSet" => TraceEvent::AttributeSet {
+            element: GraphElement::Edge(node("source")?, node("sink")?),
+            name: name()?,
+            value: value(field(event, "value")?)?,
+        },
+        "error" => TraceEvent::Error(str_field(event, "error")?.to_string()),
+        other => {
+            return Err(DecodeError::Invalid(format!(
+                "invalid trace event {}",
+                other
+            )))
+        }
+    })
+}
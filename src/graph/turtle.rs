@@ -0,0 +1,203 @@
+// -*- coding: utf-8 -*-
+// ------------------------------------------------------------------------------------------------
+// Copyright © 2023, tree-sitter authors.
+// Licensed under either of Apache License, Version 2.0, or MIT license, at your option.
+// Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
+// ------------------------------------------------------------------------------------------------
+
+//! Renders graphs as [RDF Turtle](https://www.w3.org/TR/turtle/) documents
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::Graph;
+use super::StoredAttributes;
+use super::Value;
+use crate::Identifier;
+
+/// Options that control how a graph is rendered by [`Graph::to_turtle`].
+///
+/// Nodes are identified by the IRI `node/INDEX`, relative to the base IRI.  Each attribute is
+/// written as a triple whose predicate is the IRI that the attribute is mapped to, or
+/// `attr/NAME` relative to the base IRI if it is not mapped.  Each edge is written as a triple
+/// whose predicate is the edge predicate, which defaults to `edge` relative to the base IRI.
+#[derive(Clone, Debug)]
+pub struct TurtleOptions {
+    base_iri: String,
+    predicates: HashMap<Identifier, String>,
+    edge_predicate: Option<String>,
+}
+
+impl TurtleOptions {
+    /// Creates options that resolve node and default predicate IRIs against the given base IRI.
+    pub fn new(base_iri: &str) -> Self {
+        Self {
+            base_iri: base_iri.to_string(),
+            predicates: HashMap::new(),
+            edge_predicate: None,
+        }
+    }
+
+    /// Writes the given attribute of nodes and edges using the given predicate IRI.
+    pub fn predicate(mut self, attr: &str, iri: &str) -> Self {
+        self.predicates.insert(attr.into(), iri.to_string());
+        self
+    }
+
+    /// Writes edges using the given predicate IRI.
+    pub fn edge_predicate(mut self, iri: &str) -> Self {
+        self.edge_predicate = Some(iri.to_string());
+        self
+    }
+
+    fn write_predicate(&self, f: &mut fmt::Formatter, name: &Identifier) -> fmt::Result {
+        match self.predicates.get(name) {
+            Some(iri) => write!(f, "<{}>", Iri(iri)),
+            None => write!(f, "<attr/{}>", Iri(name.as_str())),
+        }
+    }
+
+    fn write_edge_predicate(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.edge_predicate {
+            Some(iri) => write!(f, "<{}>", Iri(iri)),
+            None => write!(f, "<edge>"),
+        }
+    }
+}
+
+impl<'tree> Graph<'tree> {
+    /// Renders the contents of this graph as an [RDF Turtle](https://www.w3.org/TR/turtle/)
+    /// document.  Null attribute values are omitted, lists are written as RDF collections, each
+    /// element of a set is written as a separate object, and graph node references are written
+    /// as node IRIs.  Edges that have attributes are additionally described by an RDF
+    /// reification statement `edge/INDEX`, which carries the edge's attributes.
+    pub fn to_turtle<'a>(&'a self, options: &'a TurtleOptions) -> impl fmt::Display + 'a {
+        struct DisplayTurtle<'a, 'tree>(&'a Graph<'tree>, &'a TurtleOptions);
+
+        impl<'a, 'tree> fmt::Display for DisplayTurtle<'a, 'tree> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let graph = self.0;
+                let options = self.1;
+                writeln!(f, "@base <{}> .", Iri(&options.base_iri))?;
+                writeln!(
+                    f,
+                    "@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> ."
+                )?;
+
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    let subject = format!("<node/{}>", node_index);
+                    write_attributes(f, options, &subject, node.attributes())?;
+                }
+
+                let mut edge_index = 0;
+                for (node_index, node) in graph.graph_nodes().enumerate() {
+                    for (sink, edge) in node.iter_edges() {
+                        write!(f, "<node/{}> ", node_index)?;
+                        options.write_edge_predicate(f)?;
+                        writeln!(f, " <node/{}> .", sink.index())?;
+                        if edge.attributes().iter().any(|(_, value)| !value.is_null()) {
+                            let subject = format!("<edge/{}>", edge_index);
+                            write!(
+                                f,
+                                "{} rdf:subject <node/{}> ; rdf:predicate ",
+                                subject, node_index
+                            )?;
+                            options.write_edge_predicate(f)?;
+                            writeln!(f, " ; rdf:object <node/{}> .", sink.index())?;
+                            write_attributes(f, options, &subject, edge.attributes())?;
+                        }
+                        edge_index += 1;
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        DisplayTurtle(self, options)
+    }
+}
+
+fn write_attributes(
+    f: &mut fmt::Formatter,
+    options: &TurtleOptions,
+    subject: &str,
+    attributes: StoredAttributes,
+) -> fmt::Result {
+    let mut values = attributes.iter().collect::<Vec<_>>();
+    values.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in values {
+        let objects = match value {
+            Value::Null => continue,
+            Value::Set(values) if values.is_empty() => continue,
+            Value::Set(values) => values.iter().collect::<Vec<_>>(),
+            value => vec![value],
+        };
+        write!(f, "{} ", subject)?;
+        options.write_predicate(f, name)?;
+        for (index, object) in objects.into_iter().enumerate() {
+            write!(f, "{} {}", if index > 0 { "," } else { "" }, Object(object))?;
+        }
+        writeln!(f, " .")?;
+    }
+    Ok(())
+}
+
+/// A value written as the object of a triple.
+struct Object<'a>(&'a Value);
+
+impl fmt::Display for Object<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::String(value) => write_string(f, value),
+            Value::List(values) => write_collection(f, values.iter()),
+            Value::Set(values) => write_collection(f, values.iter()),
+            Value::GraphNode(node) => write!(f, "<node/{}>", node.index()),
+            value => write_string(f, &value.to_string()),
+        }
+    }
+}
+
+fn write_collection<'a>(
+    f: &mut fmt::Formatter,
+    values: impl Iterator<Item = &'a Value>,
+) -> fmt::Result {
+    write!(f, "(")?;
+    for value in values {
+        write!(f, " {}", Object(value))?;
+    }
+    write!(f, " )")
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in value.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Percent-encodes the characters that are not allowed in Turtle IRI references.
+struct Iri<'a>(&'a str);
+
+impl fmt::Display for Iri<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ch in self.0.chars() {
+            match ch {
+                '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\' | '\0'..=' ' => {
+                    write!(f, "%{:02X}", ch as u32)?
+                }
+                ch => write!(f, "{}", ch)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -37,16 +37,40 @@ pub mod parse_error;
 mod parser;
 mod variables;
 
+pub use checker::CheckWarning;
 pub use execution::error::ExecutionError;
+pub use execution::error::ExecutionWarning;
+pub use execution::AttributeType;
 pub use execution::CancellationError;
 pub use execution::CancellationFlag;
+pub use execution::CancellationToken;
+pub use execution::DryRunStats;
+pub use execution::Engine;
+pub use execution::EngineDifference;
 pub use execution::ExecutionConfig;
+pub use execution::ExecutionObserver;
+pub use execution::ExecutionReport;
+pub use execution::ExecutionSession;
+pub use execution::ExecutionState;
+pub use execution::GraphElement;
+pub use execution::Injection;
+pub use execution::LazyMemoryPolicy;
 pub use execution::Match;
+pub use execution::MatchPredicate;
 pub use execution::NoCancellation;
+pub use execution::OnDemandGraph;
+pub use execution::PreviousExecution;
+pub use execution::Profile;
+pub use execution::ResourceLimits;
+pub use execution::StanzaProfile;
+pub use execution::TraceRecorder;
+pub use execution::ROOT_VARIABLE;
 pub use parser::Location;
 pub use parser::ParseError;
+pub use parser::ReloadStats;
 pub use variables::Globals as Variables;
 pub use variables::Iter as VariableIter;
+pub use variables::Snapshot as VariablesSnapshot;
 pub use variables::VariableError;
 
 use std::borrow::Borrow;
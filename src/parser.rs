@@ -5,6 +5,8 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::iter::Peekable;
 use std::path::Path;
@@ -23,6 +25,7 @@ use tree_sitter::Query;
 use tree_sitter::QueryError;
 
 use crate::ast;
+use crate::execution::MAX_MATCH_LIMIT;
 use crate::parse_error::Excerpt;
 use crate::Identifier;
 
@@ -45,6 +48,227 @@ impl ast::File {
     pub fn parse(&mut self, content: &str) -> Result<(), ParseError> {
         Parser::new(content).parse_into_file(self)
     }
+
+    /// Parses a new version of this graph DSL file, and replaces this file with it.  What was
+    /// compiled for this version, and is unchanged in the new one, is reused instead of being
+    /// compiled again: the queries of the stanzas whose query is the same, the combined query of
+    /// the file if no stanza query changed, the regular expressions of `scan` statements, and the
+    /// identifiers.  A long-running process can then pick up edits to a graph DSL file without
+    /// parsing it from scratch.  The caches of an
+    /// [`ExecutionSession`][`crate::ExecutionSession`] do not depend on the file, so the session
+    /// can be used to execute the new version too.
+    ///
+    /// If the new version cannot be parsed, returns the error, and leaves this file unchanged.
+    pub fn reload(&mut self, source: &str) -> Result<ReloadStats, ParseError> {
+        let mut parser = Parser::new(source);
+        parser.cache = ParseCache::take_from(self);
+        let mut file = ast::File::new(self.language);
+        let result = parser
+            .parse_into_file(&mut file)
+            .and_then(|()| Ok(file.check()?));
+        match result {
+            Ok(()) => {
+                *self = file;
+                Ok(parser.cache.stats)
+            }
+            Err(error) => {
+                parser.cache.give_back(self, file);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// What [`File::reload`][`ast::File::reload`] reused from the previous version of a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReloadStats {
+    /// The number of stanza queries that did not change, and were not compiled again
+    pub reused_queries: usize,
+    /// The number of stanza queries that are new or changed, and were compiled
+    pub compiled_queries: usize,
+    /// Whether the combined query of the file was reused, because no stanza query changed
+    pub reused_file_query: bool,
+}
+
+/// What a parser can use instead of compiling it again, and what it has reused
+#[derive(Default)]
+struct ParseCache {
+    queries: HashMap<String, Vec<Query>>,
+    file_query: Option<(String, Query)>,
+    regexes: HashMap<String, Regex>,
+    identifiers: HashSet<Identifier>,
+    stats: ReloadStats,
+}
+
+impl ParseCache {
+    /// Takes the compiled queries of a file, leaving empty queries in their place, and collects
+    /// its regular expressions and identifiers.
+    fn take_from(file: &mut ast::File) -> ParseCache {
+        let mut cache = ParseCache::default();
+        let mut file_query_source = String::new();
+        for stanza in &mut file.stanzas {
+            file_query_source += &stanza.query_source;
+            file_query_source += "\n";
+            let query = std::mem::replace(&mut stanza.query, empty_query(file.language));
+            cache
+                .queries
+                .entry(stanza.query_source.clone())
+                .or_default()
+                .push(query);
+        }
+        cache.file_query = file.query.take().map(|query| (file_query_source, query));
+        for global in &file.globals {
+            cache.identifier(&global.name);
+        }
+        for shorthand in file.shorthands.iter() {
+            cache.identifier(&shorthand.name);
+            cache.identifier(&shorthand.variable.name);
+            cache.attributes(&shorthand.attributes);
+        }
+        for stanza in &file.stanzas {
+            cache.statements(&stanza.statements);
+        }
+        cache
+    }
+
+    /// Gives the compiled queries that were taken from a file back to it, taking them from the new
+    /// version of the file that could not be parsed, if they were reused for it.
+    fn give_back(mut self, file: &mut ast::File, new_file: ast::File) {
+        for stanza in new_file.stanzas {
+            self.queries
+                .entry(stanza.query_source)
+                .or_default()
+                .push(stanza.query);
+        }
+        if self.file_query.is_none() {
+            self.file_query = new_file.query.map(|query| (String::new(), query));
+        }
+        for stanza in &mut file.stanzas {
+            if let Some(query) = self.take_query(&stanza.query_source) {
+                stanza.query = query;
+            }
+        }
+        file.query = self.file_query.map(|(_, query)| query);
+    }
+
+    fn take_query(&mut self, query_source: &str) -> Option<Query> {
+        self.queries
+            .get_mut(query_source)
+            .and_then(|queries| queries.pop())
+    }
+
+    fn identifier(&mut self, identifier: &Identifier) {
+        self.identifiers.insert(identifier.clone());
+    }
+
+    fn statements(&mut self, statements: &[ast::Statement]) {
+        for statement in statements {
+            match statement {
+                ast::Statement::DeclareImmutable(stmt) => {
+                    self.variable(&stmt.variable);
+                    self.expression(&stmt.value);
+                }
+                ast::Statement::DeclareMutable(stmt) => {
+                    self.variable(&stmt.variable);
+                    self.expression(&stmt.value);
+                }
+                ast::Statement::Assign(stmt) => {
+                    self.variable(&stmt.variable);
+                    self.expression(&stmt.value);
+                }
+                ast::Statement::CreateGraphNode(stmt) => self.variable(&stmt.node),
+                ast::Statement::AddGraphNodeAttribute(stmt) => {
+                    self.expression(&stmt.node);
+                    self.attributes(&stmt.attributes);
+                }
+                ast::Statement::CreateEdge(stmt) => {
+                    self.expression(&stmt.source);
+                    self.expression(&stmt.sink);
+                }
+                ast::Statement::AddEdgeAttribute(stmt) => {
+                    self.expression(&stmt.source);
+                    self.expression(&stmt.sink);
+                    self.attributes(&stmt.attributes);
+                }
+                ast::Statement::Scan(stmt) => {
+                    self.expression(&stmt.value);
+                    for arm in &stmt.arms {
+                        self.regexes
+                            .insert(arm.regex.as_str().to_string(), arm.regex.clone());
+                        self.statements(&arm.statements);
+                    }
+                }
+                ast::Statement::Print(stmt) => stmt.values.iter().for_each(|e| self.expression(e)),
+                ast::Statement::If(stmt) => {
+                    for arm in &stmt.arms {
+                        for condition in &arm.conditions {
+                            match condition {
+                                ast::Condition::Some { value, .. }
+                                | ast::Condition::None { value, .. }
+                                | ast::Condition::Bool { value, .. } => self.expression(value),
+                            }
+                        }
+                        self.statements(&arm.statements);
+                    }
+                }
+                ast::Statement::ForIn(stmt) => {
+                    self.identifier(&stmt.variable.name);
+                    self.expression(&stmt.value);
+                    self.statements(&stmt.statements);
+                }
+            }
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[ast::Attribute]) {
+        for attribute in attributes {
+            self.identifier(&attribute.name);
+            self.expression(&attribute.value);
+        }
+    }
+
+    fn variable(&mut self, variable: &ast::Variable) {
+        match variable {
+            ast::Variable::Scoped(variable) => {
+                self.expression(&variable.scope);
+                self.identifier(&variable.name);
+            }
+            ast::Variable::Unscoped(variable) => self.identifier(&variable.name),
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::ListLiteral(expr) => {
+                expr.elements.iter().for_each(|e| self.expression(e))
+            }
+            ast::Expression::SetLiteral(expr) => {
+                expr.elements.iter().for_each(|e| self.expression(e))
+            }
+            ast::Expression::ListComprehension(expr) => {
+                self.expression(&expr.element);
+                self.identifier(&expr.variable.name);
+                self.expression(&expr.value);
+            }
+            ast::Expression::SetComprehension(expr) => {
+                self.expression(&expr.element);
+                self.identifier(&expr.variable.name);
+                self.expression(&expr.value);
+            }
+            ast::Expression::Capture(expr) => self.identifier(&expr.name),
+            ast::Expression::Variable(variable) => self.variable(variable),
+            ast::Expression::Call(expr) => {
+                self.identifier(&expr.function);
+                expr.parameters.iter().for_each(|e| self.expression(e));
+            }
+            ast::Expression::FunctionRef(expr) => self.identifier(&expr.name),
+            _ => {}
+        }
+    }
+}
+
+fn empty_query(language: Language) -> Query {
+    Query::new(language, "").expect("empty query is valid")
 }
 
 // ----------------------------------------------------------------------------
@@ -61,6 +285,8 @@ pub enum ParseError {
     ExpectedVariable(Location),
     #[error("Expected unscoped variable at {0}")]
     ExpectedUnscopedVariable(Location),
+    #[error("Invalid match limit '{0}' at {1}, expected an integer from 1 to 65536")]
+    InvalidMatchLimit(String, Location),
     #[error("Invalid regular expression /{0}/ at {1}")]
     InvalidRegex(String, Location),
     #[error("Expected integer constant in regex capture at {0}")]
@@ -69,6 +295,8 @@ pub enum ParseError {
     QueryError(#[from] QueryError),
     #[error("Unexpected character '{0}' in {1} at {2}")]
     UnexpectedCharacter(char, &'static str, Location),
+    #[error("Unknown annotation '@{0}' at {1}")]
+    UnknownAnnotation(String, Location),
     #[error("Unexpected end of file at {0}")]
     UnexpectedEOF(Location),
     #[error("Unexpected keyword '{0}' at {1}")]
@@ -108,6 +336,7 @@ impl std::fmt::Display for DisplayParseErrorPretty<'_> {
             ParseError::ExpectedToken(_, location) => *location,
             ParseError::ExpectedVariable(location) => *location,
             ParseError::ExpectedUnscopedVariable(location) => *location,
+            ParseError::InvalidMatchLimit(_, location) => *location,
             ParseError::InvalidRegex(_, location) => *location,
             ParseError::InvalidRegexCapture(location) => *location,
             ParseError::QueryError(err) => Location {
@@ -115,6 +344,7 @@ impl std::fmt::Display for DisplayParseErrorPretty<'_> {
                 column: err.column,
             },
             ParseError::UnexpectedCharacter(_, _, location) => *location,
+            ParseError::UnknownAnnotation(_, location) => *location,
             ParseError::UnexpectedEOF(location) => *location,
             ParseError::UnexpectedKeyword(_, location) => *location,
             ParseError::UnexpectedLiteral(_, location) => *location,
@@ -144,14 +374,14 @@ impl std::fmt::Display for DisplayParseErrorPretty<'_> {
 // Location
 
 /// The location of a graph DSL entity within its file
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Location {
     pub row: usize,
     pub column: usize,
 }
 
 impl Location {
-    fn advance(&mut self, ch: char) {
+    pub(crate) fn advance(&mut self, ch: char) {
         if ch == '\n' {
             self.row += 1;
             self.column = 0;
@@ -196,6 +426,7 @@ struct Parser<'a> {
     offset: usize,
     location: Location,
     query_source: String,
+    cache: ParseCache,
 }
 
 fn is_ident_start(c: char) -> bool {
@@ -216,6 +447,7 @@ impl<'a> Parser<'a> {
             offset: 0,
             location: Location::default(),
             query_source,
+            cache: ParseCache::default(),
         }
     }
 }
@@ -305,8 +537,17 @@ impl<'a> Parser<'a> {
             }
             self.consume_whitespace();
         }
-        // we can unwrap here because all queries have already been parsed before
-        file.query = Some(Query::new(file.language, &self.query_source).unwrap());
+        file.query = Some(match self.cache.file_query.take() {
+            Some((query_source, query)) if query_source == self.query_source => {
+                self.cache.stats.reused_file_query = true;
+                query
+            }
+            file_query => {
+                self.cache.file_query = file_query;
+                // we can unwrap here because all queries have already been parsed before
+                Query::new(file.language, &self.query_source).unwrap()
+            }
+        });
         Ok(())
     }
 
@@ -365,22 +606,78 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_stanza(&mut self, language: Language) -> Result<ast::Stanza, ParseError> {
+        let mut match_limit = None;
+        let mut tags = Vec::new();
+        while self.consume_token("@").is_ok() {
+            let location = self.location;
+            let name = self.parse_name("annotation")?;
+            self.consume_whitespace();
+            self.consume_token("(")?;
+            self.consume_whitespace();
+            match name {
+                "match-limit" => match_limit = Some(self.parse_match_limit()?),
+                "tag" => self.parse_tags(&mut tags)?,
+                _ => return Err(ParseError::UnknownAnnotation(name.into(), location)),
+            }
+            self.consume_whitespace();
+            self.consume_token(")")?;
+            self.consume_whitespace();
+        }
         let start = self.location;
-        let (query, full_match_stanza_capture_index) = self.parse_query(language)?;
+        let (query, query_source, full_match_stanza_capture_index) = self.parse_query(language)?;
         self.consume_whitespace();
-        let statements = self.parse_statements()?;
+        let statements = match self.parse_statements() {
+            Ok(statements) => statements,
+            Err(error) => {
+                // keep the query, in case it is reused, so that it can be given back
+                self.cache
+                    .queries
+                    .entry(query_source)
+                    .or_default()
+                    .push(query);
+                return Err(error);
+            }
+        };
         let end = self.location;
         let range = Range { start, end };
         Ok(ast::Stanza {
             query,
+            query_source,
             statements,
             full_match_stanza_capture_index,
             full_match_file_capture_index: usize::MAX, // set in checker
+            match_limit,
+            tags,
             range,
         })
     }
 
-    fn parse_query(&mut self, language: Language) -> Result<(Query, usize), ParseError> {
+    fn parse_match_limit(&mut self) -> Result<u32, ParseError> {
+        let location = self.location;
+        let start = self.offset;
+        self.consume_while(|ch| ch.is_ascii_digit());
+        let limit = &self.source[start..self.offset];
+        match limit.parse::<u32>() {
+            Ok(value) if (1..=MAX_MATCH_LIMIT).contains(&value) => Ok(value),
+            _ => Err(ParseError::InvalidMatchLimit(limit.into(), location)),
+        }
+    }
+
+    fn parse_tags(&mut self, tags: &mut Vec<String>) -> Result<(), ParseError> {
+        loop {
+            let tag = self.parse_name("tag")?;
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+            self.consume_whitespace();
+            if self.consume_token(",").is_err() {
+                return Ok(());
+            }
+            self.consume_whitespace();
+        }
+    }
+
+    fn parse_query(&mut self, language: Language) -> Result<(Query, String, usize), ParseError> {
         let location = self.location;
         let query_start = self.offset;
         self.skip_query()?;
@@ -390,6 +687,15 @@ impl<'a> Parser<'a> {
         // the global query_source.
         self.query_source += &query_source;
         self.query_source += "\n";
+        if let Some(query) = self.cache.take_query(&query_source) {
+            self.cache.stats.reused_queries += 1;
+            let full_match_capture_index = query
+                .capture_index_for_name(FULL_MATCH)
+                .expect("missing capture index for full match")
+                as usize;
+            return Ok((query, query_source, full_match_capture_index));
+        }
+        self.cache.stats.compiled_queries += 1;
         let query = Query::new(language, &query_source).map_err(|mut e| {
             // the column of the first row of a query pattern must be shifted by the whitespace
             // that was already consumed
@@ -408,7 +714,7 @@ impl<'a> Parser<'a> {
             .capture_index_for_name(FULL_MATCH)
             .expect("missing capture index for full match")
             as usize;
-        Ok((query, full_match_capture_index))
+        Ok((query, query_source, full_match_capture_index))
     }
 
     fn skip_query(&mut self) -> Result<(), ParseError> {
@@ -594,8 +900,16 @@ impl<'a> Parser<'a> {
             while self.peek()? != '}' {
                 let pattern_location = self.location;
                 let pattern = self.parse_string()?;
-                let regex = Regex::new(&pattern)
-                    .map_err(|_| ParseError::InvalidRegex(pattern.into(), pattern_location))?;
+                let regex = match self.cache.regexes.get(&pattern) {
+                    Some(regex) => regex.clone(),
+                    None => {
+                        let regex = Regex::new(&pattern).map_err(|_| {
+                            ParseError::InvalidRegex(pattern.clone(), pattern_location)
+                        })?;
+                        self.cache.regexes.insert(pattern, regex.clone());
+                        regex
+                    }
+                };
                 self.consume_whitespace();
                 let statements = self.parse_statements()?;
                 arms.push(ast::ScanArm {
@@ -731,7 +1045,33 @@ impl<'a> Parser<'a> {
 
     fn parse_identifier(&mut self, within: &'static str) -> Result<Identifier, ParseError> {
         let content = self.parse_name(within)?;
-        Ok(Identifier::from(content))
+        Ok(self.identifier(content))
+    }
+
+    /// Returns an identifier with the given name, sharing it with the other identifiers of the
+    /// same name in the file.
+    fn identifier(&mut self, name: &str) -> Identifier {
+        match self.cache.identifiers.get(name) {
+            Some(identifier) => identifier.clone(),
+            None => {
+                let identifier = Identifier::from(name);
+                self.cache.identifiers.insert(identifier.clone());
+                identifier
+            }
+        }
+    }
+
+    /// Parses a function name, which can be qualified with one or more namespaces, as in
+    /// `ns::name`.
+    fn parse_function_name(&mut self, within: &'static str) -> Result<Identifier, ParseError> {
+        let start = self.offset;
+        self.parse_name(within)?;
+        while self.source[self.offset..].starts_with("::") {
+            self.consume_token("::")?;
+            self.parse_name(within)?;
+        }
+        let end = self.offset;
+        Ok(self.identifier(&self.source[start..end]))
     }
 
     fn parse_string(&mut self) -> Result<String, ParseError> {
@@ -765,14 +1105,20 @@ impl<'a> Parser<'a> {
             '"' => self.parse_string()?.into(),
             '@' => self.parse_capture()?.into(),
             '$' => self.parse_regex_capture()?.into(),
+            '&' => self.parse_function_ref()?.into(),
             '(' => self.parse_call()?,
             '[' => self.parse_list()?,
             '{' => self.parse_set()?,
             ch if ch.is_ascii_digit() => self.parse_integer_constant()?,
             ch if is_ident_start(ch) => {
                 let location = self.location;
-                let name = self.parse_identifier("variable name")?;
-                ast::UnscopedVariable { name, location }.into()
+                let name = self.parse_function_name("variable name")?;
+                if name.as_str().contains("::") {
+                    // only functions can have namespaces, so this must be a function reference
+                    ast::FunctionRef { name, location }.into()
+                } else {
+                    ast::UnscopedVariable { name, location }.into()
+                }
             }
             ch => {
                 return Err(ParseError::UnexpectedCharacter(
@@ -803,7 +1149,7 @@ impl<'a> Parser<'a> {
     fn parse_call(&mut self) -> Result<ast::Expression, ParseError> {
         self.consume_token("(")?;
         self.consume_whitespace();
-        let function = self.parse_identifier("function name")?;
+        let function = self.parse_function_name("function name")?;
         self.consume_whitespace();
         let mut parameters = Vec::new();
         while self.peek()? != ')' {
@@ -818,6 +1164,13 @@ impl<'a> Parser<'a> {
         .into())
     }
 
+    fn parse_function_ref(&mut self) -> Result<ast::FunctionRef, ParseError> {
+        let location = self.location;
+        self.consume_token("&")?;
+        let name = self.parse_function_name("function name")?;
+        Ok(ast::FunctionRef { name, location })
+    }
+
     fn parse_sequence(&mut self, end_marker: char) -> Result<Vec<ast::Expression>, ParseError> {
         let mut elements = Vec::new();
         while self.peek()? != end_marker {
@@ -923,7 +1276,7 @@ impl<'a> Parser<'a> {
         }
         self.consume_while(is_ident);
         let end = self.offset;
-        let name = Identifier::from(&self.source[start + 1..end]);
+        let name = self.identifier(&self.source[start + 1..end]);
         Ok(ast::Capture {
             name,
             quantifier: Zero,                 // set in checker
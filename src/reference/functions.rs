@@ -31,6 +31,101 @@
 //!   - Input parameters: one value
 //!   - Output value: a boolean indicating whether the value is null or not
 //!
+//! ## `is-string`
+//!
+//! Check if a value is a string.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a string or not
+//!
+//! ## `is-int`
+//!
+//! Check if a value is an integer.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is an integer or not
+//!
+//! ## `is-list`
+//!
+//! Check if a value is a list.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a list or not
+//!
+//! ## `is-set`
+//!
+//! Check if a value is a set.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a set or not
+//!
+//! ## `is-map`
+//!
+//! Check if a value is a map.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a map or not
+//!
+//! ## `is-span`
+//!
+//! Check if a value is a span.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a span or not
+//!
+//! ## `is-syntax-node`
+//!
+//! Check if a value is a syntax node.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a syntax node or not
+//!
+//! ## `is-graph-node`
+//!
+//! Check if a value is a graph node.
+//!
+//!   - Input parameters: one value
+//!   - Output value: a boolean indicating whether the value is a graph node or not
+//!
+//! ## `coalesce`
+//!
+//! Returns the first non-null value.
+//!
+//!   - Input parameters: zero or more values
+//!   - Output value: the first input value that is not null, or null if there is none
+//!
+//! Parameters are evaluated from left to right, and evaluation stops at the first non-null
+//! value.  The remaining parameters are never evaluated, so they can refer to values that might
+//! not be defined, and any graph nodes they would create are not created.
+//!
+//! ## `contains`
+//!
+//! Check if a collection contains a value.
+//!
+//!   - Input parameters:
+//!     - `collection`: a list, set, or string
+//!     - `item`: the value to look for
+//!   - Output value: a boolean indicating whether `item` is an element of `collection`
+//!
+//! If `collection` is a string, then `item` must also be a string, and the result indicates
+//! whether `item` occurs as a substring of `collection`.
+//!
+//! ## `env`
+//!
+//! Returns the value of an environment variable.
+//!
+//!   - Input parameters:
+//!     - `name`: a string containing the name of the environment variable
+//!   - Output value: a string containing the value of the environment variable, or `#null` if it
+//!     is not set
+//!
+//! It is an error if the value of the environment variable is not valid Unicode.
+//!
+//! This function is not available by default, since it makes the result of executing a graph DSL
+//! file depend on the environment of the process executing it.  Library users can enable it by
+//! calling [`Functions::add_env`][`crate::functions::Functions::add_env`], and command-line users
+//! by passing `--allow-env`.
+//!
 //! # Graph manipulation functions
 //!
 //! ## `node`
@@ -40,6 +135,81 @@
 //!   - Input parameters: none
 //!   - Output value: a reference to the new graph node
 //!
+//! ## `gensym`
+//!
+//! Generates a unique string.
+//!
+//!   - Input parameters:
+//!     - `prefix`: an optional string, which defaults to `g`
+//!   - Output value: a string consisting of `prefix` followed by a number
+//!
+//! The number starts at zero for each execution, or at the number set with
+//! [`ExecutionConfig::first_symbol`][`crate::ExecutionConfig::first_symbol`], and increases by one
+//! each time `gensym` is called, so the result is different every time within an execution.
+//! Successive executions into the same graph each start from that number again.  The numbers are
+//! assigned in the order in which calls are evaluated, which is reproducible for a given graph DSL
+//! file and source, but can differ between the strict and lazy execution engines.
+//!
+//! ## `out-edges`
+//!
+//! Returns the nodes that a graph node has edges to.
+//!
+//!   - Input parameters:
+//!     - `node`: a graph node
+//!   - Output value: a list of the sinks of the edges whose source is `node`, ordered by node
+//!
+//! ## `in-edges`
+//!
+//! Returns the nodes that have edges to a graph node.
+//!
+//!   - Input parameters:
+//!     - `node`: a graph node
+//!   - Output value: a list of the sources of the edges whose sink is `node`, ordered by node
+//!
+//! ## `neighbors`
+//!
+//! Returns the nodes that are connected to a graph node by an edge in either direction.
+//!
+//!   - Input parameters:
+//!     - `node`: a graph node
+//!   - Output value: a list of the neighbors of `node`, without duplicates, ordered by node
+//!
+//! ## `has-edge`
+//!
+//! Returns whether there is an edge between two graph nodes.
+//!
+//!   - Input parameters:
+//!     - `source`: a graph node
+//!     - `sink`: a graph node
+//!   - Output value: `#true` if there is an edge from `source` to `sink`, `#false` otherwise
+//!
+//! These functions only see edges that exist at the time they are called.  In the strict execution
+//! engine, that is the edges created by statements that have already been executed, in earlier
+//! stanzas or earlier in the same stanza.  When a file uses any of these functions, the lazy
+//! execution engine creates every edge before it evaluates any attribute values or `print`
+//! arguments, so these functions see all of the edges in the graph when used there; when they are
+//! used to compute the endpoints of an edge, they only see the edges that have been created so far.
+//! The lazy engine reports an error if these functions are called from a value that it needs while
+//! matching, such as the condition of an `if` statement or the list of a `for` loop, because the
+//! graph does not have any edges yet at that point.
+//!
+//! ## `get-attr`
+//!
+//! Returns the value of an attribute of a graph node.
+//!
+//!   - Input parameters:
+//!     - `node`: a graph node
+//!     - `name`: a string containing the name of the attribute
+//!   - Output value: the value of the attribute, or `#null` if `node` does not have the attribute
+//!
+//! In the strict execution engine, this sees the attributes added by statements that have already
+//! been executed, in earlier stanzas or earlier in the same stanza.  In the lazy execution engine,
+//! this first evaluates any statements that add the attribute to `node`, wherever they appear, so
+//! the attribute's value is seen regardless of the order of the stanzas.  (A value that depends on
+//! the attribute it is reading sees `#null`.)  Like the functions that read edges, the lazy engine
+//! reports an error if this is called from a value that it needs while matching, such as the
+//! condition of an `if` statement.
+//!
 //! # Logical functions
 //!
 //! ## `not`
@@ -74,6 +244,43 @@
 //!   - Input parameters: zero or more integers
 //!   - Output value: the sum of all of the input integers
 //!
+//! ## `random-int`
+//!
+//! Returns a pseudo-random integer.
+//!
+//!   - Input parameters:
+//!     - `lo`: an integer
+//!     - `hi`: an integer, which must not be less than `lo`
+//!   - Output value: an integer between `lo` and `hi`, inclusive
+//!
+//! The numbers are generated from a seed, which can be set with
+//! [`ExecutionConfig::random_seed`][`crate::ExecutionConfig::random_seed`], and which defaults to
+//! 0.  Each execution starts from the seed, so the results are reproducible for a given graph DSL
+//! file, source, and seed, though they can differ between the strict and lazy execution engines,
+//! and for [parallel][`crate::ExecutionConfig::parallel`] executions, in which each stanza match
+//! has its own sequence of numbers derived from the seed.
+//!
+//! # Time functions
+//!
+//! ## `now-unix`
+//!
+//! Returns the time at which execution started.
+//!
+//!   - Input parameters: none
+//!   - Output value: the number of whole seconds between the Unix epoch and the start of execution
+//!
+//! ## `now-iso8601`
+//!
+//! Returns the time at which execution started.
+//!
+//!   - Input parameters: none
+//!   - Output value: a string containing the start of execution as an ISO 8601 timestamp in UTC,
+//!     such as `2023-05-10T14:03:12Z`
+//!
+//! Both functions return the same time for every call within an execution.  The time can be fixed
+//! using [`ExecutionConfig::fixed_time`][`crate::ExecutionConfig::fixed_time`], which is useful to
+//! produce deterministic graphs in tests.
+//!
 //! # String functions
 //!
 //! ## `format`
@@ -105,6 +312,188 @@
 //! [`Regex::new`]: https://docs.rs/regex/*/regex/struct.Regex.html#method.new
 //! [`Regex::replace_all`]: https://docs.rs/regex/*/regex/struct.Regex.html#method.replace_all
 //!
+//! # Hash functions
+//!
+//! ## `sha256`
+//!
+//! Computes the SHA-256 hash of a string.
+//!
+//!   - Input parameters:
+//!     - `text`: a string
+//!   - Output value: a string containing the hash of the UTF-8 encoding of `text`, as 64 lowercase
+//!     hexadecimal digits
+//!
+//! ## `fnv1a`
+//!
+//! Computes the 64-bit FNV-1a hash of a string.
+//!
+//!   - Input parameters:
+//!     - `text`: a string
+//!   - Output value: a string containing the hash of the UTF-8 encoding of `text`, as 16 lowercase
+//!     hexadecimal digits
+//!
+//! FNV-1a is much faster than SHA-256, but is not a cryptographic hash.
+//!
+//! ## `stable-id`
+//!
+//! Computes a short identifier from a list of values.
+//!
+//!   - Input parameters: zero or more values
+//!   - Output value: a string of 16 lowercase hexadecimal digits
+//!
+//! The identifier only depends on the values of the parameters, so executing the same graph DSL
+//! file on the same source always produces the same identifiers, and different parameters produce
+//! different identifiers with overwhelming probability.  Syntax nodes are identified by their
+//! position in the source and their type, and graph nodes by the order in which they were created.
+//!
+//! # Encoding functions
+//!
+//! ## `base64-encode`
+//!
+//! Encodes a string using base64.
+//!
+//!   - Input parameters:
+//!     - `text`: a string
+//!   - Output value: the base64 encoding of the UTF-8 encoding of `text`, using the standard
+//!     alphabet with padding
+//!
+//! ## `base64-decode`
+//!
+//! Decodes a base64-encoded string.
+//!
+//!   - Input parameters:
+//!     - `encoded`: a string containing base64, using the standard alphabet with padding
+//!   - Output value: the decoded string
+//!
+//! It is an error if `encoded` is not valid base64, or if the decoded bytes are not valid UTF-8.
+//!
+//! ## `json-encode`
+//!
+//! Encodes a value as JSON.
+//!
+//!   - Input parameters:
+//!     - `value`: a null, boolean, integer, string, list, set, or map value
+//!   - Output value: a string containing the JSON encoding of `value`
+//!
+//! Lists and sets are encoded as JSON arrays; the elements of a set are ordered as by
+//! [`sort`](#sort).  Maps are encoded as JSON objects.  It is an error if `value` contains syntax
+//! nodes, graph nodes, or functions.
+//!
+//! ## `json-decode`
+//!
+//! Decodes a JSON string into a value.
+//!
+//!   - Input parameters:
+//!     - `text`: a string containing a JSON value
+//!   - Output value: the decoded value
+//!
+//! JSON arrays are decoded as lists, and JSON objects are decoded as maps, so that decoding the
+//! result of `json-encode` gives back the value that was encoded.  It is an error if the JSON
+//! contains a number that is not a non-negative integer that fits in 32 bits.
+//!
+//! # Path functions
+//!
+//! These functions manipulate paths represented as strings.  They never access the filesystem,
+//! and use the path syntax of the platform that the graph DSL file is executed on.
+//!
+//! ## `path-basename`
+//!
+//! Returns the final component of a path.
+//!
+//!   - Input parameters:
+//!     - `path`: a string
+//!   - Output value: the final component of `path`, or null if `path` ends in `..` or is a root
+//!
+//! ## `path-dirname`
+//!
+//! Returns a path without its final component.
+//!
+//!   - Input parameters:
+//!     - `path`: a string
+//!   - Output value: `path` without its final component, which is the empty string for a relative
+//!     path with only one component, or null if `path` is a root or empty
+//!
+//! ## `path-extension`
+//!
+//! Returns the extension of the final component of a path.
+//!
+//!   - Input parameters:
+//!     - `path`: a string
+//!   - Output value: the extension, without the leading `.`, or null if there is none
+//!
+//! ## `path-join`
+//!
+//! Joins paths together.
+//!
+//!   - Input parameters: zero or more strings
+//!   - Output value: the paths joined with the path separator
+//!
+//! If one of the paths is absolute, it replaces everything that comes before it.
+//!
+//! ## `path-normalize`
+//!
+//! Normalizes a path.
+//!
+//!   - Input parameters:
+//!     - `path`: a string
+//!   - Output value: `path` with `.` components removed, and `..` components removed together with
+//!     the component that precedes them, or `.` if nothing remains
+//!
+//! This is a purely textual operation, which does not resolve symbolic links.  Leading `..`
+//! components of a relative path are kept, and `..` components directly after a root are removed.
+//!
+//! # Process functions
+//!
+//! ## `exec-capture`
+//!
+//! Runs an external command and returns its output.
+//!
+//!   - Input parameters:
+//!     - `command`: a string containing the name or path of the command to run
+//!     - zero or more strings, which are passed to the command as arguments
+//!   - Output value: a string containing the standard output of the command, without any trailing
+//!     newlines
+//!
+//! The command is run directly, and not through a shell, so its arguments are not subject to
+//! expansion or word splitting.  Its standard input is empty.  It is an error if the command
+//! cannot be run, exits unsuccessfully, or produces output that is not valid UTF-8.
+//!
+//! This function is only available if the `exec-capture` cargo feature of the `tree-sitter-graph`
+//! crate is enabled, and it is an error to call it unless running external processes has also been
+//! allowed for the execution with `ExecutionConfig::allow_exec_capture`.
+//!
+//! # Conversion functions
+//!
+//! ## `to-string`
+//!
+//! Converts a value to a string.
+//!
+//!   - Input parameters: one value
+//!   - Output value: the value formatted as a string
+//!
+//! Strings are returned unchanged.  Other values are formatted the same way as by
+//! [`format`](#format).
+//!
+//! ## `to-int`
+//!
+//! Converts a string to an integer.
+//!
+//!   - Input parameters: a string or integer value
+//!   - Output value: the integer represented by the string
+//!
+//! The string must contain a non-negative decimal integer, optionally surrounded by whitespace;
+//! anything else is an error.  Integers are returned unchanged.
+//!
+//! ## `to-bool`
+//!
+//! Converts a string to a boolean.
+//!
+//!   - Input parameters: a string or boolean value
+//!   - Output value: the boolean represented by the string
+//!
+//! The string must be one of `true`, `#true`, `false`, or `#false`, optionally surrounded by
+//! whitespace; anything else is an error.  Booleans are returned unchanged.
+//!
 //! # List functions
 //!
 //! ## `concat`
@@ -139,6 +528,166 @@
 //!   - Input parameters: a list value
 //!   - Output value: an integer indicating the length of the list
 //!
+//! ## `sort`
+//!
+//! Sorts a list of values.
+//!
+//!   - Input parameters: a list value
+//!   - Output value: a new list containing the same values, in ascending order
+//!
+//! The ordering is deterministic, and does not depend on the order in which values were created.
+//! Values of different types are ordered by type: null, booleans, integers, strings, lists, sets,
+//! maps, spans, syntax nodes, graph nodes, and finally function references.  Booleans order
+//! `#false` before `#true`; strings are ordered lexicographically by their UTF-8 bytes; lists and
+//! sets are ordered lexicographically by their elements, and maps by their keys and values.  Spans
+//! are ordered by start byte, then end byte.  Syntax nodes are ordered by their position in the
+//! source: by start byte, then enclosing nodes before the nodes they contain, then by node type.
+//! Graph nodes are ordered by creation, and function references by name.  The sort is stable, so
+//! values that compare equal keep their original order.
+//!
+//! ## `sort-by`
+//!
+//! Sorts a list of syntax nodes or graph nodes by a key.
+//!
+//!   - Input parameters:
+//!     - `list`: a list of syntax nodes or graph nodes
+//!     - `key`: a string naming the key to sort by
+//!   - Output value: a new list containing the same values, in ascending order of their keys
+//!
+//! For graph nodes, `key` is the name of an attribute; nodes without that attribute have a null
+//! key.  For syntax nodes, `key` is one of `start-byte`, `end-byte`, `start-row`, `start-column`,
+//! `end-row`, `end-column`, `node-type`, or `source-text`, and the key is the value that the
+//! function of that name would return.  Keys are compared using the same ordering as
+//! [`sort`](#sort), and the sort is stable.
+//!
+//! ## `map`
+//!
+//! Applies a function to each element of a list.
+//!
+//!   - Input parameters:
+//!     - `list`: a list value
+//!     - `function`: a reference to a function that takes a single parameter, such as
+//!       `&source-text`
+//!   - Output value: a new list containing the result of calling `function` on each element of
+//!     `list`, in order
+//!
+//! ## `filter`
+//!
+//! Selects the elements of a list that satisfy a predicate.
+//!
+//!   - Input parameters:
+//!     - `list`: a list value
+//!     - `function`: a reference to a function that takes a single parameter and returns a boolean
+//!   - Output value: a new list containing the elements of `list` for which `function` returns
+//!     `#true`, in their original order
+//!
+//! ## `flatten`
+//!
+//! Flattens a list of lists by one level.
+//!
+//!   - Input parameters: a list value
+//!   - Output value: a new list containing the elements of each list in the input, in order
+//!
+//! Elements of the input that are not lists are included in the result as they are.  Lists nested
+//! more deeply are not flattened.
+//!
+//! ## `unique`
+//!
+//! Removes duplicate values from a list.
+//!
+//!   - Input parameters: a list value
+//!   - Output value: a new list containing each distinct value of the input once, in the order of
+//!     its first occurrence
+//!
+//! ## `zip`
+//!
+//! Pairs up the elements of two lists.
+//!
+//!   - Input parameters:
+//!     - `left`: a list value
+//!     - `right`: a list value
+//!   - Output value: a new list of two-element lists, where the _i_-th element is a list
+//!     containing the _i_-th elements of `left` and `right`
+//!
+//! If the lists have different lengths, the extra elements of the longer list are ignored.
+//!
+//! # Set functions
+//!
+//! ## `union`
+//!
+//! Computes the union of two sets.
+//!
+//!   - Input parameters:
+//!     - `left`: a set value
+//!     - `right`: a set value
+//!   - Output value: a new set containing the values that are in `left`, `right`, or both
+//!
+//! ## `intersect`
+//!
+//! Computes the intersection of two sets.
+//!
+//!   - Input parameters:
+//!     - `left`: a set value
+//!     - `right`: a set value
+//!   - Output value: a new set containing the values that are in both `left` and `right`
+//!
+//! ## `difference`
+//!
+//! Computes the difference of two sets.
+//!
+//!   - Input parameters:
+//!     - `left`: a set value
+//!     - `right`: a set value
+//!   - Output value: a new set containing the values that are in `left` but not in `right`
+//!
+//! ## `symmetric-difference`
+//!
+//! Computes the symmetric difference of two sets.
+//!
+//!   - Input parameters:
+//!     - `left`: a set value
+//!     - `right`: a set value
+//!   - Output value: a new set containing the values that are in exactly one of `left` and `right`
+//!
+//! # Map functions
+//!
+//! Maps associate string keys with values, so that nested metadata can be stored in a single
+//! attribute.  Their keys are always ordered.
+//!
+//! ## `map-of`
+//!
+//! Creates a map.
+//!
+//!   - Input parameters: zero or more pairs of parameters, each consisting of a string key
+//!     followed by its value
+//!   - Output value: a new map containing the given keys and values
+//!
+//! If a key appears more than once, the last value for it is used.  It is an error if the last
+//! key does not have a value.
+//!
+//! ## `map-get`
+//!
+//! Looks up a key in a map.
+//!
+//!   - Input parameters:
+//!     - `map`: a map value
+//!     - `key`: a string
+//!   - Output value: the value of `key` in `map`, or null if `map` does not contain `key`
+//!
+//! ## `map-keys`
+//!
+//! Returns the keys of a map.
+//!
+//!   - Input parameters: a map value
+//!   - Output value: a list of the keys of the map, as strings, in order
+//!
+//! ## `map-values`
+//!
+//! Returns the values of a map.
+//!
+//!   - Input parameters: a map value
+//!   - Output value: a list of the values of the map, ordered by their keys
+//!
 //! # Syntax manipulation functions
 //!
 //! ## `named-child-index`
@@ -160,6 +709,79 @@
 //!   - Output value:
 //!     - The number of _named_ children in `node`
 //!
+//! ## `child-by-field`
+//!
+//! Returns the child of a syntax node for a field.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!     - `field`: A string containing the name of a field in `node`'s grammar rule
+//!   - Output value:
+//!     - The child of `node` for `field`, or null if `node` has no such child.  If there are
+//!       several children for the field, the first one is returned.
+//!
+//! ## `next-named-sibling`
+//!
+//! Returns the next "named sibling" of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The first _named_ node that follows `node` within its parent, or null if there is none
+//!
+//! ## `previous-named-sibling`
+//!
+//! Returns the previous "named sibling" of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The last _named_ node that precedes `node` within its parent, or null if there is none
+//!
+//! ## `named-siblings`
+//!
+//! Returns the "named siblings" of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - A list of the _named_ children of `node`'s parent, other than `node` itself, in source
+//!       order.  The list is empty if `node` is the root.
+//!
+//! ## `ancestors`
+//!
+//! Returns the ancestors of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - A list of the syntax nodes that enclose `node`, starting with its parent and ending with
+//!       the root of the syntax tree.  The list is empty if `node` is the root.
+//!
+//! ## `descendants-of-kind`
+//!
+//! Returns the descendants of a syntax node that have a given type.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!     - `kind`: A string containing the name of a node type
+//!     - `max-depth`: An optional integer limiting how deep to search.  The children of `node`
+//!       are at depth 1, their children at depth 2, and so on.
+//!   - Output value:
+//!     - A list of the syntax nodes within `node` (not including `node` itself) whose type is
+//!       `kind`, in source order
+//!
+//! ## `parent-of-type`
+//!
+//! Returns the nearest enclosing syntax node of one of the given types.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!     - one or more strings, each the name of a node type
+//!   - Output value:
+//!     - The nearest ancestor of `node` (not including `node` itself) whose type is one of the
+//!       given types, or null if there is no such ancestor
+//!
 //! ## `source-text`
 //!
 //! Returns the source text represented by a syntax node.
@@ -179,6 +801,27 @@
 //!   - Output value:
 //!     - A string containing the type of `node`
 //!
+//! ## `kind-id`
+//!
+//! Returns the numeric ID of a syntax node's type.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - An integer identifying the type of `node` in the underlying tree-sitter grammar
+//!
+//! Kind IDs are only meaningful for a particular version of a grammar.
+//!
+//! ## `field-name`
+//!
+//! Returns the name of the field under which a syntax node appears in its parent.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - A string containing the name of the field of `node`'s parent that `node` belongs to, or
+//!       null if `node` is the root or does not belong to a field
+//!
 //! ## `start-column`
 //!
 //! Returns the zero-based start column of a syntax node.
@@ -214,3 +857,57 @@
 //!     - `node`: A syntax node
 //!   - Output value:
 //!     - The zero-based end row of `node`
+//!
+//! ## `start-byte`
+//!
+//! Returns the zero-based start byte offset of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The zero-based offset, in bytes, of the start of `node` in the source
+//!
+//! ## `end-byte`
+//!
+//! Returns the zero-based end byte offset of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The zero-based offset, in bytes, of the end of `node` in the source.  This is the offset
+//!       of the first byte _after_ `node`.
+//!
+//! ## `span`
+//!
+//! Returns the span of source covered by a syntax node, or by a range of syntax nodes.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!     - `last`: An optional syntax node
+//!   - Output value:
+//!     - A span from the start of `node` to the end of `last`, or to the end of `node` if `last`
+//!       is not given.  Each end of the span records its zero-based byte offset, row, and column.
+//!
+//! ## `text-between`
+//!
+//! Returns the source text between two syntax nodes.
+//!
+//!   - Input parameters:
+//!     - `first`: A syntax node
+//!     - `second`: A syntax node
+//!   - Output value:
+//!     - A string containing the source text from the end of `first` to the start of `second`
+//!
+//! It is an error if `second` starts before `first` ends.
+//!
+//! ## `source-line`
+//!
+//! Returns a full line of the source text.
+//!
+//!   - Input parameters:
+//!     - `node-or-row`: A syntax node, or an integer containing a zero-based row
+//!   - Output value:
+//!     - A string containing the line of the source that `node-or-row` starts on, or the line at
+//!       `node-or-row` if it is a row, without its line terminator
+//!
+//! It is an error if the row is past the end of the source.
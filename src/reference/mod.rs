@@ -97,9 +97,12 @@
 //! these capture assignments, creating any graph nodes, edges, or attributes mentioned in the
 //! block.
 //!
-//! Regular execution will apply the stanzas _in order_, and it is important to make sure that scoped variables
-//! have been assigned before they are used.  This is not a requirement when using the lazy evaluation strategy,
-//! which handles this implicitly.  The lazy evaluation strategy is also more efficient when there are many stanzas,
+//! Regular execution will apply the matches of the stanzas in the order that a single query for all of them
+//! finds them, followed by the matches of the stanzas with their own [match limit](#stanza-annotations), in
+//! order, and it is important to make sure that scoped variables have been assigned before they are used.  The
+//! query finds a match as soon as it has matched all of the stanza's pattern, so a stanza whose pattern is
+//! matched earlier in the syntax tree is applied first, even if it comes later in the file.  This is not a
+//! requirement when using the lazy evaluation strategy, which handles this implicitly.  The lazy evaluation strategy is also more efficient when there are many stanzas,
 //! because it can reduce tree traversals.  Therefore, using the lazy evaluation strategy is recommended, and will
 //! likely become the only supported strategy in future releases.
 //!
@@ -115,6 +118,54 @@
 //! }
 //! ```
 //!
+//! A stanza that has the same query, statements, and annotations as an earlier stanza in the file
+//! only repeats what the earlier stanza does, and is reported as a duplicate in the
+//! [warnings][`crate::ast::File::warnings`] of the file.  The queries must be written the same
+//! way, apart from indentation and blank lines, and the statements must be the same, apart from
+//! their layout.  Stanzas whose queries are written differently are not reported, even if they
+//! match the same syntax nodes.
+//!
+//! ## Stanza annotations
+//!
+//! A stanza can be preceded by annotations, which start with `@`, and change how the stanza is
+//! executed.  The `@match-limit` annotation limits the number of matches of the stanza's query
+//! that tree-sitter keeps in progress at a time, which must be between 1 and 65536:
+//!
+//! ``` tsg
+//! @match-limit(100)
+//! (module (_) @first (_) @second)
+//! {
+//!   ; Without a limit, this query keeps a match in progress for every
+//!   ; statement that could be the first one of a pair.
+//! }
+//! ```
+//!
+//! When a query exceeds its limit, tree-sitter drops its oldest in-progress matches, so some
+//! matches may be missing, and the execution records a warning.  The limit overrides the one set
+//! for all stanzas with `ExecutionConfig::match_limit`.  With lazy evaluation, the stanzas share a
+//! single query, whose limit is the smallest of those of the stanzas.
+//!
+//! The `@tag` annotation gives the stanza one or more tags, separated by commas, which let a single
+//! graph DSL file serve several purposes:
+//!
+//! ``` tsg
+//! @tag(outline, deep)
+//! (function_definition name: (identifier) @name) @function
+//! {
+//!   node @function.def
+//! }
+//!
+//! @tag(deep)
+//! (call function: (identifier) @name) @call
+//! {
+//!   node @call.ref
+//! }
+//! ```
+//!
+//! `ExecutionConfig::with_tags` selects the stanzas to execute by their tags: a stanza with tags
+//! is executed if it has one of the included tags, or no tags are included, and none of the
+//! excluded tags.  Stanzas without tags are always executed.
+//!
 //! # Expressions
 //!
 //! The value of an expression in the graph DSL can be any of the following:
@@ -129,6 +180,9 @@
 //!   - a list comprehension
 //!   - an unordered set of values
 //!   - a set comprehension
+//!   - a map from strings to values, created with the
+//!     [`map-of`][`crate::reference::functions#map-of`] function
+//!   - a span of source, created with the [`span`][`crate::reference::functions#span`] function
 //!
 //! The null value is spelled `#null`.
 //!
@@ -191,14 +245,26 @@
 //! value that is iterated over must be local.  It is therefore not possible to iterator over the value
 //! of a scoped variable. Using scoped variables in the element expression however is no problem.
 //!
+//! The types of expressions are checked when a graph DSL file is parsed, as far as they are known
+//! from literals, captures, `node` statements, and the `let` statements that bind them.  Creating an
+//! edge from a string, for instance, or iterating over a set, is an error in the graph DSL file,
+//! rather than an error when it is executed.  The values of function calls, of mutable variables,
+//! and of scoped variables can have any type, unless the function has a
+//! [signature][`crate::functions::Signature`] and the file is checked with
+//! [`File::check_with_functions`][`crate::ast::File::check_with_functions`].
+//!
 //! # Syntax nodes
 //!
 //! Syntax nodes are identified by tree-sitter query captures (`@name`).  For instance, in our
 //! example stanza, whose query is `(identifier) @id`, `@id` would refer to the `identifier` syntax
 //! node that the stanza matched against.
 //!
-//! Unused query captures are considered errors, unless they start with an underscode. For example,
-//! a capture `@id` must be used within the stanza, but `@_id` does not.
+//! Query captures that are not used within the stanza are reported as warnings, in the
+//! [`warnings`][`crate::ast::File::warnings`] of the file, unless they start with an underscore.
+//! For example, a capture `@id` should be used within the stanza, but `@_id` does not have to be.
+//! Likewise, local variables that are declared with `let` or `var` but never read are reported as
+//! warnings, unless they start with an underscore.  Assigning a variable with `set` does not count
+//! as reading it.
 //!
 //! # Variables
 //!
@@ -282,6 +348,36 @@
 //!
 //! [standard library]: functions/index.html
 //!
+//! Functions can also be grouped into **_namespaces_**, which keeps functions provided by different
+//! libraries from colliding with each other, or with the standard library.  The name of a function
+//! in a namespace is qualified with the name of the namespace, separated by `::`:
+//!
+//! ``` tsg
+//! (identifier) @id
+//! {
+//!    let @id.parts = (myorg::split-name (source-text @id))
+//! }
+//! ```
+//!
+//! Functions can also be passed as parameters to other functions.  A function name prefixed with
+//! `&` is a reference to the function with that name.  This lets you call higher-order functions
+//! like [`map`][] and [`filter`][]:
+//!
+//! ``` tsg
+//! (argument_list (_)* @args)
+//! {
+//!    let @args.texts = (map @args &source-text)
+//! }
+//! ```
+//!
+//! A bare name is always a variable, so a misspelled function reference without the `&` is an
+//! undefined variable.  Whether the referenced function exists is only known when the file is
+//! executed, or when it is checked with
+//! [`File::check_with_functions`][`crate::ast::File::check_with_functions`].
+//!
+//! [`map`]: functions/index.html#map
+//! [`filter`]: functions/index.html#filter
+//!
 //! # Graph nodes
 //!
 //! You can use this graph DSL to create any graph structure that you want.  There are no
@@ -499,6 +595,12 @@
 //! }
 //! ```
 //!
+//! The arms of an `if` statement that can never be taken, because one of their clauses is
+//! `#false`, or because an earlier arm has no other clauses than `#true`, are reported as
+//! [`warnings`][`crate::ast::File::warnings`] of the file.  A clause that is a local variable
+//! declared with `let` counts as `#true` or `#false` if the variable is bound to one of them,
+//! directly or through other such variables.
+//!
 //! # List iteration
 //!
 //! You can use a `for` statement to execute blocks of statements for every element in list
@@ -5,9 +5,12 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::cell::Cell;
+use std::cell::OnceCell;
 use std::collections::hash_map::Entry::Occupied;
 use std::collections::hash_map::Entry::Vacant;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use thiserror::Error;
 
 use crate::graph::Value;
@@ -120,8 +123,24 @@ impl<V> MutVariables<V> for VariableMap<'_, V> {
 
 /// Environment of immutable variables
 pub struct Globals<'a> {
-    context: Option<&'a dyn Variables<Value>>,
+    context: Option<&'a Globals<'a>>,
     values: HashMap<Identifier, Value>,
+    lazy_values: HashMap<Identifier, LazyGlobal>,
+}
+
+/// A global variable whose value is computed when it is first used
+struct LazyGlobal {
+    compute: Cell<Option<Box<dyn FnOnce() -> Value>>>,
+    value: OnceCell<Value>,
+}
+
+impl LazyGlobal {
+    fn get(&self) -> &Value {
+        self.value.get_or_init(|| {
+            let compute = self.compute.take().expect("lazy global computed twice");
+            compute()
+        })
+    }
 }
 
 impl<'a> Globals<'a> {
@@ -130,6 +149,7 @@ impl<'a> Globals<'a> {
         Self {
             context: None,
             values: HashMap::new(),
+            lazy_values: HashMap::new(),
         }
     }
 
@@ -139,12 +159,16 @@ impl<'a> Globals<'a> {
         Self {
             context: Some(context),
             values: HashMap::new(),
+            lazy_values: HashMap::new(),
         }
     }
 
     /// Adds a new variable to this environment, returning an error if the variable already
     /// exists.
     pub fn add(&mut self, name: Identifier, value: Value) -> Result<(), VariableError> {
+        if self.lazy_values.contains_key(&name) {
+            return Err(VariableError::VariableAlreadyDefined(name.to_string()));
+        }
         match self.values.entry(name) {
             Vacant(v) => {
                 v.insert(value);
@@ -154,22 +178,74 @@ impl<'a> Globals<'a> {
         }
     }
 
-    /// Returns the value of a variable, if it exists in this environment.
+    /// Adds a new variable to this environment, whose value is computed by calling `compute`
+    /// when the variable is first used, returning an error if the variable already exists.  This
+    /// is useful for global variables that are expensive to compute, since `compute` is only
+    /// called if a stanza that refers to the variable is executed.  Parallel execution computes
+    /// the values of all of the global variables that a file declares before it starts.
+    ///
+    /// The value is not checked against the declaration of the variable in the file, so it must
+    /// be a list if the file declares the variable with a `*` or `+` quantifier.
+    pub fn add_lazy<F>(&mut self, name: Identifier, compute: F) -> Result<(), VariableError>
+    where
+        F: FnOnce() -> Value + 'static,
+    {
+        if self.values.contains_key(&name) {
+            return Err(VariableError::VariableAlreadyDefined(name.to_string()));
+        }
+        match self.lazy_values.entry(name) {
+            Vacant(v) => {
+                v.insert(LazyGlobal {
+                    compute: Cell::new(Some(Box::new(compute))),
+                    value: OnceCell::new(),
+                });
+                Ok(())
+            }
+            Occupied(o) => Err(VariableError::VariableAlreadyDefined(o.key().to_string())),
+        }
+    }
+
+    /// Returns the value of a variable, if it exists in this environment.  The value of a lazy
+    /// variable is computed if this is the first time it is used.
     pub fn get(&self, name: &Identifier) -> Option<&Value> {
         self.values
             .get(name)
-            .or_else(|| self.context.as_ref().map(|p| p.get(name)).flatten())
+            .or_else(|| self.lazy_values.get(name).map(LazyGlobal::get))
+            .or_else(|| self.context.and_then(|context| context.get(name)))
+    }
+
+    /// Returns whether a variable exists in this environment, without computing its value if it
+    /// is lazy.
+    pub fn contains(&self, name: &Identifier) -> bool {
+        self.values.contains_key(name)
+            || self.lazy_values.contains_key(name)
+            || self.context.is_some_and(|context| context.contains(name))
+    }
+
+    /// Returns the value of a variable, if it exists in this environment, and is not a lazy
+    /// variable whose value has not been computed yet.
+    pub(crate) fn get_computed(&self, name: &Identifier) -> Option<&Value> {
+        if let Some(value) = self.values.get(name) {
+            return Some(value);
+        }
+        match self.lazy_values.get(name) {
+            Some(lazy) => lazy.value.get(),
+            None => self.context.and_then(|context| context.get_computed(name)),
+        }
     }
 
     /// Remove a variable from this enviroment, if it exists.
     pub fn remove(&mut self, name: &Identifier) {
         self.values.remove(name);
+        self.lazy_values.remove(name);
     }
 
     pub fn is_empty(&self) -> bool {
-        self.values.is_empty()
+        self.values.is_empty() && self.lazy_values.is_empty()
     }
 
+    /// Returns an iterator over the variables of this environment, not including its lazy
+    /// variables.
     pub fn iter<'b>(&'b self) -> Iter<'b> {
         Iter(self.values.iter())
     }
@@ -177,7 +253,36 @@ impl<'a> Globals<'a> {
     /// Clears this enviroment.
     pub fn clear(&mut self) {
         self.values.clear();
+        self.lazy_values.clear();
     }
+
+    /// Returns a snapshot of the variables of this environment, which [`Globals::restore`] can
+    /// restore it to.  This lets hosts that execute many files reuse an environment, by adding
+    /// the variables that are specific to each file after taking a snapshot, and restoring it
+    /// afterwards, and lets speculative changes to the environment be rolled back.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            values: self.values.clone(),
+            lazy_names: self.lazy_values.keys().cloned().collect(),
+        }
+    }
+
+    /// Restores the variables of this environment to those it had when `snapshot` was taken.
+    /// Lazy variables that were added since then are removed, but lazy variables that were
+    /// removed since then cannot be restored, since their values cannot be copied before they
+    /// are computed.  The environment that this one inherits from is not affected.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.values = snapshot.values.clone();
+        self.lazy_values
+            .retain(|name, _| snapshot.lazy_names.contains(name));
+    }
+}
+
+/// The variables of a [`Globals`] environment at some point.  See [`Globals::snapshot`].
+#[derive(Clone)]
+pub struct Snapshot {
+    values: HashMap<Identifier, Value>,
+    lazy_names: HashSet<Identifier>,
 }
 
 pub struct Iter<'a>(std::collections::hash_map::Iter<'a, Identifier, Value>);
@@ -5,14 +5,39 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
 use indoc::indoc;
+use tree_sitter::InputEdit;
 use tree_sitter::Parser;
+use tree_sitter::Point;
+use tree_sitter_graph::ast::CacheError;
 use tree_sitter_graph::ast::File;
+use tree_sitter_graph::ast::Stanza;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::Trace;
+use tree_sitter_graph::graph::TraceEvent;
+use tree_sitter_graph::graph::Value;
+use tree_sitter_graph::AttributeType;
+use tree_sitter_graph::CancellationToken;
+use tree_sitter_graph::Engine;
+use tree_sitter_graph::EngineDifference;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
+use tree_sitter_graph::ExecutionObserver;
+use tree_sitter_graph::ExecutionSession;
+use tree_sitter_graph::ExecutionWarning;
+use tree_sitter_graph::GraphElement;
 use tree_sitter_graph::Identifier;
+use tree_sitter_graph::Injection;
+use tree_sitter_graph::MatchPredicate;
 use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::PreviousExecution;
+use tree_sitter_graph::ResourceLimits;
+use tree_sitter_graph::TraceRecorder;
 use tree_sitter_graph::Variables;
 
 fn init_log() {
@@ -936,3 +961,1738 @@ fn can_execute_shorthand() {
         "#},
     );
 }
+
+#[test]
+fn can_execute_nested_shorthands_and_scopes() {
+    check_execution(
+        indoc! { r#"
+          x = 1
+          y = 2
+        "#},
+        indoc! {r#"
+            attribute def = x => symbol = x
+            attribute named = x => def = (source-text x)
+            (module (_)* @stmts) @_module
+            {
+              node n
+              for stmt in @stmts {
+                let text = (source-text stmt)
+                node m
+                attr (m) named = stmt, text = text
+                edge n -> m
+              }
+              attr (n) texts = [(source-text s) for s in @stmts]
+            }
+        "#},
+        indoc! {r#"
+          node 0
+            texts: ["x = 1", "y = 2"]
+          edge 0 -> 1
+          edge 0 -> 2
+          node 1
+            symbol: "x = 1"
+            text: "x = 1"
+          node 2
+            symbol: "y = 2"
+            text: "y = 2"
+        "#},
+    );
+}
+
+#[test]
+fn attribute_types_are_enforced() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            attr (n) name = "n", names = ["n", "m"]
+            edge n -> m
+            attr (n -> m) name = 1
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .attribute_type(
+                "names".into(),
+                AttributeType::List(Box::new(AttributeType::String)),
+            );
+        file.execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+
+        let config = config.attribute_type("name".into(), AttributeType::String);
+        let error = match file.execute(&tree, "pass", &config, &NoCancellation) {
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+            Err(error) => error.to_string(),
+        };
+        assert!(
+            error.contains("Attribute name must have type string, but the value has type integer"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("attr (n -> m) name = 1 at (7, 3)"),
+            "{}",
+            error
+        );
+
+        let config = config.attribute_type(
+            "names".into(),
+            AttributeType::List(Box::new(AttributeType::GraphNode)),
+        );
+        let error = match file.execute(&tree, "pass", &config, &NoCancellation) {
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+            Err(error) => error.to_string(),
+        };
+        assert!(
+            error.contains(
+                "must have type list-of-graph-node, but the value has type list-of-string"
+            ),
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn can_record_provenance() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            attr (n) name = "n"
+            edge n -> m
+            attr (n -> m) precedence = 1
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .with_provenance(true);
+        let graph = file
+            .execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+        let mut nodes = graph.iter_nodes();
+        let (n, m) = (nodes.next().unwrap(), nodes.next().unwrap());
+
+        let provenance = graph.node_provenance(m).unwrap();
+        assert_eq!(provenance.stanza_location.row, 0);
+        assert_eq!(provenance.statement_location.row, 3);
+        assert_eq!(provenance.node_kind, "module");
+        let provenance = graph
+            .node_attribute_provenance(n, &Identifier::from("name"))
+            .unwrap();
+        assert_eq!(provenance.statement_location.row, 4);
+        let provenance = graph.edge_provenance(n, m).unwrap();
+        assert_eq!(provenance.statement_location.row, 5);
+        let provenance = graph
+            .edge_attribute_provenance(n, m, &Identifier::from("precedence"))
+            .unwrap();
+        assert_eq!(provenance.statement_location.row, 6);
+        assert!(graph.pretty_print().to_string().starts_with(
+            "node 0 from statement at (3, 3) of stanza at (1, 1) matching (module) node at (1, 1)\n"
+        ));
+
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+        assert!(graph.node_provenance(n).is_none());
+    }
+}
+
+#[test]
+fn can_execute_incrementally_after_edits() {
+    let old_source = "x = 1\ny = 2\nz = 3\n";
+    let new_source = "x = 1\nwhy = 2\nz = 3\n";
+    let edit = InputEdit {
+        start_byte: 6,
+        old_end_byte: 7,
+        new_end_byte: 9,
+        start_position: Point::new(1, 0),
+        old_end_position: Point::new(1, 1),
+        new_end_position: Point::new(1, 3),
+    };
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let old_tree = parser.parse(old_source, None).unwrap();
+    let mut edited_tree = old_tree.clone();
+    edited_tree.edit(&edit);
+    let new_tree = parser.parse(new_source, Some(&edited_tree)).unwrap();
+
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.def
+          }
+          (expression_statement (assignment left: (identifier) @name)) @stmt
+          {
+            node @stmt.def
+            attr (@stmt.def) name = (source-text @name), span = (span @stmt)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let describe = |graph: &Graph| {
+        let mut nodes = graph
+            .iter_nodes()
+            .map(|node| graph.node(node).attributes().to_string())
+            .collect::<Vec<_>>();
+        nodes.sort();
+        nodes
+    };
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let expected = file
+            .execute(&new_tree, new_source, &config, &NoCancellation)
+            .expect("Cannot execute file");
+        for provenance in [true, false] {
+            let previous = file
+                .execute(
+                    &old_tree,
+                    old_source,
+                    &ExecutionConfig::new(&functions, &globals)
+                        .lazy(lazy)
+                        .with_provenance(provenance),
+                    &NoCancellation,
+                )
+                .expect("Cannot execute file");
+            let previous = PreviousExecution {
+                graph: &previous,
+                tree: &old_tree,
+                edits: &[edit],
+            };
+            let graph = file
+                .execute_incremental(&previous, &new_tree, new_source, &config, &NoCancellation)
+                .expect("Cannot execute file incrementally");
+            assert_eq!(describe(&graph), describe(&expected));
+            // The nodes of unaffected matches are copied first, unless the previous graph has no
+            // provenance, and the whole file is executed again.
+            let first = graph.iter_nodes().next().unwrap();
+            let first_name = graph.node(first).attributes().get("name");
+            if provenance {
+                assert_eq!(first_name, Some(&Value::from("x")));
+            } else {
+                assert_eq!(first_name, None);
+            }
+            for node in graph.iter_nodes() {
+                assert!(graph.node_provenance(node).is_some());
+            }
+        }
+    }
+}
+
+#[test]
+fn incremental_execution_reports_errors() {
+    let old_source = "x = 1\n";
+    let new_source = "x = y\n";
+    let edit = InputEdit {
+        start_byte: 4,
+        old_end_byte: 5,
+        new_end_byte: 5,
+        start_position: Point::new(0, 4),
+        old_end_position: Point::new(0, 5),
+        new_end_position: Point::new(0, 5),
+    };
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let old_tree = parser.parse(old_source, None).unwrap();
+    let mut edited_tree = old_tree.clone();
+    edited_tree.edit(&edit);
+    let new_tree = parser.parse(new_source, Some(&edited_tree)).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (assignment right: (_) @value) @stmt
+          {
+            node @stmt.def
+            attr (@stmt.def) value = (to-int (source-text @value))
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals).with_provenance(true);
+    let previous = file
+        .execute(&old_tree, old_source, &config, &NoCancellation)
+        .expect("Cannot execute file");
+    let previous = PreviousExecution {
+        graph: &previous,
+        tree: &old_tree,
+        edits: &[edit],
+    };
+    assert!(file
+        .execute_incremental(&previous, &new_tree, new_source, &config, &NoCancellation)
+        .is_err());
+}
+
+#[test]
+fn can_execute_stanzas_in_parallel() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          global root
+
+          (module) @module
+          {
+            node @module.def
+            edge root -> @module.def
+          }
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id), symbol = (gensym "s")
+            edge root -> n
+          }
+          (module (_) @stmt) @module
+          {
+            node @stmt.def
+            edge @module.def -> @stmt.def
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let mut graph = Graph::new();
+    let root = graph.add_graph_node();
+    let mut globals = Variables::new();
+    globals
+        .add(Identifier::from("root"), root.into())
+        .expect("Cannot add global");
+    let config = ExecutionConfig::new(&functions, &globals).parallel(true);
+    file.execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+        .expect("Cannot execute file");
+    // The first and last stanzas share the `def` scoped variable, so they are executed together,
+    // before the nodes of the second stanza are added.
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+          edge 0 -> 1
+          edge 0 -> 4
+          edge 0 -> 5
+          node 1
+          edge 1 -> 2
+          edge 1 -> 3
+          node 2
+          node 3
+          node 4
+            name: "x"
+            symbol: "s1"
+          node 5
+            name: "y"
+            symbol: "s3"
+        "#}
+    );
+}
+
+#[test]
+fn parallel_random_numbers_only_depend_on_seed_and_match() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let stanza = indoc! {r#"
+      (identifier) @id
+      {
+        node n
+        attr (n) name = (source-text @id), random = (random-int 0 1000000000)
+      }
+    "#};
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let random_numbers = |dsl_source: &str, seed: u64| {
+        let file =
+            File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+        let config = ExecutionConfig::new(&functions, &globals)
+            .parallel(true)
+            .random_seed(seed);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Cannot execute file");
+        graph
+            .iter_nodes()
+            .filter_map(|node| graph.node(node).attributes().get("random").cloned())
+            .collect::<Vec<_>>()
+    };
+    let expected = random_numbers(stanza, 1);
+    assert_eq!(expected.len(), 2);
+    assert_ne!(expected[0], expected[1]);
+    // another stanza, which is executed in another group, does not change the numbers
+    let with_other_stanza = format!(
+        "{}\n{}",
+        stanza,
+        indoc! {r#"
+          (integer)
+          {
+            node n
+            attr (n) random = (random-int 0 1000000000)
+          }
+        "#}
+    );
+    assert_eq!(&random_numbers(&with_other_stanza, 1)[..2], &expected[..]);
+    assert_ne!(random_numbers(stanza, 2), expected);
+}
+
+#[test]
+fn cancellation_token_cancels_execution() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let token = CancellationToken::new();
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .cancellation_token(token.clone());
+        file.execute(&tree, source, &config, &NoCancellation)
+            .expect("Cannot execute file");
+        token.cancel();
+        match file.execute(&tree, source, &config, &NoCancellation) {
+            Err(ExecutionError::Cancelled(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+        }
+    }
+}
+
+#[test]
+fn execution_times_out() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .with_timeout(Duration::from_secs(60));
+        file.execute(&tree, source, &config, &NoCancellation)
+            .expect("Cannot execute file");
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .with_timeout(Duration::from_secs(0));
+        match file.execute(&tree, source, &config, &NoCancellation) {
+            Err(ExecutionError::TimedOut(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+        }
+    }
+}
+
+#[test]
+fn execution_fails_when_resource_limits_are_exceeded() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+          }
+          (module (_) @stmt) @module
+          {
+            node n
+            attr (n) text = (source-text @stmt), list = [1, 2, 3]
+            edge @module.node -> n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let check = |lazy: bool, limits: ResourceLimits, expected: Option<&str>| {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .resource_limits(limits);
+        match (
+            file.execute(&tree, source, &config, &NoCancellation),
+            expected,
+        ) {
+            (Ok(_), None) => {}
+            (Err(e), Some(expected)) => assert!(
+                e.to_string()
+                    .contains(&format!("Resource limit exceeded: {}", expected)),
+                "Unexpected error: {}",
+                e
+            ),
+            (Ok(_), Some(_)) => panic!("Execution succeeded unexpectedly"),
+            (Err(e), None) => panic!("Could not execute file: {}", e),
+        }
+    };
+    for lazy in [false, true] {
+        let limits = ResourceLimits {
+            max_nodes: Some(3),
+            max_edges: Some(2),
+            max_attribute_bytes: Some(10),
+            max_list_length: Some(3),
+            ..ResourceLimits::default()
+        };
+        check(lazy, limits.clone(), None);
+        let max_nodes = ResourceLimits {
+            max_nodes: Some(2),
+            ..limits.clone()
+        };
+        check(lazy, max_nodes, Some("more than 2 graph nodes"));
+        let max_edges = ResourceLimits {
+            max_edges: Some(1),
+            ..limits.clone()
+        };
+        check(lazy, max_edges, Some("more than 1 edges"));
+        let max_attribute_bytes = ResourceLimits {
+            max_attribute_bytes: Some(9),
+            ..limits.clone()
+        };
+        check(
+            lazy,
+            max_attribute_bytes,
+            Some("more than 9 bytes of attributes"),
+        );
+        let max_list_length = ResourceLimits {
+            max_list_length: Some(2),
+            ..limits.clone()
+        };
+        check(lazy, max_list_length, Some("more than 2 list elements"));
+    }
+}
+
+#[test]
+fn can_profile_execution() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+          }
+          (module (_) @stmt) @module
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            edge @module.node -> n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let mut graph = Graph::new();
+        let report = file
+            .execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert!(report.profile().is_none());
+
+        let config = config.profile(true);
+        let mut graph = Graph::new();
+        let report = file
+            .execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let profile = report.profile().expect("Missing profile");
+        let counts = profile
+            .stanzas()
+            .iter()
+            .map(|stanza| {
+                (
+                    stanza.location.row,
+                    stanza.matches,
+                    stanza.nodes,
+                    stanza.edges,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![(0, 1, 1, 0), (4, 2, 2, 2)]);
+        let table = profile.to_string();
+        assert_eq!(table.lines().count(), 3, "Unexpected table:\n{}", table);
+    }
+}
+
+#[test]
+fn match_limits_record_warnings() {
+    let source = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let check = |annotation: &str, match_limit: Option<u32>, lazy: bool, exceeded: bool| {
+        let dsl = format!(
+            indoc! {r#"
+              {}
+              (module (_) @_a (_) @_b)
+              {{
+                node n
+              }}
+            "#},
+            annotation
+        );
+        let file = File::from_str(tree_sitter_python::language(), &dsl).expect("Cannot parse file");
+        let mut config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        if let Some(match_limit) = match_limit {
+            config = config.match_limit(match_limit);
+        }
+        let mut graph = Graph::new();
+        let report = file
+            .execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let expected = if !exceeded {
+            vec![]
+        } else if lazy || annotation.is_empty() {
+            vec![ExecutionWarning::MatchLimitExceeded(
+                "for the file query".to_string(),
+            )]
+        } else {
+            vec![ExecutionWarning::MatchLimitExceeded(
+                "for stanza at (2, 1)".to_string(),
+            )]
+        };
+        assert_eq!(report.warnings(), &expected[..]);
+        if !exceeded {
+            assert_eq!(graph.node_count(), 28);
+        }
+    };
+    for lazy in [false, true] {
+        check("", None, lazy, false);
+        check("", Some(2), lazy, true);
+        check("@match-limit(2)", None, lazy, true);
+        // the lazy engine uses the smallest limit for the query that the stanzas share
+        check("@match-limit(1000)", Some(2), lazy, lazy);
+    }
+}
+
+#[test]
+fn can_cache_compiled_files() {
+    let dsl_source = indoc! {r#"
+      global filename
+      attribute named = x => name = (source-text x)
+
+      @match-limit(100)
+      (module (expression_statement (assignment left: (identifier) @name)) @stmt) @_module
+      {
+        var kind = "assignment"
+        let unused = kind
+        node @stmt.node
+        attr (@stmt.node) named = @name, kind = kind, file = filename
+        scan (source-text @name) {
+          "^[a-z]+$" { attr (@stmt.node) lower = #true }
+        }
+        if (eq (source-text @name) "x") {
+          edge @stmt.node -> @stmt.node
+        }
+      }
+    "#};
+    let source = "x = 1\nY = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let bytes = file.to_cache(dsl_source);
+    let cached = File::from_cache(tree_sitter_python::language(), dsl_source, &bytes)
+        .expect("Cannot decode file");
+    assert_eq!(file.globals, cached.globals);
+    for (stanza, cached_stanza) in file.stanzas.iter().zip(&cached.stanzas) {
+        assert_eq!(stanza.statements, cached_stanza.statements);
+        assert_eq!(stanza.query_source, cached_stanza.query_source);
+        assert_eq!(stanza.match_limit, cached_stanza.match_limit);
+    }
+    assert_eq!(file.warnings.len(), 1);
+    assert_eq!(file.warnings, cached.warnings);
+
+    let functions = Functions::stdlib();
+    let mut globals = Variables::new();
+    globals
+        .add(Identifier::from("filename"), "test.py".into())
+        .unwrap();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let cached_graph = cached
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute cached file");
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            cached_graph.pretty_print().to_string()
+        );
+    }
+
+    assert!(matches!(
+        File::from_cache(tree_sitter_python::language(), "", &bytes),
+        Err(CacheError::Stale(_))
+    ));
+    assert!(matches!(
+        File::from_cache(tree_sitter_python::language(), dsl_source, &bytes[..10]),
+        Err(CacheError::Invalid(_))
+    ));
+}
+
+#[test]
+fn dry_runs_count_instead_of_building_the_graph() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+          }
+          (module (_) @stmt) @module
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            edge @module.node -> n
+            attr (@module.node -> n) kind = "statement"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let config = config.dry_run(true);
+        let mut dry_graph = Graph::new();
+        let report = file
+            .execute_into(&mut dry_graph, &tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(dry_graph.node_count(), 0);
+        let (stats, dry_stats) = (graph.stats(), report.dry_run().expect("Missing dry run"));
+        assert_eq!(stats.node_count, dry_stats.node_count);
+        assert_eq!(stats.edge_count, dry_stats.edge_count);
+        assert_eq!(stats.node_attribute_count, dry_stats.node_attribute_count);
+        assert_eq!(stats.edge_attribute_count, dry_stats.edge_attribute_count);
+        assert_eq!(stats.node_attribute_names, dry_stats.node_attribute_names);
+        assert_eq!(stats.edge_attribute_names, dry_stats.edge_attribute_names);
+        assert!(dry_stats.attribute_bytes > 0);
+    }
+
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module (_) @stmt)
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            attr (n) text = "again"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .dry_run(true);
+        assert!(file
+            .execute(&tree, source, &config, &NoCancellation)
+            .is_err());
+    }
+
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+          }
+          (module (_) @_stmt) @module
+          {
+            node n
+            edge @module.node -> n
+            edge @module.node -> n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .dry_run(true);
+        assert!(file
+            .execute(&tree, source, &config, &NoCancellation)
+            .is_err());
+    }
+}
+
+#[test]
+fn dry_runs_that_read_the_graph_see_attribute_values() {
+    let source = "x = 1\ny = 2\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module (_) @stmt)
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            attr (n) copy = (get-attr n "text")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .dry_run(true);
+        let mut graph = Graph::new();
+        let report = file
+            .execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(report.dry_run().map(|stats| stats.node_count), Some(2));
+        for node in graph.iter_nodes() {
+            let attributes = graph.node(node).attributes();
+            assert_ne!(attributes.get("copy"), Some(&Value::Null));
+            assert_eq!(attributes.get("copy"), attributes.get("text"));
+        }
+    }
+}
+
+#[derive(Default)]
+struct RecordingObserver(Mutex<Vec<String>>);
+
+impl RecordingObserver {
+    fn record(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+impl ExecutionObserver for RecordingObserver {
+    fn on_stanza_start(&self, stanza: &Stanza) {
+        self.record(format!("stanza {}", stanza.range.start));
+    }
+
+    fn on_match(&self, _stanza: &Stanza, node: tree_sitter::Node) {
+        self.record(format!("match {}", node.kind()));
+    }
+
+    fn on_node_created(&self, node: tree_sitter_graph::graph::GraphNodeRef) {
+        self.record(format!("node {}", node.index()));
+    }
+
+    fn on_attr_set(&self, element: GraphElement, name: &Identifier, value: &Value) {
+        self.record(format!("attr {:?} {} {}", element, name, value));
+    }
+
+    fn on_error(&self, error: &ExecutionError) {
+        self.record(format!("error {}", error));
+    }
+}
+
+#[test]
+fn observers_are_told_about_execution_progress() {
+    let source = "x = 1\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+          }
+          (module (_) @stmt) @module
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            edge @module.node -> n
+            attr (@module.node -> n) kind = "statement"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let observer = RecordingObserver::default();
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .observer(&observer);
+        file.execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let mut events = observer.0.into_inner().unwrap();
+        // the stanzas share a query, so they all start before any match is executed
+        assert_eq!(&events[..2], ["stanza (1, 1)", "stanza (5, 1)"]);
+        assert_eq!(&events[2..4], ["match module", "node 0"]);
+        events.sort();
+        assert_eq!(
+            events,
+            [
+                "attr Edge([graph node 0], [graph node 1]) kind statement",
+                "attr Node([graph node 1]) text x = 1",
+                "match module",
+                "match module",
+                "node 0",
+                "node 1",
+                "stanza (1, 1)",
+                "stanza (5, 1)",
+            ]
+        );
+    }
+
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) text = "once"
+            attr (n) text = "twice"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    for lazy in [false, true] {
+        let observer = RecordingObserver::default();
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .observer(&observer);
+        let error = match file.execute(&tree, source, &config, &NoCancellation) {
+            Ok(_) => panic!("Expected a duplicate attribute"),
+            Err(error) => error,
+        };
+        let events = observer.0.into_inner().unwrap();
+        assert_eq!(events.last(), Some(&format!("error {}", error)));
+    }
+}
+
+#[test]
+fn can_execute_within_byte_range() {
+    let source = "x = 1\ny = 2\nz = 3\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (expression_statement) @stmt
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .within_byte_range(7..8);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let texts = graph
+            .iter_nodes()
+            .filter_map(|node| graph.node(node).attributes().get("text").cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec![Value::from("y = 2")]);
+    }
+}
+
+#[test]
+fn can_execute_with_injections() {
+    let source = "x = 1\ns = \"y = 2\"\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    // the contents of the string are another (Python) source file
+    let byte_range = 11..16;
+    parser
+        .set_included_ranges(&[tree_sitter::Range {
+            start_byte: byte_range.start,
+            end_byte: byte_range.end,
+            start_point: Point::new(1, 5),
+            end_point: Point::new(1, 10),
+        }])
+        .unwrap();
+    let injected_tree = parser.parse(source, None).unwrap();
+
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (string) @string
+          {
+            node @string.node
+            attr (@string.node) anchor = @string, text = (source-text @string)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let injected_file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @module
+          {
+            node @module.node
+            attr (@module.node) anchor = @module
+          }
+          (module (_) @stmt) @module
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            edge @module.node -> n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let injections = [Injection {
+        file: &injected_file,
+        tree: &injected_tree,
+        byte_range,
+    }];
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .injection_anchors(Identifier::from("anchor"));
+        let graph = file
+            .execute_with_injections(&tree, source, &injections, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let texts = graph
+            .iter_nodes()
+            .map(|node| graph.node(node).attributes().get("text").cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                Some(Value::from("\"y = 2\"")),
+                None,
+                Some(Value::from("y = 2"))
+            ]
+        );
+        let nodes = graph.iter_nodes().collect::<Vec<_>>();
+        let edges = nodes
+            .iter()
+            .flat_map(|source| {
+                graph
+                    .node(*source)
+                    .iter_edges()
+                    .map(move |(sink, _)| (source.index(), sink.index()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+    }
+}
+
+#[test]
+fn lazy_globals_are_computed_when_used() {
+    let source = "x = 1\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          global used
+          global unmatched
+          global unused
+
+          (module) @module
+          {
+            node @module.node
+            attr (@module.node) used = used, again = used
+          }
+          (function_definition) @def
+          {
+            node @def.node
+            attr (@def.node) unmatched = unmatched
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    for lazy in [false, true] {
+        let computed = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut globals = Variables::new();
+        for name in ["used", "unmatched", "unused"] {
+            let computed = computed.clone();
+            globals
+                .add_lazy(Identifier::from(name), move || {
+                    computed.borrow_mut().push(name);
+                    Value::from(name)
+                })
+                .unwrap();
+        }
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(*computed.borrow(), vec!["used"]);
+        let node = graph.iter_nodes().next().unwrap();
+        assert_eq!(
+            graph.node(node).attributes().get("used"),
+            Some(&Value::from("used"))
+        );
+    }
+}
+
+#[test]
+fn can_continue_on_error() {
+    let source = "a = 1\nbad = 2\nc = 3\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (assignment left: (identifier) @name)
+          {
+            node n
+            attr (n) name = (source-text @name)
+            if (eq (source-text @name) "bad") {
+              attr (n) sum = (plus 1 "x")
+            }
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        for parallel in [false, true] {
+            let config = ExecutionConfig::new(&functions, &globals)
+                .lazy(lazy)
+                .parallel(parallel);
+            assert!(file
+                .execute(&tree, source, &config, &NoCancellation)
+                .is_err());
+
+            let config = config.continue_on_error(true);
+            let mut graph = Graph::new();
+            let report = file
+                .execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+                .expect("Could not execute file");
+            let names = graph
+                .iter_nodes()
+                .filter_map(|node| graph.node(node).attributes().get("name").cloned())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                vec![Value::from("a"), Value::from("bad"), Value::from("c")]
+            );
+            assert_eq!(report.errors().len(), 1);
+            let error = report.errors()[0].to_string();
+            assert!(
+                error.contains("(plus 1 \"x\")") && error.contains("(2, 1)"),
+                "Unexpected error: {}",
+                error
+            );
+        }
+    }
+
+    // resource limits still stop the execution
+    let config = ExecutionConfig::new(&functions, &globals)
+        .continue_on_error(true)
+        .resource_limits(ResourceLimits {
+            max_nodes: Some(1),
+            ..ResourceLimits::default()
+        });
+    match file.execute(&tree, source, &config, &NoCancellation) {
+        Ok(_) => panic!("Expected the resource limit to be exceeded"),
+        Err(error) => assert!(error.to_string().contains("Resource limit exceeded")),
+    }
+}
+
+#[test]
+fn can_reuse_sessions_across_executions() {
+    let sources = ["x = 1\n", "y = x\n"];
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (replace (source-text @id) "[xy]" "v")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let session = ExecutionSession::new();
+    for lazy in [false, true] {
+        let mut names = Vec::new();
+        for (index, source) in sources.iter().enumerate() {
+            let tree = parser.parse(source, None).unwrap();
+            let mut config = ExecutionConfig::new(&functions, &globals)
+                .lazy(lazy)
+                .session(&session);
+            // the cursors that the session reuses must not keep the settings of earlier executions
+            if index == 0 {
+                config = config.within_byte_range(0..1);
+            }
+            let graph = file
+                .execute(&tree, source, &config, &NoCancellation)
+                .expect("Could not execute file");
+            let expected = if index == 0 { 1 } else { 2 };
+            assert_eq!(graph.node_count(), expected);
+            for node in graph.iter_nodes() {
+                match graph.node(node).attributes().get("name") {
+                    Some(Value::String(name)) => names.push(name.clone()),
+                    value => panic!("Unexpected name {:?}", value),
+                }
+            }
+        }
+        // the graphs share the storage of equal strings
+        assert!(names.iter().all(|name| Arc::ptr_eq(name, &names[0])));
+    }
+    session.clear();
+}
+
+#[test]
+fn can_compare_engines() {
+    let source = "x = y\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let compare = |dsl_source: &str| {
+        let file =
+            File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+        file.compare_engines(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file")
+    };
+
+    let differences = compare(indoc! {r#"
+      (assignment left: (_) @left right: (_) @right) @assignment
+      {
+        node @assignment.node
+        attr (@assignment.node) names = [(source-text @left), (source-text @right)]
+        attr (@assignment.node) syntax = @assignment
+      }
+      (assignment right: (identifier) @id) @assignment
+      {
+        node n
+        attr (n) name = (source-text @id), nodes = {@assignment.node}
+        edge n -> @assignment.node
+      }
+    "#});
+    assert_eq!(differences, vec![]);
+
+    // symbols are generated in the order that the engines execute the matches, and the strict
+    // engine executes the stanzas with their own match limit after the others
+    let differences = compare(indoc! {r#"
+      (identifier)
+      {
+        node n
+        attr (n) symbol = (gensym)
+      }
+      @match-limit(10)
+      (module)
+      {
+        node n
+        attr (n) symbol = (gensym)
+      }
+    "#});
+    assert!(!differences.is_empty());
+    assert!(differences.iter().all(|difference| matches!(
+        difference,
+        EngineDifference::NodeAttribute { name, .. } if name.as_str() == "symbol"
+    )));
+}
+
+#[test]
+fn engines_agree_on_graph_reads() {
+    let source = "x = y\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let compare = |dsl_source: &str| {
+        let file =
+            File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+        file.compare_engines(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file")
+    };
+
+    let differences = compare(indoc! {r#"
+      (module) @m
+      {
+        node @m.a
+        node @m.b
+        edge @m.a -> @m.b
+        attr (@m.a) name = "a"
+        attr (@m.b) linked = (has-edge @m.a @m.b), name = (get-attr @m.a "name")
+      }
+    "#});
+    assert_eq!(differences, vec![]);
+
+    // the lazy engine evaluates conditions before it builds the graph, so it cannot read the graph
+    // in them, and fails instead of taking a different branch than the strict engine
+    for condition in ["(has-edge a b)", r#"(eq (get-attr a "name") "a")"#] {
+        let differences = compare(&format!(
+            indoc! {r#"
+              (module)
+              {{
+                node a
+                node b
+                edge a -> b
+                attr (a) name = "a"
+                if {} {{
+                  attr (b) linked = #true
+                }}
+              }}
+            "#},
+            condition
+        ));
+        match differences.as_slice() {
+            [EngineDifference::Failed {
+                engine: Engine::Lazy,
+                error,
+            }] => assert!(
+                error.contains("Cannot read the graph in call to"),
+                "{}",
+                error
+            ),
+            _ => panic!("Unexpected differences {:?}", differences),
+        }
+    }
+}
+
+#[test]
+fn can_record_and_replay_traces() {
+    let source = "x = y\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (assignment left: (_) @left right: (_) @right) @assignment
+          {
+            node @assignment.node
+            attr (@assignment.node) names = [(source-text @left), (source-text @right)]
+          }
+          (assignment right: (identifier) @id) @assignment
+          {
+            node n
+            attr (n) name = (source-text @id), line = (start-row @id)
+            edge n -> @assignment.node
+            attr (n -> @assignment.node) label = "parent"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let recorder = TraceRecorder::new();
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .observer(&recorder);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let trace = recorder.into_trace();
+        assert!(trace
+            .events
+            .iter()
+            .any(|event| matches!(event, TraceEvent::Statement { .. })));
+        if lazy {
+            assert!(trace
+                .events
+                .iter()
+                .any(|event| matches!(event, TraceEvent::ThunkForced { .. })));
+        }
+
+        let mut ndjson = Vec::new();
+        trace.write(&mut ndjson).expect("Cannot write trace");
+        let read = Trace::read(std::str::from_utf8(&ndjson).unwrap()).expect("Cannot read trace");
+        assert_eq!(read, trace);
+        let replayed = read.replay().expect("Cannot replay trace");
+        assert_eq!(
+            replayed.pretty_print().to_string(),
+            graph.pretty_print().to_string()
+        );
+    }
+}
+
+#[test]
+fn can_select_stanzas_by_tags() {
+    let source = "x = y\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "module"
+          }
+          @tag(outline, deep)
+          (assignment)
+          {
+            node n
+            attr (n) name = "assignment"
+          }
+          @tag(deep)
+          (identifier)
+          {
+            node n
+            attr (n) name = "identifier"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let names = |include: &[&str], exclude: &[&str]| {
+            let config = ExecutionConfig::new(&functions, &globals)
+                .lazy(lazy)
+                .with_tags(include, exclude);
+            let graph = file
+                .execute(&tree, source, &config, &NoCancellation)
+                .expect("Could not execute file");
+            let mut names = graph
+                .iter_nodes()
+                .map(|node| {
+                    graph
+                        .node(node)
+                        .attributes()
+                        .get("name")
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names
+        };
+        assert_eq!(names(&[], &[]), ["assignment", "identifier", "module"]);
+        assert_eq!(names(&["outline"], &[]), ["assignment", "module"]);
+        assert_eq!(
+            names(&["deep"], &[]),
+            ["assignment", "identifier", "module"]
+        );
+        assert_eq!(names(&[], &["outline"]), ["identifier", "module"]);
+        assert_eq!(names(&["deep"], &["outline"]), ["identifier", "module"]);
+    }
+}
+
+#[test]
+fn can_execute_many_files_into_one_graph() {
+    let sources = [("a.py", "x = 1\n"), ("b.py", "y = z\n")];
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let trees = sources
+        .iter()
+        .map(|(_, source)| parser.parse(source, None).unwrap())
+        .collect::<Vec<_>>();
+    let files = || (sources.iter().zip(&trees)).map(|((path, source), tree)| (path, *source, tree));
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+
+        let file = File::from_str(
+            tree_sitter_python::language(),
+            indoc! {r#"
+              global ROOT
+              (identifier) @id
+              {
+                node n
+                attr (n) name = (source-text @id)
+                edge ROOT -> n
+              }
+            "#},
+        )
+        .expect("Cannot parse file");
+        let mut graph = Graph::new();
+        let (roots, _) = file
+            .execute_files_into(&mut graph, files(), &config, &NoCancellation)
+            .expect("Could not execute files");
+        assert_eq!(roots.len(), 2);
+        let names = roots
+            .iter()
+            .map(|root| {
+                graph
+                    .node(*root)
+                    .iter_edges()
+                    .map(|(sink, _)| {
+                        graph
+                            .node(sink)
+                            .attributes()
+                            .get("name")
+                            .unwrap()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![vec!["x"], vec!["y", "z"]]);
+
+        let file = File::from_str(
+            tree_sitter_python::language(),
+            indoc! {r#"
+              global ROOT
+              (identifier) @id
+              {
+                attr (ROOT) name = (source-text @id)
+              }
+            "#},
+        )
+        .expect("Cannot parse file");
+        let mut graph = Graph::new();
+        match file.execute_files_into(&mut graph, files(), &config, &NoCancellation) {
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+            Err(e) => assert!(e.to_string().contains("source file b.py"), "{}", e),
+        }
+        // the graph keeps the results of the files before the one that failed
+        assert_eq!(graph.node_count(), 2);
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn can_execute_asynchronously() {
+    use std::future::Future;
+    use std::task::Context;
+    use std::task::Poll;
+    use std::task::Wake;
+    use std::task::Waker;
+
+    struct NoopWaker;
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let source = "x = y\nz = 1\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let waker = Waker::from(Arc::new(NoopWaker));
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let expected = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+
+        let mut future = Box::pin(file.execute_async(&tree, source, &config, &NoCancellation));
+        let mut yields = 0;
+        let graph = loop {
+            match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(result) => break result.expect("Could not execute file"),
+                Poll::Pending => yields += 1,
+            }
+        };
+        // the execution yields after each of the three matches
+        assert!(yields >= 3);
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            expected.pretty_print().to_string()
+        );
+
+        // dropping the future stops the execution
+        let mut graph = Graph::new();
+        let mut future =
+            Box::pin(file.execute_into_async(&mut graph, &tree, source, &config, &NoCancellation));
+        assert!(future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+        drop(future);
+        assert_eq!(graph.node_count(), 1);
+    }
+}
+
+#[test]
+fn can_filter_matches() {
+    let source = "x = 1\n# generated\ny = 2\nz = 3\n";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (expression_statement) @stmt
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+          }
+
+          @match-limit(10)
+          (expression_statement) @stmt
+          {
+            node n
+            attr (n) limited = (source-text @stmt)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    // skip the statement after the comment, as if it were generated code
+    let predicate: &MatchPredicate = &|mat| {
+        mat.captures
+            .iter()
+            .all(|capture| capture.node.start_position().row != 2)
+    };
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .filter_matches(predicate);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        for name in ["text", "limited"] {
+            let texts = graph
+                .iter_nodes()
+                .filter_map(|node| graph.node(node).attributes().get(name).cloned())
+                .collect::<Vec<_>>();
+            assert_eq!(texts, vec![Value::from("x = 1"), Value::from("z = 3")]);
+        }
+    }
+}
+
+#[test]
+fn can_execute_file_repeatedly_with_different_globals() {
+    init_log();
+    let source = "pass";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          global filename
+
+          (module)
+          {
+            node n
+            attr (n) filename = filename
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    for filename in ["a.py", "b.py"] {
+        let mut globals = Variables::new();
+        globals
+            .add(Identifier::from("filename"), filename.into())
+            .unwrap();
+        let config = ExecutionConfig::new(&functions, &globals);
+        let graph = file
+            .execute(&tree, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(
+            graph
+                .node(graph.iter_nodes().next().unwrap())
+                .attributes()
+                .get("filename"),
+            Some(&Value::from(filename)),
+        );
+    }
+}
+
+#[test]
+fn cannot_execute_file_without_query() {
+    let source = "pass";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let file = File::new(tree_sitter_python::language());
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    match file.execute(&tree, source, &config, &NoCancellation) {
+        Err(ExecutionError::MissingQuery(_)) => {}
+        result => panic!("Unexpected result {:?}", result.map(|_| ())),
+    }
+}
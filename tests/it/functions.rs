@@ -5,12 +5,25 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
 use indoc::indoc;
 use tree_sitter::Parser;
 use tree_sitter_graph::ast::File;
+use tree_sitter_graph::functions::Function;
+use tree_sitter_graph::functions::FunctionState;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::functions::LazyParameters;
+use tree_sitter_graph::functions::ParameterType;
+use tree_sitter_graph::functions::Parameters;
+use tree_sitter_graph::functions::Signature;
+use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::Value;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
+use tree_sitter_graph::ExecutionState;
 use tree_sitter_graph::Identifier;
 use tree_sitter_graph::NoCancellation;
 use tree_sitter_graph::Variables;
@@ -232,3 +245,1711 @@ fn can_join_list_without_separator() {
         "#},
     );
 }
+
+#[test]
+fn can_sort_list() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (sort [3, "b", 1, #null, "a", 2, #true])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [#null, #true, 1, 2, 3, "a", "b"]
+        "#},
+    );
+}
+
+#[test]
+fn can_sort_syntax_nodes_by_source_text() {
+    check_execution(
+        "c = 1\nb = 2\na = 3",
+        indoc! {r#"
+          (module (expression_statement (assignment left:(_)@names))* )
+          {
+            node n
+            attr (n) names = (sort-by @names "source-text")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            names: [[syntax node identifier (3, 1)], [syntax node identifier (2, 1)], [syntax node identifier (1, 1)]]
+        "#},
+    );
+}
+
+#[test]
+fn can_sort_graph_nodes_by_attribute() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node a
+            attr (a) rank = 2
+            node b
+            attr (b) rank = 1
+            node c
+            node n
+            attr (n) sorted = (sort-by [a, b, c] "rank")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            rank: 2
+          node 1
+            rank: 1
+          node 2
+          node 3
+            sorted: [[graph node 2], [graph node 1], [graph node 0]]
+        "#},
+    );
+}
+
+#[test]
+fn cannot_sort_by_unknown_syntax_key() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) xs = (sort-by [@root] "no-such-key")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_map_function_over_list() {
+    check_execution(
+        "c = 1\nb = 2\na = 3",
+        indoc! {r#"
+          (module (expression_statement (assignment left:(_)@names))* )
+          {
+            node n
+            attr (n) names = (map @names &source-text)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            names: ["c", "b", "a"]
+        "#},
+    );
+}
+
+#[test]
+fn can_filter_list_by_predicate() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (filter [[], [1], [], [2, 3]] &is-empty)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [[], []]
+        "#},
+    );
+}
+
+#[test]
+fn variables_take_precedence_over_function_references() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            let is-empty = 1
+            node n
+            attr (n) xs = (plus is-empty 1)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: 2
+        "#},
+    );
+}
+
+#[test]
+fn cannot_map_undefined_function() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (map [1, 2] &no-such-function)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_describe_functions() {
+    struct Undocumented;
+
+    impl Function for Undocumented {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            _parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            Ok(Value::Null)
+        }
+    }
+
+    let mut functions = Functions::stdlib();
+    functions.add(Identifier::from("undocumented"), Undocumented);
+    let descriptions = functions.descriptions();
+
+    let names = descriptions
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect::<Vec<_>>();
+    let mut sorted_names = names.clone();
+    sorted_names.sort();
+    assert_eq!(names, sorted_names);
+
+    let sort_by = descriptions.iter().find(|d| d.name == "sort-by").unwrap();
+    assert_eq!(sort_by.signature, "(sort-by list key)");
+    assert!(!sort_by.doc.is_empty());
+
+    let node = descriptions.iter().find(|d| d.name == "node").unwrap();
+    assert_eq!(node.signature, "(node)");
+
+    let undocumented = descriptions
+        .iter()
+        .find(|d| d.name == "undocumented")
+        .unwrap();
+    assert_eq!(undocumented.signature, "(undocumented ...)");
+    assert_eq!(undocumented.doc, "");
+}
+
+#[test]
+fn can_flatten_list() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (flatten [[1, 2], 3, [], [[4], 5]])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [1, 2, 3, [4], 5]
+        "#},
+    );
+}
+
+#[test]
+fn can_unique_list_preserving_first_occurrence() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (unique [3, 1, 3, "a", 2, 1, "a"])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [3, 1, "a", 2]
+        "#},
+    );
+}
+
+#[test]
+fn can_zip_lists() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (zip [1, 2, 3] ["a", "b"])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [[1, "a"], [2, "b"]]
+        "#},
+    );
+}
+
+#[test]
+fn can_compute_set_algebra() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) union = (union {1, 2, 3} {3, 4})
+            attr (n) intersect = (intersect {1, 2, 3} {3, 4})
+            attr (n) difference = (difference {1, 2, 3} {3, 4})
+            attr (n) symmetric_difference = (symmetric-difference {1, 2, 3} {3, 4})
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            difference: {1, 2}
+            intersect: {3}
+            symmetric_difference: {1, 2, 4}
+            union: {1, 2, 3, 4}
+        "#},
+    );
+}
+
+#[test]
+fn can_build_and_inspect_maps() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            let meta = (map-of "line" 1 "kind" "def" "tags" ["a"])
+            node n
+            attr (n) meta = meta
+            attr (n) kind = (map-get meta "kind"), missing = (map-get meta "missing")
+            attr (n) keys = (map-keys meta), values = (map-values meta)
+            attr (n) is_map = (is-map meta), is_not_map = (is-map [])
+            attr (n) json = (json-encode meta)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            is_map: #true
+            is_not_map: #false
+            json: "{\"kind\":\"def\",\"line\":1,\"tags\":[\"a\"]}"
+            keys: ["kind", "line", "tags"]
+            kind: "def"
+            meta: {kind: "def", line: 1, tags: ["a"]}
+            missing: #null
+            values: ["def", 1, ["a"]]
+        "#},
+    );
+}
+
+#[test]
+fn cannot_build_map_with_missing_value() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) meta = (map-of "line" 1 "kind")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_build_and_inspect_spans() {
+    check_execution(
+        indoc! {r#"
+          pass
+          x = 1
+        "#},
+        indoc! {r#"
+          (module (pass_statement) @first (expression_statement) @second)
+          {
+            node n
+            attr (n) first = (span @first), both = (span @first @second)
+            attr (n) same = (eq (span @first) (span @first @first))
+            attr (n) is_span = (is-span (span @second)), is_not_span = (is-span @second)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            both: [span (1, 1)-(2, 6)]
+            first: [span (1, 1)-(1, 5)]
+            is_not_span: #false
+            is_span: #true
+            same: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_union_lists() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (union [1, 2] {3})
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_test_membership() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) in_list = (contains [1, 2, 3] 2)
+            attr (n) in_set = (contains {"a", "b"} "c")
+            attr (n) in_string = (contains "hello world" "o w")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            in_list: #true
+            in_set: #false
+            in_string: #true
+        "#},
+    );
+}
+
+#[test]
+fn can_test_membership_in_condition() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            if (contains ["module", "class"] (node-type @root)) {
+              attr (n) is_scope = #true
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            is_scope: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_test_membership_in_integer() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) x = (contains 12 1)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_convert_values() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          global filename
+          (module)
+          {
+            node n
+            attr (n) str = (to-string 42)
+            attr (n) int = (plus (to-int " 41 ") 1)
+            attr (n) bool = (to-bool "false")
+            attr (n) file = (to-string filename)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            bool: #false
+            file: "test.py"
+            int: 42
+            str: "42"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_convert_invalid_string_to_int() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) int = (to-int "forty-two")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_convert_invalid_string_to_bool() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) bool = (to-bool "yes")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_test_value_types() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) string = [(is-string "a"), (is-string 1)]
+            attr (n) int = [(is-int 1), (is-int "1")]
+            attr (n) list = [(is-list [1]), (is-list {1})]
+            attr (n) set = [(is-set {1}), (is-set [1])]
+            attr (n) syntax_node = [(is-syntax-node @root), (is-syntax-node n)]
+            attr (n) graph_node = [(is-graph-node n), (is-graph-node @root)]
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            graph_node: [#true, #false]
+            int: [#true, #false]
+            list: [#true, #false]
+            set: [#true, #false]
+            string: [#true, #false]
+            syntax_node: [#true, #false]
+        "#},
+    );
+}
+
+#[test]
+fn can_coalesce_values() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) first = (coalesce #null 1 2)
+            attr (n) none = (coalesce #null #null)
+            attr (n) empty = (coalesce)
+            attr (n) short_circuit = (coalesce "a" @root.undefined (node))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            empty: #null
+            first: 1
+            none: #null
+            short_circuit: "a"
+        "#},
+    );
+}
+
+#[test]
+fn can_find_ancestors() {
+    check_execution(
+        "def f():\n  return 1\n",
+        indoc! {r#"
+          (return_statement) @stmt
+          {
+            node n
+            attr (n) ancestors = (map (ancestors @stmt) &node-type)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            ancestors: ["block", "function_definition", "module"]
+        "#},
+    );
+}
+
+#[test]
+fn can_find_parent_of_type() {
+    check_execution(
+        "class C:\n  def f():\n    return 1\n",
+        indoc! {r#"
+          (return_statement) @stmt
+          {
+            node n
+            attr (n) scope = (node-type (parent-of-type @stmt "class_definition" "function_definition"))
+            attr (n) missing = (parent-of-type @stmt "lambda")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            missing: #null
+            scope: "function_definition"
+        "#},
+    );
+}
+
+#[test]
+fn can_find_named_siblings() {
+    check_execution(
+        "# comment\nx = 1\ny = 2\n",
+        indoc! {r##"
+          (module (expression_statement) @stmt)
+          {
+            node n
+            attr (n) text = (source-text @stmt)
+            attr (n) next = (next-named-sibling @stmt)
+            attr (n) previous = (source-text (previous-named-sibling @stmt))
+            attr (n) siblings = (map (named-siblings @stmt) &node-type)
+          }
+        "##},
+        indoc! {r##"
+          node 0
+            next: [syntax node expression_statement (3, 1)]
+            previous: "# comment"
+            siblings: ["comment", "expression_statement"]
+            text: "x = 1"
+          node 1
+            next: #null
+            previous: "x = 1"
+            siblings: ["comment", "expression_statement"]
+            text: "y = 2"
+        "##},
+    );
+}
+
+#[test]
+fn can_find_child_by_field() {
+    check_execution(
+        "def f():\n  pass\n",
+        indoc! {r#"
+          (function_definition) @def
+          {
+            node n
+            attr (n) name = (source-text (child-by-field @def "name"))
+            attr (n) return_type = (child-by-field @def "return_type")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "f"
+            return_type: #null
+        "#},
+    );
+}
+
+#[test]
+fn can_find_descendants_of_kind() {
+    check_execution(
+        "def f(a):\n  b = a\n  def g():\n    c = b\n",
+        indoc! {r#"
+          (function_definition name:(_)@name body:(_)@body)
+          {
+            node n
+            attr (n) name = (source-text @name)
+            attr (n) all = (map (descendants-of-kind @body "identifier") &source-text)
+            attr (n) shallow = (map (descendants-of-kind @body "identifier" 3) &source-text)
+            attr (n) none = (descendants-of-kind @body "identifier" 0)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            all: ["b", "a", "g", "c", "b"]
+            name: "f"
+            none: []
+            shallow: ["b", "a", "g"]
+          node 1
+            all: ["c", "b"]
+            name: "g"
+            none: []
+            shallow: ["c", "b"]
+        "#},
+    );
+}
+
+#[test]
+fn can_get_field_name() {
+    check_execution(
+        "x = y",
+        indoc! {r#"
+          (assignment (_) @child) @assign
+          {
+            node n
+            attr (n) text = (source-text @child)
+            attr (n) field = (field-name @child)
+            attr (n) root_field = (field-name (parent-of-type @assign "module"))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            field: "left"
+            root_field: #null
+            text: "x"
+          node 1
+            field: "right"
+            root_field: #null
+            text: "y"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_kind_id() {
+    check_execution(
+        "x = y",
+        indoc! {r#"
+          (assignment left:(_)@left right:(_)@right)
+          {
+            node n
+            attr (n) same_kind = (eq (kind-id @left) (kind-id @right))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            same_kind: #true
+        "#},
+    );
+}
+
+#[test]
+fn can_get_byte_offsets_and_text_between() {
+    check_execution(
+        "x  =  y",
+        indoc! {r#"
+          (assignment left:(_)@left right:(_)@right)
+          {
+            node n
+            attr (n) left = [(start-byte @left), (end-byte @left)]
+            attr (n) right = [(start-byte @right), (end-byte @right)]
+            attr (n) between = (text-between @left @right)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            between: "  =  "
+            left: [0, 1]
+            right: [6, 7]
+        "#},
+    );
+}
+
+#[test]
+fn cannot_get_text_between_nodes_out_of_order() {
+    fail_execution(
+        "x = y",
+        indoc! {r#"
+          (assignment left:(_)@left right:(_)@right)
+          {
+            node n
+            attr (n) between = (text-between @right @left)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_get_source_line() {
+    check_execution(
+        "def f():\n    return 1\n",
+        indoc! {r#"
+          (return_statement (_) @value)
+          {
+            node n
+            attr (n) node_line = (source-line @value)
+            attr (n) row_line = (source-line 0)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            node_line: "    return 1"
+            row_line: "def f():"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_get_source_line_past_end() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) line = (source-line 5)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_manipulate_paths() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          global filename
+          (module)
+          {
+            node n
+            attr (n) basename = (path-basename "src/pkg/mod.py")
+            attr (n) dirname = (path-dirname "src/pkg/mod.py")
+            attr (n) extension = (path-extension filename)
+            attr (n) no_extension = (path-extension "Makefile")
+            attr (n) join = (path-join "src" "pkg" filename)
+            attr (n) normalize = (path-normalize "./src/../lib/./pkg/../mod.py")
+            attr (n) normalize_parent = (path-normalize "../a/..")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            basename: "mod.py"
+            dirname: "src/pkg"
+            extension: "py"
+            join: "src/pkg/test.py"
+            no_extension: #null
+            normalize: "lib/mod.py"
+            normalize_parent: ".."
+        "#},
+    );
+}
+
+#[test]
+fn can_hash_strings() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) sha256 = (sha256 "abc")
+            attr (n) fnv1a = (fnv1a "a")
+            attr (n) fnv1a_empty = (fnv1a "")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            fnv1a: "af63dc4c8601ec8c"
+            fnv1a_empty: "cbf29ce484222325"
+            sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        "#},
+    );
+}
+
+#[test]
+fn can_generate_stable_ids() {
+    check_execution(
+        "x = y",
+        indoc! {r#"
+          (assignment left:(_)@left right:(_)@right)
+          {
+            node n
+            attr (n) same = (eq (stable-id "a" 1 @left) (stable-id "a" 1 @left))
+            attr (n) different_node = (eq (stable-id @left) (stable-id @right))
+            attr (n) different_split = (eq (stable-id "ab" "c") (stable-id "a" "bc"))
+            attr (n) set_order = (eq (stable-id {@left, @right}) (stable-id {@right, @left}))
+            attr (n) empty = (stable-id)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            different_node: #false
+            different_split: #false
+            empty: "e3b0c44298fc1c14"
+            same: #true
+            set_order: #true
+        "#},
+    );
+}
+
+#[test]
+fn can_gensym() {
+    check_execution(
+        "x = 1\ny = 2\n",
+        indoc! {r#"
+          (assignment)
+          {
+            node n
+            attr (n) sym = (gensym)
+            attr (n) prefixed = (gensym "tmp")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            prefixed: "tmp1"
+            sym: "g0"
+          node 1
+            prefixed: "tmp3"
+            sym: "g2"
+        "#},
+    );
+}
+
+#[test]
+fn gensym_counts_per_execution() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) sym = (gensym)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut graph = Graph::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    file.execute_into(&mut graph, &tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    file.execute_into(&mut graph, &tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    let config = config.first_symbol(10);
+    file.execute_into(&mut graph, &tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            sym: "g0"
+          node 1
+            sym: "g0"
+          node 2
+            sym: "g10"
+        "#}
+    );
+}
+
+#[test]
+fn can_encode_and_decode_base64() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) encoded = (base64-encode "def f(): pass")
+            attr (n) decoded = (base64-decode "ZGVmIGYoKTogcGFzcw==")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            decoded: "def f(): pass"
+            encoded: "ZGVmIGYoKTogcGFzcw=="
+        "#},
+    );
+}
+
+#[test]
+fn cannot_decode_invalid_base64() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) decoded = (base64-decode "not base64!")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_encode_and_decode_json() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) encoded = (json-encode [#null, #true, 1, "a", {2, 1}])
+            attr (n) decoded = (json-decode "{\"b\": [1, \"x\"], \"a\": null}")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            decoded: {a: #null, b: [1, "x"]}
+            encoded: "[null,true,1,\"a\",[1,2]]"
+        "#},
+    );
+}
+
+#[test]
+fn can_decode_encoded_maps_from_json() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            let meta = (map-of "k" 1 "nested" (map-of "list" [#true, "x"]))
+            attr (n) decoded = (json-decode (json-encode meta))
+            attr (n) same = (eq meta (json-decode (json-encode meta)))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            decoded: {k: 1, nested: {list: [#true, "x"]}}
+            same: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_encode_syntax_node_as_json() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) encoded = (json-encode @root)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_decode_negative_json_number() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) decoded = (json-decode "-1")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_inspect_edges() {
+    check_execution(
+        indoc! { r#"
+          pass
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            node @m.a
+            node @m.b
+            node @m.c
+            edge @m.a -> @m.b
+            edge @m.a -> @m.c
+            edge @m.c -> @m.a
+          }
+          (module) @m
+          {
+            attr (@m.a) out = (out-edges @m.a), in = (in-edges @m.a), neighbors = (neighbors @m.a)
+            attr (@m.b) out = (out-edges @m.b), in = (in-edges @m.b)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            in: [[graph node 2]]
+            neighbors: [[graph node 1], [graph node 2]]
+            out: [[graph node 1], [graph node 2]]
+          edge 0 -> 1
+          edge 0 -> 2
+          node 1
+            in: [[graph node 0]]
+            out: []
+          node 2
+          edge 2 -> 0
+        "#},
+    );
+}
+
+#[test]
+fn can_get_attributes() {
+    check_execution(
+        indoc! { r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            node @m.a
+            attr (@m.a) name = "a"
+          }
+          (module) @m
+          {
+            node n
+            attr (n) name = (get-attr @m.a "name"), missing = (get-attr @m.a "missing")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "a"
+          node 1
+            missing: #null
+            name: "a"
+        "#},
+    );
+}
+
+#[test]
+fn can_check_for_edges() {
+    check_execution(
+        indoc! { r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            node @m.a
+            node @m.b
+            edge @m.a -> @m.b
+            attr (@m.a) forward = (has-edge @m.a @m.b), backward = (has-edge @m.b @m.a)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            backward: #false
+            forward: #true
+          edge 0 -> 1
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn can_generate_random_ints() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) fixed = (random-int 7 7)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            fixed: 7
+        "#},
+    );
+}
+
+#[test]
+fn cannot_generate_random_ints_from_empty_range() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) value = (random-int 8 7)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn random_ints_depend_on_seed() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) a = (random-int 0 1000000), b = (random-int 0 1000000)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let execute = |seed| {
+        let config = ExecutionConfig::new(&functions, &globals).random_seed(seed);
+        let graph = file
+            .execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+        let result = graph.pretty_print().to_string();
+        result
+    };
+    assert_eq!(execute(1), execute(1));
+    assert_ne!(execute(1), execute(2));
+}
+
+#[test]
+fn can_get_fixed_time() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) iso = (now-iso8601), unix = (now-unix)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1683727392);
+    let config = ExecutionConfig::new(&functions, &globals).fixed_time(time);
+    let graph = file
+        .execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            iso: "2023-05-10T14:03:12Z"
+            unix: 1683727392
+        "#}
+    );
+}
+
+#[test]
+fn cannot_call_env_by_default() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) value = (env "PATH")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_read_environment_variables_when_enabled() {
+    std::env::set_var("TREE_SITTER_GRAPH_TEST_ENV", "value");
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) set = (env "TREE_SITTER_GRAPH_TEST_ENV")
+            attr (n) unset = (env "TREE_SITTER_GRAPH_TEST_UNSET_ENV")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut functions = Functions::stdlib();
+    functions.add_env();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            set: "value"
+            unset: #null
+        "#}
+    );
+}
+
+#[test]
+fn can_check_signatures_of_host_functions() {
+    struct Repeat;
+
+    impl Function for Repeat {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let string = parameters.param()?.into_string()?;
+            let count = match parameters.param() {
+                Ok(count) => count.into_integer()?,
+                Err(_) => 2,
+            };
+            parameters.finish()?;
+            Ok(Value::String(string.repeat(count as usize).into()))
+        }
+    }
+
+    let mut functions = Functions::new();
+    functions.add_with_signature(
+        Identifier::from("repeat"),
+        Signature::new()
+            .required(ParameterType::String)
+            .optional(ParameterType::Integer),
+        Repeat,
+    );
+    let description = &functions.descriptions()[0];
+    assert_eq!(description.signature, "(repeat string [integer])");
+
+    let mut graph = Graph::new();
+    let mut state = ExecutionState::new();
+    let mut call = |parameters: Vec<Value>| {
+        functions
+            .call(
+                &Identifier::from("repeat"),
+                &mut graph,
+                &mut state,
+                "",
+                &mut parameters.into_iter(),
+            )
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(call(vec!["ab".into()]), Ok(Value::from("abab")));
+    assert_eq!(call(vec!["ab".into(), 3.into()]), Ok(Value::from("ababab")));
+    assert_eq!(
+        call(vec![]),
+        Err("Invalid parameters for repeat: expected 1 to 2 parameters, got 0".to_string())
+    );
+    assert_eq!(
+        call(vec!["ab".into(), 3.into(), 4.into()]),
+        Err("Invalid parameters for repeat: expected 1 to 2 parameters, got 3".to_string())
+    );
+    assert_eq!(
+        call(vec!["ab".into(), "cd".into()]),
+        Err(
+            "Invalid parameters for repeat: expected parameter 2 to be an integer, got cd"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn can_check_types_against_signatures_of_host_functions() {
+    struct Name;
+
+    impl Function for Name {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let node = parameters.param()?;
+            parameters.finish()?;
+            Ok(Value::from(node.to_string()))
+        }
+    }
+
+    let mut functions = Functions::stdlib();
+    functions.add_with_signature(
+        Identifier::from("name"),
+        Signature::new()
+            .required(ParameterType::GraphNode)
+            .returns(ParameterType::String),
+        Name,
+    );
+    let check = |dsl: &str| {
+        let mut file =
+            File::from_str(tree_sitter_python::language(), dsl).expect("Cannot parse file");
+        file.check_with_functions(&functions)
+            .map_err(|e| e.to_string())
+    };
+    assert!(check(indoc! {r#"
+      (module)
+      {
+        node n
+        let name = (name n)
+        scan name { "x" { } }
+      }
+    "#})
+    .is_ok());
+    assert_eq!(
+        check(indoc! {r#"
+          (module) @m
+          {
+            let x = @m
+            print (name x)
+          }
+        "#}),
+        Err("Expected a graph node at (4, 3), got a syntax node from (3, 11)".to_string())
+    );
+    assert_eq!(
+        check(indoc! {r#"
+          (module)
+          {
+            node n
+            let name = (name n)
+            edge n -> name
+          }
+        "#}),
+        Err("Expected a graph node at (5, 3), got a string from (4, 3)".to_string())
+    );
+    assert_eq!(
+        check(indoc! {r#"
+          (module)
+          {
+            print (map [1, 2] &no-such-function)
+          }
+        "#}),
+        Err("Undefined function no-such-function at (3, 21)".to_string())
+    );
+}
+
+#[test]
+fn can_check_variadic_signatures_of_host_functions() {
+    let signature = Signature::new()
+        .required(ParameterType::String)
+        .variadic(ParameterType::Integer);
+    assert_eq!(signature.to_string(), "string integer...");
+    let name = Identifier::from("f");
+    assert!(signature.check(&name, &["a".into()]).is_ok());
+    assert!(signature
+        .check(&name, &["a".into(), 1.into(), 2.into()])
+        .is_ok());
+    assert!(signature.check(&name, &[]).is_err());
+    assert!(signature
+        .check(&name, &["a".into(), 1.into(), "b".into()])
+        .is_err());
+}
+
+#[test]
+fn can_call_host_functions_with_lazy_parameters() {
+    /// Returns its first parameter if it is true, and otherwise its second parameter, which is
+    /// only evaluated if it is needed.
+    struct OrElse;
+
+    impl Function for OrElse {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let first = parameters.param()?;
+            let second = parameters.param()?;
+            parameters.finish()?;
+            Ok(if first.as_boolean()? { first } else { second })
+        }
+
+        fn lazy_parameters(&self) -> bool {
+            true
+        }
+
+        fn call_lazy(
+            &self,
+            _source: &str,
+            parameters: &mut dyn LazyParameters,
+        ) -> Result<Value, ExecutionError> {
+            let first = parameters.param()?;
+            let result = if first.as_boolean()? {
+                parameters.skip()?;
+                first
+            } else {
+                parameters.param()?
+            };
+            parameters.finish()?;
+            Ok(result)
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) short = (or-else #true (node)), long = (or-else #false (node))
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut functions = Functions::stdlib();
+    functions.add(Identifier::from("or-else"), OrElse);
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            indoc! {r#"
+              node 0
+                long: [graph node 1]
+                short: #true
+              node 1
+            "#}
+        );
+    }
+}
+
+#[test]
+fn can_call_namespaced_functions() {
+    struct Shout;
+
+    impl Function for Shout {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let string = parameters.param()?.into_string()?;
+            parameters.finish()?;
+            Ok(Value::String(string.to_uppercase().into()))
+        }
+    }
+
+    let mut pack = Functions::new();
+    pack.add(Identifier::from("shout"), Shout);
+    let mut functions = Functions::stdlib();
+    functions.add_namespace("text", pack);
+    assert!(functions.get(&Identifier::from("shout")).is_none());
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) direct = (text::shout "a"), mapped = (map ["b", "c"] &text::shout)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            direct: "A"
+            mapped: ["B", "C"]
+        "#}
+    );
+}
+
+#[test]
+fn can_inspect_function_libraries() {
+    let mut pack = Functions::new();
+    pack.add(
+        Identifier::from("node"),
+        tree_sitter_graph::functions::stdlib::graph::Node,
+    );
+    let mut functions = Functions::new();
+    assert!(functions.is_empty());
+    functions.add(
+        Identifier::from("eq"),
+        tree_sitter_graph::functions::stdlib::Eq,
+    );
+    functions.add_namespace("graph", pack);
+
+    assert_eq!(functions.len(), 2);
+    assert!(functions.contains(&Identifier::from("graph::node")));
+    assert!(!functions.contains(&Identifier::from("node")));
+    assert_eq!(
+        functions.names(),
+        vec![Identifier::from("eq"), Identifier::from("graph::node")]
+    );
+
+    let node = functions
+        .describe(&Identifier::from("graph::node"))
+        .unwrap();
+    assert_eq!(node.signature, "(graph::node)");
+    assert_eq!(node.namespace(), Some("graph"));
+    assert_eq!(node.local_name(), "node");
+    let eq = functions.describe(&Identifier::from("eq")).unwrap();
+    assert_eq!(eq.namespace(), None);
+    assert_eq!(eq.local_name(), "eq");
+    assert!(functions.describe(&Identifier::from("missing")).is_none());
+}
+
+#[test]
+fn can_keep_function_state_during_execution() {
+    struct Counter(u32);
+
+    struct Count;
+
+    impl Function for Count {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            parameters.finish()?;
+            let counter = state.function_state().get_mut::<Counter>().unwrap();
+            counter.0 += 1;
+            Ok(Value::Integer(counter.0))
+        }
+
+        fn on_execution_start(&self, state: &mut FunctionState) {
+            state.insert(Counter(0));
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) first = (count), second = (count)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut functions = Functions::stdlib();
+    functions.add(Identifier::from("count"), Count);
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    for _ in 0..2 {
+        let graph = file
+            .execute(&tree, "pass", &config, &NoCancellation)
+            .expect("Cannot execute file");
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            indoc! {r#"
+              node 0
+                first: 1
+                second: 2
+            "#}
+        );
+    }
+}
+
+#[cfg(feature = "exec-capture")]
+fn execute_exec_capture(dsl_source: &str, allow: bool) -> Result<String, ExecutionError> {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals).allow_exec_capture(allow);
+    let graph = file.execute(&tree, "pass", &config, &NoCancellation)?;
+    let result = graph.pretty_print().to_string();
+    Ok(result)
+}
+
+#[cfg(all(unix, feature = "exec-capture"))]
+#[test]
+fn can_capture_output_of_external_process() {
+    let dsl_source = indoc! {r#"
+      (module)
+      {
+        node n
+        attr (n) output = (exec-capture "echo" "hello" "$HOME")
+      }
+    "#};
+    assert_eq!(
+        execute_exec_capture(dsl_source, true).unwrap(),
+        indoc! {r#"
+          node 0
+            output: "hello $HOME"
+        "#}
+    );
+    assert!(execute_exec_capture(dsl_source, false).is_err());
+}
+
+#[cfg(all(unix, feature = "exec-capture"))]
+#[test]
+fn cannot_capture_output_of_failing_process() {
+    let dsl_source = indoc! {r#"
+      (module)
+      {
+        node n
+        attr (n) output = (exec-capture "false")
+      }
+    "#};
+    assert!(execute_exec_capture(dsl_source, true).is_err());
+}
+
+#[test]
+fn can_cache_results_of_pure_functions() {
+    struct Calls(Arc<AtomicUsize>);
+
+    impl Function for Calls {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _state: &mut ExecutionState,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let value = parameters.param()?;
+            parameters.finish()?;
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(value)
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) a = (calls "x"), b = (calls "x"), c = (calls "y")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut functions = Functions::stdlib();
+    functions.add_pure(Identifier::from("calls"), Calls(calls.clone()));
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        calls.store(0, Ordering::SeqCst);
+        for _ in 0..2 {
+            let graph = file
+                .execute(&tree, "pass", &config, &NoCancellation)
+                .expect("Cannot execute file");
+            assert_eq!(
+                graph.pretty_print().to_string(),
+                indoc! {r#"
+                  node 0
+                    a: "x"
+                    b: "x"
+                    c: "y"
+                "#}
+            );
+        }
+        // the results are only cached within each execution
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -5,23 +5,73 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::sync::Arc;
+
 use indoc::indoc;
 use tree_sitter::Parser;
+use tree_sitter_graph::ast::File;
+use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::graph::AttributeSchema;
+use tree_sitter_graph::graph::ConflictPolicy;
+use tree_sitter_graph::graph::CypherOptions;
+use tree_sitter_graph::graph::DotOptions;
+use tree_sitter_graph::graph::EdgePattern;
 use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::GraphBuilder;
+use tree_sitter_graph::graph::GraphNodeRef;
+use tree_sitter_graph::graph::GraphSchema;
+use tree_sitter_graph::graph::GraphSink;
+use tree_sitter_graph::graph::JsonStyle;
+use tree_sitter_graph::graph::NodePattern;
+use tree_sitter_graph::graph::PathPattern;
+use tree_sitter_graph::graph::SchemaElement;
+use tree_sitter_graph::graph::SchemaError;
+use tree_sitter_graph::graph::SourcePosition;
+use tree_sitter_graph::graph::StoredAttributes;
+use tree_sitter_graph::graph::StoredAttributesMut;
+use tree_sitter_graph::graph::TurtleOptions;
 use tree_sitter_graph::graph::Value;
+use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::Identifier;
+use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::Variables;
 
 #[test]
 fn can_overwrite_attributes() {
     let mut graph = Graph::new();
     let node = graph.add_graph_node();
-    let attrs = &mut graph[node].attributes;
+    let mut attrs = graph.node_mut(node).into_attributes();
     let name = Identifier::from("name");
     attrs.add(name.clone(), "node0").unwrap();
     attrs.add(name.clone(), "overwritten").unwrap_err();
     assert_eq!(*attrs.get(&name).unwrap(), Value::from("overwritten"));
 }
 
+#[test]
+fn can_look_up_attributes_added_in_any_order() {
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node).into_attributes();
+    for name in ["c", "a", "d", "b"] {
+        attrs.add(Identifier::from(name), name).unwrap();
+    }
+    for name in ["a", "b", "c", "d"] {
+        assert_eq!(attrs.get(name), Some(&Value::from(name)));
+    }
+    assert_eq!(attrs.get("e"), None);
+    let names = |attrs: &StoredAttributesMut| {
+        attrs
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&attrs), vec!["c", "a", "d", "b"]);
+    attrs.add(Identifier::from("a"), "replaced").unwrap_err();
+    assert_eq!(names(&attrs), vec!["c", "a", "d", "b"]);
+    attrs.sort_by_name();
+    assert_eq!(names(&attrs), vec!["a", "b", "c", "d"]);
+}
+
 #[test]
 fn can_iterate_graph_nodes() {
     let mut graph = Graph::new();
@@ -38,15 +88,53 @@ fn can_iterate_graph_edges() {
     let node0 = graph.add_graph_node();
     let node1 = graph.add_graph_node();
     let node2 = graph.add_graph_node();
-    let _ = graph[node0].add_edge(node1);
-    let _ = graph[node0].add_edge(node2);
-    let edges = graph[node0]
+    let _ = graph.node_mut(node0).add_edge(node1);
+    let _ = graph.node_mut(node0).add_edge(node2);
+    let edges = graph
+        .node(node0)
         .iter_edges()
         .map(|(node, _)| node)
         .collect::<Vec<_>>();
     assert_eq!(edges, vec![node1, node2]);
 }
 
+#[test]
+fn can_add_edges_in_any_order() {
+    let mut graph = Graph::new();
+    let nodes = (0..10).map(|_| graph.add_graph_node()).collect::<Vec<_>>();
+    // interleave the edges of two nodes, so that both have to move their edges as they grow
+    for i in [7, 2, 9, 0, 5, 3, 8, 1, 6, 4] {
+        for source in [nodes[0], nodes[1]] {
+            let mut edge = graph
+                .node_mut(source)
+                .add_edge(nodes[i])
+                .unwrap_or_else(|_| unreachable!());
+            edge.attributes()
+                .add(Identifier::from("sink"), i as u32)
+                .unwrap();
+        }
+    }
+    assert!(graph.node_mut(nodes[0]).add_edge(nodes[5]).is_err());
+    assert!(graph.node_mut(nodes[1]).remove_edge(nodes[3]).is_some());
+    let edges = |source: GraphNodeRef| {
+        graph
+            .node(source)
+            .iter_edges()
+            .map(|(sink, edge)| (sink.index(), edge.attributes().get("sink").cloned()))
+            .collect::<Vec<_>>()
+    };
+    let expected = (0..10)
+        .map(|i| (i, Some(Value::from(i as u32))))
+        .collect::<Vec<_>>();
+    assert_eq!(edges(nodes[0]), expected);
+    let expected = expected
+        .into_iter()
+        .filter(|(i, _)| *i != 3)
+        .collect::<Vec<_>>();
+    assert_eq!(edges(nodes[1]), expected);
+    assert_eq!(graph.node(nodes[2]).edge_count(), 0);
+}
+
 #[test]
 fn can_display_graph() {
     let python_source = "pass";
@@ -57,33 +145,39 @@ fn can_display_graph() {
     let mut graph = Graph::new();
     let root = graph.add_syntax_node(tree.root_node());
     let node0 = graph.add_graph_node();
-    graph[node0]
-        .attributes
+    graph
+        .node_mut(node0)
+        .attributes()
         .add(Identifier::from("name"), "node0")
         .unwrap();
-    graph[node0]
-        .attributes
+    graph
+        .node_mut(node0)
+        .attributes()
         .add(Identifier::from("source"), root)
         .unwrap();
     let node1 = graph.add_graph_node();
-    graph[node1]
-        .attributes
+    graph
+        .node_mut(node1)
+        .attributes()
         .add(Identifier::from("name"), "node1")
         .unwrap();
     let node2 = graph.add_graph_node();
-    graph[node2]
-        .attributes
+    graph
+        .node_mut(node2)
+        .attributes()
         .add(Identifier::from("name"), "node2")
         .unwrap();
-    graph[node2]
-        .attributes
+    graph
+        .node_mut(node2)
+        .attributes()
         .add(Identifier::from("parent"), node1)
         .unwrap();
-    let edge01 = graph[node0]
+    let mut edge01 = graph
+        .node_mut(node0)
         .add_edge(node1)
         .unwrap_or_else(|_| unreachable!());
     edge01
-        .attributes
+        .attributes()
         .add(Identifier::from("precedence"), 14)
         .unwrap();
     assert_eq!(
@@ -102,3 +196,1345 @@ fn can_display_graph() {
         "#}
     );
 }
+
+#[test]
+fn can_display_graph_as_gexf() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "<node0>")
+        .unwrap();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("weight"), 3)
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("weight"), "heavy")
+        .unwrap();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("missing"), Value::Null)
+        .unwrap();
+    let mut edge01 = graph
+        .node_mut(node0)
+        .add_edge(node1)
+        .unwrap_or_else(|_| unreachable!());
+    edge01
+        .attributes()
+        .add(Identifier::from("precedence"), 14)
+        .unwrap();
+    let _ = graph.node_mut(node1).add_edge(node0);
+    assert_eq!(
+        graph.to_gexf().to_string(),
+        indoc! {r#"
+          <?xml version="1.0" encoding="UTF-8"?>
+          <gexf xmlns="http://gexf.net/1.3" version="1.3">
+            <graph defaultedgetype="directed">
+              <attributes class="node">
+                <attribute id="name" title="name" type="string"/>
+                <attribute id="weight" title="weight" type="string"/>
+              </attributes>
+              <attributes class="edge">
+                <attribute id="precedence" title="precedence" type="integer"/>
+              </attributes>
+              <nodes>
+                <node id="0" label="0">
+                  <attvalues>
+                    <attvalue for="name" value="&lt;node0&gt;"/>
+                    <attvalue for="weight" value="3"/>
+                  </attvalues>
+                </node>
+                <node id="1" label="1">
+                  <attvalues>
+                    <attvalue for="weight" value="heavy"/>
+                  </attvalues>
+                </node>
+              </nodes>
+              <edges>
+                <edge id="0" source="0" target="1">
+                  <attvalues>
+                    <attvalue for="precedence" value="14"/>
+                  </attvalues>
+                </edge>
+                <edge id="1" source="1" target="0"/>
+              </edges>
+            </graph>
+          </gexf>
+        "#}
+    );
+}
+
+#[test]
+fn can_display_graph_as_mermaid() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "say \"hi\" #1")
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    let _ = graph.node_mut(node0).add_edge(node1);
+    assert_eq!(
+        graph.to_mermaid(Some("name")).to_string(),
+        indoc! {r#"
+          flowchart TD
+            n0["say #quot;hi#quot; #35;1"]
+            n1["1"]
+            n0 --> n1
+        "#}
+    );
+    assert_eq!(
+        graph.to_mermaid(None).to_string(),
+        indoc! {r#"
+          flowchart TD
+            n0["0"]
+            n1["1"]
+            n0 --> n1
+        "#}
+    );
+}
+
+#[test]
+fn can_display_graph_as_dot() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node0).into_attributes();
+    attrs.add(Identifier::from("name"), "main").unwrap();
+    attrs.add(Identifier::from("kind"), "function").unwrap();
+    attrs.add(Identifier::from("shape"), "box").unwrap();
+    attrs.add(Identifier::from("file"), "a.py").unwrap();
+    let node1 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node1).into_attributes();
+    attrs.add(Identifier::from("name"), "\"x\"").unwrap();
+    attrs.add(Identifier::from("color"), "red").unwrap();
+    let _ = graph.node_mut(node0).add_edge(node1);
+
+    assert_eq!(
+        graph.to_dot(&DotOptions::new()).to_string(),
+        indoc! {r#"
+          digraph {
+            n0 [label="0"];
+            n1 [label="1"];
+            n0 -> n1;
+          }
+        "#}
+    );
+
+    let options = DotOptions::new()
+        .shape_attr("shape")
+        .color_attr("color")
+        .label_template("{}: {name} ({kind})")
+        .cluster_attr("file");
+    assert_eq!(
+        graph.to_dot(&options).to_string(),
+        indoc! {r#"
+          digraph {
+            n1 [label="1: \"x\" ()" color="red"];
+            subgraph cluster_0 {
+              label="a.py";
+              n0 [label="0: main (function)" shape="box"];
+            }
+            n0 -> n1;
+          }
+        "#}
+    );
+}
+
+#[test]
+fn can_write_graph_as_ndjson() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    let mut edge = match graph.node_mut(node0).add_edge(node1) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("weight"), 2)
+        .unwrap();
+    let mut output = Vec::new();
+    graph.write_ndjson(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        indoc! {r#"
+          {"type":"node","id":0,"attrs":{"name":{"type":"string","string":"a"}}}
+          {"type":"node","id":1,"attrs":{}}
+          {"type":"edge","source":0,"sink":1,"attrs":{"weight":{"type":"int","int":2}}}
+        "#}
+    );
+}
+
+#[test]
+fn can_write_graph_as_versioned_json() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    let _ = graph.node_mut(node0).add_edge(node1);
+
+    let mut output = Vec::new();
+    graph.write_json(&mut output, JsonStyle::Compact).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        concat!(
+            r#"{"version":1,"nodes":["#,
+            r#"{"id":0,"edges":[{"sink":1,"attrs":{}}],"attrs":{"name":{"type":"string","string":"a"}}},"#,
+            r#"{"id":1,"edges":[],"attrs":{}}"#,
+            r#"]}"#,
+        )
+    );
+
+    let mut output = Vec::new();
+    graph.write_json(&mut output, JsonStyle::Pretty).unwrap();
+    let pretty: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(
+        pretty["version"],
+        tree_sitter_graph::graph::JSON_FORMAT_VERSION
+    );
+}
+
+#[test]
+fn json_schema_describes_current_version() {
+    let schema: serde_json::Value =
+        serde_json::from_str(tree_sitter_graph::graph::JSON_SCHEMA).unwrap();
+    assert_eq!(
+        schema["properties"]["version"]["const"],
+        tree_sitter_graph::graph::JSON_FORMAT_VERSION
+    );
+}
+
+#[test]
+fn can_display_graph_as_cypher() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node0).into_attributes();
+    attrs.add(Identifier::from("name"), "it's").unwrap();
+    attrs.add(Identifier::from("line"), 3).unwrap();
+    let node1 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node1).into_attributes();
+    attrs.add(Identifier::from("name"), "b").unwrap();
+    attrs.add(Identifier::from("source-file"), "a.py").unwrap();
+    let mut edge = match graph.node_mut(node0).add_edge(node1) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("precedence"), 1)
+        .unwrap();
+
+    assert_eq!(
+        graph.to_cypher(&CypherOptions::new()).to_string(),
+        indoc! {r#"
+          CREATE (n:Node {id: 0, line: 3, name: 'it\'s'});
+          CREATE (n:Node {id: 1, name: 'b', `source-file`: 'a.py'});
+          MATCH (a:Node {id: 0}), (b:Node {id: 1}) CREATE (a)-[:EDGE {precedence: 1}]->(b);
+        "#}
+    );
+
+    let options = CypherOptions::new()
+        .node_label("Symbol")
+        .edge_type("REFERS_TO")
+        .identity_attrs(&["name"]);
+    assert_eq!(
+        graph.to_cypher(&options).to_string(),
+        indoc! {r#"
+          MERGE (n:Symbol {name: 'it\'s'}) SET n += {line: 3};
+          MERGE (n:Symbol {name: 'b'}) SET n += {`source-file`: 'a.py'};
+          MATCH (a:Symbol {name: 'it\'s'}), (b:Symbol {name: 'b'}) CREATE (a)-[:REFERS_TO {precedence: 1}]->(b);
+        "#}
+    );
+}
+
+#[test]
+fn can_write_graph_to_sink() {
+    #[derive(Default)]
+    struct RecordingSink(Vec<String>);
+
+    impl GraphSink for RecordingSink {
+        type Error = ();
+
+        fn node(&mut self, index: usize, attributes: StoredAttributes) -> Result<(), ()> {
+            self.0
+                .push(format!("node {} {}", index, attributes.iter().count()));
+            Ok(())
+        }
+
+        fn edge(&mut self, source: usize, sink: usize, _: StoredAttributes) -> Result<(), ()> {
+            self.0.push(format!("edge {} -> {}", source, sink));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), ()> {
+            self.0.push("finish".to_string());
+            Ok(())
+        }
+    }
+
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("name"), "b")
+        .unwrap();
+    let _ = graph.node_mut(node1).add_edge(node0);
+    let mut sink = RecordingSink::default();
+    graph.write_to_sink(&mut sink).unwrap();
+    assert_eq!(
+        sink.0,
+        vec!["node 0 0", "node 1 1", "edge 1 -> 0", "finish"]
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn can_write_graph_to_sqlite() {
+    use tree_sitter_graph::graph::SqliteSink;
+
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("line"), 7)
+        .unwrap();
+    let mut edge = match graph.node_mut(node0).add_edge(node1) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("precedence"), true)
+        .unwrap();
+
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    let mut sink = SqliteSink::new(connection).unwrap();
+    graph.write_to_sink(&mut sink).unwrap();
+    let connection = sink.into_connection();
+
+    let node: i64 = connection
+        .query_row(
+            "SELECT node FROM node_attributes WHERE name = 'name' AND value = 'a'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(node, 0);
+    let line: i64 = connection
+        .query_row(
+            "SELECT value FROM node_attributes WHERE node = 1 AND name = 'line'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(line, 7);
+    let (source, sink, precedence): (i64, i64, i64) = connection
+        .query_row(
+            "SELECT source, sink, value FROM edges JOIN edge_attributes ON edge = edges.id",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!((source, sink, precedence), (0, 1, 1));
+}
+
+#[test]
+fn can_display_graph_as_turtle() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node0).into_attributes();
+    attrs.add(Identifier::from("name"), "say \"hi\"").unwrap();
+    attrs
+        .add(
+            Identifier::from("tags"),
+            Value::Set(
+                vec![Value::from("a"), Value::from("b")]
+                    .into_iter()
+                    .collect(),
+            ),
+        )
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("parent"), node0)
+        .unwrap();
+    let mut edge = match graph.node_mut(node0).add_edge(node1) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("precedence"), 1)
+        .unwrap();
+
+    let options = TurtleOptions::new("http://example.org/graph/")
+        .predicate("name", "http://schema.org/name")
+        .edge_predicate("http://example.org/vocab#flowsTo");
+    assert_eq!(
+        graph.to_turtle(&options).to_string(),
+        indoc! {r#"
+          @base <http://example.org/graph/> .
+          @prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+          <node/0> <http://schema.org/name> "say \"hi\"" .
+          <node/0> <attr/tags> "a", "b" .
+          <node/1> <attr/parent> <node/0> .
+          <node/0> <http://example.org/vocab#flowsTo> <node/1> .
+          <edge/0> rdf:subject <node/0> ; rdf:predicate <http://example.org/vocab#flowsTo> ; rdf:object <node/1> .
+          <edge/0> <attr/precedence> 1 .
+        "#}
+    );
+}
+
+#[test]
+fn can_round_trip_graph_through_binary() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node0).into_attributes();
+    attrs.add(Identifier::from("name"), "a").unwrap();
+    attrs.add(Identifier::from("line"), 300).unwrap();
+    attrs.add(Identifier::from("exported"), true).unwrap();
+    attrs.add(Identifier::from("missing"), Value::Null).unwrap();
+    attrs
+        .add(
+            Identifier::from("refs"),
+            Value::List(vec![node1.into(), Value::from("b")]),
+        )
+        .unwrap();
+    attrs
+        .add(
+            Identifier::from("meta"),
+            Value::Map(
+                vec![
+                    (Identifier::from("kind"), Value::from("def")),
+                    (Identifier::from("ref"), node1.into()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        )
+        .unwrap();
+    attrs
+        .add(
+            Identifier::from("span"),
+            Value::Span {
+                start: SourcePosition {
+                    byte: 4,
+                    row: 1,
+                    column: 0,
+                },
+                end: SourcePosition {
+                    byte: 300,
+                    row: 12,
+                    column: 7,
+                },
+            },
+        )
+        .unwrap();
+    let mut edge = match graph.node_mut(node1).add_edge(node0) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("name"), "edge")
+        .unwrap();
+
+    let bytes = graph.to_binary();
+    let decoded = Graph::from_binary(&bytes).unwrap();
+    assert_eq!(
+        decoded.pretty_print().to_string(),
+        graph.pretty_print().to_string()
+    );
+    assert_eq!(decoded.to_binary(), bytes);
+}
+
+#[test]
+fn cannot_decode_invalid_binary_graph() {
+    assert!(Graph::from_binary(b"").is_err());
+    assert!(Graph::from_binary(b"TSGB\x02").is_err());
+    let mut graph = Graph::new();
+    graph.add_graph_node();
+    let mut bytes = graph.to_binary();
+    bytes.pop();
+    assert!(Graph::from_binary(&bytes).is_err());
+}
+
+#[test]
+fn can_round_trip_graph_through_json() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let mut graph = Graph::new();
+    let syntax_node = graph.add_syntax_node(tree.root_node());
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let mut attrs = graph.node_mut(node0).into_attributes();
+    attrs.add(Identifier::from("node"), syntax_node).unwrap();
+    attrs.add(Identifier::from("line"), 3).unwrap();
+    attrs
+        .add(Identifier::from("span"), tree.root_node().range())
+        .unwrap();
+    attrs
+        .add(
+            Identifier::from("refs"),
+            Value::Set(vec![node1.into(), Value::Null].into_iter().collect()),
+        )
+        .unwrap();
+    attrs
+        .add(
+            Identifier::from("meta"),
+            Value::Map(
+                vec![(Identifier::from("nested"), Value::List(vec![3.into()]))]
+                    .into_iter()
+                    .collect(),
+            ),
+        )
+        .unwrap();
+    let mut edge = match graph.node_mut(node1).add_edge(node0) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("exported"), false)
+        .unwrap();
+
+    let mut json = Vec::new();
+    graph.write_json(&mut json, JsonStyle::Compact).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    let mut decoded = Graph::from_json(&json).unwrap();
+    assert_eq!(
+        decoded.pretty_print().to_string(),
+        graph.pretty_print().to_string()
+    );
+
+    let node2 = decoded.add_graph_node();
+    let _ = decoded.node_mut(node2).add_edge(node0);
+    assert_eq!(decoded.node_count(), 3);
+}
+
+#[test]
+fn cannot_decode_json_graph_with_other_version() {
+    let result = Graph::from_json(r#"{"version":0,"nodes":[]}"#);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn can_deserialize_graph_with_serde() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    let _ = graph.node_mut(node0).add_edge(node0);
+
+    let json = serde_json::to_string(&graph).unwrap();
+    let decoded: Graph = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        decoded.pretty_print().to_string(),
+        graph.pretty_print().to_string()
+    );
+
+    let value: Value =
+        serde_json::from_str(r#"{"type":"list","values":[{"type":"int","int":1}]}"#).unwrap();
+    assert_eq!(value, Value::List(vec![Value::Integer(1)]));
+
+    let edge = graph.node(node0).get_edge(node0).unwrap();
+    let json = serde_json::to_string(&edge).unwrap();
+    assert_eq!(json, r#"{"attrs":{}}"#);
+
+    let json = serde_json::to_string(&graph.node(node0).attributes()).unwrap();
+    let decoded: tree_sitter_graph::graph::Attributes = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.get("name"), Some(&Value::from("a")));
+}
+
+#[test]
+fn can_merge_graphs_by_identity() {
+    let name = Identifier::from("name");
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    graph
+        .node_mut(a)
+        .attributes()
+        .add(name.clone(), "a")
+        .unwrap();
+    graph
+        .node_mut(a)
+        .attributes()
+        .add(Identifier::from("file"), "one.py")
+        .unwrap();
+
+    let mut other = Graph::new();
+    let other_b = other.add_graph_node();
+    other
+        .node_mut(other_b)
+        .attributes()
+        .add(name.clone(), "b")
+        .unwrap();
+    let other_a = other.add_graph_node();
+    other
+        .node_mut(other_a)
+        .attributes()
+        .add(name.clone(), "a")
+        .unwrap();
+    other
+        .node_mut(other_a)
+        .attributes()
+        .add(Identifier::from("file"), "two.py")
+        .unwrap();
+    other
+        .node_mut(other_a)
+        .attributes()
+        .add(Identifier::from("target"), other_b)
+        .unwrap();
+    let _ = other.node_mut(other_a).add_edge(other_b);
+
+    let identity = [name];
+    let mut merged = Graph::new();
+    merged
+        .merge(&graph, &identity, ConflictPolicy::Error)
+        .unwrap();
+    assert!(merged
+        .merge(&other, &identity, ConflictPolicy::Error)
+        .is_err());
+
+    let mapping = graph
+        .merge(&other, &identity, ConflictPolicy::KeepExisting)
+        .unwrap();
+    assert_eq!(mapping[0].index(), 1);
+    assert_eq!(mapping[1], a);
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            file: "one.py"
+            name: "a"
+            target: [graph node 1]
+          edge 0 -> 1
+          node 1
+            name: "b"
+        "#}
+    );
+}
+
+#[test]
+fn can_find_paths_matching_pattern() {
+    let kind = Identifier::from("kind");
+    let label = Identifier::from("label");
+    let mut graph = Graph::new();
+    let definition = graph.add_graph_node();
+    graph
+        .node_mut(definition)
+        .attributes()
+        .add(kind.clone(), "definition")
+        .unwrap();
+    let call = graph.add_graph_node();
+    graph
+        .node_mut(call)
+        .attributes()
+        .add(kind.clone(), "call")
+        .unwrap();
+    let other = graph.add_graph_node();
+    graph
+        .node_mut(other)
+        .attributes()
+        .add(kind.clone(), "call")
+        .unwrap();
+    for (sink, edge_label) in [(call, "contains"), (other, "references")] {
+        let mut edge = match graph.node_mut(definition).add_edge(sink) {
+            Ok(edge) => edge,
+            Err(edge) => edge,
+        };
+        edge.attributes().add(label.clone(), edge_label).unwrap();
+    }
+
+    let pattern = PathPattern::new(NodePattern::new("def").attr("kind", "definition")).then(
+        EdgePattern::new().attr("label", "contains"),
+        NodePattern::new("call").attr("kind", "call"),
+    );
+    let matches = graph.find_paths(&pattern);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].get("def"), Some(definition));
+    assert_eq!(matches[0].get("call"), Some(call));
+    assert_eq!(
+        matches[0].nodes().collect::<Vec<_>>(),
+        vec![definition, call]
+    );
+
+    let pattern = PathPattern::new(NodePattern::new("def")).then(
+        EdgePattern::new(),
+        NodePattern::new("target").has_attr("kind"),
+    );
+    assert_eq!(graph.find_paths(&pattern).len(), 2);
+
+    let cycle =
+        PathPattern::new(NodePattern::new("a")).then(EdgePattern::new(), NodePattern::new("a"));
+    assert!(graph.find_paths(&cycle).is_empty());
+}
+
+#[test]
+fn can_index_nodes_by_attribute() {
+    let symbol = Identifier::from("symbol");
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    graph
+        .node_mut(a)
+        .attributes()
+        .add(symbol.clone(), "x")
+        .unwrap();
+    let b = graph.add_graph_node();
+    graph
+        .node_mut(b)
+        .attributes()
+        .add(symbol.clone(), "y")
+        .unwrap();
+    let c = graph.add_graph_node();
+    graph
+        .node_mut(c)
+        .attributes()
+        .add(symbol.clone(), "x")
+        .unwrap();
+    graph.add_graph_node();
+
+    let index = graph.index_by_attribute(&symbol);
+    assert_eq!(index.get(&Value::from("x")), &[a, c]);
+    assert_eq!(index.get(&Value::from("y")), &[b]);
+    assert!(index.get(&Value::from("z")).is_empty());
+    assert_eq!(index.iter().count(), 2);
+
+    graph
+        .node_mut(b)
+        .attributes()
+        .add(symbol.clone(), "z")
+        .unwrap_err();
+    let index = graph.index_by_attribute(&symbol);
+    assert!(index.get(&Value::from("y")).is_empty());
+    assert_eq!(index.get(&Value::from("z")), &[b]);
+}
+
+#[test]
+fn can_find_graph_nodes_for_syntax_nodes() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("x = 1", None).unwrap();
+    let module = tree.root_node();
+    let assignment = module.child(0).unwrap().child(0).unwrap();
+    let name = assignment.child_by_field_name("left").unwrap();
+
+    let mut graph = Graph::new();
+    let module_ref = graph.add_syntax_node(module);
+    let name_ref = graph.add_syntax_node(name);
+    let a = graph.add_graph_node();
+    graph
+        .node_mut(a)
+        .attributes()
+        .add(Identifier::from("node"), module_ref)
+        .unwrap();
+    let b = graph.add_graph_node();
+    graph
+        .node_mut(b)
+        .attributes()
+        .add(
+            Identifier::from("nodes"),
+            Value::List(vec![name_ref.into(), module_ref.into()]),
+        )
+        .unwrap();
+
+    assert_eq!(graph.graph_nodes_for_syntax_node(module_ref), vec![a, b]);
+    assert_eq!(graph.graph_nodes_for_syntax_node(name_ref), vec![b]);
+    assert_eq!(
+        graph.graph_nodes_at(tree_sitter_graph::Location { row: 0, column: 0 }),
+        vec![b, a]
+    );
+    assert_eq!(
+        graph.graph_nodes_at(tree_sitter_graph::Location { row: 0, column: 4 }),
+        vec![a, b]
+    );
+}
+
+#[test]
+fn can_iterate_incoming_edges() {
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    let b = graph.add_graph_node();
+    let c = graph.add_graph_node();
+    let mut edge = match graph.node_mut(c).add_edge(b) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("label"), "cb")
+        .unwrap();
+    let _ = graph.node_mut(a).add_edge(b);
+    let incoming = graph
+        .incoming_edges(b)
+        .map(|(source, edge)| (source, edge.attributes().get("label").cloned()))
+        .collect::<Vec<_>>();
+    assert_eq!(incoming, vec![(a, None), (c, Some(Value::from("cb")))]);
+    assert_eq!(graph.incoming_edge_count(a), 0);
+
+    let d = graph.add_graph_node();
+    assert_eq!(graph.incoming_edge_count(d), 0);
+    let _ = graph.node_mut(d).add_edge(a);
+    assert_eq!(
+        graph
+            .incoming_edges(a)
+            .map(|(source, _)| source)
+            .collect::<Vec<_>>(),
+        vec![d]
+    );
+}
+
+#[test]
+fn can_collect_graph_stats() {
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    let b = graph.add_graph_node();
+    let c = graph.add_graph_node();
+    graph
+        .node_mut(a)
+        .attributes()
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    graph
+        .node_mut(b)
+        .attributes()
+        .add(Identifier::from("name"), "b")
+        .unwrap();
+    graph
+        .node_mut(b)
+        .attributes()
+        .add(Identifier::from("kind"), "call")
+        .unwrap();
+    let mut edge = match graph.node_mut(a).add_edge(b) {
+        Ok(edge) => edge,
+        Err(edge) => edge,
+    };
+    edge.attributes()
+        .add(Identifier::from("label"), "ab")
+        .unwrap();
+    let _ = graph.node_mut(a).add_edge(c);
+    let _ = graph.node_mut(b).add_edge(c);
+
+    let stats = graph.stats();
+    assert_eq!(stats.node_count, 3);
+    assert_eq!(stats.edge_count, 3);
+    assert_eq!(stats.syntax_node_count, 0);
+    assert_eq!(stats.node_attribute_count, 3);
+    assert_eq!(stats.edge_attribute_count, 1);
+    assert_eq!(
+        stats.out_degrees.into_iter().collect::<Vec<_>>(),
+        vec![(0, 1), (1, 1), (2, 1)]
+    );
+    assert_eq!(
+        stats.in_degrees.into_iter().collect::<Vec<_>>(),
+        vec![(0, 1), (1, 1), (2, 1)]
+    );
+    assert_eq!(
+        stats.node_attribute_names.into_iter().collect::<Vec<_>>(),
+        vec![(Identifier::from("kind"), 1), (Identifier::from("name"), 2)]
+    );
+    assert_eq!(
+        stats.edge_attribute_names.into_iter().collect::<Vec<_>>(),
+        vec![(Identifier::from("label"), 1)]
+    );
+    assert!(stats.estimated_bytes > 0);
+}
+
+#[test]
+fn can_validate_graph_against_schema() {
+    let python_source = "pass";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) {
+            node definition
+            attr (definition) kind = "definition", name = "x"
+            node reference
+            attr (reference) kind = "reference", nmae = "x"
+            edge reference -> definition
+            attr (reference -> definition) precedence = 1
+          }
+        "#},
+    )
+    .unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals)
+        .debug_attributes("_location".into(), "_variable".into());
+    let graph = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .unwrap();
+
+    let schema = GraphSchema::new()
+        .kind_attr("kind")
+        .node_kind("definition", AttributeSchema::new().required("name"))
+        .node_kind("reference", AttributeSchema::new().required("name"))
+        .edges(AttributeSchema::new().optional("precedence"))
+        .debug_attributes("_location", "_variable");
+    let errors = graph.validate(&schema).unwrap_err();
+    let reference = graph.iter_nodes().nth(1).unwrap();
+    assert_eq!(
+        errors,
+        vec![
+            SchemaError::MissingAttribute(
+                SchemaElement::Node {
+                    node: reference,
+                    location: Some("reference at line 4 column 8".into()),
+                },
+                Identifier::from("name"),
+            ),
+            SchemaError::UndeclaredAttribute(
+                SchemaElement::Node {
+                    node: reference,
+                    location: Some("reference at line 4 column 8".into()),
+                },
+                Identifier::from("nmae"),
+            ),
+        ]
+    );
+    assert_eq!(
+        errors[1].to_string(),
+        "node 1 (created by reference at line 4 column 8) has undeclared attribute nmae"
+    );
+
+    let schema = GraphSchema::new()
+        .kind_attr("kind")
+        .node_kind("call", AttributeSchema::new());
+    assert!(matches!(
+        graph.validate(&schema).unwrap_err()[0],
+        SchemaError::UndeclaredKind(_, _)
+    ));
+}
+
+#[test]
+fn graphs_serialize_deterministically() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("x = 1", None).unwrap();
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    let module = graph.add_syntax_node(tree.root_node());
+    let statement = graph.add_syntax_node(tree.root_node().child(0).unwrap());
+    let mut attrs = graph.node_mut(node).into_attributes();
+    attrs.add(Identifier::from("zeta"), statement).unwrap();
+    attrs.add(Identifier::from("alpha"), module).unwrap();
+    attrs.add(Identifier::from("mu"), 1).unwrap();
+    let names = attrs
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["zeta", "alpha", "mu"]);
+
+    let mut json = Vec::new();
+    graph.write_json(&mut json, JsonStyle::Compact).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        concat!(
+            r#"{"version":1,"nodes":[{"id":0,"edges":[],"attrs":{"#,
+            r#""zeta":{"type":"syntaxNode","id":1,"kind":"expression_statement","row":0,"column":0},"#,
+            r#""alpha":{"type":"syntaxNode","id":0,"kind":"module","row":0,"column":0},"#,
+            r#""mu":{"type":"int","int":1}"#,
+            r#"}}]}"#,
+        )
+    );
+
+    graph.canonicalize();
+    let mut json = Vec::new();
+    graph.write_json(&mut json, JsonStyle::Compact).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        concat!(
+            r#"{"version":1,"nodes":[{"id":0,"edges":[],"attrs":{"#,
+            r#""alpha":{"type":"syntaxNode","id":0,"kind":"module","row":0,"column":0},"#,
+            r#""mu":{"type":"int","int":1},"#,
+            r#""zeta":{"type":"syntaxNode","id":1,"kind":"expression_statement","row":0,"column":0}"#,
+            r#"}}]}"#,
+        )
+    );
+}
+
+#[test]
+fn can_canonicalize_decoded_graph() {
+    let mut graph = Graph::from_json(
+        r#"{"version":1,"nodes":[
+            {"id":0,"edges":[{"sink":2,"attrs":{}},{"sink":1,"attrs":{}}],"attrs":{}},
+            {"id":1,"edges":[],"attrs":{}},
+            {"id":2,"edges":[],"attrs":{}}
+        ]}"#,
+    )
+    .unwrap();
+    let node0 = graph.iter_nodes().next().unwrap();
+    graph.canonicalize();
+    let sinks = graph
+        .node(node0)
+        .iter_edges()
+        .map(|(sink, _)| sink.index())
+        .collect::<Vec<_>>();
+    assert_eq!(sinks, vec![1, 2]);
+    let node1 = graph.iter_nodes().nth(1).unwrap();
+    assert!(graph.node(node0).get_edge(node1).is_some());
+}
+
+#[test]
+fn can_derive_stable_node_ids() {
+    let build = |extra_nodes: usize| {
+        let mut graph = Graph::new();
+        for _ in 0..extra_nodes {
+            graph.add_graph_node();
+        }
+        let node = graph.add_graph_node();
+        let mut attrs = graph.node_mut(node).into_attributes();
+        attrs.add(Identifier::from("file"), "a.py").unwrap();
+        attrs.add(Identifier::from("name"), "f").unwrap();
+        attrs
+            .add(Identifier::from("line"), extra_nodes as u32)
+            .unwrap();
+        (graph, node)
+    };
+    let identity = [Identifier::from("file"), Identifier::from("name")];
+    let (graph0, node0) = build(0);
+    let (graph1, node1) = build(3);
+    let id = graph0.stable_node_id(node0, &identity).unwrap();
+    assert_eq!(id.len(), 16);
+    assert_eq!(graph1.stable_node_id(node1, &identity), Some(id.clone()));
+    assert_ne!(
+        graph0.stable_node_id(node0, &[Identifier::from("name")]),
+        Some(id.clone())
+    );
+    assert_eq!(
+        graph1.stable_node_id(graph1.iter_nodes().next().unwrap(), &identity),
+        None
+    );
+
+    let (mut graph, node) = build(1);
+    graph
+        .add_stable_node_ids(&identity, Identifier::from("id"))
+        .unwrap();
+    assert_eq!(
+        graph.node(node).attributes().get("id"),
+        Some(&Value::from(id))
+    );
+    assert_eq!(
+        graph
+            .node(graph.iter_nodes().next().unwrap())
+            .attributes()
+            .get("id"),
+        None
+    );
+
+    let copy = graph.add_graph_node();
+    graph
+        .node_mut(copy)
+        .attributes()
+        .add(Identifier::from("file"), "a.py")
+        .unwrap();
+    graph
+        .node_mut(copy)
+        .attributes()
+        .add(Identifier::from("name"), "f")
+        .unwrap();
+    assert!(graph
+        .add_stable_node_ids(&identity, Identifier::from("stable"))
+        .is_err());
+}
+
+#[test]
+fn can_render_graph_as_html() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("name"), "</script><b>")
+        .unwrap();
+    let _ = graph.node_mut(node0).add_edge(node1);
+    let html = graph.to_html(Some("name")).to_string();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(!html.contains("/*GRAPH_DATA*/"));
+    assert!(html.contains(r#""edges":[{"attrs":{},"sink":1,"source":0}]"#));
+    assert!(html.contains(r#""label":"<\/script><b>""#));
+    assert_eq!(html.matches("</script>").count(), 1);
+}
+
+#[test]
+fn can_intern_attribute_values() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let value = graph.intern(Value::from("definition"));
+    graph
+        .node_mut(node0)
+        .attributes()
+        .add(Identifier::from("kind"), value)
+        .unwrap();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(Identifier::from("kind"), "definition")
+        .unwrap();
+    graph
+        .node_mut(node1)
+        .attributes()
+        .add(
+            Identifier::from("names"),
+            Value::List(vec!["definition".into(), "other".into()]),
+        )
+        .unwrap();
+    graph.intern_attributes();
+    assert_eq!(graph.interned_string_count(), 2);
+
+    let string = |node: GraphNodeRef, name: &str| match graph.node(node).attributes().get(name) {
+        Some(Value::String(string)) => string.clone(),
+        value => panic!("expected string, got {:?}", value),
+    };
+    assert!(Arc::ptr_eq(&string(node0, "kind"), &string(node1, "kind")));
+    let names = match graph.node(node1).attributes().get("names") {
+        Some(Value::List(names)) => names.clone(),
+        value => panic!("expected list, got {:?}", value),
+    };
+    match &names[0] {
+        Value::String(name) => assert!(Arc::ptr_eq(name, &string(node0, "kind"))),
+        value => panic!("expected string, got {:?}", value),
+    }
+    assert_eq!(
+        graph.node(node0).attributes().get("kind"),
+        Some(&Value::from("definition"))
+    );
+}
+
+#[test]
+fn can_build_graph_from_rust() {
+    let mut graph = Graph::new();
+    let existing = graph.add_graph_node();
+    let mut builder = GraphBuilder::new(&mut graph);
+    let file = builder
+        .node()
+        .attr("kind", "file")
+        .attr("path", "test.py")
+        .edge_to(existing)
+        .finish();
+    let module = builder
+        .node()
+        .attr("kind", "module")
+        .edge_from(file)
+        .finish();
+    builder.edge(file, module).attr("label", "contains");
+    builder.existing_node(existing).attr("kind", "definition");
+    assert_eq!(builder.graph().node_count(), 3);
+
+    assert_eq!(
+        graph.node(file).attributes().get("path"),
+        Some(&Value::from("test.py"))
+    );
+    assert_eq!(
+        graph.node(existing).attributes().get("kind"),
+        Some(&Value::from("definition"))
+    );
+    let edges = graph
+        .node(file)
+        .iter_edges()
+        .map(|(sink, edge)| (sink, edge.attributes().get("label").cloned()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edges,
+        vec![(existing, None), (module, Some(Value::from("contains")))]
+    );
+}
+
+#[test]
+fn can_remove_nodes_and_edges() {
+    let mut graph = Graph::new();
+    let mut builder = GraphBuilder::new(&mut graph);
+    let node0 = builder.node().attr("name", "node0").finish();
+    let debug = builder.node().attr("debug", true).edge_from(node0).finish();
+    let node2 = builder
+        .node()
+        .attr("name", "node2")
+        .attr("refs", Value::List(vec![node0.into(), debug.into()]))
+        .edge_from(node0)
+        .edge_to(debug)
+        .finish();
+    assert_eq!(graph.incoming_edge_count(debug), 2);
+
+    let mapping = graph.retain_nodes(|_, node| node.attributes().get("debug").is_none());
+    assert_eq!(mapping.len(), 3);
+    assert_eq!(mapping[debug.index()], None);
+    let node0 = mapping[node0.index()].unwrap();
+    let node2 = mapping[node2.index()].unwrap();
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(node2.index(), 1);
+    assert_eq!(
+        graph
+            .node(node0)
+            .iter_edges()
+            .map(|(sink, _)| sink)
+            .collect::<Vec<_>>(),
+        vec![node2]
+    );
+    assert_eq!(graph.node(node2).edge_count(), 0);
+    assert_eq!(
+        graph.node(node2).attributes().get("refs"),
+        Some(&Value::List(vec![node0.into(), Value::Null]))
+    );
+    assert_eq!(graph.incoming_edge_count(node2), 1);
+
+    assert!(graph.remove_edge(node0, node2).is_some());
+    assert!(graph.remove_edge(node0, node2).is_none());
+    assert_eq!(graph.incoming_edge_count(node2), 0);
+
+    let mut attrs = graph.node_mut(node2).into_attributes();
+    *attrs.get_mut("name").unwrap() = Value::from("renamed");
+    assert_eq!(
+        attrs.remove("refs"),
+        Some(Value::List(vec![node0.into(), Value::Null]))
+    );
+    attrs.retain(|name, _| name.as_str() != "missing");
+    assert_eq!(attrs.len(), 1);
+    assert_eq!(attrs.get("name"), Some(&Value::from("renamed")));
+
+    let mapping = graph.remove_nodes(vec![node0]);
+    assert_eq!(mapping, vec![None, Some(node0)]);
+    assert_eq!(graph.node_count(), 1);
+}
+
+#[test]
+fn can_group_nodes() {
+    let mut graph = Graph::new();
+    let mut builder = GraphBuilder::new(&mut graph);
+    let file = builder.node().attr("name", "file").group("a.py").finish();
+    let other = builder.node().attr("name", "other").group("b.py").finish();
+    let def = builder
+        .node()
+        .attr("name", "def")
+        .attr("refs", Value::List(vec![file.into(), other.into()]))
+        .group("a.py")
+        .edge_from(file)
+        .edge_to(other)
+        .finish();
+    let ungrouped = builder.node().finish();
+
+    assert_eq!(graph.groups().collect::<Vec<_>>(), vec!["a.py", "b.py"]);
+    assert_eq!(graph.nodes_in_group("a.py"), vec![file, def]);
+    assert_eq!(graph.node_group(other), Some("b.py"));
+    assert_eq!(graph.node_group(ungrouped), None);
+
+    let subgraph = graph.subgraph("a.py");
+    assert_eq!(subgraph.node_count(), 2);
+    let nodes = subgraph.iter_nodes().collect::<Vec<_>>();
+    assert_eq!(
+        subgraph
+            .node(nodes[0])
+            .iter_edges()
+            .map(|(sink, _)| sink)
+            .collect::<Vec<_>>(),
+        vec![nodes[1]]
+    );
+    assert_eq!(subgraph.node(nodes[1]).edge_count(), 0);
+    assert_eq!(
+        subgraph.node(nodes[1]).attributes().get("refs"),
+        Some(&Value::List(vec![nodes[0].into(), Value::Null]))
+    );
+    assert_eq!(subgraph.nodes_in_group("a.py"), nodes);
+
+    let dot = graph.to_dot(&DotOptions::new()).to_string();
+    assert_eq!(
+        dot,
+        indoc! {r#"
+            digraph {
+              n3 [label="3"];
+              subgraph cluster_0 {
+                label="a.py";
+                n0 [label="0"];
+                n2 [label="2"];
+              }
+              subgraph cluster_1 {
+                label="b.py";
+                n1 [label="1"];
+              }
+              n0 -> n2;
+              n2 -> n1;
+            }
+        "#}
+    );
+
+    graph.clear_node_group(other);
+    graph.remove_nodes(vec![file]);
+    assert_eq!(graph.groups().collect::<Vec<_>>(), vec!["a.py"]);
+}
+
+#[test]
+fn can_snapshot_graph() {
+    let mut graph = Graph::new();
+    for index in 0..2000 {
+        let node = graph.add_graph_node();
+        graph
+            .node_mut(node)
+            .attributes()
+            .add(Identifier::from("index"), index)
+            .unwrap();
+    }
+    let nodes = graph.iter_nodes().collect::<Vec<_>>();
+    let _ = graph.node_mut(nodes[0]).add_edge(nodes[1]);
+    let before = graph.snapshot();
+
+    graph
+        .node_mut(nodes[1500])
+        .attributes()
+        .add(Identifier::from("name"), "changed")
+        .unwrap();
+    // Setting an attribute to the value it already has does not count as a change.
+    let _ = graph
+        .node_mut(nodes[3])
+        .attributes()
+        .add(Identifier::from("index"), 3);
+    let added = graph.add_graph_node();
+    let _ = graph.node_mut(nodes[0]).add_edge(added);
+    let after = graph.snapshot();
+
+    assert_eq!(before.node_count(), 2000);
+    assert_eq!(before.node(nodes[1500]).attributes().get("name"), None);
+    assert_eq!(before.node(nodes[0]).iter_edges().count(), 1);
+    assert_eq!(
+        after.node(nodes[1500]).attributes().get("name"),
+        Some(&Value::from("changed"))
+    );
+    assert_eq!(
+        before.changed_nodes(&after),
+        vec![nodes[0], nodes[1500], added]
+    );
+    assert_eq!(
+        after.pretty_print().to_string(),
+        graph.pretty_print().to_string()
+    );
+}
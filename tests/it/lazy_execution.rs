@@ -9,9 +9,12 @@ use indoc::indoc;
 use tree_sitter::Parser;
 use tree_sitter_graph::ast::File;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::graph::Graph;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
+use tree_sitter_graph::LazyMemoryPolicy;
 use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::ResourceLimits;
 use tree_sitter_graph::Variables;
 
 fn init_log() {
@@ -24,6 +27,14 @@ fn init_log() {
 }
 
 fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionError> {
+    execute_with_parallel(python_source, dsl_source, false)
+}
+
+fn execute_with_parallel(
+    python_source: &str,
+    dsl_source: &str,
+    parallel: bool,
+) -> Result<String, ExecutionError> {
     init_log();
     let mut parser = Parser::new();
     parser.set_language(tree_sitter_python::language()).unwrap();
@@ -35,7 +46,9 @@ fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionErr
     globals
         .add("filename".into(), "test.py".into())
         .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
-    let mut config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let mut config = ExecutionConfig::new(&functions, &globals)
+        .lazy(true)
+        .parallel(parallel);
     let graph = file.execute(&tree, python_source, &mut config, &NoCancellation)?;
     let result = graph.pretty_print().to_string();
     Ok(result)
@@ -1455,3 +1468,425 @@ fn can_execute_shorthand() {
         "#},
     );
 }
+
+#[test]
+fn can_map_function_over_list() {
+    check_execution(
+        "c = 1\nb = 2\na = 3",
+        indoc! {r#"
+          (module (expression_statement (assignment left:(_)@names))* )
+          {
+            node n
+            attr (n) names = (map @names &source-text)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            names: ["c", "b", "a"]
+        "#},
+    );
+}
+
+#[test]
+fn can_coalesce_without_forcing_later_arguments() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) first = (coalesce #null 1 2)
+            attr (n) short_circuit = (coalesce "a" @root.undefined (node))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            first: 1
+            short_circuit: "a"
+        "#},
+    );
+}
+
+#[test]
+fn can_inspect_edges_created_later() {
+    check_execution(
+        indoc! { r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            node @m.a
+            node @m.b
+            attr (@m.a) out = (out-edges @m.a)
+            attr (@m.b) in = (in-edges @m.b)
+            edge @m.a -> @m.b
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            out: [[graph node 1]]
+          edge 0 -> 1
+          node 1
+            in: [[graph node 0]]
+        "#},
+    );
+}
+
+#[test]
+fn can_get_attributes_set_later() {
+    check_execution(
+        indoc! { r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            attr (n) name = (get-attr @m.a "name"), missing = (get-attr @m.a "missing")
+          }
+          (module) @m
+          {
+            node @m.a
+            attr (@m.a) name = "a"
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            missing: #null
+            name: "a"
+          node 1
+            name: "a"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_attributes_set_later_through_namespaces() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            attr (n) name = (std::get-attr @m.a "name")
+          }
+          (module) @m
+          {
+            node @m.a
+            attr (@m.a) name = "a"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut functions = Functions::new();
+    functions.add_namespace("std", Functions::stdlib());
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let graph = file
+        .execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "a"
+          node 1
+            name: "a"
+        "#}
+    );
+}
+
+#[test]
+fn can_set_map_attributes() {
+    check_execution(
+        indoc! { r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) meta = (map-of "kind" "module" "line" (plus 1 1))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            meta: {kind: "module", line: 2}
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_matches_in_parallel() {
+    let python_source = (0..500)
+        .map(|i| format!("x{} = {}\n", i, i))
+        .collect::<String>();
+    let dsl_source = indoc! {r#"
+      (module) @module
+      {
+        node @module.def
+      }
+      (module (expression_statement) @stmt) @module
+      {
+        node @stmt.def
+        edge @module.def -> @stmt.def
+        attr (@module.def -> @stmt.def) text = (source-text @stmt)
+      }
+      (expression_statement (assignment left: (identifier) @id right: (_) @value)) @stmt
+      {
+        node n
+        attr (n) name = (source-text @id), value = @value
+        edge @stmt.def -> n
+        let @id.node = n
+      }
+      (identifier) @id
+      {
+        attr (@id.node) ref = @id
+      }
+    "#};
+    let sequential =
+        execute_with_parallel(&python_source, dsl_source, false).expect("Could not execute file");
+    let parallel = execute_with_parallel(&python_source, dsl_source, true)
+        .expect("Could not execute file in parallel");
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn parallel_execution_reports_the_same_errors() {
+    let python_source = (0..500)
+        .map(|i| format!("x{} = {}\n", i, i))
+        .collect::<String>();
+    let dsl_source = indoc! {r#"
+      (expression_statement) @stmt
+      {
+        node n
+        if (eq (source-text @stmt) "x400 = 400") {
+          if (plus (source-text @stmt) 1) {
+            attr (n) unreachable = #true
+          }
+        }
+      }
+    "#};
+    let sequential = execute_with_parallel(&python_source, dsl_source, false)
+        .expect_err("Execution succeeded unexpectedly");
+    let parallel = execute_with_parallel(&python_source, dsl_source, true)
+        .expect_err("Execution in parallel succeeded unexpectedly");
+    assert_eq!(parallel.to_string(), sequential.to_string());
+}
+
+#[test]
+fn can_force_parts_of_the_graph_on_demand() {
+    init_log();
+    let python_source = indoc! {r#"
+      def a(): pass
+      def b(): pass
+      class C: pass
+    "#};
+    let dsl_source = indoc! {r#"
+      (module) @m
+      {
+        node @m.root
+        attr (@m.root) name = "root"
+      }
+      (module (function_definition) @f) @m
+      {
+        edge @m.root -> @f.node
+        attr (@m.root -> @f.node) kind = "defines"
+      }
+      (function_definition name: (identifier) @name) @f
+      {
+        node @f.node
+        attr (@f.node) name = (source-text @name)
+      }
+      (class_definition name: (identifier) @name) @c
+      {
+        node @c.node
+        attr (@c.node) name = (source-text @name)
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let expected = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file")
+        .pretty_print()
+        .to_string();
+
+    let attribute_counts = |graph: &Graph| {
+        graph
+            .iter_nodes()
+            .map(|node| {
+                (
+                    graph.node(node).attributes().iter().count(),
+                    graph.node(node).edge_count(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut graph = Graph::new();
+    let mut on_demand = file
+        .execute_on_demand_into(&mut graph, &tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        attribute_counts(on_demand.graph()),
+        vec![(0, 0), (0, 0), (0, 0), (0, 0)]
+    );
+    let a = on_demand.graph().iter_nodes().nth(1).unwrap();
+    on_demand
+        .force_node(a, &NoCancellation)
+        .expect("Could not force node");
+    assert_eq!(
+        attribute_counts(on_demand.graph()),
+        vec![(0, 0), (1, 0), (0, 0), (0, 0)]
+    );
+    let root = on_demand.graph().iter_nodes().next().unwrap();
+    on_demand
+        .force_reachable(root, &NoCancellation)
+        .expect("Could not force reachable nodes");
+    assert_eq!(
+        attribute_counts(on_demand.graph()),
+        vec![(1, 2), (1, 0), (1, 0), (0, 0)]
+    );
+    on_demand
+        .force_all(&NoCancellation)
+        .expect("Could not force graph");
+    drop(on_demand);
+    assert_eq!(graph.pretty_print().to_string(), expected);
+}
+
+#[test]
+fn lazy_execution_keeps_to_memory_budget() {
+    init_log();
+    let python_source = "x = 1\n".repeat(10);
+    // each variable is defined by a large expression, whose value is small
+    let long = "a".repeat(1000);
+    let dsl_source = format!(
+        indoc! {r#"
+          (module (_) @stmt)
+          {{
+            node @stmt.node
+            let same = (eq "{0}" "{0}")
+            attr (@stmt.node) same = same
+          }}
+        "#},
+        long
+    );
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(&python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), &dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let execute = |lazy_memory_policy| {
+        let limits = ResourceLimits {
+            max_lazy_bytes: Some(10000),
+            lazy_memory_policy,
+            ..ResourceLimits::default()
+        };
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(true)
+            .resource_limits(limits);
+        file.execute(&tree, &python_source, &config, &NoCancellation)
+    };
+    match execute(LazyMemoryPolicy::Fail) {
+        Err(ExecutionError::MemoryBudgetExceeded { used, budget }) => {
+            assert_eq!(budget, 10000);
+            assert!(used > budget);
+        }
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+    let graph = execute(LazyMemoryPolicy::ForceEagerly).expect("Could not execute file");
+    let config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let expected = file
+        .execute(&tree, &python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        expected.pretty_print().to_string()
+    );
+}
+
+#[test]
+fn recursive_definitions_report_the_whole_cycle() {
+    let python_source = "pass";
+    let dsl_source = indoc! {r#"
+      (module) @m
+      {
+        let @m.a = @m.b
+        let @m.b = @m.c
+        let @m.c = @m.a
+      }
+    "#};
+    match execute(python_source, dsl_source) {
+        Err(e) => {
+            let error = e.to_string();
+            assert!(
+                error.contains(
+                    "Recursively defined variable let @m.a = @m.b at (3, 3) -> \
+                     let @m.b = @m.c at (4, 3) -> \
+                     let @m.c = @m.a at (5, 3) -> \
+                     let @m.a = @m.b at (3, 3)"
+                ),
+                "Unexpected error: {}",
+                error
+            );
+        }
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn lazy_execution_limits_forcing_depth() {
+    init_log();
+    let python_source = "pass";
+    let dsl_source = indoc! {r#"
+      (module) @m
+      {
+        node n
+        attr (n) value = @m.a
+        let @m.a = @m.b
+        let @m.b = @m.c
+        let @m.c = @m.d
+        let @m.d = 1
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let execute = |max_forcing_depth| {
+        let limits = ResourceLimits {
+            max_forcing_depth: Some(max_forcing_depth),
+            ..ResourceLimits::default()
+        };
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(true)
+            .resource_limits(limits);
+        file.execute(&tree, python_source, &config, &NoCancellation)
+    };
+    assert!(execute(4).is_ok());
+    match execute(3) {
+        Err(e) => assert!(
+            e.to_string()
+                .contains("Resource limit exceeded: more than 3 nested variables"),
+            "Unexpected error: {}",
+            e
+        ),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
@@ -11,6 +11,7 @@ use tree_sitter_graph::ast::*;
 use tree_sitter_graph::Identifier;
 use tree_sitter_graph::Location;
 use tree_sitter_graph::ParseError;
+use tree_sitter_graph::ReloadStats;
 
 #[test]
 fn can_parse_blocks() {
@@ -1581,21 +1582,484 @@ fn multiline_query_parse_errors_have_file_location() {
 }
 
 #[test]
-fn cannot_parse_unused_capture() {
+fn can_warn_about_unused_captures() {
     let source = r#"
-        (function_definition name: (identifier) @name) {
+        (function_definition
+          name: (identifier) @name
+          parameters: (_) @_parameters
+          body: (_) @body (#not-eq? @body "@other")) @other {
+          node @body.node
         }
     "#;
-    if let Ok(_) = File::from_str(tree_sitter_python::language(), source) {
-        panic!("Parse succeeded unexpectedly");
+    let file = File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
+    let warnings = file
+        .warnings
+        .iter()
+        .map(|warning| warning.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        vec![
+            "Unused capture @name at (3, 30). Remove or prefix with _.",
+            "Unused capture @other at (5, 54). Remove or prefix with _.",
+        ]
+    );
+}
+
+#[test]
+fn cannot_parse_type_mismatches() {
+    let source = r#"
+        (module) @root
+        {
+          let name = "root"
+          node n
+          edge name -> n
+        }
+    "#;
+    let err = File::from_str(tree_sitter_python::language(), source)
+        .expect_err("Parse succeeded unexpectedly");
+    assert_eq!(
+        err.to_string(),
+        "Expected a graph node at (6, 11), got a string from (4, 11)"
+    );
+
+    for statement in [
+        "edge n -> @_root",
+        "attr (@_root) kind = \"module\"",
+        "let x = n\n let y = x\n attr (y.prop) a = 1",
+        "scan n { \"x\" { } }",
+        "if (eq 1 1) { }\n if n { }",
+        "for x in {1, 2} { }",
+    ] {
+        let source = format!(
+            r#"
+            (module) @_root
+            {{
+              node n
+              {}
+            }}
+            "#,
+            statement
+        );
+        assert!(
+            File::from_str(tree_sitter_python::language(), &source).is_err(),
+            "Parse succeeded unexpectedly for {}",
+            statement
+        );
     }
 }
 
+#[test]
+fn can_check_patterns_against_node_types() {
+    let check = |node_types: &NodeTypes, source: &str| {
+        File::from_str(tree_sitter_python::language(), source)
+            .expect("Cannot parse file")
+            .check_node_types(node_types)
+            .map_err(|e| e.to_string())
+    };
+    let source = r#"
+        ; the function of a call is a primary expression
+        (call function: (identifier) @_f arguments: (argument_list . (_)* @_args)) { }
+        (binary_operator left: (_) operator: ["+" "-"] right: [(integer) (float)]) { }
+        (function_definition !return_type name: (identifier) @_name (#eq? @_name "f")) { }
+        ((comment) (expression_statement (string))+) @_docs { }
+    "#;
+    let python =
+        NodeTypes::from_json(tree_sitter_python::NODE_TYPES).expect("Cannot read node types");
+    assert!(check(&python, source).is_ok());
+
+    // a newer version of the grammar, which renamed some node kinds and fields
+    let upgraded = NodeTypes::from_json(
+        r#"[
+          {"type": "module", "named": true},
+          {"type": "function_definition", "named": true, "fields": {
+            "name": {"multiple": false, "required": true, "types": [{"type": "name", "named": true}]},
+            "body": {"multiple": false, "required": true, "types": [{"type": "block", "named": true}]}
+          }},
+          {"type": "call", "named": true, "fields": {
+            "function": {"multiple": false, "required": true, "types": [{"type": "primary_expression", "named": true}]}
+          }},
+          {"type": "primary_expression", "named": true, "subtypes": [{"type": "identifier", "named": true}]},
+          {"type": "identifier", "named": true},
+          {"type": "name", "named": true},
+          {"type": "block", "named": true},
+          {"type": "def", "named": false}
+        ]"#,
+    )
+    .expect("Cannot read node types");
+    assert!(check(&upgraded, "(call function: (identifier) @_f) { }").is_ok());
+    assert!(check(
+        &upgraded,
+        "(function_definition \"def\" body: (block) @_b) { }"
+    )
+    .is_ok());
+    assert_eq!(
+        check(
+            &upgraded,
+            r#"
+            (function_definition
+              name: (identifier) @_name) { }
+            "#
+        ),
+        Err("Field name of (function_definition) cannot contain (identifier) at (3, 21)".into())
+    );
+    assert_eq!(
+        check(&upgraded, "(function_definition parameters: (_)) { }"),
+        Err("Unknown field parameters of (function_definition) at (1, 22)".into())
+    );
+    assert_eq!(
+        check(&upgraded, "(module (pass_statement)) { }"),
+        Err("Unknown node kind (pass_statement) at (1, 9)".into())
+    );
+    assert_eq!(
+        check(&upgraded, "(function_definition \"def\" \":\") { }"),
+        Err("Unknown node kind \":\" at (1, 28)".into())
+    );
+    assert!(NodeTypes::from_json(r#"{"type": "module"}"#).is_err());
+}
+
 #[test]
 fn can_parse_explicitly_unused_capture() {
     let source = r#"
         (function_definition name: (identifier) @_name) {
         }
     "#;
-    File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
+    let file = File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
+    assert!(file.warnings.is_empty());
+}
+
+#[test]
+fn can_warn_about_unused_variables() {
+    let source = r#"
+        (function_definition name: (identifier) @name) {
+          let used = (source-text @name)
+          let unused = 1
+          let _ignored = 2
+          var assigned = 3
+          set assigned = 4
+          node n
+          attr (n) name = used
+          if #true {
+            let inner = 5
+          }
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
+    let warnings = file
+        .warnings
+        .iter()
+        .map(|warning| warning.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        vec![
+            "Unused variable unused at (4, 15). Remove or prefix with _.",
+            "Unused variable assigned at (6, 15). Remove or prefix with _.",
+            "Unused variable inner at (11, 17). Remove or prefix with _.",
+        ]
+    );
+}
+
+#[test]
+fn can_warn_about_duplicate_stanzas_and_unreachable_statements() {
+    let source = r#"
+        (identifier) @id {
+          node @id.node
+        }
+
+        (identifier) @id {
+            node @id.node
+        }
+
+        (identifier) @id {
+          node @id.other
+        }
+
+        (module) @_root {
+          if #false {
+            node a
+          } elif #true {
+            if #true, #false {
+              node b
+            }
+          } else {
+            node c
+          }
+          let debug = #false
+          let verbose = debug
+          if verbose {
+            node d
+          }
+        }
+
+        (identifier) @id { node @id.node }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
+    let warnings = file
+        .warnings
+        .iter()
+        .map(|warning| warning.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        vec![
+            "Unreachable statements at (15, 11)",
+            "Unreachable statements at (18, 13)",
+            "Unreachable statements at (21, 13)",
+            "Unreachable statements at (26, 11)",
+            "Stanza at (6, 9) duplicates the stanza at (2, 9), which has the same pattern and statements",
+            "Stanza at (31, 9) duplicates the stanza at (2, 9), which has the same pattern and statements",
+        ]
+    );
+}
+
+#[test]
+fn can_parse_namespaced_functions() {
+    let source = r#"
+        (module)
+        {
+          print (ns::f ns::inner::g)
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+
+    let statements = file
+        .stanzas
+        .into_iter()
+        .map(|s| s.statements)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statements,
+        vec![vec![Print {
+            values: vec![Call {
+                function: "ns::f".into(),
+                parameters: vec![FunctionRef {
+                    name: "ns::inner::g".into(),
+                    location: Location { row: 3, column: 23 }
+                }
+                .into()]
+            }
+            .into()],
+            location: Location { row: 3, column: 10 }
+        }
+        .into()]]
+    );
+}
+
+#[test]
+fn can_parse_function_references() {
+    let source = r#"
+        (module)
+        {
+          print (map [] &source-text)
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+
+    let statements = file
+        .stanzas
+        .into_iter()
+        .map(|s| s.statements)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statements,
+        vec![vec![Print {
+            values: vec![Call {
+                function: "map".into(),
+                parameters: vec![
+                    ListLiteral { elements: vec![] }.into(),
+                    FunctionRef {
+                        name: "source-text".into(),
+                        location: Location { row: 3, column: 24 }
+                    }
+                    .into()
+                ]
+            }
+            .into()],
+            location: Location { row: 3, column: 10 }
+        }
+        .into()]]
+    );
+}
+
+#[test]
+fn cannot_parse_undefined_variable_as_parameter() {
+    let source = r#"
+        (module)
+        {
+          print (plus undefined-var 1)
+        }
+    "#;
+    let err = File::from_str(tree_sitter_python::language(), source)
+        .expect_err("Parse succeeded unexpectedly");
+    assert_eq!(
+        err.to_string(),
+        "Undefined variable undefined-var at (4, 23)"
+    );
+}
+
+#[test]
+fn cannot_parse_incomplete_namespaced_function() {
+    let source = r#"
+        (module)
+        {
+          print (ns:: "a")
+        }
+    "#;
+    if File::from_str(tree_sitter_python::language(), source).is_ok() {
+        panic!("Parse succeeded unexpectedly");
+    }
+}
+
+#[test]
+fn can_parse_match_limit_annotations() {
+    let source = r#"
+        @match-limit(100)
+        (module) @_m
+        {
+        }
+
+        @match-limit( 65536 ) (identifier) @_id {}
+
+        (function_definition) @_f {}
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let match_limits = file
+        .stanzas
+        .iter()
+        .map(|s| (s.match_limit, s.range.start))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        match_limits,
+        vec![
+            (Some(100), Location { row: 2, column: 8 }),
+            (Some(65536), Location { row: 6, column: 30 }),
+            (None, Location { row: 8, column: 8 }),
+        ]
+    );
+}
+
+#[test]
+fn cannot_parse_invalid_annotations() {
+    let source = r#"
+        @match-limit(0)
+        (module) @_m {}
+    "#;
+    match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(ParseError::InvalidMatchLimit(limit, _)) => assert_eq!(limit, "0"),
+        Err(e) => panic!("Unexpected error: {}", e),
+    }
+
+    let source = r#"
+        @matches(10)
+        (module) @_m {}
+    "#;
+    match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(ParseError::UnknownAnnotation(name, _)) => assert_eq!(name, "matches"),
+        Err(e) => panic!("Unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn can_parse_tag_annotations() {
+    let source = r#"
+        @tag(outline, deep)
+        (module) @_m
+        {
+        }
+
+        @tag( deep ) @match-limit(10) @tag(slow,deep) (identifier) @_id {}
+
+        (function_definition) @_f {}
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let tags = file
+        .stanzas
+        .iter()
+        .map(|s| s.tags.iter().map(String::as_str).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tags,
+        vec![vec!["outline", "deep"], vec!["deep", "slow"], vec![]]
+    );
+    assert_eq!(file.stanzas[1].match_limit, Some(10));
+
+    let source = r#"
+        @tag()
+        (module) @_m {}
+    "#;
+    if File::from_str(tree_sitter_python::language(), source).is_ok() {
+        panic!("Parse succeeded unexpectedly");
+    }
+}
+
+#[test]
+fn can_reload_files() {
+    let source = r#"
+        (module) @m
+        {
+          node @m.node
+        }
+        (function_definition name: (identifier) @name) @f
+        {
+          scan (source-text @name) {
+            "^_" { attr (@f.node) private = #true }
+          }
+        }
+        (class_definition) @c
+        {
+          node @c.node
+        }
+    "#;
+    let mut file =
+        File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+
+    // the same file reuses all of its queries
+    let stats = file.reload(source).expect("Cannot reload file");
+    assert_eq!(
+        stats,
+        ReloadStats {
+            reused_queries: 3,
+            compiled_queries: 0,
+            reused_file_query: true,
+        }
+    );
+
+    // only the changed stanza is compiled again
+    let changed = source.replace("(class_definition) @c", "(class_definition name: (_)) @c");
+    let stats = file.reload(&changed).expect("Cannot reload file");
+    assert_eq!(
+        stats,
+        ReloadStats {
+            reused_queries: 2,
+            compiled_queries: 1,
+            reused_file_query: false,
+        }
+    );
+    let expected =
+        File::from_str(tree_sitter_python::language(), &changed).expect("Cannot parse file");
+    assert_eq!(file.stanzas.len(), expected.stanzas.len());
+    for (stanza, expected) in file.stanzas.iter().zip(&expected.stanzas) {
+        assert_eq!(stanza.query_source, expected.query_source);
+        assert_eq!(stanza.query.pattern_count(), 1);
+        assert_eq!(stanza.statements, expected.statements);
+        assert_eq!(
+            stanza.full_match_file_capture_index,
+            expected.full_match_file_capture_index
+        );
+    }
+    assert_eq!(file.query.as_ref().unwrap().pattern_count(), 3);
+
+    // a version that cannot be parsed leaves the file unchanged
+    let invalid = changed.replace("node @c.node", "node @c.node @c");
+    assert!(file.reload(&invalid).is_err());
+    for stanza in &file.stanzas {
+        assert_eq!(stanza.query.pattern_count(), 1);
+    }
+    assert_eq!(file.query.as_ref().unwrap().pattern_count(), 3);
+    let stats = file.reload(&changed).expect("Cannot reload file");
+    assert_eq!(stats.reused_queries, 3);
+    assert!(stats.reused_file_query);
 }
@@ -19,3 +19,28 @@ fn can_create_nested_variables() {
     let w = f(&v);
     w.get(&"foo".into()).expect("Failed to get foo");
 }
+
+#[test]
+fn can_restore_snapshots_of_variables() {
+    let mut v = Variables::new();
+    v.add("foo".into(), 1.into()).expect("Failed to set foo");
+    v.add_lazy("lazy".into(), || 2.into())
+        .expect("Failed to set lazy");
+    let snapshot = v.snapshot();
+
+    v.remove(&"foo".into());
+    v.add("foo".into(), 3.into()).expect("Failed to set foo");
+    v.add("bar".into(), 4.into()).expect("Failed to set bar");
+    v.add_lazy("baz".into(), || 5.into())
+        .expect("Failed to set baz");
+    v.restore(&snapshot);
+    assert_eq!(v.get(&"foo".into()), Some(&1.into()));
+    assert_eq!(v.get(&"lazy".into()), Some(&2.into()));
+    assert!(!v.contains(&"bar".into()));
+    assert!(!v.contains(&"baz".into()));
+
+    // a snapshot can be restored more than once
+    v.add("bar".into(), 6.into()).expect("Failed to set bar");
+    v.restore(&snapshot);
+    assert!(!v.contains(&"bar".into()));
+}